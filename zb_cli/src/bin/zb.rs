@@ -1,28 +1,52 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use console::style;
+use std::time::Duration;
 use zb_cli::{
     cli::{Cli, Commands},
     commands,
+    config::Config,
     init::ensure_init,
-    logging,
+    logging, progress,
     ui::Ui,
-    utils::{get_prefix_path, get_root_path},
+    utils::{get_prefix_path, get_root_path, is_formula_url},
 };
-use zb_io::create_installer;
+use zb_core::{LoggerHandle, Paths};
+use zb_io::{Installer, LinkConflictPolicy, RetryPolicy, configure_tls, set_proxy};
 
 #[tokio::main]
 async fn main() {
-    let cli = Cli::parse();
-    logging::init(cli.verbose, cli.quiet);
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
-    if let Err(e) = run(cli).await {
+    let config = load_config();
+    if let Ok(config) = &config {
+        config.apply(&mut cli, &matches);
+        config.apply_color();
+    }
+    let log_file = Paths::from_root(get_root_path(cli.root.clone())).log_file();
+    logging::init(&LoggerHandle::from_flags(cli.verbose, cli.quiet, cli.debug).with_file(log_file));
+
+    let result = match config {
+        Ok(_) => run(cli).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        tracing::debug!(error = %e, "zb failed");
         eprintln!("{} {}", style("error:").red().bold(), e);
         std::process::exit(1);
     }
 }
 
+fn load_config() -> Result<Config, zb_core::Error> {
+    match Config::default_path() {
+        Some(path) => Config::load(&path),
+        None => Ok(Config::default()),
+    }
+}
+
 async fn run(cli: Cli) -> Result<(), zb_core::Error> {
     let mut ui = Ui::new();
+    ui.set_json(cli.json);
 
     if let Commands::Completion { shell } = cli.command {
         return commands::completion::execute(shell);
@@ -31,6 +55,10 @@ async fn run(cli: Cli) -> Result<(), zb_core::Error> {
     let root = get_root_path(cli.root);
     let prefix = get_prefix_path(cli.prefix, &root);
 
+    if let Commands::Shellenv { shell } = cli.command {
+        return commands::shellenv::execute(&root, &prefix, shell);
+    }
+
     if let Commands::Init { no_modify_path } = cli.command {
         return commands::init::execute(&root, &prefix, no_modify_path, &mut ui);
     }
@@ -39,59 +67,329 @@ async fn run(cli: Cli) -> Result<(), zb_core::Error> {
         ensure_init(&root, &prefix, cli.auto_init, &mut ui)?;
     }
 
-    let mut installer = create_installer(&root, &prefix, cli.concurrency)?;
+    if let Some(proxy) = &cli.proxy {
+        set_proxy(proxy)?;
+    }
+    if cli.insecure {
+        ui.warn(
+            "TLS certificate verification is disabled: metadata and downloads can be \
+             intercepted (bottle checksums are still verified)",
+        )
+        .map_err(|e| zb_core::Error::FileError {
+            message: format!("failed to write CLI output: {e}"),
+        })?;
+    }
+    configure_tls(cli.ca_bundle.as_deref(), cli.insecure)?;
+
+    let retry = RetryPolicy {
+        max_retries: cli.retries,
+        base_delay: Duration::from_millis(cli.retry_backoff_ms),
+        jitter: !cli.no_retry_jitter,
+        ..RetryPolicy::default()
+    };
+    let mut builder = Installer::builder(&root, &prefix)
+        .concurrency(cli.concurrency)
+        .max_download_rate(cli.max_download_rate)
+        .retry_policy(retry)
+        .mirrors(cli.mirrors)
+        .keep_build_dir_on_failure(cli.keep_tmp_on_failure)
+        .verify_after_install(cli.verify_after_install)
+        .require_relocation(cli.require_relocation)
+        .verify_signatures(cli.verify_signatures)
+        .wait_for_lock(!cli.no_wait)
+        .appdir(cli.appdir);
+    if let Commands::Install {
+        print_env,
+        force_bottle,
+        bottle_arch,
+        offline,
+        ignore_conflicts_with,
+        ignore_pins,
+        with,
+        without,
+        overwrite,
+        skip_conflicts,
+        ..
+    } = &cli.command
+    {
+        builder = builder
+            .print_build_env(*print_env)
+            .bottle_policy(zb_core::BottlePolicy {
+                force: *force_bottle,
+                arch: bottle_arch.clone(),
+            })
+            .offline(*offline)
+            .ignored_conflicts(ignore_conflicts_with.clone())
+            .ignore_pins(*ignore_pins)
+            .dependency_options(zb_core::DependencyOptions {
+                with: with.clone(),
+                without: without.clone(),
+            });
+        if *overwrite {
+            builder = builder.link_conflict_policy(LinkConflictPolicy::Overwrite);
+        } else if *skip_conflicts {
+            builder = builder.link_conflict_policy(LinkConflictPolicy::Skip);
+        }
+    }
+    let mut installer = builder.build()?;
+    if cli.command.modifies_installs() {
+        for name in installer.recover_interrupted_installs()? {
+            ui.warn(format!(
+                "rolled back an interrupted install of {name}; run the install again"
+            ))
+            .map_err(|e| zb_core::Error::FileError {
+                message: format!("failed to write CLI output: {e}"),
+            })?;
+        }
+    }
+    installer.set_progress(Some(progress::lock_notice()));
 
     match cli.command {
         Commands::Init { .. } => unreachable!(),
         Commands::Completion { .. } => unreachable!(),
+        Commands::Shellenv { .. } => unreachable!(),
         Commands::Install {
             formulas,
             no_link,
             build_from_source,
+            head,
+            from_lockfile,
+            frozen,
+            formula_url,
+            formula_sha256,
+            formula_file,
+            build_deps_only,
+            only_if_newer,
+            dependencies_from_source,
+            ..
+        } => match from_lockfile {
+            Some(lockfile) => {
+                commands::install::execute_lockfile(
+                    &mut installer,
+                    &lockfile,
+                    frozen,
+                    no_link,
+                    build_from_source,
+                    &mut ui,
+                )
+                .await
+            }
+            None => {
+                let mut names = Vec::with_capacity(formulas.len());
+                for formula in formulas {
+                    if is_formula_url(&formula) {
+                        names.push(installer.add_formula_from_url(&formula, None).await?);
+                    } else {
+                        names.push(formula);
+                    }
+                }
+                let mut formulas = names;
+                if let Some(path) = formula_file {
+                    formulas.push(installer.add_formula_from_file(&path)?);
+                }
+                if let Some(url) = formula_url {
+                    formulas.push(
+                        installer
+                            .add_formula_from_url(&url, formula_sha256.as_deref())
+                            .await?,
+                    );
+                }
+                if let Some(floor) = only_if_newer {
+                    commands::install::execute_only_if_newer(
+                        &mut installer,
+                        formulas,
+                        &floor,
+                        no_link,
+                        build_from_source,
+                        &mut ui,
+                    )
+                    .await
+                } else if build_deps_only {
+                    commands::install::execute_build_deps_only(
+                        &mut installer,
+                        formulas,
+                        no_link,
+                        build_from_source,
+                        &mut ui,
+                    )
+                    .await
+                } else {
+                    commands::install::execute(
+                        &mut installer,
+                        formulas,
+                        no_link,
+                        build_from_source,
+                        dependencies_from_source,
+                        head,
+                        &mut ui,
+                    )
+                    .await
+                }
+            }
+        },
+        Commands::Fetch {
+            formulas,
+            download_only,
+        } => commands::fetch::execute(&mut installer, formulas, download_only, &mut ui).await,
+        Commands::Bundle { command } => {
+            commands::bundle::execute(&mut installer, command, &mut ui).await
+        }
+        Commands::Services { command } => {
+            commands::services::execute(&mut installer, command, &mut ui).await
+        }
+        Commands::Uninstall { formulas, all } => {
+            commands::uninstall::execute(&mut installer, formulas, all, &mut ui)
+        }
+        Commands::Migrate {
+            yes,
+            force,
+            only,
+            dry_run,
+        } => commands::migrate::execute(&mut installer, yes, force, only, dry_run, &mut ui).await,
+        Commands::Doctor { repair } => commands::doctor::execute(&mut installer, repair, &mut ui),
+        Commands::Du { formulas } => commands::du::execute(&mut installer, formulas),
+        Commands::Use { tool, version } => {
+            commands::use_version::execute(&mut installer, tool, version, &mut ui)
+        }
+        Commands::Link {
+            formulas,
+            force,
+            overwrite,
+            dry_run,
         } => {
-            commands::install::execute(
+            commands::link::execute(&mut installer, formulas, force, overwrite, dry_run, &mut ui)
+                .await
+        }
+        Commands::Unlink { formulas } => {
+            commands::unlink::execute(&mut installer, formulas, &mut ui)
+        }
+        Commands::Rollback {
+            formula: Some(formula),
+            ..
+        } => commands::rollback::execute(&mut installer, formula, &mut ui),
+        Commands::Rollback { formula: None, to } => {
+            commands::rollback::execute_generation(&mut installer, to, &mut ui)
+        }
+        Commands::Generations => commands::generations::execute(&mut installer, &mut ui),
+        Commands::Pin { formulas } => commands::pin::execute(&mut installer, formulas, &mut ui),
+        Commands::Unpin { formulas } => {
+            commands::pin::execute_unpin(&mut installer, formulas, &mut ui)
+        }
+        Commands::Verify {
+            store: true,
+            repair,
+            ..
+        } => commands::verify::execute_store(&mut installer, repair, &mut ui).await,
+        Commands::Verify { formulas, all, .. } => {
+            commands::verify::execute(&mut installer, formulas, all, &mut ui)
+        }
+        Commands::List { size } => commands::list::execute(&mut installer, size, &mut ui),
+        Commands::Caveats { formulas } => {
+            commands::caveats::execute(&mut installer, formulas, &mut ui).await
+        }
+        Commands::Info {
+            formulas,
+            history,
+            eval_caveats,
+            used_by,
+            bottle_tags,
+        } => {
+            commands::info::execute(
                 &mut installer,
                 formulas,
-                no_link,
-                build_from_source,
+                history,
+                eval_caveats,
+                used_by,
+                bottle_tags,
                 &mut ui,
             )
             .await
         }
-        Commands::Bundle { command } => {
-            commands::bundle::execute(&mut installer, command, &mut ui).await
+        Commands::Deps {
+            formula,
+            tree,
+            installed,
+        } => commands::deps::execute(&mut installer, formula, tree, installed).await,
+        Commands::Uses { formula, recursive } => {
+            commands::uses::execute(&mut installer, formula, recursive).await
         }
-        Commands::Uninstall { formulas, all } => {
-            commands::uninstall::execute(&mut installer, formulas, all, &mut ui)
+        Commands::Leaves => commands::leaves::execute(&mut installer).await,
+        Commands::Autoremove { dry_run } => {
+            commands::autoremove::execute(&mut installer, dry_run, &mut ui).await
         }
-        Commands::Migrate { yes, force } => {
-            commands::migrate::execute(&mut installer, yes, force, &mut ui).await
+        Commands::Sbom {
+            formulas,
+            all: _,
+            format,
+            output,
+        } => commands::sbom::execute(&mut installer, formulas, format, output, &mut ui).await,
+        Commands::Audit { formulas, fix } => {
+            commands::audit::execute(&mut installer, formulas, fix, &mut ui).await
         }
-        Commands::Doctor { repair } => commands::doctor::execute(&mut installer, repair, &mut ui),
-        Commands::List => commands::list::execute(&mut installer),
-        Commands::Info { formula } => commands::info::execute(&mut installer, formula),
-        Commands::Gc => commands::gc::execute(&mut installer),
+        Commands::Which { command } => commands::which::execute(&mut installer, command, &mut ui),
+        Commands::Provides { path } => {
+            commands::which::execute_provides(&mut installer, path, &mut ui)
+        }
+        Commands::FormulaPath { formula } => {
+            commands::formula_path::execute(&mut installer, formula).await
+        }
+        Commands::Log { formula, path } => commands::log::execute(&root, formula, path),
+        Commands::Search {
+            query,
+            installed,
+            desc,
+        } => commands::search::execute(&mut installer, query, installed, desc).await,
+        Commands::Gc { aggressive, yes } => {
+            if aggressive {
+                commands::gc::execute_aggressive(&mut installer, yes, &mut ui).await
+            } else {
+                commands::gc::execute(&mut installer, &mut ui)
+            }
+        }
+        Commands::Cleanup { prune, dry_run } => {
+            commands::cleanup::execute(&mut installer, prune, dry_run, &mut ui)
+        }
+        Commands::Cache { command } => commands::cache::execute(&mut installer, command, &mut ui),
         Commands::Update => commands::update::execute(&mut installer),
-        Commands::Outdated { json } => {
-            commands::outdated::execute(&mut installer, cli.quiet, cli.verbose > 0, json).await
+        Commands::Outdated => {
+            commands::outdated::execute(&mut installer, cli.quiet, cli.verbose > 0, &mut ui).await
+        }
+        Commands::Postinstall { formulas } => {
+            commands::postinstall::execute(&mut installer, formulas, &mut ui).await
+        }
+        Commands::Reinstall {
+            formulas,
+            from_source,
+            no_link,
+        } => {
+            commands::reinstall::execute(&mut installer, formulas, from_source, no_link, &mut ui)
+                .await
         }
         Commands::Upgrade {
             formulas,
+            all,
             build_from_source,
             no_link,
+            fail_fast,
+            no_cleanup,
         } => {
             commands::upgrade::execute(
                 &mut installer,
                 formulas,
+                all,
                 build_from_source,
                 no_link,
+                fail_fast,
+                no_cleanup,
                 &mut ui,
             )
             .await
         }
         Commands::Reset { yes } => commands::reset::execute(&root, &prefix, yes, &mut ui),
-        Commands::Run { formula, args } => {
-            commands::run::execute(&mut installer, formula, args).await
-        }
+        Commands::Run {
+            formula,
+            ephemeral,
+            args,
+        } => commands::run::execute(&mut installer, formula, args, ephemeral).await,
     }
 }
@@ -16,13 +16,130 @@ pub struct Cli {
     pub prefix: Option<PathBuf>,
 
     #[arg(
-        long,
+        long = "download-concurrency",
+        visible_alias = "concurrency",
+        env = "ZEROBREW_DOWNLOAD_CONCURRENCY",
         default_value = "20",
         value_parser = parse_concurrency,
-        help = "Number of concurrent download threads"
+        help = "Number of concurrent download connections; bottles unpack one at a time as they arrive (1 installs strictly serially)"
     )]
     pub concurrency: usize,
 
+    #[arg(
+        long = "max-download-rate",
+        global = true,
+        env = "ZEROBREW_MAX_DOWNLOAD_RATE",
+        value_name = "RATE",
+        value_parser = parse_download_rate,
+        help = "Cap aggregate download throughput, e.g. `5MB/s`, `512KiB` or bytes per second"
+    )]
+    pub max_download_rate: Option<u64>,
+
+    #[arg(
+        long,
+        env = "ZEROBREW_DOWNLOAD_RETRIES",
+        default_value = "3",
+        help = "Retries for a bottle or API request that fails with a transient error"
+    )]
+    pub retries: u32,
+
+    #[arg(
+        long = "retry-backoff",
+        env = "ZEROBREW_RETRY_BACKOFF_MS",
+        value_name = "MS",
+        default_value = "100",
+        help = "Delay before the first retry in milliseconds, doubling on each retry after it"
+    )]
+    pub retry_backoff_ms: u64,
+
+    #[arg(
+        long = "no-retry-jitter",
+        env = "ZEROBREW_NO_RETRY_JITTER",
+        help = "Wait the exact backoff between retries instead of a randomised share of it"
+    )]
+    pub no_retry_jitter: bool,
+
+    #[arg(
+        long = "mirror",
+        env = "ZEROBREW_BOTTLE_MIRRORS",
+        value_name = "URL",
+        value_delimiter = ',',
+        help = "Bottle mirror tried in order when ghcr.io fails: a domain or base URL (repeatable)"
+    )]
+    pub mirrors: Vec<String>,
+
+    #[arg(
+        long,
+        env = "ZEROBREW_PROXY",
+        value_name = "URL",
+        help = "Proxy for every request, in place of HTTPS_PROXY/ALL_PROXY"
+    )]
+    pub proxy: Option<String>,
+
+    #[arg(
+        long = "ca-bundle",
+        env = "ZEROBREW_CA_BUNDLE",
+        value_name = "PEM",
+        help = "Also trust the CA certificates in this PEM file, e.g. a TLS-intercepting proxy's"
+    )]
+    pub ca_bundle: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "ZEROBREW_INSECURE",
+        conflicts_with = "ca_bundle",
+        help = "Skip TLS certificate verification (unsafe; bottle checksums are still verified)"
+    )]
+    pub insecure: bool,
+
+    #[arg(
+        long = "keep-tmp-on-failure",
+        global = true,
+        env = "ZEROBREW_KEEP_TMP_ON_FAILURE",
+        help = "Keep the build work directory when a source build fails"
+    )]
+    pub keep_tmp_on_failure: bool,
+
+    #[arg(
+        long = "verify-after-install",
+        global = true,
+        env = "ZEROBREW_VERIFY_AFTER_INSTALL",
+        help = "Record a checksum manifest for each installed keg and check its symlinks"
+    )]
+    pub verify_after_install: bool,
+
+    #[arg(
+        long = "require-relocation",
+        global = true,
+        env = "ZEROBREW_REQUIRE_RELOCATION",
+        help = "Fail a bottle install if a binary still references a default Homebrew prefix after relocation"
+    )]
+    pub require_relocation: bool,
+
+    #[arg(
+        long = "verify-signatures",
+        global = true,
+        env = "ZEROBREW_VERIFY_SIGNATURES",
+        help = "Check each bottle's GitHub artifact attestation before unpacking it, refusing bottles that have none (needs `gh`)"
+    )]
+    pub verify_signatures: bool,
+
+    #[arg(
+        long = "no-wait",
+        global = true,
+        env = "ZEROBREW_NO_WAIT",
+        help = "Fail instead of waiting while another zerobrew process is installing"
+    )]
+    pub no_wait: bool,
+
+    #[arg(
+        long,
+        global = true,
+        env = "ZEROBREW_APPDIR",
+        help = "Directory cask apps are installed into (default: /Applications)"
+    )]
+    pub appdir: Option<PathBuf>,
+
     #[arg(
         long = "auto-init",
         global = true,
@@ -43,6 +160,23 @@ pub struct Cli {
     )]
     pub quiet: bool,
 
+    #[arg(
+        long,
+        global = true,
+        env = "ZEROBREW_DEBUG",
+        conflicts_with = "quiet",
+        help = "Show debug logs with timestamps and targets (the full trace is always in <root>/logs/zb.log)"
+    )]
+    pub debug: bool,
+
+    #[arg(
+        long,
+        global = true,
+        conflicts_with_all = ["quiet", "verbose"],
+        help = "Print structured JSON results and events instead of styled output"
+    )]
+    pub json: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -57,9 +191,59 @@ fn parse_concurrency(value: &str) -> Result<usize, String> {
     Ok(parsed)
 }
 
+/// Bytes per second from a plain number or a size with a unit, optionally
+/// followed by `/s`: `KB`/`MB`/`GB` are decimal, `KiB`/`MiB`/`GiB` and the
+/// bare `K`/`M`/`G` binary, as in curl's `--limit-rate`.
+pub(crate) fn parse_download_rate(value: &str) -> Result<u64, String> {
+    let invalid = || {
+        format!(
+            "invalid value '{}': expected bytes per second or a rate like `5MB/s`",
+            value
+        )
+    };
+    let rate = value.trim();
+    let rate = rate.strip_suffix("/s").unwrap_or(rate).trim_end();
+    let split = rate
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(rate.len());
+    let (number, unit) = rate.split_at(split);
+    let number = number.parse::<f64>().map_err(|_| invalid())?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        _ => return Err(invalid()),
+    };
+    let parsed = (number * multiplier as f64) as u64;
+    if parsed == 0 {
+        return Err("download rate must be at least 1 byte per second".to_string());
+    }
+    Ok(parsed)
+}
+
+/// Days a download must have been cached before `cleanup` removes it;
+/// `all` is zero days.
+fn parse_prune_days(value: &str) -> Result<u64, String> {
+    if value == "all" {
+        return Ok(0);
+    }
+    value.parse::<u64>().map_err(|_| {
+        format!(
+            "invalid value '{}': expected a number of days or `all`",
+            value
+        )
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Cli;
+    use super::{
+        BundleCommands, CacheCommands, Cli, Commands, SbomFormat, ServicesCommands, ShellenvShell,
+    };
     use clap::Parser;
 
     #[test]
@@ -76,6 +260,373 @@ mod tests {
         assert!(err.contains("at least 1"));
     }
 
+    #[test]
+    fn max_download_rate_defaults_to_unlimited() {
+        let cli = Cli::try_parse_from(["zb", "list"]).unwrap();
+        assert_eq!(cli.max_download_rate, None);
+    }
+
+    #[test]
+    fn accepts_positive_max_download_rate() {
+        let cli = Cli::try_parse_from(["zb", "--max-download-rate", "1048576", "list"]).unwrap();
+        assert_eq!(cli.max_download_rate, Some(1_048_576));
+    }
+
+    #[test]
+    fn max_download_rate_is_global() {
+        let cli =
+            Cli::try_parse_from(["zb", "install", "jq", "--max-download-rate", "1MB/s"]).unwrap();
+        assert_eq!(cli.max_download_rate, Some(1_000_000));
+    }
+
+    #[test]
+    fn max_download_rate_accepts_units() {
+        for (value, expected) in [
+            ("5MB/s", 5_000_000),
+            ("512K", 512 * 1024),
+            ("1.5MiB/s", 1_572_864),
+            ("2 GB", 2_000_000_000),
+            ("100b/s", 100),
+        ] {
+            let cli = Cli::try_parse_from(["zb", "--max-download-rate", value, "list"]).unwrap();
+            assert_eq!(cli.max_download_rate, Some(expected), "{value}");
+        }
+        assert!(Cli::try_parse_from(["zb", "--max-download-rate", "5TB/s", "list"]).is_err());
+        assert!(Cli::try_parse_from(["zb", "--max-download-rate", "fast", "list"]).is_err());
+    }
+
+    #[test]
+    fn download_concurrency_is_also_accepted_as_concurrency() {
+        let cli = Cli::try_parse_from(["zb", "--download-concurrency", "3", "list"]).unwrap();
+        assert_eq!(cli.concurrency, 3);
+        let cli = Cli::try_parse_from(["zb", "--concurrency", "5", "list"]).unwrap();
+        assert_eq!(cli.concurrency, 5);
+    }
+
+    #[test]
+    fn rejects_zero_max_download_rate() {
+        let result = Cli::try_parse_from(["zb", "--max-download-rate", "0", "list"]);
+        assert!(result.is_err());
+        let err = result.err().map(|e| e.to_string()).unwrap_or_default();
+        assert!(err.contains("at least 1 byte"));
+    }
+
+    #[test]
+    fn retry_settings_default_to_three_jittered_retries() {
+        let cli = Cli::try_parse_from(["zb", "list"]).unwrap();
+        assert_eq!(cli.retries, 3);
+        assert_eq!(cli.retry_backoff_ms, 100);
+        assert!(!cli.no_retry_jitter);
+        assert!(cli.mirrors.is_empty());
+        assert_eq!(cli.proxy, None);
+        assert_eq!(cli.ca_bundle, None);
+        assert!(!cli.insecure);
+    }
+
+    #[test]
+    fn run_passes_everything_after_the_formula_through() {
+        let cli =
+            Cli::try_parse_from(["zb", "run", "--ephemeral", "ripgrep", "--", "rg", "-i"]).unwrap();
+        match cli.command {
+            Commands::Run {
+                formula,
+                ephemeral,
+                args,
+            } => {
+                assert_eq!(formula, "ripgrep");
+                assert!(ephemeral);
+                assert_eq!(args, ["rg", "-i"]);
+            }
+            _ => panic!("expected run command"),
+        }
+    }
+
+    #[test]
+    fn insecure_conflicts_with_ca_bundle() {
+        let cli = Cli::try_parse_from(["zb", "--ca-bundle", "/etc/corp.pem", "list"]).unwrap();
+        assert_eq!(
+            cli.ca_bundle,
+            Some(std::path::PathBuf::from("/etc/corp.pem"))
+        );
+
+        let result =
+            Cli::try_parse_from(["zb", "--ca-bundle", "/etc/corp.pem", "--insecure", "list"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn mirrors_keep_their_order() {
+        let cli = Cli::try_parse_from([
+            "zb",
+            "--retries",
+            "0",
+            "--mirror",
+            "https://cache.example/ghcr",
+            "--mirror",
+            "mirror.example,other.example",
+            "install",
+            "jq",
+        ])
+        .unwrap();
+        assert_eq!(cli.retries, 0);
+        assert_eq!(
+            cli.mirrors,
+            [
+                "https://cache.example/ghcr",
+                "mirror.example",
+                "other.example"
+            ]
+        );
+    }
+
+    #[test]
+    fn install_accepts_lockfile_without_formulas() {
+        let cli = Cli::try_parse_from(["zb", "install", "--from-lockfile", "zb.lock", "--frozen"])
+            .unwrap();
+        match cli.command {
+            Commands::Install {
+                formulas,
+                from_lockfile,
+                frozen,
+                ..
+            } => {
+                assert!(formulas.is_empty());
+                assert_eq!(from_lockfile, Some(std::path::PathBuf::from("zb.lock")));
+                assert!(frozen);
+            }
+            _ => panic!("expected install command"),
+        }
+    }
+
+    #[test]
+    fn install_overwrite_and_skip_conflicts_are_exclusive() {
+        let cli = Cli::try_parse_from(["zb", "install", "jq", "--skip-conflicts"]).unwrap();
+        match cli.command {
+            Commands::Install {
+                overwrite,
+                skip_conflicts,
+                ..
+            } => assert!(skip_conflicts && !overwrite),
+            _ => panic!("expected install command"),
+        }
+        assert!(
+            Cli::try_parse_from(["zb", "install", "jq", "--overwrite", "--skip-conflicts"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn install_offline_rejects_source_builds() {
+        let cli = Cli::try_parse_from(["zb", "install", "jq", "--offline"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Install { offline: true, .. }
+        ));
+        assert!(Cli::try_parse_from(["zb", "install", "jq", "--offline", "-s"]).is_err());
+    }
+
+    #[test]
+    fn fetch_takes_formulas_and_download_only() {
+        let cli = Cli::try_parse_from(["zb", "fetch", "jq", "wget", "--download-only"]).unwrap();
+        match cli.command {
+            Commands::Fetch {
+                formulas,
+                download_only,
+            } => {
+                assert_eq!(formulas, vec!["jq", "wget"]);
+                assert!(download_only);
+            }
+            _ => panic!("expected fetch command"),
+        }
+        assert!(Cli::try_parse_from(["zb", "fetch"]).is_err());
+    }
+
+    #[test]
+    fn install_ignore_conflicts_with_is_repeatable() {
+        let cli = Cli::try_parse_from([
+            "zb",
+            "install",
+            "foo",
+            "--ignore-conflicts-with",
+            "bar",
+            "--ignore-conflicts-with",
+            "baz",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Install {
+                formulas,
+                ignore_conflicts_with,
+                ..
+            } => {
+                assert_eq!(formulas, vec!["foo"]);
+                assert_eq!(ignore_conflicts_with, vec!["bar", "baz"]);
+            }
+            _ => panic!("expected install command"),
+        }
+    }
+
+    #[test]
+    fn install_with_and_without_are_repeatable() {
+        let cli = Cli::try_parse_from([
+            "zb",
+            "install",
+            "ffmpeg",
+            "--with",
+            "libvpx",
+            "--with",
+            "x265",
+            "--without",
+            "sdl2",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Install { with, without, .. } => {
+                assert_eq!(with, vec!["libvpx", "x265"]);
+                assert_eq!(without, vec!["sdl2"]);
+            }
+            _ => panic!("expected install command"),
+        }
+    }
+
+    #[test]
+    fn install_build_deps_only_conflicts_with_lockfile() {
+        let cli = Cli::try_parse_from(["zb", "install", "foo", "--build-deps-only"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Install {
+                build_deps_only: true,
+                ..
+            }
+        ));
+        assert!(
+            Cli::try_parse_from([
+                "zb",
+                "install",
+                "--from-lockfile",
+                "zb.lock",
+                "--build-deps-only"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn install_only_if_newer_takes_a_version() {
+        let cli = Cli::try_parse_from(["zb", "install", "jq", "--only-if-newer", "1.7"]).unwrap();
+        match cli.command {
+            Commands::Install {
+                formulas,
+                only_if_newer,
+                ..
+            } => {
+                assert_eq!(formulas, vec!["jq"]);
+                assert_eq!(only_if_newer.as_deref(), Some("1.7"));
+            }
+            _ => panic!("expected install command"),
+        }
+        assert!(
+            Cli::try_parse_from([
+                "zb",
+                "install",
+                "jq",
+                "--only-if-newer",
+                "1.7",
+                "--build-deps-only"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn install_dependencies_from_source_conflicts_with_build_from_source() {
+        let cli =
+            Cli::try_parse_from(["zb", "install", "jq", "--dependencies-from-source"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Install {
+                dependencies_from_source: true,
+                build_from_source: false,
+                ..
+            }
+        ));
+        assert!(
+            Cli::try_parse_from(["zb", "install", "jq", "--dependencies-from-source", "-s"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn install_frozen_requires_lockfile() {
+        assert!(Cli::try_parse_from(["zb", "install", "jq", "--frozen"]).is_err());
+    }
+
+    #[test]
+    fn keep_tmp_on_failure_is_global() {
+        let cli = Cli::try_parse_from(["zb", "install", "jq", "--keep-tmp-on-failure"]).unwrap();
+        assert!(cli.keep_tmp_on_failure);
+    }
+
+    #[test]
+    fn verify_after_install_is_global() {
+        let cli = Cli::try_parse_from(["zb", "upgrade", "--verify-after-install"]).unwrap();
+        assert!(cli.verify_after_install);
+    }
+
+    #[test]
+    fn verify_signatures_is_global_and_off_by_default() {
+        let cli = Cli::try_parse_from(["zb", "install", "jq", "--verify-signatures"]).unwrap();
+        assert!(cli.verify_signatures);
+        let cli = Cli::try_parse_from(["zb", "install", "jq"]).unwrap();
+        assert!(!cli.verify_signatures);
+    }
+
+    #[test]
+    fn require_relocation_is_global() {
+        let cli = Cli::try_parse_from(["zb", "install", "jq", "--require-relocation"]).unwrap();
+        assert!(cli.require_relocation);
+        let cli = Cli::try_parse_from(["zb", "install", "jq"]).unwrap();
+        assert!(!cli.require_relocation);
+    }
+
+    #[test]
+    fn log_takes_a_formula() {
+        let cli = Cli::try_parse_from(["zb", "log", "jq", "--path"]).unwrap();
+        match cli.command {
+            Commands::Log { formula, path } => {
+                assert_eq!(formula, "jq");
+                assert!(path);
+            }
+            _ => panic!("expected log command"),
+        }
+    }
+
+    #[test]
+    fn only_commands_that_change_installs_modify_them() {
+        let modifies = |args: &[&str]| {
+            Cli::try_parse_from(args)
+                .unwrap()
+                .command
+                .modifies_installs()
+        };
+        assert!(modifies(&["zb", "install", "jq"]));
+        assert!(modifies(&["zb", "doctor", "--repair"]));
+        assert!(modifies(&["zb", "cache", "clean"]));
+        assert!(!modifies(&["zb", "list"]));
+        assert!(!modifies(&["zb", "info", "jq"]));
+        assert!(!modifies(&["zb", "doctor"]));
+        assert!(!modifies(&["zb", "autoremove", "--dry-run"]));
+        assert!(!modifies(&["zb", "cache"]));
+    }
+
+    #[test]
+    fn no_wait_is_global() {
+        let cli = Cli::try_parse_from(["zb", "upgrade", "--no-wait"]).unwrap();
+        assert!(cli.no_wait);
+        let cli = Cli::try_parse_from(["zb", "install", "jq"]).unwrap();
+        assert!(!cli.no_wait);
+    }
+
     #[test]
     fn accepts_verbose_levels() {
         let cli = Cli::try_parse_from(["zb", "-vv", "list"]).unwrap();
@@ -89,6 +640,373 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn accepts_debug_but_not_with_quiet() {
+        let cli = Cli::try_parse_from(["zb", "list", "--debug"]).unwrap();
+        assert!(cli.debug);
+        assert!(Cli::try_parse_from(["zb", "--debug", "-q", "list"]).is_err());
+    }
+
+    #[test]
+    fn search_accepts_installed_flag() {
+        let cli = Cli::try_parse_from(["zb", "search", "jq", "--installed"]).unwrap();
+        match cli.command {
+            Commands::Search {
+                query, installed, ..
+            } => {
+                assert_eq!(query, "jq");
+                assert!(installed);
+            }
+            _ => panic!("expected search command"),
+        }
+    }
+
+    #[test]
+    fn search_desc_conflicts_with_installed() {
+        let cli = Cli::try_parse_from(["zb", "search", "json", "--desc"]).unwrap();
+        assert!(matches!(cli.command, Commands::Search { desc: true, .. }));
+        assert!(Cli::try_parse_from(["zb", "search", "json", "--desc", "--installed"]).is_err());
+    }
+
+    #[test]
+    fn migrate_accepts_only_with_dry_run() {
+        let cli =
+            Cli::try_parse_from(["zb", "migrate", "--only", "wget", "jq", "--dry-run"]).unwrap();
+        match cli.command {
+            Commands::Migrate { only, dry_run, .. } => {
+                assert_eq!(only, vec!["wget", "jq"]);
+                assert!(dry_run);
+            }
+            _ => panic!("expected migrate command"),
+        }
+    }
+
+    #[test]
+    fn caveats_requires_formulas() {
+        let cli = Cli::try_parse_from(["zb", "caveats", "postgresql@17", "node"]).unwrap();
+        match cli.command {
+            Commands::Caveats { formulas } => {
+                assert_eq!(formulas, vec!["postgresql@17", "node"])
+            }
+            _ => panic!("expected caveats command"),
+        }
+        assert!(Cli::try_parse_from(["zb", "caveats"]).is_err());
+    }
+
+    #[test]
+    fn which_and_provides_take_one_argument() {
+        let cli = Cli::try_parse_from(["zb", "which", "jq"]).unwrap();
+        match cli.command {
+            Commands::Which { command } => assert_eq!(command, "jq"),
+            _ => panic!("expected which command"),
+        }
+        let cli = Cli::try_parse_from(["zb", "provides", "/opt/zerobrew/lib/libz.dylib"]).unwrap();
+        match cli.command {
+            Commands::Provides { path } => {
+                assert_eq!(path, PathBuf::from("/opt/zerobrew/lib/libz.dylib"))
+            }
+            _ => panic!("expected provides command"),
+        }
+        assert!(Cli::try_parse_from(["zb", "which"]).is_err());
+    }
+
+    #[test]
+    fn postinstall_requires_formulas() {
+        let cli = Cli::try_parse_from(["zb", "postinstall", "postgresql@17"]).unwrap();
+        match cli.command {
+            Commands::Postinstall { formulas } => assert_eq!(formulas, vec!["postgresql@17"]),
+            _ => panic!("expected postinstall command"),
+        }
+        assert!(Cli::try_parse_from(["zb", "postinstall"]).is_err());
+    }
+
+    #[test]
+    fn pin_and_unpin_require_formulas() {
+        let cli = Cli::try_parse_from(["zb", "pin", "node", "python@3.12"]).unwrap();
+        match cli.command {
+            Commands::Pin { formulas } => assert_eq!(formulas, vec!["node", "python@3.12"]),
+            _ => panic!("expected pin command"),
+        }
+        assert!(matches!(
+            Cli::try_parse_from(["zb", "unpin", "node"])
+                .unwrap()
+                .command,
+            Commands::Unpin { .. }
+        ));
+        assert!(Cli::try_parse_from(["zb", "pin"]).is_err());
+        assert!(Cli::try_parse_from(["zb", "unpin"]).is_err());
+    }
+
+    #[test]
+    fn upgrade_all_conflicts_with_formula_names() {
+        let result = Cli::try_parse_from(["zb", "upgrade", "--all", "jq"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn install_accepts_formula_url_without_names() {
+        let cli = Cli::try_parse_from([
+            "zb",
+            "install",
+            "--formula-url",
+            "https://example.com/widget.rb",
+            "--formula-sha256",
+            "abc",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Install {
+                formulas,
+                formula_url,
+                formula_sha256,
+                ..
+            } => {
+                assert!(formulas.is_empty());
+                assert_eq!(
+                    formula_url.as_deref(),
+                    Some("https://example.com/widget.rb")
+                );
+                assert_eq!(formula_sha256.as_deref(), Some("abc"));
+            }
+            _ => panic!("expected install command"),
+        }
+
+        assert!(Cli::try_parse_from(["zb", "install", "jq", "--formula-sha256", "abc"]).is_err());
+    }
+
+    #[test]
+    fn install_accepts_bottle_policy_flags() {
+        let cli = Cli::try_parse_from([
+            "zb",
+            "install",
+            "jq",
+            "--force-bottle",
+            "--bottle-arch",
+            "x86_64",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Install {
+                force_bottle,
+                bottle_arch,
+                ..
+            } => {
+                assert!(force_bottle);
+                assert_eq!(bottle_arch.as_deref(), Some("x86_64"));
+            }
+            _ => panic!("expected install command"),
+        }
+
+        assert!(Cli::try_parse_from(["zb", "install", "jq", "--force-bottle", "-s"]).is_err());
+    }
+
+    #[test]
+    fn install_accepts_head() {
+        let cli = Cli::try_parse_from(["zb", "install", "jq", "--HEAD"]).unwrap();
+        assert!(matches!(cli.command, Commands::Install { head: true, .. }));
+        assert!(Cli::try_parse_from(["zb", "install", "jq", "--HEAD", "--force-bottle"]).is_err());
+    }
+
+    #[test]
+    fn install_accepts_formula_file_without_names() {
+        let cli = Cli::try_parse_from(["zb", "install", "--formula-file", "./my-tool.rb"]).unwrap();
+        match cli.command {
+            Commands::Install {
+                formulas,
+                formula_file,
+                ..
+            } => {
+                assert!(formulas.is_empty());
+                assert_eq!(formula_file, Some(std::path::PathBuf::from("./my-tool.rb")));
+            }
+            _ => panic!("expected install command"),
+        }
+
+        assert!(
+            Cli::try_parse_from([
+                "zb",
+                "install",
+                "--formula-file",
+                "./my-tool.rb",
+                "--from-lockfile",
+                "zb.lock"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn gc_yes_requires_aggressive() {
+        assert!(Cli::try_parse_from(["zb", "gc", "--yes"]).is_err());
+        let cli = Cli::try_parse_from(["zb", "gc", "--aggressive", "-y"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Gc {
+                aggressive: true,
+                yes: true
+            }
+        ));
+    }
+
+    #[test]
+    fn cleanup_prune_accepts_days_or_all() {
+        let prune = |args: &[&str]| match Cli::try_parse_from(args).unwrap().command {
+            Commands::Cleanup { prune, .. } => prune,
+            _ => panic!("expected cleanup command"),
+        };
+        assert_eq!(prune(&["zb", "cleanup"]), 120);
+        assert_eq!(prune(&["zb", "cleanup", "--prune", "30"]), 30);
+        assert_eq!(prune(&["zb", "cleanup", "--prune=all", "--dry-run"]), 0);
+        assert!(Cli::try_parse_from(["zb", "cleanup", "--prune", "soon"]).is_err());
+    }
+
+    #[test]
+    fn info_accepts_multiple_formulas() {
+        assert!(Cli::try_parse_from(["zb", "info"]).is_err());
+
+        let cli = Cli::try_parse_from(["zb", "info", "jq", "wget", "--json"]).unwrap();
+        assert!(cli.json);
+        match cli.command {
+            Commands::Info { formulas, .. } => {
+                assert_eq!(formulas, vec!["jq", "wget"]);
+            }
+            _ => panic!("expected info command"),
+        }
+    }
+
+    #[test]
+    fn shellenv_shell_is_optional() {
+        let cli = Cli::try_parse_from(["zb", "shellenv"]).unwrap();
+        assert!(matches!(cli.command, Commands::Shellenv { shell: None }));
+
+        let cli = Cli::try_parse_from(["zb", "shellenv", "nu"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Shellenv {
+                shell: Some(ShellenvShell::Nu)
+            }
+        ));
+        assert!(Cli::try_parse_from(["zb", "shellenv", "tcsh"]).is_err());
+    }
+
+    #[test]
+    fn use_takes_tool_and_version() {
+        let cli = Cli::try_parse_from(["zb", "use", "node", "18"]).unwrap();
+        match cli.command {
+            Commands::Use { tool, version } => {
+                assert_eq!(tool, "node");
+                assert_eq!(version, "18");
+            }
+            _ => panic!("expected use command"),
+        }
+        assert!(Cli::try_parse_from(["zb", "use", "node"]).is_err());
+    }
+
+    #[test]
+    fn rollback_takes_a_formula_or_a_generation() {
+        let rollback = |args: &[&str]| match Cli::try_parse_from(args).unwrap().command {
+            Commands::Rollback { formula, to } => (formula, to),
+            _ => panic!("expected rollback command"),
+        };
+        assert_eq!(
+            rollback(&["zb", "rollback", "jq"]),
+            (Some("jq".to_string()), None)
+        );
+        assert_eq!(rollback(&["zb", "rollback"]), (None, None));
+        assert_eq!(rollback(&["zb", "rollback", "--to", "3"]), (None, Some(3)));
+        assert!(Cli::try_parse_from(["zb", "rollback", "jq", "--to", "3"]).is_err());
+        assert!(matches!(
+            Cli::try_parse_from(["zb", "generations"]).unwrap().command,
+            Commands::Generations
+        ));
+    }
+
+    #[test]
+    fn deps_and_uses_take_a_formula_and_flags() {
+        let cli = Cli::try_parse_from(["zb", "deps", "wget", "--tree", "--installed"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Deps {
+                tree: true,
+                installed: true,
+                ..
+            }
+        ));
+        let cli = Cli::try_parse_from(["zb", "uses", "openssl@3", "--recursive"]).unwrap();
+        match cli.command {
+            Commands::Uses { formula, recursive } => {
+                assert_eq!(formula, "openssl@3");
+                assert!(recursive);
+            }
+            _ => panic!("expected uses command"),
+        }
+        assert!(Cli::try_parse_from(["zb", "deps"]).is_err());
+    }
+
+    #[test]
+    fn leaves_and_autoremove_parse() {
+        let cli = Cli::try_parse_from(["zb", "leaves"]).unwrap();
+        assert!(matches!(cli.command, Commands::Leaves));
+        let cli = Cli::try_parse_from(["zb", "autoremove", "--dry-run"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Autoremove { dry_run: true }
+        ));
+        assert!(Cli::try_parse_from(["zb", "leaves", "jq"]).is_err());
+    }
+
+    #[test]
+    fn link_takes_conflict_flags_and_unlink_requires_a_formula() {
+        let cli =
+            Cli::try_parse_from(["zb", "link", "libpq", "--force", "--overwrite", "--dry-run"])
+                .unwrap();
+        match cli.command {
+            Commands::Link {
+                formulas,
+                force,
+                overwrite,
+                dry_run,
+            } => {
+                assert_eq!(formulas, vec!["libpq"]);
+                assert!(force && overwrite && dry_run);
+            }
+            _ => panic!("expected link command"),
+        }
+        assert!(Cli::try_parse_from(["zb", "link"]).is_err());
+        assert!(Cli::try_parse_from(["zb", "unlink"]).is_err());
+    }
+
+    #[test]
+    fn verify_requires_formulas_or_all() {
+        assert!(Cli::try_parse_from(["zb", "verify"]).is_err());
+        assert!(Cli::try_parse_from(["zb", "verify", "--all", "jq"]).is_err());
+
+        let cli = Cli::try_parse_from(["zb", "verify", "--all", "--json"]).unwrap();
+        assert!(cli.json);
+        match cli.command {
+            Commands::Verify { formulas, all, .. } => {
+                assert!(formulas.is_empty());
+                assert!(all);
+            }
+            _ => panic!("expected verify command"),
+        }
+    }
+
+    #[test]
+    fn verify_store_takes_no_formulas() {
+        let cli = Cli::try_parse_from(["zb", "verify", "--store", "--repair"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Verify {
+                store: true,
+                repair: true,
+                ..
+            }
+        ));
+        assert!(Cli::try_parse_from(["zb", "verify", "--store", "jq"]).is_err());
+        assert!(Cli::try_parse_from(["zb", "verify", "--repair", "jq"]).is_err());
+    }
+
     #[test]
     fn outdated_quiet_and_verbose_conflict() {
         let result = Cli::try_parse_from(["zb", "outdated", "--quiet", "--verbose"]);
@@ -106,18 +1024,322 @@ mod tests {
         let result = Cli::try_parse_from(["zb", "outdated", "--verbose", "--json"]);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn bundle_lock_and_locked_install_parse() {
+        let cli = Cli::try_parse_from(["zb", "bundle", "lock", "--file", "Brewfile.dev"]).unwrap();
+        match cli.command {
+            Commands::Bundle {
+                command: Some(BundleCommands::Lock { file, global }),
+            } => {
+                assert_eq!(file, std::path::PathBuf::from("Brewfile.dev"));
+                assert!(!global);
+            }
+            _ => panic!("expected bundle lock"),
+        }
+
+        let cli = Cli::try_parse_from(["zb", "bundle", "install", "--locked"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Bundle {
+                command: Some(BundleCommands::Install { locked: true, .. })
+            }
+        ));
+    }
+
+    #[test]
+    fn bundle_check_and_cleanup_parse() {
+        let cli = Cli::try_parse_from(["zb", "bundle", "check", "-f", "Brewfile.dev"]).unwrap();
+        match cli.command {
+            Commands::Bundle {
+                command: Some(BundleCommands::Check { file, global }),
+            } => {
+                assert_eq!(file, std::path::PathBuf::from("Brewfile.dev"));
+                assert!(!global);
+            }
+            _ => panic!("expected bundle check"),
+        }
+
+        let cli = Cli::try_parse_from(["zb", "bundle", "cleanup", "--global", "--force"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Bundle {
+                command: Some(BundleCommands::Cleanup {
+                    global: true,
+                    force: true,
+                    ..
+                })
+            }
+        ));
+    }
+
+    #[test]
+    fn sbom_needs_formulas_or_all() {
+        assert!(Cli::try_parse_from(["zb", "sbom"]).is_err());
+        assert!(Cli::try_parse_from(["zb", "sbom", "jq", "--all"]).is_err());
+
+        let cli = Cli::try_parse_from(["zb", "sbom", "--all", "--format", "cyclonedx"]).unwrap();
+        match cli.command {
+            Commands::Sbom {
+                formulas,
+                all,
+                format,
+                output,
+            } => {
+                assert!(formulas.is_empty());
+                assert!(all);
+                assert_eq!(format, SbomFormat::Cyclonedx);
+                assert_eq!(output, None);
+            }
+            _ => panic!("expected sbom"),
+        }
+
+        let cli = Cli::try_parse_from(["zb", "sbom", "jq", "-o", "jq.spdx.json"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Sbom {
+                format: SbomFormat::Spdx,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn bundle_dump_accepts_fidelity_options() {
+        let cli = Cli::try_parse_from([
+            "zb",
+            "bundle",
+            "dump",
+            "--describe",
+            "--no-deps",
+            "--versions",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Bundle {
+                command: Some(BundleCommands::Dump {
+                    describe: true,
+                    no_deps: true,
+                    versions: true,
+                    ..
+                })
+            }
+        ));
+    }
+
+    #[test]
+    fn audit_takes_formulas_and_fix() {
+        let cli = Cli::try_parse_from(["zb", "audit"]).unwrap();
+        match cli.command {
+            Commands::Audit { formulas, fix } => {
+                assert!(formulas.is_empty());
+                assert!(!fix);
+            }
+            _ => panic!("expected audit"),
+        }
+
+        let cli = Cli::try_parse_from(["zb", "audit", "openssl@3", "--fix"]).unwrap();
+        match cli.command {
+            Commands::Audit { formulas, fix } => {
+                assert_eq!(formulas, vec!["openssl@3".to_string()]);
+                assert!(fix);
+            }
+            _ => panic!("expected audit"),
+        }
+    }
+
+    #[test]
+    fn cache_defaults_to_list_and_clean_takes_an_age() {
+        let cli = Cli::try_parse_from(["zb", "cache"]).unwrap();
+        assert!(matches!(cli.command, Commands::Cache { command: None }));
+
+        let cli = Cli::try_parse_from(["zb", "cache", "clean"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Cache {
+                command: Some(CacheCommands::Clean { older_than: 0 })
+            }
+        ));
+
+        let cli = Cli::try_parse_from(["zb", "cache", "clean", "--older-than", "30"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Cache {
+                command: Some(CacheCommands::Clean { older_than: 30 })
+            }
+        ));
+        assert!(Cli::try_parse_from(["zb", "cache", "clean", "--older-than", "soon"]).is_err());
+    }
+
+    #[test]
+    fn services_defaults_to_list_and_takes_a_formula() {
+        let cli = Cli::try_parse_from(["zb", "services"]).unwrap();
+        assert!(matches!(cli.command, Commands::Services { command: None }));
+
+        let cli = Cli::try_parse_from(["zb", "services", "start", "postgresql@17"]).unwrap();
+        match cli.command {
+            Commands::Services {
+                command: Some(ServicesCommands::Start { formula }),
+            } => assert_eq!(formula, "postgresql@17"),
+            _ => panic!("expected services start"),
+        }
+
+        assert!(Cli::try_parse_from(["zb", "services", "stop"]).is_err());
+    }
+
+    #[test]
+    fn json_is_global_across_commands() {
+        let cli = Cli::try_parse_from(["zb", "--json", "gc"]).unwrap();
+        assert!(cli.json);
+        assert!(matches!(cli.command, Commands::Gc { .. }));
+
+        let cli = Cli::try_parse_from(["zb", "install", "jq", "--json"]).unwrap();
+        assert!(cli.json);
+
+        assert!(!Cli::try_parse_from(["zb", "list"]).unwrap().json);
+    }
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     /// Install formulas and casks
     Install {
+        #[arg(
+            required_unless_present_any = ["from_lockfile", "formula_url", "formula_file"],
+            conflicts_with = "from_lockfile",
+            num_args = 1..
+        )]
+        formulas: Vec<String>,
+        #[arg(long, help = "Do not create symlinks after installation")]
+        no_link: bool,
+        #[arg(long, short = 's', help = "Build from source instead of using bottles")]
+        build_from_source: bool,
+        #[arg(
+            long,
+            conflicts_with_all = ["build_from_source", "dependencies_from_source"],
+            help = "Never build from source, settling for the closest bottle when none matches this OS release"
+        )]
+        force_bottle: bool,
+        #[arg(
+            long,
+            value_name = "ARCH",
+            help = "Use bottles for this architecture (arm64, x86_64) or this exact bottle tag"
+        )]
+        bottle_arch: Option<String>,
+        #[arg(
+            long = "HEAD",
+            conflicts_with_all = ["from_lockfile", "force_bottle", "build_deps_only", "only_if_newer", "dependencies_from_source", "offline"],
+            help = "Build the requested formulas from the latest commit of their upstream git repository"
+        )]
+        head: bool,
+        #[arg(
+            long,
+            help = "Print the build environment to stderr before each source build"
+        )]
+        print_env: bool,
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "Install the formulas pinned in a lockfile"
+        )]
+        from_lockfile: Option<PathBuf>,
+        #[arg(
+            long,
+            requires = "from_lockfile",
+            help = "Fail if current metadata resolves differently from the lockfile"
+        )]
+        frozen: bool,
+        #[arg(
+            long,
+            value_name = "FORMULA",
+            help = "Install despite a declared conflict with this formula (repeatable)"
+        )]
+        ignore_conflicts_with: Vec<String>,
+        #[arg(
+            long,
+            help = "Replace pinned formulas the install would otherwise skip"
+        )]
+        ignore_pins: bool,
+        #[arg(
+            long,
+            value_name = "FORMULA",
+            help = "Also install this optional dependency (repeatable)"
+        )]
+        with: Vec<String>,
+        #[arg(
+            long,
+            value_name = "FORMULA",
+            help = "Skip this recommended dependency (repeatable)"
+        )]
+        without: Vec<String>,
+        #[arg(
+            long,
+            help = "Replace files and other formulas' links that are in the way when linking"
+        )]
+        overwrite: bool,
+        #[arg(
+            long,
+            conflicts_with = "overwrite",
+            help = "Leave files that are in the way alone and link everything else"
+        )]
+        skip_conflicts: bool,
+        #[arg(
+            long,
+            value_name = "URL",
+            conflicts_with = "from_lockfile",
+            help = "Fetch a formula .rb from a URL and install it without a tap"
+        )]
+        formula_url: Option<String>,
+        #[arg(
+            long,
+            value_name = "SHA256",
+            requires = "formula_url",
+            help = "Expected sha256 of the file fetched with --formula-url"
+        )]
+        formula_sha256: Option<String>,
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with = "from_lockfile",
+            help = "Install a formula from a local .rb file without a tap"
+        )]
+        formula_file: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with = "from_lockfile",
+            help = "Only install the formulas' :build dependencies, for iterating on a source build"
+        )]
+        build_deps_only: bool,
+        #[arg(
+            long,
+            value_name = "VERSION",
+            conflicts_with_all = ["from_lockfile", "build_deps_only"],
+            help = "Install or upgrade only if the installed version is older than VERSION"
+        )]
+        only_if_newer: Option<String>,
+        #[arg(
+            long,
+            conflicts_with_all = ["build_from_source", "from_lockfile", "build_deps_only", "only_if_newer"],
+            help = "Build dependencies from source but install the requested formulas from bottles"
+        )]
+        dependencies_from_source: bool,
+        #[arg(
+            long,
+            conflicts_with_all = ["build_from_source", "formula_url", "dependencies_from_source"],
+            help = "Install only from cached metadata and bottles, without network access"
+        )]
+        offline: bool,
+    },
+    /// Download formulas and their dependencies for a later `install --offline`
+    Fetch {
         #[arg(required = true, num_args = 1..)]
         formulas: Vec<String>,
-        #[arg(long, help = "Do not create symlinks after installation")]
-        no_link: bool,
-        #[arg(long, short = 's', help = "Build from source instead of using bottles")]
-        build_from_source: bool,
+        #[arg(
+            long,
+            help = "Only download bottles into the cache, without unpacking them into the store"
+        )]
+        download_only: bool,
     },
     /// Install or dump from a Brewfile
     Bundle {
@@ -137,21 +1359,245 @@ pub enum Commands {
         yes: bool,
         #[arg(long, help = "Force uninstall from Homebrew even if errors occur")]
         force: bool,
+        #[arg(
+            long,
+            num_args = 1..,
+            value_name = "FORMULA",
+            help = "Only migrate these formulas and their dependencies"
+        )]
+        only: Vec<String>,
+        #[arg(long, help = "Show what would be migrated without changing anything")]
+        dry_run: bool,
+    },
+    /// Show disk usage per formula across all versions in the Cellar
+    Du {
+        #[arg(help = "Formulas to report (default: everything in the Cellar)")]
+        formulas: Vec<String>,
+    },
+    /// Point a tool's generic commands at one of its installed versions
+    Use {
+        #[arg(help = "Tool whose versions are installed side by side, e.g. node")]
+        tool: String,
+        #[arg(help = "Version to activate, e.g. 18 for node@18")]
+        version: String,
+    },
+    /// Link installed formulas into the prefix
+    Link {
+        #[arg(required = true, num_args = 1..)]
+        formulas: Vec<String>,
+        #[arg(long, help = "Link keg-only formulas too")]
+        force: bool,
+        #[arg(
+            long,
+            help = "Delete files and other formulas' links that are in the way"
+        )]
+        overwrite: bool,
+        #[arg(
+            long,
+            help = "List the files --overwrite would delete without changing anything"
+        )]
+        dry_run: bool,
+    },
+    /// Remove installed formulas' links from the prefix, keeping them installed
+    Unlink {
+        #[arg(required = true, num_args = 1..)]
+        formulas: Vec<String>,
+    },
+    /// Roll the prefix back to an earlier generation, or one formula to its previous version
+    Rollback {
+        #[arg(help = "Formula to roll back, kept with `zb upgrade --no-cleanup`")]
+        formula: Option<String>,
+        #[arg(
+            long,
+            value_name = "N",
+            conflicts_with = "formula",
+            help = "Generation to roll back to instead of the previous one"
+        )]
+        to: Option<i64>,
+    },
+    /// List the recorded generations of the prefix
+    Generations,
+    /// Keep formulas at their installed version when upgrading
+    Pin {
+        #[arg(required = true, num_args = 1..)]
+        formulas: Vec<String>,
+    },
+    /// Let pinned formulas be upgraded again
+    Unpin {
+        #[arg(required = true, num_args = 1..)]
+        formulas: Vec<String>,
+    },
+    /// Check installed kegs against the file manifest recorded at install time
+    Verify {
+        #[arg(required_unless_present_any = ["all", "store"], num_args = 0..)]
+        formulas: Vec<String>,
+        #[arg(
+            long,
+            conflicts_with = "formulas",
+            help = "Verify every installed formula"
+        )]
+        all: bool,
+        #[arg(
+            long,
+            conflicts_with_all = ["formulas", "all"],
+            help = "Re-hash every store entry against the checksums taken when it was extracted"
+        )]
+        store: bool,
+        #[arg(
+            long,
+            requires = "store",
+            help = "Re-download the bottles of damaged store entries"
+        )]
+        repair: bool,
     },
     /// List installed packages
-    List,
-    /// Show information about an installed package
+    List {
+        #[arg(long, help = "Include each keg's on-disk size and the total")]
+        size: bool,
+    },
+    /// Show a formula's caveats again
+    Caveats {
+        #[arg(required = true, num_args = 1..)]
+        formulas: Vec<String>,
+    },
+    /// Show information about a package, installed or not
     Info {
-        #[arg(help = "Name of the installed package")]
+        #[arg(required = true, num_args = 1.., help = "Names of the packages")]
+        formulas: Vec<String>,
+        #[arg(long, help = "Show when each installed version was recorded")]
+        history: bool,
+        #[arg(
+            long,
+            help = "Show caveats with prefix placeholders replaced by real paths"
+        )]
+        eval_caveats: bool,
+        #[arg(long, help = "List installed formulas that depend on this one")]
+        used_by: bool,
+        #[arg(
+            long,
+            help = "List the platforms the formula publishes bottles for and mark this host's"
+        )]
+        bottle_tags: bool,
+    },
+    /// List the runtime dependencies of a formula
+    Deps {
+        #[arg(help = "Name of the formula")]
+        formula: String,
+        #[arg(long, help = "Show how each dependency is pulled in")]
+        tree: bool,
+        #[arg(long, help = "Only show dependencies that are installed")]
+        installed: bool,
+    },
+    /// List installed formulas that depend on a formula
+    Uses {
+        #[arg(help = "Name of the formula")]
+        formula: String,
+        #[arg(long, help = "Include formulas that depend on it indirectly")]
+        recursive: bool,
+    },
+    /// List installed formulas that were asked for by name and that nothing else depends on
+    Leaves,
+    /// Uninstall kegs that were only installed as dependencies and are no longer needed
+    Autoremove {
+        #[arg(long, help = "Show what would be removed without changing anything")]
+        dry_run: bool,
+    },
+    /// Write a software bill of materials for installed kegs
+    Sbom {
+        #[arg(required_unless_present = "all", num_args = 1..)]
+        formulas: Vec<String>,
+        #[arg(long, conflicts_with = "formulas", help = "Cover every installed keg")]
+        all: bool,
+        #[arg(long, value_enum, default_value = "spdx", help = "Document format")]
+        format: SbomFormat,
+        #[arg(long, short = 'o', help = "Write to this file instead of stdout")]
+        output: Option<PathBuf>,
+    },
+    /// Check installed kegs for known vulnerabilities using OSV.dev; exits
+    /// non-zero when any are critical
+    Audit {
+        #[arg(required = false, num_args = 0..)]
+        formulas: Vec<String>,
+        #[arg(long, help = "Upgrade affected kegs that have a newer version")]
+        fix: bool,
+    },
+    /// Show which installed formula links a command into the prefix
+    Which {
+        #[arg(help = "Name of the command, e.g. `jq`")]
+        command: String,
+    },
+    /// Show which installed formula owns a file in the prefix
+    Provides {
+        #[arg(help = "Path of the file, e.g. a link in the prefix's `lib`")]
+        path: PathBuf,
+    },
+    /// Print the formula file a source build would use
+    FormulaPath {
+        #[arg(help = "Name of the formula")]
+        formula: String,
+    },
+    /// Show the log of a formula's most recent source build
+    Log {
+        #[arg(help = "Name of the formula")]
         formula: String,
+        #[arg(long, help = "Print only the log file's path")]
+        path: bool,
+    },
+    /// Search formulas by name
+    Search {
+        #[arg(help = "Name or partial name to search for")]
+        query: String,
+        #[arg(long, help = "Only search installed formulas (works offline)")]
+        installed: bool,
+        #[arg(
+            long,
+            conflicts_with = "installed",
+            help = "Also match formula descriptions and print them"
+        )]
+        desc: bool,
     },
     /// Run diagnostics and optionally repair issues
     Doctor {
-        #[arg(long, help = "Automatically repair detected issues")]
+        #[arg(
+            long,
+            visible_alias = "fix",
+            help = "Automatically repair detected issues"
+        )]
         repair: bool,
     },
     /// Remove unreferenced store entries
-    Gc,
+    Gc {
+        #[arg(
+            long,
+            help = "Also remove superseded kegs, broken links and cached downloads"
+        )]
+        aggressive: bool,
+        #[arg(
+            long,
+            short = 'y',
+            requires = "aggressive",
+            help = "Skip the confirmation prompt"
+        )]
+        yes: bool,
+    },
+    /// Remove superseded kegs and old cached downloads
+    Cleanup {
+        #[arg(
+            long,
+            value_name = "DAYS",
+            default_value = "120",
+            value_parser = parse_prune_days,
+            help = "Remove downloads cached at least this many days ago, or `all`"
+        )]
+        prune: u64,
+        #[arg(long, help = "Show what would be removed without changing anything")]
+        dry_run: bool,
+    },
+    /// Inspect and prune the download cache
+    Cache {
+        #[command(subcommand)]
+        command: Option<CacheCommands>,
+    },
     /// Reset zerobrew data directories
     Reset {
         #[arg(long, short = 'y', help = "Skip confirmation prompts")]
@@ -159,7 +1605,10 @@ pub enum Commands {
     },
     /// Initialize zerobrew directories
     Init {
-        #[arg(long, help = "Do not modify shell configuration files")]
+        #[arg(
+            long,
+            help = "Do not modify shell configuration files; print the `zb shellenv` line to add instead"
+        )]
         no_modify_path: bool,
     },
     /// Generate shell completions
@@ -170,31 +1619,110 @@ pub enum Commands {
         )]
         shell: clap_complete::shells::Shell,
     },
-    /// Run an installed formula as a command
+    /// Print shell commands that put the prefix on PATH, MANPATH and INFOPATH
+    Shellenv {
+        #[arg(
+            value_enum,
+            help = "Shell to print commands for (defaults to the one in $SHELL)"
+        )]
+        shell: Option<ShellenvShell>,
+    },
+    /// Run a formula's executable, installing it unlinked first if needed
     Run {
         #[arg(help = "Name of the formula to run")]
         formula: String,
+        #[arg(
+            long,
+            help = "Mark a formula installed just for this run so `zb gc --aggressive` removes it"
+        )]
+        ephemeral: bool,
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
     },
+    /// Manage background services of installed formulas (launchd/systemd)
+    Services {
+        #[command(subcommand)]
+        command: Option<ServicesCommands>,
+    },
     /// Refresh cached formula metadata
     Update,
     /// List installed packages with newer versions available
-    Outdated {
-        #[arg(long, conflicts_with_all = ["quiet", "verbose"], help = "Output as JSON")]
-        json: bool,
+    Outdated,
+    /// Rerun the post_install step of installed formulas
+    Postinstall {
+        #[arg(required = true, num_args = 1..)]
+        formulas: Vec<String>,
+    },
+    /// Remove and reinstall packages at their installed version
+    Reinstall {
+        #[arg(required = true, num_args = 1..)]
+        formulas: Vec<String>,
+        #[arg(long, help = "Rebuild from source even when a bottle is available")]
+        from_source: bool,
+        #[arg(long, help = "Do not create symlinks after installation")]
+        no_link: bool,
     },
     /// Upgrade installed packages to the latest versions
     Upgrade {
         #[arg(required = false, num_args = 0..)]
         formulas: Vec<String>,
+        #[arg(
+            long,
+            conflicts_with = "formulas",
+            help = "Upgrade every outdated package, skipping pinned ones"
+        )]
+        all: bool,
         #[arg(long, short = 's', help = "Build from source instead of using bottles")]
         build_from_source: bool,
         #[arg(long, help = "Do not create symlinks after installation")]
         no_link: bool,
+        #[arg(
+            long,
+            help = "Stop at the first failure instead of upgrading the remaining packages"
+        )]
+        fail_fast: bool,
+        #[arg(
+            long,
+            help = "Keep the previous version in the Cellar instead of removing it"
+        )]
+        no_cleanup: bool,
     },
 }
 
+impl Commands {
+    /// Whether the command can change what is installed. Only these roll
+    /// back installs a crashed run left half-done before starting; queries
+    /// leave that to the next command that needs the install lock.
+    pub fn modifies_installs(&self) -> bool {
+        match self {
+            Commands::Verify { repair, .. } => *repair,
+            Commands::Doctor { repair } => *repair,
+            Commands::Audit { fix, .. } => *fix,
+            Commands::Autoremove { dry_run }
+            | Commands::Cleanup { dry_run, .. }
+            | Commands::Migrate { dry_run, .. } => !*dry_run,
+            Commands::Cache { command } => matches!(command, Some(CacheCommands::Clean { .. })),
+            Commands::List { .. }
+            | Commands::Caveats { .. }
+            | Commands::Info { .. }
+            | Commands::Deps { .. }
+            | Commands::Uses { .. }
+            | Commands::Leaves
+            | Commands::Sbom { .. }
+            | Commands::Which { .. }
+            | Commands::Provides { .. }
+            | Commands::FormulaPath { .. }
+            | Commands::Log { .. }
+            | Commands::Search { .. }
+            | Commands::Generations
+            | Commands::Du { .. }
+            | Commands::Update
+            | Commands::Outdated => false,
+            _ => true,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 pub enum BundleCommands {
     /// Install packages from a Brewfile
@@ -207,8 +1735,75 @@ pub enum BundleCommands {
             help = "Path to the Brewfile"
         )]
         file: PathBuf,
+        #[arg(
+            long,
+            conflicts_with = "file",
+            help = "Read the global Brewfile: $XDG_CONFIG_HOME/zerobrew/Brewfile if it exists, else ~/.Brewfile"
+        )]
+        global: bool,
         #[arg(long, help = "Do not create symlinks after installation")]
         no_link: bool,
+        #[arg(
+            long,
+            help = "Install exactly what the Brewfile's lockfile pins, failing on any difference"
+        )]
+        locked: bool,
+    },
+    /// Pin the Brewfile's formulas and their dependencies in a lockfile
+    Lock {
+        #[arg(
+            long,
+            short = 'f',
+            value_name = "FILE",
+            default_value = "Brewfile",
+            help = "Path to the Brewfile"
+        )]
+        file: PathBuf,
+        #[arg(
+            long,
+            conflicts_with = "file",
+            help = "Lock the global Brewfile: $XDG_CONFIG_HOME/zerobrew/Brewfile if it exists, else ~/.Brewfile"
+        )]
+        global: bool,
+    },
+    /// Fail, listing them, if any Brewfile entries are not installed
+    Check {
+        #[arg(
+            long,
+            short = 'f',
+            value_name = "FILE",
+            default_value = "Brewfile",
+            help = "Path to the Brewfile"
+        )]
+        file: PathBuf,
+        #[arg(
+            long,
+            conflicts_with = "file",
+            help = "Check the global Brewfile: $XDG_CONFIG_HOME/zerobrew/Brewfile if it exists, else ~/.Brewfile"
+        )]
+        global: bool,
+    },
+    /// Uninstall formulas and casks not listed in the Brewfile
+    Cleanup {
+        #[arg(
+            long,
+            short = 'f',
+            value_name = "FILE",
+            default_value = "Brewfile",
+            help = "Path to the Brewfile"
+        )]
+        file: PathBuf,
+        #[arg(
+            long,
+            conflicts_with = "file",
+            help = "Clean up against the global Brewfile: $XDG_CONFIG_HOME/zerobrew/Brewfile if it exists, else ~/.Brewfile"
+        )]
+        global: bool,
+        #[arg(
+            long,
+            help = "Actually uninstall; without it, only list what would be removed"
+        )]
+        force: bool,
     },
     /// Dump installed packages to a Brewfile
     Dump {
@@ -220,7 +1815,82 @@ pub enum BundleCommands {
             help = "Output file path"
         )]
         file: PathBuf,
+        #[arg(
+            long,
+            conflicts_with = "file",
+            help = "Write the global Brewfile: $XDG_CONFIG_HOME/zerobrew/Brewfile if it exists, else ~/.Brewfile"
+        )]
+        global: bool,
         #[arg(long, help = "Overwrite existing file")]
         force: bool,
+        #[arg(long, conflicts_with = "cask_only", help = "Only dump formulas")]
+        formula_only: bool,
+        #[arg(long, help = "Only dump casks")]
+        cask_only: bool,
+        #[arg(long, help = "Write each entry's description as a comment above it")]
+        describe: bool,
+        #[arg(
+            long,
+            help = "Leave out formulas that were only installed as dependencies"
+        )]
+        no_deps: bool,
+        #[arg(long, help = "Note installed versions and pins in comments")]
+        versions: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CacheCommands {
+    /// List cached bottles with their size, age and owning formulas
+    List,
+    /// Show how much space the cache takes up
+    Size,
+    /// Remove cached and partial downloads
+    Clean {
+        #[arg(
+            long,
+            value_name = "DAYS",
+            default_value = "all",
+            value_parser = parse_prune_days,
+            help = "Only remove downloads cached at least this many days ago"
+        )]
+        older_than: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ServicesCommands {
+    /// List installed formulas that define a service, and their state
+    List,
+    /// Start a formula's service now and at every login
+    Start {
+        #[arg(help = "Name of the formula")]
+        formula: String,
     },
+    /// Stop a formula's service and stop starting it at login
+    Stop {
+        #[arg(help = "Name of the formula")]
+        formula: String,
+    },
+    /// Stop and start a formula's service, reloading its definition
+    Restart {
+        #[arg(help = "Name of the formula")]
+        formula: String,
+    },
+}
+
+/// Document formats `zb sbom` can write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SbomFormat {
+    Spdx,
+    Cyclonedx,
+}
+
+/// Shells `zb shellenv` knows how to write for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ShellenvShell {
+    Bash,
+    Zsh,
+    Fish,
+    Nu,
 }
@@ -0,0 +1,153 @@
+use console::style;
+use zb_io::{AuditReport, OsvClient, Severity};
+
+use crate::ui::StdUi;
+use crate::utils::normalize_formula_name;
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    fix: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let names = formulas
+        .iter()
+        .map(|name| normalize_formula_name(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let osv = OsvClient::new();
+
+    ui.heading("Checking installed kegs against OSV...".to_string())
+        .map_err(ui_error)?;
+    let mut report = installer.audit(&osv, &names).await?;
+    print_report(&report, ui)?;
+
+    let upgradable: Vec<String> = report
+        .affected
+        .iter()
+        .filter(|keg| keg.upgrade_available.is_some())
+        .map(|keg| keg.name.clone())
+        .collect();
+    if !upgradable.is_empty() {
+        if fix {
+            super::upgrade::execute(installer, upgradable, false, false, false, true, false, ui)
+                .await?;
+            // The new versions may still be affected, so judge those.
+            report = installer.audit(&osv, &names).await?;
+            if !report.affected.is_empty() {
+                ui.heading("Still affected after upgrading:".to_string())
+                    .map_err(ui_error)?;
+                print_report(&report, ui)?;
+            }
+        } else {
+            ui.info(format!(
+                "Versions with fixes are available; run {} or {} to upgrade",
+                style(format!("zb upgrade {}", upgradable.join(" "))).cyan(),
+                style("zb audit --fix").cyan()
+            ))
+            .map_err(ui_error)?;
+        }
+    }
+
+    let critical = report
+        .affected
+        .iter()
+        .filter(|keg| keg.severity() == Severity::Critical)
+        .count();
+    if critical > 0 {
+        return Err(zb_core::Error::ExecutionError {
+            message: format!(
+                "{critical} installed {} critical vulnerabilities",
+                if critical == 1 {
+                    "keg has"
+                } else {
+                    "kegs have"
+                }
+            ),
+        });
+    }
+    Ok(())
+}
+
+fn print_report(report: &AuditReport, ui: &mut StdUi) -> Result<(), zb_core::Error> {
+    for keg in &report.affected {
+        ui.emit(&serde_json::json!({
+            "name": keg.name,
+            "version": keg.version,
+            "severity": keg.severity().as_str(),
+            "upgrade_available": keg.upgrade_available,
+            "vulnerabilities": keg.vulnerabilities.iter().map(|vuln| serde_json::json!({
+                "id": vuln.id,
+                "aliases": vuln.aliases,
+                "severity": vuln.severity.as_str(),
+                "score": vuln.score,
+                "summary": vuln.summary,
+            })).collect::<Vec<_>>(),
+        }))
+        .map_err(ui_error)?;
+
+        let upgrade = keg
+            .upgrade_available
+            .as_ref()
+            .map(|version| format!(" (upgrade to {version} available)"))
+            .unwrap_or_default();
+        ui.println(format!(
+            "{} {} {}{}",
+            severity_label(keg.severity()),
+            style(&keg.name).bold(),
+            style(&keg.version).dim(),
+            upgrade
+        ))
+        .map_err(ui_error)?;
+        for vuln in &keg.vulnerabilities {
+            let id = vuln
+                .aliases
+                .iter()
+                .find(|alias| alias.starts_with("CVE-"))
+                .unwrap_or(&vuln.id);
+            let score = vuln
+                .score
+                .map(|score| format!(" {score:.1}"))
+                .unwrap_or_default();
+            let summary = vuln
+                .summary
+                .as_deref()
+                .map(|summary| format!(": {summary}"))
+                .unwrap_or_default();
+            ui.bullet(format!("{id} [{}{score}]{summary}", vuln.severity))
+                .map_err(ui_error)?;
+        }
+    }
+
+    let summary = format!(
+        "{} of {} audited kegs affected",
+        report.affected.len(),
+        report.scanned
+    );
+    if report.affected.is_empty() {
+        ui.info(format!("No known vulnerabilities ({summary})."))
+            .map_err(ui_error)?;
+    } else {
+        ui.heading(summary).map_err(ui_error)?;
+    }
+    if !report.skipped.is_empty() {
+        ui.note(format!("Not audited: {}", report.skipped.join(", ")))
+            .map_err(ui_error)?;
+    }
+    Ok(())
+}
+
+fn severity_label(severity: Severity) -> String {
+    let label = format!("{:>8}", severity.as_str().to_uppercase());
+    match severity {
+        Severity::Critical => style(label).red().bold().to_string(),
+        Severity::High => style(label).red().to_string(),
+        Severity::Medium => style(label).yellow().to_string(),
+        Severity::Low | Severity::Unknown => style(label).dim().to_string(),
+    }
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
@@ -0,0 +1,46 @@
+use crate::ui::StdUi;
+use console::style;
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    dry_run: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    if dry_run {
+        let orphans = installer.orphaned_dependencies().await?;
+        if orphans.is_empty() {
+            ui.info("No orphaned dependencies.").map_err(ui_error)?;
+            return Ok(());
+        }
+        ui.heading("Would remove:").map_err(ui_error)?;
+        for name in orphans {
+            ui.bullet(name).map_err(ui_error)?;
+        }
+        return Ok(());
+    }
+
+    let removed = installer.autoremove().await?;
+    if removed.is_empty() {
+        ui.info("No orphaned dependencies.").map_err(ui_error)?;
+    } else {
+        ui.heading(format!(
+            "Removed {} orphaned {}: {}",
+            removed.len(),
+            if removed.len() == 1 {
+                "dependency"
+            } else {
+                "dependencies"
+            },
+            style(removed.join(", ")).bold()
+        ))
+        .map_err(ui_error)?;
+    }
+
+    Ok(())
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::StoreCorruption {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
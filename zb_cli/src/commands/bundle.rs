@@ -1,11 +1,15 @@
 use console::style;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::time::Instant;
+use tracing::warn;
+use zb_io::{InstallReason, ManifestFormula};
 
 use super::install;
 use crate::cli::BundleCommands;
 use crate::ui::StdUi;
+use crate::utils::{normalize_formula_name, suggest_missing_formula_matches};
 
 pub async fn execute(
     installer: &mut zb_io::Installer,
@@ -14,76 +18,753 @@ pub async fn execute(
 ) -> Result<(), zb_core::Error> {
     match command.unwrap_or(BundleCommands::Install {
         file: PathBuf::from("Brewfile"),
+        global: false,
         no_link: false,
+        locked: false,
     }) {
-        BundleCommands::Install { file, no_link } => {
-            install_from_file(installer, &file, no_link, ui).await
+        BundleCommands::Install {
+            file,
+            global,
+            no_link,
+            locked,
+        } => {
+            let file = if global {
+                global_brewfile_path()?
+            } else {
+                file
+            };
+            if locked {
+                install_locked(installer, &file, no_link, ui).await
+            } else {
+                install_from_file(installer, &file, no_link, ui).await
+            }
+        }
+        BundleCommands::Lock { file, global } => {
+            let file = if global {
+                global_brewfile_path()?
+            } else {
+                file
+            };
+            lock_file(installer, &file, ui).await
+        }
+        BundleCommands::Check { file, global } => {
+            let file = if global {
+                global_brewfile_path()?
+            } else {
+                file
+            };
+            check_file(installer, &file, ui)
+        }
+        BundleCommands::Cleanup {
+            file,
+            global,
+            force,
+        } => {
+            let file = if global {
+                global_brewfile_path()?
+            } else {
+                file
+            };
+            cleanup_file(installer, &file, force, ui).await
+        }
+        BundleCommands::Dump {
+            file,
+            global,
+            force,
+            formula_only,
+            cask_only,
+            describe,
+            no_deps,
+            versions,
+        } => {
+            let file = if global {
+                global_brewfile_path()?
+            } else {
+                file
+            };
+            let filter = if formula_only {
+                DumpFilter::FormulaOnly
+            } else if cask_only {
+                DumpFilter::CaskOnly
+            } else {
+                DumpFilter::All
+            };
+            let options = DumpOptions {
+                filter,
+                describe,
+                no_deps,
+                versions,
+            };
+            dump_to_file(installer, &file, force, options).await
         }
-        BundleCommands::Dump { file, force } => dump_to_file(installer, &file, force),
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DumpFilter {
+    All,
+    FormulaOnly,
+    CaskOnly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DumpOptions {
+    filter: DumpFilter,
+    describe: bool,
+    /// Leave out kegs installed only as dependencies.
+    no_deps: bool,
+    /// Note versions and pins in trailing comments.
+    versions: bool,
+}
+
+impl DumpOptions {
+    fn new(filter: DumpFilter) -> Self {
+        Self {
+            filter,
+            describe: false,
+            no_deps: false,
+            versions: false,
+        }
+    }
+}
+
+/// Install the whole Brewfile through one plan: the union of its formulas'
+/// dependency closures is resolved once, and every bottle in it goes
+/// through the same concurrent download and extraction pipeline, so a
+/// dependency shared by several entries is fetched and installed once.
 async fn install_from_file(
     installer: &mut zb_io::Installer,
     manifest_path: &Path,
     no_link: bool,
     ui: &mut StdUi,
 ) -> Result<(), zb_core::Error> {
-    let formulas = load_manifest(manifest_path)?;
-    println!(
-        "{} Installing {} formulas from {}...",
-        style("==>").cyan().bold(),
-        style(formulas.len()).green().bold(),
-        manifest_path.display()
-    );
+    let (apps, entries) = split_apps(load_manifest(manifest_path)?);
+    let (formulas, casks) = split_manifest(entries)?;
 
     let start = Instant::now();
-    for formula in formulas {
-        install::execute(installer, vec![formula], no_link, false, ui).await?;
+    let mut installed = 0usize;
+    if !formulas.is_empty() {
+        ui.heading(format!(
+            "Installing {} formulas from {}...",
+            style(formulas.len()).green().bold(),
+            manifest_path.display()
+        ))
+        .map_err(ui_error)?;
+        let plan = match installer.plan(&formulas).await {
+            Ok(plan) => plan,
+            Err(e) => {
+                let _ = suggest_missing_formula_matches(installer, &e).await;
+                return Err(e);
+            }
+        };
+        installed += install::execute_formula_plan(installer, &formulas, plan, no_link, ui).await?;
+    }
+    if !casks.is_empty() {
+        ui.heading(format!("Installing casks ({} packages)...", casks.len()))
+            .map_err(ui_error)?;
+        let casks: Vec<String> = casks
+            .into_iter()
+            .map(|token| format!("cask:{token}"))
+            .collect();
+        installed += installer.install_casks(&casks, !no_link).await?.installed;
     }
+    install_apps(&apps, &AppHelpers::default(), ui)?;
 
-    println!(
-        "{} Finished installing manifest in {:.2}s",
-        style("==>").cyan().bold(),
+    ui.heading(format!(
+        "Finished installing manifest ({} packages) in {:.2}s",
+        style(installed).green().bold(),
         start.elapsed().as_secs_f64()
-    );
+    ))
+    .map_err(ui_error)
+}
+
+/// A Brewfile entry installed by another tool rather than by zerobrew.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AppEntry {
+    /// `mas "Xcode", id: 497799835`: a Mac App Store app, through `mas`.
+    Mas { name: String, id: u64 },
+    /// `vscode "publisher.extension"`: a VS Code extension, through `code`.
+    Vscode { extension: String },
+}
+
+impl AppEntry {
+    /// The entry behind a `mas:<id>:<name>` or `vscode:<extension>` line
+    /// from [`load_manifest`].
+    fn parse(entry: &str) -> Option<Self> {
+        if let Some(rest) = entry.strip_prefix("mas:") {
+            let (id, name) = rest.split_once(':')?;
+            return Some(AppEntry::Mas {
+                name: name.to_string(),
+                id: id.parse().ok()?,
+            });
+        }
+        entry
+            .strip_prefix("vscode:")
+            .map(|extension| AppEntry::Vscode {
+                extension: extension.to_string(),
+            })
+    }
+
+    fn label(&self) -> String {
+        match self {
+            AppEntry::Mas { name, id } => format!("{name} ({id})"),
+            AppEntry::Vscode { extension } => extension.clone(),
+        }
+    }
+}
+
+/// The programs `mas` and `vscode` entries are installed with.
+#[derive(Debug, Clone)]
+struct AppHelpers {
+    mas: String,
+    code: String,
+}
+
+impl Default for AppHelpers {
+    fn default() -> Self {
+        Self {
+            mas: "mas".to_string(),
+            code: "code".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct AppSummary {
+    installed: Vec<String>,
+    already_installed: Vec<String>,
+    /// Entries skipped because the helper that installs them is missing,
+    /// by helper.
+    skipped: BTreeMap<String, Vec<String>>,
+    failed: Vec<String>,
+}
+
+/// Install `mas` and `vscode` entries that aren't installed yet. Entries
+/// whose helper isn't on PATH are skipped and listed in the summary; the
+/// bundle only fails if a helper that is there fails to install something.
+fn install_apps(
+    apps: &[AppEntry],
+    helpers: &AppHelpers,
+    ui: &mut StdUi,
+) -> Result<AppSummary, zb_core::Error> {
+    let mut summary = AppSummary::default();
+    if apps.is_empty() {
+        return Ok(summary);
+    }
+
+    let mas_apps: Vec<&AppEntry> = apps
+        .iter()
+        .filter(|app| matches!(app, AppEntry::Mas { .. }))
+        .collect();
+    let extensions: Vec<&AppEntry> = apps
+        .iter()
+        .filter(|app| matches!(app, AppEntry::Vscode { .. }))
+        .collect();
+
+    for (program, list_arg, entries) in [
+        (&helpers.mas, "list", mas_apps),
+        (&helpers.code, "--list-extensions", extensions),
+    ] {
+        if entries.is_empty() {
+            continue;
+        }
+        let Some(listing) = helper_output(program, list_arg) else {
+            summary
+                .skipped
+                .entry(program.clone())
+                .or_default()
+                .extend(entries.iter().map(|app| app.label()));
+            continue;
+        };
+
+        for app in entries {
+            if app_is_listed(app, &listing) {
+                summary.already_installed.push(app.label());
+                continue;
+            }
+            let args = match app {
+                AppEntry::Mas { id, .. } => vec!["install".to_string(), id.to_string()],
+                AppEntry::Vscode { extension } => {
+                    vec!["--install-extension".to_string(), extension.clone()]
+                }
+            };
+
+            ui.step_start(format!("Installing {}", app.label()))
+                .map_err(ui_error)?;
+            let status = Command::new(program)
+                .args(&args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .status();
+            if status.is_ok_and(|status| status.success()) {
+                ui.step_ok().map_err(ui_error)?;
+                summary.installed.push(app.label());
+            } else {
+                ui.step_fail().map_err(ui_error)?;
+                summary.failed.push(app.label());
+            }
+        }
+    }
+
+    print_app_summary(&summary, ui)?;
+    if !summary.failed.is_empty() {
+        return Err(zb_core::Error::ExecutionError {
+            message: format!("failed to install {}", summary.failed.join(", ")),
+        });
+    }
+    Ok(summary)
+}
+
+fn print_app_summary(summary: &AppSummary, ui: &mut StdUi) -> Result<(), zb_core::Error> {
+    if !summary.installed.is_empty() || !summary.already_installed.is_empty() {
+        ui.heading(format!(
+            "{} apps and extensions installed, {} already present",
+            style(summary.installed.len()).green().bold(),
+            summary.already_installed.len()
+        ))
+        .map_err(ui_error)?;
+    }
+    for (program, entries) in &summary.skipped {
+        ui.warn(format!(
+            "Skipped {} entries because `{program}` is not installed:",
+            entries.len()
+        ))
+        .map_err(ui_error)?;
+        for entry in entries {
+            ui.bullet(entry).map_err(ui_error)?;
+        }
+    }
     Ok(())
 }
 
-fn dump_to_file(
+/// `mas` and `vscode` entries that aren't installed, or can't be checked
+/// because their helper isn't on PATH.
+fn missing_apps(apps: &[AppEntry], helpers: &AppHelpers) -> Vec<String> {
+    let mut listings: HashMap<&str, Option<String>> = HashMap::new();
+    apps.iter()
+        .filter(|app| {
+            let (program, list_arg) = match app {
+                AppEntry::Mas { .. } => (helpers.mas.as_str(), "list"),
+                AppEntry::Vscode { .. } => (helpers.code.as_str(), "--list-extensions"),
+            };
+            let listing = listings
+                .entry(program)
+                .or_insert_with(|| helper_output(program, list_arg));
+            !listing
+                .as_deref()
+                .is_some_and(|listing| app_is_listed(app, listing))
+        })
+        .map(AppEntry::label)
+        .collect()
+}
+
+/// Whether `app` appears in its helper's listing of what is installed.
+fn app_is_listed(app: &AppEntry, listing: &str) -> bool {
+    match app {
+        AppEntry::Mas { id, .. } => parse_mas_list(listing).contains(id),
+        AppEntry::Vscode { extension } => listing
+            .lines()
+            .any(|line| line.trim().eq_ignore_ascii_case(extension)),
+    }
+}
+
+/// What `program arg` prints, or `None` if `program` can't be run. A
+/// listing that fails counts as empty, so installs are still attempted.
+fn helper_output(program: &str, arg: &str) -> Option<String> {
+    let output = Command::new(program)
+        .arg(arg)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        warn!(program, arg, "helper listing failed; treating it as empty");
+        return Some(String::new());
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// App ids from `mas list`, whose lines look like
+/// `497799835  Xcode  (15.0)`.
+fn parse_mas_list(listing: &str) -> HashSet<u64> {
+    listing
+        .lines()
+        .filter_map(|line| line.split_whitespace().next()?.parse().ok())
+        .collect()
+}
+
+/// Fail, listing them, if any of the Brewfile's entries aren't installed.
+fn check_file(
+    installer: &zb_io::Installer,
+    manifest_path: &Path,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let (apps, entries) = split_apps(load_manifest(manifest_path)?);
+    let listed = listed_kegs(entries)?;
+
+    let mut missing = installer.missing_kegs(&listed)?;
+    missing.extend(missing_apps(&apps, &AppHelpers::default()));
+    if missing.is_empty() {
+        return ui
+            .info(format!(
+                "The Brewfile's dependencies are satisfied ({} entries).",
+                listed.len() + apps.len()
+            ))
+            .map_err(ui_error);
+    }
+
+    ui.heading(format!(
+        "{} entries in {} are not installed:",
+        style(missing.len()).red().bold(),
+        manifest_path.display()
+    ))
+    .map_err(ui_error)?;
+    for entry in &missing {
+        ui.bullet(entry).map_err(ui_error)?;
+    }
+    Err(zb_core::Error::ExecutionError {
+        message: "the Brewfile's dependencies are not satisfied; run `zb bundle install`"
+            .to_string(),
+    })
+}
+
+/// Uninstall formulas and casks that the Brewfile doesn't list and nothing
+/// it lists depends on. Without `force`, only list them.
+async fn cleanup_file(
     installer: &mut zb_io::Installer,
-    file_path: &Path,
+    manifest_path: &Path,
     force: bool,
+    ui: &mut StdUi,
 ) -> Result<(), zb_core::Error> {
-    if file_path.exists() && !force {
+    let (_, entries) = split_apps(load_manifest(manifest_path)?);
+    let listed = listed_kegs(entries)?;
+
+    let unlisted = installer.unlisted_kegs(&listed).await?;
+    if unlisted.is_empty() {
+        return ui
+            .info(format!(
+                "Nothing to clean up; everything installed is in {}.",
+                manifest_path.display()
+            ))
+            .map_err(ui_error);
+    }
+
+    if !force {
+        ui.heading(format!(
+            "Would uninstall {} kegs not in {}:",
+            style(unlisted.len()).yellow().bold(),
+            manifest_path.display()
+        ))
+        .map_err(ui_error)?;
+        for name in &unlisted {
+            ui.bullet(name).map_err(ui_error)?;
+        }
+        return ui
+            .info(format!(
+                "Run {} to uninstall them",
+                style("zb bundle cleanup --force").cyan()
+            ))
+            .map_err(ui_error);
+    }
+
+    super::uninstall::execute(installer, unlisted, false, ui)
+}
+
+/// The Brewfile's formulas, normalized, and its casks as `cask:<token>`,
+/// the way they are recorded once installed.
+fn listed_kegs(entries: Vec<String>) -> Result<Vec<String>, zb_core::Error> {
+    let (mut listed, casks) = split_manifest(entries)?;
+    listed.extend(casks.into_iter().map(|token| format!("cask:{token}")));
+    Ok(listed)
+}
+
+/// Resolve the Brewfile's formulas and write their closure, with exact
+/// versions and bottle checksums, to the lockfile beside it.
+async fn lock_file(
+    installer: &mut zb_io::Installer,
+    manifest_path: &Path,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let (apps, entries) = split_apps(load_manifest(manifest_path)?);
+    let (formulas, casks) = split_manifest(entries)?;
+    if !casks.is_empty() {
+        ui.warn(format!("Casks are not locked: {}", casks.join(", ")))
+            .map_err(ui_error)?;
+    }
+    if !apps.is_empty() {
+        let labels: Vec<String> = apps.iter().map(AppEntry::label).collect();
+        ui.warn(format!(
+            "App Store apps and VS Code extensions are not locked: {}",
+            labels.join(", ")
+        ))
+        .map_err(ui_error)?;
+    }
+    if formulas.is_empty() {
+        return Err(zb_core::Error::InvalidArgument {
+            message: format!("{} has no formulas to lock", manifest_path.display()),
+        });
+    }
+
+    let plan = installer.plan(&formulas).await?;
+    let lockfile = zb_io::Lockfile::from_plan(&plan);
+    let lock_path = lockfile_path(manifest_path);
+    lockfile.save(&lock_path)?;
+
+    ui.heading(format!(
+        "Locked {} formulas to {}",
+        style(lockfile.formulas.len()).green().bold(),
+        lock_path.display()
+    ))
+    .map_err(ui_error)
+}
+
+/// Install the Brewfile from its lockfile, refusing any formula, version or
+/// bottle the lockfile does not pin.
+async fn install_locked(
+    installer: &mut zb_io::Installer,
+    manifest_path: &Path,
+    no_link: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let (apps, entries) = split_apps(load_manifest(manifest_path)?);
+    let (formulas, casks) = split_manifest(entries)?;
+    if !casks.is_empty() {
+        return Err(zb_core::Error::InvalidArgument {
+            message: format!(
+                "casks cannot be installed with --locked: {}",
+                casks.join(", ")
+            ),
+        });
+    }
+
+    let lock_path = lockfile_path(manifest_path);
+    if !lock_path.exists() {
         return Err(zb_core::Error::FileError {
             message: format!(
-                "file {} already exists (use --force to overwrite)",
-                file_path.display()
+                "no lockfile at {}; run `zb bundle lock` first",
+                lock_path.display()
+            ),
+        });
+    }
+    let lockfile = zb_io::Lockfile::load(&lock_path)?;
+    let unlocked: Vec<&str> = formulas
+        .iter()
+        .filter(|name| !lockfile.formulas.iter().any(|f| &f.name == *name))
+        .map(String::as_str)
+        .collect();
+    if !unlocked.is_empty() {
+        return Err(zb_core::Error::ExecutionError {
+            message: format!(
+                "{} is not in {}; run `zb bundle lock` to update it",
+                unlocked.join(", "),
+                lock_path.display()
             ),
         });
     }
 
-    let installed = installer.list_installed()?;
-    let mut content = String::new();
-    for keg in &installed {
-        content.push_str(&format!("brew \"{}\"\n", keg.name));
+    let start = Instant::now();
+    ui.heading(format!(
+        "Installing {} formulas from {}...",
+        style(lockfile.formulas.len()).green().bold(),
+        lock_path.display()
+    ))
+    .map_err(ui_error)?;
+
+    let (plan, _) = installer.plan_from_lockfile(&lockfile, false, true).await?;
+    install::execute_formula_plan(installer, &formulas, plan, no_link, ui).await?;
+    install_apps(&apps, &AppHelpers::default(), ui)?;
+
+    ui.heading(format!(
+        "Finished installing lockfile in {:.2}s",
+        start.elapsed().as_secs_f64()
+    ))
+    .map_err(ui_error)
+}
+
+/// `Brewfile` locks to `Brewfile.lock.json` in the same directory.
+fn lockfile_path(manifest_path: &Path) -> PathBuf {
+    let mut name = manifest_path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_else(|| "Brewfile".into());
+    name.push(".lock.json");
+    manifest_path.with_file_name(name)
+}
+
+/// Take the `mas` and `vscode` entries out of `entries`, leaving the
+/// formulas and casks zerobrew installs itself.
+fn split_apps(entries: Vec<String>) -> (Vec<AppEntry>, Vec<String>) {
+    let mut apps = Vec::new();
+    let mut rest = Vec::new();
+    for entry in entries {
+        match AppEntry::parse(&entry) {
+            Some(app) => apps.push(app),
+            None => rest.push(entry),
+        }
     }
+    (apps, rest)
+}
 
-    std::fs::write(file_path, content).map_err(|e| zb_core::Error::FileError {
-        message: format!("failed to write {}: {}", file_path.display(), e),
+/// Separate Brewfile entries into normalized formula names and cask tokens,
+/// dropping entries that name the same formula or cask twice.
+fn split_manifest(entries: Vec<String>) -> Result<(Vec<String>, Vec<String>), zb_core::Error> {
+    let mut formulas = Vec::new();
+    let mut casks = Vec::new();
+    for entry in entries {
+        let name = normalize_formula_name(&entry)?;
+        match name.strip_prefix("cask:") {
+            Some(token) => casks.push(token.to_string()),
+            None => formulas.push(name),
+        }
+    }
+    let mut seen = HashSet::new();
+    formulas.retain(|name| seen.insert(name.clone()));
+    seen.clear();
+    casks.retain(|token| seen.insert(token.clone()));
+    Ok((formulas, casks))
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
+
+/// The Brewfile used by `--global`: `$XDG_CONFIG_HOME/zerobrew/Brewfile` when
+/// it already exists, otherwise `~/.Brewfile` like `brew bundle --global`.
+fn global_brewfile_path() -> Result<PathBuf, zb_core::Error> {
+    let home = std::env::var_os("HOME").ok_or_else(|| zb_core::Error::FileError {
+        message: "HOME is not set; cannot locate the global Brewfile".to_string(),
     })?;
+    let xdg_config_home = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty());
+
+    Ok(global_brewfile_path_in(
+        Path::new(&home),
+        xdg_config_home.as_deref().map(Path::new),
+    ))
+}
+
+fn global_brewfile_path_in(home: &Path, xdg_config_home: Option<&Path>) -> PathBuf {
+    if let Some(config_dir) = xdg_config_home {
+        let candidate = config_dir.join("zerobrew").join("Brewfile");
+        if candidate.exists() {
+            return candidate;
+        }
+    }
+
+    home.join(".Brewfile")
+}
+
+async fn dump_to_file(
+    installer: &mut zb_io::Installer,
+    file_path: &Path,
+    force: bool,
+    options: DumpOptions,
+) -> Result<(), zb_core::Error> {
+    let manifest = installer.export_manifest()?;
+    let descriptions = if options.describe {
+        installer.descriptions(&manifest.names()).await
+    } else {
+        HashMap::new()
+    };
+    let (content, count) = render_brewfile(&manifest.formulas, options, &descriptions);
+
+    write_brewfile(file_path, &content, force)?;
 
     println!(
         "{} Dumped {} packages to {}",
         style("==>").cyan().bold(),
-        style(installed.len()).green().bold(),
+        style(count).green().bold(),
         file_path.display()
     );
 
     Ok(())
 }
 
+fn write_brewfile(file_path: &Path, content: &str, force: bool) -> Result<(), zb_core::Error> {
+    if file_path.exists() && !force {
+        return Err(zb_core::Error::FileError {
+            message: format!(
+                "file {} already exists (use --force to overwrite)",
+                file_path.display()
+            ),
+        });
+    }
+
+    std::fs::write(file_path, content).map_err(|e| zb_core::Error::FileError {
+        message: format!("failed to write {}: {}", file_path.display(), e),
+    })
+}
+
+/// Render installed kegs as Brewfile lines in the order `brew bundle dump`
+/// uses: `tap` lines for the taps they come from, then `brew` entries, then
+/// `cask` entries, each sorted and de-duplicated. Kegs installed only as
+/// dependencies are marked so in a comment. Returns the content and the
+/// number of `brew` and `cask` entries.
+fn render_brewfile(
+    entries: &[ManifestFormula],
+    options: DumpOptions,
+    descriptions: &HashMap<String, String>,
+) -> (String, usize) {
+    let mut formulas = BTreeMap::new();
+    let mut casks = BTreeMap::new();
+    for entry in entries {
+        if options.no_deps && entry.reason != InstallReason::Installed {
+            continue;
+        }
+        match entry.name.strip_prefix("cask:") {
+            Some(token) if options.filter != DumpFilter::FormulaOnly => {
+                casks.insert(token, entry);
+            }
+            None if options.filter != DumpFilter::CaskOnly => {
+                formulas.insert(entry.name.as_str(), entry);
+            }
+            _ => {}
+        }
+    }
+
+    let mut content = String::new();
+    let taps: BTreeSet<&str> = formulas
+        .values()
+        .chain(casks.values())
+        .filter_map(|entry| entry.tap())
+        .collect();
+    for tap in taps {
+        content.push_str(&format!("tap \"{tap}\"\n"));
+    }
+
+    for (directive, kegs) in [("brew", &formulas), ("cask", &casks)] {
+        for (name, entry) in kegs {
+            if let Some(desc) = descriptions.get(&entry.name) {
+                content.push_str(&format!("# {desc}\n"));
+            }
+            content.push_str(&format!("{directive} \"{name}\""));
+            if directive == "brew" && zb_core::is_head_version(&entry.version) {
+                content.push_str(", args: [\"HEAD\"]");
+            }
+
+            let mut notes = Vec::new();
+            if options.versions {
+                notes.push(entry.version.clone());
+                if entry.pinned {
+                    notes.push("pinned".to_string());
+                }
+            }
+            match entry.reason {
+                InstallReason::Installed => {}
+                InstallReason::Dependency => notes.push("dependency".to_string()),
+                InstallReason::BuildDependency => notes.push("build dependency".to_string()),
+            }
+            if !notes.is_empty() {
+                content.push_str(&format!(" # {}", notes.join(", ")));
+            }
+            content.push('\n');
+        }
+    }
+
+    (content, formulas.len() + casks.len())
+}
+
 fn load_manifest(path: &Path) -> Result<Vec<String>, zb_core::Error> {
     let contents = std::fs::read_to_string(path).map_err(|e| zb_core::Error::FileError {
         message: format!("failed to read manifest {}: {}", path.display(), e),
@@ -124,6 +805,18 @@ fn parse_brewfile_entry(line: &str) -> Option<String> {
         return Some(format!("cask:{token}"));
     }
 
+    if let Some(name) = parse_quoted_directive(line, "mas") {
+        let Some(id) = parse_mas_id(line) else {
+            warn!(entry = line, "mas entry has no numeric `id:`; skipping it");
+            return None;
+        };
+        return Some(format!("mas:{id}:{name}"));
+    }
+
+    if let Some(extension) = parse_quoted_directive(line, "vscode") {
+        return Some(format!("vscode:{extension}"));
+    }
+
     if let Some(formula) = parse_quoted_directive(line, "brew") {
         return Some(formula.to_string());
     }
@@ -131,6 +824,17 @@ fn parse_brewfile_entry(line: &str) -> Option<String> {
     Some(line.to_string())
 }
 
+/// The `id:` of a `mas "Name", id: 123` line.
+fn parse_mas_id(line: &str) -> Option<u64> {
+    let (_, rest) = line.rsplit_once("id:")?;
+    let digits: String = rest
+        .trim_start()
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
 fn parse_quoted_directive<'a>(line: &'a str, directive: &str) -> Option<&'a str> {
     if !line.starts_with(directive) {
         return None;
@@ -152,6 +856,96 @@ mod tests {
     use super::*;
     use std::io::Write;
 
+    fn entries(names: &[&str]) -> Vec<ManifestFormula> {
+        names
+            .iter()
+            .map(|name| ManifestFormula {
+                name: name.to_string(),
+                version: "1.0".to_string(),
+                reason: InstallReason::Installed,
+                pinned: false,
+                linked: true,
+            })
+            .collect()
+    }
+
+    fn render(names: &[&str], filter: DumpFilter) -> (String, usize) {
+        render_brewfile(&entries(names), DumpOptions::new(filter), &HashMap::new())
+    }
+
+    #[test]
+    fn global_dump_round_trips_through_default_path() {
+        let home = tempfile::tempdir().unwrap();
+        let path = global_brewfile_path_in(home.path(), None);
+        assert_eq!(path, home.path().join(".Brewfile"));
+
+        let (content, _) = render(&["wget", "cask:firefox", "jq"], DumpFilter::All);
+        write_brewfile(&path, &content, false).unwrap();
+
+        let reread = global_brewfile_path_in(home.path(), None);
+        assert_eq!(
+            load_manifest(&reread).unwrap(),
+            vec!["jq", "wget", "cask:firefox"]
+        );
+    }
+
+    #[test]
+    fn global_dump_requires_force_to_overwrite() {
+        let home = tempfile::tempdir().unwrap();
+        let path = global_brewfile_path_in(home.path(), None);
+        write_brewfile(&path, "brew \"jq\"\n", false).unwrap();
+
+        let err = write_brewfile(&path, "brew \"wget\"\n", false).unwrap_err();
+        assert!(err.to_string().contains("--force"));
+
+        write_brewfile(&path, "brew \"wget\"\n", true).unwrap();
+        assert_eq!(load_manifest(&path).unwrap(), vec!["wget"]);
+    }
+
+    #[test]
+    fn lockfile_sits_beside_the_brewfile() {
+        assert_eq!(
+            lockfile_path(Path::new("Brewfile")),
+            PathBuf::from("Brewfile.lock.json")
+        );
+        assert_eq!(
+            lockfile_path(Path::new("/home/me/.Brewfile")),
+            PathBuf::from("/home/me/.Brewfile.lock.json")
+        );
+    }
+
+    #[test]
+    fn split_manifest_separates_casks() {
+        let (formulas, casks) = split_manifest(vec![
+            "jq".to_string(),
+            "cask:firefox".to_string(),
+            "wget".to_string(),
+            "homebrew/core/jq".to_string(),
+            "homebrew/cask/firefox".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(formulas, vec!["jq", "wget"]);
+        assert_eq!(casks, vec!["firefox"]);
+    }
+
+    #[test]
+    fn global_path_prefers_existing_xdg_brewfile() {
+        let home = tempfile::tempdir().unwrap();
+        let config = home.path().join(".config");
+
+        assert_eq!(
+            global_brewfile_path_in(home.path(), Some(&config)),
+            home.path().join(".Brewfile")
+        );
+
+        std::fs::create_dir_all(config.join("zerobrew")).unwrap();
+        std::fs::write(config.join("zerobrew/Brewfile"), "jq\n").unwrap();
+        assert_eq!(
+            global_brewfile_path_in(home.path(), Some(&config)),
+            config.join("zerobrew/Brewfile")
+        );
+    }
+
     #[test]
     fn load_manifest_parses_entries_ignoring_whitespace_and_comments() {
         let mut file = tempfile::NamedTempFile::new().unwrap();
@@ -219,6 +1013,84 @@ mod tests {
         assert_eq!(entries, vec!["wget", "cask:docker-desktop"]);
     }
 
+    #[test]
+    fn render_brewfile_sorts_dedupes_and_separates_casks() {
+        let names = ["wget", "cask:docker", "jq", "jq"];
+
+        let (content, count) = render(&names, DumpFilter::All);
+
+        assert_eq!(content, "brew \"jq\"\nbrew \"wget\"\ncask \"docker\"\n");
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn render_brewfile_formula_only_skips_casks() {
+        let names = ["cask:docker", "jq", "cask:iterm2", "wget"];
+
+        let (content, count) = render(&names, DumpFilter::FormulaOnly);
+
+        assert!(content.lines().all(|line| line.starts_with("brew ")));
+        assert_eq!(content, "brew \"jq\"\nbrew \"wget\"\n");
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn render_brewfile_cask_only_skips_formulas() {
+        let names = ["cask:docker", "jq"];
+
+        let (content, count) = render(&names, DumpFilter::CaskOnly);
+
+        assert_eq!(content, "cask \"docker\"\n");
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn render_brewfile_records_taps_dependencies_versions_and_descriptions() {
+        let mut kegs = entries(&["hashicorp/tap/terraform", "jq", "oniguruma", "cask:firefox"]);
+        kegs[1].pinned = true;
+        kegs[1].version = "1.7.1".to_string();
+        kegs[2].reason = InstallReason::Dependency;
+        kegs[2].version = "HEAD-abc1234".to_string();
+        let descriptions = HashMap::from([(
+            "jq".to_string(),
+            "Lightweight and flexible command-line JSON processor".to_string(),
+        )]);
+
+        let (content, count) =
+            render_brewfile(&kegs, DumpOptions::new(DumpFilter::All), &descriptions);
+        assert_eq!(
+            content,
+            "tap \"hashicorp/tap\"\n\
+             brew \"hashicorp/tap/terraform\"\n\
+             # Lightweight and flexible command-line JSON processor\n\
+             brew \"jq\"\n\
+             brew \"oniguruma\", args: [\"HEAD\"] # dependency\n\
+             cask \"firefox\"\n"
+        );
+        assert_eq!(count, 4);
+
+        let options = DumpOptions {
+            no_deps: true,
+            versions: true,
+            ..DumpOptions::new(DumpFilter::FormulaOnly)
+        };
+        let (content, count) = render_brewfile(&kegs, options, &HashMap::new());
+        assert_eq!(
+            content,
+            "tap \"hashicorp/tap\"\n\
+             brew \"hashicorp/tap/terraform\" # 1.0\n\
+             brew \"jq\" # 1.7.1, pinned\n"
+        );
+        assert_eq!(count, 2);
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), &content).unwrap();
+        assert_eq!(
+            load_manifest(file.path()).unwrap(),
+            vec!["hashicorp/tap/terraform", "jq"]
+        );
+    }
+
     #[test]
     fn parse_brewfile_entry_handles_brew_directive() {
         assert_eq!(parse_brewfile_entry("brew \"jq\""), Some("jq".to_string()));
@@ -236,6 +1108,89 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_brewfile_entry_handles_mas_and_vscode_directives() {
+        assert_eq!(
+            parse_brewfile_entry("mas \"Xcode\", id: 497799835"),
+            Some("mas:497799835:Xcode".to_string())
+        );
+        assert_eq!(parse_brewfile_entry("mas \"Xcode\""), None);
+        assert_eq!(
+            parse_brewfile_entry("vscode \"rust-lang.rust-analyzer\""),
+            Some("vscode:rust-lang.rust-analyzer".to_string())
+        );
+
+        let (apps, rest) = split_apps(vec![
+            "jq".to_string(),
+            "mas:497799835:Xcode".to_string(),
+            "vscode:rust-lang.rust-analyzer".to_string(),
+        ]);
+        assert_eq!(rest, vec!["jq"]);
+        assert_eq!(
+            apps,
+            vec![
+                AppEntry::Mas {
+                    name: "Xcode".to_string(),
+                    id: 497799835
+                },
+                AppEntry::Vscode {
+                    extension: "rust-lang.rust-analyzer".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn apps_are_skipped_when_their_helper_is_missing() {
+        let (apps, _) = split_apps(vec![
+            "mas:497799835:Xcode".to_string(),
+            "vscode:rust-lang.rust-analyzer".to_string(),
+        ]);
+        let helpers = AppHelpers {
+            mas: "/nonexistent/mas".to_string(),
+            code: "/nonexistent/code".to_string(),
+        };
+        let mut ui = StdUi::new();
+
+        let summary = install_apps(&apps, &helpers, &mut ui).unwrap();
+        assert!(summary.installed.is_empty() && summary.failed.is_empty());
+        assert_eq!(
+            summary.skipped["/nonexistent/mas"],
+            vec!["Xcode (497799835)"]
+        );
+        assert_eq!(
+            summary.skipped["/nonexistent/code"],
+            vec!["rust-lang.rust-analyzer"]
+        );
+        assert_eq!(
+            missing_apps(&apps, &helpers),
+            vec!["Xcode (497799835)", "rust-lang.rust-analyzer"]
+        );
+    }
+
+    #[test]
+    fn listed_apps_are_recognised_in_helper_listings() {
+        let (apps, _) = split_apps(vec![
+            "mas:497799835:Xcode".to_string(),
+            "vscode:rust-lang.rust-analyzer".to_string(),
+        ]);
+        assert!(app_is_listed(&apps[0], "497799835  Xcode  (15.0)\n"));
+        assert!(!app_is_listed(&apps[0], "409183694 Keynote (13.1)\n"));
+        assert!(app_is_listed(&apps[1], "Rust-Lang.rust-analyzer\n"));
+    }
+
+    #[test]
+    fn listed_kegs_prefix_casks() {
+        let listed = listed_kegs(vec!["jq".to_string(), "cask:firefox".to_string()]).unwrap();
+        assert_eq!(listed, vec!["jq", "cask:firefox"]);
+    }
+
+    #[test]
+    fn parses_mas_list_ids() {
+        let ids = parse_mas_list("497799835  Xcode  (15.0)\n  409183694 Keynote (13.1)\n\n");
+        assert_eq!(ids, HashSet::from([497799835, 409183694]));
+    }
+
     #[test]
     fn parse_brewfile_entry_skips_tap_directive() {
         assert_eq!(parse_brewfile_entry("tap \"homebrew/core\""), None);
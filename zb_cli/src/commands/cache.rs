@@ -0,0 +1,104 @@
+use std::time::{Duration, SystemTime};
+
+use console::style;
+use indicatif::HumanBytes;
+
+use crate::cli::CacheCommands;
+use crate::ui::StdUi;
+
+pub fn execute(
+    installer: &mut zb_io::Installer,
+    command: Option<CacheCommands>,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    match command.unwrap_or(CacheCommands::List) {
+        CacheCommands::List => list(installer, ui),
+        CacheCommands::Size => {
+            let size = installer.cache_size()?;
+            ui.emit(&serde_json::json!({
+                "path": installer.cache_dir(),
+                "size_bytes": size,
+            }))
+            .map_err(ui_error)?;
+            ui.println(format!(
+                "{}  {}",
+                HumanBytes(size),
+                style(installer.cache_dir().display()).dim()
+            ))
+            .map_err(ui_error)
+        }
+        CacheCommands::Clean { older_than } => {
+            let clean = installer
+                .clean_cache(Duration::from_secs(older_than.saturating_mul(24 * 60 * 60)))?;
+            ui.heading(format!(
+                "Removed {} cached downloads and {} partial downloads, freeing {}",
+                clean.removed_downloads,
+                clean.removed_partials,
+                style(HumanBytes(clean.freed_bytes)).green().bold()
+            ))
+            .map_err(ui_error)?;
+            ui.emit(&serde_json::json!({
+                "removed_downloads": clean.removed_downloads,
+                "removed_partials": clean.removed_partials,
+                "freed_bytes": clean.freed_bytes,
+            }))
+            .map_err(ui_error)
+        }
+    }
+}
+
+fn list(installer: &zb_io::Installer, ui: &mut StdUi) -> Result<(), zb_core::Error> {
+    let downloads = installer.cache_entries()?;
+
+    if downloads.is_empty() {
+        ui.info(format!(
+            "No cached downloads in {}.",
+            installer.cache_dir().display()
+        ))
+        .map_err(ui_error)?;
+        return Ok(());
+    }
+
+    let now = SystemTime::now();
+    for download in &downloads {
+        let age_days = download
+            .modified
+            .and_then(|modified| now.duration_since(modified).ok())
+            .map(|age| age.as_secs() / (24 * 60 * 60));
+        ui.emit(&serde_json::json!({
+            "sha256": download.sha256,
+            "size_bytes": download.size_bytes,
+            "age_days": age_days,
+            "formulas": download.formulas,
+        }))
+        .map_err(ui_error)?;
+
+        let owners = if download.formulas.is_empty() {
+            style("(not installed)".to_string()).dim()
+        } else {
+            style(download.formulas.join(", ")).bold()
+        };
+        let age = age_days.map_or_else(|| "?".to_string(), |days| format!("{days}d"));
+        ui.println(format!(
+            "{:>10}  {}  {}  {}",
+            HumanBytes(download.size_bytes).to_string(),
+            &download.sha256[..download.sha256.len().min(12)],
+            style(format!("{age:>5}")).dim(),
+            owners
+        ))
+        .map_err(ui_error)?;
+    }
+
+    ui.println(format!(
+        "{:>10}  {}",
+        HumanBytes(downloads.iter().map(|d| d.size_bytes).sum()).to_string(),
+        style("total").dim()
+    ))
+    .map_err(ui_error)
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
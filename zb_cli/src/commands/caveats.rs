@@ -0,0 +1,27 @@
+use crate::commands::install::print_caveats;
+use crate::ui::StdUi;
+use crate::utils::normalize_formula_name;
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    for formula in formulas {
+        let name = normalize_formula_name(&formula)?;
+        match installer.caveats(&name).await? {
+            Some(text) => print_caveats(&[zb_io::FormulaCaveats { name, text }], ui)?,
+            None => ui
+                .info(format!("{name} has no caveats"))
+                .map_err(ui_error)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use console::style;
+use indicatif::HumanBytes;
+
+use crate::ui::StdUi;
+
+pub fn execute(
+    installer: &mut zb_io::Installer,
+    prune_days: u64,
+    dry_run: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let plan =
+        installer.plan_cleanup(Duration::from_secs(prune_days.saturating_mul(24 * 60 * 60)))?;
+    if plan.is_empty() {
+        ui.info("Nothing to clean up.").map_err(ui_error)?;
+        return ui.emit(&report(dry_run, 0, 0, 0)).map_err(ui_error);
+    }
+
+    if dry_run {
+        ui.heading("Would remove:").map_err(ui_error)?;
+        for keg in &plan.kegs {
+            ui.bullet(format!(
+                "{} {} ({})",
+                style(&keg.name).bold(),
+                keg.version,
+                HumanBytes(keg.size_bytes)
+            ))
+            .map_err(ui_error)?;
+        }
+        if !plan.cached_downloads.is_empty() {
+            ui.bullet(format!(
+                "{} cached downloads ({})",
+                plan.cached_downloads.len(),
+                HumanBytes(plan.cached_downloads.iter().map(|e| e.size_bytes).sum())
+            ))
+            .map_err(ui_error)?;
+        }
+        ui.heading(format!(
+            "Would free {}",
+            style(HumanBytes(plan.reclaimable_bytes())).green().bold()
+        ))
+        .map_err(ui_error)?;
+        return ui
+            .emit(&report(
+                true,
+                plan.kegs.len(),
+                plan.cached_downloads.len(),
+                plan.reclaimable_bytes(),
+            ))
+            .map_err(ui_error);
+    }
+
+    let summary = installer.gc_aggressive(plan)?;
+    ui.heading(format!(
+        "Removed {} kegs and {} cached downloads, freeing {}",
+        summary.removed_kegs,
+        summary.removed_cached_downloads,
+        style(HumanBytes(summary.freed_bytes)).green().bold()
+    ))
+    .map_err(ui_error)?;
+
+    ui.emit(&report(
+        false,
+        summary.removed_kegs,
+        summary.removed_cached_downloads,
+        summary.freed_bytes,
+    ))
+    .map_err(ui_error)
+}
+
+fn report(dry_run: bool, kegs: usize, downloads: usize, bytes: u64) -> serde_json::Value {
+    serde_json::json!({
+        "dry_run": dry_run,
+        "removed_kegs": kegs,
+        "removed_cached_downloads": downloads,
+        "freed_bytes": bytes,
+    })
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::StoreCorruption {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
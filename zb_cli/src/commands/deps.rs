@@ -0,0 +1,48 @@
+use console::style;
+
+use crate::utils::normalize_formula_name;
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    formula: String,
+    tree: bool,
+    installed: bool,
+) -> Result<(), zb_core::Error> {
+    let name = normalize_formula_name(&formula)?;
+
+    if tree {
+        let tree = installer.dependency_tree(&name).await?;
+        println!("{}", style(&tree.name).bold());
+        print_children(&tree, installed, "");
+        return Ok(());
+    }
+
+    for dep in installer.dependencies(&name).await? {
+        if installed && !installer.is_installed(&dep) {
+            continue;
+        }
+        println!("{dep}");
+    }
+
+    Ok(())
+}
+
+fn print_children(node: &zb_io::DependencyTree, installed_only: bool, indent: &str) {
+    let children: Vec<_> = node
+        .dependencies
+        .iter()
+        .filter(|child| !installed_only || child.installed)
+        .collect();
+
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let branch = if last { "└── " } else { "├── " };
+        if child.installed {
+            println!("{indent}{branch}{} {}", child.name, style("✓").green());
+        } else {
+            println!("{indent}{branch}{}", child.name);
+        }
+        let next = format!("{indent}{}", if last { "    " } else { "│   " });
+        print_children(child, installed_only, &next);
+    }
+}
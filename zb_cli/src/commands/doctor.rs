@@ -1,5 +1,10 @@
+use std::path::PathBuf;
+
 use console::style;
 
+use crate::init::{
+    ShellConfigDuplicates, consolidate_path_entries, find_duplicate_path_entries, shell_config_path,
+};
 use crate::ui::StdUi;
 
 pub fn execute(
@@ -10,8 +15,9 @@ pub fn execute(
     ui.heading("Running diagnostics...").map_err(ui_error)?;
 
     let report = installer.doctor()?;
+    let shell_config = scan_shell_config();
 
-    if report.is_healthy() {
+    if report.is_healthy() && shell_config.is_none() {
         ui.println(format!("    {} No issues found", style("✓").green()))
             .map_err(ui_error)?;
         return Ok(());
@@ -73,12 +79,74 @@ pub fn execute(
         .map_err(ui_error)?;
     }
 
+    for stale in &report.stale_opt_links {
+        let detail = match &stale.current_target {
+            Some(target) => format!("points at {}", target.display()),
+            None => "missing".to_string(),
+        };
+        ui.warn(format!(
+            "Stale opt link: {} ({}, expected {})",
+            stale.link_path.display(),
+            detail,
+            stale.version
+        ))
+        .map_err(ui_error)?;
+    }
+
+    for keg in &report.broken_linkage {
+        for broken in &keg.broken {
+            let detail = match broken.problem {
+                zb_io::LinkageProblem::Missing => "not found",
+                zb_io::LinkageProblem::DefaultPrefix => "still points at a default Homebrew prefix",
+            };
+            ui.warn(format!(
+                "Broken linkage in {}/{}: {} loads {} ({})",
+                keg.name,
+                keg.version,
+                broken.binary.display(),
+                broken.library,
+                detail
+            ))
+            .map_err(ui_error)?;
+        }
+    }
+
+    for dir in &report.stale_build_dirs {
+        ui.warn(format!("Leftover build directory: {}", dir.display()))
+            .map_err(ui_error)?;
+    }
+
+    if let Some((path, duplicates)) = &shell_config {
+        if duplicates.extra_blocks > 0 {
+            ui.warn(format!(
+                "Duplicate zerobrew PATH {} in {}",
+                pluralize("block", duplicates.extra_blocks + 1),
+                path.display()
+            ))
+            .map_err(ui_error)?;
+        }
+        for line in &duplicates.stray_lines {
+            ui.warn(format!(
+                "Stray zerobrew PATH entry in {}: {}",
+                path.display(),
+                line
+            ))
+            .map_err(ui_error)?;
+        }
+    }
+
     let issue_count = report.orphaned_cellar_kegs.len()
         + report.missing_cellar_kegs.len()
         + report.orphaned_store_entries.len()
         + report.stale_store_refs.len()
         + report.broken_symlinks.len()
-        + usize::from(report.stale_keg_file_records > 0);
+        + usize::from(report.stale_keg_file_records > 0)
+        + report.stale_opt_links.len()
+        + report.broken_linkage.len()
+        + report.stale_build_dirs.len()
+        + shell_config
+            .as_ref()
+            .map_or(0, |(_, duplicates)| duplicates.count());
 
     ui.blank_line().map_err(ui_error)?;
     ui.heading(format!(
@@ -100,13 +168,14 @@ pub fn execute(
     ui.blank_line().map_err(ui_error)?;
     ui.heading("Repairing...").map_err(ui_error)?;
 
-    let summary = installer.repair(&report)?;
+    let summary = installer.repair(report)?;
+    let mut total_fixes = summary.total_fixes();
 
-    if summary.removed_orphaned_kegs > 0 {
+    if summary.adopted_orphaned_kegs > 0 {
         ui.bullet(format!(
-            "Removed {} orphaned cellar {}",
-            summary.removed_orphaned_kegs,
-            pluralize("keg", summary.removed_orphaned_kegs)
+            "Adopted {} orphaned cellar {}",
+            summary.adopted_orphaned_kegs,
+            pluralize("keg", summary.adopted_orphaned_kegs)
         ))
         .map_err(ui_error)?;
     }
@@ -151,28 +220,84 @@ pub fn execute(
         .map_err(ui_error)?;
     }
 
+    if summary.repointed_opt_links > 0 {
+        ui.bullet(format!(
+            "Repointed {} opt {}",
+            summary.repointed_opt_links,
+            pluralize("link", summary.repointed_opt_links)
+        ))
+        .map_err(ui_error)?;
+    }
+
+    if summary.relocated_kegs > 0 {
+        ui.bullet(format!(
+            "Re-ran relocation on {} {}",
+            summary.relocated_kegs,
+            pluralize("keg", summary.relocated_kegs)
+        ))
+        .map_err(ui_error)?;
+    }
+
+    if summary.removed_build_dirs > 0 {
+        ui.bullet(format!(
+            "Removed {} leftover build {}",
+            summary.removed_build_dirs,
+            pluralize("directory", summary.removed_build_dirs)
+        ))
+        .map_err(ui_error)?;
+    }
+
+    if let Some((path, _)) = &shell_config {
+        let content = std::fs::read_to_string(path).map_err(|e| zb_core::Error::FileError {
+            message: format!("failed to read {}: {e}", path.display()),
+        })?;
+        std::fs::write(path, consolidate_path_entries(&content)).map_err(|e| {
+            zb_core::Error::FileError {
+                message: format!("failed to write {}: {e}", path.display()),
+            }
+        })?;
+        ui.bullet(format!(
+            "Consolidated zerobrew PATH entries in {}",
+            path.display()
+        ))
+        .map_err(ui_error)?;
+        total_fixes += 1;
+    }
+
     ui.blank_line().map_err(ui_error)?;
     ui.println(format!(
         "    {} Applied {} {}",
         style("✓").green(),
-        summary.total_fixes(),
-        pluralize("fix", summary.total_fixes())
+        total_fixes,
+        pluralize("fix", total_fixes)
     ))
     .map_err(ui_error)?;
 
     Ok(())
 }
 
+/// The rc file `zb init` manages for the current shell, if it has more
+/// zerobrew PATH setup than its one managed block.
+fn scan_shell_config() -> Option<(PathBuf, ShellConfigDuplicates)> {
+    let path = shell_config_path()?;
+    let content = std::fs::read_to_string(&path).ok()?;
+    let duplicates = find_duplicate_path_entries(&content);
+    (!duplicates.is_empty()).then_some((path, duplicates))
+}
+
 fn pluralize(word: &str, count: usize) -> &str {
     if count == 1 {
         word
     } else {
         match word {
             "keg" => "kegs",
+            "block" => "blocks",
             "record" => "records",
             "ref" => "refs",
             "entry" => "entries",
             "symlink" => "symlinks",
+            "link" => "links",
+            "directory" => "directories",
             "fix" => "fixes",
             "issue" => "issues",
             _ => word,
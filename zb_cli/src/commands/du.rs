@@ -0,0 +1,36 @@
+use console::style;
+use indicatif::HumanBytes;
+
+pub fn execute(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+) -> Result<(), zb_core::Error> {
+    let usage = installer.disk_usage(&formulas)?;
+
+    if usage.is_empty() {
+        println!("No formulas installed.");
+        return Ok(());
+    }
+
+    for entry in &usage {
+        let versions = if entry.versions == 1 {
+            "1 version".to_string()
+        } else {
+            format!("{} versions", entry.versions)
+        };
+        println!(
+            "{:>10}  {} {}",
+            HumanBytes(entry.size_bytes).to_string(),
+            style(&entry.name).bold(),
+            style(versions).dim()
+        );
+    }
+
+    println!(
+        "{:>10}  {}",
+        HumanBytes(usage.iter().map(|entry| entry.size_bytes).sum()).to_string(),
+        style("total").dim()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use console::style;
+use std::time::Instant;
+
+use crate::progress::InstallProgressDisplay;
+use crate::ui::StdUi;
+use crate::utils::normalize_formula_name;
+
+/// Cache formulas' metadata and bottles so `zb install --offline` can install
+/// them later, possibly after the root directory is copied to another host.
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    download_only: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let start = Instant::now();
+    let mut names = Vec::with_capacity(formulas.len());
+    for formula in &formulas {
+        let name = normalize_formula_name(formula)?;
+        if name.starts_with("cask:") {
+            return Err(zb_core::Error::InvalidArgument {
+                message: format!("cannot fetch {formula}: only formulas can be fetched"),
+            });
+        }
+        names.push(name);
+    }
+
+    ui.heading(format!("Fetching {}...", style(names.join(", ")).bold()))
+        .map_err(ui_error)?;
+
+    let summary = if ui.is_json() {
+        installer.fetch(&names, download_only, None).await?
+    } else {
+        let display = InstallProgressDisplay::new("fetched");
+        let result = installer
+            .fetch(&names, download_only, Some(display.callback()))
+            .await;
+        display.finish();
+        result?
+    };
+
+    for name in &summary.source_only {
+        ui.warn(format!(
+            "{name} has no bottle for this platform; an offline install can't build it from source"
+        ))
+        .map_err(ui_error)?;
+    }
+
+    ui.blank_line().map_err(ui_error)?;
+    ui.heading(format!(
+        "Fetched {} bottles ({} already local) in {:.2}s",
+        style(summary.downloaded.len()).green().bold(),
+        summary.already_local.len(),
+        start.elapsed().as_secs_f64()
+    ))
+    .map_err(ui_error)?;
+
+    ui.emit(&serde_json::json!({
+        "downloaded": summary.downloaded,
+        "unpacked": summary.unpacked,
+        "already_local": summary.already_local,
+        "source_only": summary.source_only,
+    }))
+    .map_err(ui_error)
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
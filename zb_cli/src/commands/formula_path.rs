@@ -0,0 +1,22 @@
+use console::style;
+
+use crate::utils::normalize_formula_name;
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    formula: String,
+) -> Result<(), zb_core::Error> {
+    let name = normalize_formula_name(&formula)?;
+    let file = installer.formula_file(&name).await?;
+
+    // Only the path goes to stdout so the output can be used in scripts.
+    println!("{}", file.path.display());
+    eprintln!(
+        "{} {} ({})",
+        style("from").dim(),
+        file.origin,
+        style(&file.url).dim()
+    );
+
+    Ok(())
+}
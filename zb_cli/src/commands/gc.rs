@@ -1,24 +1,215 @@
 use console::style;
+use indicatif::HumanBytes;
 
-pub fn execute(installer: &mut zb_io::Installer) -> Result<(), zb_core::Error> {
-    println!(
-        "{} Running garbage collection...",
-        style("==>").cyan().bold()
-    );
+use crate::ui::{PromptDefault, StdUi};
+
+pub fn execute(installer: &mut zb_io::Installer, ui: &mut StdUi) -> Result<(), zb_core::Error> {
+    ui.heading("Running garbage collection...")
+        .map_err(ui_error)?;
     let removed = installer.gc()?;
 
     if removed.is_empty() {
-        println!("No unreferenced store entries to remove.");
+        ui.println("No unreferenced store entries to remove.")
+            .map_err(ui_error)?;
     } else {
         for key in &removed {
-            println!("    {} Removed {}", style("✓").green(), &key[..12]);
+            ui.println(format!("    {} Removed {}", style("✓").green(), &key[..12]))
+                .map_err(ui_error)?;
         }
-        println!(
-            "{} Removed {} store entries",
-            style("==>").cyan().bold(),
+        ui.heading(format!(
+            "Removed {} store entries",
             style(removed.len()).green().bold()
-        );
+        ))
+        .map_err(ui_error)?;
+    }
+
+    let pruned = installer.prune_build_tmp(zb_io::STALE_BUILD_DIR_AGE)?;
+    if pruned.removed > 0 {
+        ui.heading(format!(
+            "Removed {} leftover build directories ({})",
+            style(pruned.removed).green().bold(),
+            HumanBytes(pruned.freed_bytes)
+        ))
+        .map_err(ui_error)?;
+    }
+
+    ui.emit(&serde_json::json!({
+        "removed_store_entries": removed,
+        "removed_build_dirs": pruned.removed,
+        "freed_build_dir_bytes": pruned.freed_bytes,
+    }))
+    .map_err(ui_error)
+}
+
+/// Run every prune pass at once behind a single confirmation.
+pub async fn execute_aggressive(
+    installer: &mut zb_io::Installer,
+    yes: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    if ui.is_json() && !yes {
+        return Err(zb_core::Error::InvalidArgument {
+            message: "gc --aggressive --json cannot prompt; pass --yes".to_string(),
+        });
+    }
+
+    ui.heading("Collecting reclaimable data...")
+        .map_err(ui_error)?;
+    // Before planning: the plan holds the install lock, which would make
+    // every build dir look like it belongs to a running install.
+    let build_dirs = installer.stale_build_dirs(zb_io::STALE_BUILD_DIR_AGE)?;
+    let build_dir_bytes: u64 = build_dirs.iter().map(|dir| zb_io::tree_size(dir)).sum();
+    let plan = installer.plan_aggressive_gc().await?;
+
+    if plan.is_empty() && build_dirs.is_empty() {
+        ui.println("Nothing to reclaim.").map_err(ui_error)?;
+        return ui
+            .emit(&aggressive_report(
+                &zb_io::GcSummary::default(),
+                &zb_io::BuildTmpPrune::default(),
+            ))
+            .map_err(ui_error);
+    }
+
+    for keg in &plan.kegs {
+        ui.bullet(format!(
+            "Keg {} {} ({})",
+            style(&keg.name).bold(),
+            keg.version,
+            HumanBytes(keg.size_bytes)
+        ))
+        .map_err(ui_error)?;
+    }
+    for keg in &plan.build_dependencies {
+        ui.bullet(format!(
+            "Build dependency {} {} ({})",
+            style(&keg.name).bold(),
+            keg.version,
+            HumanBytes(keg.size_bytes)
+        ))
+        .map_err(ui_error)?;
+    }
+    for keg in &plan.ephemeral {
+        ui.bullet(format!(
+            "Ephemeral {} {} ({})",
+            style(&keg.name).bold(),
+            keg.version,
+            HumanBytes(keg.size_bytes)
+        ))
+        .map_err(ui_error)?;
+    }
+    for keg in &plan.orphaned_dependencies {
+        ui.bullet(format!(
+            "Orphaned dependency {} {} ({})",
+            style(&keg.name).bold(),
+            keg.version,
+            HumanBytes(keg.size_bytes)
+        ))
+        .map_err(ui_error)?;
+    }
+    if !plan.broken_links.is_empty() {
+        ui.bullet(format!("{} broken links", plan.broken_links.len()))
+            .map_err(ui_error)?;
+    }
+    if plan.stale_keg_file_records > 0 {
+        ui.bullet(format!(
+            "{} stale link records",
+            plan.stale_keg_file_records
+        ))
+        .map_err(ui_error)?;
+    }
+    if !plan.store_entries.is_empty() {
+        ui.bullet(format!(
+            "{} unreferenced store entries ({})",
+            plan.store_entries.len(),
+            HumanBytes(plan.store_entries.iter().map(|e| e.size_bytes).sum())
+        ))
+        .map_err(ui_error)?;
+    }
+    if !plan.cached_downloads.is_empty() {
+        ui.bullet(format!(
+            "{} cached downloads ({})",
+            plan.cached_downloads.len(),
+            HumanBytes(plan.cached_downloads.iter().map(|e| e.size_bytes).sum())
+        ))
+        .map_err(ui_error)?;
+    }
+    if !plan.build_logs.is_empty() {
+        ui.bullet(format!(
+            "{} old build logs ({})",
+            plan.build_logs.len(),
+            HumanBytes(plan.build_logs.iter().map(|log| log.size_bytes).sum())
+        ))
+        .map_err(ui_error)?;
+    }
+    if !build_dirs.is_empty() {
+        ui.bullet(format!(
+            "{} leftover build directories ({})",
+            build_dirs.len(),
+            HumanBytes(build_dir_bytes)
+        ))
+        .map_err(ui_error)?;
+    }
+
+    if !yes
+        && !ui
+            .prompt_yes_no(
+                &format!(
+                    "Remove all of the above, freeing {}? [y/N]",
+                    HumanBytes(plan.reclaimable_bytes() + build_dir_bytes)
+                ),
+                PromptDefault::No,
+            )
+            .map_err(ui_error)?
+    {
+        ui.println("Aborted.").map_err(ui_error)?;
+        return Ok(());
     }
 
-    Ok(())
+    let summary = installer.gc_aggressive(plan)?;
+    let pruned = installer.prune_build_tmp(zb_io::STALE_BUILD_DIR_AGE)?;
+
+    ui.heading(format!(
+        "Removed {} kegs, {} links, {} store entries, {} cached downloads, {} build logs and {} build directories, freeing {}",
+        summary.removed_kegs
+            + summary.removed_build_dependencies
+            + summary.removed_ephemeral
+            + summary.removed_orphaned_dependencies,
+        summary.removed_links,
+        summary.removed_store_entries,
+        summary.removed_cached_downloads,
+        summary.removed_build_logs,
+        pruned.removed,
+        style(HumanBytes(summary.freed_bytes + pruned.freed_bytes))
+            .green()
+            .bold()
+    ))
+    .map_err(ui_error)?;
+
+    ui.emit(&aggressive_report(&summary, &pruned))
+        .map_err(ui_error)
+}
+
+fn aggressive_report(
+    summary: &zb_io::GcSummary,
+    pruned: &zb_io::BuildTmpPrune,
+) -> serde_json::Value {
+    serde_json::json!({
+        "removed_kegs": summary.removed_kegs,
+        "removed_build_dependencies": summary.removed_build_dependencies,
+        "removed_ephemeral": summary.removed_ephemeral,
+        "removed_orphaned_dependencies": summary.removed_orphaned_dependencies,
+        "removed_links": summary.removed_links,
+        "removed_store_entries": summary.removed_store_entries,
+        "removed_cached_downloads": summary.removed_cached_downloads,
+        "removed_build_logs": summary.removed_build_logs,
+        "removed_build_dirs": pruned.removed,
+        "freed_bytes": summary.freed_bytes + pruned.freed_bytes,
+    })
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::StoreCorruption {
+        message: format!("failed to write CLI output: {err}"),
+    }
 }
@@ -0,0 +1,53 @@
+use chrono::{DateTime, Local};
+use console::style;
+
+use crate::ui::StdUi;
+
+pub fn execute(installer: &mut zb_io::Installer, ui: &mut StdUi) -> Result<(), zb_core::Error> {
+    let generations = installer.list_generations()?;
+    if generations.is_empty() {
+        ui.info("No generations recorded yet.").map_err(ui_error)?;
+    }
+
+    for generation in &generations {
+        let marker = if generation.current {
+            style("*").green().to_string()
+        } else {
+            " ".to_string()
+        };
+        let created = DateTime::from_timestamp(generation.created_at, 0)
+            .map(|dt| {
+                dt.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_else(|| "invalid timestamp".to_string());
+        ui.println(format!(
+            "{} {:>4}  {}  {:<10}  {} formulas",
+            marker, generation.id, created, generation.operation, generation.kegs
+        ))
+        .map_err(ui_error)?;
+    }
+
+    ui.emit(&serde_json::Value::Array(
+        generations
+            .iter()
+            .map(|g| {
+                serde_json::json!({
+                    "id": g.id,
+                    "created_at": g.created_at,
+                    "operation": g.operation,
+                    "current": g.current,
+                    "formulas": g.kegs,
+                })
+            })
+            .collect(),
+    ))
+    .map_err(ui_error)
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
@@ -1,19 +1,323 @@
 use chrono::{DateTime, Local};
 use console::style;
 
-pub fn execute(installer: &mut zb_io::Installer, formula: String) -> Result<(), zb_core::Error> {
-    if let Some(keg) = installer.get_installed(&formula) {
+use crate::ui::StdUi;
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    history: bool,
+    eval_caveats: bool,
+    used_by: bool,
+    bottle_tags: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    if ui.is_json() {
+        let mut entries = Vec::with_capacity(formulas.len());
+        for formula in &formulas {
+            entries.push(
+                json_entry(
+                    installer,
+                    formula,
+                    history,
+                    eval_caveats,
+                    used_by,
+                    bottle_tags,
+                )
+                .await?,
+            );
+        }
+        return ui
+            .emit(&serde_json::Value::Array(entries))
+            .map_err(ui_error);
+    }
+
+    for (i, formula) in formulas.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print_info(
+            installer,
+            formula,
+            history,
+            eval_caveats,
+            used_by,
+            bottle_tags,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn print_info(
+    installer: &mut zb_io::Installer,
+    formula: &str,
+    history: bool,
+    eval_caveats: bool,
+    used_by: bool,
+    bottle_tags: bool,
+) -> Result<(), zb_core::Error> {
+    if let Some(keg) = installer.get_installed(formula) {
         print_field("Name:", style(&keg.name).bold());
-        print_field("Version:", &keg.version);
+        match installer
+            .cached_outdated(&keg.name)
+            .and_then(|pkg| newer_version_hint(&pkg))
+        {
+            Some(hint) => print_field("Version:", format!("{} {}", keg.version, style(hint).dim())),
+            None => print_field("Version:", &keg.version),
+        }
         print_field("Store key:", &keg.store_key[..12]);
         print_field("Installed:", format_timestamp(keg.installed_at));
+        if let Some(reason) = installer.keg_only_reason(&keg.name).await.ok().flatten() {
+            print_field("Keg-only:", reason);
+        }
+
+        let paths = installer.keg_paths(&keg.name)?;
+        println!();
+        println!("{}", style("Paths:").dim());
+        println!("  {:<6}{}", "keg", paths.keg.display());
+        println!("  {:<6}{}", "opt", paths.opt.display());
+        if paths.linked_binaries.is_empty() {
+            println!("  {:<6}{}", "bin", style("nothing linked").dim());
+        }
+        for binary in &paths.linked_binaries {
+            println!("  {:<6}{}", "bin", binary.display());
+        }
+
+        if history {
+            println!();
+            println!("{}", style("History:").dim());
+            for entry in installer.install_history(&keg.name)? {
+                let marker = if entry.version == keg.version {
+                    style("*").green().to_string()
+                } else {
+                    " ".to_string()
+                };
+                println!(
+                    "  {} {:<12}  {}",
+                    marker,
+                    entry.version,
+                    format_timestamp(entry.installed_at)
+                );
+            }
+        }
+
+        if eval_caveats && let Some(caveats) = installer.caveats(&keg.name).await? {
+            println!();
+            println!("{}", style("Caveats:").dim());
+            for line in caveats.lines() {
+                println!("  {line}");
+            }
+        }
+
+        if used_by {
+            println!();
+            println!("{}", style("Used by:").dim());
+            let dependents = installer.installed_dependents(&keg.name).await?;
+            if dependents.is_empty() {
+                println!("  nothing depends on this");
+            }
+            for dependent in dependents {
+                println!("  {dependent}");
+            }
+        }
     } else {
-        println!("Formula '{}' is not installed.", formula);
+        match installer.get_formula(formula).await {
+            Ok(available) => print_available(&available),
+            Err(e) => println!("Formula '{}' is not installed ({}).", formula, e),
+        }
+    }
+
+    if bottle_tags {
+        println!();
+        println!("{}", style("Bottle tags:").dim());
+        let tags = installer.bottle_tags(formula).await?;
+        if tags.is_empty() {
+            println!("  no bottles published");
+        }
+        for tag in &tags {
+            if tag.host {
+                println!(
+                    "  {} {}",
+                    style(&tag.tag).green().bold(),
+                    style("(this host)").dim()
+                );
+            } else {
+                println!("  {}", tag.tag);
+            }
+        }
+        if !tags.iter().any(|t| t.host) {
+            println!(
+                "  {}",
+                style("no bottle matches this host; install with --build-from-source").yellow()
+            );
+        }
     }
 
     Ok(())
 }
 
+/// What the API knows about a formula that isn't installed, like
+/// `brew info`.
+fn print_available(formula: &zb_core::Formula) {
+    print_field("Name:", style(&formula.name).bold());
+    print_field(
+        "Version:",
+        format!(
+            "{} {}",
+            formula.effective_version(),
+            style("(not installed)").dim()
+        ),
+    );
+    if let Some(desc) = &formula.desc {
+        print_field("Desc:", desc);
+    }
+    if let Some(homepage) = &formula.homepage {
+        print_field("Homepage:", homepage);
+    }
+    if let Some(license) = &formula.license {
+        print_field("License:", license);
+    }
+    if let Some(reason) = formula.keg_only_reason_text() {
+        print_field("Keg-only:", reason);
+    }
+    match zb_core::select_bottle(formula) {
+        Ok(bottle) => print_field("Bottle:", style(bottle.tag).green()),
+        Err(_) => print_field(
+            "Bottle:",
+            style("none for this host; install with --build-from-source").yellow(),
+        ),
+    }
+
+    use zb_core::DependencyKind;
+    let deps: Vec<(String, Option<&str>)> = [
+        (DependencyKind::Runtime, None),
+        (DependencyKind::Recommended, Some("(recommended)")),
+        (DependencyKind::Optional, Some("(optional)")),
+        (DependencyKind::Build, Some("(build)")),
+    ]
+    .into_iter()
+    .flat_map(|(kind, label)| {
+        formula
+            .dependencies_of_kind(kind)
+            .into_iter()
+            .map(move |dep| (dep, label))
+    })
+    .collect();
+    if deps.is_empty() {
+        return;
+    }
+    println!();
+    println!("{}", style("Dependencies:").dim());
+    for (dep, label) in &deps {
+        match label {
+            Some(label) => println!("  {} {}", dep, style(label).dim()),
+            None => println!("  {dep}"),
+        }
+    }
+}
+
+/// The same fields as the human output, plus whichever of history, caveats
+/// and dependents were asked for.
+async fn json_entry(
+    installer: &mut zb_io::Installer,
+    formula: &str,
+    history: bool,
+    eval_caveats: bool,
+    used_by: bool,
+    bottle_tags: bool,
+) -> Result<serde_json::Value, zb_core::Error> {
+    let keg = installer.get_installed(formula);
+    let mut entry = keg_json(formula, keg.as_ref());
+    if bottle_tags {
+        let tags: Vec<serde_json::Value> = installer
+            .bottle_tags(formula)
+            .await?
+            .into_iter()
+            .map(|t| serde_json::json!({ "tag": t.tag, "host": t.host }))
+            .collect();
+        entry["bottle_tags"] = serde_json::Value::Array(tags);
+    }
+    let Some(keg) = keg else {
+        if let Ok(formula) = installer.get_formula(formula).await {
+            available_json(&mut entry, &formula);
+        }
+        return Ok(entry);
+    };
+
+    let latest = installer
+        .cached_outdated(&keg.name)
+        .map(|pkg| pkg.current_version);
+    entry["latest_version"] = serde_json::json!(latest.unwrap_or_else(|| keg.version.clone()));
+    entry["keg_only"] =
+        serde_json::json!(installer.keg_only_reason(&keg.name).await.ok().flatten());
+
+    let paths = installer.keg_paths(&keg.name)?;
+    entry["paths"] = serde_json::json!({
+        "keg": paths.keg,
+        "opt": paths.opt,
+        "linked_binaries": paths.linked_binaries,
+    });
+
+    if history {
+        let versions: Vec<serde_json::Value> = installer
+            .install_history(&keg.name)?
+            .into_iter()
+            .map(|h| serde_json::json!({ "version": h.version, "installed_at": h.installed_at }))
+            .collect();
+        entry["history"] = serde_json::Value::Array(versions);
+    }
+    if eval_caveats {
+        entry["caveats"] = serde_json::json!(installer.caveats(&keg.name).await?);
+    }
+    if used_by {
+        entry["used_by"] = serde_json::json!(installer.installed_dependents(&keg.name).await?);
+    }
+
+    Ok(entry)
+}
+
+/// A formula that isn't installed is reported rather than failing the batch.
+fn keg_json(formula: &str, keg: Option<&zb_io::InstalledKeg>) -> serde_json::Value {
+    match keg {
+        Some(keg) => serde_json::json!({
+            "name": keg.name,
+            "installed": true,
+            "version": keg.version,
+            "store_key": keg.store_key,
+            "installed_at": keg.installed_at,
+        }),
+        None => serde_json::json!({ "name": formula, "installed": false }),
+    }
+}
+
+/// The API's view of a formula that isn't installed.
+fn available_json(entry: &mut serde_json::Value, formula: &zb_core::Formula) {
+    entry["latest_version"] = serde_json::json!(formula.effective_version());
+    entry["desc"] = serde_json::json!(formula.desc);
+    entry["homepage"] = serde_json::json!(formula.homepage);
+    entry["license"] = serde_json::json!(formula.license);
+    entry["keg_only"] = serde_json::json!(formula.keg_only_reason_text());
+    entry["dependencies"] = serde_json::json!(formula.runtime_dependencies());
+    entry["build_dependencies"] = serde_json::json!(formula.all_build_dependencies());
+    entry["bottle"] = serde_json::json!(zb_core::select_bottle(formula).ok().map(|b| b.tag));
+}
+
+/// Upgrade hint for an installed keg whose index version has moved on.
+///
+/// Bottle rebuilds at the same version are not worth a hint. The lookup
+/// behind this reads only cached metadata, so `info` never waits on the
+/// network for it; with nothing cached the hint is simply left out.
+fn newer_version_hint(pkg: &zb_io::OutdatedPackage) -> Option<String> {
+    (pkg.current_version != pkg.installed_version).then(|| {
+        format!(
+            "(a newer version {} is available; run `zb upgrade {}`)",
+            pkg.current_version, pkg.name
+        )
+    })
+}
+
 fn print_field(label: &str, value: impl std::fmt::Display) {
     println!("{:<10}  {}", style(label).dim(), value);
 }
@@ -48,3 +352,106 @@ fn format_timestamp(timestamp: i64) -> String {
         None => "invalid timestamp".to_string(),
     }
 }
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outdated(installed: &str, current: &str) -> zb_io::OutdatedPackage {
+        zb_io::OutdatedPackage {
+            name: "jq".to_string(),
+            installed_version: installed.to_string(),
+            current_version: current.to_string(),
+            installed_sha256: "a".repeat(64),
+            current_sha256: "b".repeat(64),
+            is_source_build: false,
+        }
+    }
+
+    #[test]
+    fn hint_names_newer_version_and_upgrade_command() {
+        assert_eq!(
+            newer_version_hint(&outdated("1.6", "1.7.1")).as_deref(),
+            Some("(a newer version 1.7.1 is available; run `zb upgrade jq`)")
+        );
+    }
+
+    #[test]
+    fn json_batch_reports_missing_formulas_without_failing() {
+        let jq = zb_io::InstalledKeg {
+            name: "jq".to_string(),
+            version: "1.7.1".to_string(),
+            store_key: "a".repeat(64),
+            installed_at: 1_700_000_000,
+        };
+
+        let batch: Vec<serde_json::Value> = [("jq", Some(&jq)), ("nope", None)]
+            .into_iter()
+            .map(|(name, keg)| keg_json(name, keg))
+            .collect();
+
+        assert_eq!(
+            serde_json::Value::Array(batch),
+            serde_json::json!([
+                {
+                    "name": "jq",
+                    "installed": true,
+                    "version": "1.7.1",
+                    "store_key": "a".repeat(64),
+                    "installed_at": 1_700_000_000,
+                },
+                { "name": "nope", "installed": false },
+            ])
+        );
+    }
+
+    #[test]
+    fn missing_formula_json_carries_api_metadata() {
+        let formula: zb_core::Formula = serde_json::from_str(
+            r#"{
+                "name": "wget",
+                "versions": { "stable": "1.24.5" },
+                "revision": 1,
+                "dependencies": ["openssl@3"],
+                "build_dependencies": ["pkgconf"],
+                "bottle": { "stable": { "files": {} } },
+                "desc": "Internet file retriever",
+                "homepage": "https://www.gnu.org/software/wget/",
+                "license": "GPL-3.0-or-later"
+            }"#,
+        )
+        .unwrap();
+
+        let mut entry = keg_json("wget", None);
+        available_json(&mut entry, &formula);
+        assert_eq!(entry["installed"], false);
+        assert_eq!(entry["latest_version"], "1.24.5_1");
+        assert_eq!(entry["desc"], "Internet file retriever");
+        assert_eq!(entry["license"], "GPL-3.0-or-later");
+        assert_eq!(entry["keg_only"], serde_json::Value::Null);
+        assert_eq!(entry["bottle"], serde_json::Value::Null);
+        assert!(
+            entry["dependencies"]
+                .as_array()
+                .unwrap()
+                .contains(&serde_json::json!("openssl@3"))
+        );
+        assert!(
+            entry["build_dependencies"]
+                .as_array()
+                .unwrap()
+                .contains(&serde_json::json!("pkgconf"))
+        );
+    }
+
+    #[test]
+    fn no_hint_when_only_the_bottle_changed() {
+        assert_eq!(newer_version_hint(&outdated("1.7.1", "1.7.1")), None);
+    }
+}
@@ -1,11 +1,12 @@
 use console::style;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Instant;
 use zb_io::{InstallProgress, ProgressCallback};
 
-use crate::ui::StdUi;
+use crate::progress::InstallProgressDisplay;
+use crate::ui::{StdUi, emit_json_line};
 use crate::utils::{normalize_formula_name, suggest_homebrew, suggest_missing_formula_matches};
 
 pub async fn execute(
@@ -13,6 +14,8 @@ pub async fn execute(
     formulas: Vec<String>,
     no_link: bool,
     build_from_source: bool,
+    dependencies_from_source: bool,
+    head: bool,
     ui: &mut StdUi,
 ) -> Result<(), zb_core::Error> {
     let start = Instant::now();
@@ -43,10 +46,20 @@ pub async fn execute(
     let mut installed_count = 0usize;
 
     if !normalized_names.is_empty() {
-        let plan = match installer
-            .plan_with_options(&normalized_names, build_from_source)
-            .await
-        {
+        let planned = if head {
+            installer
+                .plan_head(&normalized_names, build_from_source)
+                .await
+        } else if dependencies_from_source {
+            installer
+                .plan_dependencies_from_source(&normalized_names)
+                .await
+        } else {
+            installer
+                .plan_with_options(&normalized_names, build_from_source)
+                .await
+        };
+        let plan = match planned {
             Ok(p) => p,
             Err(e) => {
                 let handled_missing = suggest_missing_formula_matches(installer, &e).await;
@@ -82,9 +95,168 @@ pub async fn execute(
     ))
     .map_err(ui_error)?;
 
+    emit_result(ui, installed_count, elapsed)
+}
+
+/// Install only the `:build` dependencies of `formulas`, marking them so
+/// `zb gc --aggressive` can reclaim them later.
+pub async fn execute_build_deps_only(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    no_link: bool,
+    build_from_source: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let start = Instant::now();
+    ui.heading(format!(
+        "Installing build dependencies of {}...",
+        style(formulas.join(", ")).bold()
+    ))
+    .map_err(ui_error)?;
+
+    let mut normalized_names = Vec::with_capacity(formulas.len());
+    for formula in &formulas {
+        match normalize_formula_name(formula) {
+            Ok(name) if name.starts_with("cask:") => {
+                return Err(zb_core::Error::InvalidArgument {
+                    message: format!("casks have no build dependencies: {formula}"),
+                });
+            }
+            Ok(name) => normalized_names.push(name),
+            Err(e) => {
+                suggest_homebrew(formula, &e);
+                return Err(e);
+            }
+        }
+    }
+
+    let plan = installer
+        .plan_build_dependencies(&normalized_names, build_from_source)
+        .await?;
+    if plan.items.is_empty() {
+        ui.println("All build dependencies are already installed.")
+            .map_err(ui_error)?;
+        return emit_result(ui, 0, start.elapsed());
+    }
+
+    let planned = plan.install_names();
+    let installed_count = execute_formula_plan(installer, &formulas, plan, no_link, ui).await?;
+    installer.mark_build_dependencies(&planned)?;
+
+    ui.blank_line().map_err(ui_error)?;
+    ui.heading(format!(
+        "Installed {} build dependencies in {:.2}s",
+        style(installed_count).green().bold(),
+        start.elapsed().as_secs_f64()
+    ))
+    .map_err(ui_error)?;
+
+    emit_result(ui, installed_count, start.elapsed())
+}
+
+/// Install or upgrade a single formula only when the installed version is
+/// older than `floor`; otherwise succeed without doing anything.
+pub async fn execute_only_if_newer(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    floor: &str,
+    no_link: bool,
+    build_from_source: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let [formula] = formulas.as_slice() else {
+        return Err(zb_core::Error::InvalidArgument {
+            message: "--only-if-newer takes exactly one formula".to_string(),
+        });
+    };
+    let name = normalize_formula_name(formula)?;
+    if name.starts_with("cask:") {
+        return Err(zb_core::Error::InvalidArgument {
+            message: format!("--only-if-newer does not support casks: {formula}"),
+        });
+    }
+
+    let previous = installer.get_installed(&name).map(|keg| keg.version);
+    let installed = installer
+        .install_at_least(&name, floor, build_from_source, !no_link, None)
+        .await?;
+    ui.emit(&serde_json::json!({
+        "name": name,
+        "previous_version": previous,
+        "installed_version": installed,
+    }))
+    .map_err(ui_error)?;
+
+    match installed {
+        None => ui
+            .info(format!(
+                "{} {} is already at least {}",
+                style(&name).bold(),
+                previous.unwrap_or_default(),
+                floor
+            ))
+            .map_err(ui_error)?,
+        Some(version) => {
+            let action = match previous {
+                Some(previous) => format!("Upgraded {} {} ->", style(&name).bold(), previous),
+                None => format!("Installed {}", style(&name).bold()),
+            };
+            ui.heading(format!("{action} {}", style(version).green().bold()))
+                .map_err(ui_error)?;
+        }
+    }
+
     Ok(())
 }
 
+pub async fn execute_lockfile(
+    installer: &mut zb_io::Installer,
+    lockfile_path: &Path,
+    frozen: bool,
+    no_link: bool,
+    build_from_source: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let start = Instant::now();
+    let lockfile = zb_io::Lockfile::load(lockfile_path)?;
+    ui.heading(format!(
+        "Installing {} formulas from {}...",
+        style(lockfile.formulas.len()).bold(),
+        lockfile_path.display()
+    ))
+    .map_err(ui_error)?;
+
+    let (plan, changes) = installer
+        .plan_from_lockfile(&lockfile, build_from_source, frozen)
+        .await?;
+
+    if !changes.is_empty() {
+        ui.warn("Current formula metadata differs from the lockfile:")
+            .map_err(ui_error)?;
+        for change in &changes {
+            ui.println(format!("    {change}")).map_err(ui_error)?;
+        }
+        ui.println(format!(
+            "    Installing the locked versions; pass {} to fail instead",
+            style("--frozen").bold()
+        ))
+        .map_err(ui_error)?;
+    }
+
+    let installed_count =
+        execute_formula_plan(installer, &lockfile.names(), plan, no_link, ui).await?;
+
+    ui.blank_line().map_err(ui_error)?;
+    ui.heading(format!(
+        "Installed {} packages in {:.2}s",
+        style(installed_count).green().bold(),
+        start.elapsed().as_secs_f64()
+    ))
+    .map_err(ui_error)?;
+
+    emit_result(ui, installed_count, start.elapsed())
+}
+
 pub async fn execute_formula_plan(
     installer: &mut zb_io::Installer,
     requested_formulas: &[String],
@@ -105,127 +277,64 @@ pub async fn execute_formula_plan(
         ))
         .map_err(ui_error)?;
     }
+    let planned: Vec<serde_json::Value> = plan
+        .items
+        .iter()
+        .map(|item| {
+            serde_json::json!({
+                "name": item.formula.name,
+                "version": item.formula.versions.stable,
+            })
+        })
+        .collect();
+    ui.emit(&serde_json::json!({ "event": "plan", "formulas": planned }))
+        .map_err(ui_error)?;
 
-    let multi = MultiProgress::new();
-    let bars: Arc<Mutex<HashMap<String, ProgressBar>>> = Arc::new(Mutex::new(HashMap::new()));
-
-    let download_style = ProgressStyle::default_bar()
-        .template("    {prefix:<16} {bar:25.cyan/dim} {bytes:>10}/{total_bytes:<10} {eta:>6}")
-        .unwrap()
-        .progress_chars("━━╸");
-
-    let spinner_style = ProgressStyle::default_spinner()
-        .template("    {prefix:<16} {spinner:.cyan} {msg}")
-        .unwrap()
-        .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏");
-
-    let done_style = ProgressStyle::default_spinner()
-        .template("    {prefix:<16} {msg}")
-        .unwrap();
+    if ui.is_json() {
+        let progress_callback: Arc<ProgressCallback> = Arc::new(Box::new(|event| {
+            if let Some(line) = progress_event(&event) {
+                let _ = emit_json_line(&line);
+            }
+        }));
+        let result = installer
+            .execute_with_progress(plan, !no_link, Some(progress_callback))
+            .await;
+        return finish_formula_plan(installer, requested_formulas, result, ui).await;
+    }
 
     ui.heading("Downloading and installing formulas...")
         .map_err(ui_error)?;
 
-    let bars_clone = bars.clone();
-    let multi_clone = multi.clone();
-    let download_style_clone = download_style.clone();
-    let spinner_style_clone = spinner_style.clone();
-    let done_style_clone = done_style.clone();
-
-    let progress_callback: Arc<ProgressCallback> = Arc::new(Box::new(move |event| {
-        let mut bars = bars_clone.lock().unwrap();
-        match event {
-            InstallProgress::DownloadStarted { name, total_bytes } => {
-                let pb = if let Some(total) = total_bytes {
-                    let pb = multi_clone.add(ProgressBar::new(total));
-                    pb.set_style(download_style_clone.clone());
-                    pb
-                } else {
-                    let pb = multi_clone.add(ProgressBar::new_spinner());
-                    pb.set_style(spinner_style_clone.clone());
-                    pb.set_message("downloading...");
-                    pb.enable_steady_tick(std::time::Duration::from_millis(80));
-                    pb
-                };
-                pb.set_prefix(name.clone());
-                bars.insert(name, pb);
-            }
-            InstallProgress::DownloadProgress {
-                name,
-                downloaded,
-                total_bytes,
-            } => {
-                if let Some(pb) = bars.get(&name)
-                    && total_bytes.is_some()
-                {
-                    pb.set_position(downloaded);
-                }
-            }
-            InstallProgress::DownloadCompleted { name, total_bytes } => {
-                if let Some(pb) = bars.get(&name) {
-                    if total_bytes > 0 {
-                        pb.set_position(total_bytes);
-                    }
-                    pb.set_style(spinner_style_clone.clone());
-                    pb.set_message("unpacking...");
-                    pb.enable_steady_tick(std::time::Duration::from_millis(80));
-                }
-            }
-            InstallProgress::UnpackStarted { name } => {
-                if let Some(pb) = bars.get(&name) {
-                    pb.set_message("unpacking...");
-                }
-            }
-            InstallProgress::UnpackCompleted { name } => {
-                if let Some(pb) = bars.get(&name) {
-                    pb.set_message("unpacked");
-                }
-            }
-            InstallProgress::LinkStarted { name } => {
-                if let Some(pb) = bars.get(&name) {
-                    pb.set_message("linking...");
-                }
-            }
-            InstallProgress::LinkCompleted { name } => {
-                if let Some(pb) = bars.get(&name) {
-                    pb.set_message("linked");
-                }
-            }
-            InstallProgress::LinkSkipped { name, reason } => {
-                if let Some(pb) = bars.get(&name) {
-                    pb.set_message(format!("keg-only ({})", reason));
-                }
-            }
-            InstallProgress::InstallCompleted { name } => {
-                if let Some(pb) = bars.get(&name) {
-                    pb.set_style(done_style_clone.clone());
-                    pb.set_message(format!("{} installed", style("✓").green()));
-                    pb.finish();
-                }
-            }
-        }
-    }));
-
+    let display = InstallProgressDisplay::new("installed");
+    if std::io::stdin().is_terminal() {
+        installer.set_link_conflict_resolver(Some(display.conflict_resolver()));
+    }
     let result_val = installer
-        .execute_with_progress(plan, !no_link, Some(progress_callback))
+        .execute_with_progress(plan, !no_link, Some(display.callback()))
         .await;
+    installer.set_link_conflict_resolver(None);
+    display.finish();
 
-    {
-        let bars = bars.lock().unwrap();
-        for (_, pb) in bars.iter() {
-            if !pb.is_finished() {
-                pb.finish();
-            }
-        }
-    }
+    finish_formula_plan(installer, requested_formulas, result_val, ui).await
+}
 
-    match result_val {
-        Ok(result) => Ok(result.installed),
+async fn finish_formula_plan(
+    installer: &mut zb_io::Installer,
+    requested_formulas: &[String],
+    result: Result<zb_io::ExecuteResult, zb_core::Error>,
+    ui: &mut StdUi,
+) -> Result<usize, zb_core::Error> {
+    match result {
+        Ok(result) => {
+            print_skipped_pins(&result.skipped_pinned, ui)?;
+            print_caveats(&result.caveats, ui)?;
+            Ok(result.installed)
+        }
         Err(ref e @ zb_core::Error::LinkConflict { ref conflicts }) => {
             ui.blank_line().map_err(ui_error)?;
             ui.error("The link step did not complete successfully.")
                 .map_err(ui_error)?;
-            ui.println("The formula was installed, but is not symlinked into the prefix.")
+            ui.println("The install was rolled back, so nothing was left half-linked.")
                 .map_err(ui_error)?;
             ui.blank_line().map_err(ui_error)?;
             ui.println("Possible conflicting files:")
@@ -244,6 +353,10 @@ pub async fn execute_formula_plan(
                 }
             }
             ui.blank_line().map_err(ui_error)?;
+            ui.println(
+                "Install with --overwrite to replace them, or with --skip-conflicts to leave them in place.",
+            )
+            .map_err(ui_error)?;
             Err(e.clone())
         }
         Err(e) => {
@@ -259,6 +372,108 @@ pub async fn execute_formula_plan(
     }
 }
 
+fn print_skipped_pins(skipped: &[zb_io::SkippedPin], ui: &mut StdUi) -> Result<(), zb_core::Error> {
+    for pin in skipped {
+        ui.emit(&serde_json::json!({
+            "event": "skipped_pinned",
+            "name": pin.name,
+            "pinned_version": pin.pinned_version,
+            "planned_version": pin.planned_version,
+        }))
+        .map_err(ui_error)?;
+        ui.warn(format!(
+            "{} is pinned at {}, skipped {} (pass --ignore-pins to replace it)",
+            style(&pin.name).bold(),
+            pin.pinned_version,
+            pin.planned_version
+        ))
+        .map_err(ui_error)?;
+    }
+    Ok(())
+}
+
+/// Show each installed formula's caveats once the progress bars are done,
+/// where they won't scroll away.
+pub(crate) fn print_caveats(
+    caveats: &[zb_io::FormulaCaveats],
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    for entry in caveats {
+        ui.emit(&serde_json::json!({
+            "event": "caveats",
+            "name": entry.name,
+            "caveats": entry.text,
+        }))
+        .map_err(ui_error)?;
+        ui.blank_line().map_err(ui_error)?;
+        ui.heading(format!("Caveats for {}", style(&entry.name).bold()))
+            .map_err(ui_error)?;
+        ui.println(&entry.text).map_err(ui_error)?;
+    }
+    Ok(())
+}
+
+fn emit_result(
+    ui: &mut StdUi,
+    installed: usize,
+    elapsed: std::time::Duration,
+) -> Result<(), zb_core::Error> {
+    ui.emit(&serde_json::json!({
+        "installed": installed,
+        "elapsed_secs": elapsed.as_secs_f64(),
+    }))
+    .map_err(ui_error)
+}
+
+/// One `--json` line per install progress event, named after the event.
+/// Per-chunk download progress is left out to keep the stream readable.
+fn progress_event(event: &InstallProgress) -> Option<serde_json::Value> {
+    let line = match event {
+        InstallProgress::DownloadStarted { name, total_bytes } => serde_json::json!({
+            "event": "download_started",
+            "name": name,
+            "total_bytes": total_bytes,
+        }),
+        InstallProgress::DownloadProgress { .. } => return None,
+        InstallProgress::DownloadCompleted { name, total_bytes } => serde_json::json!({
+            "event": "download_completed",
+            "name": name,
+            "total_bytes": total_bytes,
+        }),
+        InstallProgress::UnpackStarted { name } => {
+            serde_json::json!({ "event": "unpack_started", "name": name })
+        }
+        InstallProgress::UnpackCompleted { name } => {
+            serde_json::json!({ "event": "unpack_completed", "name": name })
+        }
+        InstallProgress::LinkStarted { name } => {
+            serde_json::json!({ "event": "link_started", "name": name })
+        }
+        InstallProgress::LinkCompleted { name } => {
+            serde_json::json!({ "event": "link_completed", "name": name })
+        }
+        InstallProgress::LinkSkipped { name, reason } => serde_json::json!({
+            "event": "link_skipped",
+            "name": name,
+            "reason": reason,
+        }),
+        InstallProgress::InstallCompleted { name } => {
+            serde_json::json!({ "event": "install_completed", "name": name })
+        }
+        InstallProgress::ResolveStarted { names } => {
+            serde_json::json!({ "event": "resolve_started", "names": names })
+        }
+        InstallProgress::ResolveCompleted { count } => {
+            serde_json::json!({ "event": "resolve_completed", "count": count })
+        }
+        InstallProgress::WaitingForLock { holder } => {
+            serde_json::json!({ "event": "waiting_for_lock", "pid": holder })
+        }
+        _ => return None,
+    };
+    Some(line)
+}
+
 fn ui_error(err: std::io::Error) -> zb_core::Error {
     zb_core::Error::FileError {
         message: format!("failed to write CLI output: {err}"),
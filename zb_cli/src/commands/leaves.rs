@@ -0,0 +1,7 @@
+pub async fn execute(installer: &mut zb_io::Installer) -> Result<(), zb_core::Error> {
+    for name in installer.leaves().await? {
+        println!("{name}");
+    }
+
+    Ok(())
+}
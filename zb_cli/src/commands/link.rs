@@ -0,0 +1,55 @@
+use console::style;
+
+use crate::ui::StdUi;
+use crate::utils::normalize_formula_name;
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    force: bool,
+    overwrite: bool,
+    dry_run: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    for formula in formulas {
+        let name = normalize_formula_name(&formula)?;
+
+        if dry_run {
+            let conflicts = installer.link_conflicts(&name, force).await?;
+            if conflicts.is_empty() {
+                ui.info(format!(
+                    "Linking {} would not replace anything",
+                    style(&name).bold()
+                ))
+                .map_err(ui_error)?;
+                continue;
+            }
+            ui.heading(format!("Would remove for {}:", style(&name).bold()))
+                .map_err(ui_error)?;
+            for conflict in conflicts {
+                let line = match conflict.owned_by {
+                    Some(owner) => format!("{} (linked by {owner})", conflict.path.display()),
+                    None => conflict.path.display().to_string(),
+                };
+                ui.bullet(line).map_err(ui_error)?;
+            }
+            continue;
+        }
+
+        let linked = installer.link(&name, force, overwrite).await?;
+        ui.heading(format!(
+            "Linked {} ({} files)",
+            style(&name).bold(),
+            linked.len()
+        ))
+        .map_err(ui_error)?;
+    }
+
+    Ok(())
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
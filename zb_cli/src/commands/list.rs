@@ -1,15 +1,121 @@
 use console::style;
+use indicatif::HumanBytes;
+use zb_io::InstalledKeg;
 
-pub fn execute(installer: &mut zb_io::Installer) -> Result<(), zb_core::Error> {
+use crate::ui::StdUi;
+
+pub fn execute(
+    installer: &mut zb_io::Installer,
+    size: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
     let installed = installer.list_installed()?;
 
-    if installed.is_empty() {
-        println!("No formulas installed.");
-    } else {
-        for keg in installed {
-            println!("{} {}", style(&keg.name).bold(), style(&keg.version).dim());
+    let sizes = if size {
+        let mut sizes = Vec::with_capacity(installed.len());
+        for keg in &installed {
+            sizes.push(installer.keg_size(keg)?);
         }
+        Some(sizes)
+    } else {
+        None
+    };
+
+    if ui.is_json() {
+        let report = json_report(&installed, sizes.as_deref());
+        return ui.emit(&report).map_err(ui_error);
+    }
+
+    if installed.is_empty() {
+        ui.println("No formulas installed.").map_err(ui_error)?;
+        return Ok(());
+    }
+
+    for (idx, keg) in installed.iter().enumerate() {
+        let line = match sizes.as_ref().map(|sizes| sizes[idx]) {
+            Some(bytes) => format!(
+                "{} {} {}",
+                style(&keg.name).bold(),
+                style(&keg.version).dim(),
+                HumanBytes(bytes)
+            ),
+            None => format!("{} {}", style(&keg.name).bold(), style(&keg.version).dim()),
+        };
+        ui.println(line).map_err(ui_error)?;
+    }
+
+    if let Some(sizes) = &sizes {
+        ui.println(format!(
+            "{} {}",
+            style("Total:").dim(),
+            HumanBytes(sizes.iter().sum())
+        ))
+        .map_err(ui_error)?;
     }
 
     Ok(())
 }
+
+fn json_report(installed: &[InstalledKeg], sizes: Option<&[u64]>) -> serde_json::Value {
+    let formulas: Vec<serde_json::Value> = installed
+        .iter()
+        .enumerate()
+        .map(|(idx, keg)| {
+            let mut entry = serde_json::json!({
+                "name": keg.name,
+                "version": keg.version,
+                "installed_at": keg.installed_at,
+            });
+            if let Some(sizes) = sizes {
+                entry["size_bytes"] = serde_json::json!(sizes[idx]);
+            }
+            entry
+        })
+        .collect();
+
+    let mut report = serde_json::json!({ "formulas": formulas });
+    if let Some(sizes) = sizes {
+        report["total_size_bytes"] = serde_json::json!(sizes.iter().sum::<u64>());
+    }
+    report
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keg(name: &str) -> InstalledKeg {
+        InstalledKeg {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            store_key: "key".to_string(),
+            installed_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn json_report_includes_sizes_and_total() {
+        let installed = vec![keg("jq"), keg("wget")];
+
+        let report = json_report(&installed, Some(&[1024, 2048]));
+
+        let formulas = report["formulas"].as_array().unwrap();
+        assert_eq!(formulas[0]["size_bytes"].as_u64(), Some(1024));
+        assert_eq!(formulas[1]["size_bytes"].as_u64(), Some(2048));
+        assert_eq!(report["total_size_bytes"].as_u64(), Some(3072));
+    }
+
+    #[test]
+    fn json_report_omits_sizes_when_not_requested() {
+        let report = json_report(&[keg("jq")], None);
+
+        assert!(report["formulas"][0].get("size_bytes").is_none());
+        assert!(report.get("total_size_bytes").is_none());
+    }
+}
@@ -0,0 +1,31 @@
+use std::io::Write;
+use std::path::Path;
+
+use console::style;
+use zb_io::{build_logs_dir, latest_build_log};
+
+use crate::utils::normalize_formula_name;
+
+pub fn execute(root: &Path, formula: String, path_only: bool) -> Result<(), zb_core::Error> {
+    let name = normalize_formula_name(&formula)?;
+    let logs_dir = build_logs_dir(root);
+
+    // Builds log under the formula's short name, even for tap formulas.
+    let short_name = name.rsplit('/').next().unwrap_or(&name);
+    let log = latest_build_log(&logs_dir, &name)
+        .or_else(|| latest_build_log(&logs_dir, short_name))
+        .ok_or_else(|| zb_core::Error::InvalidArgument {
+            message: format!("no source build log for '{name}'"),
+        })?;
+
+    if path_only {
+        println!("{}", log.display());
+        return Ok(());
+    }
+
+    let contents = std::fs::read(&log).map_err(zb_core::Error::file("failed to read build log"))?;
+    eprintln!("{} {}", style("==>").cyan().bold(), log.display());
+    std::io::stdout()
+        .write_all(&contents)
+        .map_err(zb_core::Error::file("failed to write build log"))
+}
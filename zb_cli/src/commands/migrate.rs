@@ -6,12 +6,38 @@ pub async fn execute(
     installer: &mut zb_io::Installer,
     yes: bool,
     force: bool,
+    only: Vec<String>,
+    dry_run: bool,
     ui: &mut StdUi,
 ) -> Result<(), zb_core::Error> {
     ui.heading("Fetching installed Homebrew packages...")
         .map_err(ui_error)?;
 
-    let packages = zb_io::get_homebrew_packages()?;
+    let packages = if only.is_empty() {
+        zb_io::get_homebrew_packages()?
+    } else {
+        zb_io::get_homebrew_packages_only(&only)?
+    };
+
+    let not_in_homebrew: Vec<&String> = only
+        .iter()
+        .filter(|name| {
+            !packages
+                .formulas
+                .iter()
+                .chain(&packages.non_core_formulas)
+                .chain(&packages.casks)
+                .any(|pkg| &pkg.name == *name)
+        })
+        .collect();
+    if !not_in_homebrew.is_empty() {
+        ui.note("Not installed by Homebrew, skipping:")
+            .map_err(ui_error)?;
+        for name in not_in_homebrew {
+            ui.bullet(name).map_err(ui_error)?;
+        }
+        ui.blank_line().map_err(ui_error)?;
+    }
 
     if packages.formulas.is_empty()
         && packages.non_core_formulas.is_empty()
@@ -66,6 +92,12 @@ pub async fn execute(
     }
     ui.blank_line().map_err(ui_error)?;
 
+    if dry_run {
+        ui.println("Dry run: nothing was migrated.")
+            .map_err(ui_error)?;
+        return Ok(());
+    }
+
     if !yes
         && !ui
             .prompt_yes_no("Continue with migration? [y/N]", PromptDefault::No)
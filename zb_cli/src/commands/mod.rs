@@ -1,15 +1,41 @@
+pub mod audit;
+pub mod autoremove;
 pub mod bundle;
+pub mod cache;
+pub mod caveats;
+pub mod cleanup;
 pub mod completion;
+pub mod deps;
 pub mod doctor;
+pub mod du;
+pub mod fetch;
+pub mod formula_path;
 pub mod gc;
+pub mod generations;
 pub mod info;
 pub mod init;
 pub mod install;
+pub mod leaves;
+pub mod link;
 pub mod list;
+pub mod log;
 pub mod migrate;
 pub mod outdated;
+pub mod pin;
+pub mod postinstall;
+pub mod reinstall;
 pub mod reset;
+pub mod rollback;
 pub mod run;
+pub mod sbom;
+pub mod search;
+pub mod services;
+pub mod shellenv;
 pub mod uninstall;
+pub mod unlink;
 pub mod update;
 pub mod upgrade;
+pub mod use_version;
+pub mod uses;
+pub mod verify;
+pub mod which;
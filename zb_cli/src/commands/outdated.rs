@@ -1,19 +1,21 @@
 use console::style;
 
+use crate::ui::StdUi;
+
 pub async fn execute(
     installer: &mut zb_io::Installer,
     quiet: bool,
     verbose: bool,
-    json: bool,
+    ui: &mut StdUi,
 ) -> Result<(), zb_core::Error> {
     let (outdated, warnings) = installer.check_outdated().await?;
 
     // Warnings always go to stderr (never pollute stdout, especially in --json mode)
     for warning in &warnings {
-        eprintln!("{} {}", style("Warning:").yellow().bold(), warning);
+        ui.warn(warning).map_err(ui_error)?;
     }
 
-    if json {
+    if ui.is_json() {
         let json_output: Vec<serde_json::Value> = outdated
             .iter()
             .map(|pkg| {
@@ -24,38 +26,44 @@ pub async fn execute(
                 })
             })
             .collect();
-        println!("{}", serde_json::to_string_pretty(&json_output).unwrap());
-        return Ok(());
+        return ui
+            .emit(&serde_json::Value::Array(json_output))
+            .map_err(ui_error);
     }
 
     if outdated.is_empty() {
         if !quiet {
-            println!(
-                "{} All packages are up to date.",
-                style("==>").cyan().bold()
-            );
+            ui.heading("All packages are up to date.")
+                .map_err(ui_error)?;
         }
         return Ok(());
     }
 
     for pkg in &outdated {
-        if quiet {
-            println!("{}", pkg.name);
+        let line = if quiet {
+            pkg.name.clone()
         } else if verbose {
-            println!(
+            format!(
                 "{} {} {} {}",
                 pkg.name,
                 style(&pkg.installed_version).red(),
                 style("→").dim(),
                 style(&pkg.current_version).green(),
-            );
+            )
         } else {
-            println!(
+            format!(
                 "{} ({}) < {}",
                 pkg.name, pkg.installed_version, pkg.current_version
-            );
-        }
+            )
+        };
+        ui.println(line).map_err(ui_error)?;
     }
 
     Ok(())
 }
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
@@ -0,0 +1,51 @@
+use console::style;
+
+use crate::ui::StdUi;
+use crate::utils::normalize_formula_name;
+
+pub fn execute(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    for formula in formulas {
+        let name = normalize_formula_name(&formula)?;
+        installer.pin(&name)?;
+        let version = installer
+            .get_installed(&name)
+            .map(|keg| keg.version)
+            .unwrap_or_default();
+        ui.heading(format!(
+            "Pinned {} at {}",
+            style(&name).bold(),
+            style(version).green()
+        ))
+        .map_err(ui_error)?;
+    }
+
+    Ok(())
+}
+
+pub fn execute_unpin(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    for formula in formulas {
+        let name = normalize_formula_name(&formula)?;
+        if installer.unpin(&name)? {
+            ui.heading(format!("Unpinned {}", style(&name).bold()))
+                .map_err(ui_error)?;
+        } else {
+            ui.info(format!("{name} is not pinned")).map_err(ui_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
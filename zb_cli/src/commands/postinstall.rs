@@ -0,0 +1,29 @@
+use console::style;
+
+use crate::ui::StdUi;
+use crate::utils::normalize_formula_name;
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    for formula in formulas {
+        let name = normalize_formula_name(&formula)?;
+        if installer.post_install(&name).await? {
+            ui.heading(format!("Ran post_install for {}", style(&name).bold()))
+                .map_err(ui_error)?;
+        } else {
+            ui.info(format!("{name} has no post_install step"))
+                .map_err(ui_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
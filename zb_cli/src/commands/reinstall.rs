@@ -0,0 +1,59 @@
+use console::style;
+use std::time::Instant;
+
+use crate::ui::StdUi;
+use crate::utils::normalize_formula_name;
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    from_source: bool,
+    no_link: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let start = Instant::now();
+
+    let mut names = Vec::with_capacity(formulas.len());
+    for formula in &formulas {
+        names.push(normalize_formula_name(formula)?);
+    }
+
+    let mut reinstalled = 0usize;
+    let mut first_error = None;
+
+    for name in &names {
+        ui.step_start(name).map_err(ui_error)?;
+        match installer.reinstall(name, from_source, !no_link, None).await {
+            Ok(()) => {
+                ui.step_ok().map_err(ui_error)?;
+                reinstalled += 1;
+            }
+            Err(e) => {
+                ui.step_fail().map_err(ui_error)?;
+                ui.error(format!("Failed to reinstall {}: {}", style(name).bold(), e))
+                    .map_err(ui_error)?;
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    ui.blank_line().map_err(ui_error)?;
+    ui.heading(format!(
+        "Reinstalled {} of {} packages in {:.2}s",
+        style(reinstalled).green().bold(),
+        names.len(),
+        start.elapsed().as_secs_f64()
+    ))
+    .map_err(ui_error)?;
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::StoreCorruption {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
@@ -0,0 +1,81 @@
+use console::style;
+
+use crate::ui::StdUi;
+use crate::utils::normalize_formula_name;
+
+pub fn execute(
+    installer: &mut zb_io::Installer,
+    formula: String,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let name = normalize_formula_name(&formula)?;
+    let previous = installer.get_installed(&name).map(|keg| keg.version);
+
+    let version = installer.rollback(&name)?;
+    ui.heading(format!(
+        "Rolled back {} {} -> {}",
+        style(&name).bold(),
+        previous.unwrap_or_default(),
+        style(&version).green().bold()
+    ))
+    .map_err(ui_error)?;
+
+    Ok(())
+}
+
+/// Roll the whole prefix back to generation `to`, or the previous one.
+pub fn execute_generation(
+    installer: &mut zb_io::Installer,
+    to: Option<i64>,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let switch = installer.rollback_generation(to)?;
+
+    for keg in &switch.deactivated {
+        ui.bullet(format!(
+            "{} {} {}",
+            style("-").red(),
+            style(&keg.name).bold(),
+            keg.version
+        ))
+        .map_err(ui_error)?;
+    }
+    for keg in &switch.activated {
+        ui.bullet(format!(
+            "{} {} {}",
+            style("+").green(),
+            style(&keg.name).bold(),
+            keg.version
+        ))
+        .map_err(ui_error)?;
+    }
+    for keg in &switch.relinked {
+        let action = if keg.linked { "linked" } else { "unlinked" };
+        ui.bullet(format!("{} {}", style(&keg.name).bold(), action))
+            .map_err(ui_error)?;
+    }
+    ui.heading(format!(
+        "Rolled back to generation {}",
+        style(switch.generation).green().bold()
+    ))
+    .map_err(ui_error)?;
+
+    let kegs = |kegs: &[zb_io::GenerationKeg]| {
+        kegs.iter()
+            .map(|keg| serde_json::json!({ "name": keg.name, "version": keg.version }))
+            .collect::<Vec<_>>()
+    };
+    ui.emit(&serde_json::json!({
+        "generation": switch.generation,
+        "activated": kegs(&switch.activated),
+        "deactivated": kegs(&switch.deactivated),
+        "relinked": kegs(&switch.relinked),
+    }))
+    .map_err(ui_error)
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
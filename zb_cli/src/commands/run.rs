@@ -7,12 +7,16 @@ use zb_io::Installer;
 
 use crate::utils::{normalize_formula_name, suggest_missing_formula_matches};
 
-/// Prepare a package for execution by ensuring it's installed
-/// Returns the path to the executable
+/// Prepare a package for execution by ensuring it's installed. Returns the
+/// executable to run and the arguments to pass it; a leading argument naming
+/// another executable in the keg selects that one, as in
+/// `zb run ripgrep -- rg foo`.
 pub async fn prepare_execution(
     installer: &mut Installer,
     formula: &str,
-) -> Result<PathBuf, zb_core::Error> {
+    args: Vec<String>,
+    ephemeral: bool,
+) -> Result<(PathBuf, Vec<String>), zb_core::Error> {
     let normalized = normalize_formula_name(formula)?;
 
     let was_installed = installer.is_installed(&normalized);
@@ -24,8 +28,14 @@ pub async fn prepare_execution(
             style(&normalized).green()
         );
 
-        let plan = installer.plan(std::slice::from_ref(&normalized)).await?;
-        installer.execute(plan, false).await?;
+        if ephemeral {
+            installer
+                .install_ephemeral(std::slice::from_ref(&normalized))
+                .await?;
+        } else {
+            let plan = installer.plan(std::slice::from_ref(&normalized)).await?;
+            installer.execute(plan, false).await?;
+        }
     }
 
     let installed =
@@ -37,24 +47,58 @@ pub async fn prepare_execution(
 
     let executable_name = formula_token(&installed.name);
     let keg_path = installer.keg_path(executable_name, &installed.version);
-    let bin_path = keg_path.join("bin").join(executable_name);
-
-    if !bin_path.exists() {
-        return Err(zb_core::Error::ExecutionError {
-            message: format!(
-                "executable '{}' not found in package '{}'",
-                executable_name, normalized
-            ),
-        });
+    select_executable(&keg_path.join("bin"), executable_name, args).map_err(|message| {
+        zb_core::Error::ExecutionError {
+            message: format!("{message} in package '{normalized}'"),
+        }
+    })
+}
+
+/// Pick the executable in `bin_dir`: the one `args` starts with, else
+/// `default_name`, else the keg's only executable.
+fn select_executable(
+    bin_dir: &Path,
+    default_name: &str,
+    mut args: Vec<String>,
+) -> Result<(PathBuf, Vec<String>), String> {
+    if let Some(first) = args.first()
+        && !first.contains('/')
+        && bin_dir.join(first).is_file()
+    {
+        let bin_path = bin_dir.join(args.remove(0));
+        return Ok((bin_path, args));
+    }
+
+    let default_path = bin_dir.join(default_name);
+    if default_path.exists() {
+        return Ok((default_path, args));
     }
 
-    Ok(bin_path)
+    let mut available: Vec<String> = std::fs::read_dir(bin_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    available.sort();
+
+    match available.as_slice() {
+        [only] => Ok((bin_dir.join(only), args)),
+        [] => Err(format!("executable '{default_name}' not found")),
+        names => Err(format!(
+            "executable '{default_name}' not found; name one of {} before the arguments",
+            names.join(", ")
+        )),
+    }
 }
 
 pub async fn execute(
     installer: &mut Installer,
     formula: String,
     args: Vec<String>,
+    ephemeral: bool,
 ) -> Result<(), zb_core::Error> {
     println!(
         "{} Running {}...",
@@ -62,8 +106,8 @@ pub async fn execute(
         style(&formula).bold()
     );
 
-    let bin_path = match prepare_execution(installer, &formula).await {
-        Ok(path) => path,
+    let (bin_path, args) = match prepare_execution(installer, &formula, args, ephemeral).await {
+        Ok(prepared) => prepared,
         Err(e) => {
             let _ = suggest_missing_formula_matches(installer, &e).await;
             return Err(e);
@@ -79,6 +123,18 @@ pub async fn execute(
     let mut cmd = Command::new(&bin_path);
     cmd.args(&args);
 
+    // The keg isn't linked, so put its other executables on PATH for
+    // anything the command spawns.
+    if let Some(bin_dir) = bin_path.parent() {
+        let mut paths = vec![bin_dir.to_path_buf()];
+        if let Some(existing) = std::env::var_os("PATH") {
+            paths.extend(std::env::split_paths(&existing));
+        }
+        if let Ok(path) = std::env::join_paths(paths) {
+            cmd.env("PATH", path);
+        }
+    }
+
     if let Some(prefix_path) = detect_runtime_prefix(&bin_path) {
         if let Some(ca_bundle) = zb_io::find_ca_bundle_from_prefix(&prefix_path) {
             cmd.env("CURL_CA_BUNDLE", &ca_bundle);
@@ -256,7 +312,9 @@ mod tests {
 
         assert!(!installer.is_installed("testrun"));
 
-        let bin_path = prepare_execution(&mut installer, "testrun").await.unwrap();
+        let (bin_path, _) = prepare_execution(&mut installer, "testrun", Vec::new(), false)
+            .await
+            .unwrap();
 
         assert!(installer.is_installed("testrun"));
         assert!(!prefix.join("bin/testrun").exists());
@@ -339,9 +397,10 @@ mod tests {
             .unwrap();
         assert!(installer.is_installed("alreadyinstalled"));
 
-        let bin_path = prepare_execution(&mut installer, "alreadyinstalled")
-            .await
-            .unwrap();
+        let (bin_path, _) =
+            prepare_execution(&mut installer, "alreadyinstalled", Vec::new(), false)
+                .await
+                .unwrap();
 
         assert!(bin_path.exists());
         assert!(bin_path.ends_with("bin/alreadyinstalled"));
@@ -387,10 +446,32 @@ mod tests {
             root.join("locks"),
         );
 
-        let result = prepare_execution(&mut installer, "nonexistent").await;
+        let result = prepare_execution(&mut installer, "nonexistent", Vec::new(), false).await;
         assert!(result.is_err());
     }
 
+    #[test]
+    fn leading_argument_can_select_another_executable() {
+        let tmp = TempDir::new().unwrap();
+        let bin_dir = tmp.path().join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::write(bin_dir.join("rg"), "").unwrap();
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let (bin, rest) = select_executable(&bin_dir, "ripgrep", args(&["rg", "foo"])).unwrap();
+        assert_eq!(bin, bin_dir.join("rg"));
+        assert_eq!(rest, ["foo"]);
+
+        // The keg's only executable is used when the formula name isn't one.
+        let (bin, rest) = select_executable(&bin_dir, "ripgrep", args(&["foo"])).unwrap();
+        assert_eq!(bin, bin_dir.join("rg"));
+        assert_eq!(rest, ["foo"]);
+
+        fs::write(bin_dir.join("rga"), "").unwrap();
+        let err = select_executable(&bin_dir, "ripgrep", args(&["foo"])).unwrap_err();
+        assert!(err.contains("rg, rga"), "{err}");
+    }
+
     #[test]
     fn ssl_cert_paths_use_prefix() {
         let prefix = "/opt/test/prefix";
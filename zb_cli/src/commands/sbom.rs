@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use chrono::{SecondsFormat, Utc};
+use console::style;
+
+use crate::cli::SbomFormat;
+use crate::ui::StdUi;
+use crate::utils::normalize_formula_name;
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    format: SbomFormat,
+    output: Option<PathBuf>,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let names = formulas
+        .iter()
+        .map(|name| normalize_formula_name(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let sbom = installer.sbom(&names).await?;
+
+    let created = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let tool = format!("zerobrew-{}", env!("CARGO_PKG_VERSION"));
+    let document = match format {
+        SbomFormat::Spdx => {
+            let name = if names.is_empty() {
+                "zerobrew-prefix".to_string()
+            } else {
+                names.join("-")
+            };
+            sbom.to_spdx(&name, &created, &tool)
+        }
+        SbomFormat::Cyclonedx => sbom.to_cyclonedx(&created, &tool),
+    };
+    let mut contents =
+        serde_json::to_string_pretty(&document).map_err(|e| zb_core::Error::FileError {
+            message: format!("failed to serialize SBOM: {e}"),
+        })?;
+    contents.push('\n');
+
+    let Some(path) = output else {
+        print!("{contents}");
+        return Ok(());
+    };
+    std::fs::write(&path, contents).map_err(|e| zb_core::Error::FileError {
+        message: format!("failed to write SBOM {}: {e}", path.display()),
+    })?;
+    ui.heading(format!(
+        "Wrote {} packages to {}",
+        sbom.packages.len(),
+        style(path.display()).bold()
+    ))
+    .map_err(ui_error)
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
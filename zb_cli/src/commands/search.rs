@@ -0,0 +1,29 @@
+use console::style;
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    query: String,
+    installed: bool,
+    desc: bool,
+) -> Result<(), zb_core::Error> {
+    let results = installer.search(&query, installed, desc).await?;
+
+    if results.is_empty() {
+        println!("No formulas found for '{}'.", query);
+        return Ok(());
+    }
+
+    for result in results {
+        let name = if result.installed {
+            format!("{} {}", style(&result.name).bold(), style("✓").green())
+        } else {
+            result.name.clone()
+        };
+        match result.desc.as_deref().filter(|_| desc) {
+            Some(text) => println!("{name}: {}", style(text).dim()),
+            None => println!("{name}"),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use console::style;
+
+use crate::cli::ServicesCommands;
+use crate::ui::StdUi;
+use crate::utils::normalize_formula_name;
+use zb_io::{ServiceManager, ServiceState, ServiceStatus};
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    command: Option<ServicesCommands>,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let manager = ServiceManager::for_user()?;
+
+    match command.unwrap_or(ServicesCommands::List) {
+        ServicesCommands::List => list(installer, &manager, ui).await,
+        ServicesCommands::Start { formula } => {
+            let name = normalize_formula_name(&formula)?;
+            let status = installer.start_service(&name, &manager).await?;
+            report(ui, "Started", &status)
+        }
+        ServicesCommands::Stop { formula } => {
+            let name = normalize_formula_name(&formula)?;
+            if installer.stop_service(&name, &manager)? {
+                ui.heading(format!("Stopped {}", style(&name).bold()))
+                    .map_err(ui_error)?;
+            } else {
+                ui.info(format!("{name} is not started"))
+                    .map_err(ui_error)?;
+            }
+            ui.emit(&serde_json::json!({ "name": name, "status": ServiceState::Stopped }))
+                .map_err(ui_error)
+        }
+        ServicesCommands::Restart { formula } => {
+            let name = normalize_formula_name(&formula)?;
+            let status = installer.restart_service(&name, &manager).await?;
+            report(ui, "Restarted", &status)
+        }
+    }
+}
+
+async fn list(
+    installer: &zb_io::Installer,
+    manager: &ServiceManager,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let services = installer.services(manager).await?;
+
+    if ui.is_json() {
+        let entries: Vec<serde_json::Value> = services.iter().map(status_json).collect();
+        return ui
+            .emit(&serde_json::Value::Array(entries))
+            .map_err(ui_error);
+    }
+
+    if services.is_empty() {
+        ui.info("No installed formulas define a service.")
+            .map_err(ui_error)?;
+        return Ok(());
+    }
+
+    let width = services
+        .iter()
+        .map(|s| s.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("Name".len());
+    ui.println(style(format!("{:<width$}  {:<8}  File", "Name", "Status")).bold())
+        .map_err(ui_error)?;
+    for service in &services {
+        // Pad before styling, so escape codes don't throw off the columns.
+        let state = match service.state {
+            ServiceState::Started => style(format!("{:<8}", "started")).green(),
+            ServiceState::Stopped => style(format!("{:<8}", "none")).dim(),
+            ServiceState::Error => style(format!("{:<8}", "error")).red(),
+        };
+        let file = service
+            .file
+            .as_ref()
+            .map(|f| f.display().to_string())
+            .unwrap_or_default();
+        ui.println(format!("{:<width$}  {state}  {file}", service.name))
+            .map_err(ui_error)?;
+    }
+
+    Ok(())
+}
+
+fn report(ui: &mut StdUi, action: &str, status: &ServiceStatus) -> Result<(), zb_core::Error> {
+    match status.state {
+        ServiceState::Started => ui.heading(format!(
+            "{action} {} ({})",
+            style(&status.name).bold(),
+            status.label
+        )),
+        _ => ui.warn(format!(
+            "{action} {}, but it is not running; check its log",
+            status.name
+        )),
+    }
+    .map_err(ui_error)?;
+    ui.emit(&status_json(status)).map_err(ui_error)
+}
+
+fn status_json(status: &ServiceStatus) -> serde_json::Value {
+    serde_json::json!({
+        "name": status.name,
+        "label": status.label,
+        "status": status.state,
+        "file": status.file,
+    })
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use crate::cli::ShellenvShell;
+use crate::init::{fish_shell_quote, posix_shell_quote};
+
+pub fn execute(
+    root: &Path,
+    prefix: &Path,
+    shell: Option<ShellenvShell>,
+) -> Result<(), zb_core::Error> {
+    let shell = shell.unwrap_or_else(|| detect_shell(&std::env::var("SHELL").unwrap_or_default()));
+    print!("{}", render(shell, root, prefix));
+    Ok(())
+}
+
+/// Anything we don't recognise gets POSIX syntax, which is also what
+/// `zb init` writes to `~/.profile` for unknown shells.
+fn detect_shell(shell: &str) -> ShellenvShell {
+    let name = Path::new(shell)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match name.as_str() {
+        "zsh" => ShellenvShell::Zsh,
+        "fish" => ShellenvShell::Fish,
+        "nu" => ShellenvShell::Nu,
+        _ => ShellenvShell::Bash,
+    }
+}
+
+/// MANPATH and INFOPATH keep an empty component so the system defaults are
+/// still searched after the prefix, matching `brew shellenv`.
+fn render(shell: ShellenvShell, root: &Path, prefix: &Path) -> String {
+    let root = root.display().to_string();
+    let prefix_str = prefix.display().to_string();
+    let bin = prefix.join("bin").display().to_string();
+    let sbin = prefix.join("sbin").display().to_string();
+    let man = prefix.join("share/man").display().to_string();
+    let info = prefix.join("share/info").display().to_string();
+
+    match shell {
+        ShellenvShell::Bash | ShellenvShell::Zsh => format!(
+            "export ZEROBREW_ROOT={root};\n\
+             export ZEROBREW_PREFIX={prefix};\n\
+             export PATH={bin}:{sbin}\"${{PATH+:$PATH}}\";\n\
+             export MANPATH={man}:\"${{MANPATH#:}}\";\n\
+             export INFOPATH={info}:\"${{INFOPATH:-}}\";\n",
+            root = posix_shell_quote(&root),
+            prefix = posix_shell_quote(&prefix_str),
+            bin = posix_shell_quote(&bin),
+            sbin = posix_shell_quote(&sbin),
+            man = posix_shell_quote(&man),
+            info = posix_shell_quote(&info),
+        ),
+        ShellenvShell::Fish => format!(
+            "set -gx ZEROBREW_ROOT {root};\n\
+             set -gx ZEROBREW_PREFIX {prefix};\n\
+             fish_add_path --global --move --path {bin} {sbin};\n\
+             if test -n \"$MANPATH[1]\"; set -gx MANPATH '' $MANPATH; end;\n\
+             if not contains {man} $MANPATH; set -gx MANPATH {man} $MANPATH; end;\n\
+             if not contains {info} $INFOPATH; set -gx INFOPATH {info} $INFOPATH; end;\n",
+            root = fish_shell_quote(&root),
+            prefix = fish_shell_quote(&prefix_str),
+            bin = fish_shell_quote(&bin),
+            sbin = fish_shell_quote(&sbin),
+            man = fish_shell_quote(&man),
+            info = fish_shell_quote(&info),
+        ),
+        ShellenvShell::Nu => format!(
+            "$env.ZEROBREW_ROOT = {root}\n\
+             $env.ZEROBREW_PREFIX = {prefix}\n\
+             $env.PATH = ($env.PATH | split row (char esep) | prepend [{bin} {sbin}] | uniq)\n\
+             $env.MANPATH = ($env.MANPATH? | default '' | split row (char esep) | prepend {man} | uniq | str join (char esep))\n\
+             $env.INFOPATH = ($env.INFOPATH? | default '' | split row (char esep) | prepend {info} | uniq | str join (char esep))\n",
+            root = nu_quote(&root),
+            prefix = nu_quote(&prefix_str),
+            bin = nu_quote(&bin),
+            sbin = nu_quote(&sbin),
+            man = nu_quote(&man),
+            info = nu_quote(&info),
+        ),
+    }
+}
+
+fn nu_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_shell_puts_prefix_bin_on_path_in_its_own_syntax() {
+        let root = Path::new("/opt/zerobrew");
+        let prefix = Path::new("/opt/zerobrew/prefix");
+
+        let bash = render(ShellenvShell::Bash, root, prefix);
+        assert!(bash.contains(
+            "export PATH='/opt/zerobrew/prefix/bin':'/opt/zerobrew/prefix/sbin'\"${PATH+:$PATH}\";"
+        ));
+        assert!(bash.contains("export MANPATH='/opt/zerobrew/prefix/share/man':"));
+        assert!(bash.contains("export INFOPATH='/opt/zerobrew/prefix/share/info':"));
+        assert_eq!(render(ShellenvShell::Zsh, root, prefix), bash);
+
+        let fish = render(ShellenvShell::Fish, root, prefix);
+        assert!(fish.contains(
+            "fish_add_path --global --move --path \"/opt/zerobrew/prefix/bin\" \"/opt/zerobrew/prefix/sbin\";"
+        ));
+        assert!(!fish.contains("export "));
+
+        let nu = render(ShellenvShell::Nu, root, prefix);
+        assert!(nu.contains(
+            "$env.PATH = ($env.PATH | split row (char esep) | prepend [\"/opt/zerobrew/prefix/bin\" \"/opt/zerobrew/prefix/sbin\"] | uniq)"
+        ));
+        assert!(nu.contains("$env.ZEROBREW_PREFIX = \"/opt/zerobrew/prefix\""));
+    }
+
+    #[test]
+    fn prefix_with_quotes_is_escaped_for_posix_shells() {
+        let out = render(
+            ShellenvShell::Bash,
+            Path::new("/tmp/root"),
+            Path::new("/tmp/it's"),
+        );
+        assert!(out.contains("export ZEROBREW_PREFIX='/tmp/it'\\''s';"));
+    }
+
+    #[test]
+    fn detects_shell_from_path() {
+        assert_eq!(detect_shell("/bin/zsh"), ShellenvShell::Zsh);
+        assert_eq!(detect_shell("/usr/local/bin/fish"), ShellenvShell::Fish);
+        assert_eq!(detect_shell("/home/me/.cargo/bin/nu"), ShellenvShell::Nu);
+        assert_eq!(detect_shell("/bin/dash"), ShellenvShell::Bash);
+        assert_eq!(detect_shell(""), ShellenvShell::Bash);
+    }
+}
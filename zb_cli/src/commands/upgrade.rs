@@ -1,18 +1,18 @@
 use console::style;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
 use std::time::Instant;
-use zb_io::{InstallProgress, ProgressCallback};
 
+use crate::progress::InstallProgressDisplay;
 use crate::ui::StdUi;
 use crate::utils::normalize_formula_name;
 
 pub async fn execute(
     installer: &mut zb_io::Installer,
     formulas: Vec<String>,
+    all: bool,
     build_from_source: bool,
     no_link: bool,
+    fail_fast: bool,
+    no_cleanup: bool,
     ui: &mut StdUi,
 ) -> Result<(), zb_core::Error> {
     let start = Instant::now();
@@ -20,20 +20,33 @@ pub async fn execute(
     // Reported at the end so explicit-arg upgrades still run; we exit
     // non-zero afterwards rather than discard partial progress.
     let mut missing: Vec<String> = Vec::new();
+    let mut pinned: Vec<String> = Vec::new();
 
-    let outdated = if formulas.is_empty() {
+    let outdated = if all || formulas.is_empty() {
         ui.heading("Checking for outdated packages...".to_string())
             .map_err(ui_error)?;
-        let (outdated, warnings) = installer.check_outdated().await?;
-        for warning in &warnings {
+        let selection = installer.select_upgrades().await?;
+        for warning in &selection.warnings {
             eprintln!("{} {}", style("Warning:").yellow().bold(), warning);
         }
-        if outdated.is_empty() {
-            ui.info("All packages are up to date.".to_string())
-                .map_err(ui_error)?;
+        for pkg in &selection.pinned {
+            ui.info(format!(
+                "{} is pinned at {}, skipping {}",
+                pkg.name, pkg.installed_version, pkg.current_version
+            ))
+            .map_err(ui_error)?;
+            pinned.push(pkg.name.clone());
+        }
+        if selection.outdated.is_empty() {
+            if pinned.is_empty() {
+                ui.info("All packages are up to date.".to_string())
+                    .map_err(ui_error)?;
+            } else {
+                print_summary(ui, 0, pinned.len(), 0)?;
+            }
             return Ok(());
         }
-        outdated
+        selection.outdated
     } else {
         let mut normalized = Vec::with_capacity(formulas.len());
         for formula in &formulas {
@@ -41,6 +54,12 @@ pub async fn execute(
         }
         let mut outdated = Vec::new();
         for name in &normalized {
+            if installer.is_pinned(name)? {
+                ui.info(format!("{} is pinned, skipping", name))
+                    .map_err(ui_error)?;
+                pinned.push(name.clone());
+                continue;
+            }
             match installer.is_outdated(name).await {
                 Ok(Some(pkg)) => outdated.push(pkg),
                 Ok(None) => {
@@ -59,8 +78,12 @@ pub async fn execute(
             if let Some(name) = missing.into_iter().next() {
                 return Err(zb_core::Error::NotInstalled { name });
             }
-            ui.info("All specified packages are up to date.".to_string())
-                .map_err(ui_error)?;
+            if pinned.is_empty() {
+                ui.info("All specified packages are up to date.".to_string())
+                    .map_err(ui_error)?;
+            } else {
+                print_summary(ui, 0, pinned.len(), 0)?;
+            }
             return Ok(());
         }
         outdated
@@ -69,101 +92,8 @@ pub async fn execute(
     ui.heading(format!("Upgrading {}...", style(outdated.len()).bold()))
         .map_err(ui_error)?;
 
-    let multi = MultiProgress::new();
-    let bars: Arc<Mutex<HashMap<String, ProgressBar>>> = Arc::new(Mutex::new(HashMap::new()));
-
-    let spinner_style = ProgressStyle::default_spinner()
-        .template("    {prefix:<16} {spinner:.cyan} {msg}")
-        .unwrap()
-        .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏");
-
-    let done_style = ProgressStyle::default_spinner()
-        .template("    {prefix:<16} {msg}")
-        .unwrap();
-
-    let bars_clone = bars.clone();
-    let multi_clone = multi.clone();
-    let spinner_style_clone = spinner_style.clone();
-    let done_style_clone = done_style.clone();
-
-    let progress_callback: Arc<ProgressCallback> = Arc::new(Box::new(move |event| {
-        let mut bars = bars_clone.lock().unwrap();
-        match event {
-            InstallProgress::DownloadStarted { name, total_bytes } => {
-                let pb = if let Some(total) = total_bytes {
-                    let pb = multi_clone.add(ProgressBar::new(total));
-                    pb.set_style(
-                        ProgressStyle::default_bar()
-                            .template("    {prefix:<16} {bar:25.cyan/dim} {bytes:>10}/{total_bytes:<10} {eta:>6}")
-                            .unwrap()
-                            .progress_chars("━━╸"),
-                    );
-                    pb
-                } else {
-                    let pb = multi_clone.add(ProgressBar::new_spinner());
-                    pb.set_style(spinner_style_clone.clone());
-                    pb.set_message("downloading...");
-                    pb.enable_steady_tick(std::time::Duration::from_millis(80));
-                    pb
-                };
-                pb.set_prefix(name.clone());
-                bars.insert(name, pb);
-            }
-            InstallProgress::DownloadProgress {
-                name,
-                downloaded,
-                total_bytes,
-            } => {
-                if let Some(pb) = bars.get(&name)
-                    && total_bytes.is_some()
-                {
-                    pb.set_position(downloaded);
-                }
-            }
-            InstallProgress::DownloadCompleted { name, total_bytes } => {
-                if let Some(pb) = bars.get(&name) {
-                    if total_bytes > 0 {
-                        pb.set_position(total_bytes);
-                    }
-                    pb.set_style(spinner_style_clone.clone());
-                    pb.set_message("unpacking...");
-                    pb.enable_steady_tick(std::time::Duration::from_millis(80));
-                }
-            }
-            InstallProgress::UnpackStarted { name } => {
-                if let Some(pb) = bars.get(&name) {
-                    pb.set_message("unpacking...");
-                }
-            }
-            InstallProgress::UnpackCompleted { name } => {
-                if let Some(pb) = bars.get(&name) {
-                    pb.set_message("unpacked");
-                }
-            }
-            InstallProgress::LinkStarted { name } => {
-                if let Some(pb) = bars.get(&name) {
-                    pb.set_message("linking...");
-                }
-            }
-            InstallProgress::LinkCompleted { name } => {
-                if let Some(pb) = bars.get(&name) {
-                    pb.set_message("linked");
-                }
-            }
-            InstallProgress::LinkSkipped { name, reason } => {
-                if let Some(pb) = bars.get(&name) {
-                    pb.set_message(format!("keg-only ({})", reason));
-                }
-            }
-            InstallProgress::InstallCompleted { name } => {
-                if let Some(pb) = bars.get(&name) {
-                    pb.set_style(done_style_clone.clone());
-                    pb.set_message(format!("{} upgraded", style("✓").green()));
-                    pb.finish();
-                }
-            }
-        }
-    }));
+    let display = InstallProgressDisplay::new("upgraded");
+    let progress_callback = display.callback();
 
     let mut upgraded = 0usize;
     let mut errors: Vec<(String, zb_core::Error)> = Vec::new();
@@ -177,6 +107,7 @@ pub async fn execute(
                 name,
                 build_from_source,
                 !no_link,
+                !no_cleanup,
                 Some(progress_callback.clone()),
             )
             .await
@@ -188,18 +119,14 @@ pub async fn execute(
             Err(e) => {
                 ui.step_fail().map_err(ui_error)?;
                 errors.push((name.clone(), e));
+                if fail_fast {
+                    break;
+                }
             }
         }
     }
 
-    {
-        let bars = bars.lock().unwrap();
-        for (_, pb) in bars.iter() {
-            if !pb.is_finished() {
-                pb.finish();
-            }
-        }
-    }
+    display.finish();
 
     let elapsed = start.elapsed();
     ui.blank_line().map_err(ui_error)?;
@@ -209,6 +136,15 @@ pub async fn execute(
             .map_err(ui_error)?;
     }
 
+    let attempted = upgraded + errors.len();
+    if attempted < outdated.len() {
+        ui.note(format!(
+            "Stopped after the first failure; {} not attempted (--fail-fast)",
+            outdated.len() - attempted
+        ))
+        .map_err(ui_error)?;
+    }
+
     if errors.is_empty() && missing.is_empty() {
         ui.heading(format!(
             "Upgraded {} packages in {:.2}s",
@@ -216,6 +152,10 @@ pub async fn execute(
             elapsed.as_secs_f64()
         ))
         .map_err(ui_error)?;
+    }
+    print_summary(ui, upgraded, pinned.len(), errors.len())?;
+
+    if errors.is_empty() && missing.is_empty() {
         Ok(())
     } else if !errors.is_empty() {
         Err(errors.remove(0).1)
@@ -226,6 +166,19 @@ pub async fn execute(
     }
 }
 
+fn print_summary(
+    ui: &mut StdUi,
+    upgraded: usize,
+    pinned: usize,
+    failed: usize,
+) -> Result<(), zb_core::Error> {
+    ui.println(format!(
+        "    upgraded: {}, pinned-skipped: {}, failed: {}",
+        upgraded, pinned, failed
+    ))
+    .map_err(ui_error)
+}
+
 fn ui_error(err: std::io::Error) -> zb_core::Error {
     zb_core::Error::FileError {
         message: format!("failed to write CLI output: {err}"),
@@ -0,0 +1,47 @@
+use console::style;
+
+use crate::ui::StdUi;
+
+pub fn execute(
+    installer: &mut zb_io::Installer,
+    tool: String,
+    version: String,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    match installer.use_version(&tool, &version) {
+        Ok(active) => {
+            ui.heading(format!(
+                "{} now runs {}",
+                style(&tool).bold(),
+                style(&active).green().bold()
+            ))
+            .map_err(ui_error)?;
+            Ok(())
+        }
+        Err(e @ zb_core::Error::NotInstalled { .. }) => {
+            let installed = installer.installed_versions_of(&tool)?;
+            if installed.is_empty() {
+                ui.note(format!(
+                    "No versions of {} are installed.",
+                    style(&tool).bold()
+                ))
+                .map_err(ui_error)?;
+            } else {
+                ui.note(format!("Installed versions of {}:", style(&tool).bold()))
+                    .map_err(ui_error)?;
+                for keg in &installed {
+                    ui.bullet(format!("{} {}", keg.name, style(&keg.version).dim()))
+                        .map_err(ui_error)?;
+                }
+            }
+            Err(e)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::StoreCorruption {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
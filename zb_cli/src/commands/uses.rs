@@ -0,0 +1,19 @@
+use crate::utils::normalize_formula_name;
+
+pub async fn execute(
+    installer: &mut zb_io::Installer,
+    formula: String,
+    recursive: bool,
+) -> Result<(), zb_core::Error> {
+    let name = normalize_formula_name(&formula)?;
+    let dependents = installer.uses(&name, recursive).await?;
+
+    if dependents.is_empty() {
+        eprintln!("No installed formulas depend on {name}.");
+    }
+    for dependent in dependents {
+        println!("{dependent}");
+    }
+
+    Ok(())
+}
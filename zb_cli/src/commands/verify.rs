@@ -0,0 +1,261 @@
+use console::style;
+use zb_io::{KegVerification, StoreVerification};
+
+use crate::ui::StdUi;
+
+pub fn execute(
+    installer: &mut zb_io::Installer,
+    formulas: Vec<String>,
+    all: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let results = if all {
+        installer.verify_all()?
+    } else {
+        formulas
+            .iter()
+            .map(|name| installer.verify_keg(name))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    let failed = results.iter().filter(|result| !result.is_ok()).count();
+
+    if ui.is_json() {
+        ui.emit(&json_report(&results)).map_err(ui_error)?;
+    } else {
+        if results.is_empty() {
+            ui.println("No formulas installed.").map_err(ui_error)?;
+            return Ok(());
+        }
+
+        for result in &results {
+            let status = if !result.manifest {
+                style("no manifest recorded").yellow()
+            } else if result.is_ok() {
+                style("ok").green()
+            } else {
+                style("FAILED").red().bold()
+            };
+            ui.println(format!(
+                "{} {} {}",
+                style(&result.name).bold(),
+                style(&result.version).dim(),
+                status
+            ))
+            .map_err(ui_error)?;
+            for path in &result.modified {
+                ui.println(format!("    {} {}", style("modified").red(), path))
+                    .map_err(ui_error)?;
+            }
+            for path in &result.missing {
+                ui.println(format!("    {} {}", style("missing").red(), path))
+                    .map_err(ui_error)?;
+            }
+            for path in &result.extra {
+                ui.println(format!("    {} {}", style("extra").yellow(), path))
+                    .map_err(ui_error)?;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(zb_core::Error::ExecutionError {
+            message: format!("verification failed for {failed} formula(s)"),
+        });
+    }
+
+    Ok(())
+}
+
+/// Check the store, optionally repairing damaged entries from their bottles.
+pub async fn execute_store(
+    installer: &mut zb_io::Installer,
+    repair: bool,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let results = installer.verify_store()?;
+    let damaged: Vec<&StoreVerification> = results.iter().filter(|r| !r.is_ok()).collect();
+
+    let mut repaired = Vec::new();
+    let mut repair_failures = Vec::new();
+    if repair {
+        for result in &damaged {
+            match installer.repair_store_entry(result).await {
+                Ok(()) => repaired.push(result.store_key.clone()),
+                Err(e) => repair_failures.push((result.store_key.clone(), e)),
+            }
+        }
+    }
+
+    if ui.is_json() {
+        let mut report = store_json_report(&results);
+        report["repaired"] = serde_json::json!(repaired);
+        ui.emit(&report).map_err(ui_error)?;
+    } else {
+        if results.is_empty() {
+            ui.println("Store is empty.").map_err(ui_error)?;
+            return Ok(());
+        }
+
+        for result in &results {
+            let status = if repaired.contains(&result.store_key) {
+                style("repaired").green()
+            } else if result.entry_missing {
+                style("MISSING").red().bold()
+            } else if !result.manifest {
+                style("no manifest recorded").yellow()
+            } else if result.is_ok() {
+                style("ok").green()
+            } else {
+                style("FAILED").red().bold()
+            };
+            let used_by = if result.formulas.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", result.formulas.join(", "))
+            };
+            ui.println(format!(
+                "{}{} {}",
+                style(&result.store_key[..result.store_key.len().min(12)]).bold(),
+                style(used_by).dim(),
+                status
+            ))
+            .map_err(ui_error)?;
+            for path in &result.modified {
+                ui.println(format!("    {} {}", style("modified").red(), path))
+                    .map_err(ui_error)?;
+            }
+            for path in &result.missing {
+                ui.println(format!("    {} {}", style("missing").red(), path))
+                    .map_err(ui_error)?;
+            }
+            for path in &result.extra {
+                ui.println(format!("    {} {}", style("extra").yellow(), path))
+                    .map_err(ui_error)?;
+            }
+        }
+        for (store_key, err) in &repair_failures {
+            ui.println(format!(
+                "{} {}: {err}",
+                style("could not repair").red(),
+                &store_key[..store_key.len().min(12)]
+            ))
+            .map_err(ui_error)?;
+        }
+        if !repaired.is_empty() {
+            ui.println(
+                "Kegs installed from repaired entries keep their files until reinstalled; \
+                 run `zb reinstall` for the formulas listed above.",
+            )
+            .map_err(ui_error)?;
+        }
+    }
+
+    let failed = damaged.len() - repaired.len();
+    if failed > 0 {
+        let hint = if repair { "" } else { "; run with --repair" };
+        return Err(zb_core::Error::ExecutionError {
+            message: format!("store verification failed for {failed} entry(s){hint}"),
+        });
+    }
+
+    Ok(())
+}
+
+fn store_json_report(results: &[StoreVerification]) -> serde_json::Value {
+    serde_json::json!({
+        "ok": results.iter().all(StoreVerification::is_ok),
+        "entries": results,
+    })
+}
+
+/// Kegs without a recorded manifest are listed with `"manifest": false` but
+/// do not count against the overall `ok`, since there was nothing to compare.
+fn json_report(results: &[KegVerification]) -> serde_json::Value {
+    let formulas: Vec<serde_json::Value> = results
+        .iter()
+        .map(|result| {
+            serde_json::json!({
+                "name": result.name,
+                "version": result.version,
+                "ok": result.is_ok(),
+                "manifest": result.manifest,
+                "modified": result.modified,
+                "missing": result.missing,
+                "extra": result.extra,
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "ok": results.iter().all(KegVerification::is_ok),
+        "formulas": formulas,
+    })
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verified(name: &str, modified: &[&str]) -> KegVerification {
+        KegVerification {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            manifest: true,
+            modified: modified.iter().map(|path| path.to_string()).collect(),
+            missing: Vec::new(),
+            extra: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn json_report_flags_exactly_the_modified_file() {
+        let results = vec![verified("jq", &["bin/jq"]), verified("wget", &[])];
+
+        let report = json_report(&results);
+
+        assert_eq!(report["ok"], false);
+        let formulas = report["formulas"].as_array().unwrap();
+        assert_eq!(formulas[0]["ok"], false);
+        assert_eq!(formulas[0]["modified"], serde_json::json!(["bin/jq"]));
+        assert_eq!(formulas[0]["missing"], serde_json::json!([]));
+        assert_eq!(formulas[0]["extra"], serde_json::json!([]));
+        assert_eq!(formulas[1]["ok"], true);
+        assert_eq!(formulas[1]["modified"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn store_json_report_flags_missing_entries() {
+        let missing = StoreVerification {
+            store_key: "ab".repeat(32),
+            formulas: vec!["jq".to_string()],
+            manifest: true,
+            entry_missing: true,
+            modified: Vec::new(),
+            missing: Vec::new(),
+            extra: Vec::new(),
+        };
+
+        let report = store_json_report(&[missing]);
+
+        assert_eq!(report["ok"], false);
+        assert_eq!(report["entries"][0]["entry_missing"], true);
+        assert_eq!(report["entries"][0]["formulas"], serde_json::json!(["jq"]));
+    }
+
+    #[test]
+    fn json_report_is_ok_without_manifests() {
+        let mut result = verified("jq", &[]);
+        result.manifest = false;
+
+        let report = json_report(&[result]);
+
+        assert_eq!(report["ok"], true);
+        assert_eq!(report["formulas"][0]["manifest"], false);
+    }
+}
@@ -0,0 +1,59 @@
+use console::style;
+
+use crate::ui::StdUi;
+
+/// `zb which`: the formula that put a command on PATH.
+pub fn execute(
+    installer: &mut zb_io::Installer,
+    command: String,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let owner = installer
+        .which(&command)?
+        .ok_or_else(|| zb_core::Error::ExecutionError {
+            message: format!("no installed formula links '{command}' into the prefix"),
+        })?;
+    print_owners(&[owner], ui)
+}
+
+/// `zb provides`: the formulas owning any file in the prefix.
+pub fn execute_provides(
+    installer: &mut zb_io::Installer,
+    path: std::path::PathBuf,
+    ui: &mut StdUi,
+) -> Result<(), zb_core::Error> {
+    let path = std::path::absolute(&path).map_err(zb_core::Error::file("invalid path"))?;
+    let owners = installer.provides(&path)?;
+    if owners.is_empty() {
+        return Err(zb_core::Error::ExecutionError {
+            message: format!("no installed formula provides '{}'", path.display()),
+        });
+    }
+    print_owners(&owners, ui)
+}
+
+fn print_owners(owners: &[zb_io::FileOwner], ui: &mut StdUi) -> Result<(), zb_core::Error> {
+    for owner in owners {
+        ui.emit(&serde_json::json!({
+            "name": owner.name,
+            "version": owner.version,
+            "path": owner.path,
+            "target": owner.target,
+        }))
+        .map_err(ui_error)?;
+        ui.println(format!(
+            "{} {} {}",
+            style(&owner.name).green(),
+            owner.version,
+            style(format!("({})", owner.target.display())).dim()
+        ))
+        .map_err(ui_error)?;
+    }
+    Ok(())
+}
+
+fn ui_error(err: std::io::Error) -> zb_core::Error {
+    zb_core::Error::FileError {
+        message: format!("failed to write CLI output: {err}"),
+    }
+}
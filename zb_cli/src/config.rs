@@ -0,0 +1,393 @@
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use std::path::{Path, PathBuf};
+
+use crate::cli::Cli;
+
+/// Defaults read from `~/.config/zerobrew/config.toml`. Each one applies
+/// only where neither a flag nor its environment variable gave a value.
+///
+/// ```toml
+/// root = "/opt/zerobrew"
+/// prefix = "/opt/zerobrew/prefix"
+/// concurrency = 8      # or download_concurrency
+/// max_download_rate = "5MB/s"
+/// mirrors = ["https://mirror.example/v2"]
+/// proxy = "http://proxy.internal:3128"
+/// ca_bundle = "/etc/ssl/corp-root.pem"
+/// color = "never"        # "auto", "always" or "never"
+/// auto_init = true
+/// ```
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    pub root: Option<PathBuf>,
+    pub prefix: Option<PathBuf>,
+    pub concurrency: Option<usize>,
+    pub max_download_rate: Option<u64>,
+    pub mirrors: Vec<String>,
+    pub proxy: Option<String>,
+    pub ca_bundle: Option<PathBuf>,
+    pub color: Option<ColorChoice>,
+    pub auto_init: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<String>),
+}
+
+impl Config {
+    /// `ZEROBREW_CONFIG` if set, else `$XDG_CONFIG_HOME/zerobrew/config.toml`,
+    /// else `~/.config/zerobrew/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("ZEROBREW_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("zerobrew").join("config.toml"))
+    }
+
+    /// Load the config file at `path`. A missing file is an empty config.
+    pub fn load(path: &Path) -> Result<Self, zb_core::Error> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(zb_core::Error::FileError {
+                    message: format!("failed to read config '{}': {e}", path.display()),
+                });
+            }
+        };
+
+        Self::parse(&contents).map_err(|message| zb_core::Error::InvalidArgument {
+            message: format!("invalid config '{}': {message}", path.display()),
+        })
+    }
+
+    fn parse(contents: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, raw) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {line_number}: expected `key = value`"))?;
+            let key = key.trim();
+            let value = parse_value(raw.trim())
+                .map_err(|message| format!("line {line_number}: {message}"))?;
+
+            config
+                .set(key, value)
+                .map_err(|message| format!("line {line_number}: {message}"))?;
+        }
+
+        Ok(config)
+    }
+
+    fn set(&mut self, key: &str, value: Value) -> Result<(), String> {
+        match (key, value) {
+            ("root", Value::String(root)) => self.root = Some(PathBuf::from(root)),
+            ("prefix", Value::String(prefix)) => self.prefix = Some(PathBuf::from(prefix)),
+            ("concurrency" | "download_concurrency", Value::Integer(concurrency)) => {
+                if concurrency < 1 {
+                    return Err("concurrency must be at least 1".to_string());
+                }
+                self.concurrency = Some(concurrency as usize);
+            }
+            ("max_download_rate", Value::String(rate)) => {
+                self.max_download_rate = Some(crate::cli::parse_download_rate(&rate)?);
+            }
+            ("max_download_rate", Value::Integer(rate)) => {
+                self.max_download_rate = Some(crate::cli::parse_download_rate(&rate.to_string())?);
+            }
+            ("mirrors", Value::Array(mirrors)) => self.mirrors = mirrors,
+            ("mirrors", Value::String(mirror)) => self.mirrors = vec![mirror],
+            ("proxy", Value::String(proxy)) => self.proxy = Some(proxy),
+            ("ca_bundle", Value::String(path)) => self.ca_bundle = Some(PathBuf::from(path)),
+            ("color", Value::String(color)) => {
+                self.color = Some(match color.as_str() {
+                    "auto" => ColorChoice::Auto,
+                    "always" => ColorChoice::Always,
+                    "never" => ColorChoice::Never,
+                    _ => {
+                        return Err(format!(
+                            "color must be \"auto\", \"always\" or \"never\", not \"{color}\""
+                        ));
+                    }
+                });
+            }
+            ("auto_init", Value::Bool(auto_init)) => self.auto_init = Some(auto_init),
+            (
+                "root"
+                | "prefix"
+                | "concurrency"
+                | "download_concurrency"
+                | "max_download_rate"
+                | "mirrors"
+                | "proxy"
+                | "ca_bundle"
+                | "color"
+                | "auto_init",
+                value,
+            ) => return Err(format!("unexpected value for `{key}`: {value:?}")),
+            _ => return Err(format!("unknown key `{key}`")),
+        }
+        Ok(())
+    }
+
+    /// Fill in whatever `cli` left at its default, given the `matches` it was
+    /// parsed from. Flags and their environment variables always win.
+    pub fn apply(&self, cli: &mut Cli, matches: &ArgMatches) {
+        let unset = |id: &str| {
+            matches!(
+                matches.value_source(id),
+                None | Some(ValueSource::DefaultValue)
+            )
+        };
+
+        if cli.root.is_none() {
+            cli.root = self.root.clone();
+        }
+        if cli.prefix.is_none() && std::env::var_os("ZEROBREW_PREFIX").is_none() {
+            cli.prefix = self.prefix.clone();
+        }
+        if let Some(concurrency) = self.concurrency
+            && unset("concurrency")
+        {
+            cli.concurrency = concurrency;
+        }
+        if cli.max_download_rate.is_none() {
+            cli.max_download_rate = self.max_download_rate;
+        }
+        if unset("mirrors") {
+            cli.mirrors = self.mirrors.clone();
+        }
+        if cli.proxy.is_none() {
+            cli.proxy = self.proxy.clone();
+        }
+        if cli.ca_bundle.is_none() && !cli.insecure {
+            cli.ca_bundle = self.ca_bundle.clone();
+        }
+        if let Some(auto_init) = self.auto_init
+            && unset("auto_init")
+        {
+            cli.auto_init = auto_init;
+        }
+    }
+
+    /// Force colored output on or off, unless `NO_COLOR` or `CLICOLOR_FORCE`
+    /// already decided it.
+    pub fn apply_color(&self) {
+        if std::env::var_os("NO_COLOR").is_some() || std::env::var_os("CLICOLOR_FORCE").is_some() {
+            return;
+        }
+        let enabled = match self.color {
+            Some(ColorChoice::Always) => true,
+            Some(ColorChoice::Never) => false,
+            Some(ColorChoice::Auto) | None => return,
+        };
+        console::set_colors_enabled(enabled);
+        console::set_colors_enabled_stderr(enabled);
+    }
+}
+
+/// Parse the subset of TOML values the config uses: strings, integers,
+/// booleans and arrays of strings, each optionally followed by a comment.
+fn parse_value(raw: &str) -> Result<Value, String> {
+    let (value, rest) = if let Some(inner) = raw.strip_prefix('[') {
+        let mut items = Vec::new();
+        let mut rest = inner.trim_start();
+        loop {
+            if let Some(after) = rest.strip_prefix(']') {
+                break (Value::Array(items), after);
+            }
+            let (item, after) = parse_string(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after.trim_start();
+            } else if !rest.starts_with(']') {
+                return Err("expected `,` or `]` in array".to_string());
+            }
+        }
+    } else if raw.starts_with('"') || raw.starts_with('\'') {
+        let (string, rest) = parse_string(raw)?;
+        (Value::String(string), rest)
+    } else {
+        let end = raw.find('#').unwrap_or(raw.len());
+        let (token, rest) = raw.split_at(end);
+        let value = match token.trim() {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            token => Value::Integer(
+                token
+                    .replace('_', "")
+                    .parse()
+                    .map_err(|_| format!("unsupported value `{token}`"))?,
+            ),
+        };
+        (value, rest)
+    };
+
+    let rest = rest.trim();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("unexpected `{rest}` after value"));
+    }
+    Ok(value)
+}
+
+/// Parse a basic (`"..."`) or literal (`'...'`) string at the start of
+/// `raw`, returning it and whatever follows the closing quote.
+fn parse_string(raw: &str) -> Result<(String, &str), String> {
+    if let Some(inner) = raw.strip_prefix('\'') {
+        let end = inner
+            .find('\'')
+            .ok_or_else(|| "unterminated string".to_string())?;
+        return Ok((inner[..end].to_string(), &inner[end + 1..]));
+    }
+
+    let inner = raw
+        .strip_prefix('"')
+        .ok_or_else(|| "expected a string".to_string())?;
+    let mut value = String::new();
+    let mut chars = inner.char_indices();
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            '"' => return Ok((value, &inner[index + 1..])),
+            '\\' => match chars.next().map(|(_, escaped)| escaped) {
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(other) => return Err(format!("unsupported escape `\\{other}`")),
+                None => break,
+            },
+            _ => value.push(ch),
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+    use tempfile::TempDir;
+
+    fn parse_cli(args: &[&str]) -> (Cli, ArgMatches) {
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        let cli = Cli::from_arg_matches(&matches).unwrap();
+        (cli, matches)
+    }
+
+    #[test]
+    fn parses_every_setting() {
+        let config = Config::parse(
+            r#"
+            # zerobrew defaults
+            root = "/opt/zb"
+            prefix = '/opt/zb/prefix'
+            concurrency = 8   # fewer connections on this network
+            max_download_rate = "5MB/s"
+            mirrors = ["https://a.example/v2", "b.example",]
+            proxy = "http://proxy.internal:3128"
+            ca_bundle = "/etc/ssl/corp.pem"
+            color = "never"
+            auto_init = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                root: Some(PathBuf::from("/opt/zb")),
+                prefix: Some(PathBuf::from("/opt/zb/prefix")),
+                concurrency: Some(8),
+                max_download_rate: Some(5_000_000),
+                mirrors: vec!["https://a.example/v2".to_string(), "b.example".to_string()],
+                proxy: Some("http://proxy.internal:3128".to_string()),
+                ca_bundle: Some(PathBuf::from("/etc/ssl/corp.pem")),
+                color: Some(ColorChoice::Never),
+                auto_init: Some(true),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_mistyped_values() {
+        let err = Config::parse("concurency = 8").unwrap_err();
+        assert!(err.contains("line 1: unknown key `concurency`"), "{err}");
+
+        let err = Config::parse("\nconcurrency = \"8\"").unwrap_err();
+        assert!(err.contains("line 2"), "{err}");
+
+        assert!(Config::parse("concurrency = 0").is_err());
+        assert!(Config::parse("max_download_rate = \"fast\"").is_err());
+        assert!(Config::parse("color = \"sometimes\"").is_err());
+        assert!(Config::parse("root = \"/opt/zb").is_err());
+    }
+
+    #[test]
+    fn missing_file_is_an_empty_config() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::load(&tmp.path().join("config.toml")).unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn file_values_fill_in_defaults() {
+        let config =
+            Config::parse("download_concurrency = 4\nauto_init = true\nmirrors = [\"m.example\"]")
+                .unwrap();
+        let (mut cli, matches) = parse_cli(&["zb", "list"]);
+        config.apply(&mut cli, &matches);
+
+        assert_eq!(cli.concurrency, 4);
+        assert!(cli.auto_init);
+        assert_eq!(cli.mirrors, ["m.example"]);
+    }
+
+    #[test]
+    fn flags_override_file_values() {
+        let config = Config::parse(
+            "root = \"/from/file\"\nconcurrency = 4\nproxy = \"http://file:1\"\nmirrors = \"m.example\"",
+        )
+        .unwrap();
+        let (mut cli, matches) = parse_cli(&[
+            "zb",
+            "--root",
+            "/from/flag",
+            "--concurrency",
+            "12",
+            "--proxy",
+            "http://flag:1",
+            "--mirror",
+            "flag.example",
+            "list",
+        ]);
+        config.apply(&mut cli, &matches);
+
+        assert_eq!(cli.root, Some(PathBuf::from("/from/flag")));
+        assert_eq!(cli.concurrency, 12);
+        assert_eq!(cli.proxy.as_deref(), Some("http://flag:1"));
+        assert_eq!(cli.mirrors, ["flag.example"]);
+    }
+}
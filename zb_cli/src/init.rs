@@ -1,5 +1,6 @@
 use console::style;
 use std::io::Write;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
@@ -195,20 +196,45 @@ pub fn run_init(
 const ZB_BLOCK_START: &str = "# >>> zerobrew >>>";
 const ZB_BLOCK_END: &str = "# <<< zerobrew <<<";
 
-fn upsert_managed_block(existing: &str, managed_block: &str) -> String {
-    if let Some(start_idx) = existing.find(ZB_BLOCK_START)
-        && let Some(end_rel_idx) = existing[start_idx..].find(ZB_BLOCK_END)
-    {
-        let mut end_idx = start_idx + end_rel_idx + ZB_BLOCK_END.len();
-        if existing[end_idx..].starts_with("\r\n") {
-            end_idx += 2;
-        } else if existing[end_idx..].starts_with('\n') {
-            end_idx += 1;
+/// Byte ranges of every complete managed block in `content`, each including
+/// its end marker's line break. Markers only count on a line of their own,
+/// so a marker quoted inside another line doesn't open a block.
+fn managed_block_ranges(content: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut open = None;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let end = offset + line.len();
+        match line.trim() {
+            ZB_BLOCK_START => open = Some(offset),
+            ZB_BLOCK_END => {
+                if let Some(start) = open.take() {
+                    ranges.push(start..end);
+                }
+            }
+            _ => {}
         }
+        offset = end;
+    }
+
+    ranges
+}
+
+/// Replace the first managed block with `managed_block`, dropping any later
+/// copies, or append it when there is none.
+fn upsert_managed_block(existing: &str, managed_block: &str) -> String {
+    let ranges = managed_block_ranges(existing);
+    if let Some(first) = ranges.first() {
         let mut out = String::with_capacity(existing.len() + managed_block.len());
-        out.push_str(&existing[..start_idx]);
+        out.push_str(&existing[..first.start]);
         out.push_str(managed_block);
-        out.push_str(&existing[end_idx..]);
+        let mut cursor = first.end;
+        for range in &ranges[1..] {
+            out.push_str(&existing[cursor..range.start]);
+            cursor = range.end;
+        }
+        out.push_str(&existing[cursor..]);
         return out;
     }
 
@@ -225,7 +251,91 @@ fn upsert_managed_block(existing: &str, managed_block: &str) -> String {
     }
 }
 
-fn posix_shell_quote(value: &str) -> String {
+/// zerobrew PATH setup in a shell config beyond the single managed block.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct ShellConfigDuplicates {
+    /// Managed blocks after the first one.
+    pub extra_blocks: usize,
+    /// zerobrew exports outside any managed block, e.g. from a hand-edited
+    /// config or an installer that predates the markers.
+    pub stray_lines: Vec<String>,
+}
+
+impl ShellConfigDuplicates {
+    pub fn is_empty(&self) -> bool {
+        self.extra_blocks == 0 && self.stray_lines.is_empty()
+    }
+
+    pub fn count(&self) -> usize {
+        self.extra_blocks + self.stray_lines.len()
+    }
+}
+
+/// Lines we write into the managed block that would shadow or repeat it if
+/// they also appeared outside it.
+fn is_stray_path_line(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("export ZEROBREW_")
+        || line.starts_with("set -gx ZEROBREW_")
+        || line.starts_with("_zb_path_append ")
+        || ((line.starts_with("export PATH=") || line.starts_with("set -gx PATH "))
+            && line.contains("ZEROBREW_"))
+}
+
+/// Outside-of-block lines in `content`, with the byte range of each.
+fn stray_path_lines(content: &str) -> Vec<(Range<usize>, &str)> {
+    let blocks = managed_block_ranges(content);
+    let mut stray = Vec::new();
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let end = offset + line.len();
+        let in_block = blocks.iter().any(|block| block.contains(&offset));
+        if !in_block && is_stray_path_line(line) {
+            stray.push((offset..end, line.trim_end()));
+        }
+        offset = end;
+    }
+
+    stray
+}
+
+pub(crate) fn find_duplicate_path_entries(content: &str) -> ShellConfigDuplicates {
+    ShellConfigDuplicates {
+        extra_blocks: managed_block_ranges(content).len().saturating_sub(1),
+        stray_lines: stray_path_lines(content)
+            .into_iter()
+            .map(|(_, line)| line.to_string())
+            .collect(),
+    }
+}
+
+/// Reduce `content` to a single zerobrew entry: the first managed block,
+/// which is the one `zb init` rewrites, is kept where it is and every other
+/// block and stray zerobrew line is removed.
+pub(crate) fn consolidate_path_entries(content: &str) -> String {
+    let mut removed: Vec<Range<usize>> = managed_block_ranges(content)
+        .into_iter()
+        .skip(1)
+        .chain(
+            stray_path_lines(content)
+                .into_iter()
+                .map(|(range, _)| range),
+        )
+        .collect();
+    removed.sort_by_key(|range| range.start);
+
+    let mut out = String::with_capacity(content.len());
+    let mut cursor = 0;
+    for range in removed {
+        out.push_str(&content[cursor..range.start]);
+        cursor = range.end;
+    }
+    out.push_str(&content[cursor..]);
+    out
+}
+
+pub(crate) fn posix_shell_quote(value: &str) -> String {
     if value.is_empty() {
         return "''".to_string();
     }
@@ -233,7 +343,7 @@ fn posix_shell_quote(value: &str) -> String {
     format!("'{}'", value.replace('\'', "'\\''"))
 }
 
-fn fish_shell_quote(value: &str) -> String {
+pub(crate) fn fish_shell_quote(value: &str) -> String {
     format!(
         "\"{}\"",
         value
@@ -243,24 +353,18 @@ fn fish_shell_quote(value: &str) -> String {
     )
 }
 
-fn add_to_path(
-    prefix: &Path,
-    zerobrew_dir: &str,
-    zerobrew_bin: &str,
-    root: &Path,
-    no_modify_path: bool,
-    ui: &mut StdUi,
-) -> Result<(), InitError> {
-    #[derive(Clone, Copy)]
-    enum ShellConfigKind {
-        Posix,
-        Fish,
-    }
+#[derive(Clone, Copy)]
+enum ShellConfigKind {
+    Posix,
+    Fish,
+}
 
+/// The rc file `zb init` writes to for the current `$SHELL`.
+fn detect_shell_config() -> Result<(String, ShellConfigKind), InitError> {
     let shell = std::env::var("SHELL").unwrap_or_default();
     let home = std::env::var("HOME").map_err(|_| InitError::Message("HOME not set".to_string()))?;
 
-    let (config_file, shell_kind) = if shell.contains("zsh") {
+    let config = if shell.contains("zsh") {
         let zdotdir = std::env::var("ZDOTDIR").unwrap_or_else(|_| home.clone());
         let zshenv = format!("{}/.zshenv", zdotdir);
         let zshrc = format!("{}/.zshrc", zdotdir);
@@ -289,10 +393,28 @@ fn add_to_path(
         (format!("{}/.profile", home), ShellConfigKind::Posix)
     };
 
+    Ok(config)
+}
+
+pub(crate) fn shell_config_path() -> Option<PathBuf> {
+    detect_shell_config()
+        .ok()
+        .map(|(config_file, _)| PathBuf::from(config_file))
+}
+
+fn add_to_path(
+    prefix: &Path,
+    zerobrew_dir: &str,
+    zerobrew_bin: &str,
+    root: &Path,
+    no_modify_path: bool,
+    ui: &mut StdUi,
+) -> Result<(), InitError> {
+    let (config_file, shell_kind) = detect_shell_config()?;
+
     let prefix_bin = prefix.join("bin");
     let root_str = root.display().to_string();
     let prefix_str = prefix.display().to_string();
-    let prefix_bin_str = prefix_bin.display().to_string();
     let existing_config = std::fs::read_to_string(&config_file).unwrap_or_default();
 
     if !no_modify_path {
@@ -445,53 +567,26 @@ end
         }
     } else if no_modify_path {
         ui.info("Skipped shell configuration (--no-modify-path)")?;
-        match shell_kind {
-            ShellConfigKind::Posix => {
-                ui.info("Run this in your current shell:")?;
-                ui.println(format!(
-                    "    export ZEROBREW_DIR={}",
-                    posix_shell_quote(zerobrew_dir)
-                ))?;
-                ui.println(format!(
-                    "    export ZEROBREW_ROOT={}",
-                    posix_shell_quote(&root_str)
-                ))?;
-                ui.println(format!(
-                    "    export ZEROBREW_PREFIX={}",
-                    posix_shell_quote(&prefix_str)
-                ))?;
-                ui.println(format!(
-                    "    export PATH={}:{}:$PATH",
-                    posix_shell_quote(zerobrew_bin),
-                    posix_shell_quote(&prefix_bin_str)
-                ))?;
-            }
-            ShellConfigKind::Fish => {
-                ui.info("Run this in your current shell:")?;
-                ui.println(format!(
-                    "    set -gx ZEROBREW_DIR {}",
-                    fish_shell_quote(zerobrew_dir)
-                ))?;
-                ui.println(format!(
-                    "    set -gx ZEROBREW_ROOT {}",
-                    fish_shell_quote(&root_str)
-                ))?;
-                ui.println(format!(
-                    "    set -gx ZEROBREW_PREFIX {}",
-                    fish_shell_quote(&prefix_str)
-                ))?;
-                ui.println(format!(
-                    "    set -gx PATH {} {} $PATH",
-                    fish_shell_quote(zerobrew_bin),
-                    fish_shell_quote(&prefix_bin_str)
-                ))?;
-            }
-        }
+        ui.info(format!(
+            "To set up your shell, add this line to {}:",
+            config_file
+        ))?;
+        ui.println(format!("    {}", shellenv_line(shell_kind, zerobrew_bin)))?;
     }
 
     Ok(())
 }
 
+/// The rc-file line that sets up a shell through `zb shellenv`, for users
+/// who manage their own shell configuration.
+fn shellenv_line(shell_kind: ShellConfigKind, zerobrew_bin: &str) -> String {
+    let zb = format!("{}/zb", zerobrew_bin.trim_end_matches('/'));
+    match shell_kind {
+        ShellConfigKind::Posix => format!("eval \"$({} shellenv)\"", posix_shell_quote(&zb)),
+        ShellConfigKind::Fish => format!("{} shellenv fish | source", fish_shell_quote(&zb)),
+    }
+}
+
 pub fn ensure_init(
     root: &Path,
     prefix: &Path,
@@ -1087,4 +1182,78 @@ mod tests {
         assert!(first.contains("# <<< zerobrew <<<\npostfix\n"));
         assert!(!first.contains("# <<< zerobrew <<<\n\npostfix\n"));
     }
+
+    #[test]
+    fn upsert_managed_block_drops_later_copies() {
+        let managed_block =
+            format!("{ZB_BLOCK_START}\n# zerobrew\nexport ZEROBREW_DIR=/new\n{ZB_BLOCK_END}\n");
+        let existing = format!(
+            "a\n{ZB_BLOCK_START}\nexport ZEROBREW_DIR=/one\n{ZB_BLOCK_END}\nb\n\
+             {ZB_BLOCK_START}\nexport ZEROBREW_DIR=/two\n{ZB_BLOCK_END}\nc\n\
+             echo '{ZB_BLOCK_START}'\n"
+        );
+
+        let updated = upsert_managed_block(&existing, &managed_block);
+
+        assert_eq!(
+            updated,
+            format!("a\n{managed_block}b\nc\necho '{ZB_BLOCK_START}'\n")
+        );
+    }
+
+    #[test]
+    fn duplicate_path_entries_are_consolidated_to_the_first_block() {
+        let rc = format!(
+            "export KEEP_ME=true\n\
+             export ZEROBREW_PREFIX='/old/prefix'\n\
+             export PATH=\"$ZEROBREW_PREFIX/bin:$PATH\"\n\
+             {ZB_BLOCK_START}\n\
+             export ZEROBREW_PREFIX='/opt/zerobrew/prefix'\n\
+             _zb_path_append \"$ZEROBREW_PREFIX/bin\"\n\
+             {ZB_BLOCK_END}\n\
+             alias ll='ls -l'\n\
+             {ZB_BLOCK_START}\n\
+             export ZEROBREW_PREFIX='/stale/prefix'\n\
+             _zb_path_append \"$ZEROBREW_PREFIX/bin\"\n\
+             {ZB_BLOCK_END}\n\
+             _zb_path_append \"$ZEROBREW_BIN\"\n"
+        );
+
+        let found = find_duplicate_path_entries(&rc);
+        assert_eq!(found.extra_blocks, 1);
+        assert_eq!(
+            found.stray_lines,
+            vec![
+                "export ZEROBREW_PREFIX='/old/prefix'",
+                "export PATH=\"$ZEROBREW_PREFIX/bin:$PATH\"",
+                "_zb_path_append \"$ZEROBREW_BIN\"",
+            ]
+        );
+
+        let fixed = consolidate_path_entries(&rc);
+        assert_eq!(
+            fixed,
+            format!(
+                "export KEEP_ME=true\n\
+                 {ZB_BLOCK_START}\n\
+                 export ZEROBREW_PREFIX='/opt/zerobrew/prefix'\n\
+                 _zb_path_append \"$ZEROBREW_PREFIX/bin\"\n\
+                 {ZB_BLOCK_END}\n\
+                 alias ll='ls -l'\n"
+            )
+        );
+        assert!(find_duplicate_path_entries(&fixed).is_empty());
+    }
+
+    #[test]
+    fn no_modify_path_points_at_shellenv() {
+        assert_eq!(
+            shellenv_line(ShellConfigKind::Posix, "/home/u/.local/bin/"),
+            "eval \"$('/home/u/.local/bin/zb' shellenv)\""
+        );
+        assert_eq!(
+            shellenv_line(ShellConfigKind::Fish, "/home/u/.local/bin"),
+            "\"/home/u/.local/bin/zb\" shellenv fish | source"
+        );
+    }
 }
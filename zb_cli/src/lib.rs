@@ -1,6 +1,8 @@
 pub mod cli;
 pub mod commands;
+pub mod config;
 pub mod init;
 pub mod logging;
+pub mod progress;
 pub mod ui;
 pub mod utils;
@@ -1,42 +1,141 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tracing::debug;
 use tracing::level_filters::LevelFilter;
-use tracing_subscriber::EnvFilter;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+use zb_core::{LogLevel, LoggerHandle};
+
+/// The log file is rotated when a run starts and finds it this large.
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+/// How many rotated logs (`zb.log.1`, `zb.log.2`, ...) are kept.
+const KEEP_ROTATED: usize = 3;
+/// What the log file records whatever the console shows: every operation
+/// zerobrew traces, plus the HTTP client's requests.
+const FILE_DIRECTIVES: &str = "info,zb_core=trace,zb_io=trace,zb_cli=trace,zb=trace,reqwest=debug";
 
-pub fn init(verbose: u8, quiet: bool) {
-    let level = if quiet {
-        LevelFilter::ERROR
+pub fn init(logger: &LoggerHandle) {
+    let console_filter = EnvFilter::builder()
+        .with_default_directive(level_filter(logger.level).into())
+        .from_env_lossy();
+    let console = if logger.debug {
+        tracing_subscriber::fmt::layer().boxed()
     } else {
-        match verbose {
-            0 => LevelFilter::WARN,
-            1 => LevelFilter::INFO,
-            2 => LevelFilter::DEBUG,
-            _ => LevelFilter::TRACE,
-        }
+        tracing_subscriber::fmt::layer()
+            .with_target(false)
+            .without_time()
+            .boxed()
     };
 
-    let filter = EnvFilter::builder()
-        .with_default_directive(level.into())
-        .from_env_lossy();
+    let file = logger.file.as_deref().and_then(open_log).map(|file| {
+        tracing_subscriber::fmt::layer()
+            .with_writer(Mutex::new(file))
+            .with_ansi(false)
+            .with_filter(EnvFilter::new(FILE_DIRECTIVES))
+    });
+
+    let initialized = tracing_subscriber::registry()
+        .with(console.with_filter(console_filter))
+        .with(file)
+        .try_init()
+        .is_ok();
+    if initialized {
+        debug!(
+            version = env!("CARGO_PKG_VERSION"),
+            args = ?std::env::args().collect::<Vec<_>>(),
+            "zb started"
+        );
+    }
+}
+
+fn level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    }
+}
+
+/// Open `path` for appending, rotating it first if it has grown too large.
+/// The logs directory is only created inside an existing root, so logging
+/// never creates the root ahead of `zb init`. `None` if it can't be opened;
+/// a run is never failed over its log.
+fn open_log(path: &Path) -> Option<File> {
+    let dir = path.parent()?;
+    if let Err(e) = fs::create_dir(dir)
+        && e.kind() != std::io::ErrorKind::AlreadyExists
+    {
+        return None;
+    }
+    rotate(path);
+    OpenOptions::new().create(true).append(true).open(path).ok()
+}
+
+fn rotate(path: &Path) {
+    let Ok(meta) = fs::metadata(path) else {
+        return;
+    };
+    if meta.len() < MAX_LOG_BYTES {
+        return;
+    }
+    for n in (1..KEEP_ROTATED).rev() {
+        let _ = fs::rename(rotated(path, n), rotated(path, n + 1));
+    }
+    let _ = fs::rename(path, rotated(path, 1));
+}
 
-    let _ = tracing_subscriber::registry()
-        .with(filter)
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_target(false)
-                .without_time(),
-        )
-        .try_init();
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::init;
+    use super::*;
+    use tempfile::TempDir;
 
     #[test]
     fn init_is_idempotent() {
-        init(0, false);
-        init(2, false);
-        init(0, true);
+        init(&LoggerHandle::from_flags(0, false, false));
+        init(&LoggerHandle::from_flags(2, false, true));
+        init(&LoggerHandle::from_flags(0, true, false));
+    }
+
+    #[test]
+    fn log_is_only_opened_inside_an_existing_root() {
+        let tmp = TempDir::new().unwrap();
+        assert!(open_log(&tmp.path().join("missing/logs/zb.log")).is_none());
+        assert!(!tmp.path().join("missing").exists());
+
+        let log = tmp.path().join("logs/zb.log");
+        assert!(open_log(&log).is_some());
+        assert!(log.exists());
+    }
+
+    #[test]
+    fn oversized_log_is_rotated() {
+        let tmp = TempDir::new().unwrap();
+        let log = tmp.path().join("zb.log");
+        fs::write(rotated(&log, 1), "older").unwrap();
+        fs::write(rotated(&log, KEEP_ROTATED), "oldest").unwrap();
+        File::create(&log).unwrap().set_len(MAX_LOG_BYTES).unwrap();
+
+        rotate(&log);
+
+        assert!(!log.exists());
+        assert_eq!(fs::metadata(rotated(&log, 1)).unwrap().len(), MAX_LOG_BYTES);
+        assert_eq!(fs::read_to_string(rotated(&log, 2)).unwrap(), "older");
+        assert!(!rotated(&log, KEEP_ROTATED + 1).exists());
+
+        fs::write(&log, "small").unwrap();
+        rotate(&log);
+        assert_eq!(fs::read_to_string(&log).unwrap(), "small");
     }
 }
@@ -0,0 +1,335 @@
+use console::style;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use zb_core::ConflictedLink;
+use zb_io::{InstallProgress, LinkConflictDecision, LinkConflictResolver, ProgressCallback};
+
+/// Progress for a batch of installs: one line per formula that moves from
+/// downloading through unpacking and linking, plus a `total` line summing
+/// every sized download once more than one is in flight.
+///
+/// Downloads report as the installer starts them, so no more bars are ever
+/// active than `--concurrency` allows.
+pub struct InstallProgressDisplay {
+    multi: MultiProgress,
+    state: Arc<Mutex<DisplayState>>,
+    done_label: &'static str,
+}
+
+#[derive(Default)]
+struct DisplayState {
+    bars: HashMap<String, ProgressBar>,
+    downloads: HashMap<String, DownloadTally>,
+    total: Option<ProgressBar>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct DownloadTally {
+    downloaded: u64,
+    total: u64,
+}
+
+impl InstallProgressDisplay {
+    /// `done_label` finishes each line, e.g. "installed" or "upgraded".
+    pub fn new(done_label: &'static str) -> Self {
+        Self {
+            multi: MultiProgress::new(),
+            state: Arc::new(Mutex::new(DisplayState::default())),
+            done_label,
+        }
+    }
+
+    pub fn callback(&self) -> Arc<ProgressCallback> {
+        let multi = self.multi.clone();
+        let state = self.state.clone();
+        let done_label = self.done_label;
+
+        Arc::new(Box::new(move |event| {
+            let mut state = state.lock().unwrap();
+            state.apply(&multi, event, done_label);
+        }))
+    }
+
+    /// Ask on the terminal what to do about an install's link conflicts,
+    /// with the bars paused so they don't draw over the question.
+    pub fn conflict_resolver(&self) -> LinkConflictResolver {
+        let multi = self.multi.clone();
+        Box::new(move |name, conflicts| {
+            multi.suspend(|| prompt_link_conflict(name, conflicts, &mut io::stdin().lock()))
+        })
+    }
+
+    /// Stop every line still animating, e.g. after a failed install.
+    pub fn finish(&self) {
+        let state = self.state.lock().unwrap();
+        for pb in state.bars.values().chain(state.total.iter()) {
+            if !pb.is_finished() {
+                pb.finish();
+            }
+        }
+    }
+}
+
+/// The installer-wide subscriber: says so on stderr when an install has to
+/// queue behind another zerobrew process. Everything else is left to the
+/// per-command displays.
+pub fn lock_notice() -> Arc<ProgressCallback> {
+    Arc::new(Box::new(|event| {
+        if let InstallProgress::WaitingForLock { holder } = event {
+            let holder = holder
+                .map(|pid| format!(" (pid {pid})"))
+                .unwrap_or_default();
+            eprintln!("waiting for other zerobrew process{holder}...");
+        }
+    }))
+}
+
+fn prompt_link_conflict(
+    name: &str,
+    conflicts: &[ConflictedLink],
+    input: &mut impl BufRead,
+) -> LinkConflictDecision {
+    let mut err = io::stderr().lock();
+    let _ = writeln!(
+        err,
+        "{} linking {} would replace files already in the prefix:",
+        style("Warning:").yellow().bold(),
+        style(name).bold()
+    );
+    for conflict in conflicts {
+        let _ = match &conflict.owned_by {
+            Some(owner) => writeln!(
+                err,
+                "  {} (linked by {})",
+                conflict.path.display(),
+                style(owner).yellow()
+            ),
+            None => writeln!(err, "  {}", conflict.path.display()),
+        };
+    }
+    let _ = write!(
+        err,
+        "[s]kip these files, [o]verwrite them, [a]lways overwrite for {name}, or a[b]ort? [b] "
+    );
+    let _ = err.flush();
+
+    let mut answer = String::new();
+    if input.read_line(&mut answer).is_err() {
+        return LinkConflictDecision::Abort;
+    }
+    parse_conflict_answer(&answer)
+}
+
+fn parse_conflict_answer(answer: &str) -> LinkConflictDecision {
+    match answer.trim().to_ascii_lowercase().as_str() {
+        "s" | "skip" => LinkConflictDecision::Skip,
+        "o" | "overwrite" => LinkConflictDecision::Overwrite,
+        "a" | "always" => LinkConflictDecision::AlwaysOverwrite,
+        _ => LinkConflictDecision::Abort,
+    }
+}
+
+impl DisplayState {
+    fn apply(&mut self, multi: &MultiProgress, event: InstallProgress, done_label: &str) {
+        match event {
+            InstallProgress::DownloadStarted { name, total_bytes } => {
+                let pb = match total_bytes {
+                    Some(total) => {
+                        self.downloads.insert(
+                            name.clone(),
+                            DownloadTally {
+                                downloaded: 0,
+                                total,
+                            },
+                        );
+                        let pb = self.add_bar(multi, ProgressBar::new(total));
+                        pb.set_style(download_style());
+                        pb
+                    }
+                    None => {
+                        let pb = self.add_bar(multi, ProgressBar::new_spinner());
+                        pb.set_style(spinner_style());
+                        pb.set_message("downloading...");
+                        pb.enable_steady_tick(Duration::from_millis(80));
+                        pb
+                    }
+                };
+                pb.set_prefix(name.clone());
+                self.bars.insert(name, pb);
+
+                if self.total.is_none() && self.downloads.len() > 1 {
+                    let total = multi.add(ProgressBar::new(0));
+                    total.set_style(total_style());
+                    total.set_prefix("total");
+                    self.total = Some(total);
+                }
+                self.refresh_total();
+            }
+            InstallProgress::DownloadProgress {
+                name,
+                downloaded,
+                total_bytes,
+            } => {
+                if let Some(pb) = self.bars.get(&name)
+                    && total_bytes.is_some()
+                {
+                    pb.set_position(downloaded);
+                }
+                if let Some(tally) = self.downloads.get_mut(&name) {
+                    tally.downloaded = downloaded;
+                }
+                self.refresh_total();
+            }
+            InstallProgress::DownloadCompleted { name, total_bytes } => {
+                if let Some(pb) = self.bars.get(&name) {
+                    if total_bytes > 0 {
+                        pb.set_position(total_bytes);
+                    }
+                    pb.set_style(spinner_style());
+                    pb.set_message("unpacking...");
+                    pb.enable_steady_tick(Duration::from_millis(80));
+                }
+                if let Some(tally) = self.downloads.get_mut(&name) {
+                    tally.downloaded = tally.total;
+                }
+                self.refresh_total();
+            }
+            InstallProgress::UnpackStarted { name } => self.set_message(&name, "unpacking..."),
+            InstallProgress::UnpackCompleted { name } => self.set_message(&name, "unpacked"),
+            InstallProgress::LinkStarted { name } => self.set_message(&name, "linking..."),
+            InstallProgress::LinkCompleted { name } => self.set_message(&name, "linked"),
+            InstallProgress::LinkSkipped { name, reason } => {
+                self.set_message(&name, format!("keg-only ({})", reason))
+            }
+            InstallProgress::InstallCompleted { name } => {
+                if let Some(pb) = self.bars.get(&name) {
+                    pb.set_style(done_style());
+                    pb.set_message(format!("{} {done_label}", style("✓").green()));
+                    pb.finish();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Keep the `total` line below every per-formula line.
+    fn add_bar(&self, multi: &MultiProgress, pb: ProgressBar) -> ProgressBar {
+        match &self.total {
+            Some(total) => multi.insert_before(total, pb),
+            None => multi.add(pb),
+        }
+    }
+
+    fn set_message(&self, name: &str, message: impl Into<std::borrow::Cow<'static, str>>) {
+        if let Some(pb) = self.bars.get(name) {
+            pb.set_message(message);
+        }
+    }
+
+    fn refresh_total(&self) {
+        if let Some(total) = &self.total {
+            let (downloaded, length) = self.totals();
+            total.set_length(length);
+            total.set_position(downloaded);
+        }
+    }
+
+    /// Bytes downloaded so far and bytes expected, across sized downloads.
+    fn totals(&self) -> (u64, u64) {
+        self.downloads
+            .values()
+            .fold((0, 0), |(downloaded, total), tally| {
+                (downloaded + tally.downloaded, total + tally.total)
+            })
+    }
+}
+
+fn download_style() -> ProgressStyle {
+    ProgressStyle::default_bar()
+        .template(
+            "    {prefix:<16} {bar:25.cyan/dim} {bytes:>10}/{total_bytes:<10} {binary_bytes_per_sec:>12} {eta:>6}",
+        )
+        .unwrap()
+        .progress_chars("━━╸")
+}
+
+fn total_style() -> ProgressStyle {
+    ProgressStyle::default_bar()
+        .template(
+            "    {prefix:<16} {bar:25.green/dim} {bytes:>10}/{total_bytes:<10} {binary_bytes_per_sec:>12} {eta:>6}",
+        )
+        .unwrap()
+        .progress_chars("━━╸")
+}
+
+fn spinner_style() -> ProgressStyle {
+    ProgressStyle::default_spinner()
+        .template("    {prefix:<16} {spinner:.cyan} {msg}")
+        .unwrap()
+        .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏")
+}
+
+fn done_style() -> ProgressStyle {
+    ProgressStyle::default_spinner()
+        .template("    {prefix:<16} {msg}")
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indicatif::ProgressDrawTarget;
+
+    #[test]
+    fn conflict_answers_default_to_abort() {
+        assert_eq!(parse_conflict_answer("s\n"), LinkConflictDecision::Skip);
+        assert_eq!(parse_conflict_answer("O"), LinkConflictDecision::Overwrite);
+        assert_eq!(
+            parse_conflict_answer("always"),
+            LinkConflictDecision::AlwaysOverwrite
+        );
+        assert_eq!(parse_conflict_answer("\n"), LinkConflictDecision::Abort);
+        assert_eq!(parse_conflict_answer("maybe"), LinkConflictDecision::Abort);
+    }
+
+    #[test]
+    fn total_appears_with_the_second_download_and_sums_sized_ones() {
+        let multi = MultiProgress::with_draw_target(ProgressDrawTarget::hidden());
+        let mut state = DisplayState::default();
+        let started = |name: &str, total_bytes| InstallProgress::DownloadStarted {
+            name: name.to_string(),
+            total_bytes,
+        };
+
+        state.apply(&multi, started("jq", Some(100)), "installed");
+        assert!(state.total.is_none());
+
+        state.apply(&multi, started("oniguruma", Some(300)), "installed");
+        state.apply(&multi, started("unsized", None), "installed");
+        state.apply(
+            &multi,
+            InstallProgress::DownloadProgress {
+                name: "oniguruma".to_string(),
+                downloaded: 150,
+                total_bytes: Some(300),
+            },
+            "installed",
+        );
+        state.apply(
+            &multi,
+            InstallProgress::DownloadCompleted {
+                name: "jq".to_string(),
+                total_bytes: 100,
+            },
+            "installed",
+        );
+
+        let total = state.total.as_ref().unwrap();
+        assert_eq!(total.length(), Some(400));
+        assert_eq!(total.position(), 250);
+        assert_eq!(state.totals(), (250, 400));
+    }
+}
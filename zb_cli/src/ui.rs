@@ -75,6 +75,7 @@ pub struct UiTheme {
 pub struct Ui<O: Write, E: Write> {
     out: O,
     err: E,
+    json: bool,
     pub theme: UiTheme,
 }
 
@@ -89,6 +90,7 @@ impl Ui<io::Stdout, io::Stderr> {
         Self {
             out: io::stdout(),
             err: io::stderr(),
+            json: false,
             theme,
         }
     }
@@ -99,15 +101,44 @@ impl<O: Write, E: Write> Ui<O, E> {
         Self {
             out,
             err,
+            json: false,
             theme: UiTheme::default(),
         }
     }
 
     pub fn with_theme_and_writers(theme: UiTheme, out: O, err: E) -> Self {
-        Self { out, err, theme }
+        Self {
+            out,
+            err,
+            json: false,
+            theme,
+        }
+    }
+
+    /// Switch to structured output. Human-styled messages meant for stdout
+    /// are dropped so it only carries what commands [`Ui::emit`]; warnings
+    /// and errors still go to stderr.
+    pub fn set_json(&mut self, json: bool) {
+        self.json = json;
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.json
+    }
+
+    /// Write `value` to stdout as a single line of JSON. Does nothing
+    /// outside JSON mode.
+    pub fn emit(&mut self, value: &serde_json::Value) -> io::Result<()> {
+        if !self.json {
+            return Ok(());
+        }
+        write_json_line(&mut self.out, value)
     }
 
     pub fn heading(&mut self, message: impl Display) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
         let label = self
             .theme
             .styles
@@ -118,6 +149,9 @@ impl<O: Write, E: Write> Ui<O, E> {
     }
 
     pub fn note(&mut self, message: impl Display) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
         let label = self
             .theme
             .styles
@@ -128,6 +162,9 @@ impl<O: Write, E: Write> Ui<O, E> {
     }
 
     pub fn info(&mut self, message: impl Display) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
         let label = self
             .theme
             .styles
@@ -158,6 +195,9 @@ impl<O: Write, E: Write> Ui<O, E> {
     }
 
     pub fn bullet(&mut self, message: impl Display) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
         let symbol = self
             .theme
             .styles
@@ -168,6 +208,9 @@ impl<O: Write, E: Write> Ui<O, E> {
     }
 
     pub fn step_start(&mut self, message: impl Display) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
         let pending = self
             .theme
             .styles
@@ -178,6 +221,9 @@ impl<O: Write, E: Write> Ui<O, E> {
     }
 
     pub fn step_ok(&mut self) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
         writeln!(
             self.out,
             " {}",
@@ -189,6 +235,9 @@ impl<O: Write, E: Write> Ui<O, E> {
     }
 
     pub fn step_fail(&mut self) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
         writeln!(
             self.out,
             " {}",
@@ -200,6 +249,9 @@ impl<O: Write, E: Write> Ui<O, E> {
     }
 
     pub fn println(&mut self, message: impl Display) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
         writeln!(self.out, "{message}")
     }
 
@@ -208,6 +260,9 @@ impl<O: Write, E: Write> Ui<O, E> {
     }
 
     pub fn blank_line(&mut self) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
         writeln!(self.out)
     }
 
@@ -238,6 +293,17 @@ impl Default for Ui<io::Stdout, io::Stderr> {
     }
 }
 
+/// Write `value` as one line of JSON to stdout, for callbacks that cannot
+/// borrow the [`Ui`], such as install progress reporting.
+pub fn emit_json_line(value: &serde_json::Value) -> io::Result<()> {
+    write_json_line(&mut io::stdout().lock(), value)
+}
+
+fn write_json_line(out: &mut impl Write, value: &serde_json::Value) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, value)?;
+    writeln!(out)
+}
+
 fn parse_yes_no_input(input: &str, default: PromptDefault) -> bool {
     let normalized = input.trim().to_ascii_lowercase();
 
@@ -304,4 +370,21 @@ mod tests {
         let stripped = console::strip_ansi_codes(&out).into_owned();
         assert!(stripped.contains("-> hello"));
     }
+
+    #[test]
+    fn json_mode_drops_human_output_and_emits_one_line_per_value() {
+        let mut ui = Ui::with_writers(Vec::<u8>::new(), Vec::<u8>::new());
+        ui.emit(&serde_json::json!({ "ignored": true })).unwrap();
+        ui.set_json(true);
+        ui.heading("hello").unwrap();
+        ui.bullet("item").unwrap();
+        ui.emit(&serde_json::json!({ "installed": 2 })).unwrap();
+        ui.emit(&serde_json::json!(["jq"])).unwrap();
+        ui.warn("careful").unwrap();
+
+        let out = String::from_utf8(ui.out).unwrap();
+        assert_eq!(out, "{\"installed\":2}\n[\"jq\"]\n");
+        let err = String::from_utf8(ui.err).unwrap();
+        assert!(console::strip_ansi_codes(&err).contains("Warning: careful"));
+    }
 }
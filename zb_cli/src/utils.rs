@@ -34,6 +34,12 @@ pub fn normalize_formula_name(name: &str) -> Result<String, zb_core::Error> {
     Ok(trimmed.to_string())
 }
 
+/// Whether an install argument names a formula `.rb` to fetch rather than a
+/// formula to look up.
+pub fn is_formula_url(arg: &str) -> bool {
+    (arg.starts_with("https://") || arg.starts_with("http://")) && arg.ends_with(".rb")
+}
+
 pub fn format_formula_suggestions(requested: &str, suggestions: &[String]) -> Option<String> {
     if suggestions.is_empty() {
         return None;
@@ -144,7 +150,22 @@ pub fn get_root_path(cli_root: Option<PathBuf>) -> PathBuf {
                     .map(|h| PathBuf::from(h).join(".local").join("share"))
                     .unwrap_or_else(|_| legacy_root.clone())
             });
-        xdg_data_home.join("zerobrew")
+        default_linux_root(xdg_data_home.join("zerobrew"), |path| path.exists())
+    }
+}
+
+/// Home of the `linuxbrew` user that Homebrew on Linux installs under.
+const LINUXBREW_HOME: &str = "/home/linuxbrew";
+
+/// On a machine set up for Homebrew on Linux, zerobrew lives beside it in
+/// `/home/linuxbrew/.zerobrew`, shared by every user like the macOS root.
+/// Otherwise, or when a per-user root already exists, the XDG data dir.
+fn default_linux_root(xdg_root: PathBuf, exists: impl Fn(&Path) -> bool) -> PathBuf {
+    let linuxbrew_home = Path::new(LINUXBREW_HOME);
+    if !exists(&xdg_root) && exists(linuxbrew_home) {
+        linuxbrew_home.join(".zerobrew")
+    } else {
+        xdg_root
     }
 }
 
@@ -187,16 +208,22 @@ mod tests {
     use tempfile::TempDir;
     use wiremock::matchers::{method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
-    use zb_io::cellar::Cellar;
-    use zb_io::network::ApiClient;
-    use zb_io::storage::{BlobCache, Database, Store};
-    use zb_io::{Installer, Linker};
+    use zb_io::{ApiClient, BlobCache, Cellar, Database, Installer, Linker, Store};
 
     use super::{
-        format_formula_suggestions, get_prefix_path_for_os, normalize_formula_name,
-        suggest_missing_formula_matches,
+        default_linux_root, format_formula_suggestions, get_prefix_path_for_os, is_formula_url,
+        normalize_formula_name, suggest_missing_formula_matches,
     };
 
+    #[test]
+    fn formula_urls_are_told_apart_from_names() {
+        assert!(is_formula_url("https://example.com/formulas/widget.rb"));
+        assert!(is_formula_url("http://intranet/widget.rb"));
+        assert!(!is_formula_url("acme/tools/widget"));
+        assert!(!is_formula_url("https://example.com/widget.tar.gz"));
+        assert!(!is_formula_url("widget.rb"));
+    }
+
     #[test]
     fn macos_default_prefix_is_root() {
         let root = PathBuf::from("/opt/zerobrew");
@@ -204,6 +231,19 @@ mod tests {
         assert_eq!(get_prefix_path_for_os(None, &root, true), root);
     }
 
+    #[test]
+    fn linux_root_sits_beside_linuxbrew_unless_a_user_root_exists() {
+        let xdg_root = PathBuf::from("/home/user/.local/share/zerobrew");
+
+        assert_eq!(
+            default_linux_root(xdg_root.clone(), |path| path
+                == std::path::Path::new("/home/linuxbrew")),
+            PathBuf::from("/home/linuxbrew/.zerobrew")
+        );
+        assert_eq!(default_linux_root(xdg_root.clone(), |_| true), xdg_root);
+        assert_eq!(default_linux_root(xdg_root.clone(), |_| false), xdg_root);
+    }
+
     #[test]
     fn linux_default_prefix_is_root_prefix() {
         let root = PathBuf::from("/home/user/.local/share/zerobrew");
@@ -1,3 +1,5 @@
 pub mod plan;
 
-pub use plan::{BuildPlan, BuildSystem, InstallMethod};
+pub use plan::{
+    BuildPlan, BuildSystem, HeadCheckout, InstallMethod, head_version, is_head_version,
+};
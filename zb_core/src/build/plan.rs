@@ -1,6 +1,6 @@
 use std::path::{Path, PathBuf};
 
-use crate::Formula;
+use crate::{BottlePolicy, Error, Formula, select_bottle_with_policy};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BuildSystem {
@@ -14,7 +14,56 @@ pub enum BuildSystem {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum InstallMethod {
     Bottle(crate::SelectedBottle),
-    Source(BuildPlan),
+    Source(Box<BuildPlan>),
+}
+
+impl InstallMethod {
+    /// Decide how `formula` should be installed.
+    ///
+    /// A bottle for this platform is preferred and a source build is the
+    /// fallback; `build_from_source` flips that order. Fails only when
+    /// neither is available.
+    pub fn select(
+        formula: &Formula,
+        prefix: &Path,
+        build_from_source: bool,
+    ) -> Result<Self, Error> {
+        Self::select_with_policy(formula, prefix, build_from_source, &BottlePolicy::default())
+    }
+
+    /// [`Self::select`] with the user's bottle overrides. A forced bottle
+    /// never falls back to a source build, so its selection error is
+    /// returned as is.
+    pub fn select_with_policy(
+        formula: &Formula,
+        prefix: &Path,
+        build_from_source: bool,
+        policy: &BottlePolicy,
+    ) -> Result<Self, Error> {
+        if policy.force {
+            return select_bottle_with_policy(formula, policy).map(InstallMethod::Bottle);
+        }
+
+        let bottle = || {
+            select_bottle_with_policy(formula, policy)
+                .ok()
+                .map(InstallMethod::Bottle)
+        };
+        let source = || {
+            BuildPlan::from_formula(formula, prefix)
+                .map(|plan| InstallMethod::Source(Box::new(plan)))
+        };
+
+        let method = if build_from_source {
+            source().or_else(bottle)
+        } else {
+            bottle().or_else(source)
+        };
+
+        method.ok_or_else(|| Error::UnsupportedBottle {
+            name: formula.name.clone(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -23,12 +72,33 @@ pub struct BuildPlan {
     pub version: String,
     pub source_url: String,
     pub source_checksum: Option<String>,
+    pub source_mirrors: Vec<String>,
     pub ruby_source_path: Option<String>,
     pub build_dependencies: Vec<String>,
     pub runtime_dependencies: Vec<String>,
     pub detected_system: BuildSystem,
     pub prefix: PathBuf,
     pub cellar_path: PathBuf,
+    /// Set for `--HEAD` builds, which clone `source_url` as a git
+    /// repository instead of downloading a release.
+    pub head: Option<HeadCheckout>,
+}
+
+/// The commit a `--HEAD` build checks out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadCheckout {
+    pub branch: Option<String>,
+    pub commit: String,
+}
+
+/// The version a `--HEAD` keg of `commit` is installed as, as Homebrew
+/// names them: `HEAD-` and the abbreviated commit.
+pub fn head_version(commit: &str) -> String {
+    format!("HEAD-{}", &commit[..commit.len().min(7)])
+}
+
+pub fn is_head_version(version: &str) -> bool {
+    version.starts_with("HEAD-")
 }
 
 impl BuildPlan {
@@ -45,12 +115,40 @@ impl BuildPlan {
             version,
             source_url: source.url.clone(),
             source_checksum: source.checksum.clone(),
+            source_mirrors: source.mirrors.clone(),
             ruby_source_path: formula.ruby_source_path.clone(),
             build_dependencies: all_build_deps,
             runtime_dependencies: formula.runtime_dependencies(),
             detected_system,
             prefix: prefix.to_path_buf(),
             cellar_path,
+            head: None,
+        })
+    }
+
+    /// Plan a build of `commit` from `formula`'s head repository. `None`
+    /// when the formula has no git head.
+    pub fn for_head(formula: &Formula, prefix: &Path, commit: &str) -> Option<Self> {
+        let head = formula.head_url()?;
+        let version = head_version(commit);
+        let all_build_deps = formula.all_build_dependencies();
+
+        Some(Self {
+            formula_name: formula.name.clone(),
+            cellar_path: prefix.join("Cellar").join(&formula.name).join(&version),
+            version,
+            detected_system: detect_build_system(&head.url, &all_build_deps),
+            source_url: head.url,
+            source_checksum: None,
+            source_mirrors: Vec::new(),
+            ruby_source_path: formula.ruby_source_path.clone(),
+            build_dependencies: all_build_deps,
+            runtime_dependencies: formula.runtime_dependencies(),
+            prefix: prefix.to_path_buf(),
+            head: Some(HeadCheckout {
+                branch: head.branch,
+                commit: commit.to_string(),
+            }),
         })
     }
 }
@@ -102,12 +200,16 @@ mod tests {
             keg_only: KegOnly::default(),
             keg_only_reason: None,
             build_dependencies: build_deps.iter().map(|s| s.to_string()).collect(),
+            test_dependencies: Vec::new(),
+            recommended_dependencies: Vec::new(),
+            optional_dependencies: Vec::new(),
             urls: Some(FormulaUrls {
                 stable: Some(SourceUrl {
                     url: source_url.to_string(),
                     checksum: Some("abc123".to_string()),
                     tag: None,
                     revision: None,
+                    mirrors: Vec::new(),
                 }),
                 head: None,
             }),
@@ -116,6 +218,14 @@ mod tests {
             uses_from_macos: Vec::new(),
             requirements: Vec::new(),
             variations: None,
+            caveats: None,
+            conflicts_with: Vec::new(),
+            conflicts_with_reasons: Vec::new(),
+            service: None,
+            desc: None,
+            homepage: None,
+            license: None,
+            post_install_defined: false,
         }
     }
 
@@ -151,6 +261,109 @@ mod tests {
         assert!(BuildPlan::from_formula(&f, &prefix).is_none());
     }
 
+    #[test]
+    fn select_prefers_bottle_unless_building_from_source() {
+        let mut f = test_formula("wget", "https://example.com/src.tar.gz", &[]);
+        f.bottle.stable.files = BTreeMap::from([(
+            "all".to_string(),
+            BottleFile {
+                url: "https://example.com/wget.tar.gz".to_string(),
+                sha256: "deadbeef".repeat(8),
+            },
+        )]);
+        let prefix = PathBuf::from("/opt/zerobrew");
+
+        assert!(matches!(
+            InstallMethod::select(&f, &prefix, false).unwrap(),
+            InstallMethod::Bottle(_)
+        ));
+        assert!(matches!(
+            InstallMethod::select(&f, &prefix, true).unwrap(),
+            InstallMethod::Source(_)
+        ));
+
+        f.urls = None;
+        assert!(matches!(
+            InstallMethod::select(&f, &prefix, true).unwrap(),
+            InstallMethod::Bottle(_)
+        ));
+    }
+
+    #[test]
+    fn select_falls_back_to_source_without_bottle() {
+        let mut f = test_formula("wget", "https://example.com/src.tar.gz", &[]);
+        f.bottle.stable.files.clear();
+        let prefix = PathBuf::from("/opt/zerobrew");
+
+        assert!(matches!(
+            InstallMethod::select(&f, &prefix, false).unwrap(),
+            InstallMethod::Source(_)
+        ));
+
+        f.urls = None;
+        assert!(matches!(
+            InstallMethod::select(&f, &prefix, false),
+            Err(Error::UnsupportedBottle { .. })
+        ));
+    }
+
+    #[test]
+    fn head_plan_is_versioned_by_commit() {
+        let mut f = test_formula("wget", "https://example.com/src.tar.gz", &[]);
+        let prefix = PathBuf::from("/opt/zerobrew");
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        assert!(BuildPlan::for_head(&f, &prefix, commit).is_none());
+
+        f.urls.as_mut().unwrap().head = Some(serde_json::json!({
+            "url": "https://git.example.com/wget.git",
+            "branch": "main"
+        }));
+        let plan = BuildPlan::for_head(&f, &prefix, commit).unwrap();
+        assert_eq!(plan.version, "HEAD-0123456");
+        assert!(is_head_version(&plan.version));
+        assert_eq!(plan.source_url, "https://git.example.com/wget.git");
+        assert_eq!(plan.source_checksum, None);
+        assert_eq!(
+            plan.cellar_path,
+            PathBuf::from("/opt/zerobrew/Cellar/wget/HEAD-0123456")
+        );
+        assert_eq!(
+            plan.head,
+            Some(HeadCheckout {
+                branch: Some("main".to_string()),
+                commit: commit.to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn forced_bottle_never_falls_back_to_source() {
+        let mut f = test_formula("wget", "https://example.com/src.tar.gz", &[]);
+        f.bottle.stable.files.clear();
+        let prefix = PathBuf::from("/opt/zerobrew");
+        let force = BottlePolicy {
+            force: true,
+            arch: None,
+        };
+
+        assert!(matches!(
+            InstallMethod::select_with_policy(&f, &prefix, false, &force),
+            Err(Error::UnsupportedBottle { .. })
+        ));
+
+        f.bottle.stable.files = BTreeMap::from([(
+            "all".to_string(),
+            BottleFile {
+                url: "https://example.com/wget.tar.gz".to_string(),
+                sha256: "deadbeef".repeat(8),
+            },
+        )]);
+        assert!(matches!(
+            InstallMethod::select_with_policy(&f, &prefix, true, &force).unwrap(),
+            InstallMethod::Bottle(_)
+        ));
+    }
+
     #[test]
     fn cellar_path_includes_version() {
         let f = test_formula("wget", "https://example.com/src.tar.gz", &[]);
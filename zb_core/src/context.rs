@@ -8,6 +8,7 @@ pub struct Paths {
     pub cache: PathBuf,
     pub db: PathBuf,
     pub locks: PathBuf,
+    pub logs: PathBuf,
 }
 
 impl Paths {
@@ -17,6 +18,7 @@ impl Paths {
         let cache = root.join("cache");
         let db = root.join("db").join("zb.sqlite3");
         let locks = root.join("locks");
+        let logs = root.join("logs");
 
         Self {
             root,
@@ -25,8 +27,14 @@ impl Paths {
             cache,
             db,
             locks,
+            logs,
         }
     }
+
+    /// The debug log every run appends its full trace to.
+    pub fn log_file(&self) -> PathBuf {
+        self.logs.join("zb.log")
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -51,17 +59,67 @@ pub enum LogLevel {
     Info,
     Warn,
     Error,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    /// The level's name as `tracing` filter directives spell it.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct LoggerHandle {
+    /// What reaches the terminal.
     pub level: LogLevel,
+    /// Where the full trace of the run is appended, whatever `level` is.
+    pub file: Option<PathBuf>,
+    /// Show timestamps, targets and spans on the terminal too.
+    pub debug: bool,
+}
+
+impl LoggerHandle {
+    /// The handle for `-v` repeated `verbose` times, `--quiet` and
+    /// `--debug`. Warnings are shown by default; `--debug` implies at
+    /// least `-vv`.
+    pub fn from_flags(verbose: u8, quiet: bool, debug: bool) -> Self {
+        let level = if quiet {
+            LogLevel::Error
+        } else {
+            match (verbose, debug) {
+                (0, false) => LogLevel::Warn,
+                (1, false) => LogLevel::Info,
+                (0..=2, _) => LogLevel::Debug,
+                _ => LogLevel::Trace,
+            }
+        };
+        Self {
+            level,
+            file: None,
+            debug,
+        }
+    }
+
+    pub fn with_file(mut self, file: PathBuf) -> Self {
+        self.file = Some(file);
+        self
+    }
 }
 
 impl Default for LoggerHandle {
     fn default() -> Self {
         Self {
             level: LogLevel::Info,
+            file: None,
+            debug: false,
         }
     }
 }
@@ -112,5 +170,38 @@ mod tests {
             context.paths.locks,
             PathBuf::from("/opt/zerobrew").join("locks")
         );
+        assert_eq!(
+            context.paths.log_file(),
+            PathBuf::from("/opt/zerobrew").join("logs").join("zb.log")
+        );
+    }
+
+    #[test]
+    fn logger_level_follows_flags() {
+        assert_eq!(LoggerHandle::default().level, LogLevel::Info);
+        assert_eq!(
+            LoggerHandle::from_flags(0, false, false).level,
+            LogLevel::Warn
+        );
+        assert_eq!(
+            LoggerHandle::from_flags(1, false, false).level,
+            LogLevel::Info
+        );
+        assert_eq!(
+            LoggerHandle::from_flags(5, false, false).level,
+            LogLevel::Trace
+        );
+        assert_eq!(
+            LoggerHandle::from_flags(0, true, false).level,
+            LogLevel::Error
+        );
+        assert_eq!(
+            LoggerHandle::from_flags(0, false, true).level,
+            LogLevel::Debug
+        );
+        assert_eq!(
+            LoggerHandle::from_flags(3, false, true).level,
+            LogLevel::Trace
+        );
     }
 }
@@ -7,23 +7,77 @@ pub struct ConflictedLink {
     pub owned_by: Option<String>,
 }
 
+/// Everything zerobrew can fail with. Variants are added between releases,
+/// so branch on [`Error::kind`] where a category is enough.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+#[rustfmt::skip]
 pub enum Error {
     UnsupportedBottle { name: String },
     ChecksumMismatch { expected: String, actual: String },
     LinkConflict { conflicts: Vec<ConflictedLink> },
     StoreCorruption { message: String },
     NetworkFailure { message: String },
+    /// A server answered with a non-success HTTP status; `message` describes
+    /// the request that failed.
+    HttpStatus { status: u16, message: String },
     MissingFormula { name: String },
     UnsupportedTap { name: String },
     UnsupportedFormula { name: String, reason: String },
     DependencyCycle { cycle: Vec<String> },
     NotInstalled { name: String },
+    /// `reasons` says why each of `conflicts` conflicts, in the same order;
+    /// entries may be empty.
+    FormulaConflict { name: String, conflicts: Vec<String>, reasons: Vec<String> },
     FileError { message: String },
     InvalidArgument { message: String },
     ExecutionError { message: String },
 }
 
+/// Coarse category of an [`Error`], for callers that branch on what went
+/// wrong rather than on individual variants. New variants map onto an
+/// existing kind, so matching on this stays stable as errors are added.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A formula doesn't exist, or isn't installed.
+    NotFound,
+    /// The formula, tap or bottle can't be installed on this system.
+    Unsupported,
+    /// Two formulas, or a formula and existing files, can't coexist.
+    Conflict,
+    /// Dependencies can't be resolved.
+    Dependency,
+    /// A download or the store failed an integrity check.
+    Integrity,
+    /// The network request failed.
+    Network,
+    /// Reading or writing a file failed.
+    Io,
+    /// The caller passed something invalid.
+    InvalidInput,
+    /// A build, hook or other external step failed.
+    Execution,
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::MissingFormula { .. } | Error::NotInstalled { .. } => ErrorKind::NotFound,
+            Error::UnsupportedBottle { .. }
+            | Error::UnsupportedTap { .. }
+            | Error::UnsupportedFormula { .. } => ErrorKind::Unsupported,
+            Error::LinkConflict { .. } | Error::FormulaConflict { .. } => ErrorKind::Conflict,
+            Error::DependencyCycle { .. } => ErrorKind::Dependency,
+            Error::ChecksumMismatch { .. } | Error::StoreCorruption { .. } => ErrorKind::Integrity,
+            Error::NetworkFailure { .. } | Error::HttpStatus { .. } => ErrorKind::Network,
+            Error::FileError { .. } => ErrorKind::Io,
+            Error::InvalidArgument { .. } => ErrorKind::InvalidInput,
+            Error::ExecutionError { .. } => ErrorKind::Execution,
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -53,6 +107,7 @@ impl fmt::Display for Error {
             }
             Error::StoreCorruption { message } => write!(f, "store corruption: {message}"),
             Error::NetworkFailure { message } => write!(f, "network failure: {message}"),
+            Error::HttpStatus { message, .. } => write!(f, "network failure: {message}"),
             Error::MissingFormula { name } => write!(f, "missing formula '{name}'"),
             Error::UnsupportedTap { name } => {
                 write!(
@@ -68,6 +123,27 @@ impl fmt::Display for Error {
                 write!(f, "dependency cycle detected: {rendered}")
             }
             Error::NotInstalled { name } => write!(f, "formula '{name}' is not installed"),
+            Error::FormulaConflict {
+                name,
+                conflicts,
+                reasons,
+            } => {
+                let rendered: Vec<String> = conflicts
+                    .iter()
+                    .enumerate()
+                    .map(
+                        |(i, conflict)| match reasons.get(i).filter(|r| !r.is_empty()) {
+                            Some(reason) => format!("{conflict} ({reason})"),
+                            None => conflict.clone(),
+                        },
+                    )
+                    .collect();
+                write!(
+                    f,
+                    "formula '{name}' conflicts with {} (uninstall it first or use --ignore-conflicts-with to override)",
+                    rendered.join(", ")
+                )
+            }
             Error::FileError { message } => write!(f, "file error: {message}"),
             Error::InvalidArgument { message } => write!(f, "invalid argument: {message}"),
             Error::ExecutionError { message } => write!(f, "{message}"),
@@ -108,4 +184,45 @@ mod tests {
 
         assert!(err.to_string().contains("libheif"));
     }
+
+    #[test]
+    fn formula_conflict_display_includes_reasons() {
+        let err = Error::FormulaConflict {
+            name: "mysql".to_string(),
+            conflicts: vec!["mariadb".to_string(), "percona-server".to_string()],
+            reasons: vec!["both install `mysql`".to_string(), String::new()],
+        };
+
+        let message = err.to_string();
+        assert!(message.contains("mariadb (both install `mysql`), percona-server"));
+    }
+
+    #[test]
+    fn kind_groups_related_variants() {
+        let missing = Error::MissingFormula {
+            name: "jq".to_string(),
+        };
+        let not_installed = Error::NotInstalled {
+            name: "jq".to_string(),
+        };
+        let checksum = Error::ChecksumMismatch {
+            expected: "a".to_string(),
+            actual: "b".to_string(),
+        };
+
+        assert_eq!(missing.kind(), ErrorKind::NotFound);
+        assert_eq!(not_installed.kind(), ErrorKind::NotFound);
+        assert_eq!(checksum.kind(), ErrorKind::Integrity);
+    }
+
+    #[test]
+    fn http_status_reads_like_a_network_failure() {
+        let err = Error::HttpStatus {
+            status: 404,
+            message: "HTTP 404 Not Found".to_string(),
+        };
+
+        assert_eq!(err.kind(), ErrorKind::Network);
+        assert_eq!(err.to_string(), "network failure: HTTP 404 Not Found");
+    }
 }
@@ -1,3 +1,6 @@
+use tracing::debug;
+
+use crate::formula::types::BottleFile;
 use crate::{Error, Formula};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -7,9 +10,67 @@ pub struct SelectedBottle {
     pub sha256: String,
 }
 
+/// Overrides for which bottle an install picks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BottlePolicy {
+    /// Never build from source, and settle for the closest bottle for the
+    /// architecture when none is built for this OS release.
+    pub force: bool,
+    /// Pick bottles for this architecture (`arm64` or `x86_64`), or this
+    /// exact bottle tag, instead of the host's.
+    pub arch: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BottleArch {
+    Arm64,
+    X86_64,
+}
+
+impl BottleArch {
+    fn parse(arch: &str) -> Option<Self> {
+        match arch {
+            "arm64" | "aarch64" => Some(Self::Arm64),
+            "x86_64" | "amd64" => Some(Self::X86_64),
+            _ => None,
+        }
+    }
+
+    /// The tags of this arch's bottles that run on the host OS, most
+    /// preferred first.
+    fn tags(self, linux: bool, macos_version: Option<u32>) -> Vec<String> {
+        if linux {
+            let arch = match self {
+                Self::Arm64 => "aarch64",
+                Self::X86_64 => "x86_64",
+            };
+            return preferred_linux_bottle_tags_for_arch(arch)
+                .iter()
+                .map(|tag| tag.to_string())
+                .collect();
+        }
+        compatible_codenames(macos_version)
+            .into_iter()
+            .map(|codename| match self {
+                Self::Arm64 => format!("arm64_{codename}"),
+                Self::X86_64 => codename.to_string(),
+            })
+            .collect()
+    }
+
+    /// Whether `tag` is a bottle for this arch on the host OS, for any
+    /// release of it.
+    fn builds(self, tag: &str, linux: bool) -> bool {
+        if tag == "all" || tag.ends_with("_linux") != linux {
+            return false;
+        }
+        let arm = tag.starts_with("arm64_") || tag.starts_with("aarch64_");
+        arm == (self == Self::Arm64)
+    }
+}
+
 const MACOS_CODENAMES_NEWEST_FIRST: &[&str] = &["tahoe", "sequoia", "sonoma", "ventura"];
 
-#[cfg(any(target_os = "linux", test))]
 fn preferred_linux_bottle_tags_for_arch(arch: &str) -> &'static [&'static str] {
     match arch {
         "aarch64" => &["arm64_linux", "aarch64_linux"],
@@ -76,6 +137,109 @@ pub fn select_bottle(formula: &Formula) -> Result<SelectedBottle, Error> {
     select_bottle_with_version(formula, macos_version)
 }
 
+/// A bottle tag a formula publishes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BottleTag {
+    pub tag: String,
+    /// Whether `select_bottle` would install this bottle on the current host.
+    pub host: bool,
+}
+
+/// Every tag in the formula's stable bottle, sorted, with the one this host
+/// would use flagged. No tag is flagged when the host has no compatible bottle.
+pub fn bottle_tags(formula: &Formula) -> Vec<BottleTag> {
+    let selected = select_bottle(formula).ok().map(|bottle| bottle.tag);
+    formula
+        .bottle
+        .stable
+        .files
+        .keys()
+        .map(|tag| BottleTag {
+            host: selected.as_deref() == Some(tag.as_str()),
+            tag: tag.clone(),
+        })
+        .collect()
+}
+
+/// [`select_bottle`] with the user's overrides applied.
+pub fn select_bottle_with_policy(
+    formula: &Formula,
+    policy: &BottlePolicy,
+) -> Result<SelectedBottle, Error> {
+    #[cfg(target_os = "macos")]
+    let macos_version = macos_major_version();
+    #[cfg(not(target_os = "macos"))]
+    let macos_version: Option<u32> = None;
+
+    let bottle =
+        select_bottle_with_policy_for(formula, policy, cfg!(target_os = "linux"), macos_version);
+    match &bottle {
+        Ok(bottle) => debug!(formula = %formula.name, tag = %bottle.tag, "selected bottle"),
+        Err(e) => debug!(formula = %formula.name, error = %e, "no usable bottle"),
+    }
+    bottle
+}
+
+fn select_bottle_with_policy_for(
+    formula: &Formula,
+    policy: &BottlePolicy,
+    linux: bool,
+    macos_version: Option<u32>,
+) -> Result<SelectedBottle, Error> {
+    let files = &formula.bottle.stable.files;
+    let unsupported = || Error::UnsupportedBottle {
+        name: formula.name.clone(),
+    };
+
+    let arch = match policy.arch.as_deref() {
+        None if !policy.force => return select_bottle_with_version(formula, macos_version),
+        None => match select_bottle_with_version(formula, macos_version) {
+            Ok(bottle) => return Ok(bottle),
+            Err(e) => BottleArch::parse(std::env::consts::ARCH).ok_or(e)?,
+        },
+        Some(tag) if files.contains_key(tag) => return Ok(selected(tag, &files[tag])),
+        Some(arch) => BottleArch::parse(arch).ok_or_else(|| Error::InvalidArgument {
+            message: format!(
+                "unknown bottle arch '{arch}': expected arm64, x86_64 or one of the formula's bottle tags"
+            ),
+        })?,
+    };
+
+    let exact = arch
+        .tags(linux, macos_version)
+        .into_iter()
+        .chain(std::iter::once("all".to_string()))
+        .find_map(|tag| files.get(&tag).map(|file| selected(&tag, file)));
+    if let Some(bottle) = exact {
+        return Ok(bottle);
+    }
+    if !policy.force {
+        return Err(unsupported());
+    }
+
+    // Built for a release this host may not run: the oldest known one is
+    // the least likely to need anything newer than the host has.
+    files
+        .iter()
+        .filter(|(tag, _)| arch.builds(tag, linux))
+        .max_by_key(|(tag, _)| {
+            let codename = tag.strip_prefix("arm64_").unwrap_or(tag);
+            MACOS_CODENAMES_NEWEST_FIRST
+                .iter()
+                .position(|&known| known == codename)
+        })
+        .map(|(tag, file)| selected(tag, file))
+        .ok_or_else(unsupported)
+}
+
+fn selected(tag: &str, file: &BottleFile) -> SelectedBottle {
+    SelectedBottle {
+        tag: tag.to_string(),
+        url: file.url.clone(),
+        sha256: file.sha256.clone(),
+    }
+}
+
 fn select_bottle_with_version(
     formula: &Formula,
     macos_version: Option<u32>,
@@ -237,6 +401,91 @@ mod tests {
         }
     }
 
+    #[test]
+    fn bottle_tags_lists_every_file_and_flags_the_host() {
+        let fixture = include_str!("../../fixtures/formula_foo.json");
+        let formula: Formula = serde_json::from_str(fixture).unwrap();
+
+        let tags = bottle_tags(&formula);
+
+        let listed: Vec<&str> = tags.iter().map(|t| t.tag.as_str()).collect();
+        let published: Vec<&str> = formula
+            .bottle
+            .stable
+            .files
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(listed, published);
+
+        let host: Vec<&str> = tags
+            .iter()
+            .filter(|t| t.host)
+            .map(|t| t.tag.as_str())
+            .collect();
+        assert_eq!(host, vec![select_bottle(&formula).unwrap().tag.as_str()]);
+    }
+
+    fn policy(force: bool, arch: Option<&str>) -> BottlePolicy {
+        BottlePolicy {
+            force,
+            arch: arch.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn bottle_arch_overrides_the_host_arch() {
+        let fixture = include_str!("../../fixtures/formula_foo.json");
+        let formula: Formula = serde_json::from_str(fixture).unwrap();
+        let select = |arch, linux| {
+            select_bottle_with_policy_for(&formula, &policy(false, Some(arch)), linux, Some(15))
+        };
+
+        assert_eq!(select("x86_64", false).unwrap().tag, "sonoma");
+        assert_eq!(select("arm64", false).unwrap().tag, "arm64_sonoma");
+        assert_eq!(select("x86_64", true).unwrap().tag, "x86_64_linux");
+        // An exact tag is taken as given, even for another OS.
+        assert_eq!(select("x86_64_linux", false).unwrap().tag, "x86_64_linux");
+        assert!(matches!(
+            select("aarch64", true),
+            Err(Error::UnsupportedBottle { .. })
+        ));
+        assert!(matches!(
+            select("sparc", false),
+            Err(Error::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn force_settles_for_the_closest_release() {
+        let mut formula: Formula =
+            serde_json::from_str(include_str!("../../fixtures/formula_foo.json")).unwrap();
+        let files = &mut formula.bottle.stable.files;
+        let file = files.remove("arm64_sonoma").unwrap();
+        files.clear();
+        files.insert("arm64_tahoe".to_string(), file.clone());
+        files.insert("arm64_sequoia".to_string(), file);
+
+        // Both bottles need a newer macOS than Sonoma.
+        let arm64 = Some("arm64");
+        assert!(matches!(
+            select_bottle_with_policy_for(&formula, &policy(false, arm64), false, Some(14)),
+            Err(Error::UnsupportedBottle { .. })
+        ));
+        assert_eq!(
+            select_bottle_with_policy_for(&formula, &policy(true, arm64), false, Some(14))
+                .unwrap()
+                .tag,
+            "arm64_sequoia"
+        );
+
+        // Force never crosses architectures.
+        assert!(matches!(
+            select_bottle_with_policy_for(&formula, &policy(true, Some("x86_64")), false, Some(14)),
+            Err(Error::UnsupportedBottle { .. })
+        ));
+    }
+
     #[test]
     fn linux_arm_prefers_arm64_bottle_tags() {
         assert_eq!(
@@ -290,12 +539,23 @@ mod tests {
             keg_only: KegOnly::default(),
             keg_only_reason: None,
             build_dependencies: Vec::new(),
+            test_dependencies: Vec::new(),
+            recommended_dependencies: Vec::new(),
+            optional_dependencies: Vec::new(),
             urls: None,
             ruby_source_path: None,
             ruby_source_checksum: None,
             uses_from_macos: Vec::new(),
             requirements: Vec::new(),
             variations: None,
+            caveats: None,
+            conflicts_with: Vec::new(),
+            conflicts_with_reasons: Vec::new(),
+            service: None,
+            desc: None,
+            homepage: None,
+            license: None,
+            post_install_defined: false,
         };
 
         let selected = select_bottle(&formula).unwrap();
@@ -329,12 +589,23 @@ mod tests {
             keg_only: KegOnly::default(),
             keg_only_reason: None,
             build_dependencies: Vec::new(),
+            test_dependencies: Vec::new(),
+            recommended_dependencies: Vec::new(),
+            optional_dependencies: Vec::new(),
             urls: None,
             ruby_source_path: None,
             ruby_source_checksum: None,
             uses_from_macos: Vec::new(),
             requirements: Vec::new(),
             variations: None,
+            caveats: None,
+            conflicts_with: Vec::new(),
+            conflicts_with_reasons: Vec::new(),
+            service: None,
+            desc: None,
+            homepage: None,
+            license: None,
+            post_install_defined: false,
         };
 
         let err = select_bottle(&formula).unwrap_err();
@@ -370,12 +641,23 @@ mod tests {
             keg_only: KegOnly::default(),
             keg_only_reason: None,
             build_dependencies: Vec::new(),
+            test_dependencies: Vec::new(),
+            recommended_dependencies: Vec::new(),
+            optional_dependencies: Vec::new(),
             urls: None,
             ruby_source_path: None,
             ruby_source_checksum: None,
             uses_from_macos: Vec::new(),
             requirements: Vec::new(),
             variations: None,
+            caveats: None,
+            conflicts_with: Vec::new(),
+            conflicts_with_reasons: Vec::new(),
+            service: None,
+            desc: None,
+            homepage: None,
+            license: None,
+            post_install_defined: false,
         };
 
         let err = select_bottle(&formula).unwrap_err();
@@ -453,12 +735,23 @@ mod tests {
             keg_only: KegOnly::default(),
             keg_only_reason: None,
             build_dependencies: Vec::new(),
+            test_dependencies: Vec::new(),
+            recommended_dependencies: Vec::new(),
+            optional_dependencies: Vec::new(),
             urls: None,
             ruby_source_path: None,
             ruby_source_checksum: None,
             uses_from_macos: Vec::new(),
             requirements: Vec::new(),
             variations: None,
+            caveats: None,
+            conflicts_with: Vec::new(),
+            conflicts_with_reasons: Vec::new(),
+            service: None,
+            desc: None,
+            homepage: None,
+            license: None,
+            post_install_defined: false,
         };
 
         let selected = select_bottle_with_version(&formula, Some(15)).unwrap();
@@ -502,12 +795,23 @@ mod tests {
             keg_only: KegOnly::default(),
             keg_only_reason: None,
             build_dependencies: Vec::new(),
+            test_dependencies: Vec::new(),
+            recommended_dependencies: Vec::new(),
+            optional_dependencies: Vec::new(),
             urls: None,
             ruby_source_path: None,
             ruby_source_checksum: None,
             uses_from_macos: Vec::new(),
             requirements: Vec::new(),
             variations: None,
+            caveats: None,
+            conflicts_with: Vec::new(),
+            conflicts_with_reasons: Vec::new(),
+            service: None,
+            desc: None,
+            homepage: None,
+            license: None,
+            post_install_defined: false,
         };
 
         let selected = select_bottle_with_version(&formula, Some(26)).unwrap();
@@ -1,16 +1,24 @@
 pub mod bottle;
 pub mod resolve;
+pub mod service;
 pub mod types;
+pub mod version;
 
-pub use bottle::{SelectedBottle, compatible_codenames, select_bottle};
+pub use bottle::{
+    BottlePolicy, BottleTag, SelectedBottle, bottle_tags, compatible_codenames, select_bottle,
+    select_bottle_with_policy,
+};
 
 #[cfg(target_os = "macos")]
 pub use bottle::macos_major_version;
-pub use resolve::resolve_closure;
+pub use resolve::{resolve_closure, resolve_closure_for, resolve_closure_with};
+pub use service::{KeepAlive, RestartPolicy, Service, ServiceRun, ServiceSpec, service_label};
 pub use types::{
-    Bottle, BottleFile, BottleStable, Formula, FormulaUrls, KegOnly, KegOnlyReason,
-    RubySourceChecksum, SourceUrl, UsesFromMacos, Versions,
+    Bottle, BottleFile, BottleStable, DependencyKind, DependencyOptions, Formula, FormulaUrls,
+    HeadUrl, KegOnly, KegOnlyReason, RubySourceChecksum, SourceUrl, TargetOs, UsesFromMacos,
+    Versions,
 };
+pub use version::compare_versions;
 
 /// Extract the formula token from an install key.
 /// Examples:
@@ -1,9 +1,32 @@
-use crate::{Error, Formula};
+use crate::{DependencyOptions, Error, Formula, TargetOs};
 use std::collections::{BTreeMap, BTreeSet, HashMap};
+use tracing::{debug, trace};
 
 pub fn resolve_closure(
     roots: &[String],
     formulas: &BTreeMap<String, Formula>,
+) -> Result<Vec<String>, Error> {
+    resolve_closure_with(roots, formulas, &DependencyOptions::default())
+}
+
+/// [`resolve_closure`] following the dependencies `options` selects, so
+/// recommended dependencies can be left out and optional ones pulled in.
+pub fn resolve_closure_with(
+    roots: &[String],
+    formulas: &BTreeMap<String, Formula>,
+    options: &DependencyOptions,
+) -> Result<Vec<String>, Error> {
+    resolve_closure_for(roots, formulas, options, TargetOs::host())
+}
+
+/// [`resolve_closure_with`] for `target` rather than the host, so a Linux
+/// closure picks up `uses_from_macos` dependencies even when resolved on a
+/// Mac, and the other way around.
+pub fn resolve_closure_for(
+    roots: &[String],
+    formulas: &BTreeMap<String, Formula>,
+    options: &DependencyOptions,
+    target: TargetOs,
 ) -> Result<Vec<String>, Error> {
     let name_to_idx: HashMap<&str, usize> = formulas
         .keys()
@@ -13,7 +36,7 @@ pub fn resolve_closure(
     let idx_to_name: Vec<&str> = formulas.keys().map(|k| k.as_str()).collect();
     let n = idx_to_name.len();
 
-    let closure = compute_closure(roots, formulas, &name_to_idx)?;
+    let closure = compute_closure(roots, formulas, &name_to_idx, options, target)?;
 
     let mut indegree = vec![0u32; n];
     let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); n];
@@ -21,7 +44,7 @@ pub fn resolve_closure(
     for &idx in &closure {
         let formula = &formulas[idx_to_name[idx]];
         let mut dep_indices: Vec<usize> = formula
-            .runtime_dependencies()
+            .dependencies_for(options, target)
             .iter()
             .filter_map(|dep| {
                 let &di = name_to_idx.get(dep.as_str())?;
@@ -59,19 +82,24 @@ pub fn resolve_closure(
             .filter(|&&i| indegree[i] > 0)
             .map(|&i| idx_to_name[i].to_string())
             .collect();
+        debug!(?cycle, "dependency cycle");
         return Err(Error::DependencyCycle { cycle });
     }
 
-    Ok(ordered
+    let ordered: Vec<String> = ordered
         .into_iter()
         .map(|i| idx_to_name[i].to_string())
-        .collect())
+        .collect();
+    debug!(?roots, order = ?ordered, "resolved install order");
+    Ok(ordered)
 }
 
 fn compute_closure(
     roots: &[String],
     formulas: &BTreeMap<String, Formula>,
     name_to_idx: &HashMap<&str, usize>,
+    options: &DependencyOptions,
+    target: TargetOs,
 ) -> Result<BTreeSet<usize>, Error> {
     let mut closure = BTreeSet::new();
     let mut stack: Vec<usize> = Vec::with_capacity(roots.len());
@@ -91,11 +119,16 @@ fn compute_closure(
         }
 
         let formula = &formulas[idx_to_name[idx]];
-        for dep in formula.runtime_dependencies() {
-            if let Some(&di) = name_to_idx.get(dep.as_str())
-                && !closure.contains(&di)
-            {
-                stack.push(di);
+        for dep in formula.dependencies_for(options, target) {
+            match name_to_idx.get(dep.as_str()) {
+                Some(&di) if !closure.contains(&di) => {
+                    trace!(formula = idx_to_name[idx], dependency = %dep, "following dependency");
+                    stack.push(di);
+                }
+                Some(_) => {}
+                None => {
+                    debug!(formula = idx_to_name[idx], dependency = %dep, "dependency not in the formula set; skipped");
+                }
             }
         }
     }
@@ -132,12 +165,23 @@ mod tests {
             keg_only: KegOnly::default(),
             keg_only_reason: None,
             build_dependencies: Vec::new(),
+            test_dependencies: Vec::new(),
+            recommended_dependencies: Vec::new(),
+            optional_dependencies: Vec::new(),
             urls: None,
             ruby_source_path: None,
             ruby_source_checksum: None,
             uses_from_macos: Vec::new(),
             requirements: Vec::new(),
             variations: None,
+            caveats: None,
+            conflicts_with: Vec::new(),
+            conflicts_with_reasons: Vec::new(),
+            service: None,
+            desc: None,
+            homepage: None,
+            license: None,
+            post_install_defined: false,
         }
     }
 
@@ -191,7 +235,31 @@ mod tests {
     }
 
     #[test]
-    #[cfg(not(target_os = "macos"))]
+    fn follows_selected_recommended_and_optional_dependencies() {
+        let mut formulas = BTreeMap::new();
+        let mut app = formula("app", &["lib"]);
+        app.build_dependencies = vec!["cmake".to_string()];
+        app.test_dependencies = vec!["bats".to_string()];
+        app.recommended_dependencies = vec!["readline".to_string()];
+        app.optional_dependencies = vec!["x11".to_string()];
+        formulas.insert("app".to_string(), app);
+        for name in ["lib", "cmake", "bats", "readline", "x11"] {
+            formulas.insert(name.to_string(), formula(name, &[]));
+        }
+        let roots = ["app".to_string()];
+
+        let order = resolve_closure(&roots, &formulas).unwrap();
+        assert_eq!(order, vec!["lib", "readline", "app"]);
+
+        let options = DependencyOptions {
+            with: vec!["x11".to_string()],
+            without: vec!["readline".to_string(), "lib".to_string()],
+        };
+        let order = resolve_closure_with(&roots, &formulas, &options).unwrap();
+        assert_eq!(order, vec!["lib", "x11", "app"]);
+    }
+
+    #[test]
     fn resolves_uses_from_macos_runtime_dependencies_on_linux() {
         use crate::formula::UsesFromMacos;
 
@@ -203,7 +271,12 @@ mod tests {
         formulas.insert("openssl@3".to_string(), formula("openssl@3", &[]));
         formulas.insert("expat".to_string(), formula("expat", &[]));
 
-        let order = resolve_closure(&["python@3.14".to_string()], &formulas).unwrap();
+        let roots = ["python@3.14".to_string()];
+        let options = DependencyOptions::default();
+        let order = resolve_closure_for(&roots, &formulas, &options, TargetOs::Linux).unwrap();
         assert_eq!(order, vec!["expat", "openssl@3", "python@3.14"]);
+
+        let order = resolve_closure_for(&roots, &formulas, &options, TargetOs::MacOs).unwrap();
+        assert_eq!(order, vec!["openssl@3", "python@3.14"]);
     }
 }
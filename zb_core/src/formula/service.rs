@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::Error;
+
+/// How a formula runs in the background, as given by the API's `service` key.
+/// Paths may contain `$HOMEBREW_PREFIX` and `$HOMEBREW_CELLAR`.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct Service {
+    #[serde(default)]
+    pub run: Option<ServiceRun>,
+    /// `immediate` (the default), `interval` or `cron`.
+    #[serde(default)]
+    pub run_type: Option<String>,
+    /// Seconds between runs of an `interval` service.
+    #[serde(default)]
+    pub interval: Option<u64>,
+    #[serde(default)]
+    pub keep_alive: Option<KeepAlive>,
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    #[serde(default)]
+    pub log_path: Option<String>,
+    #[serde(default)]
+    pub error_log_path: Option<String>,
+    #[serde(default)]
+    pub environment_variables: BTreeMap<String, String>,
+}
+
+/// The command a service runs: an argument vector, a single program, or
+/// either of those per OS (`macos`/`linux`).
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ServiceRun {
+    Args(Vec<String>),
+    Program(String),
+    PerOs(BTreeMap<String, ServiceRun>),
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum KeepAlive {
+    Flag(bool),
+    Conditions {
+        #[serde(default)]
+        always: bool,
+        #[serde(default)]
+        successful_exit: Option<bool>,
+        #[serde(default)]
+        crashed: Option<bool>,
+    },
+}
+
+/// When the service manager should restart a service that exited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    Never,
+    Always,
+    /// Only after a non-zero exit.
+    OnFailure,
+    /// Only after a clean exit.
+    OnSuccess,
+}
+
+/// A [`Service`] with placeholders resolved for one formula on this host,
+/// ready to be written out as a launchd plist or systemd unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceSpec {
+    /// The launchd label, also used as the systemd unit name.
+    pub label: String,
+    pub program_args: Vec<String>,
+    pub interval: Option<u64>,
+    pub restart: RestartPolicy,
+    pub working_dir: Option<PathBuf>,
+    pub log_path: Option<PathBuf>,
+    pub error_log_path: Option<PathBuf>,
+    pub environment: BTreeMap<String, String>,
+}
+
+const HOST_OS: &str = if cfg!(target_os = "macos") {
+    "macos"
+} else {
+    "linux"
+};
+
+/// The label zerobrew gives `formula`'s service. `@` would make systemd read
+/// the unit as a template instance, so versioned formulas get a `-` instead.
+pub fn service_label(formula: &str) -> String {
+    format!(
+        "zerobrew.{}",
+        crate::formula_token(formula).replace('@', "-")
+    )
+}
+
+impl ServiceRun {
+    fn args_for(&self, os: &str) -> Option<Vec<String>> {
+        match self {
+            ServiceRun::Args(args) if !args.is_empty() => Some(args.clone()),
+            ServiceRun::Args(_) => None,
+            ServiceRun::Program(program) => Some(vec![program.clone()]),
+            ServiceRun::PerOs(by_os) => by_os.get(os)?.args_for(os),
+        }
+    }
+}
+
+impl KeepAlive {
+    fn restart_policy(&self) -> RestartPolicy {
+        match self {
+            KeepAlive::Flag(true) => RestartPolicy::Always,
+            KeepAlive::Flag(false) => RestartPolicy::Never,
+            KeepAlive::Conditions { always: true, .. } => RestartPolicy::Always,
+            KeepAlive::Conditions {
+                crashed: Some(true),
+                ..
+            } => RestartPolicy::OnFailure,
+            KeepAlive::Conditions {
+                successful_exit: Some(true),
+                ..
+            } => RestartPolicy::OnSuccess,
+            KeepAlive::Conditions { .. } => RestartPolicy::Never,
+        }
+    }
+}
+
+impl Service {
+    /// Resolve this service for `formula` on the host OS.
+    pub fn resolve(
+        &self,
+        formula: &str,
+        prefix: &Path,
+        cellar: &Path,
+    ) -> Result<ServiceSpec, Error> {
+        self.resolve_for(HOST_OS, formula, prefix, cellar)
+    }
+
+    fn resolve_for(
+        &self,
+        os: &str,
+        formula: &str,
+        prefix: &Path,
+        cellar: &Path,
+    ) -> Result<ServiceSpec, Error> {
+        let expand = |value: &str| {
+            value
+                .replace("$HOMEBREW_CELLAR", &cellar.display().to_string())
+                .replace("$HOMEBREW_PREFIX", &prefix.display().to_string())
+        };
+
+        let program_args = self
+            .run
+            .as_ref()
+            .and_then(|run| run.args_for(os))
+            .ok_or_else(|| Error::InvalidArgument {
+                message: format!("{formula}'s service has no command to run on {os}"),
+            })?;
+
+        let interval = match self.run_type.as_deref() {
+            None | Some("immediate") => None,
+            Some("interval") => Some(self.interval.ok_or_else(|| Error::InvalidArgument {
+                message: format!("{formula}'s interval service does not say how often to run"),
+            })?),
+            Some(other) => {
+                return Err(Error::InvalidArgument {
+                    message: format!("{formula}'s service has an unsupported run type '{other}'"),
+                });
+            }
+        };
+
+        Ok(ServiceSpec {
+            label: service_label(formula),
+            program_args: program_args.iter().map(|arg| expand(arg)).collect(),
+            interval,
+            restart: self
+                .keep_alive
+                .as_ref()
+                .map_or(RestartPolicy::Never, KeepAlive::restart_policy),
+            working_dir: self.working_dir.as_deref().map(|p| expand(p).into()),
+            log_path: self.log_path.as_deref().map(|p| expand(p).into()),
+            error_log_path: self.error_log_path.as_deref().map(|p| expand(p).into()),
+            environment: self
+                .environment_variables
+                .iter()
+                .map(|(key, value)| (key.clone(), expand(value)))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(json: &str) -> Service {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn resolves_placeholders_and_keep_alive() {
+        let postgres = service(
+            r#"{
+                "run": ["$HOMEBREW_PREFIX/opt/postgresql@17/bin/postgres", "-D", "$HOMEBREW_PREFIX/var/postgresql@17"],
+                "keep_alive": { "always": true },
+                "working_dir": "$HOMEBREW_PREFIX",
+                "log_path": "$HOMEBREW_PREFIX/var/log/postgresql@17.log",
+                "environment_variables": { "LC_ALL": "C" }
+            }"#,
+        );
+
+        let spec = postgres
+            .resolve_for(
+                "linux",
+                "postgresql@17",
+                Path::new("/opt/zerobrew"),
+                Path::new("/opt/zerobrew/Cellar"),
+            )
+            .unwrap();
+
+        assert_eq!(spec.label, "zerobrew.postgresql-17");
+        assert_eq!(
+            spec.program_args,
+            [
+                "/opt/zerobrew/opt/postgresql@17/bin/postgres",
+                "-D",
+                "/opt/zerobrew/var/postgresql@17"
+            ]
+        );
+        assert_eq!(spec.restart, RestartPolicy::Always);
+        assert_eq!(
+            spec.working_dir.as_deref(),
+            Some(Path::new("/opt/zerobrew"))
+        );
+        assert_eq!(
+            spec.log_path.as_deref(),
+            Some(Path::new("/opt/zerobrew/var/log/postgresql@17.log"))
+        );
+        assert_eq!(spec.error_log_path, None);
+        assert_eq!(spec.environment["LC_ALL"], "C");
+        assert_eq!(spec.interval, None);
+    }
+
+    #[test]
+    fn picks_the_command_for_the_host_os() {
+        let per_os = service(
+            r#"{
+                "run": { "macos": "$HOMEBREW_PREFIX/bin/foo-mac", "linux": ["$HOMEBREW_PREFIX/bin/foo", "--linux"] },
+                "run_type": "interval",
+                "interval": 300,
+                "keep_alive": { "crashed": true }
+            }"#,
+        );
+        let prefix = Path::new("/p");
+        let cellar = Path::new("/p/Cellar");
+
+        let mac = per_os.resolve_for("macos", "foo", prefix, cellar).unwrap();
+        assert_eq!(mac.program_args, ["/p/bin/foo-mac"]);
+        assert_eq!(mac.interval, Some(300));
+        assert_eq!(mac.restart, RestartPolicy::OnFailure);
+
+        let linux = per_os.resolve_for("linux", "foo", prefix, cellar).unwrap();
+        assert_eq!(linux.program_args, ["/p/bin/foo", "--linux"]);
+    }
+
+    #[test]
+    fn rejects_services_it_cannot_run() {
+        let prefix = Path::new("/p");
+        let cellar = Path::new("/p/Cellar");
+
+        let mac_only = service(r#"{ "run": { "macos": ["/bin/true"] } }"#);
+        assert!(matches!(
+            mac_only.resolve_for("linux", "foo", prefix, cellar),
+            Err(Error::InvalidArgument { .. })
+        ));
+
+        let cron = service(r#"{ "run": ["/bin/true"], "run_type": "cron" }"#);
+        assert!(matches!(
+            cron.resolve_for("linux", "foo", prefix, cellar),
+            Err(Error::InvalidArgument { .. })
+        ));
+    }
+}
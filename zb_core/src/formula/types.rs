@@ -1,5 +1,8 @@
 use serde::Deserialize;
 use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::service::Service;
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum KegOnly {
@@ -35,6 +38,22 @@ impl KegOnlyReason {
             ":provided_by_macos" | ":shadowed_by_macos"
         )
     }
+
+    /// The explanation if the formula gives one, else Homebrew's wording for
+    /// the reason symbol.
+    pub fn describe(&self) -> Option<String> {
+        if !self.explanation.trim().is_empty() {
+            return Some(self.explanation.trim().to_string());
+        }
+        let described = match self.reason.as_str() {
+            ":provided_by_macos" => "macOS already provides this software",
+            ":shadowed_by_macos" => "macOS provides similar software",
+            ":versioned_formula" => "this is an alternate version of another formula",
+            "" => return None,
+            other => return Some(other.trim_start_matches(':').replace('_', " ")),
+        };
+        Some(described.to_string())
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
@@ -46,6 +65,22 @@ pub struct SourceUrl {
     pub tag: Option<String>,
     #[serde(default)]
     pub revision: Option<String>,
+    /// Alternate locations for the same file, tried in order when `url`
+    /// can't be fetched.
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+}
+
+/// Where a formula's development source lives, for `--HEAD` builds.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct HeadUrl {
+    pub url: String,
+    /// Branch to build; the repository's default branch when unset.
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// The download strategy, such as `git` or `hg`; git when unset.
+    #[serde(default)]
+    pub using: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
@@ -108,6 +143,55 @@ impl UsesFromMacos {
     }
 }
 
+/// How a formula depends on another, following the API's grouping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DependencyKind {
+    Runtime,
+    Build,
+    Test,
+    Recommended,
+    Optional,
+}
+
+/// The platform a dependency closure is resolved for. On macOS,
+/// `uses_from_macos` dependencies come with the system; everywhere else
+/// they're real dependencies and the Linux variations of the API apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TargetOs {
+    MacOs,
+    Linux,
+}
+
+impl TargetOs {
+    pub fn host() -> Self {
+        if cfg!(target_os = "macos") {
+            TargetOs::MacOs
+        } else {
+            TargetOs::Linux
+        }
+    }
+}
+
+/// Which recommended and optional dependencies an install pulls in, from
+/// `--without` and `--with`. Required runtime dependencies can't be left
+/// out, and test dependencies are never installed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyOptions {
+    pub with: Vec<String>,
+    pub without: Vec<String>,
+}
+
+impl DependencyOptions {
+    pub fn includes(&self, name: &str, kind: DependencyKind) -> bool {
+        match kind {
+            DependencyKind::Runtime => true,
+            DependencyKind::Recommended => !self.without.iter().any(|n| n == name),
+            DependencyKind::Optional => self.with.iter().any(|n| n == name),
+            DependencyKind::Build | DependencyKind::Test => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 pub struct Formula {
     pub name: String,
@@ -122,6 +206,15 @@ pub struct Formula {
     pub keg_only_reason: Option<KegOnlyReason>,
     #[serde(default)]
     pub build_dependencies: Vec<String>,
+    /// Only needed by the formula's `test do` block, so never installed.
+    #[serde(default)]
+    pub test_dependencies: Vec<String>,
+    /// Installed by default; `--without` skips them.
+    #[serde(default)]
+    pub recommended_dependencies: Vec<String>,
+    /// Installed only when asked for with `--with`.
+    #[serde(default)]
+    pub optional_dependencies: Vec<String>,
     #[serde(default)]
     pub urls: Option<FormulaUrls>,
     #[serde(default)]
@@ -134,6 +227,27 @@ pub struct Formula {
     pub requirements: Vec<serde_json::Value>,
     #[serde(default)]
     pub variations: Option<serde_json::Value>,
+    #[serde(default)]
+    pub caveats: Option<String>,
+    #[serde(default)]
+    pub conflicts_with: Vec<String>,
+    /// Why each of `conflicts_with` conflicts, in the same order; entries
+    /// may be empty.
+    #[serde(default)]
+    pub conflicts_with_reasons: Vec<String>,
+    #[serde(default)]
+    pub service: Option<Service>,
+    #[serde(default)]
+    pub desc: Option<String>,
+    #[serde(default)]
+    pub homepage: Option<String>,
+    /// SPDX expression, e.g. `MIT` or `Apache-2.0 OR MIT`.
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Whether the formula has a `post_install` block, which the installer
+    /// runs through the Ruby shim once the keg is in place.
+    #[serde(default)]
+    pub post_install_defined: bool,
 }
 
 impl Formula {
@@ -161,6 +275,55 @@ impl Formula {
         true
     }
 
+    /// Why this formula conflicts with `name`, if it says.
+    pub fn conflict_reason(&self, name: &str) -> Option<&str> {
+        let index = self.conflicts_with.iter().position(|c| c == name)?;
+        self.conflicts_with_reasons
+            .get(index)
+            .map(|reason| reason.trim())
+            .filter(|reason| !reason.is_empty())
+    }
+
+    /// Why the formula isn't linked into the prefix, or `None` if it is.
+    pub fn keg_only_reason_text(&self) -> Option<String> {
+        if !self.is_keg_only() {
+            return None;
+        }
+        if let KegOnly::Reason(reason) = &self.keg_only
+            && !reason.trim().is_empty()
+        {
+            return Some(reason.trim().to_string());
+        }
+        if let Some(reason) = self
+            .keg_only_reason
+            .as_ref()
+            .and_then(KegOnlyReason::describe)
+        {
+            return Some(reason);
+        }
+        Some(if self.name.contains('@') {
+            "this is an alternate version of another formula".to_string()
+        } else {
+            "this formula is keg-only".to_string()
+        })
+    }
+
+    /// Caveats with the API's `$HOMEBREW_PREFIX` and `$HOMEBREW_CELLAR`
+    /// placeholders replaced by the real locations, so the commands they
+    /// contain can be copied and run as-is.
+    pub fn rendered_caveats(&self, prefix: &Path, cellar: &Path) -> Option<String> {
+        let caveats = self.caveats.as_deref()?.trim_end();
+        if caveats.is_empty() {
+            return None;
+        }
+
+        Some(
+            caveats
+                .replace("$HOMEBREW_CELLAR", &cellar.display().to_string())
+                .replace("$HOMEBREW_PREFIX", &prefix.display().to_string()),
+        )
+    }
+
     pub fn source_url(&self) -> Option<&SourceUrl> {
         self.urls.as_ref().and_then(|u| u.stable.as_ref())
     }
@@ -169,49 +332,99 @@ impl Formula {
         self.source_url().is_some()
     }
 
+    /// The git repository a `--HEAD` build clones. Heads fetched some other
+    /// way, such as Mercurial, aren't supported.
+    pub fn head_url(&self) -> Option<HeadUrl> {
+        let head = self.urls.as_ref()?.head.clone()?;
+        let head: HeadUrl = serde_json::from_value(head).ok()?;
+        matches!(head.using.as_deref(), None | Some("git")).then_some(head)
+    }
+
     pub fn all_build_dependencies(&self) -> Vec<String> {
-        let deps = self.build_dependencies.clone();
-        #[cfg(not(target_os = "macos"))]
-        let deps = {
-            let mut deps = deps;
-            for u in self.active_uses_from_macos() {
-                push_unique_dep(&mut deps, u.name());
-            }
-            deps
-        };
+        self.all_build_dependencies_for(TargetOs::host())
+    }
+
+    /// `:build` dependencies on `target`, where every `uses_from_macos`
+    /// entry counts outside macOS.
+    pub fn all_build_dependencies_for(&self, target: TargetOs) -> Vec<String> {
+        let mut deps = self.build_dependencies.clone();
+        for u in self.active_uses_from_macos(target) {
+            push_unique_dep(&mut deps, u.name());
+        }
         deps
     }
 
+    /// What a default install pulls in: required runtime dependencies plus
+    /// recommended ones.
     pub fn runtime_dependencies(&self) -> Vec<String> {
-        #[cfg(not(target_os = "macos"))]
+        self.runtime_dependencies_for(TargetOs::host())
+    }
+
+    /// [`Self::runtime_dependencies`] on `target`.
+    pub fn runtime_dependencies_for(&self, target: TargetOs) -> Vec<String> {
+        let mut deps = self.platform_dependencies(target);
+        for dep in self
+            .active_uses_from_macos(target)
+            .iter()
+            .filter(|dep| dep.is_runtime_dependency())
         {
-            let mut deps = self.platform_dependencies();
-            for dep in self
-                .active_uses_from_macos()
-                .iter()
-                .filter(|dep| dep.is_runtime_dependency())
-            {
-                push_unique_dep(&mut deps, dep.name());
-            }
-            deps
+            push_unique_dep(&mut deps, dep.name());
+        }
+        for dep in &self.recommended_dependencies {
+            push_unique_dep(&mut deps, dep);
         }
+        deps
+    }
 
-        #[cfg(target_os = "macos")]
-        {
-            self.platform_dependencies()
+    /// The dependencies of one kind. `Runtime` means the required ones only,
+    /// unlike [`Self::runtime_dependencies`].
+    pub fn dependencies_of_kind(&self, kind: DependencyKind) -> Vec<String> {
+        match kind {
+            DependencyKind::Runtime => self
+                .runtime_dependencies()
+                .into_iter()
+                .filter(|dep| !self.recommended_dependencies.contains(dep))
+                .collect(),
+            DependencyKind::Build => self.all_build_dependencies(),
+            DependencyKind::Test => self.test_dependencies.clone(),
+            DependencyKind::Recommended => self.recommended_dependencies.clone(),
+            DependencyKind::Optional => self.optional_dependencies.clone(),
         }
     }
 
-    fn platform_dependencies(&self) -> Vec<String> {
-        #[cfg(target_os = "linux")]
-        if let Some(deps) = self.variation_dependencies(preferred_linux_variation_keys()) {
+    /// The dependencies installing this formula pulls in under `options`.
+    pub fn dependencies_with(&self, options: &DependencyOptions) -> Vec<String> {
+        self.dependencies_for(options, TargetOs::host())
+    }
+
+    /// [`Self::dependencies_with`] on `target`.
+    pub fn dependencies_for(&self, options: &DependencyOptions, target: TargetOs) -> Vec<String> {
+        let mut deps: Vec<String> = self
+            .runtime_dependencies_for(target)
+            .into_iter()
+            .filter(|dep| {
+                !self.recommended_dependencies.contains(dep)
+                    || options.includes(dep, DependencyKind::Recommended)
+            })
+            .collect();
+        for dep in &self.optional_dependencies {
+            if options.includes(dep, DependencyKind::Optional) {
+                push_unique_dep(&mut deps, dep);
+            }
+        }
+        deps
+    }
+
+    fn platform_dependencies(&self, target: TargetOs) -> Vec<String> {
+        if target == TargetOs::Linux
+            && let Some(deps) = self.variation_dependencies(preferred_linux_variation_keys())
+        {
             return deps;
         }
 
         self.dependencies.clone()
     }
 
-    #[cfg(target_os = "linux")]
     fn variation_dependencies(&self, keys: &[&str]) -> Option<Vec<String>> {
         let variations = self.variations.as_ref()?.as_object()?;
         for key in keys {
@@ -230,9 +443,12 @@ impl Formula {
         None
     }
 
-    #[cfg(not(target_os = "macos"))]
-    fn active_uses_from_macos(&self) -> Vec<UsesFromMacos> {
-        #[cfg(target_os = "linux")]
+    /// The `uses_from_macos` entries that are dependencies on `target`:
+    /// none on macOS, where the system provides them.
+    fn active_uses_from_macos(&self, target: TargetOs) -> Vec<UsesFromMacos> {
+        if target == TargetOs::MacOs {
+            return Vec::new();
+        }
         if let Some(deps) = self.variation_uses_from_macos(preferred_linux_variation_keys()) {
             return deps;
         }
@@ -240,7 +456,6 @@ impl Formula {
         self.uses_from_macos.clone()
     }
 
-    #[cfg(not(target_os = "macos"))]
     fn variation_uses_from_macos(&self, keys: &[&str]) -> Option<Vec<UsesFromMacos>> {
         let variations = self.variations.as_ref()?.as_object()?;
         for key in keys {
@@ -255,14 +470,12 @@ impl Formula {
     }
 }
 
-#[cfg(not(target_os = "macos"))]
 fn push_unique_dep(deps: &mut Vec<String>, name: &str) {
     if !deps.iter().any(|existing| existing == name) {
         deps.push(name.to_string());
     }
 }
 
-#[cfg(target_os = "linux")]
 fn preferred_linux_variation_keys() -> &'static [&'static str] {
     match std::env::consts::ARCH {
         "aarch64" => &["arm64_linux", "aarch64_linux"],
@@ -336,6 +549,46 @@ mod tests {
         assert_eq!(formula.effective_version(), "1.2.3_1");
     }
 
+    #[test]
+    fn rendered_caveats_substitutes_prefix_placeholders() {
+        let mut formula: Formula =
+            serde_json::from_str(include_str!("../../fixtures/formula_foo.json")).unwrap();
+        formula.caveats = Some(
+            "To start foo now:\n  $HOMEBREW_PREFIX/opt/foo/bin/foo -D $HOMEBREW_PREFIX/var/foo\n\
+             Data lives in $HOMEBREW_CELLAR/foo\n"
+                .to_string(),
+        );
+
+        let rendered = formula
+            .rendered_caveats(
+                Path::new("/opt/zerobrew"),
+                Path::new("/opt/zerobrew/Cellar"),
+            )
+            .unwrap();
+
+        assert_eq!(
+            rendered,
+            "To start foo now:\n  /opt/zerobrew/opt/foo/bin/foo -D /opt/zerobrew/var/foo\n\
+             Data lives in /opt/zerobrew/Cellar/foo"
+        );
+    }
+
+    #[test]
+    fn rendered_caveats_skips_blank_caveats() {
+        let mut formula: Formula =
+            serde_json::from_str(include_str!("../../fixtures/formula_foo.json")).unwrap();
+        assert_eq!(
+            formula.rendered_caveats(Path::new("/p"), Path::new("/c")),
+            None
+        );
+
+        formula.caveats = Some("  \n".to_string());
+        assert_eq!(
+            formula.rendered_caveats(Path::new("/p"), Path::new("/c")),
+            None
+        );
+    }
+
     #[test]
     fn effective_version_ignores_rebuild_for_dir_name() {
         let fixture = include_str!("../../fixtures/formula_with_rebuild.json");
@@ -347,6 +600,31 @@ mod tests {
         assert_eq!(formula.effective_version(), "8.0.1");
     }
 
+    #[test]
+    fn head_url_reads_git_heads_only() {
+        let mut formula: Formula =
+            serde_json::from_str(include_str!("../../fixtures/formula_foo.json")).unwrap();
+        assert_eq!(formula.head_url(), None);
+
+        formula.urls = Some(FormulaUrls {
+            stable: None,
+            head: Some(serde_json::json!({
+                "url": "https://github.com/example/foo.git",
+                "branch": "main",
+                "using": null
+            })),
+        });
+        let head = formula.head_url().unwrap();
+        assert_eq!(head.url, "https://github.com/example/foo.git");
+        assert_eq!(head.branch.as_deref(), Some("main"));
+
+        formula.urls = Some(FormulaUrls {
+            stable: None,
+            head: Some(serde_json::json!({ "url": "https://hg.example.com/foo", "using": "hg" })),
+        });
+        assert_eq!(formula.head_url(), None);
+    }
+
     #[test]
     fn revision_field_defaults_to_zero() {
         let fixture = include_str!("../../fixtures/formula_foo.json");
@@ -354,6 +632,67 @@ mod tests {
         assert_eq!(formula.revision, 0);
     }
 
+    #[test]
+    fn metadata_fields_are_optional() {
+        let fixture = include_str!("../../fixtures/formula_foo.json");
+        let formula: Formula = serde_json::from_str(fixture).unwrap();
+        assert_eq!(formula.desc, None);
+
+        let json = r#"{
+            "name": "jq",
+            "versions": { "stable": "1.7.1" },
+            "dependencies": [],
+            "bottle": { "stable": { "files": {} } },
+            "desc": "Lightweight and flexible command-line JSON processor",
+            "homepage": "https://jqlang.github.io/jq/",
+            "license": "MIT"
+        }"#;
+        let formula: Formula = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            formula.homepage.as_deref(),
+            Some("https://jqlang.github.io/jq/")
+        );
+        assert_eq!(formula.license.as_deref(), Some("MIT"));
+        assert!(formula.desc.unwrap().starts_with("Lightweight"));
+    }
+
+    #[test]
+    fn dependency_kinds_deserialize_separately() {
+        let json = r#"{
+            "name": "gnupg",
+            "versions": { "stable": "2.4.5" },
+            "dependencies": ["libgcrypt"],
+            "build_dependencies": ["pkgconf"],
+            "test_dependencies": ["expect"],
+            "recommended_dependencies": ["pinentry"],
+            "optional_dependencies": ["libusb"],
+            "bottle": { "stable": { "files": {} } }
+        }"#;
+        let formula: Formula = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            formula.dependencies_of_kind(DependencyKind::Runtime),
+            vec!["libgcrypt"]
+        );
+        assert_eq!(
+            formula.dependencies_of_kind(DependencyKind::Test),
+            vec!["expect"]
+        );
+        assert_eq!(
+            formula.runtime_dependencies(),
+            vec!["libgcrypt", "pinentry"]
+        );
+
+        let options = DependencyOptions {
+            with: vec!["libusb".to_string(), "expect".to_string()],
+            without: vec!["pinentry".to_string()],
+        };
+        assert_eq!(
+            formula.dependencies_with(&options),
+            vec!["libgcrypt", "libusb"]
+        );
+    }
+
     #[test]
     fn keg_only_defaults_to_no() {
         let fixture = include_str!("../../fixtures/formula_foo.json");
@@ -362,6 +701,25 @@ mod tests {
         assert!(!formula.is_keg_only());
     }
 
+    #[test]
+    fn conflict_reasons_line_up_with_conflicts() {
+        let json = r#"{
+            "name": "mysql",
+            "versions": { "stable": "9.0" },
+            "dependencies": [],
+            "conflicts_with": ["mariadb", "percona-server"],
+            "conflicts_with_reasons": ["both install the same binaries", ""],
+            "bottle": { "stable": { "files": {} } }
+        }"#;
+        let formula: Formula = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            formula.conflict_reason("mariadb"),
+            Some("both install the same binaries")
+        );
+        assert_eq!(formula.conflict_reason("percona-server"), None);
+        assert_eq!(formula.conflict_reason("postgresql"), None);
+    }
+
     #[test]
     fn keg_only_deserializes_bool_true() {
         let json = r#"{
@@ -439,6 +797,44 @@ mod tests {
         assert!(reason.is_macos_specific());
     }
 
+    #[test]
+    fn keg_only_reason_text_prefers_the_explanation() {
+        let json = r#"{
+            "name": "libpq",
+            "versions": { "stable": "17.0" },
+            "dependencies": [],
+            "keg_only": true,
+            "keg_only_reason": { "reason": ":versioned_formula", "explanation": "conflicts with postgres" },
+            "bottle": { "stable": { "files": {} } }
+        }"#;
+        let formula: Formula = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            formula.keg_only_reason_text().as_deref(),
+            Some("conflicts with postgres")
+        );
+
+        let json = r#"{
+            "name": "python@3.11",
+            "versions": { "stable": "3.11.9" },
+            "dependencies": [],
+            "bottle": { "stable": { "files": {} } }
+        }"#;
+        let formula: Formula = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            formula.keg_only_reason_text().as_deref(),
+            Some("this is an alternate version of another formula")
+        );
+
+        let json = r#"{
+            "name": "jq",
+            "versions": { "stable": "1.7.1" },
+            "dependencies": [],
+            "bottle": { "stable": { "files": {} } }
+        }"#;
+        let formula: Formula = serde_json::from_str(json).unwrap();
+        assert_eq!(formula.keg_only_reason_text(), None);
+    }
+
     #[test]
     fn keg_only_reason_generic_is_not_macos_specific() {
         let reason = KegOnlyReason {
@@ -515,7 +911,6 @@ mod tests {
     }
 
     #[test]
-    #[cfg(not(target_os = "macos"))]
     fn runtime_dependencies_include_runtime_uses_from_macos_on_linux() {
         let mut formula: Formula =
             serde_json::from_str(include_str!("../../fixtures/formula_foo.json")).unwrap();
@@ -529,16 +924,26 @@ mod tests {
         ];
 
         assert_eq!(
-            formula.runtime_dependencies(),
+            formula.runtime_dependencies_for(TargetOs::Linux),
             vec!["openssl@3".to_string(), "expat".to_string()]
         );
+        assert_eq!(
+            formula.all_build_dependencies_for(TargetOs::Linux),
+            vec!["expat".to_string(), "pkgconf".to_string()]
+        );
+        assert_eq!(
+            formula.runtime_dependencies_for(TargetOs::MacOs),
+            vec!["openssl@3".to_string()]
+        );
+        assert!(
+            formula
+                .all_build_dependencies_for(TargetOs::MacOs)
+                .is_empty()
+        );
     }
 
     #[test]
-    #[cfg(all(
-        target_os = "linux",
-        any(target_arch = "x86_64", target_arch = "aarch64")
-    ))]
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
     fn runtime_dependencies_use_linux_variation_dependencies() {
         let mut formula: Formula =
             serde_json::from_str(include_str!("../../fixtures/formula_foo.json")).unwrap();
@@ -550,7 +955,11 @@ mod tests {
         formula.uses_from_macos = vec![UsesFromMacos::Plain("expat".to_string())];
 
         assert_eq!(
-            formula.runtime_dependencies(),
+            formula.runtime_dependencies_for(TargetOs::MacOs),
+            vec!["openssl@3".to_string()]
+        );
+        assert_eq!(
+            formula.runtime_dependencies_for(TargetOs::Linux),
             vec![
                 "openssl@3".to_string(),
                 "zlib-ng-compat".to_string(),
@@ -560,10 +969,7 @@ mod tests {
     }
 
     #[test]
-    #[cfg(all(
-        target_os = "linux",
-        any(target_arch = "x86_64", target_arch = "aarch64")
-    ))]
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
     fn runtime_dependencies_include_linux_variation_uses_from_macos() {
         let mut formula: Formula =
             serde_json::from_str(include_str!("../../fixtures/formula_foo.json")).unwrap();
@@ -581,7 +987,7 @@ mod tests {
         }));
 
         assert_eq!(
-            formula.runtime_dependencies(),
+            formula.runtime_dependencies_for(TargetOs::Linux),
             vec![
                 "openssl@3".to_string(),
                 "zlib-ng-compat".to_string(),
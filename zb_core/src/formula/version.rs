@@ -0,0 +1,143 @@
+use std::cmp::Ordering;
+
+/// Compare two formula versions the way Homebrew orders them.
+///
+/// Versions are split into runs of digits and runs of letters, ignoring
+/// separators, and compared piece by piece: numbers numerically, words
+/// alphabetically. A trailing `_N` revision (see `Formula::effective_version`)
+/// breaks ties. Missing pieces count as zero, so `1.2` equals `1.2.0`, and
+/// pre-release words (`alpha`, `beta`, `pre`, `rc`) sort before a release,
+/// so `2.0rc1` is older than `2.0`.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a, a_revision) = split_revision(a);
+    let (b, b_revision) = split_revision(b);
+    let a = tokenize(a);
+    let b = tokenize(b);
+
+    for i in 0..a.len().max(b.len()) {
+        let ordering = match (a.get(i), b.get(i)) {
+            (Some(x), Some(y)) => x.cmp(y),
+            (Some(x), None) => x.cmp_missing(),
+            (None, Some(y)) => y.cmp_missing().reverse(),
+            (None, None) => Ordering::Equal,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    a_revision.cmp(&b_revision)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    PreRelease(&'a str),
+    Number(u64),
+    Word(&'a str),
+}
+
+impl Token<'_> {
+    fn rank(&self) -> u8 {
+        match self {
+            Token::PreRelease(_) => 0,
+            Token::Number(_) => 1,
+            Token::Word(_) => 2,
+        }
+    }
+
+    /// How this token compares to the absence of one in a shorter version.
+    fn cmp_missing(&self) -> Ordering {
+        match self {
+            Token::PreRelease(_) => Ordering::Less,
+            Token::Number(0) => Ordering::Equal,
+            Token::Number(_) | Token::Word(_) => Ordering::Greater,
+        }
+    }
+}
+
+impl Ord for Token<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Token::Number(x), Token::Number(y)) => x.cmp(y),
+            (Token::PreRelease(x), Token::PreRelease(y)) | (Token::Word(x), Token::Word(y)) => {
+                x.cmp(y)
+            }
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Token<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn split_revision(version: &str) -> (&str, u64) {
+    if let Some((base, revision)) = version.rsplit_once('_')
+        && let Ok(revision) = revision.parse()
+    {
+        return (base, revision);
+    }
+    (version, 0)
+}
+
+fn tokenize(version: &str) -> Vec<Token<'_>> {
+    // Byte offset where the current run starts, and whether it is digits.
+    let mut run: Option<(usize, bool)> = None;
+    let mut pieces = Vec::new();
+
+    for (i, c) in version.char_indices() {
+        let kind = c.is_ascii_alphanumeric().then_some(c.is_ascii_digit());
+        match (run, kind) {
+            (Some((start, digits)), Some(is_digit)) if digits != is_digit => {
+                pieces.push(&version[start..i]);
+                run = Some((i, is_digit));
+            }
+            (Some((start, _)), None) => {
+                pieces.push(&version[start..i]);
+                run = None;
+            }
+            (None, Some(is_digit)) => run = Some((i, is_digit)),
+            _ => {}
+        }
+    }
+    if let Some((start, _)) = run {
+        pieces.push(&version[start..]);
+    }
+
+    pieces
+        .into_iter()
+        .map(|piece| match piece.parse() {
+            Ok(number) => Token::Number(number),
+            Err(_) if matches!(piece, "alpha" | "beta" | "pre" | "rc") => Token::PreRelease(piece),
+            Err(_) => Token::Word(piece),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_components_compare_as_numbers() {
+        assert_eq!(compare_versions("1.10.0", "1.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("1.7.1", "1.7.1"), Ordering::Equal);
+        assert_eq!(compare_versions("1.2", "1.2.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.2", "1.2.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn pre_releases_sort_before_the_release() {
+        assert_eq!(compare_versions("2.0rc1", "2.0"), Ordering::Less);
+        assert_eq!(compare_versions("2.0-beta", "2.0-rc1"), Ordering::Less);
+        assert_eq!(compare_versions("1.1.1w", "1.1.1"), Ordering::Greater);
+    }
+
+    #[test]
+    fn revision_breaks_ties() {
+        assert_eq!(compare_versions("1.7.1_1", "1.7.1"), Ordering::Greater);
+        assert_eq!(compare_versions("1.7.1_1", "1.7.2"), Ordering::Less);
+    }
+}
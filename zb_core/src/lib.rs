@@ -3,12 +3,16 @@ pub mod context;
 pub mod errors;
 pub mod formula;
 
-pub use build::{BuildPlan, BuildSystem, InstallMethod};
+pub use build::{
+    BuildPlan, BuildSystem, HeadCheckout, InstallMethod, head_version, is_head_version,
+};
 pub use context::{ConcurrencyLimits, Context, LogLevel, LoggerHandle, Paths};
-pub use errors::{ConflictedLink, Error};
+pub use errors::{ConflictedLink, Error, ErrorKind};
 pub use formula::{
-    Formula, KegOnly, KegOnlyReason, SelectedBottle, compatible_codenames, formula_token,
-    resolve_closure, select_bottle,
+    BottlePolicy, BottleTag, DependencyKind, DependencyOptions, Formula, KegOnly, KegOnlyReason,
+    RestartPolicy, SelectedBottle, Service, ServiceSpec, TargetOs, bottle_tags, compare_versions,
+    compatible_codenames, formula_token, resolve_closure, resolve_closure_for,
+    resolve_closure_with, select_bottle, select_bottle_with_policy, service_label,
 };
 
 #[cfg(target_os = "macos")]
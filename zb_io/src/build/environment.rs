@@ -46,19 +46,13 @@ pub fn build_env(plan: &BuildPlan, prefix: &Path) -> HashMap<String, String> {
             .to_string(),
     );
 
-    env.insert("HOMEBREW_PREFIX".into(), prefix.display().to_string());
-    env.insert(
-        "HOMEBREW_CELLAR".into(),
-        prefix.join("Cellar").display().to_string(),
-    );
-
-    env.insert("ZEROBREW_PREFIX".into(), prefix.display().to_string());
-    env.insert(
-        "ZEROBREW_CELLAR".into(),
-        prefix.join("Cellar").display().to_string(),
+    insert_shim_env(
+        &mut env,
+        prefix,
+        &prefix.join("Cellar"),
+        &plan.formula_name,
+        &plan.version,
     );
-    env.insert("ZEROBREW_FORMULA_NAME".into(), plan.formula_name.clone());
-    env.insert("ZEROBREW_FORMULA_VERSION".into(), plan.version.clone());
 
     env.insert("MAKEFLAGS".into(), format!("-j{}", num_cpus()));
 
@@ -75,6 +69,57 @@ pub fn build_env(plan: &BuildPlan, prefix: &Path) -> HashMap<String, String> {
     env
 }
 
+/// Environment for a formula's `post_install` block, run against the keg
+/// already in `cellar`: the prefix's tools come first on `PATH`, and there
+/// are no compiler flags since nothing is built.
+pub fn post_install_env(
+    prefix: &Path,
+    cellar: &Path,
+    formula_name: &str,
+    version: &str,
+) -> HashMap<String, String> {
+    let mut env = HashMap::new();
+    let system_path = std::env::var("PATH").unwrap_or_default();
+    env.insert(
+        "PATH".into(),
+        format!("{}:{system_path}", prefix.join("bin").display()),
+    );
+    insert_shim_env(&mut env, prefix, cellar, formula_name, version);
+    env
+}
+
+/// The variables the Ruby shim reads to place the formula.
+fn insert_shim_env(
+    env: &mut HashMap<String, String>,
+    prefix: &Path,
+    cellar: &Path,
+    formula_name: &str,
+    version: &str,
+) {
+    env.insert("HOMEBREW_PREFIX".into(), prefix.display().to_string());
+    env.insert("HOMEBREW_CELLAR".into(), cellar.display().to_string());
+
+    env.insert("ZEROBREW_PREFIX".into(), prefix.display().to_string());
+    env.insert("ZEROBREW_CELLAR".into(), cellar.display().to_string());
+    env.insert("ZEROBREW_FORMULA_NAME".into(), formula_name.to_string());
+    env.insert("ZEROBREW_FORMULA_VERSION".into(), version.to_string());
+}
+
+/// Render a build environment as sorted `KEY=value` lines, one per variable.
+pub fn format_build_env(env: &HashMap<String, String>) -> String {
+    let mut keys: Vec<&String> = env.keys().collect();
+    keys.sort();
+
+    let mut out = String::new();
+    for key in keys {
+        out.push_str(key);
+        out.push('=');
+        out.push_str(&env[key]);
+        out.push('\n');
+    }
+    out
+}
+
 fn num_cpus() -> usize {
     std::thread::available_parallelism()
         .map(|n| n.get())
@@ -93,12 +138,14 @@ mod tests {
             version: "1.0.0".to_string(),
             source_url: "https://example.com/test.tar.gz".to_string(),
             source_checksum: None,
+            source_mirrors: Vec::new(),
             ruby_source_path: None,
             build_dependencies: Vec::new(),
             runtime_dependencies: Vec::new(),
             detected_system: BuildSystem::Autoconf,
             prefix: PathBuf::from("/opt/zerobrew/prefix"),
             cellar_path: PathBuf::from("/opt/zerobrew/cellar/test/1.0.0"),
+            head: None,
         }
     }
 
@@ -123,4 +170,28 @@ mod tests {
         assert!(env.contains_key("ZEROBREW_FORMULA_NAME"));
         assert!(env.contains_key("MAKEFLAGS"));
     }
+
+    #[test]
+    fn post_install_env_points_the_shim_at_the_installed_keg() {
+        let prefix = PathBuf::from("/opt/zerobrew/prefix");
+        let cellar = PathBuf::from("/opt/zerobrew/prefix/Cellar");
+        let env = post_install_env(&prefix, &cellar, "test", "1.0.0_1");
+        assert_eq!(env["ZEROBREW_CELLAR"], "/opt/zerobrew/prefix/Cellar");
+        assert_eq!(env["ZEROBREW_FORMULA_VERSION"], "1.0.0_1");
+        assert!(env["PATH"].starts_with("/opt/zerobrew/prefix/bin:"));
+        assert!(!env.contains_key("CFLAGS"));
+    }
+
+    #[test]
+    fn format_build_env_sorts_keys() {
+        let mut env = HashMap::new();
+        env.insert("PATH".to_string(), "/usr/bin".to_string());
+        env.insert("CFLAGS".to_string(), "-O2".to_string());
+        env.insert("LDFLAGS".to_string(), String::new());
+
+        assert_eq!(
+            format_build_env(&env),
+            "CFLAGS=-O2\nLDFLAGS=\nPATH=/usr/bin\n"
+        );
+    }
 }
@@ -1,26 +1,63 @@
 use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::{Arc, Mutex};
 
 use tokio::fs;
 use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 use tokio::process::Command;
+use tracing::warn;
 use zb_core::{BuildPlan, Error};
 
-use super::environment::build_env;
-use super::source::download_and_extract_source;
+use super::environment::{build_env, format_build_env, post_install_env};
+use super::log::create_build_log;
+use super::patch::{Patch, apply_patches};
+use super::resource::{Resource, fetch_resources};
+use super::source::{clone_source, download_and_extract_source};
 
 const SHIM_RUBY: &str = include_str!("shim.rb");
 
+/// Knobs for source builds that don't change what gets built, only how much
+/// of the process is exposed for debugging.
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    /// Print the fully assembled build environment to stderr before the ruby
+    /// shim runs.
+    pub print_env: bool,
+    /// Leave the work directory in place when the build fails so logs such as
+    /// `config.log` can be inspected. Successful builds are always cleaned up.
+    pub keep_work_dir_on_failure: bool,
+    /// Write each build's complete output to
+    /// `<log_dir>/<formula>/<timestamp>.log` as well as the console.
+    pub log_dir: Option<PathBuf>,
+}
+
 pub struct BuildExecutor {
     prefix: PathBuf,
     work_root: PathBuf,
+    options: BuildOptions,
+}
+
+/// Parent of every source build's work directory.
+pub(crate) fn build_work_root(prefix: &Path) -> PathBuf {
+    prefix.join("tmp").join("build")
 }
 
 impl BuildExecutor {
     pub fn new(prefix: PathBuf) -> Self {
-        let work_root = prefix.join("tmp").join("build");
-        Self { prefix, work_root }
+        let work_root = build_work_root(&prefix);
+        Self {
+            prefix,
+            work_root,
+            options: BuildOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: BuildOptions) -> Self {
+        self.options = options;
+        self
     }
 
     pub async fn execute(
@@ -32,12 +69,31 @@ impl BuildExecutor {
         let work_dir = self.work_root.join(&plan.formula_name);
         self.prepare_work_dir(&work_dir).await?;
 
-        let source_root = download_and_extract_source(
-            &plan.source_url,
-            plan.source_checksum.as_deref(),
-            &work_dir,
-        )
-        .await?;
+        let result = self
+            .build_in_work_dir(plan, formula_rb_path, installed_deps, &work_dir)
+            .await;
+        self.finish_work_dir(&work_dir, result).await
+    }
+
+    async fn build_in_work_dir(
+        &self,
+        plan: &BuildPlan,
+        formula_rb_path: &Path,
+        installed_deps: &HashMap<String, DepInfo>,
+        work_dir: &Path,
+    ) -> Result<(), Error> {
+        let source_root = match &plan.head {
+            Some(checkout) => clone_source(&plan.source_url, checkout, work_dir).await?,
+            None => {
+                download_and_extract_source(
+                    &plan.source_url,
+                    &plan.source_mirrors,
+                    plan.source_checksum.as_deref(),
+                    work_dir,
+                )
+                .await?
+            }
+        };
 
         let shim_path = work_dir.join("zerobrew_shim.rb");
         fs::write(&shim_path, SHIM_RUBY)
@@ -48,6 +104,95 @@ impl BuildExecutor {
             .await
             .map_err(Error::file("failed to create cellar directory"))?;
 
+        let mut env = self.source_build_env(plan, formula_rb_path, installed_deps);
+        let ruby = find_ruby().await?;
+        let declared = read_declarations(&ruby, &shim_path, &source_root, &env).await?;
+
+        apply_patches(&declared.patches, formula_rb_path, &source_root, work_dir).await?;
+        let resources = fetch_resources(&declared.resources, work_dir).await?;
+        let resources_json = serde_json::to_string(&resources).unwrap_or_else(|_| "{}".into());
+        env.insert("ZEROBREW_RESOURCES".into(), resources_json);
+
+        if self.options.print_env {
+            eprint!("{}", format_build_env(&env));
+        }
+
+        let log = match &self.options.log_dir {
+            Some(log_dir) => Some(create_build_log(log_dir, &plan.formula_name)?),
+            None => None,
+        };
+        let (log_path, log_file) = log.unzip();
+        run_shim(
+            &ruby,
+            &shim_path,
+            &source_root,
+            &env,
+            log_file,
+            "source build",
+        )
+        .await
+        .map_err(|err| match log_path {
+            Some(path) => note_build_log(err, &path),
+            None => err,
+        })
+    }
+
+    /// Run the `post_install` block of `formula_rb_path` against the keg
+    /// already installed at `cellar/<formula_name>/<version>`.
+    pub async fn run_post_install(
+        &self,
+        formula_name: &str,
+        version: &str,
+        cellar: &Path,
+        formula_rb_path: &Path,
+    ) -> Result<(), Error> {
+        let work_dir = self.work_root.join(format!("{formula_name}-post_install"));
+        self.prepare_work_dir(&work_dir).await?;
+
+        let result = async {
+            let shim_path = work_dir.join("zerobrew_shim.rb");
+            fs::write(&shim_path, SHIM_RUBY)
+                .await
+                .map_err(Error::file("failed to write ruby shim"))?;
+
+            let mut env = post_install_env(&self.prefix, cellar, formula_name, version);
+            env.insert(
+                "ZEROBREW_FORMULA_FILE".into(),
+                formula_rb_path.display().to_string(),
+            );
+            env.insert("ZEROBREW_POST_INSTALL".into(), "1".into());
+
+            let ruby = find_ruby().await?;
+            run_shim(&ruby, &shim_path, &work_dir, &env, None, "post_install").await
+        }
+        .await;
+
+        self.cleanup_work_dir(&work_dir).await;
+        result
+    }
+
+    async fn finish_work_dir(
+        &self,
+        work_dir: &Path,
+        result: Result<(), Error>,
+    ) -> Result<(), Error> {
+        match result {
+            Err(err) if self.options.keep_work_dir_on_failure => {
+                Err(note_kept_work_dir(err, work_dir))
+            }
+            result => {
+                self.cleanup_work_dir(work_dir).await;
+                result
+            }
+        }
+    }
+
+    fn source_build_env(
+        &self,
+        plan: &BuildPlan,
+        formula_rb_path: &Path,
+        installed_deps: &HashMap<String, DepInfo>,
+    ) -> HashMap<String, String> {
         let mut env = build_env(plan, &self.prefix);
         env.insert(
             "ZEROBREW_FORMULA_FILE".into(),
@@ -56,12 +201,7 @@ impl BuildExecutor {
 
         let deps_json = serde_json::to_string(installed_deps).unwrap_or_else(|_| "{}".into());
         env.insert("ZEROBREW_INSTALLED_DEPS".into(), deps_json);
-
-        let ruby = find_ruby().await?;
-        run_build(&ruby, &shim_path, &source_root, &env).await?;
-
-        self.cleanup_work_dir(&work_dir).await;
-        Ok(())
+        env
     }
 
     async fn prepare_work_dir(&self, work_dir: &Path) -> Result<(), Error> {
@@ -78,6 +218,38 @@ impl BuildExecutor {
     }
 }
 
+fn note_kept_work_dir(err: Error, work_dir: &Path) -> Error {
+    append_note(
+        err,
+        format!("build directory kept at {}", work_dir.display()),
+    )
+}
+
+fn note_build_log(err: Error, log_path: &Path) -> Error {
+    append_note(err, format!("full build log: {}", log_path.display()))
+}
+
+/// Add `note` to the message of `err`. Variants without a free-form message
+/// are returned as they are and the note is logged instead, so callers still
+/// see the original kind of failure.
+fn append_note(err: Error, note: String) -> Error {
+    match err {
+        Error::ExecutionError { message } => Error::ExecutionError {
+            message: format!("{message}\n{note}"),
+        },
+        Error::FileError { message } => Error::FileError {
+            message: format!("{message}\n{note}"),
+        },
+        Error::NetworkFailure { message } => Error::NetworkFailure {
+            message: format!("{message}\n{note}"),
+        },
+        other => {
+            warn!("{note}");
+            other
+        }
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct DepInfo {
     pub cellar_path: String,
@@ -99,11 +271,63 @@ async fn find_ruby() -> Result<PathBuf, Error> {
     })
 }
 
-async fn run_build(
+/// What the formula declares beyond its main source, which zerobrew
+/// fetches itself so it can verify checksums.
+#[derive(Debug, Default, serde::Deserialize)]
+struct Declarations {
+    #[serde(default)]
+    patches: Vec<Patch>,
+    #[serde(default)]
+    resources: Vec<Resource>,
+}
+
+async fn read_declarations(
     ruby: &Path,
     shim_path: &Path,
     source_root: &Path,
     env: &HashMap<String, String>,
+) -> Result<Declarations, Error> {
+    let output = Command::new(ruby)
+        .arg(shim_path)
+        .current_dir(source_root)
+        .envs(env)
+        .env("ZEROBREW_DECLARATIONS", "1")
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(Error::exec("failed to execute ruby shim"))?;
+
+    if !output.status.success() {
+        return Err(Error::ExecutionError {
+            message: format!(
+                "failed to read the formula's patches and resources: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+
+    parse_declarations(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Loading the formula may print; the declarations are always the last line.
+fn parse_declarations(stdout: &str) -> Result<Declarations, Error> {
+    let Some(json) = stdout.lines().rfind(|l| !l.trim().is_empty()) else {
+        return Ok(Declarations::default());
+    };
+    serde_json::from_str(json).map_err(|e| Error::ExecutionError {
+        message: format!("ruby shim reported malformed declarations: {e}"),
+    })
+}
+
+/// Run the shim in `source_root`, streaming its output; `what` names the
+/// step in the error when it fails.
+async fn run_shim(
+    ruby: &Path,
+    shim_path: &Path,
+    source_root: &Path,
+    env: &HashMap<String, String>,
+    log: Option<File>,
+    what: &str,
 ) -> Result<(), Error> {
     let mut child = Command::new(ruby)
         .arg(shim_path)
@@ -121,8 +345,9 @@ async fn run_build(
         message: "failed to capture ruby shim stderr".to_string(),
     })?;
 
-    let stdout_task = tokio::spawn(stream_output_and_capture_tail(stdout, false));
-    let stderr_task = tokio::spawn(stream_output_and_capture_tail(stderr, true));
+    let log = log.map(|file| Arc::new(Mutex::new(file)));
+    let stdout_task = tokio::spawn(stream_output_and_capture_tail(stdout, false, log.clone()));
+    let stderr_task = tokio::spawn(stream_output_and_capture_tail(stderr, true, log));
 
     let status = child
         .wait()
@@ -139,7 +364,7 @@ async fn run_build(
         .map_err(Error::exec("failed reading stderr"))?;
 
     if !status.success() {
-        let mut msg = format!("source build failed (exit code: {:?})", status.code());
+        let mut msg = format!("{what} failed (exit code: {:?})", status.code());
         let tail = if !stderr_tail.is_empty() {
             stderr_tail
         } else {
@@ -158,6 +383,7 @@ async fn run_build(
 async fn stream_output_and_capture_tail<R>(
     reader: R,
     stderr: bool,
+    log: Option<Arc<Mutex<File>>>,
 ) -> Result<Vec<String>, std::io::Error>
 where
     R: AsyncRead + Unpin,
@@ -172,6 +398,10 @@ where
         } else {
             println!("{line}");
         }
+        if let Some(log) = &log {
+            let mut file = log.lock().unwrap_or_else(|e| e.into_inner());
+            writeln!(file, "{line}")?;
+        }
 
         if tail.len() == TAIL_LINES {
             tail.pop_front();
@@ -186,6 +416,165 @@ where
 mod tests {
     use super::*;
 
+    fn failed_build() -> Result<(), Error> {
+        Err(Error::ExecutionError {
+            message: "source build failed (exit code: Some(1))".to_string(),
+        })
+    }
+
+    #[test]
+    fn declarations_are_read_from_the_last_line() {
+        let declared = parse_declarations(concat!(
+            "loading formula...\n",
+            r#"{"patches":[{"type":"data","strip":"p0"}],"#,
+            r#""resources":[{"name":"six","url":"https://example.com/six.tar.gz","sha256":null,"mirrors":[]}]}"#,
+            "\n",
+        ))
+        .unwrap();
+        assert_eq!(declared.patches.len(), 1);
+        assert_eq!(declared.resources[0].name, "six");
+
+        let empty = parse_declarations("").unwrap();
+        assert!(empty.patches.is_empty() && empty.resources.is_empty());
+        assert!(parse_declarations("not json\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn failed_build_keeps_work_dir_when_requested() {
+        let tmp = tempfile::tempdir().unwrap();
+        let executor = BuildExecutor::new(tmp.path().to_path_buf()).with_options(BuildOptions {
+            keep_work_dir_on_failure: true,
+            ..BuildOptions::default()
+        });
+        let work_dir = executor.work_root.join("foo");
+        executor.prepare_work_dir(&work_dir).await.unwrap();
+        std::fs::write(work_dir.join("config.log"), "checking...").unwrap();
+
+        let err = executor
+            .finish_work_dir(&work_dir, failed_build())
+            .await
+            .unwrap_err();
+
+        assert!(work_dir.join("config.log").exists());
+        let message = err.to_string();
+        assert!(message.contains("exit code"));
+        assert!(message.contains(&work_dir.display().to_string()));
+    }
+
+    #[tokio::test]
+    async fn successful_build_removes_work_dir_even_when_keeping_failures() {
+        let tmp = tempfile::tempdir().unwrap();
+        let executor = BuildExecutor::new(tmp.path().to_path_buf()).with_options(BuildOptions {
+            keep_work_dir_on_failure: true,
+            ..BuildOptions::default()
+        });
+        let work_dir = executor.work_root.join("foo");
+        executor.prepare_work_dir(&work_dir).await.unwrap();
+
+        executor.finish_work_dir(&work_dir, Ok(())).await.unwrap();
+
+        assert!(!work_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn failed_build_removes_work_dir_by_default() {
+        let tmp = tempfile::tempdir().unwrap();
+        let executor = BuildExecutor::new(tmp.path().to_path_buf());
+        let work_dir = executor.work_root.join("foo");
+        executor.prepare_work_dir(&work_dir).await.unwrap();
+
+        let err = executor
+            .finish_work_dir(&work_dir, failed_build())
+            .await
+            .unwrap_err();
+
+        assert!(!work_dir.exists());
+        assert!(!err.to_string().contains("build directory kept"));
+    }
+
+    #[test]
+    fn notes_keep_the_error_variant() {
+        let err = append_note(
+            Error::FileError {
+                message: "boom".to_string(),
+            },
+            "see here".to_string(),
+        );
+        assert_eq!(
+            err,
+            Error::FileError {
+                message: "boom\nsee here".to_string(),
+            }
+        );
+
+        let missing = Error::NotInstalled {
+            name: "foo".to_string(),
+        };
+        assert_eq!(
+            append_note(missing.clone(), "see here".to_string()),
+            missing
+        );
+    }
+
+    #[test]
+    fn printed_env_contains_source_build_keys() {
+        let tmp = tempfile::tempdir().unwrap();
+        let prefix = tmp.path().join("prefix");
+        let executor = BuildExecutor::new(prefix.clone());
+        let plan = BuildPlan {
+            formula_name: "foo".to_string(),
+            version: "1.0.0".to_string(),
+            source_url: "https://example.com/foo.tar.gz".to_string(),
+            source_checksum: None,
+            source_mirrors: Vec::new(),
+            ruby_source_path: None,
+            build_dependencies: Vec::new(),
+            runtime_dependencies: vec!["bar".to_string()],
+            detected_system: zb_core::BuildSystem::Autoconf,
+            prefix: prefix.clone(),
+            cellar_path: prefix.join("Cellar/foo/1.0.0"),
+            head: None,
+        };
+        let mut deps = HashMap::new();
+        deps.insert(
+            "bar".to_string(),
+            DepInfo {
+                cellar_path: prefix.join("Cellar/bar/2.0").display().to_string(),
+            },
+        );
+
+        let env = executor.source_build_env(&plan, &tmp.path().join("foo.rb"), &deps);
+        let printed = format_build_env(&env);
+        let keys: Vec<&str> = printed
+            .lines()
+            .map(|line| line.split_once('=').unwrap().0)
+            .collect();
+
+        for expected in [
+            "PATH",
+            "PKG_CONFIG_PATH",
+            "CFLAGS",
+            "CPPFLAGS",
+            "LDFLAGS",
+            "ZEROBREW_PREFIX",
+            "ZEROBREW_CELLAR",
+            "ZEROBREW_FORMULA_NAME",
+            "ZEROBREW_FORMULA_VERSION",
+            "ZEROBREW_FORMULA_FILE",
+            "ZEROBREW_INSTALLED_DEPS",
+        ] {
+            assert!(
+                keys.contains(&expected),
+                "missing {expected} in:\n{printed}"
+            );
+        }
+
+        let mut sorted = keys.clone();
+        sorted.sort_unstable();
+        assert_eq!(keys, sorted);
+        assert!(printed.contains(&format!("LDFLAGS=-L{}", prefix.join("lib").display())));
+    }
+
     #[tokio::test]
     async fn run_build_supports_mv_in_formula_install() {
         let Some(ruby) = find_ruby().await.ok() else {
@@ -228,7 +617,7 @@ end
         );
         env.insert("ZEROBREW_INSTALLED_DEPS".to_string(), "{}".to_string());
 
-        run_build(&ruby, &shim_path, &source_root, &env)
+        run_shim(&ruby, &shim_path, &source_root, &env, None, "source build")
             .await
             .unwrap();
 
@@ -284,7 +673,7 @@ end
         );
         env.insert("ZEROBREW_INSTALLED_DEPS".to_string(), "{}".to_string());
 
-        let err = run_build(&ruby, &shim_path, &source_root, &env)
+        let err = run_shim(&ruby, &shim_path, &source_root, &env, None, "source build")
             .await
             .unwrap_err();
 
@@ -292,4 +681,45 @@ end
         assert!(message.contains("source build failed"));
         assert!(message.contains("boom-from-stderr"));
     }
+
+    #[tokio::test]
+    async fn run_post_install_runs_the_block_against_the_keg() {
+        if find_ruby().await.is_err() {
+            return;
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let prefix = tmp.path().join("prefix");
+        let cellar = prefix.join("Cellar");
+        std::fs::create_dir_all(cellar.join("foo/1.0.0")).unwrap();
+
+        let formula_path = tmp.path().join("foo.rb");
+        std::fs::write(
+            &formula_path,
+            r#"
+class Foo < Formula
+  def install
+    raise "install must not run"
+  end
+
+  def post_install
+    (var/"foo").mkpath
+    (prefix/"post_install_ran").write("yes")
+  end
+end
+"#,
+        )
+        .unwrap();
+
+        BuildExecutor::new(prefix.clone())
+            .run_post_install("foo", "1.0.0", &cellar, &formula_path)
+            .await
+            .unwrap();
+
+        assert!(prefix.join("var/foo").is_dir());
+        assert_eq!(
+            std::fs::read_to_string(cellar.join("foo/1.0.0/post_install_ran")).unwrap(),
+            "yes"
+        );
+    }
 }
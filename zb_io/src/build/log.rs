@@ -0,0 +1,121 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zb_core::Error;
+
+/// Where source build logs live under the zerobrew root.
+pub fn build_logs_dir(root: &Path) -> PathBuf {
+    root.join("logs")
+}
+
+fn formula_logs_dir(logs_dir: &Path, formula: &str) -> PathBuf {
+    logs_dir.join(formula.replace('/', "--"))
+}
+
+/// Create `<logs_dir>/<formula>/<timestamp>.log` for a new build of
+/// `formula`, with the timestamp in milliseconds since the epoch.
+pub fn create_build_log(logs_dir: &Path, formula: &str) -> Result<(PathBuf, File), Error> {
+    let dir = formula_logs_dir(logs_dir, formula);
+    fs::create_dir_all(&dir).map_err(Error::file("failed to create build log directory"))?;
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let path = dir.join(format!("{millis}.log"));
+    let file = File::create(&path).map_err(Error::file("failed to create build log"))?;
+    Ok((path, file))
+}
+
+/// The log of the most recent build of `formula`, if it was ever built.
+pub fn latest_build_log(logs_dir: &Path, formula: &str) -> Option<PathBuf> {
+    stamped_logs(&formula_logs_dir(logs_dir, formula))
+        .max_by_key(|(stamp, _)| *stamp)
+        .map(|(_, path)| path)
+}
+
+/// Every build log except the most recent one of each formula, which
+/// `zb log` shows.
+pub(crate) fn superseded_build_logs(logs_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(logs_dir) else {
+        return Vec::new();
+    };
+    let mut superseded = Vec::new();
+    for dir in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+        let mut logs: Vec<(u128, PathBuf)> = stamped_logs(&dir).collect();
+        logs.sort();
+        logs.pop();
+        superseded.extend(logs.into_iter().map(|(_, path)| path));
+    }
+    superseded.sort();
+    superseded
+}
+
+/// The `<timestamp>.log` files in one formula's log directory.
+fn stamped_logs(dir: &Path) -> impl Iterator<Item = (u128, PathBuf)> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter_map(|path| {
+            let stamp: u128 = path
+                .file_name()?
+                .to_str()?
+                .strip_suffix(".log")?
+                .parse()
+                .ok()?;
+            Some((stamp, path))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn latest_build_log_picks_the_newest_timestamp() {
+        let tmp = TempDir::new().unwrap();
+        let logs = build_logs_dir(tmp.path());
+        assert!(latest_build_log(&logs, "jq").is_none());
+
+        let dir = logs.join("jq");
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["900.log", "1000.log", "notes.txt"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        assert_eq!(latest_build_log(&logs, "jq"), Some(dir.join("1000.log")));
+
+        let (path, _file) = create_build_log(&logs, "jq").unwrap();
+        assert_eq!(latest_build_log(&logs, "jq"), Some(path));
+    }
+
+    #[test]
+    fn superseded_build_logs_keep_the_newest_of_each_formula() {
+        let tmp = TempDir::new().unwrap();
+        let logs = build_logs_dir(tmp.path());
+        assert!(superseded_build_logs(&logs).is_empty());
+
+        for (formula, names) in [
+            ("jq", &["900.log", "1000.log", "notes.txt"][..]),
+            ("wget", &["5.log"]),
+        ] {
+            let dir = logs.join(formula);
+            fs::create_dir_all(&dir).unwrap();
+            for name in names {
+                fs::write(dir.join(name), "").unwrap();
+            }
+        }
+        assert_eq!(superseded_build_logs(&logs), vec![logs.join("jq/900.log")]);
+    }
+
+    #[test]
+    fn tap_formulas_get_one_directory() {
+        let tmp = TempDir::new().unwrap();
+        let (path, _file) = create_build_log(tmp.path(), "user/tap/tool").unwrap();
+        assert_eq!(path.parent().unwrap(), tmp.path().join("user--tap--tool"));
+        assert_eq!(latest_build_log(tmp.path(), "user/tap/tool"), Some(path));
+    }
+}
@@ -1,5 +1,9 @@
 pub mod environment;
 pub mod executor;
+pub mod log;
+pub mod patch;
+pub mod resource;
 pub mod source;
 
-pub use executor::{BuildExecutor, DepInfo};
+pub use executor::{BuildExecutor, BuildOptions, DepInfo};
+pub use log::{build_logs_dir, latest_build_log};
@@ -0,0 +1,327 @@
+use std::path::Path;
+use std::process::Stdio;
+
+use serde::Deserialize;
+use tokio::fs;
+use tokio::process::Command;
+use tracing::info;
+use zb_core::Error;
+
+use super::source::{download_source, require_checksum};
+use crate::checksum::verify_sha256_bytes;
+
+/// A `patch` declared by a formula, as reported by the ruby shim.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Patch {
+    #[serde(flatten)]
+    pub source: PatchSource,
+    /// Strip level in Homebrew's spelling, e.g. `p1`.
+    pub strip: String,
+    /// Directory under the source root to apply the patch in.
+    #[serde(default)]
+    pub directory: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PatchSource {
+    Url {
+        url: String,
+        sha256: Option<String>,
+        #[serde(default)]
+        apply: Vec<String>,
+    },
+    /// The formula file's `__END__` section.
+    Data,
+    Inline {
+        content: String,
+    },
+}
+
+/// Everything after the `__END__` line of a formula file, which is where
+/// `patch :DATA` patches live.
+pub fn data_section(formula_rb: &str) -> Option<&str> {
+    let mut offset = 0;
+    for line in formula_rb.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim_end() == "__END__" {
+            return Some(&formula_rb[offset..]);
+        }
+    }
+    None
+}
+
+/// Fetch, verify and apply `patches` in order inside `source_root`.
+pub async fn apply_patches(
+    patches: &[Patch],
+    formula_rb_path: &Path,
+    source_root: &Path,
+    work_dir: &Path,
+) -> Result<(), Error> {
+    if patches.is_empty() {
+        return Ok(());
+    }
+
+    let patch_dir = work_dir.join("patches");
+    fs::create_dir_all(&patch_dir)
+        .await
+        .map_err(Error::file("failed to create patch directory"))?;
+
+    for (index, patch) in patches.iter().enumerate() {
+        let patch_path = patch_dir.join(format!("{index:03}.patch"));
+        fetch_patch(patch, formula_rb_path, &patch_path).await?;
+
+        let dir = match &patch.directory {
+            Some(directory) => source_root.join(directory),
+            None => source_root.to_path_buf(),
+        };
+        run_patch(&patch.strip, &patch_path, &dir).await?;
+    }
+    Ok(())
+}
+
+async fn fetch_patch(patch: &Patch, formula_rb_path: &Path, dest: &Path) -> Result<(), Error> {
+    let content = match &patch.source {
+        PatchSource::Url { apply, .. } if !apply.is_empty() => {
+            return Err(Error::ExecutionError {
+                message: "patch archives with `apply` are not supported yet".to_string(),
+            });
+        }
+        PatchSource::Url { url, sha256, .. } => {
+            let sha256 = require_checksum(sha256.as_deref(), url)?;
+            info!(url = %url, "downloading patch");
+            download_source(url, dest).await?;
+            let bytes = fs::read(dest)
+                .await
+                .map_err(Error::file("failed to read downloaded patch"))?;
+            verify_sha256_bytes(&bytes, Some(sha256)).map_err(|e| match e {
+                Error::InvalidArgument { message } => Error::InvalidArgument {
+                    message: format!("invalid patch checksum for '{url}': {message}"),
+                },
+                other => other,
+            })?;
+            return Ok(());
+        }
+        PatchSource::Data => {
+            let formula_rb = fs::read_to_string(formula_rb_path)
+                .await
+                .map_err(Error::file("failed to read formula file"))?;
+            data_section(&formula_rb)
+                .ok_or_else(|| Error::ExecutionError {
+                    message: "formula declares `patch :DATA` but has no __END__ section"
+                        .to_string(),
+                })?
+                .to_string()
+        }
+        PatchSource::Inline { content } => content.clone(),
+    };
+
+    fs::write(dest, content)
+        .await
+        .map_err(Error::file("failed to write patch"))
+}
+
+async fn run_patch(strip: &str, patch_path: &Path, dir: &Path) -> Result<(), Error> {
+    let level = strip_level(strip)?;
+    info!(patch = %patch_path.display(), "applying patch");
+    let output = Command::new("patch")
+        .args(["-g", "0", "-f", &format!("-p{level}"), "-i"])
+        .arg(patch_path)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(Error::exec("failed to run patch"))?;
+
+    if !output.status.success() {
+        return Err(Error::ExecutionError {
+            message: format!(
+                "patch {} failed to apply:\n{}",
+                patch_path.display(),
+                String::from_utf8_lossy(&output.stdout).trim()
+            ),
+        });
+    }
+    info!(patch = %patch_path.display(), "applied patch");
+    Ok(())
+}
+
+fn strip_level(strip: &str) -> Result<u32, Error> {
+    strip
+        .strip_prefix('p')
+        .and_then(|level| level.parse().ok())
+        .ok_or_else(|| Error::InvalidArgument {
+            message: format!("invalid patch strip level '{strip}'"),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use sha2::{Digest, Sha256};
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    const PATCH: &str = "\
+--- a/greeting.txt
++++ b/greeting.txt
+@@ -1 +1 @@
+-hello
++hello, patched
+";
+
+    fn have_patch() -> bool {
+        std::process::Command::new("patch")
+            .arg("--version")
+            .output()
+            .is_ok()
+    }
+
+    fn source_root(tmp: &TempDir) -> PathBuf {
+        let root = tmp.path().join("src");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("greeting.txt"), "hello\n").unwrap();
+        root
+    }
+
+    #[test]
+    fn parses_shim_patch_list() {
+        let patches: Vec<Patch> = serde_json::from_str(
+            r#"[
+                {"type":"url","url":"https://example.com/a.diff","sha256":"abc","strip":"p1","directory":null,"apply":[]},
+                {"type":"data","strip":"p0"},
+                {"type":"inline","content":"diff","strip":"p1","directory":"src"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            patches[0].source,
+            PatchSource::Url {
+                url: "https://example.com/a.diff".to_string(),
+                sha256: Some("abc".to_string()),
+                apply: Vec::new(),
+            }
+        );
+        assert_eq!(patches[1].source, PatchSource::Data);
+        assert_eq!(patches[1].strip, "p0");
+        assert_eq!(patches[2].directory.as_deref(), Some("src"));
+    }
+
+    #[test]
+    fn data_section_follows_end_marker() {
+        let rb = "class Foo < Formula\n  patch :DATA\nend\n__END__\n--- a/x\n+++ b/x\n";
+        assert_eq!(data_section(rb), Some("--- a/x\n+++ b/x\n"));
+        assert_eq!(data_section("class Foo < Formula\nend\n"), None);
+    }
+
+    #[test]
+    fn strip_level_must_be_p_and_a_number() {
+        assert_eq!(strip_level("p0").unwrap(), 0);
+        assert_eq!(strip_level("p1").unwrap(), 1);
+        assert!(strip_level("-R").is_err());
+        assert!(strip_level("p").is_err());
+    }
+
+    #[tokio::test]
+    async fn applies_verified_url_patches() {
+        if !have_patch() {
+            eprintln!("Skipping patch test: patch not found");
+            return;
+        }
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/greeting.diff"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(PATCH))
+            .mount(&mock_server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let root = source_root(&tmp);
+        let patch = Patch {
+            source: PatchSource::Url {
+                url: format!("{}/greeting.diff", mock_server.uri()),
+                sha256: Some(format!("{:x}", Sha256::digest(PATCH))),
+                apply: Vec::new(),
+            },
+            strip: "p1".to_string(),
+            directory: None,
+        };
+
+        apply_patches(&[patch], &tmp.path().join("unused.rb"), &root, tmp.path())
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.join("greeting.txt")).unwrap(),
+            "hello, patched\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_url_patches_with_the_wrong_checksum() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/greeting.diff"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(PATCH))
+            .mount(&mock_server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let root = source_root(&tmp);
+        let patch = Patch {
+            source: PatchSource::Url {
+                url: format!("{}/greeting.diff", mock_server.uri()),
+                sha256: Some("0".repeat(64)),
+                apply: Vec::new(),
+            },
+            strip: "p1".to_string(),
+            directory: None,
+        };
+
+        let err = apply_patches(&[patch], &tmp.path().join("unused.rb"), &root, tmp.path())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ChecksumMismatch { .. }));
+        assert_eq!(
+            std::fs::read_to_string(root.join("greeting.txt")).unwrap(),
+            "hello\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn applies_data_patches_from_the_formula_file() {
+        if !have_patch() {
+            eprintln!("Skipping patch test: patch not found");
+            return;
+        }
+
+        let tmp = TempDir::new().unwrap();
+        let root = source_root(&tmp);
+        let formula_rb = tmp.path().join("greeting.rb");
+        std::fs::write(
+            &formula_rb,
+            format!(
+                "class Greeting < Formula\n  patch :p0, :DATA\nend\n__END__\n{}",
+                PATCH.replace("a/", "").replace("b/", "")
+            ),
+        )
+        .unwrap();
+        let patch = Patch {
+            source: PatchSource::Data,
+            strip: "p0".to_string(),
+            directory: None,
+        };
+
+        apply_patches(&[patch], &formula_rb, &root, tmp.path())
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.join("greeting.txt")).unwrap(),
+            "hello, patched\n"
+        );
+    }
+}
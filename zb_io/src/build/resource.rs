@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tokio::fs;
+use tracing::info;
+use zb_core::Error;
+
+use super::source::{download_from_any, require_checksum, verify_checksum};
+
+/// A `resource` declared by a formula, as reported by the ruby shim.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Resource {
+    pub name: String,
+    pub url: Option<String>,
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+}
+
+/// Download and verify every resource into the work dir, returning where
+/// each one's archive ended up, keyed by resource name.
+pub async fn fetch_resources(
+    resources: &[Resource],
+    work_dir: &Path,
+) -> Result<HashMap<String, PathBuf>, Error> {
+    let mut staged = HashMap::with_capacity(resources.len());
+    for (index, resource) in resources.iter().enumerate() {
+        let url = resource
+            .url
+            .as_deref()
+            .ok_or_else(|| Error::ExecutionError {
+                message: format!("resource '{}' has no url", resource.name),
+            })?;
+
+        let sha256 = require_checksum(resource.sha256.as_deref(), url)?;
+
+        // Staging picks the unpacker by extension, so keep the url's file name.
+        let dir = work_dir.join("resources").join(format!("{index:03}"));
+        fs::create_dir_all(&dir)
+            .await
+            .map_err(Error::file("failed to create resource directory"))?;
+        let archive = dir.join(archive_name(url));
+
+        info!(resource = %resource.name, "downloading resource");
+        let served_by = download_from_any(url, &resource.mirrors, &archive).await?;
+        verify_checksum(&archive, Some(sha256), served_by).await?;
+
+        staged.insert(resource.name.clone(), archive);
+    }
+    Ok(staged)
+}
+
+fn archive_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or("resource.tar.gz")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    #[test]
+    fn archive_name_keeps_the_extension() {
+        assert_eq!(
+            archive_name("https://files.pythonhosted.org/packages/six-1.16.0.tar.gz"),
+            "six-1.16.0.tar.gz"
+        );
+        assert_eq!(
+            archive_name("https://example.com/dl/tool.zip?download=1"),
+            "tool.zip"
+        );
+        assert_eq!(archive_name("https://example.com/"), "resource.tar.gz");
+    }
+
+    #[tokio::test]
+    async fn fetches_resources_from_mirrors_and_verifies_them() {
+        let mock_server = MockServer::start().await;
+        let body = b"resource bytes".to_vec();
+        Mock::given(method("GET"))
+            .and(path("/primary/six-1.16.0.tar.gz"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/mirror/six-1.16.0.tar.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body.clone()))
+            .mount(&mock_server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let resource = Resource {
+            name: "six".to_string(),
+            url: Some(format!("{}/primary/six-1.16.0.tar.gz", mock_server.uri())),
+            sha256: Some(format!("{:x}", Sha256::digest(&body))),
+            mirrors: vec![format!("{}/mirror/six-1.16.0.tar.gz", mock_server.uri())],
+        };
+
+        let staged = fetch_resources(&[resource], tmp.path()).await.unwrap();
+        let archive = &staged["six"];
+        assert!(archive.ends_with("six-1.16.0.tar.gz"));
+        assert_eq!(std::fs::read(archive).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn rejects_resources_with_the_wrong_checksum() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/six-1.16.0.tar.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"tampered".to_vec()))
+            .mount(&mock_server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let resource = Resource {
+            name: "six".to_string(),
+            url: Some(format!("{}/six-1.16.0.tar.gz", mock_server.uri())),
+            sha256: Some("0".repeat(64)),
+            mirrors: Vec::new(),
+        };
+
+        let err = fetch_resources(&[resource], tmp.path()).await.unwrap_err();
+        assert!(matches!(err, Error::ChecksumMismatch { .. }));
+    }
+
+    #[tokio::test]
+    async fn refuses_resources_without_a_checksum() {
+        let tmp = TempDir::new().unwrap();
+        let resource = Resource {
+            name: "six".to_string(),
+            url: Some("https://example.invalid/six-1.16.0.tar.gz".to_string()),
+            sha256: None,
+            mirrors: Vec::new(),
+        };
+
+        let err = fetch_resources(&[resource], tmp.path()).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument { .. }));
+        assert!(err.to_string().contains("no sha256 checksum"), "{err}");
+        assert!(!tmp.path().join("resources/000/six-1.16.0.tar.gz").exists());
+    }
+}
@@ -23,6 +23,7 @@ FORMULA_NAME = ENV.fetch("ZEROBREW_FORMULA_NAME")
 FORMULA_VERSION = ENV.fetch("ZEROBREW_FORMULA_VERSION")
 FORMULA_FILE = ENV.fetch("ZEROBREW_FORMULA_FILE")
 INSTALLED_DEPS = JSON.parse(ENV.fetch("ZEROBREW_INSTALLED_DEPS", "{}"))
+STAGED_RESOURCES = JSON.parse(ENV.fetch("ZEROBREW_RESOURCES", "{}"))
 
 module OS
   def self.mac?
@@ -141,30 +142,35 @@ end
 ENV.extend(Homebrew::EnvExtension)
 
 class PatchDSL
-  attr_reader :patch_url, :patch_sha256
+  attr_reader :patch_url, :patch_sha256, :patch_directory, :patch_apply
 
   def initialize
     @patch_url = nil
     @patch_sha256 = nil
+    @patch_directory = nil
+    @patch_apply = []
   end
 
   def url(u, **_kwargs); @patch_url = u; end
   def sha256(s); @patch_sha256 = s; end
   def mirror(_); nil; end
+  def directory(d); @patch_directory = d.to_s; end
+  def apply(*files); @patch_apply.concat(files.flatten.map(&:to_s)); end
 end
 
 class ResourceDSL
-  attr_reader :resource_url, :resource_sha256
+  attr_reader :resource_url, :resource_sha256, :resource_mirrors
 
   def initialize(name)
     @name = name
     @resource_url = nil
     @resource_sha256 = nil
+    @resource_mirrors = []
   end
 
   def url(u, **_kwargs); @resource_url = u; end
   def sha256(s); @resource_sha256 = s; end
-  def mirror(_); nil; end
+  def mirror(m); @resource_mirrors << m; end
   def patch(&_block); nil; end
   def on_macos(&block); yield if OS.mac?; end
   def on_linux(&block); yield if OS.linux?; end
@@ -172,23 +178,27 @@ class ResourceDSL
   def on_intel(&block); yield if Hardware::CPU.intel?; end
 end
 
+# A resource zerobrew has already downloaded and verified; staging only
+# unpacks the archive it left at ZEROBREW_RESOURCES[name].
 class StagedResource
-  def initialize(url, sha256)
-    @url = url
-    @sha256 = sha256
+  attr_reader :name
+
+  def initialize(name)
+    @name = name
+    @archive = STAGED_RESOURCES[name]
+    raise "Resource '#{name}' was not downloaded" unless @archive
   end
 
-  def stage(&block)
+  def stage(target = nil, &block)
+    if target
+      FileUtils.mkdir_p(target.to_s)
+      extract_resource(@archive, target.to_s)
+      return Pathname.new(target.to_s)
+    end
+
     Dir.mktmpdir("zb_resource_") do |dir|
-      basename = File.basename(URI.parse(@url).path) rescue "resource.tar.gz"
-      archive = File.join(dir, basename)
-      Kernel.system("curl", "-sSL", "-o", archive, @url)
-      unless $?.success?
-        $stderr.puts "Error: failed to download resource #{@url}"
-        exit 1
-      end
-      extract_resource(archive, dir)
-      entries = Dir.children(dir).reject { |e| e == basename }
+      extract_resource(@archive, dir)
+      entries = Dir.children(dir)
       src_dir = if entries.length == 1 && File.directory?(File.join(dir, entries.first))
                   File.join(dir, entries.first)
                 else
@@ -205,18 +215,24 @@ class StagedResource
   private
 
   def extract_resource(archive, dir)
-    case archive
-    when /\.(tar\.gz|tgz)$/
-      Kernel.system("tar", "xzf", archive, "-C", dir)
-    when /\.tar\.xz$/
-      Kernel.system("tar", "xJf", archive, "-C", dir)
-    when /\.tar\.bz2$/
-      Kernel.system("tar", "xjf", archive, "-C", dir)
-    when /\.zip$/
-      Kernel.system("unzip", "-qo", archive, "-d", dir)
-    else
-      Kernel.system("tar", "xf", archive, "-C", dir)
-    end
+    ok = case archive
+         when /\.(tar\.gz|tgz)$/
+           Kernel.system("tar", "xzf", archive, "-C", dir)
+         when /\.tar\.xz$/
+           Kernel.system("tar", "xJf", archive, "-C", dir)
+         when /\.tar\.bz2$/
+           Kernel.system("tar", "xjf", archive, "-C", dir)
+         when /\.tar(\.\w+)?$/
+           Kernel.system("tar", "xf", archive, "-C", dir)
+         when /\.zip$/
+           Kernel.system("unzip", "-qo", archive, "-d", dir)
+         else
+           FileUtils.cp(archive, dir)
+         end
+    return unless ok == false
+
+    $stderr.puts "Error: failed to unpack resource #{@name}"
+    exit 1
   end
 end
 
@@ -280,6 +296,8 @@ class Pathname
 end
 
 class Formula
+  include FileUtils
+
   class << self
     attr_accessor :formula_name, :formula_version
 
@@ -323,7 +341,9 @@ class Formula
       @_resources ||= {}
       ctx = ResourceDSL.new(name)
       ctx.instance_eval(&block)
-      @_resources[name.to_s] = { url: ctx.resource_url, sha256: ctx.resource_sha256 }
+      @_resources[name.to_s] = {
+        url: ctx.resource_url, sha256: ctx.resource_sha256, mirrors: ctx.resource_mirrors
+      }
     end
 
     def patch(*args, &block)
@@ -348,7 +368,10 @@ class Formula
       elsif block_given?
         ctx = PatchDSL.new
         ctx.instance_eval(&block)
-        @_patches << { type: :url, url: ctx.patch_url, sha256: ctx.patch_sha256, strip: strip }
+        @_patches << {
+          type: :url, url: ctx.patch_url, sha256: ctx.patch_sha256, strip: strip,
+          directory: ctx.patch_directory, apply: ctx.patch_apply
+        }
       end
     end
 
@@ -407,9 +430,16 @@ class Formula
   def elisp; share + "emacs" + "site-lisp" + name; end
 
   def resource(name)
-    res_info = self.class.instance_variable_get(:@_resources)&.dig(name.to_s)
-    raise "Resource '#{name}' not defined" unless res_info
-    StagedResource.new(res_info[:url], res_info[:sha256])
+    raise "Resource '#{name}' not defined" unless declared_resources.key?(name.to_s)
+    StagedResource.new(name.to_s)
+  end
+
+  def resources
+    declared_resources.keys.map { |name| StagedResource.new(name) }
+  end
+
+  def declared_resources
+    self.class.instance_variable_get(:@_resources) || {}
   end
 
   def etc
@@ -449,6 +479,18 @@ class Formula
     exit 1 unless result
   end
 
+  def quiet_system(*args)
+    Kernel.system(*args.map(&:to_s), out: File::NULL, err: File::NULL)
+  end
+
+  def ohai(*messages)
+    puts "==> #{messages.join(" ")}"
+  end
+
+  def opoo(message)
+    $stderr.puts "Warning: #{message}"
+  end
+
   def mv(*sources, **options)
     normalized = sources.map { |s| s.respond_to?(:to_path) ? s.to_path : s.to_s }
     FileUtils.mv(*normalized, **options)
@@ -560,10 +602,6 @@ def shared_library(name, version = nil)
   end
 end
 
-formula_raw = File.read(FORMULA_FILE)
-end_marker_idx = formula_raw.index(/^__END__\s*$/)
-FORMULA_DATA_CONTENT = end_marker_idx ? formula_raw[(formula_raw.index("\n", end_marker_idx) + 1)..] : nil
-
 ENV["HOMEBREW_PREFIX"] = ZEROBREW_PREFIX
 ENV["HOMEBREW_CELLAR"] = ZEROBREW_CELLAR
 
@@ -575,48 +613,31 @@ unless formula_class
   exit 1
 end
 
-patches = formula_class.instance_variable_get(:@_patches) || []
-patches.each do |p|
-  strip_flag = "-#{p[:strip]}"
-  case p[:type]
-  when :data
-    if FORMULA_DATA_CONTENT
-      puts "==> Applying DATA patch"
-      IO.popen(["patch", strip_flag, "-i", "/dev/stdin"], "w") { |io| io.write(FORMULA_DATA_CONTENT) }
-      unless $?.success?
-        $stderr.puts "Error: DATA patch failed"
-        exit 1
-      end
-    end
-  when :url
-    puts "==> Downloading patch from #{p[:url]}"
-    tmp = Tempfile.new("zb_patch")
-    begin
-      Kernel.system("curl", "-sSL", "-o", tmp.path, p[:url])
-      unless $?.success?
-        $stderr.puts "Error: failed to download patch #{p[:url]}"
-        exit 1
-      end
-      Kernel.system("patch", strip_flag, "-i", tmp.path)
-      unless $?.success?
-        $stderr.puts "Error: patch failed"
-        exit 1
-      end
-    ensure
-      tmp.close!
-    end
-  when :inline
-    puts "==> Applying inline patch"
-    IO.popen(["patch", strip_flag, "-i", "/dev/stdin"], "w") { |io| io.write(p[:content]) }
-    unless $?.success?
-      $stderr.puts "Error: inline patch failed"
-      exit 1
-    end
-  end
+# zerobrew fetches, verifies and applies patches and downloads resources
+# itself before the build; this mode only reports what the formula declares.
+if ENV["ZEROBREW_DECLARATIONS"]
+  resources = (formula_class.instance_variable_get(:@_resources) || {}).map do |name, info|
+    info.merge(name: name)
+  end
+  puts JSON.generate(
+    patches: formula_class.instance_variable_get(:@_patches) || [],
+    resources: resources,
+  )
+  exit 0
 end
 
 instance = formula_class.new
 
+# Runs against a keg that is already installed: `prefix` is its Cellar
+# directory, and there is no source tree to build.
+if ENV["ZEROBREW_POST_INSTALL"]
+  if instance.respond_to?(:post_install)
+    puts "==> Running post_install for #{FORMULA_NAME} #{FORMULA_VERSION}"
+    instance.post_install
+  end
+  exit 0
+end
+
 puts "==> Building #{FORMULA_NAME} #{FORMULA_VERSION}"
 FileUtils.mkdir_p(instance.prefix.to_s)
 instance.install
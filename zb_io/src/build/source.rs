@@ -1,20 +1,26 @@
 use std::path::{Path, PathBuf};
 
 use tokio::fs;
-use zb_core::Error;
+use tokio::process::Command;
+use tracing::info;
+use zb_core::{Error, HeadCheckout};
 
 use crate::checksum::verify_sha256_bytes;
 use crate::extraction::extract_tarball;
 
+/// Download the source tarball from `url`, falling back to each of
+/// `mirrors` in order if it can't be fetched, then verify and extract it.
 pub async fn download_and_extract_source(
     url: &str,
+    mirrors: &[String],
     expected_checksum: Option<&str>,
     work_dir: &Path,
 ) -> Result<PathBuf, Error> {
+    let expected_checksum = require_checksum(expected_checksum, url)?;
     let tarball_path = work_dir.join("source.tar.gz");
-    download_source(url, &tarball_path).await?;
+    let served_by = download_from_any(url, mirrors, &tarball_path).await?;
 
-    verify_checksum(&tarball_path, expected_checksum, url).await?;
+    verify_checksum(&tarball_path, Some(expected_checksum), served_by).await?;
 
     let src_dir = work_dir.join("src");
     fs::create_dir_all(&src_dir)
@@ -26,8 +32,30 @@ pub async fn download_and_extract_source(
     find_source_root(&src_dir).await
 }
 
-async fn download_source(url: &str, dest: &Path) -> Result<(), Error> {
-    let client = reqwest::Client::builder()
+/// Try `url` and then each mirror, returning whichever one served the file.
+pub(super) async fn download_from_any<'a>(
+    url: &'a str,
+    mirrors: &'a [String],
+    dest: &Path,
+) -> Result<&'a str, Error> {
+    let mut last_err = None;
+    for candidate in std::iter::once(url).chain(mirrors.iter().map(String::as_str)) {
+        match download_source(candidate, dest).await {
+            Ok(()) => {
+                info!(url = candidate, "downloaded source");
+                return Ok(candidate);
+            }
+            Err(e) => {
+                info!(url = candidate, error = %e, "source download failed");
+                last_err = Some(e);
+            }
+        }
+    }
+    Err(last_err.expect("at least one candidate url"))
+}
+
+pub(super) async fn download_source(url: &str, dest: &Path) -> Result<(), Error> {
+    let client = crate::network::proxy::with_proxy(reqwest::Client::builder())
         .timeout(std::time::Duration::from_secs(300))
         .use_preconfigured_tls((*crate::network::tls::shared_tls_config()).clone())
         .build()
@@ -56,12 +84,19 @@ async fn download_source(url: &str, dest: &Path) -> Result<(), Error> {
         .map_err(Error::file("failed to write source tarball"))
 }
 
-async fn verify_checksum(path: &Path, expected: Option<&str>, url: &str) -> Result<(), Error> {
+/// Check a downloaded source, patch or resource against its sha256. One
+/// without a checksum is refused rather than used unverified.
+pub(super) async fn verify_checksum(
+    path: &Path,
+    expected: Option<&str>,
+    url: &str,
+) -> Result<(), Error> {
+    let expected = require_checksum(expected, url)?;
     let bytes = fs::read(path)
         .await
         .map_err(Error::file("failed to read tarball for checksum"))?;
 
-    verify_sha256_bytes(&bytes, expected).map_err(|e| match e {
+    verify_sha256_bytes(&bytes, Some(expected)).map_err(|e| match e {
         Error::ChecksumMismatch { .. } => e,
         Error::InvalidArgument { message } => Error::InvalidArgument {
             message: format!("invalid source checksum for '{url}': {message}"),
@@ -70,6 +105,95 @@ async fn verify_checksum(path: &Path, expected: Option<&str>, url: &str) -> Resu
     })
 }
 
+pub(super) fn require_checksum<'a>(expected: Option<&'a str>, url: &str) -> Result<&'a str, Error> {
+    expected.ok_or_else(|| Error::InvalidArgument {
+        message: format!("'{url}' has no sha256 checksum; refusing to use an unverified download"),
+    })
+}
+
+/// The commit `branch` (or the default branch) of the git repository at
+/// `url` currently points at.
+pub async fn resolve_head_commit(url: &str, branch: Option<&str>) -> Result<String, Error> {
+    let reference = branch.map_or_else(|| "HEAD".to_string(), |b| format!("refs/heads/{b}"));
+    let output = git(None, &["ls-remote", url, &reference]).await?;
+    output
+        .split_whitespace()
+        .next()
+        .map(str::to_string)
+        .ok_or_else(|| Error::NetworkFailure {
+            message: format!("{url} has no {reference} to build"),
+        })
+}
+
+/// Clone the git repository at `url` into the work dir and check out
+/// `checkout`'s commit, returning the checkout.
+pub async fn clone_source(
+    url: &str,
+    checkout: &HeadCheckout,
+    work_dir: &Path,
+) -> Result<PathBuf, Error> {
+    let src_dir = work_dir.join("src");
+    let src = src_dir.display().to_string();
+
+    let mut args = vec!["clone", "--quiet", "--depth", "1"];
+    if let Some(branch) = &checkout.branch {
+        args.extend(["--branch", branch.as_str()]);
+    }
+    args.extend([url, src.as_str()]);
+    git(None, &args).await?;
+
+    // The branch moved on since the commit was resolved; fetch it by hash.
+    let cloned = git(Some(&src_dir), &["rev-parse", "HEAD"]).await?;
+    if cloned.trim() != checkout.commit {
+        git(
+            Some(&src_dir),
+            &[
+                "fetch",
+                "--quiet",
+                "--depth",
+                "1",
+                "origin",
+                &checkout.commit,
+            ],
+        )
+        .await?;
+        git(
+            Some(&src_dir),
+            &["checkout", "--quiet", "--detach", &checkout.commit],
+        )
+        .await?;
+    }
+
+    info!(url, commit = %checkout.commit, "cloned source");
+    Ok(src_dir)
+}
+
+async fn git(dir: Option<&Path>, args: &[&str]) -> Result<String, Error> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .await
+        .map_err(|e| Error::ExecutionError {
+            message: format!("failed to run git, which HEAD builds need: {e}"),
+        })?;
+
+    if !output.status.success() {
+        return Err(Error::ExecutionError {
+            message: format!(
+                "`git {}` failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
 async fn find_source_root(src_dir: &Path) -> Result<PathBuf, Error> {
     let mut entries = fs::read_dir(src_dir)
         .await
@@ -100,3 +224,141 @@ async fn find_source_root(src_dir: &Path) -> Result<PathBuf, Error> {
 
     Ok(src_dir.to_path_buf())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use sha2::{Digest, Sha256};
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    fn source_tarball() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let contents = b"all:\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "hello-1.0/Makefile", &contents[..])
+            .unwrap();
+        let tar = builder.into_inner().unwrap();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_mirror_when_primary_fails() {
+        let mock_server = MockServer::start().await;
+        let tarball = source_tarball();
+        let checksum = format!("{:x}", Sha256::digest(&tarball));
+
+        Mock::given(method("GET"))
+            .and(path("/primary/hello-1.0.tar.gz"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/mirror/hello-1.0.tar.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(tarball))
+            .mount(&mock_server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let primary = format!("{}/primary/hello-1.0.tar.gz", mock_server.uri());
+        let mirrors = vec![format!("{}/mirror/hello-1.0.tar.gz", mock_server.uri())];
+
+        let root = download_and_extract_source(&primary, &mirrors, Some(&checksum), tmp.path())
+            .await
+            .unwrap();
+
+        assert!(root.ends_with("hello-1.0"));
+        assert!(root.join("Makefile").exists());
+
+        let err =
+            download_and_extract_source(&primary, &mirrors, Some(&"0".repeat(64)), tmp.path())
+                .await
+                .unwrap_err();
+        assert!(matches!(err, Error::ChecksumMismatch { .. }));
+
+        let err = download_and_extract_source(&primary, &mirrors, None, tmp.path())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no sha256 checksum"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn clones_the_resolved_head_commit() {
+        if std::process::Command::new("git")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            eprintln!("Skipping HEAD clone test: git not found");
+            return;
+        }
+
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        std::fs::create_dir_all(&repo).unwrap();
+        let commit = |message: &str| {
+            std::fs::write(repo.join("Makefile"), message).unwrap();
+            for args in [
+                &["add", "Makefile"][..],
+                &[
+                    "-c",
+                    "user.name=zb",
+                    "-c",
+                    "user.email=zb@example.com",
+                    "commit",
+                    "--quiet",
+                    "-m",
+                    message,
+                ],
+            ] {
+                let status = std::process::Command::new("git")
+                    .current_dir(&repo)
+                    .args(args)
+                    .status()
+                    .unwrap();
+                assert!(status.success());
+            }
+        };
+        let status = std::process::Command::new("git")
+            .args(["init", "--quiet", "--initial-branch", "main"])
+            .arg(&repo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        commit("first");
+
+        let url = repo.display().to_string();
+        let first = resolve_head_commit(&url, Some("main")).await.unwrap();
+        assert_eq!(first.len(), 40);
+        assert_eq!(resolve_head_commit(&url, None).await.unwrap(), first);
+        assert!(resolve_head_commit(&url, Some("missing")).await.is_err());
+
+        // The branch moves on after the commit was resolved.
+        commit("second");
+        let checkout = HeadCheckout {
+            branch: Some("main".to_string()),
+            commit: first.clone(),
+        };
+        let work_dir = tmp.path().join("work");
+        std::fs::create_dir_all(&work_dir).unwrap();
+        let src = clone_source(&url, &checkout, &work_dir).await.unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(src.join("Makefile")).unwrap(),
+            "first"
+        );
+    }
+}
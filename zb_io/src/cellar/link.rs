@@ -2,6 +2,7 @@ use std::fs;
 use std::io;
 use std::path::{Component, Path, PathBuf};
 
+use tracing::{debug, trace};
 use zb_core::{ConflictedLink, Error};
 
 const LINK_DIRS: &[&str] = &["bin", "lib", "libexec", "include", "share", "etc"];
@@ -83,6 +84,38 @@ pub struct Linker {
     opt_dir: PathBuf,
 }
 
+/// What linking does with a prefix path that's already taken by a file or
+/// another keg's link.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkConflictPolicy {
+    /// Fail the link, naming every conflict.
+    #[default]
+    Fail,
+    /// Replace the file or link in the way. Directories are never removed.
+    Overwrite,
+    /// Leave the existing path alone and link everything else.
+    Skip,
+}
+
+impl LinkConflictPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LinkConflictPolicy::Fail => "fail",
+            LinkConflictPolicy::Overwrite => "overwrite",
+            LinkConflictPolicy::Skip => "skip",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "fail" => Some(LinkConflictPolicy::Fail),
+            "overwrite" => Some(LinkConflictPolicy::Overwrite),
+            "skip" => Some(LinkConflictPolicy::Skip),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LinkedFile {
     pub link_path: PathBuf,
@@ -136,6 +169,16 @@ impl Linker {
     /// Pre-flight check: scan all destinations for conflicts without creating any symlinks.
     /// Returns Ok(()) if no conflicts, or Err(LinkConflict) with all conflicts collected.
     pub fn check_conflicts(&self, keg_path: &Path) -> Result<(), Error> {
+        let conflicts = self.find_conflicts(keg_path);
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::LinkConflict { conflicts })
+        }
+    }
+
+    /// Every prefix path that linking `keg_path` would have to replace.
+    pub fn find_conflicts(&self, keg_path: &Path) -> Vec<ConflictedLink> {
         let mut conflicts = Vec::new();
         for dir_name in LINK_DIRS {
             let src_dir = keg_path.join(dir_name);
@@ -144,11 +187,7 @@ impl Linker {
                 Self::collect_conflicts(&src_dir, &dst_dir, &mut conflicts);
             }
         }
-        if conflicts.is_empty() {
-            Ok(())
-        } else {
-            Err(Error::LinkConflict { conflicts })
-        }
+        conflicts
     }
 
     fn collect_conflicts(src: &Path, dst: &Path, conflicts: &mut Vec<ConflictedLink>) {
@@ -255,19 +294,51 @@ impl Linker {
 
     pub fn link_keg(&self, keg_path: &Path) -> Result<Vec<LinkedFile>, Error> {
         self.check_conflicts(keg_path)?;
+        self.link_keg_files(keg_path, LinkConflictPolicy::Fail)
+    }
+
+    /// Like [`Self::link_keg`], but deletes files and other kegs' links that
+    /// are in the way instead of failing. Directories are never removed.
+    pub fn link_keg_overwriting(&self, keg_path: &Path) -> Result<Vec<LinkedFile>, Error> {
+        self.link_keg_files(keg_path, LinkConflictPolicy::Overwrite)
+    }
+
+    /// Link `keg_path`, handling conflicts as `policy` says. Skipped paths
+    /// are left as they were and don't appear in the result.
+    pub fn link_keg_with(
+        &self,
+        keg_path: &Path,
+        policy: LinkConflictPolicy,
+    ) -> Result<Vec<LinkedFile>, Error> {
+        match policy {
+            LinkConflictPolicy::Fail => self.link_keg(keg_path),
+            policy => self.link_keg_files(keg_path, policy),
+        }
+    }
+
+    fn link_keg_files(
+        &self,
+        keg_path: &Path,
+        policy: LinkConflictPolicy,
+    ) -> Result<Vec<LinkedFile>, Error> {
         self.link_opt(keg_path)?;
         let mut linked = Vec::new();
         for dir_name in LINK_DIRS {
             let src_dir = keg_path.join(dir_name);
             let dst_dir = self.prefix.join(dir_name);
             if src_dir.exists() {
-                linked.extend(Self::link_recursive(&src_dir, &dst_dir)?);
+                linked.extend(Self::link_recursive(&src_dir, &dst_dir, policy)?);
             }
         }
+        debug!(keg = %keg_path.display(), ?policy, links = linked.len(), "linked keg");
         Ok(linked)
     }
 
-    fn link_recursive(src: &Path, dst: &Path) -> Result<Vec<LinkedFile>, Error> {
+    fn link_recursive(
+        src: &Path,
+        dst: &Path,
+        policy: LinkConflictPolicy,
+    ) -> Result<Vec<LinkedFile>, Error> {
         let mut linked = Vec::new();
         if !dst.exists() {
             fs::create_dir_all(dst).map_err(Error::store("failed to create directory"))?;
@@ -291,13 +362,13 @@ impl Linker {
                     let old_target = fs::read_link(&dst_path)
                         .map_err(Error::store("failed to read symlink target"))?;
                     let _ = fs::remove_file(&dst_path);
-                    Self::link_recursive(&old_target, &dst_path)?;
+                    Self::link_recursive(&old_target, &dst_path, LinkConflictPolicy::Fail)?;
                 }
-                linked.extend(Self::link_recursive(&src_path, &dst_path)?);
+                linked.extend(Self::link_recursive(&src_path, &dst_path, policy)?);
                 continue;
             }
 
-            if dst_path.symlink_metadata().is_ok() {
+            if let Ok(metadata) = dst_path.symlink_metadata() {
                 if let Ok(target) = fs::read_link(&dst_path) {
                     let resolved = if target.is_relative() {
                         dst_path.parent().unwrap_or(Path::new("")).join(&target)
@@ -314,6 +385,13 @@ impl Linker {
                         } else {
                             let _ = fs::remove_file(&dst_path);
                         }
+                    } else if policy == LinkConflictPolicy::Overwrite {
+                        debug!(path = %dst_path.display(), "replacing another keg's link");
+                        fs::remove_file(&dst_path)
+                            .map_err(Error::store("failed to remove conflicting link"))?;
+                    } else if policy == LinkConflictPolicy::Skip {
+                        debug!(path = %dst_path.display(), "skipping path linked by another keg");
+                        continue;
                     } else {
                         return Err(Error::LinkConflict {
                             conflicts: vec![ConflictedLink {
@@ -322,6 +400,13 @@ impl Linker {
                             }],
                         });
                     }
+                } else if policy == LinkConflictPolicy::Overwrite && !metadata.is_dir() {
+                    debug!(path = %dst_path.display(), "replacing file in the prefix");
+                    fs::remove_file(&dst_path)
+                        .map_err(Error::store("failed to remove conflicting file"))?;
+                } else if policy == LinkConflictPolicy::Skip {
+                    debug!(path = %dst_path.display(), "skipping file already in the prefix");
+                    continue;
                 } else {
                     return Err(Error::LinkConflict {
                         conflicts: vec![ConflictedLink {
@@ -331,6 +416,9 @@ impl Linker {
                     });
                 }
             } else if dst_path.exists() {
+                if policy == LinkConflictPolicy::Skip {
+                    continue;
+                }
                 return Err(Error::LinkConflict {
                     conflicts: vec![ConflictedLink {
                         path: dst_path,
@@ -342,6 +430,7 @@ impl Linker {
             #[cfg(unix)]
             std::os::unix::fs::symlink(&src_path, &dst_path)
                 .map_err(Error::store("failed to create symlink"))?;
+            trace!(link = %dst_path.display(), target = %src_path.display(), "created link");
             linked.push(LinkedFile {
                 link_path: dst_path,
                 target_path: src_path,
@@ -352,6 +441,12 @@ impl Linker {
 
     pub fn unlink_keg(&self, keg_path: &Path) -> Result<Vec<PathBuf>, Error> {
         self.unlink_opt(keg_path)?;
+        self.unlink_keg_files(keg_path)
+    }
+
+    /// Remove the keg's links from the prefix but keep `opt/<name>`, which
+    /// dependents still load libraries through.
+    pub fn unlink_keg_files(&self, keg_path: &Path) -> Result<Vec<PathBuf>, Error> {
         let mut unlinked = Vec::new();
         for dir_name in LINK_DIRS {
             let src_dir = keg_path.join(dir_name);
@@ -360,6 +455,7 @@ impl Linker {
                 unlinked.extend(Self::unlink_recursive(&src_dir, &dst_dir)?);
             }
         }
+        debug!(keg = %keg_path.display(), links = unlinked.len(), "unlinked keg");
         Ok(unlinked)
     }
 
@@ -454,20 +550,38 @@ impl Linker {
             .and_then(|n| n.to_str());
         if let Some(name) = name {
             let opt_link = self.opt_dir.join(name);
-            if let Ok(target) = fs::read_link(&opt_link) {
-                let resolved = if target.is_relative() {
-                    opt_link.parent().unwrap_or(Path::new("")).join(&target)
-                } else {
-                    target
-                };
-                if fs::canonicalize(&resolved).ok() == fs::canonicalize(keg_path).ok() {
-                    let _ = fs::remove_file(&opt_link);
-                }
+            if Self::opt_link_points_to(&opt_link, keg_path) {
+                let _ = fs::remove_file(&opt_link);
             }
         }
         Ok(())
     }
 
+    /// Whether `opt/<name>` exists and resolves to `keg_path`.
+    pub fn opt_link_is_current(&self, keg_path: &Path) -> bool {
+        keg_path
+            .parent()
+            .and_then(|p| p.file_name())
+            .is_some_and(|name| Self::opt_link_points_to(&self.opt_dir.join(name), keg_path))
+    }
+
+    pub fn opt_link_path(&self, name: &str) -> PathBuf {
+        self.opt_dir.join(name)
+    }
+
+    fn opt_link_points_to(opt_link: &Path, keg_path: &Path) -> bool {
+        let Ok(target) = fs::read_link(opt_link) else {
+            return false;
+        };
+        let resolved = if target.is_relative() {
+            opt_link.parent().unwrap_or(Path::new("")).join(&target)
+        } else {
+            target
+        };
+        let resolved = fs::canonicalize(&resolved).ok();
+        resolved.is_some() && resolved == fs::canonicalize(keg_path).ok()
+    }
+
     pub fn link_opt(&self, keg_path: &Path) -> Result<(), Error> {
         let name = keg_path
             .parent()
@@ -478,15 +592,8 @@ impl Linker {
             })?;
         let opt_link = self.opt_dir.join(name);
         if opt_link.symlink_metadata().is_ok() {
-            if let Ok(target) = fs::read_link(&opt_link) {
-                let resolved = if target.is_relative() {
-                    opt_link.parent().unwrap_or(Path::new("")).join(&target)
-                } else {
-                    target
-                };
-                if fs::canonicalize(&resolved).ok() == fs::canonicalize(keg_path).ok() {
-                    return Ok(());
-                }
+            if Self::opt_link_points_to(&opt_link, keg_path) {
+                return Ok(());
             }
             let _ = fs::remove_file(&opt_link);
         }
@@ -863,6 +970,72 @@ mod tests {
         assert!(!prefix.join("opt/beta").exists());
     }
 
+    #[test]
+    fn link_keg_overwriting_replaces_conflicts_and_unlink_keeps_opt() {
+        let tmp = TempDir::new().unwrap();
+        let prefix = tmp.path();
+        let linker = Linker::new(prefix).unwrap();
+
+        let keg1 = setup_keg(&tmp, "alpha");
+        linker.link_keg(&keg1).unwrap();
+        fs::write(prefix.join("bin/stray"), b"not ours").unwrap();
+
+        let keg2 = prefix.join("cellar/beta/1.0.0");
+        let bin2 = keg2.join("bin");
+        fs::create_dir_all(&bin2).unwrap();
+        fs::write(bin2.join("alpha"), b"other").unwrap();
+        fs::write(bin2.join("stray"), b"ours").unwrap();
+
+        let mut conflicts: Vec<_> = linker
+            .find_conflicts(&keg2)
+            .into_iter()
+            .map(|c| (c.path, c.owned_by))
+            .collect();
+        conflicts.sort();
+        assert_eq!(
+            conflicts,
+            vec![
+                (prefix.join("bin/alpha"), Some("alpha".to_string())),
+                (prefix.join("bin/stray"), None),
+            ]
+        );
+
+        linker.link_keg_overwriting(&keg2).unwrap();
+        assert_eq!(fs::read(prefix.join("bin/alpha")).unwrap(), b"other");
+        assert_eq!(fs::read(prefix.join("bin/stray")).unwrap(), b"ours");
+
+        linker.unlink_keg_files(&keg2).unwrap();
+        assert!(!prefix.join("bin/alpha").exists());
+        assert!(prefix.join("opt/beta").exists());
+    }
+
+    #[test]
+    fn link_keg_skipping_leaves_conflicts_and_links_the_rest() {
+        let tmp = TempDir::new().unwrap();
+        let prefix = tmp.path();
+        let linker = Linker::new(prefix).unwrap();
+
+        let keg1 = setup_keg(&tmp, "alpha");
+        linker.link_keg(&keg1).unwrap();
+        fs::write(prefix.join("bin/stray"), b"not ours").unwrap();
+
+        let keg2 = prefix.join("cellar/beta/1.0.0");
+        let bin2 = keg2.join("bin");
+        fs::create_dir_all(&bin2).unwrap();
+        fs::write(bin2.join("alpha"), b"other").unwrap();
+        fs::write(bin2.join("stray"), b"ours").unwrap();
+        fs::write(bin2.join("beta-only"), b"unique").unwrap();
+
+        let linked = linker
+            .link_keg_with(&keg2, LinkConflictPolicy::Skip)
+            .unwrap();
+
+        let linked: Vec<_> = linked.into_iter().map(|l| l.link_path).collect();
+        assert_eq!(linked, vec![prefix.join("bin/beta-only")]);
+        assert_eq!(fs::read(prefix.join("bin/alpha")).unwrap(), b"hi");
+        assert_eq!(fs::read(prefix.join("bin/stray")).unwrap(), b"not ours");
+    }
+
     #[test]
     fn symlink_to_directory_in_keg_expands_without_conflict() {
         // Reproduces the gnu-sed / gnu-tar / findutils conflict from issue #69:
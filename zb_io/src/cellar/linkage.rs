@@ -0,0 +1,592 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+#[cfg(target_os = "macos")]
+use std::process::Command;
+
+use object::Endianness;
+use object::elf;
+use object::read::elf::FileHeader;
+
+/// Prefixes bottles are built for. A library reference still pointing into
+/// one of these after relocation means the patch step missed it.
+const DEFAULT_PREFIXES: &[&str] = &[
+    "/opt/homebrew",
+    "/usr/local/Cellar",
+    "/usr/local/opt",
+    "/home/linuxbrew/.linuxbrew",
+];
+
+/// How many binaries per keg are inspected. Spawning `otool` or parsing
+/// every file in a large keg would make `zb doctor` crawl.
+const MAX_BINARIES_PER_KEG: usize = 16;
+
+const BINARY_DIRS: &[&str] = &["bin", "sbin", "lib", "libexec"];
+
+/// An absolute path to a default Homebrew prefix (or a leftover
+/// `@@HOMEBREW_...@@` placeholder) that relocation left in a binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnrelocatedReference {
+    pub binary: PathBuf,
+    pub reference: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkageProblem {
+    /// The referenced library does not exist.
+    Missing,
+    /// The reference was never relocated off a default Homebrew prefix.
+    DefaultPrefix,
+}
+
+/// A library a binary loads that won't resolve inside this prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLinkage {
+    pub binary: PathBuf,
+    pub library: String,
+    pub problem: LinkageProblem,
+}
+
+/// One library reference a binary makes. `resolved` is `None` when the
+/// library can't be found where the loader would look.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LibraryRef {
+    name: String,
+    resolved: Option<PathBuf>,
+}
+
+/// Inspect a sample of the Mach-O/ELF binaries in `keg_path` and return
+/// every library reference that is dangling or still points at a default
+/// Homebrew prefix other than `prefix`.
+pub fn check_keg_linkage(keg_path: &Path, prefix: &Path) -> Vec<BrokenLinkage> {
+    sample_binaries(keg_path)
+        .into_iter()
+        .flat_map(|binary| {
+            let refs = library_refs(&binary);
+            check_references(&binary, &refs, prefix)
+        })
+        .collect()
+}
+
+/// Scan every Mach-O/ELF binary in `keg_path` for references relocation
+/// didn't rewrite. Unlike [`check_keg_linkage`] this looks at the raw bytes,
+/// so it also catches paths baked into data sections, and it checks every
+/// binary rather than a sample. Reports the first such reference per file.
+pub fn find_unrelocated_references(keg_path: &Path, prefix: &Path) -> Vec<UnrelocatedReference> {
+    let mut needles: Vec<&str> = DEFAULT_PREFIXES
+        .iter()
+        .copied()
+        .filter(|default| !prefix.starts_with(default))
+        .collect();
+    needles.push("@@HOMEBREW_");
+
+    walkdir::WalkDir::new(keg_path)
+        .follow_links(false)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file() && is_object_file(entry.path()))
+        .filter_map(|entry| {
+            let contents = fs::read(entry.path()).ok()?;
+            let reference = needles
+                .iter()
+                .find_map(|needle| find_reference(&contents, needle.as_bytes()))?;
+            Some(UnrelocatedReference {
+                binary: entry.path().to_path_buf(),
+                reference,
+            })
+        })
+        .collect()
+}
+
+/// Find `needle` as the start of a path in `contents` and return the whole
+/// NUL-terminated string it begins.
+fn find_reference(contents: &[u8], needle: &[u8]) -> Option<String> {
+    contents
+        .windows(needle.len() + 1)
+        .enumerate()
+        .find(|(_, window)| {
+            window[..needle.len()] == *needle
+                && (needle.starts_with(b"@@") || window[needle.len()] == b'/')
+        })
+        .map(|(start, _)| {
+            let end = contents[start..]
+                .iter()
+                .position(|&b| b == 0 || !b.is_ascii_graphic())
+                .map_or(contents.len(), |len| start + len);
+            String::from_utf8_lossy(&contents[start..end]).into_owned()
+        })
+}
+
+fn sample_binaries(keg_path: &Path) -> Vec<PathBuf> {
+    let mut binaries = Vec::new();
+    for dir in BINARY_DIRS {
+        let walker = walkdir::WalkDir::new(keg_path.join(dir))
+            .follow_links(false)
+            .sort_by_file_name();
+        for entry in walker.into_iter().filter_map(|e| e.ok()) {
+            if binaries.len() >= MAX_BINARIES_PER_KEG {
+                return binaries;
+            }
+            if entry.file_type().is_file() && is_object_file(entry.path()) {
+                binaries.push(entry.path().to_path_buf());
+            }
+        }
+    }
+    binaries
+}
+
+fn is_object_file(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    let read = fs::File::open(path).and_then(|mut file| file.read_exact(&mut magic));
+    if read.is_err() {
+        return false;
+    }
+    matches!(
+        magic,
+        [0x7f, b'E', b'L', b'F']
+            | [0xcf, 0xfa, 0xed, 0xfe]
+            | [0xce, 0xfa, 0xed, 0xfe]
+            | [0xca, 0xfe, 0xba, 0xbe]
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn library_refs(binary: &Path) -> Vec<LibraryRef> {
+    match Command::new("otool").arg("-L").arg(binary).output() {
+        Ok(output) if output.status.success() => {
+            parse_otool(binary, &String::from_utf8_lossy(&output.stdout))
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn library_refs(binary: &Path) -> Vec<LibraryRef> {
+    let Ok(data) = fs::read(binary) else {
+        return Vec::new();
+    };
+    match read_elf_dynamic(&data) {
+        Some(dynamic) => resolve_needed(binary, &dynamic, &system_library_dirs()),
+        None => Vec::new(),
+    }
+}
+/// Parse `otool -L` output. `@loader_path`/`@executable_path` are resolved
+/// against the binary's directory; `@rpath` references depend on run paths
+/// otool doesn't report, so they are left out rather than guessed at.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_otool(binary: &Path, output: &str) -> Vec<LibraryRef> {
+    let binary_dir = binary.parent().unwrap_or(Path::new("/"));
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().next())
+        .filter_map(|name| {
+            let resolved = if let Some(rest) = name
+                .strip_prefix("@loader_path/")
+                .or_else(|| name.strip_prefix("@executable_path/"))
+            {
+                binary_dir.join(rest)
+            } else if name.starts_with('@') {
+                return None;
+            } else {
+                PathBuf::from(name)
+            };
+            Some(LibraryRef {
+                name: name.to_string(),
+                resolved: Some(resolved),
+            })
+        })
+        .collect()
+}
+
+/// What an ELF binary's dynamic section asks of the loader.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ElfDynamic {
+    needed: Vec<String>,
+    rpath: Vec<String>,
+    runpath: Vec<String>,
+}
+
+/// Read `DT_NEEDED`, `DT_RPATH` and `DT_RUNPATH` straight from the file,
+/// without running the loader the way `ldd` does. `None` for anything that
+/// isn't an ELF object; a static binary has no entries.
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+fn read_elf_dynamic(data: &[u8]) -> Option<ElfDynamic> {
+    match object::FileKind::parse(data).ok()? {
+        object::FileKind::Elf32 => read_dynamic::<elf::FileHeader32<Endianness>>(data),
+        object::FileKind::Elf64 => read_dynamic::<elf::FileHeader64<Endianness>>(data),
+        _ => None,
+    }
+}
+
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+fn read_dynamic<Elf: FileHeader<Endian = Endianness>>(data: &[u8]) -> Option<ElfDynamic> {
+    let header = Elf::parse(data).ok()?;
+    let endian = header.endian().ok()?;
+    let table = header
+        .sections(endian, data)
+        .ok()?
+        .dynamic_table(endian, data)
+        .ok()?;
+
+    let mut dynamic = ElfDynamic::default();
+    for entry in &table {
+        let list = match entry.tag {
+            elf::DT_NEEDED => &mut dynamic.needed,
+            elf::DT_RPATH => &mut dynamic.rpath,
+            elf::DT_RUNPATH => &mut dynamic.runpath,
+            _ => continue,
+        };
+        let Ok(value) = table.string(entry) else {
+            continue;
+        };
+        let value = String::from_utf8_lossy(value);
+        if entry.tag == elf::DT_NEEDED {
+            list.push(value.into_owned());
+        } else {
+            list.extend(
+                value
+                    .split(':')
+                    .filter(|dir| !dir.is_empty())
+                    .map(str::to_string),
+            );
+        }
+    }
+    Some(dynamic)
+}
+
+/// Find each `DT_NEEDED` library where the loader would: `DT_RPATH` (only
+/// when there is no `DT_RUNPATH`), `DT_RUNPATH`, then `system_dirs`.
+/// `LD_LIBRARY_PATH` is left out since it isn't part of the keg. `$ORIGIN`
+/// expands to the binary's directory.
+///
+/// A library found nowhere is unresolved, unless a run path still points at
+/// a default Homebrew prefix: then it resolves there, so the report says
+/// relocation missed it rather than only that it's missing.
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+fn resolve_needed(binary: &Path, dynamic: &ElfDynamic, system_dirs: &[PathBuf]) -> Vec<LibraryRef> {
+    let origin = binary.parent().unwrap_or(Path::new("/")).to_string_lossy();
+    let expand = |entry: &str| {
+        PathBuf::from(
+            entry
+                .replace("${ORIGIN}", &origin)
+                .replace("$ORIGIN", &origin),
+        )
+    };
+    let run_paths: Vec<PathBuf> = if dynamic.runpath.is_empty() {
+        &dynamic.rpath
+    } else {
+        &dynamic.runpath
+    }
+    .iter()
+    .map(|dir| expand(dir))
+    .collect();
+
+    dynamic
+        .needed
+        .iter()
+        .map(|name| {
+            let resolved = if name.contains('/') {
+                Some(expand(name))
+            } else {
+                run_paths
+                    .iter()
+                    .chain(system_dirs)
+                    .map(|dir| dir.join(name))
+                    .find(|path| path.exists())
+                    .or_else(|| {
+                        run_paths
+                            .iter()
+                            .find(|dir| DEFAULT_PREFIXES.iter().any(|d| dir.starts_with(d)))
+                            .map(|dir| dir.join(name))
+                    })
+            };
+            LibraryRef {
+                name: name.clone(),
+                resolved,
+            }
+        })
+        .collect()
+}
+
+/// Where the loader looks after a binary's own run paths: the directories
+/// `/etc/ld.so.conf` lists (what `ld.so.cache` is built from), then the
+/// built-in defaults.
+#[cfg(not(target_os = "macos"))]
+fn system_library_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    read_ld_so_conf(Path::new("/etc/ld.so.conf"), &mut dirs, 0);
+    let multiarch = format!("{}-linux-gnu", std::env::consts::ARCH);
+    for dir in ["/lib", "/usr/lib", "/lib64", "/usr/lib64"] {
+        dirs.push(Path::new(dir).join(&multiarch));
+        dirs.push(PathBuf::from(dir));
+    }
+    dirs
+}
+
+/// Collect the directories an `ld.so.conf` lists, following `include`
+/// lines. Includes may use a `*` in the file name and are relative to the
+/// including file.
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+fn read_ld_so_conf(path: &Path, dirs: &mut Vec<PathBuf>, depth: usize) {
+    // Guards against include cycles.
+    if depth > 8 {
+        return;
+    }
+    let Ok(contents) = fs::read_to_string(path) else {
+        return;
+    };
+    let conf_dir = path.parent().unwrap_or(Path::new("/"));
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(pattern) = line.strip_prefix("include ") {
+            for include in expand_conf_pattern(&conf_dir.join(pattern.trim())) {
+                read_ld_so_conf(&include, dirs, depth + 1);
+            }
+        } else if line.starts_with('/') {
+            dirs.push(PathBuf::from(line));
+        }
+    }
+}
+
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+fn expand_conf_pattern(pattern: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(file)) = (pattern.parent(), pattern.file_name()) else {
+        return Vec::new();
+    };
+    let file = file.to_string_lossy();
+    let Some((head, tail)) = file.split_once('*') else {
+        return vec![pattern.to_path_buf()];
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy())
+                .is_some_and(|name| {
+                    name.len() >= head.len() + tail.len()
+                        && name.starts_with(head)
+                        && name.ends_with(tail)
+                })
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn check_references(binary: &Path, refs: &[LibraryRef], prefix: &Path) -> Vec<BrokenLinkage> {
+    refs.iter()
+        .filter_map(|library| {
+            let problem = match &library.resolved {
+                None => LinkageProblem::Missing,
+                Some(path) if is_default_prefix(path, prefix) => LinkageProblem::DefaultPrefix,
+                // macOS system libraries live in the dyld shared cache and
+                // have no file on disk.
+                Some(path) if path.starts_with("/usr/lib") || path.starts_with("/System") => {
+                    return None;
+                }
+                Some(path) if !path.exists() => LinkageProblem::Missing,
+                Some(_) => return None,
+            };
+            Some(BrokenLinkage {
+                binary: binary.to_path_buf(),
+                library: library.name.clone(),
+                problem,
+            })
+        })
+        .collect()
+}
+
+fn is_default_prefix(path: &Path, prefix: &Path) -> bool {
+    if path.to_string_lossy().contains("@@HOMEBREW_") {
+        return true;
+    }
+    !path.starts_with(prefix)
+        && DEFAULT_PREFIXES
+            .iter()
+            .any(|default| path.starts_with(default))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn dangling_and_default_prefix_references_are_flagged() {
+        let tmp = TempDir::new().unwrap();
+        let prefix = tmp.path().join("prefix");
+        let keg = tmp.path().join("cellar/foo/1.0.0");
+        fs::create_dir_all(keg.join("bin")).unwrap();
+        fs::create_dir_all(prefix.join("lib")).unwrap();
+        fs::write(prefix.join("lib/libok.dylib"), b"").unwrap();
+        let binary = keg.join("bin/foo");
+
+        let output = format!(
+            "{binary}:\n\
+             \t{prefix}/lib/libok.dylib (compatibility version 1.0.0, current version 1.0.0)\n\
+             \t{prefix}/opt/bar/lib/libbar.1.dylib (compatibility version 1.0.0, current version 1.0.0)\n\
+             \t/opt/homebrew/opt/baz/lib/libbaz.dylib (compatibility version 1.0.0, current version 1.0.0)\n\
+             \t@rpath/libqux.dylib (compatibility version 1.0.0, current version 1.0.0)\n\
+             \t/usr/lib/libSystem.B.dylib (compatibility version 1.0.0, current version 1351.0.0)\n",
+            binary = binary.display(),
+            prefix = prefix.display(),
+        );
+
+        let broken = check_references(&binary, &parse_otool(&binary, &output), &prefix);
+
+        assert_eq!(
+            broken,
+            vec![
+                BrokenLinkage {
+                    binary: binary.clone(),
+                    library: format!("{}/opt/bar/lib/libbar.1.dylib", prefix.display()),
+                    problem: LinkageProblem::Missing,
+                },
+                BrokenLinkage {
+                    binary: binary.clone(),
+                    library: "/opt/homebrew/opt/baz/lib/libbaz.dylib".to_string(),
+                    problem: LinkageProblem::DefaultPrefix,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unrelocated_default_prefix_in_binary_is_reported() {
+        let tmp = TempDir::new().unwrap();
+        let prefix = tmp.path().join("prefix");
+        let keg = tmp.path().join("cellar/foo/1.0.0");
+        fs::create_dir_all(keg.join("bin")).unwrap();
+        fs::create_dir_all(keg.join("share")).unwrap();
+
+        let mut binary = b"\x7fELF\x02\x01\x01\0".to_vec();
+        binary.extend_from_slice(b"/opt/homebrew/opt/foo/libexec/helper\0");
+        binary.extend_from_slice(format!("{}/lib/libok.so\0", prefix.display()).as_bytes());
+        fs::write(keg.join("bin/foo"), &binary).unwrap();
+
+        let mut clean = b"\x7fELF\x02\x01\x01\0".to_vec();
+        clean.extend_from_slice(b"/opt/homebrewish\0/usr/lib/libc.so.6\0");
+        fs::write(keg.join("bin/bar"), &clean).unwrap();
+        // Text files are handled by the text relocator, not this check.
+        fs::write(keg.join("share/foo.conf"), "/opt/homebrew/etc/foo").unwrap();
+
+        assert_eq!(
+            find_unrelocated_references(&keg, &prefix),
+            vec![UnrelocatedReference {
+                binary: keg.join("bin/foo"),
+                reference: "/opt/homebrew/opt/foo/libexec/helper".to_string(),
+            }]
+        );
+        assert!(find_unrelocated_references(&keg, Path::new("/opt/homebrew")).is_empty());
+    }
+
+    #[test]
+    fn needed_libraries_resolve_through_run_paths_then_system_dirs() {
+        let tmp = TempDir::new().unwrap();
+        let prefix = tmp.path().join("prefix");
+        let keg = tmp.path().join("cellar/foo/1.0.0");
+        let system = tmp.path().join("system");
+        for dir in [
+            keg.join("bin"),
+            keg.join("lib"),
+            prefix.join("lib"),
+            system.clone(),
+        ] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(keg.join("lib/libown.so.1"), b"").unwrap();
+        fs::write(prefix.join("lib/libz.so.1"), b"").unwrap();
+        fs::write(system.join("libc.so.6"), b"").unwrap();
+        let binary = keg.join("bin/foo");
+
+        let dynamic = ElfDynamic {
+            needed: vec![
+                "libown.so.1".to_string(),
+                "libz.so.1".to_string(),
+                "libc.so.6".to_string(),
+                "libgone.so.2".to_string(),
+            ],
+            rpath: vec!["/nowhere".to_string()],
+            runpath: vec![
+                "$ORIGIN/../lib".to_string(),
+                prefix.join("lib").display().to_string(),
+            ],
+        };
+        let refs = resolve_needed(&binary, &dynamic, std::slice::from_ref(&system));
+
+        assert_eq!(
+            refs.iter().map(|r| r.resolved.clone()).collect::<Vec<_>>(),
+            vec![
+                Some(keg.join("bin/../lib/libown.so.1")),
+                Some(prefix.join("lib/libz.so.1")),
+                Some(system.join("libc.so.6")),
+                None,
+            ]
+        );
+        assert_eq!(
+            check_references(&binary, &refs, &prefix),
+            vec![BrokenLinkage {
+                binary: binary.clone(),
+                library: "libgone.so.2".to_string(),
+                problem: LinkageProblem::Missing,
+            }]
+        );
+
+        // An unrelocated run path is what left the library unfound.
+        let unrelocated = ElfDynamic {
+            needed: vec!["libgone.so.2".to_string()],
+            runpath: vec!["/home/linuxbrew/.linuxbrew/lib".to_string()],
+            ..ElfDynamic::default()
+        };
+        let refs = resolve_needed(&binary, &unrelocated, &[]);
+        assert_eq!(
+            check_references(&binary, &refs, &prefix)[0].problem,
+            LinkageProblem::DefaultPrefix
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn dynamic_section_is_read_without_running_the_loader() {
+        let exe = fs::read(std::env::current_exe().unwrap()).unwrap();
+        let dynamic = read_elf_dynamic(&exe).unwrap();
+        assert!(
+            dynamic.needed.iter().any(|lib| lib.starts_with("libc.so")),
+            "{dynamic:?}"
+        );
+
+        assert_eq!(read_elf_dynamic(b"#!/bin/sh\n"), None);
+    }
+
+    #[test]
+    fn ld_so_conf_includes_are_followed() {
+        let tmp = TempDir::new().unwrap();
+        let conf_d = tmp.path().join("ld.so.conf.d");
+        fs::create_dir_all(&conf_d).unwrap();
+        fs::write(
+            tmp.path().join("ld.so.conf"),
+            "# comment\ninclude ld.so.conf.d/*.conf\n/opt/extra/lib\n",
+        )
+        .unwrap();
+        fs::write(conf_d.join("b.conf"), "/usr/local/lib # trailing\n").unwrap();
+        fs::write(conf_d.join("a.conf"), "/usr/lib/custom\n").unwrap();
+        fs::write(conf_d.join("ignored.txt"), "/not/read\n").unwrap();
+
+        let mut dirs = Vec::new();
+        read_ld_so_conf(&tmp.path().join("ld.so.conf"), &mut dirs, 0);
+        assert_eq!(
+            dirs,
+            vec![
+                PathBuf::from("/usr/lib/custom"),
+                PathBuf::from("/usr/local/lib"),
+                PathBuf::from("/opt/extra/lib"),
+            ]
+        );
+    }
+}
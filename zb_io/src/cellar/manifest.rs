@@ -0,0 +1,85 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use zb_core::Error;
+
+use crate::checksum::sha256_hex;
+
+/// One regular file inside a keg, keyed by its path relative to the keg root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub sha256: String,
+}
+
+/// Walk `keg_path` and return a checksum for every regular file, plus every
+/// symlink inside the keg that does not resolve.
+///
+/// Entries are sorted by path so manifests for the same contents compare equal.
+pub fn scan_keg(keg_path: &Path) -> Result<(Vec<ManifestEntry>, Vec<PathBuf>), Error> {
+    let mut entries = Vec::new();
+    let mut broken_symlinks = Vec::new();
+
+    for entry in walkdir::WalkDir::new(keg_path).follow_links(false) {
+        let entry = entry.map_err(|e| Error::FileError {
+            message: format!("failed to walk keg {}: {e}", keg_path.display()),
+        })?;
+        let file_type = entry.file_type();
+
+        if file_type.is_symlink() {
+            if fs::metadata(entry.path()).is_err() {
+                broken_symlinks.push(entry.path().to_path_buf());
+            }
+        } else if file_type.is_file() {
+            let relative = entry.path().strip_prefix(keg_path).unwrap_or(entry.path());
+            entries.push(ManifestEntry {
+                path: relative.to_string_lossy().into_owned(),
+                sha256: hash_file(entry.path())?,
+            });
+        }
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok((entries, broken_symlinks))
+}
+
+fn hash_file(path: &Path) -> Result<String, Error> {
+    let mut file = File::open(path).map_err(|e| Error::FileError {
+        message: format!("failed to open {}: {e}", path.display()),
+    })?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(|e| Error::FileError {
+        message: format!("failed to read {}: {e}", path.display()),
+    })?;
+    Ok(sha256_hex(hasher))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn scan_hashes_files_and_flags_dangling_symlinks() {
+        let tmp = TempDir::new().unwrap();
+        let keg = tmp.path().join("foo/1.0.0");
+        fs::create_dir_all(keg.join("bin")).unwrap();
+        fs::write(keg.join("bin/foo"), b"hello").unwrap();
+        std::os::unix::fs::symlink("foo", keg.join("bin/foo-alias")).unwrap();
+        std::os::unix::fs::symlink("missing", keg.join("bin/broken")).unwrap();
+
+        let (entries, broken) = scan_keg(&keg).unwrap();
+
+        assert_eq!(
+            entries,
+            vec![ManifestEntry {
+                path: "bin/foo".to_string(),
+                sha256: "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+                    .to_string(),
+            }]
+        );
+        assert_eq!(broken, vec![keg.join("bin/broken")]);
+    }
+}
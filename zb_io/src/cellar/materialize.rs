@@ -37,6 +37,10 @@ impl Cellar {
         Ok(Self { cellar_dir })
     }
 
+    pub fn path(&self) -> &Path {
+        &self.cellar_dir
+    }
+
     pub fn keg_path(&self, name: &str, version: &str) -> PathBuf {
         self.cellar_dir.join(name).join(version)
     }
@@ -115,9 +119,23 @@ impl Cellar {
         // Copy the content to the cellar using best available strategy
         copy_dir_with_fallback(&src_path, &keg_path)?;
 
+        self.relocate(name, version)?;
+
+        Ok(keg_path)
+    }
+
+    /// Rewrite Homebrew placeholders and prefixes in a keg's binaries and
+    /// text files for this prefix. Runs on every materialized keg, and again
+    /// when `zb doctor --repair` finds binaries whose libraries don't resolve.
+    pub fn relocate(&self, name: &str, version: &str) -> Result<(), Error> {
         // Patch Homebrew placeholders in Mach-O binaries
         #[cfg(target_os = "macos")]
-        patch_homebrew_placeholders(&keg_path, &self.cellar_dir, name, version)?;
+        patch_homebrew_placeholders(
+            &self.keg_path(name, version),
+            &self.cellar_dir,
+            name,
+            version,
+        )?;
 
         // Patch Homebrew placeholders in ELF binaries
         #[cfg(target_os = "linux")]
@@ -132,14 +150,35 @@ impl Cellar {
                         self.cellar_dir.display()
                     ),
                 })?;
-            patch_placeholders(&keg_path, prefix, name, version)?;
+            patch_placeholders(&self.keg_path(name, version), prefix, name, version)?;
         }
 
         // Strip quarantine xattrs and ad-hoc sign Mach-O binaries
         #[cfg(target_os = "macos")]
-        codesign_and_strip_xattrs(&keg_path)?;
+        codesign_and_strip_xattrs(&self.keg_path(name, version))?;
 
-        Ok(keg_path)
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        let _ = (name, version);
+
+        Ok(())
+    }
+
+    /// Apparent size of a keg in bytes, summed over regular files without
+    /// following symlinks.
+    pub fn keg_size(&self, name: &str, version: &str) -> Result<u64, Error> {
+        let mut total = 0u64;
+        for entry in walkdir::WalkDir::new(self.keg_path(name, version)) {
+            let entry = entry.map_err(|e| Error::StoreCorruption {
+                message: format!("failed to walk keg '{name}/{version}': {e}"),
+            })?;
+            if entry.file_type().is_file() {
+                let metadata = entry.metadata().map_err(|e| Error::StoreCorruption {
+                    message: format!("failed to stat '{}': {e}", entry.path().display()),
+                })?;
+                total += metadata.len();
+            }
+        }
+        Ok(total)
     }
 
     pub fn remove_keg(&self, name: &str, version: &str) -> Result<(), Error> {
@@ -204,6 +243,19 @@ fn copy_dir_with_fallback(src: &Path, dst: &Path) -> Result<(), Error> {
     copy_dir_recursive(src, dst, true)
 }
 
+/// Copy a tree without sharing any inodes with `src`, for copies their
+/// owner may change in place, like an app that updates itself.
+pub(crate) fn copy_dir_unshared(src: &Path, dst: &Path) -> Result<(), Error> {
+    #[cfg(target_os = "macos")]
+    {
+        if try_clonefile_dir(src, dst).is_ok() {
+            return Ok(());
+        }
+    }
+
+    copy_dir_recursive(src, dst, false)
+}
+
 #[cfg(target_os = "macos")]
 fn try_clonefile_dir(src: &Path, dst: &Path) -> io::Result<()> {
     use std::ffi::CString;
@@ -395,6 +447,20 @@ mod tests {
         assert!(!cellar.has_keg("foo", "1.2.3"));
     }
 
+    #[test]
+    fn keg_size_sums_regular_files() {
+        let tmp = TempDir::new().unwrap();
+        let cellar = Cellar::new(tmp.path()).unwrap();
+        let keg = cellar.keg_path("sized", "1.0.0");
+        fs::create_dir_all(keg.join("bin")).unwrap();
+        fs::write(keg.join("bin/tool"), vec![0u8; 100]).unwrap();
+        fs::write(keg.join("README"), vec![0u8; 23]).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("tool", keg.join("bin/alias")).unwrap();
+
+        assert_eq!(cellar.keg_size("sized", "1.0.0").unwrap(), 123);
+    }
+
     #[test]
     fn keg_path_format() {
         let tmp = TempDir::new().unwrap();
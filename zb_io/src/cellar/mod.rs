@@ -1,5 +1,12 @@
 pub mod link;
+pub mod linkage;
+pub mod manifest;
 pub mod materialize;
 
-pub use link::{LinkedFile, Linker};
+pub use link::{LinkConflictPolicy, LinkedFile, Linker};
+pub use linkage::{
+    BrokenLinkage, LinkageProblem, UnrelocatedReference, check_keg_linkage,
+    find_unrelocated_references,
+};
+pub use manifest::{ManifestEntry, scan_keg};
 pub use materialize::{Cellar, CopyStrategy, MaterializedKeg};
@@ -24,9 +24,16 @@ pub fn patch_placeholders(
     Ok(())
 }
 
+/// Rewrite placeholders and the Linuxbrew prefix to `prefix_dir`, whose
+/// Cellar is `prefix_dir/Cellar`. Shared by RPATH/RUNPATH entries and text
+/// files so both agree on where a keg lives.
 fn rewrite_homebrew_prefixes(input: &str, prefix_dir: &Path) -> String {
     let prefix_str = prefix_dir.to_string_lossy().into_owned();
     input
+        .replace(
+            "@@HOMEBREW_CELLAR@@",
+            &prefix_dir.join("Cellar").to_string_lossy(),
+        )
         .replace("@@HOMEBREW_PREFIX@@", &prefix_str)
         .replace("@@HOMEBREW_REPOSITORY@@", &prefix_str)
         .replace("@@HOMEBREW_LIBRARY@@", &format!("{}/Library", prefix_str))
@@ -312,8 +319,6 @@ fn patch_elf_placeholders(keg_path: &Path, prefix_dir: &Path) -> Result<(), Erro
 
 /// Patch text files containing @@HOMEBREW_...@@ placeholders
 fn patch_text_placeholders(keg_path: &Path, prefix_dir: &Path) -> Result<(), Error> {
-    let cellar_str = prefix_dir.join("Cellar").to_string_lossy().to_string();
-
     // We search for files that are text and contain the placeholders.
     // To avoid reading every large file, we might filter by extension or size,
     // but Homebrew generally patches everything that looks like text.
@@ -351,7 +356,6 @@ fn patch_text_placeholders(keg_path: &Path, prefix_dir: &Path) -> Result<(), Err
             }
 
             let new_content = rewrite_homebrew_prefixes(&content, prefix_dir)
-                .replace("@@HOMEBREW_CELLAR@@", &cellar_str)
                 .replace("@@HOMEBREW_PERL@@", "/usr/bin/perl")
                 .replace("@@HOMEBREW_JAVA@@", "/usr/bin/java");
 
@@ -440,6 +444,22 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn rewrites_cellar_placeholders_in_rpaths() {
+        let tmp = TempDir::new().unwrap();
+        let prefix = tmp.path().join("prefix");
+
+        // Kept by the RPATH filter only once it points into the prefix.
+        let rewritten =
+            rewrite_homebrew_prefixes("@@HOMEBREW_CELLAR@@/openssl@3/3.5.0/lib", &prefix);
+        assert_eq!(
+            rewritten,
+            format!("{}/Cellar/openssl@3/3.5.0/lib", prefix.display())
+        );
+        assert!(rewritten.starts_with(prefix.to_str().unwrap()));
+    }
+
     #[test]
     #[cfg(target_os = "linux")]
     fn patches_text_files() {
@@ -93,6 +93,50 @@ fn patch_text_file_strings(path: &Path, new_prefix: &str, new_cellar: &str) -> R
     Ok(())
 }
 
+/// Point a load path or run path under one of the default Homebrew
+/// prefixes at this prefix. Unlike the in-place string pass, the new path
+/// may be longer, since `install_name_tool` rewrites load commands into the
+/// header padding. Only `Cellar/` and `opt/` paths are rewritten: anything
+/// else under `/usr/local` may be a system library.
+fn relocate_homebrew_path(path: &str, new_prefix: &str, new_cellar: &str) -> Option<String> {
+    for old_prefix in HOMEBREW_PREFIXES {
+        if *old_prefix == new_prefix {
+            continue;
+        }
+        let Some(rest) = path
+            .strip_prefix(old_prefix)
+            .and_then(|rest| rest.strip_prefix('/'))
+        else {
+            continue;
+        };
+        if let Some(keg) = rest.strip_prefix("Cellar/") {
+            return Some(format!("{new_cellar}/{keg}"));
+        }
+        if rest.starts_with("opt/") {
+            return Some(format!("{new_prefix}/{rest}"));
+        }
+    }
+    None
+}
+
+/// The `LC_RPATH` entries in `otool -l` output.
+fn parse_rpaths(otool_output: &str) -> Vec<String> {
+    let mut rpaths = Vec::new();
+    let mut in_rpath = false;
+    for line in otool_output.lines() {
+        let line = line.trim();
+        if let Some(cmd) = line.strip_prefix("cmd ") {
+            in_rpath = cmd.trim() == "LC_RPATH";
+        } else if in_rpath && let Some(rest) = line.strip_prefix("path ") {
+            // `path /some/dir (offset 12)`
+            let path = rest.rsplit_once(" (offset").map_or(rest, |(path, _)| path);
+            rpaths.push(path.to_string());
+            in_rpath = false;
+        }
+    }
+    rpaths
+}
+
 /// Patch hardcoded Homebrew paths in Mach-O binary data sections.
 /// This handles paths like /opt/homebrew/opt/git/libexec/git-core that are baked into binaries.
 fn patch_macho_binary_strings(path: &Path, new_prefix: &str) -> Result<(), Error> {
@@ -312,6 +356,12 @@ pub fn patch_homebrew_placeholders(
             changed = true;
         }
 
+        // Hardcoded default prefixes, which may be shorter than this one
+        if let Some(relocated) = relocate_homebrew_path(&new_path, &prefix_str, &cellar_str) {
+            new_path = relocated;
+            changed = true;
+        }
+
         // Fix version mismatches for this package
         if let Some(re) = &version_regex
             && re.is_match(&new_path)
@@ -410,6 +460,26 @@ pub fn patch_homebrew_placeholders(
             }
         }
 
+        // Run paths (LC_RPATH), which neither -change nor -id touch
+        if let Ok(output) = Command::new("otool")
+            .args(["-l", &path.to_string_lossy()])
+            .output()
+            && output.status.success()
+        {
+            for old_rpath in parse_rpaths(&String::from_utf8_lossy(&output.stdout)) {
+                if let Some(new_rpath) = patch_path(&old_rpath) {
+                    let result = Command::new("install_name_tool")
+                        .args(["-rpath", &old_rpath, &new_rpath, &path.to_string_lossy()])
+                        .output();
+                    if result.is_ok() {
+                        patched_any = true;
+                    } else {
+                        patch_failures.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+
         // Re-sign if we patched anything (patching invalidates code signature)
         if patched_any {
             let _ = Command::new("codesign")
@@ -700,4 +770,58 @@ echo "Hello from $PREFIX"
         assert!(patched.contains("/opt/zerobrew/prefix/Library"));
         assert!(patched.contains("/usr/bin/perl"));
     }
+
+    #[test]
+    fn relocates_default_prefix_paths_into_a_longer_prefix() {
+        let prefix = "/Users/me/tools/zerobrew/prefix";
+        let cellar = "/Users/me/tools/zerobrew/prefix/Cellar";
+
+        assert_eq!(
+            relocate_homebrew_path(
+                "/opt/homebrew/opt/openssl@3/lib/libssl.3.dylib",
+                prefix,
+                cellar
+            )
+            .as_deref(),
+            Some("/Users/me/tools/zerobrew/prefix/opt/openssl@3/lib/libssl.3.dylib")
+        );
+        assert_eq!(
+            relocate_homebrew_path(
+                "/usr/local/Cellar/xz/5.8.1/lib/liblzma.5.dylib",
+                prefix,
+                cellar
+            )
+            .as_deref(),
+            Some("/Users/me/tools/zerobrew/prefix/Cellar/xz/5.8.1/lib/liblzma.5.dylib")
+        );
+        // System libraries under /usr/local and unrelated paths are left alone.
+        assert!(relocate_homebrew_path("/usr/local/lib/libfuse.dylib", prefix, cellar).is_none());
+        assert!(relocate_homebrew_path("/usr/lib/libSystem.B.dylib", prefix, cellar).is_none());
+        assert!(
+            relocate_homebrew_path("/opt/homebrew/opt/xz/lib", "/opt/homebrew", cellar).is_none()
+        );
+    }
+
+    #[test]
+    fn parses_rpaths_from_load_commands() {
+        let output = "\
+/opt/zerobrew/prefix/bin/python3:
+Load command 12
+          cmd LC_LOAD_DYLIB
+      cmdsize 56
+         name /usr/lib/libSystem.B.dylib (offset 24)
+Load command 13
+          cmd LC_RPATH
+      cmdsize 48
+         path @@HOMEBREW_PREFIX@@/lib (offset 12)
+Load command 14
+          cmd LC_RPATH
+      cmdsize 32
+         path @loader_path/../lib (offset 12)
+";
+        assert_eq!(
+            parse_rpaths(output),
+            vec!["@@HOMEBREW_PREFIX@@/lib", "@loader_path/../lib"]
+        );
+    }
 }
@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use serde_json::Value;
 use zb_core::Error;
 
@@ -7,6 +9,13 @@ pub struct CaskBinary {
     pub target: String,
 }
 
+/// An `.app` bundle copied into the appdir under `target`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaskApp {
+    pub source: String,
+    pub target: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ResolvedCask {
     pub install_name: String,
@@ -15,6 +24,47 @@ pub struct ResolvedCask {
     pub url: String,
     pub sha256: String,
     pub binaries: Vec<CaskBinary>,
+    pub apps: Vec<CaskApp>,
+    /// Installer packages, relative to the unpacked download.
+    pub pkgs: Vec<String>,
+    /// Receipt identifiers from the `uninstall` stanza's `pkgutil` entries.
+    pub pkg_ids: Vec<String>,
+    /// Formulas named by `depends_on formula:`.
+    pub formula_dependencies: Vec<String>,
+}
+
+/// How a cask download is laid out on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaskContainer {
+    /// A disk image, mounted for the duration of the install.
+    Dmg,
+    /// A bare installer package.
+    Pkg,
+    /// A zip or tarball, unpacked into the store.
+    Archive,
+    /// A single executable.
+    Raw,
+}
+
+impl CaskContainer {
+    pub fn detect(url: &str, download: &Path) -> Result<Self, Error> {
+        let file_name = url_file_name(url).to_ascii_lowercase();
+        if file_name.ends_with(".dmg") {
+            Ok(Self::Dmg)
+        } else if file_name.ends_with(".pkg") {
+            Ok(Self::Pkg)
+        } else if crate::extraction::is_archive(download)? {
+            Ok(Self::Archive)
+        } else {
+            Ok(Self::Raw)
+        }
+    }
+}
+
+/// The last path segment of `url`, without any query or fragment.
+pub fn url_file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
 }
 
 pub fn resolve_cask(token: &str, cask: &Value) -> Result<ResolvedCask, Error> {
@@ -38,12 +88,14 @@ pub fn resolve_cask(token: &str, cask: &Value) -> Result<ResolvedCask, Error> {
     }
 
     let binaries = parse_binary_artifacts(cask)?;
-    if binaries.is_empty() {
+    let apps = parse_app_artifacts(cask)?;
+    let pkgs = parse_pkg_artifacts(cask);
+    if binaries.is_empty() && apps.is_empty() && pkgs.is_empty() {
         let found = artifact_types(cask);
         return Err(Error::InvalidArgument {
             message: format!(
-                "cask '{token}' has no binary artifacts (found: {found}); \
-                 only casks with 'binary' artifacts are currently supported"
+                "cask '{token}' has no supported artifacts (found: {found}); \
+                 only 'binary', 'app' and 'pkg' artifacts are currently supported"
             ),
         });
     }
@@ -55,9 +107,27 @@ pub fn resolve_cask(token: &str, cask: &Value) -> Result<ResolvedCask, Error> {
         url,
         sha256,
         binaries,
+        apps,
+        pkgs,
+        pkg_ids: parse_pkgutil_ids(cask),
+        formula_dependencies: cask_formula_dependencies(cask),
     })
 }
 
+/// The formulas a cask's `depends_on formula:` names, which the API gives
+/// as a list or, for a single formula, a string.
+pub fn cask_formula_dependencies(cask: &Value) -> Vec<String> {
+    match cask.get("depends_on").and_then(|deps| deps.get("formula")) {
+        Some(Value::String(name)) => vec![name.clone()],
+        Some(Value::Array(names)) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .map(ToString::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
 fn required_string(value: &Value, field: &str) -> Result<String, Error> {
     value
         .get(field)
@@ -148,6 +218,66 @@ fn parse_binary_artifacts(cask: &Value) -> Result<Vec<CaskBinary>, Error> {
     Ok(binaries)
 }
 
+fn parse_app_artifacts(cask: &Value) -> Result<Vec<CaskApp>, Error> {
+    let mut apps: Vec<CaskApp> = Vec::new();
+    for entries in artifact_entries(cask, "app") {
+        for entry in entries {
+            match entry {
+                Value::String(source) => apps.push(CaskApp {
+                    source: source.clone(),
+                    target: basename(source)?,
+                }),
+                // `app "Foo.app", target: "Bar.app"` serializes as a string
+                // followed by an options object.
+                Value::Object(options) => {
+                    let target = options.get("target").and_then(Value::as_str);
+                    if let (Some(app), Some(target)) = (apps.last_mut(), target) {
+                        check_target(target)?;
+                        app.target = target.to_string();
+                    }
+                }
+                _ => {
+                    let (source, target) = parse_binary_entry(entry)?;
+                    apps.push(CaskApp { source, target });
+                }
+            }
+        }
+    }
+    Ok(apps)
+}
+
+fn parse_pkg_artifacts(cask: &Value) -> Vec<String> {
+    artifact_entries(cask, "pkg")
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(ToString::to_string)
+        .collect()
+}
+
+fn parse_pkgutil_ids(cask: &Value) -> Vec<String> {
+    artifact_entries(cask, "uninstall")
+        .flatten()
+        .filter_map(|directive| directive.get("pkgutil"))
+        .flat_map(|ids| match ids {
+            Value::String(id) => vec![id.clone()],
+            Value::Array(ids) => ids
+                .iter()
+                .filter_map(Value::as_str)
+                .map(ToString::to_string)
+                .collect(),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+fn artifact_entries<'a>(cask: &'a Value, kind: &'a str) -> impl Iterator<Item = &'a Vec<Value>> {
+    cask.get("artifacts")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(move |artifact| artifact.get(kind).and_then(Value::as_array))
+}
+
 fn parse_binary_entry(entry: &Value) -> Result<(String, String), Error> {
     if let Some(path) = entry.as_str() {
         return Ok((path.to_string(), basename(path)?));
@@ -171,13 +301,18 @@ fn parse_binary_entry(entry: &Value) -> Result<(String, String), Error> {
         .map(ToString::to_string)
         .unwrap_or_else(|| basename(source).unwrap_or_else(|_| source.to_string()));
 
+    check_target(&target)?;
+
+    Ok((source.to_string(), target))
+}
+
+fn check_target(target: &str) -> Result<(), Error> {
     if target.contains('/') || target.contains('$') || target.contains('~') {
         return Err(Error::InvalidArgument {
-            message: format!("unsupported cask binary target path '{target}'"),
+            message: format!("unsupported cask target path '{target}'"),
         });
     }
-
-    Ok((source.to_string(), target))
+    Ok(())
 }
 
 fn basename(path: &str) -> Result<String, Error> {
@@ -269,22 +404,85 @@ mod tests {
     }
 
     #[test]
-    fn resolve_cask_no_binary_artifacts_lists_found_types() {
+    fn resolve_cask_without_supported_artifacts_lists_found_types() {
         let cask = serde_json::json!({
-            "token": "ghostty",
-            "version": "1.0.0",
-            "url": "https://example.com/Ghostty.dmg",
+            "token": "font-fira-code",
+            "version": "6.2",
+            "url": "https://example.com/Fira_Code_v6.2.zip",
             "sha256": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
             "artifacts": [
-                { "app": ["Ghostty.app"] },
-                { "zap": [{ "trash": ["~/.config/ghostty/"] }] }
+                { "font": ["ttf/FiraCode-Bold.ttf"] },
+                { "zap": [{ "trash": ["~/Library/Fonts/FiraCode-Bold.ttf"] }] }
             ]
         });
 
-        let err = resolve_cask("ghostty", &cask).unwrap_err();
+        let err = resolve_cask("font-fira-code", &cask).unwrap_err();
         let msg = err.to_string();
-        assert!(msg.contains("no binary artifacts"), "got: {msg}");
-        assert!(msg.contains("app"), "got: {msg}");
+        assert!(msg.contains("no supported artifacts"), "got: {msg}");
+        assert!(msg.contains("font"), "got: {msg}");
         assert!(msg.contains("zap"), "got: {msg}");
     }
+
+    #[test]
+    fn resolve_cask_parses_apps_pkgs_and_receipts() {
+        let cask = serde_json::json!({
+            "token": "docker",
+            "version": "4.30.0",
+            "url": "https://example.com/Docker.dmg",
+            "sha256": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "artifacts": [
+                { "app": ["Docker.app", { "target": "Docker Desktop.app" }] },
+                { "pkg": ["Helper.pkg"] },
+                { "uninstall": [
+                    { "quit": "com.docker.docker" },
+                    { "pkgutil": ["com.docker.helper", "com.docker.vmnetd"] }
+                ] }
+            ]
+        });
+
+        let resolved = resolve_cask("docker", &cask).unwrap();
+        assert_eq!(
+            resolved.apps,
+            vec![CaskApp {
+                source: "Docker.app".to_string(),
+                target: "Docker Desktop.app".to_string(),
+            }]
+        );
+        assert_eq!(resolved.pkgs, vec!["Helper.pkg"]);
+        assert_eq!(
+            resolved.pkg_ids,
+            vec!["com.docker.helper", "com.docker.vmnetd"]
+        );
+        assert!(resolved.binaries.is_empty());
+    }
+
+    #[test]
+    fn formula_dependencies_come_from_depends_on() {
+        let list = serde_json::json!({ "depends_on": { "formula": ["gnupg", "pinentry"] } });
+        assert_eq!(cask_formula_dependencies(&list), vec!["gnupg", "pinentry"]);
+
+        let single = serde_json::json!({ "depends_on": { "formula": "gnupg" } });
+        assert_eq!(cask_formula_dependencies(&single), vec!["gnupg"]);
+
+        let macos_only = serde_json::json!({ "depends_on": { "macos": { ">=": ["12"] } } });
+        assert!(cask_formula_dependencies(&macos_only).is_empty());
+    }
+
+    #[test]
+    fn container_is_chosen_from_the_url_before_the_contents() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let download = tmp.path().join("blob");
+        std::fs::write(&download, b"#!/bin/sh\n").unwrap();
+
+        let detect = |url| CaskContainer::detect(url, &download).unwrap();
+        assert_eq!(
+            detect("https://example.com/Foo-1.0.DMG"),
+            CaskContainer::Dmg
+        );
+        assert_eq!(
+            detect("https://example.com/foo.pkg?version=1#x"),
+            CaskContainer::Pkg
+        );
+        assert_eq!(detect("https://example.com/foo"), CaskContainer::Raw);
+    }
 }
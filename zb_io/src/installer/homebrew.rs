@@ -1,3 +1,5 @@
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsStr;
 use std::process::Command;
 
 use zb_core::Error;
@@ -8,6 +10,8 @@ pub struct HomebrewPackage {
     pub name: String,
     pub tap: String,
     pub is_cask: bool,
+    /// Runtime dependencies as reported by `brew info`; empty for casks
+    pub dependencies: Vec<String>,
 }
 
 /// Result of collecting Homebrew packages for migration
@@ -32,11 +36,21 @@ pub fn parse_formulas_from_json(json: &serde_json::Value) -> Vec<HomebrewPackage
                     .and_then(|t| t.as_str())
                     .unwrap_or("homebrew/core")
                     .to_string();
+                let dependencies = formula
+                    .get("dependencies")
+                    .and_then(|d| d.as_array())
+                    .map(|deps| {
+                        deps.iter()
+                            .filter_map(|d| d.as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .unwrap_or_default();
 
                 packages.push(HomebrewPackage {
                     name: name.to_string(),
                     tap,
                     is_cask: false,
+                    dependencies,
                 });
             }
         }
@@ -64,10 +78,40 @@ pub fn parse_casks_from_plain_text(output: &str) -> Vec<HomebrewPackage> {
             name: name.to_string(),
             tap: "homebrew/cask".to_string(),
             is_cask: true,
+            dependencies: Vec::new(),
         })
         .collect()
 }
 
+/// Narrow `packages` to the formulas named in `only` plus every installed
+/// package they depend on, directly or transitively
+///
+/// Names in `only` that do not match any package are ignored; callers
+/// compare against the result to report them.
+pub fn select_with_dependencies(
+    packages: Vec<HomebrewPackage>,
+    only: &[String],
+) -> Vec<HomebrewPackage> {
+    let by_name: HashMap<&str, &HomebrewPackage> =
+        packages.iter().map(|p| (p.name.as_str(), p)).collect();
+
+    let mut selected = BTreeSet::new();
+    let mut pending: Vec<&str> = only.iter().map(String::as_str).collect();
+    while let Some(name) = pending.pop() {
+        let Some(pkg) = by_name.get(name) else {
+            continue;
+        };
+        if selected.insert(pkg.name.clone()) {
+            pending.extend(pkg.dependencies.iter().map(String::as_str));
+        }
+    }
+
+    packages
+        .into_iter()
+        .filter(|p| selected.contains(&p.name))
+        .collect()
+}
+
 /// Categorize Homebrew packages for migration
 ///
 /// Returns a struct with separate lists for:
@@ -102,6 +146,42 @@ pub fn categorize_packages(packages: Vec<HomebrewPackage>) -> HomebrewMigrationP
 /// Formulas from other taps and all casks are collected separately.
 /// Only leaves are migrated, as there's no use to reinstalling dependencies.
 pub fn get_homebrew_packages() -> Result<HomebrewMigrationPackages, Error> {
+    homebrew_packages(None)
+}
+
+/// Get the named Homebrew packages and their installed dependencies,
+/// categorized for migration
+///
+/// Unlike [`get_homebrew_packages`], this is not limited to leaves: a name
+/// may refer to any installed formula or cask.
+pub fn get_homebrew_packages_only(only: &[String]) -> Result<HomebrewMigrationPackages, Error> {
+    homebrew_packages(Some(only))
+}
+
+/// The leaves and casks Homebrew has installed, or with `only`, just those
+/// names and what they depend on.
+fn homebrew_packages(only: Option<&[String]>) -> Result<HomebrewMigrationPackages, Error> {
+    let formulas = match only {
+        Some(_) => parse_formulas_from_json(&brew_info(["--installed"])?),
+        None => {
+            let leaves = get_homebrew_leaves()?;
+            if leaves.is_empty() {
+                Vec::new()
+            } else {
+                parse_formulas_from_json(&brew_info(&leaves)?)
+            }
+        }
+    };
+    let casks = get_homebrew_casks()?;
+
+    let all_packages: Vec<HomebrewPackage> = formulas.into_iter().chain(casks).collect();
+    Ok(categorize_packages(match only {
+        Some(only) => select_with_dependencies(all_packages, only),
+        None => all_packages,
+    }))
+}
+
+fn get_homebrew_leaves() -> Result<Vec<String>, Error> {
     let leaves_output = Command::new("brew")
         .args(["leaves"])
         .output()
@@ -113,29 +193,34 @@ pub fn get_homebrew_packages() -> Result<HomebrewMigrationPackages, Error> {
         ));
     }
 
-    let leaves = parse_leaves_from_plain_text(&String::from_utf8_lossy(&leaves_output.stdout));
-
-    let formulas = if leaves.is_empty() {
-        Vec::new()
-    } else {
-        let formulas_output = Command::new("brew")
-            .args(["info", "--json=v1"])
-            .args(&leaves)
-            .output()
-            .map_err(Error::exec("failed to run 'brew info'"))?;
-
-        if !formulas_output.status.success() {
-            return Err((Error::exec("brew info failed"))(String::from_utf8_lossy(
-                &formulas_output.stderr,
-            )));
-        }
+    Ok(parse_leaves_from_plain_text(&String::from_utf8_lossy(
+        &leaves_output.stdout,
+    )))
+}
 
-        let formulas_json: serde_json::Value = serde_json::from_slice(&formulas_output.stdout)
-            .map_err(Error::exec("failed to parse brew info JSON"))?;
+/// `brew info --json=v1` with `args` added.
+fn brew_info<I, S>(args: I) -> Result<serde_json::Value, Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let formulas_output = Command::new("brew")
+        .args(["info", "--json=v1"])
+        .args(args)
+        .output()
+        .map_err(Error::exec("failed to run 'brew info'"))?;
 
-        parse_formulas_from_json(&formulas_json)
-    };
+    if !formulas_output.status.success() {
+        return Err((Error::exec("brew info failed"))(String::from_utf8_lossy(
+            &formulas_output.stderr,
+        )));
+    }
+
+    serde_json::from_slice(&formulas_output.stdout)
+        .map_err(Error::exec("failed to parse brew info JSON"))
+}
 
+fn get_homebrew_casks() -> Result<Vec<HomebrewPackage>, Error> {
     let casks_output = Command::new("brew")
         .args(["list", "--cask"])
         .output()
@@ -147,10 +232,9 @@ pub fn get_homebrew_packages() -> Result<HomebrewMigrationPackages, Error> {
         ));
     }
 
-    let casks = parse_casks_from_plain_text(&String::from_utf8_lossy(&casks_output.stdout));
-
-    let all_packages: Vec<HomebrewPackage> = formulas.into_iter().chain(casks).collect();
-    Ok(categorize_packages(all_packages))
+    Ok(parse_casks_from_plain_text(&String::from_utf8_lossy(
+        &casks_output.stdout,
+    )))
 }
 #[cfg(test)]
 mod tests {
@@ -249,11 +333,13 @@ mod tests {
                 name: "git".to_string(),
                 tap: "homebrew/core".to_string(),
                 is_cask: false,
+                dependencies: Vec::new(),
             },
             HomebrewPackage {
                 name: "curl".to_string(),
                 tap: "homebrew/core".to_string(),
                 is_cask: false,
+                dependencies: Vec::new(),
             },
         ];
 
@@ -271,11 +357,13 @@ mod tests {
                 name: "php".to_string(),
                 tap: "shivammathur/php".to_string(),
                 is_cask: false,
+                dependencies: Vec::new(),
             },
             HomebrewPackage {
                 name: "mysql".to_string(),
                 tap: "homebrew/mysql".to_string(),
                 is_cask: false,
+                dependencies: Vec::new(),
             },
         ];
 
@@ -293,11 +381,13 @@ mod tests {
                 name: "visual-studio-code".to_string(),
                 tap: "homebrew/cask".to_string(),
                 is_cask: true,
+                dependencies: Vec::new(),
             },
             HomebrewPackage {
                 name: "firefox".to_string(),
                 tap: "homebrew/cask".to_string(),
                 is_cask: true,
+                dependencies: Vec::new(),
             },
         ];
 
@@ -315,16 +405,19 @@ mod tests {
                 name: "git".to_string(),
                 tap: "homebrew/core".to_string(),
                 is_cask: false,
+                dependencies: Vec::new(),
             },
             HomebrewPackage {
                 name: "php".to_string(),
                 tap: "homebrew/php".to_string(),
                 is_cask: false,
+                dependencies: Vec::new(),
             },
             HomebrewPackage {
                 name: "visual-studio-code".to_string(),
                 tap: "homebrew/cask".to_string(),
                 is_cask: true,
+                dependencies: Vec::new(),
             },
         ];
 
@@ -340,12 +433,59 @@ mod tests {
         assert_eq!(result.casks[0].name, "visual-studio-code");
     }
 
+    #[test]
+    fn test_parse_formulas_reads_dependencies() {
+        let brew_output = r#"[
+            {"name": "wget", "dependencies": ["libidn2", "openssl@3"]},
+            {"name": "tree"}
+        ]"#;
+
+        let formulas_json: serde_json::Value = serde_json::from_str(brew_output).unwrap();
+        let packages = parse_formulas_from_json(&formulas_json);
+
+        assert_eq!(packages[0].dependencies, vec!["libidn2", "openssl@3"]);
+        assert!(packages[1].dependencies.is_empty());
+    }
+
+    #[test]
+    fn test_select_with_dependencies_skips_unrelated_formulas() {
+        let brew_output = r#"[
+            {"name": "wget", "dependencies": ["openssl@3"]},
+            {"name": "openssl@3", "dependencies": ["ca-certificates"]},
+            {"name": "ca-certificates", "dependencies": []},
+            {"name": "jq", "dependencies": ["oniguruma"]},
+            {"name": "oniguruma", "dependencies": []}
+        ]"#;
+
+        let formulas_json: serde_json::Value = serde_json::from_str(brew_output).unwrap();
+        let packages = parse_formulas_from_json(&formulas_json);
+
+        let selected = select_with_dependencies(packages, &["wget".to_string()]);
+        let names: Vec<&str> = selected.iter().map(|p| p.name.as_str()).collect();
+
+        assert_eq!(names, vec!["wget", "openssl@3", "ca-certificates"]);
+    }
+
+    #[test]
+    fn test_select_with_dependencies_ignores_unknown_names() {
+        let packages = parse_casks_from_plain_text("firefox\n");
+
+        let selected = select_with_dependencies(
+            packages,
+            &["firefox".to_string(), "not-installed".to_string()],
+        );
+
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].name, "firefox");
+    }
+
     #[test]
     fn test_homebrew_package_struct() {
         let pkg = HomebrewPackage {
             name: "test-formula".to_string(),
             tap: "homebrew/core".to_string(),
             is_cask: false,
+            dependencies: Vec::new(),
         };
 
         assert_eq!(pkg.name, "test-formula");
@@ -356,6 +496,7 @@ mod tests {
             name: "test-cask".to_string(),
             tap: "homebrew/cask".to_string(),
             is_cask: true,
+            dependencies: Vec::new(),
         };
 
         assert!(cask.is_cask);
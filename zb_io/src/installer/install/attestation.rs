@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use tokio::process::Command;
+use tracing::info;
+use zb_core::{Error, SelectedBottle};
+
+use super::Installer;
+
+/// The repository whose CI builds and signs homebrew/core bottles.
+const CORE_ATTESTATION_REPO: &str = "Homebrew/homebrew-core";
+
+impl Installer {
+    /// Fail unless `blob_path` carries a valid attestation from the
+    /// repository that built `bottle`. A bottle that can't be checked fails
+    /// too: only homebrew/core publishes attestations, and a store entry
+    /// reused offline has no download left to check.
+    pub(super) async fn verify_bottle_attestation(
+        &self,
+        name: &str,
+        bottle: &SelectedBottle,
+        blob_path: &Path,
+    ) -> Result<(), Error> {
+        let repo = verifiable_repo(name, bottle, blob_path)?;
+
+        let output = Command::new("gh")
+            .arg("attestation")
+            .arg("verify")
+            .arg(blob_path)
+            .args(["--repo", repo, "--format", "json"])
+            .output()
+            .await
+            .map_err(|e| Error::ExecutionError {
+                message: if e.kind() == std::io::ErrorKind::NotFound {
+                    "--verify-signatures needs the GitHub CLI (`gh`) on PATH".to_string()
+                } else {
+                    format!("failed to run `gh attestation verify`: {e}")
+                },
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::ExecutionError {
+                message: format!(
+                    "bottle for {name} has no valid attestation from {repo}: {}",
+                    stderr.trim()
+                ),
+            });
+        }
+
+        info!(formula = %name, repo, "verified bottle attestation");
+        Ok(())
+    }
+}
+
+/// The repository to check `blob_path` against, or why it can't be checked.
+fn verifiable_repo(
+    name: &str,
+    bottle: &SelectedBottle,
+    blob_path: &Path,
+) -> Result<&'static str, Error> {
+    let Some(repo) = attestation_repo(&bottle.url) else {
+        return Err(Error::ExecutionError {
+            message: format!(
+                "cannot verify the bottle for {name}: no attestation is published for {} \
+                 (only homebrew/core bottles are signed; install without --verify-signatures \
+                 to accept it)",
+                bottle.url
+            ),
+        });
+    };
+    if !blob_path.exists() {
+        return Err(Error::ExecutionError {
+            message: format!(
+                "cannot verify the bottle for {name}: its download is no longer cached \
+                 (install online to fetch it again, or without --verify-signatures)"
+            ),
+        });
+    }
+    Ok(repo)
+}
+
+/// The repository expected to have signed a bottle, judged by where it is
+/// hosted. `None` for bottles no attestations are published for.
+fn attestation_repo(bottle_url: &str) -> Option<&'static str> {
+    bottle_url
+        .contains("ghcr.io/v2/homebrew/core/")
+        .then_some(CORE_ATTESTATION_REPO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_core_bottles_have_an_attestation_repo() {
+        assert_eq!(
+            attestation_repo("https://ghcr.io/v2/homebrew/core/jq/blobs/sha256:abc"),
+            Some("Homebrew/homebrew-core")
+        );
+        assert_eq!(
+            attestation_repo("https://ghcr.io/v2/someone/tap/tool/blobs/sha256:abc"),
+            None
+        );
+        assert_eq!(
+            attestation_repo("https://mirror.example/bottles/jq.bottle.tar.gz"),
+            None
+        );
+    }
+
+    #[test]
+    fn unverifiable_bottles_are_refused() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let blob = tmp.path().join("blob");
+        std::fs::write(&blob, b"bottle").unwrap();
+        let bottle = |url: &str| SelectedBottle {
+            tag: "arm64_sonoma".to_string(),
+            url: url.to_string(),
+            sha256: "ab".repeat(32),
+        };
+        let core = bottle("https://ghcr.io/v2/homebrew/core/jq/blobs/sha256:abc");
+
+        assert_eq!(
+            verifiable_repo("jq", &core, &blob).unwrap(),
+            "Homebrew/homebrew-core"
+        );
+        let err = verifiable_repo("jq", &core, &tmp.path().join("pruned")).unwrap_err();
+        assert!(err.to_string().contains("no longer cached"), "{err}");
+        let tap = bottle("https://ghcr.io/v2/someone/tap/tool/blobs/sha256:abc");
+        let err = verifiable_repo("tool", &tap, &blob).unwrap_err();
+        assert!(err.to_string().contains("no attestation"), "{err}");
+    }
+}
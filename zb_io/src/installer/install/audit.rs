@@ -0,0 +1,367 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use tracing::warn;
+use zb_core::{Error, Formula, compare_versions, formula_token, is_head_version};
+
+use super::Installer;
+use crate::network::osv::{OsvClient, OsvQuery, Severity, Vulnerability};
+
+/// How many advisories are fetched from OSV at once.
+const ADVISORY_CONCURRENCY: usize = 8;
+
+/// An installed keg with known vulnerabilities.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KegAudit {
+    pub name: String,
+    pub version: String,
+    /// Most severe first.
+    pub vulnerabilities: Vec<Vulnerability>,
+    /// The formula's current version, when it is newer than the keg and
+    /// reaches a version one of the advisories lists as fixed.
+    pub upgrade_available: Option<String>,
+}
+
+impl KegAudit {
+    pub fn severity(&self) -> Severity {
+        self.vulnerabilities
+            .iter()
+            .map(|vuln| vuln.severity)
+            .max()
+            .unwrap_or(Severity::Unknown)
+    }
+}
+
+/// What [`Installer::audit`] found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditReport {
+    /// Sorted by name.
+    pub affected: Vec<KegAudit>,
+    /// How many kegs were checked against OSV.
+    pub scanned: usize,
+    /// Kegs that couldn't be checked: casks, `--HEAD` builds, and formulas
+    /// whose upstream repository is unknown or whose metadata couldn't be
+    /// fetched.
+    pub skipped: Vec<String>,
+}
+
+impl AuditReport {
+    pub fn has_critical(&self) -> bool {
+        self.affected
+            .iter()
+            .any(|keg| keg.severity() == Severity::Critical)
+    }
+}
+
+impl Installer {
+    /// Check installed kegs against the OSV vulnerability database, or only
+    /// `names` when it isn't empty. Formulas are matched by their upstream
+    /// git repository and the version installed from it.
+    pub async fn audit(&self, osv: &OsvClient, names: &[String]) -> Result<AuditReport, Error> {
+        let mut installed = self.db.list_installed()?;
+        if !names.is_empty() {
+            let by_token: HashMap<&str, usize> = installed
+                .iter()
+                .enumerate()
+                .map(|(i, keg)| (formula_token(&keg.name), i))
+                .collect();
+            let mut wanted = Vec::with_capacity(names.len());
+            for name in names {
+                let index = by_token
+                    .get(formula_token(name))
+                    .ok_or_else(|| Error::NotInstalled { name: name.clone() })?;
+                wanted.push(*index);
+            }
+            wanted.sort_unstable();
+            wanted.dedup();
+            installed = wanted.into_iter().map(|i| installed[i].clone()).collect();
+        }
+
+        let mut report = AuditReport::default();
+        let mut checked = Vec::new();
+        let mut queries = Vec::new();
+        for keg in installed {
+            // A HEAD keg is a commit, not a release OSV could match.
+            if keg.name.starts_with("cask:") || is_head_version(&keg.version) {
+                report.skipped.push(keg.name);
+                continue;
+            }
+            let formula = match self.api_client.get_formula(&keg.name).await {
+                Ok(formula) => formula,
+                Err(e) => {
+                    warn!(formula = %keg.name, error = %e, "no formula metadata; not auditing");
+                    report.skipped.push(keg.name);
+                    continue;
+                }
+            };
+            let Some(repo) = upstream_repo(&formula) else {
+                report.skipped.push(keg.name);
+                continue;
+            };
+            queries.push(OsvQuery {
+                repo,
+                version: strip_revision(&keg.version).to_string(),
+            });
+            checked.push((keg.name, keg.version, formula.effective_version()));
+        }
+        report.scanned = checked.len();
+        if queries.is_empty() {
+            return Ok(report);
+        }
+
+        let ids = osv.query_batch(&queries).await?;
+        let mut unique: Vec<&String> = ids.iter().flatten().collect();
+        unique.sort();
+        unique.dedup();
+        let advisories: BTreeMap<String, Vulnerability> = stream::iter(unique)
+            .map(|id| async move { osv.vulnerability(id).await.map(|v| (id.clone(), v)) })
+            .buffer_unordered(ADVISORY_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        for ((name, version, latest), ids) in checked.into_iter().zip(ids) {
+            if ids.is_empty() {
+                continue;
+            }
+            let mut vulnerabilities: Vec<Vulnerability> = ids
+                .iter()
+                .filter_map(|id| advisories.get(id).cloned())
+                .collect();
+            vulnerabilities.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.id.cmp(&b.id)));
+            let upgrade_available =
+                reaches_fix(&version, &latest, &vulnerabilities).then_some(latest);
+            report.affected.push(KegAudit {
+                name,
+                version,
+                vulnerabilities,
+                upgrade_available,
+            });
+        }
+        report.affected.sort_by(|a, b| a.name.cmp(&b.name));
+        report.skipped.sort();
+        Ok(report)
+    }
+}
+
+/// Whether upgrading from `installed` to `latest` crosses a fixed version
+/// of any of `vulnerabilities`.
+fn reaches_fix(installed: &str, latest: &str, vulnerabilities: &[Vulnerability]) -> bool {
+    let installed = strip_revision(installed);
+    let latest = strip_revision(latest);
+    compare_versions(latest, installed) == Ordering::Greater
+        && vulnerabilities
+            .iter()
+            .flat_map(|vuln| &vuln.fixed)
+            .filter_map(|fixed| release_version(fixed))
+            .any(|fixed| {
+                compare_versions(installed, fixed) == Ordering::Less
+                    && compare_versions(latest, fixed) != Ordering::Less
+            })
+}
+
+/// `v1.2.3` or `jq-1.7.1` → the version; `None` for a commit hash, which
+/// OSV's GIT ranges mostly list and which can't be ordered against a
+/// release.
+fn release_version(fixed: &str) -> Option<&str> {
+    if fixed.len() >= 7
+        && fixed.bytes().all(|b| b.is_ascii_hexdigit())
+        && fixed.bytes().any(|b| b.is_ascii_alphabetic())
+    {
+        return None;
+    }
+    let start = fixed.find(|c: char| c.is_ascii_digit())?;
+    Some(&fixed[start..])
+}
+
+/// The git repository a formula is built from, as OSV names it: the
+/// GitHub or GitLab project its source, head, or homepage points at.
+fn upstream_repo(formula: &Formula) -> Option<String> {
+    let urls = formula.urls.as_ref();
+    let stable = urls
+        .and_then(|urls| urls.stable.as_ref())
+        .map(|s| s.url.as_str());
+    let head = urls
+        .and_then(|urls| urls.head.as_ref())
+        .and_then(|head| head.get("url").or(Some(head)))
+        .and_then(|url| url.as_str());
+    [stable, head, formula.homepage.as_deref()]
+        .into_iter()
+        .flatten()
+        .find_map(forge_repo)
+}
+
+fn forge_repo(url: &str) -> Option<String> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let mut parts = rest.split('/');
+    let host = parts.next()?;
+    if host != "github.com" && host != "gitlab.com" {
+        return None;
+    }
+    let owner = parts.next().filter(|s| !s.is_empty())?;
+    let repo = parts.next().filter(|s| !s.is_empty())?;
+    let repo = repo.strip_suffix(".git").unwrap_or(repo);
+    Some(format!("https://{host}/{owner}/{repo}"))
+}
+
+/// `1.2.3_1` → `1.2.3`: the revision is Homebrew's, not upstream's.
+fn strip_revision(version: &str) -> &str {
+    match version.rsplit_once('_') {
+        Some((base, revision)) if revision.bytes().all(|b| b.is_ascii_digit()) => base,
+        _ => version,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::network::osv::{OsvClient, Severity};
+
+    use super::super::test_support::{get_test_bottle_tag, mock_api_client, test_installer};
+    use super::{forge_repo, release_version, strip_revision};
+
+    #[test]
+    fn maps_source_urls_to_repositories() {
+        assert_eq!(
+            forge_repo("https://github.com/jqlang/jq/releases/download/jq-1.7.1/jq-1.7.1.tar.gz")
+                .as_deref(),
+            Some("https://github.com/jqlang/jq")
+        );
+        assert_eq!(
+            forge_repo("https://gitlab.com/graphviz/graphviz.git").as_deref(),
+            Some("https://gitlab.com/graphviz/graphviz")
+        );
+        assert_eq!(
+            forge_repo("https://ftp.gnu.org/gnu/wget/wget-1.24.tar.gz"),
+            None
+        );
+        assert_eq!(strip_revision("3.3.2_1"), "3.3.2");
+        assert_eq!(strip_revision("1.0"), "1.0");
+        assert_eq!(release_version("jq-1.7.1"), Some("1.7.1"));
+        assert_eq!(release_version("20240101"), Some("20240101"));
+        assert_eq!(release_version("9f8e7d6c5b4a3f2e1d0c"), None);
+    }
+
+    #[tokio::test]
+    async fn reports_vulnerable_kegs_with_upgrades() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        let tag = get_test_bottle_tag();
+        for (name, version) in [("libbar", "1.1.0"), ("libfoo", "1.1.0"), ("nosrc", "1.0.0")] {
+            let urls = if name == "nosrc" {
+                r#""urls": {"stable": {"url": "https://example.com/nosrc-1.0.0.tar.gz"}},"#
+                    .to_string()
+            } else {
+                format!(
+                    r#""urls": {{"stable": {{"url": "https://github.com/example/{name}/archive/v1.1.0.tar.gz"}}}},"#
+                )
+            };
+            Mock::given(method("GET"))
+                .and(path(format!("/formula/{name}.json")))
+                .respond_with(ResponseTemplate::new(200).set_body_string(format!(
+                    r#"{{
+                        "name": "{name}",
+                        "versions": {{ "stable": "{version}" }},
+                        "dependencies": [],
+                        {urls}
+                        "bottle": {{ "stable": {{ "files": {{ "{tag}": {{
+                            "url": "https://example.com/{name}.tar.gz",
+                            "sha256": "{sha}"
+                        }} }} }} }}
+                    }}"#,
+                    sha = "aa".repeat(32),
+                )))
+                .mount(&mock_server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(path("/osv/querybatch"))
+            .and(body_partial_json(serde_json::json!({
+                "queries": [{
+                    "package": {"name": "https://github.com/example/libbar", "ecosystem": "GIT"},
+                    "version": "1.0.0"
+                }, {
+                    "package": {"name": "https://github.com/example/libfoo", "ecosystem": "GIT"},
+                    "version": "1.0.0"
+                }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{"results": [
+                    {"vulns": [{"id": "OSV-BAR"}]},
+                    {"vulns": [{"id": "OSV-LOW"}, {"id": "OSV-CRIT"}]}
+                ]}"#,
+            ))
+            .mount(&mock_server)
+            .await;
+        // libfoo 1.1.0 fixes OSV-CRIT; nothing released yet fixes OSV-BAR.
+        for (id, vector, fixed) in [
+            (
+                "OSV-LOW",
+                "CVSS:3.1/AV:L/AC:H/PR:H/UI:R/S:U/C:L/I:N/A:N",
+                "[]",
+            ),
+            (
+                "OSV-CRIT",
+                "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H",
+                r#"[{"introduced": "0"}, {"fixed": "9f8e7d6c5b4a"}, {"fixed": "v1.1.0"}]"#,
+            ),
+            (
+                "OSV-BAR",
+                "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:N/A:N",
+                r#"[{"introduced": "0"}, {"fixed": "v2.0.0"}]"#,
+            ),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(format!("/osv/vulns/{id}")))
+                .respond_with(ResponseTemplate::new(200).set_body_string(format!(
+                    r#"{{
+                        "id": "{id}",
+                        "severity": [{{"type": "CVSS_V3", "score": "{vector}"}}],
+                        "affected": [{{"ranges": [{{"type": "GIT", "events": {fixed}}}]}}]
+                    }}"#
+                )))
+                .mount(&mock_server)
+                .await;
+        }
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("libbar", "1.0.0", "old").unwrap();
+            tx.record_install("libfoo", "1.0.0_1", "old").unwrap();
+            tx.record_install("headkeg", "HEAD-abc1234", "source:head")
+                .unwrap();
+            tx.record_install("nosrc", "1.0.0", "old").unwrap();
+            tx.record_install("cask:app", "1.0", "cask").unwrap();
+            tx.commit().unwrap();
+        }
+
+        let osv = OsvClient::with_base_url(format!("{}/osv", mock_server.uri()));
+        let report = installer.audit(&osv, &[]).await.unwrap();
+        assert_eq!(report.scanned, 2);
+        assert_eq!(report.skipped, vec!["cask:app", "headkeg", "nosrc"]);
+        assert_eq!(report.affected.len(), 2);
+        let bar = &report.affected[0];
+        assert_eq!(bar.name, "libbar");
+        assert_eq!(bar.upgrade_available, None);
+        let keg = &report.affected[1];
+        assert_eq!(keg.name, "libfoo");
+        assert_eq!(keg.severity(), Severity::Critical);
+        assert_eq!(keg.vulnerabilities[0].id, "OSV-CRIT");
+        assert_eq!(keg.vulnerabilities[1].severity, Severity::Low);
+        assert_eq!(keg.upgrade_available.as_deref(), Some("1.1.0"));
+        assert!(report.has_critical());
+
+        let err = installer
+            .audit(&osv, &["missing".to_string()])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, zb_core::Error::NotInstalled { .. }));
+    }
+}
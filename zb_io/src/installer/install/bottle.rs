@@ -2,28 +2,29 @@ use std::fs;
 use std::path::Path;
 
 use tracing::warn;
-use zb_core::{Error, InstallMethod, formula_token};
+use zb_core::{Error, SelectedBottle, formula_token};
 
 use crate::cellar::link::Linker;
+use crate::cellar::linkage::find_unrelocated_references;
 use crate::cellar::materialize::Cellar;
-use crate::installer::cask::resolve_cask;
+use crate::installer::cask::{CaskContainer, ResolvedCask, resolve_cask};
 use crate::network::download::{DownloadProgressCallback, DownloadRequest, DownloadResult};
 use crate::progress::InstallProgress;
+use crate::storage::db::{CaskArtifact, Database};
 
+use super::cask_artifacts::{MountedDmg, discard_cask_artifacts, stage_bare_pkg};
 use super::{Installer, MAX_CORRUPTION_RETRIES, PlannedInstall};
 
 impl Installer {
     pub(super) async fn process_bottle_item(
         &mut self,
         item: &PlannedInstall,
+        bottle: &SelectedBottle,
         download: &DownloadResult,
         download_progress: &Option<DownloadProgressCallback>,
         link: bool,
         report: &impl Fn(InstallProgress),
     ) -> Result<(), Error> {
-        let InstallMethod::Bottle(ref bottle) = item.method else {
-            unreachable!()
-        };
         let install_name = &item.install_name;
         let formula_name = &item.formula.name;
         let version = item.formula.effective_version();
@@ -33,30 +34,49 @@ impl Installer {
             name: formula_name.clone(),
         });
 
+        if self.verify_signatures {
+            self.verify_bottle_attestation(formula_name, bottle, &download.blob_path)
+                .await?;
+        }
+
         let store_entry = self
             .extract_with_retry(download, &item.formula, bottle, download_progress.clone())
             .await?;
 
-        let keg_path = self
+        // From here on every step is journaled, so a failure (or a crash,
+        // on the next install) rolls back the keg, record and links.
+        let mut journal = self.begin_install_journal(
+            install_name,
+            &version,
+            &self.cellar.keg_path(formula_name, &version),
+        )?;
+
+        let keg_path = match self
             .cellar
-            .materialize(formula_name, &version, &store_entry)?;
+            .materialize(formula_name, &version, &store_entry)
+        {
+            Ok(keg_path) => keg_path,
+            Err(e) => return Err(self.abort_install(journal, e)),
+        };
+
+        if self.require_relocation
+            && let Err(e) = self.ensure_relocated(formula_name, &keg_path)
+        {
+            return Err(self.abort_install(journal, e));
+        }
 
         report(InstallProgress::UnpackCompleted {
             name: formula_name.clone(),
         });
 
-        let tx = self.db.transaction().inspect_err(|_| {
-            Self::cleanup_materialized(&self.cellar, formula_name, &version);
-        })?;
-
-        tx.record_install(install_name, &version, store_key)
-            .inspect_err(|_| {
-                Self::cleanup_materialized(&self.cellar, formula_name, &version);
-            })?;
-
-        tx.commit().inspect_err(|_| {
-            Self::cleanup_materialized(&self.cellar, formula_name, &version);
-        })?;
+        if let Err(e) = journal.recording_install().and_then(|()| {
+            let tx = self.db.transaction()?;
+            tx.record_install(install_name, &version, store_key)?;
+            tx.record_dependencies(install_name, &item.formula.runtime_dependencies())?;
+            tx.commit()
+        }) {
+            return Err(self.abort_install(journal, e));
+        }
 
         if let Err(e) = self.linker.link_opt(&keg_path) {
             warn!(formula = %install_name, error = %e, "failed to create opt link");
@@ -66,7 +86,10 @@ impl Installer {
             report(InstallProgress::LinkStarted {
                 name: formula_name.clone(),
             });
-            match self.linker.link_keg(&keg_path) {
+            match journal
+                .linking()
+                .and_then(|()| self.link_new_keg(install_name, &keg_path))
+            {
                 Ok(linked_files) => {
                     report(InstallProgress::LinkCompleted {
                         name: formula_name.clone(),
@@ -74,44 +97,70 @@ impl Installer {
                     self.record_linked_files(install_name, &version, &linked_files);
                 }
                 Err(e) => {
-                    let _ = self.linker.unlink_keg(&keg_path);
                     report(InstallProgress::InstallCompleted {
                         name: formula_name.clone(),
                     });
-                    return Err(e);
+                    return Err(self.abort_install(journal, e));
                 }
             }
         } else if link && item.formula.is_keg_only() {
-            let reason = match &item.formula.keg_only {
-                zb_core::KegOnly::Reason(s) => s.clone(),
-                _ if formula_name.contains('@') => "versioned formula".to_string(),
-                _ => "keg-only formula".to_string(),
-            };
             report(InstallProgress::LinkSkipped {
                 name: formula_name.clone(),
-                reason,
+                reason: item.formula.keg_only_reason_text().unwrap_or_default(),
             });
         }
 
+        if let Err(e) = journal.commit() {
+            return Err(self.abort_install(journal, e));
+        }
+
+        let verified = if self.verify_after_install {
+            self.verify_installed_keg(install_name, &version, &keg_path)
+                .map(|_| ())
+        } else {
+            Ok(())
+        };
+
         report(InstallProgress::InstallCompleted {
             name: formula_name.clone(),
         });
 
-        Ok(())
+        verified
+    }
+
+    fn ensure_relocated(&self, formula_name: &str, keg_path: &Path) -> Result<(), Error> {
+        let Some(unrelocated) = find_unrelocated_references(keg_path, &self.prefix)
+            .into_iter()
+            .next()
+        else {
+            return Ok(());
+        };
+        let file = unrelocated
+            .binary
+            .strip_prefix(keg_path)
+            .unwrap_or(&unrelocated.binary);
+        Err(Error::UnsupportedFormula {
+            name: formula_name.to_string(),
+            reason: format!(
+                "{} still references {} after relocation (--require-relocation)",
+                file.display(),
+                unrelocated.reference
+            ),
+        })
     }
 
     async fn extract_with_retry(
         &self,
         download: &DownloadResult,
         formula: &zb_core::Formula,
-        bottle: &zb_core::SelectedBottle,
+        bottle: &SelectedBottle,
         progress: Option<DownloadProgressCallback>,
     ) -> Result<std::path::PathBuf, Error> {
         let mut blob_path = download.blob_path.clone();
         let mut last_error = None;
 
         for attempt in 0..MAX_CORRUPTION_RETRIES {
-            match self.store.ensure_entry(&bottle.sha256, &blob_path) {
+            match self.ensure_store_entry(&bottle.sha256, &blob_path) {
                 Ok(entry) => return Ok(entry),
                 Err(Error::StoreCorruption { message }) => {
                     self.downloader.remove_blob(&bottle.sha256);
@@ -163,7 +212,7 @@ impl Installer {
         }))
     }
 
-    fn record_linked_files(
+    pub(super) fn record_linked_files(
         &mut self,
         name: &str,
         version: &str,
@@ -248,36 +297,83 @@ impl Installer {
             link,
         );
 
-        if crate::extraction::is_archive(&blob_path)? {
-            let extracted = self.store.ensure_entry(&cask.sha256, &blob_path)?;
-            stage_cask_binaries(&extracted, &keg_path, &cask)?;
-        } else {
-            stage_raw_cask_binary(&blob_path, &keg_path, &cask)?;
-        }
-
-        let linked_files = if link {
-            self.linker.link_keg(&keg_path)?
-        } else {
-            Vec::new()
+        // Apps go in first so binaries that live inside them can be linked.
+        let (artifacts, staged) = match CaskContainer::detect(&cask.url, &blob_path)? {
+            CaskContainer::Archive => {
+                let extracted = self.ensure_store_entry(&cask.sha256, &blob_path)?;
+                let artifacts = self.install_cask_artifacts(&extracted, &cask)?;
+                let staged = stage_cask_binaries(&extracted, &keg_path, &cask, &self.appdir);
+                (artifacts, staged)
+            }
+            CaskContainer::Dmg => {
+                let image = MountedDmg::attach(&blob_path)?;
+                let artifacts = self.install_cask_artifacts(image.path(), &cask)?;
+                let staged = stage_cask_binaries(image.path(), &keg_path, &cask, &self.appdir);
+                (artifacts, staged)
+            }
+            CaskContainer::Pkg => {
+                let staging = stage_bare_pkg(&blob_path, &cask.url)?;
+                let artifacts = self.install_cask_artifacts(staging.path(), &cask)?;
+                let staged = stage_cask_binaries(staging.path(), &keg_path, &cask, &self.appdir);
+                (artifacts, staged)
+            }
+            CaskContainer::Raw => (
+                Vec::new(),
+                stage_raw_cask_binary(&blob_path, &keg_path, &cask),
+            ),
         };
 
-        let tx = self.db.transaction()?;
-        tx.record_install(&cask.install_name, &cask.version, &cask.sha256)?;
-        for linked in &linked_files {
-            tx.record_linked_file(
-                &cask.install_name,
-                &cask.version,
-                &linked.link_path.to_string_lossy(),
-                &linked.target_path.to_string_lossy(),
-            )?;
+        let recorded = staged.and_then(|()| {
+            record_cask_install(
+                &self.linker,
+                &mut self.db,
+                &cask,
+                &keg_path,
+                &artifacts,
+                link,
+            )
+        });
+        if let Err(e) = recorded {
+            discard_cask_artifacts(&cask.token, &artifacts);
+            return Err(e);
         }
-        tx.commit()?;
 
         cleanup.disarm();
         Ok(())
     }
 }
 
+fn record_cask_install(
+    linker: &Linker,
+    db: &mut Database,
+    cask: &ResolvedCask,
+    keg_path: &Path,
+    artifacts: &[CaskArtifact],
+    link: bool,
+) -> Result<(), Error> {
+    let linked_files = if link {
+        linker.link_keg(keg_path)?
+    } else {
+        Vec::new()
+    };
+
+    let tx = db.transaction()?;
+    tx.record_install(&cask.install_name, &cask.version, &cask.sha256)?;
+    tx.record_dependencies(&cask.install_name, &cask.formula_dependencies)?;
+    for linked in &linked_files {
+        tx.record_linked_file(
+            &cask.install_name,
+            &cask.version,
+            &linked.link_path.to_string_lossy(),
+            &linked.target_path.to_string_lossy(),
+        )?;
+    }
+    for artifact in artifacts {
+        tx.record_cask_artifact(&cask.install_name, artifact)?;
+    }
+    tx.commit()
+}
+
 pub(super) fn dependency_cellar_path(
     cellar: &Cellar,
     installed_name: &str,
@@ -343,11 +439,19 @@ fn stage_cask_binaries(
     extracted_root: &Path,
     keg_path: &Path,
     cask: &crate::installer::cask::ResolvedCask,
+    appdir: &Path,
 ) -> Result<(), Error> {
     let bin_dir = keg_path.join("bin");
     fs::create_dir_all(&bin_dir).map_err(Error::store("failed to create cask bin dir"))?;
 
     for binary in &cask.binaries {
+        // A binary inside an app bundle only works from inside it, so it is
+        // linked to the installed app rather than copied out.
+        if let Some(in_app) = binary.source.strip_prefix("$APPDIR/") {
+            link_app_binary(&appdir.join(in_app), &bin_dir.join(&binary.target), cask)?;
+            continue;
+        }
+
         let source = resolve_cask_source_path(extracted_root, cask, &binary.source)?;
         if !source.exists() {
             return Err(Error::InvalidArgument {
@@ -423,7 +527,33 @@ fn stage_raw_cask_binary(
     Ok(())
 }
 
-fn resolve_cask_source_path(
+fn link_app_binary(
+    source: &Path,
+    target: &Path,
+    cask: &crate::installer::cask::ResolvedCask,
+) -> Result<(), Error> {
+    let escapes = source
+        .components()
+        .any(|component| matches!(component, std::path::Component::ParentDir));
+    if escapes || !source.exists() {
+        return Err(Error::InvalidArgument {
+            message: format!(
+                "cask '{}' binary '{}' is not inside an installed app",
+                cask.token,
+                source.display()
+            ),
+        });
+    }
+
+    if target.symlink_metadata().is_ok() {
+        fs::remove_file(target).map_err(Error::store("failed to replace existing cask binary"))?;
+    }
+    std::os::unix::fs::symlink(source, target).map_err(|e| Error::StoreCorruption {
+        message: format!("failed to link cask binary '{}': {e}", target.display()),
+    })
+}
+
+pub(super) fn resolve_cask_source_path(
     extracted_root: &Path,
     cask: &crate::installer::cask::ResolvedCask,
     source: &str,
@@ -531,6 +661,10 @@ mod tests {
                 source: "claude".to_string(),
                 target: "claude".to_string(),
             }],
+            apps: Vec::new(),
+            pkgs: Vec::new(),
+            pkg_ids: Vec::new(),
+            formula_dependencies: Vec::new(),
         };
 
         stage_raw_cask_binary(&blob_path, &keg_path, &cask).unwrap();
@@ -573,6 +707,10 @@ mod tests {
                     target: "b".to_string(),
                 },
             ],
+            apps: Vec::new(),
+            pkgs: Vec::new(),
+            pkg_ids: Vec::new(),
+            formula_dependencies: Vec::new(),
         };
 
         let err = stage_raw_cask_binary(&blob_path, &keg_path, &cask).unwrap_err();
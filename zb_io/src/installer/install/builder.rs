@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use zb_core::{BottlePolicy, DependencyOptions, Error};
+
+use super::{Installer, cask_artifacts};
+use crate::build::{BuildOptions, build_logs_dir};
+use crate::cellar::link::{LinkConflictPolicy, Linker};
+use crate::cellar::materialize::Cellar;
+use crate::network::api::ApiClient;
+use crate::network::cache::ApiCache;
+use crate::network::download::{DownloadManager, RegistryCredentials, RetryPolicy};
+use crate::storage::blob::BlobCache;
+use crate::storage::db::Database;
+use crate::storage::store::Store;
+
+/// Downloads allowed in flight at once unless [`InstallerBuilder::concurrency`]
+/// says otherwise.
+pub const DEFAULT_CONCURRENCY: usize = 20;
+
+/// Configures and opens an [`Installer`] rooted at a zerobrew root (store,
+/// caches, database) that installs into a prefix (Cellar and links).
+/// Settings left unset keep the same defaults as the `zb` CLI.
+#[derive(Debug, Clone)]
+pub struct InstallerBuilder {
+    root: PathBuf,
+    prefix: PathBuf,
+    concurrency: usize,
+    max_download_rate: Option<u64>,
+    retry: RetryPolicy,
+    mirrors: Vec<String>,
+    cache_dir: Option<PathBuf>,
+    print_build_env: bool,
+    keep_build_dir_on_failure: bool,
+    verify_after_install: bool,
+    require_relocation: bool,
+    verify_signatures: bool,
+    wait_for_lock: bool,
+    offline: bool,
+    appdir: Option<PathBuf>,
+    bottle_policy: BottlePolicy,
+    dependency_options: DependencyOptions,
+    ignored_conflicts: Vec<String>,
+    ignore_pins: bool,
+    link_conflict_policy: LinkConflictPolicy,
+}
+
+impl Installer {
+    pub fn builder(root: impl Into<PathBuf>, prefix: impl Into<PathBuf>) -> InstallerBuilder {
+        InstallerBuilder::new(root, prefix)
+    }
+}
+
+impl InstallerBuilder {
+    pub fn new(root: impl Into<PathBuf>, prefix: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            prefix: prefix.into(),
+            concurrency: DEFAULT_CONCURRENCY,
+            max_download_rate: None,
+            retry: RetryPolicy::default(),
+            mirrors: Vec::new(),
+            cache_dir: None,
+            print_build_env: false,
+            keep_build_dir_on_failure: false,
+            verify_after_install: false,
+            require_relocation: false,
+            verify_signatures: false,
+            wait_for_lock: true,
+            offline: false,
+            appdir: None,
+            bottle_policy: BottlePolicy::default(),
+            dependency_options: DependencyOptions::default(),
+            ignored_conflicts: Vec::new(),
+            ignore_pins: false,
+            link_conflict_policy: LinkConflictPolicy::default(),
+        }
+    }
+
+    /// Downloads to run at once; 1 installs strictly serially.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Cap on combined download throughput, in bytes per second.
+    pub fn max_download_rate(mut self, bytes_per_sec: Option<u64>) -> Self {
+        self.max_download_rate = bytes_per_sec;
+        self
+    }
+
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Bottle mirrors to try before the upstream URL.
+    pub fn mirrors(mut self, mirrors: Vec<String>) -> Self {
+        self.mirrors = mirrors;
+        self
+    }
+
+    /// Keep downloads and API responses here instead of `<root>/cache`.
+    /// Unset, `ZEROBREW_CACHE_DIR` is used if present, which lets several
+    /// roots share one cache.
+    pub fn cache_dir(mut self, cache_dir: Option<PathBuf>) -> Self {
+        self.cache_dir = cache_dir;
+        self
+    }
+
+    /// Print the assembled build environment before each source build.
+    pub fn print_build_env(mut self, enabled: bool) -> Self {
+        self.print_build_env = enabled;
+        self
+    }
+
+    /// Keep a failed source build's work directory for inspection.
+    pub fn keep_build_dir_on_failure(mut self, enabled: bool) -> Self {
+        self.keep_build_dir_on_failure = enabled;
+        self
+    }
+
+    /// Record a file manifest for each new keg and check its symlinks
+    /// resolve before reporting the install as complete.
+    pub fn verify_after_install(mut self, enabled: bool) -> Self {
+        self.verify_after_install = enabled;
+        self
+    }
+
+    /// Fail a bottle install when relocation leaves a binary pointing at a
+    /// default Homebrew prefix, instead of installing a keg that won't run.
+    pub fn require_relocation(mut self, enabled: bool) -> Self {
+        self.require_relocation = enabled;
+        self
+    }
+
+    /// Check each downloaded bottle's GitHub artifact attestation with
+    /// `gh attestation verify` before unpacking it. Needs the GitHub CLI.
+    pub fn verify_signatures(mut self, enabled: bool) -> Self {
+        self.verify_signatures = enabled;
+        self
+    }
+
+    /// Wait for another zerobrew process to finish installing instead of
+    /// failing straight away. On by default.
+    pub fn wait_for_lock(mut self, enabled: bool) -> Self {
+        self.wait_for_lock = enabled;
+        self
+    }
+
+    /// Resolve metadata only from the API cache and install only from bottles
+    /// already in the blob cache or store, so no request leaves the machine.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Copy cask `app` artifacts here instead of `/Applications`.
+    pub fn appdir(mut self, appdir: Option<PathBuf>) -> Self {
+        self.appdir = appdir;
+        self
+    }
+
+    /// Force bottles or pick another architecture's when planning installs.
+    pub fn bottle_policy(mut self, policy: BottlePolicy) -> Self {
+        self.bottle_policy = policy;
+        self
+    }
+
+    /// Leave out recommended dependencies or pull in optional ones when
+    /// planning installs.
+    pub fn dependency_options(mut self, options: DependencyOptions) -> Self {
+        self.dependency_options = options;
+        self
+    }
+
+    /// Skip the `conflicts_with` check for these formulas only.
+    pub fn ignored_conflicts(mut self, names: Vec<String>) -> Self {
+        self.ignored_conflicts = names;
+        self
+    }
+
+    /// Let plans replace pinned kegs instead of leaving them out.
+    pub fn ignore_pins(mut self, enabled: bool) -> Self {
+        self.ignore_pins = enabled;
+        self
+    }
+
+    /// Handle link conflicts this way for every formula, instead of failing.
+    pub fn link_conflict_policy(mut self, policy: LinkConflictPolicy) -> Self {
+        self.link_conflict_policy = policy;
+        self
+    }
+
+    /// Create whatever of the root is missing and open the installer.
+    pub fn build(self) -> Result<Installer, Error> {
+        let root = self.root.as_path();
+        let prefix = self.prefix.as_path();
+        if !root.exists() {
+            fs::create_dir_all(root).map_err(|e| {
+                if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    Error::StoreCorruption {
+                        message: format!(
+                            "cannot create root directory '{}': permission denied.\n\n\
+                            Create it with:\n  sudo mkdir -p {} && sudo chown $USER {}",
+                            root.display(),
+                            root.display(),
+                            root.display()
+                        ),
+                    }
+                } else {
+                    Error::StoreCorruption {
+                        message: format!(
+                            "failed to create root directory '{}': {e}",
+                            root.display()
+                        ),
+                    }
+                }
+            })?;
+        }
+
+        fs::create_dir_all(root.join("db"))
+            .map_err(Error::store("failed to create db directory"))?;
+
+        let cache_dir = self
+            .cache_dir
+            .or_else(|| std::env::var_os("ZEROBREW_CACHE_DIR").map(PathBuf::from))
+            .unwrap_or_else(|| root.join("cache"));
+        fs::create_dir_all(&cache_dir).map_err(Error::store("failed to create cache directory"))?;
+
+        let api_cache_path = cache_dir.join("api-cache.sqlite");
+        let api_cache =
+            ApiCache::open(&api_cache_path).map_err(Error::store("failed to open API cache"))?;
+
+        let mut api_client = match std::env::var("ZEROBREW_API_URL") {
+            Ok(url) => ApiClient::with_base_url(url)?,
+            Err(_) => ApiClient::new(),
+        }
+        .with_cache(api_cache)
+        .with_retry_policy(self.retry);
+        api_client.set_offline(self.offline);
+
+        let blob_cache =
+            BlobCache::new(&cache_dir).map_err(Error::store("failed to create blob cache"))?;
+        let store = Store::new(root).map_err(Error::store("failed to create store"))?;
+        // Use prefix/Cellar so bottles' hardcoded rpaths work
+        let cellar = Cellar::new_at(prefix.join("Cellar"))
+            .map_err(Error::store("failed to create cellar"))?;
+        let linker = Linker::new(prefix).map_err(Error::store("failed to create linker"))?;
+        let db = Database::open(&root.join("db/zb.sqlite3"))?;
+
+        let locks_dir = root.join("locks");
+        fs::create_dir_all(&locks_dir).map_err(Error::store("failed to create locks directory"))?;
+
+        let credentials_path = std::env::var_os("ZEROBREW_REGISTRY_AUTH_FILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| root.join("registry-auth.json"));
+        let mut credentials = RegistryCredentials::load(&credentials_path)?;
+        credentials.apply_env();
+
+        let download_manager = DownloadManager::with_limits(
+            blob_cache,
+            self.concurrency,
+            self.max_download_rate,
+            self.retry,
+            self.mirrors,
+            credentials,
+        );
+
+        Ok(Installer {
+            api_client,
+            downloader: download_manager,
+            store,
+            cellar,
+            linker,
+            db,
+            prefix: prefix.to_path_buf(),
+            locks_dir,
+            build_options: BuildOptions {
+                log_dir: Some(build_logs_dir(root)),
+                print_env: self.print_build_env,
+                keep_work_dir_on_failure: self.keep_build_dir_on_failure,
+                ..BuildOptions::default()
+            },
+            verify_after_install: self.verify_after_install,
+            require_relocation: self.require_relocation,
+            verify_signatures: self.verify_signatures,
+            ignored_conflicts: self.ignored_conflicts,
+            ignore_pins: self.ignore_pins,
+            appdir: self
+                .appdir
+                .unwrap_or_else(|| PathBuf::from(cask_artifacts::DEFAULT_APPDIR)),
+            offline: self.offline,
+            wait_for_lock: self.wait_for_lock,
+            bottle_policy: self.bottle_policy,
+            dependency_options: self.dependency_options,
+            progress: None,
+            link_conflict_policy: self.link_conflict_policy,
+            link_conflict_resolver: None,
+            replacing: HashMap::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn build_creates_missing_root_layout() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("zerobrew");
+        let prefix = root.join("prefix");
+
+        Installer::builder(&root, &prefix)
+            .concurrency(0)
+            .build()
+            .unwrap();
+
+        assert!(root.join("db/zb.sqlite3").is_file());
+        assert!(root.join("locks").is_dir());
+        assert!(prefix.join("Cellar").is_dir());
+    }
+
+    #[test]
+    fn cache_dir_can_live_outside_the_root() {
+        let tmp = TempDir::new().unwrap();
+        let shared = tmp.path().join("shared-cache");
+
+        for root in ["one", "two"] {
+            let root = tmp.path().join(root);
+            let installer = Installer::builder(&root, root.join("prefix"))
+                .cache_dir(Some(shared.clone()))
+                .build()
+                .unwrap();
+            assert_eq!(installer.cache_dir(), shared);
+            assert!(!root.join("cache").exists());
+        }
+        assert!(shared.join("blobs").is_dir());
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use zb_core::Error;
+
+use super::{Installer, try_acquire_install_lock};
+
+/// A bottle in the download cache. Blobs are stored under the sha256 of
+/// their content, so a bottle fetched from a mirror and from upstream is
+/// only kept once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedDownload {
+    pub sha256: String,
+    pub size_bytes: u64,
+    pub modified: Option<SystemTime>,
+    /// Installed formulas whose bottle this is.
+    pub formulas: Vec<String>,
+}
+
+/// What [`Installer::clean_cache`] removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheClean {
+    pub removed_downloads: usize,
+    pub removed_partials: usize,
+    pub freed_bytes: u64,
+}
+
+impl Installer {
+    /// Where downloads are cached; shared between roots when
+    /// `ZEROBREW_CACHE_DIR` points several of them at one directory.
+    pub fn cache_dir(&self) -> &Path {
+        self.downloader.blob_cache().root()
+    }
+
+    /// Every cached bottle, largest first.
+    pub fn cache_entries(&self) -> Result<Vec<CachedDownload>, Error> {
+        let mut owners: HashMap<String, Vec<String>> = HashMap::new();
+        for keg in self.db.list_installed()? {
+            owners.entry(keg.store_key).or_default().push(keg.name);
+        }
+
+        let blob_cache = self.downloader.blob_cache();
+        let mut downloads = Vec::new();
+        for sha256 in blob_cache
+            .list_blobs()
+            .map_err(Error::store("failed to list cached downloads"))?
+        {
+            let Ok(meta) = fs::metadata(blob_cache.blob_path(&sha256)) else {
+                continue;
+            };
+            downloads.push(CachedDownload {
+                formulas: owners.remove(&sha256).unwrap_or_default(),
+                size_bytes: meta.len(),
+                modified: meta.modified().ok(),
+                sha256,
+            });
+        }
+        downloads.sort_by(|a, b| {
+            b.size_bytes
+                .cmp(&a.size_bytes)
+                .then(a.sha256.cmp(&b.sha256))
+        });
+        Ok(downloads)
+    }
+
+    /// Bytes used by cached bottles and interrupted downloads.
+    pub fn cache_size(&self) -> Result<u64, Error> {
+        let blobs: u64 = self
+            .cache_entries()?
+            .iter()
+            .map(|download| download.size_bytes)
+            .sum();
+        let partials: u64 = self
+            .downloader
+            .blob_cache()
+            .list_partials()
+            .map_err(Error::store("failed to list partial downloads"))?
+            .iter()
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|meta| meta.len())
+            .sum();
+        Ok(blobs + partials)
+    }
+
+    /// Remove cached bottles last written at least `older_than` ago. Partial
+    /// downloads are only removed while no install holds the lock, since one
+    /// may be resuming into them.
+    pub fn clean_cache(&self, older_than: Duration) -> Result<CacheClean, Error> {
+        let idle = try_acquire_install_lock(&self.locks_dir)?;
+        let now = SystemTime::now();
+        let old_enough = |modified: Option<SystemTime>| {
+            modified.is_none_or(|modified| {
+                now.duration_since(modified).unwrap_or(Duration::ZERO) >= older_than
+            })
+        };
+        let mut clean = CacheClean::default();
+
+        for download in self.cache_entries()? {
+            if old_enough(download.modified) && self.downloader.remove_blob(&download.sha256) {
+                clean.removed_downloads += 1;
+                clean.freed_bytes += download.size_bytes;
+            }
+        }
+
+        if idle.is_some() {
+            for path in self
+                .downloader
+                .blob_cache()
+                .list_partials()
+                .map_err(Error::store("failed to list partial downloads"))?
+            {
+                let Ok(meta) = fs::metadata(&path) else {
+                    continue;
+                };
+                if old_enough(meta.modified().ok()) && fs::remove_file(&path).is_ok() {
+                    clean.removed_partials += 1;
+                    clean.freed_bytes += meta.len();
+                }
+            }
+        }
+
+        Ok(clean)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    use tempfile::TempDir;
+
+    use crate::cellar::Cellar;
+    use crate::network::api::ApiClient;
+    use crate::storage::blob::BlobCache;
+    use crate::storage::db::Database;
+    use crate::storage::store::Store;
+    use crate::{Installer, Linker};
+
+    use super::CacheClean;
+
+    #[test]
+    fn lists_owners_and_cleans_old_downloads() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        fs::create_dir_all(root.join("db")).unwrap();
+
+        let blob_cache = BlobCache::new(&root.join("cache")).unwrap();
+        let old = "ab".repeat(32);
+        let new = "cd".repeat(32);
+        fs::write(blob_cache.blob_path(&old), b"old bottle").unwrap();
+        fs::write(blob_cache.blob_path(&new), b"new").unwrap();
+        fs::write(blob_cache.partial_path(&"ef".repeat(32)), b"part").unwrap();
+        fs::File::open(blob_cache.blob_path(&old))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(10 * 24 * 60 * 60))
+            .unwrap();
+
+        let mut installer = Installer::new(
+            ApiClient::new(),
+            blob_cache,
+            Store::new(&root).unwrap(),
+            Cellar::new(&root).unwrap(),
+            Linker::new(&prefix).unwrap(),
+            Database::open(&root.join("db/zb.sqlite3")).unwrap(),
+            prefix,
+            root.join("locks"),
+        );
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("foo", "1.0.0", &old).unwrap();
+            tx.commit().unwrap();
+        }
+
+        assert_eq!(installer.cache_dir(), root.join("cache"));
+        let downloads = installer.cache_entries().unwrap();
+        assert_eq!(downloads.len(), 2);
+        assert_eq!(downloads[0].sha256, old);
+        assert_eq!(downloads[0].formulas, vec!["foo".to_string()]);
+        assert!(downloads[1].formulas.is_empty());
+        assert_eq!(installer.cache_size().unwrap(), 10 + 3 + 4);
+
+        let clean = installer
+            .clean_cache(Duration::from_secs(7 * 24 * 60 * 60))
+            .unwrap();
+        assert_eq!(
+            clean,
+            CacheClean {
+                removed_downloads: 1,
+                removed_partials: 0,
+                freed_bytes: 10,
+            }
+        );
+        assert!(installer.downloader.blob_cache().has_blob(&new));
+
+        let clean = installer.clean_cache(Duration::ZERO).unwrap();
+        assert_eq!(clean.removed_downloads, 1);
+        assert_eq!(clean.removed_partials, 1);
+        assert_eq!(installer.cache_size().unwrap(), 0);
+    }
+}
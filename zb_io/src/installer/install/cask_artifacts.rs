@@ -0,0 +1,399 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use tempfile::TempDir;
+use tracing::warn;
+use zb_core::Error;
+
+use crate::cellar::materialize::copy_dir_unshared;
+use crate::installer::cask::{ResolvedCask, url_file_name};
+use crate::storage::db::CaskArtifact;
+
+use super::Installer;
+use super::bottle::resolve_cask_source_path;
+
+/// Where `app` artifacts go unless [`crate::InstallerBuilder::appdir`] says
+/// otherwise.
+pub(super) const DEFAULT_APPDIR: &str = "/Applications";
+
+impl Installer {
+    /// Copy a cask's apps into the appdir and run its installer packages,
+    /// resolving their sources under `root`. Returns what was installed so
+    /// the caller can record it; on failure, whatever was already put in
+    /// place is removed again.
+    pub(super) fn install_cask_artifacts(
+        &self,
+        root: &Path,
+        cask: &ResolvedCask,
+    ) -> Result<Vec<CaskArtifact>, Error> {
+        let mut installed = Vec::new();
+        if let Err(e) = self.install_cask_artifacts_into(root, cask, &mut installed) {
+            discard_cask_artifacts(&cask.token, &installed);
+            return Err(e);
+        }
+        Ok(installed)
+    }
+
+    fn install_cask_artifacts_into(
+        &self,
+        root: &Path,
+        cask: &ResolvedCask,
+        installed: &mut Vec<CaskArtifact>,
+    ) -> Result<(), Error> {
+        for app in &cask.apps {
+            let source = resolve_cask_source_path(root, cask, &app.source)?;
+            if !source.is_dir() {
+                return Err(Error::ExecutionError {
+                    message: format!(
+                        "cask '{}' app '{}' not found in download",
+                        cask.token, app.source
+                    ),
+                });
+            }
+
+            let target = self.appdir.join(&app.target);
+            if target.symlink_metadata().is_ok() {
+                return Err(Error::ExecutionError {
+                    message: format!(
+                        "{} already exists; move it aside or install with --appdir",
+                        target.display()
+                    ),
+                });
+            }
+
+            fs::create_dir_all(&self.appdir).map_err(Error::store("failed to create appdir"))?;
+            // Recorded before copying so a partial copy is cleaned up too.
+            installed.push(CaskArtifact::App(target.to_string_lossy().into_owned()));
+            copy_dir_unshared(&source, &target)?;
+        }
+
+        if !cask.pkgs.is_empty() {
+            for pkg in &cask.pkgs {
+                install_pkg(&resolve_cask_source_path(root, cask, pkg)?)?;
+            }
+            if cask.pkg_ids.is_empty() {
+                warn!(
+                    cask = %cask.token,
+                    "cask lists no package receipts; uninstall will leave its package files in place"
+                );
+            }
+            installed.extend(cask.pkg_ids.iter().cloned().map(CaskArtifact::PkgReceipt));
+        }
+
+        Ok(())
+    }
+
+    /// Remove the apps and package files recorded for cask `name`, then
+    /// forget the records.
+    pub(super) fn remove_cask_artifacts(&mut self, name: &str) -> Result<(), Error> {
+        for artifact in self.db.list_cask_artifacts(name)? {
+            remove_cask_artifact(&artifact)?;
+        }
+        self.db.clear_cask_artifacts(name)
+    }
+}
+
+/// Best-effort removal of artifacts from an install that didn't complete.
+pub(super) fn discard_cask_artifacts(token: &str, artifacts: &[CaskArtifact]) {
+    for artifact in artifacts {
+        if let Err(e) = remove_cask_artifact(artifact) {
+            warn!(
+                cask = %token,
+                artifact = ?artifact,
+                error = %e,
+                "failed to remove cask artifact after install error"
+            );
+        }
+    }
+}
+
+fn remove_cask_artifact(artifact: &CaskArtifact) -> Result<(), Error> {
+    match artifact {
+        CaskArtifact::App(path) => {
+            let path = Path::new(path);
+            let removed = match fs::symlink_metadata(path) {
+                Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+                Ok(_) => fs::remove_file(path),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e),
+            };
+            removed.map_err(|e| Error::FileError {
+                message: format!("failed to remove {}: {e}", path.display()),
+            })
+        }
+        CaskArtifact::PkgReceipt(id) => forget_pkg(id),
+    }
+}
+
+/// A disk image attached read-only at a private mount point, detached again
+/// on drop.
+pub(super) struct MountedDmg {
+    mount_point: TempDir,
+}
+
+impl MountedDmg {
+    pub(super) fn attach(dmg: &Path) -> Result<Self, Error> {
+        require_macos("disk images")?;
+        let mount_point = tempfile::Builder::new()
+            .prefix("zb-dmg-")
+            .tempdir()
+            .map_err(Error::store("failed to create disk image mount point"))?;
+        run(
+            Command::new("hdiutil")
+                .args(["attach", "-nobrowse", "-readonly", "-noautoopen", "-quiet"])
+                .arg("-mountpoint")
+                .arg(mount_point.path())
+                .arg(dmg),
+            "hdiutil attach",
+        )?;
+        Ok(Self { mount_point })
+    }
+
+    pub(super) fn path(&self) -> &Path {
+        self.mount_point.path()
+    }
+}
+
+impl Drop for MountedDmg {
+    fn drop(&mut self) {
+        let detached = Command::new("hdiutil")
+            .args(["detach", "-quiet"])
+            .arg(self.mount_point.path())
+            .status();
+        if !matches!(detached, Ok(status) if status.success()) {
+            warn!(
+                mount_point = %self.mount_point.path().display(),
+                "failed to detach disk image"
+            );
+        }
+    }
+}
+
+/// A directory holding a bare `.pkg` download under its URL file name, which
+/// is how `pkg` stanzas refer to it.
+pub(super) fn stage_bare_pkg(download: &Path, url: &str) -> Result<TempDir, Error> {
+    let staging = tempfile::Builder::new()
+        .prefix("zb-pkg-")
+        .tempdir()
+        .map_err(Error::store("failed to create package staging dir"))?;
+    std::os::unix::fs::symlink(download, staging.path().join(url_file_name(url)))
+        .map_err(Error::store("failed to stage installer package"))?;
+    Ok(staging)
+}
+
+/// Packages install as root, so this goes through `sudo` like Homebrew does.
+fn install_pkg(pkg: &Path) -> Result<(), Error> {
+    require_macos("installer packages")?;
+    run(
+        Command::new("sudo")
+            .args(["installer", "-pkg"])
+            .arg(pkg)
+            .args(["-target", "/"]),
+        "installer",
+    )
+}
+
+/// Delete the files a package receipt lists, then the receipt itself.
+fn forget_pkg(id: &str) -> Result<(), Error> {
+    require_macos("installer packages")?;
+
+    let Some(info) = pkgutil_output(&["--pkg-info", id])? else {
+        warn!(receipt = %id, "package receipt not found; nothing to remove");
+        return Ok(());
+    };
+    let field = |key: &str| {
+        info.lines()
+            .find_map(|line| line.strip_prefix(key))
+            .map(str::trim)
+            .unwrap_or("")
+            .to_string()
+    };
+    let root = Path::new("/")
+        .join(field("volume:"))
+        .join(field("location:"));
+
+    let files: Vec<PathBuf> = pkgutil_output(&["--only-files", "--files", id])?
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| root.join(line))
+        .collect();
+    if !files.is_empty() {
+        run(
+            Command::new("sudo").args(["rm", "-f", "--"]).args(&files),
+            "rm",
+        )?;
+    }
+
+    run(
+        Command::new("sudo").args(["pkgutil", "--forget", id]),
+        "pkgutil --forget",
+    )
+}
+
+/// `pkgutil` stdout, or `None` when it fails because the receipt is unknown.
+fn pkgutil_output(args: &[&str]) -> Result<Option<String>, Error> {
+    let output =
+        Command::new("pkgutil")
+            .args(args)
+            .output()
+            .map_err(|e| Error::ExecutionError {
+                message: format!("failed to run pkgutil: {e}"),
+            })?;
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned()))
+}
+
+fn run(command: &mut Command, what: &str) -> Result<(), Error> {
+    let status = command.status().map_err(|e| Error::ExecutionError {
+        message: format!("failed to run {what}: {e}"),
+    })?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::ExecutionError {
+            message: format!("{what} failed with {status}"),
+        })
+    }
+}
+
+fn require_macos(what: &str) -> Result<(), Error> {
+    if cfg!(target_os = "macos") {
+        Ok(())
+    } else {
+        Err(Error::ExecutionError {
+            message: format!("casks shipped as {what} can only be installed on macOS"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::cellar::Cellar;
+    use crate::network::api::ApiClient;
+    use crate::storage::blob::BlobCache;
+    use crate::storage::db::{CaskArtifact, Database};
+    use crate::storage::store::Store;
+    use crate::{Installer, Linker};
+
+    use super::super::test_support::sha256_hex;
+
+    fn app_tarball() -> Vec<u8> {
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, content, mode) in [
+            (
+                "Foo.app/Contents/Info.plist",
+                &b"<plist></plist>"[..],
+                0o644,
+            ),
+            ("Foo.app/Contents/MacOS/foo", &b"#!/bin/sh\n"[..], 0o755),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_path(path).unwrap();
+            header.set_size(content.len() as u64);
+            header.set_mode(mode);
+            header.set_cksum();
+            builder.append(&header, content).unwrap();
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&builder.into_inner().unwrap()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn app_cask_is_copied_into_appdir_and_removed_on_uninstall() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        let appdir = tmp.path().join("Applications");
+        fs::create_dir_all(root.join("db")).unwrap();
+
+        let tarball = app_tarball();
+        let cask_json = serde_json::json!({
+            "token": "foo",
+            "version": "1.0",
+            "url": format!("{}/downloads/Foo-1.0.tar.gz", mock_server.uri()),
+            "sha256": sha256_hex(&tarball),
+            "artifacts": [
+                { "app": ["Foo.app", { "target": "Foo Bar.app" }] },
+                { "binary": ["$APPDIR/Foo Bar.app/Contents/MacOS/foo"] }
+            ]
+        });
+        Mock::given(method("GET"))
+            .and(path("/cask/foo.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(cask_json))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/downloads/Foo-1.0.tar.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(tarball))
+            .mount(&mock_server)
+            .await;
+
+        let mut installer = Installer::new(
+            ApiClient::with_base_url(format!("{}/formula", mock_server.uri()))
+                .unwrap()
+                .with_cask_base_url(format!("{}/cask", mock_server.uri())),
+            BlobCache::new(&root.join("cache")).unwrap(),
+            Store::new(&root).unwrap(),
+            Cellar::new(&root).unwrap(),
+            Linker::new(&prefix).unwrap(),
+            Database::open(&root.join("db/zb.sqlite3")).unwrap(),
+            prefix.clone(),
+            root.join("locks"),
+        );
+        installer.appdir = appdir.clone();
+
+        installer
+            .install_casks(&["cask:foo".to_string()], true)
+            .await
+            .unwrap();
+
+        let app = appdir.join("Foo Bar.app");
+        assert!(app.join("Contents/Info.plist").exists());
+        assert_eq!(
+            fs::canonicalize(prefix.join("bin/foo")).unwrap(),
+            fs::canonicalize(app.join("Contents/MacOS/foo")).unwrap()
+        );
+        assert_eq!(
+            installer.db.list_cask_artifacts("cask:foo").unwrap(),
+            vec![CaskArtifact::App(app.to_string_lossy().into_owned())]
+        );
+
+        installer.uninstall("cask:foo").unwrap();
+        assert!(!app.exists());
+        assert!(prefix.join("bin/foo").symlink_metadata().is_err());
+        assert!(
+            installer
+                .db
+                .list_cask_artifacts("cask:foo")
+                .unwrap()
+                .is_empty()
+        );
+
+        // An app zerobrew didn't put there is never overwritten.
+        fs::create_dir_all(&app).unwrap();
+        let err = installer
+            .install_casks(&["cask:foo".to_string()], true)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("already exists"), "got: {err}");
+        assert!(!installer.is_installed("cask:foo"));
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::Path;
+
+use zb_core::{Error, Formula};
+
+use super::Installer;
+
+/// What to tell the user about a formula once it is installed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormulaCaveats {
+    pub name: String,
+    pub text: String,
+}
+
+impl Installer {
+    /// Fetch `name`'s caveats with prefix placeholders resolved against this
+    /// installation, followed by the keg-only notice if it has one.
+    pub async fn caveats(&self, name: &str) -> Result<Option<String>, Error> {
+        let formula = self.api_client.get_formula(name).await?;
+        Ok(self.formula_caveats(&formula))
+    }
+
+    /// Why `name` isn't linked into the prefix, or `None` if it isn't
+    /// keg-only.
+    pub async fn keg_only_reason(&self, name: &str) -> Result<Option<String>, Error> {
+        let formula = self.api_client.get_formula(name).await?;
+        Ok(formula.keg_only_reason_text())
+    }
+
+    pub(super) fn formula_caveats(&self, formula: &Formula) -> Option<String> {
+        let sections: Vec<String> = [
+            formula.rendered_caveats(&self.prefix, self.cellar.path()),
+            keg_only_notice(formula, &self.prefix),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        (!sections.is_empty()).then(|| sections.join("\n\n"))
+    }
+}
+
+/// Why a keg-only formula wasn't linked and how to reach it through its
+/// `opt/` link. Hints only cover the directories the keg actually has.
+fn keg_only_notice(formula: &Formula, prefix: &Path) -> Option<String> {
+    let reason = formula.keg_only_reason_text()?;
+    let name = &formula.name;
+    let opt = prefix.join("opt").join(name);
+
+    let mut notice = format!(
+        "{name} is keg-only, which means it was not symlinked into {},\nbecause {}.",
+        prefix.display(),
+        reason.trim_end_matches('.')
+    );
+
+    if opt.join("bin").is_dir() {
+        notice.push_str(&format!(
+            "\n\nIf you need to have {name} first in your PATH, run:\n  export PATH=\"{}:$PATH\"",
+            opt.join("bin").display()
+        ));
+    }
+
+    let lib = opt.join("lib").is_dir();
+    let include = opt.join("include").is_dir();
+    if lib || include {
+        notice.push_str(&format!(
+            "\n\nFor compilers to find {name} you may need to set:"
+        ));
+        if lib {
+            notice.push_str(&format!(
+                "\n  export LDFLAGS=\"-L{}\"",
+                opt.join("lib").display()
+            ));
+        }
+        if include {
+            notice.push_str(&format!(
+                "\n  export CPPFLAGS=\"-I{}\"",
+                opt.join("include").display()
+            ));
+        }
+    }
+
+    if opt.join("lib/pkgconfig").is_dir() {
+        notice.push_str(&format!(
+            "\n\nFor pkg-config to find {name} you may need to set:\n  export PKG_CONFIG_PATH=\"{}\"",
+            opt.join("lib/pkgconfig").display()
+        ));
+    }
+
+    Some(notice)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn formula(json: &str) -> Formula {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn keg_only_notice_hints_only_at_directories_the_keg_has() {
+        let tmp = TempDir::new().unwrap();
+        let prefix = tmp.path();
+        let keg = prefix.join("Cellar/libpq/17.0");
+        fs::create_dir_all(keg.join("bin")).unwrap();
+        fs::create_dir_all(keg.join("lib/pkgconfig")).unwrap();
+        fs::create_dir_all(prefix.join("opt")).unwrap();
+        std::os::unix::fs::symlink(&keg, prefix.join("opt/libpq")).unwrap();
+
+        let libpq = formula(
+            r#"{
+                "name": "libpq",
+                "versions": { "stable": "17.0" },
+                "keg_only": "it conflicts with PostgreSQL",
+                "bottle": { "stable": { "files": {} } }
+            }"#,
+        );
+        let notice = keg_only_notice(&libpq, prefix).unwrap();
+        let opt = prefix.join("opt/libpq");
+
+        assert!(notice.starts_with(&format!(
+            "libpq is keg-only, which means it was not symlinked into {},\nbecause it conflicts with PostgreSQL.",
+            prefix.display()
+        )));
+        assert!(notice.contains(&format!("export PATH=\"{}/bin:$PATH\"", opt.display())));
+        assert!(notice.contains(&format!("export LDFLAGS=\"-L{}/lib\"", opt.display())));
+        assert!(!notice.contains("CPPFLAGS"));
+        assert!(notice.contains(&format!(
+            "export PKG_CONFIG_PATH=\"{}/lib/pkgconfig\"",
+            opt.display()
+        )));
+
+        let jq = formula(
+            r#"{
+                "name": "jq",
+                "versions": { "stable": "1.7.1" },
+                "bottle": { "stable": { "files": {} } }
+            }"#,
+        );
+        assert_eq!(keg_only_notice(&jq, prefix), None);
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+
+use zb_core::Error;
+
+use super::{InstallPlan, Installer};
+
+impl Installer {
+    /// Refuse a plan in which a formula declares a conflict with something
+    /// already installed or planned alongside it, unless that conflict was
+    /// explicitly ignored.
+    pub(super) fn check_conflicts(&self, plan: &InstallPlan) -> Result<(), Error> {
+        let planned: HashSet<&str> = plan
+            .items
+            .iter()
+            .map(|item| item.formula.name.as_str())
+            .collect();
+
+        for item in &plan.items {
+            let conflicts: Vec<String> = item
+                .formula
+                .conflicts_with
+                .iter()
+                .filter(|name| !self.ignored_conflicts.contains(name))
+                .filter(|name| planned.contains(name.as_str()) || self.is_installed(name))
+                .cloned()
+                .collect();
+
+            if !conflicts.is_empty() {
+                let reasons = conflicts
+                    .iter()
+                    .map(|name| {
+                        item.formula
+                            .conflict_reason(name)
+                            .unwrap_or_default()
+                            .to_string()
+                    })
+                    .collect();
+                return Err(Error::FormulaConflict {
+                    name: item.formula.name.clone(),
+                    conflicts,
+                    reasons,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use zb_core::Error;
+
+    use super::super::test_support::{get_test_bottle_tag, mock_api_client, test_installer};
+
+    #[tokio::test]
+    async fn ignored_conflicts_are_skipped_but_others_still_block() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let tag = get_test_bottle_tag();
+        let formula_json = format!(
+            r#"{{
+                "name": "foo",
+                "versions": {{ "stable": "1.0.0" }},
+                "dependencies": [],
+                "conflicts_with": ["bar", "baz"],
+                "conflicts_with_reasons": ["", "both install a `foo` binary"],
+                "bottle": {{
+                    "stable": {{
+                        "files": {{
+                            "{tag}": {{
+                                "url": "https://example.com/foo.bottle.tar.gz",
+                                "sha256": "{sha}"
+                            }}
+                        }}
+                    }}
+                }}
+            }}"#,
+            sha = "ab".repeat(32)
+        );
+        Mock::given(method("GET"))
+            .and(path("/formula/foo.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json))
+            .mount(&mock_server)
+            .await;
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("bar", "1.0.0", "barkey").unwrap();
+            tx.record_install("baz", "1.0.0", "bazkey").unwrap();
+            tx.commit().unwrap();
+        }
+
+        let plan = installer.plan(&["foo".to_string()]).await.unwrap();
+
+        installer.ignored_conflicts = vec!["bar".to_string()];
+        let err = installer.check_conflicts(&plan).unwrap_err();
+        assert_eq!(
+            err,
+            Error::FormulaConflict {
+                name: "foo".to_string(),
+                conflicts: vec!["baz".to_string()],
+                reasons: vec!["both install a `foo` binary".to_string()],
+            }
+        );
+
+        installer.ignored_conflicts = vec!["bar".to_string(), "baz".to_string()];
+        installer.check_conflicts(&plan).unwrap();
+    }
+}
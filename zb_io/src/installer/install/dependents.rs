@@ -0,0 +1,65 @@
+use zb_core::Error;
+
+use super::Installer;
+
+impl Installer {
+    /// Installed formulas that declare `name` as a runtime dependency.
+    ///
+    /// Dependencies come from what each keg recorded at install time.
+    /// Formulas that predate that record and whose metadata cannot be
+    /// fetched are skipped with a warning rather than failing the whole
+    /// lookup.
+    pub async fn installed_dependents(&self, name: &str) -> Result<Vec<String>, Error> {
+        Ok(self
+            .installed_dependency_graph(false)
+            .await?
+            .into_iter()
+            .filter(|(keg, deps)| keg != name && deps.iter().any(|dep| dep == name))
+            .map(|(keg, _)| keg)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::super::test_support::{formula_json, mock_api_client, test_installer};
+
+    #[tokio::test]
+    async fn dependents_are_listed_for_dependency_only() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        for (name, deps) in [("app", &["lib"][..]), ("lib", &[][..])] {
+            Mock::given(method("GET"))
+                .and(path(format!("/formula/{name}.json")))
+                .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(name, deps)))
+                .mount(&mock_server)
+                .await;
+        }
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("app", "1.0.0", &"aa".repeat(32)).unwrap();
+            tx.record_install("lib", "1.0.0", &"bb".repeat(32)).unwrap();
+            tx.commit().unwrap();
+        }
+
+        assert_eq!(
+            installer.installed_dependents("lib").await.unwrap(),
+            vec!["app".to_string()]
+        );
+        assert!(
+            installer
+                .installed_dependents("app")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
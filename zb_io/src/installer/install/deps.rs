@@ -0,0 +1,438 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use tracing::warn;
+use zb_core::{Error, Formula, formula_token, resolve_closure};
+
+use super::Installer;
+use crate::installer::cask::cask_formula_dependencies;
+
+/// A formula and its runtime dependencies, recursively. A dependency shared
+/// by several formulas appears under each of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyTree {
+    pub name: String,
+    pub installed: bool,
+    pub dependencies: Vec<DependencyTree>,
+}
+
+impl Installer {
+    /// Every runtime dependency of `name`, direct or not, in install order.
+    pub async fn dependencies(&self, name: &str) -> Result<Vec<String>, Error> {
+        let roots = [name.to_string()];
+        let formulas = self.fetch_all_formulas(&roots).await?;
+        let mut ordered = resolve_closure(&roots, &formulas)?;
+        ordered.retain(|dep| dep != name);
+        Ok(ordered)
+    }
+
+    pub async fn dependency_tree(&self, name: &str) -> Result<DependencyTree, Error> {
+        let roots = [name.to_string()];
+        let formulas = self.fetch_all_formulas(&roots).await?;
+        // Rejects cycles, so the recursive walk below terminates.
+        resolve_closure(&roots, &formulas)?;
+        Ok(self.subtree(name, &formulas))
+    }
+
+    fn subtree(&self, name: &str, formulas: &BTreeMap<String, Formula>) -> DependencyTree {
+        let dependencies = formulas
+            .get(name)
+            .map(|formula| {
+                formula
+                    .runtime_dependencies()
+                    .iter()
+                    .filter(|dep| formulas.contains_key(*dep))
+                    .map(|dep| self.subtree(dep, formulas))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        DependencyTree {
+            name: name.to_string(),
+            installed: self.is_installed(name),
+            dependencies,
+        }
+    }
+
+    /// Installed formulas that depend on `name`. With `recursive` this
+    /// includes formulas that only reach it through other installed ones.
+    pub async fn uses(&self, name: &str, recursive: bool) -> Result<Vec<String>, Error> {
+        if !recursive {
+            return self.installed_dependents(name).await;
+        }
+
+        let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+        for (installed, deps) in self.installed_dependency_graph(false).await? {
+            for dep in deps {
+                dependents.entry(dep).or_default().push(installed.clone());
+            }
+        }
+
+        let mut found = BTreeSet::new();
+        let mut stack = vec![name.to_string()];
+        while let Some(current) = stack.pop() {
+            for dependent in dependents.get(&current).into_iter().flatten() {
+                if dependent != name && found.insert(dependent.clone()) {
+                    stack.push(dependent.clone());
+                }
+            }
+        }
+
+        Ok(found.into_iter().collect())
+    }
+
+    /// Installed formulas that were asked for by name and that no other
+    /// installed formula depends on.
+    pub async fn leaves(&self) -> Result<Vec<String>, Error> {
+        let graph = self.installed_dependency_graph(false).await?;
+        let mut not_leaves: HashSet<String> = self.db.list_dependencies()?.into_iter().collect();
+        not_leaves.extend(self.db.list_build_dependencies()?);
+        not_leaves.extend(graph.values().flatten().cloned());
+
+        Ok(graph
+            .into_keys()
+            .filter(|name| !not_leaves.contains(name))
+            .collect())
+    }
+
+    /// Kegs installed only as dependencies that nothing installed for its
+    /// own sake still needs, directly or indirectly. Pinned kegs, and what
+    /// they depend on, are always kept.
+    pub async fn orphaned_dependencies(&self) -> Result<Vec<String>, Error> {
+        let dependency_only: HashSet<String> = self.db.list_dependencies()?.into_iter().collect();
+        if dependency_only.is_empty() {
+            return Ok(Vec::new());
+        }
+        let graph = self.installed_dependency_graph(true).await?;
+        let pinned: HashSet<String> = self.db.list_pinned()?.into_iter().collect();
+
+        let mut needed: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<&str> = graph
+            .keys()
+            .filter(|name| !dependency_only.contains(*name) || pinned.contains(*name))
+            .map(String::as_str)
+            .collect();
+        while let Some(name) = stack.pop() {
+            if needed.insert(name) {
+                stack.extend(graph.get(name).into_iter().flatten().map(String::as_str));
+            }
+        }
+
+        Ok(graph
+            .keys()
+            .filter(|name| !needed.contains(name.as_str()))
+            .cloned()
+            .collect())
+    }
+
+    /// Uninstall every keg [`Self::orphaned_dependencies`] reports and
+    /// return their names.
+    ///
+    /// Holds the install lock from computing the orphans to removing the
+    /// last of them, so a concurrent install can't come to depend on one.
+    pub async fn autoremove(&mut self) -> Result<Vec<String>, Error> {
+        let _lock = self.install_lock()?;
+        let orphans = self.orphaned_dependencies().await?;
+        for name in &orphans {
+            self.uninstall_inner(name)?;
+        }
+        Ok(orphans)
+    }
+
+    /// Entries of a Brewfile's `listed` formulas and `cask:` tokens that
+    /// aren't installed. Formulas match by token, so `user/tap/foo` and
+    /// `foo` name the same keg.
+    pub fn missing_kegs(&self, listed: &[String]) -> Result<Vec<String>, Error> {
+        let installed: HashSet<String> = self
+            .db
+            .list_installed()?
+            .into_iter()
+            .map(|keg| formula_token(&keg.name).to_string())
+            .collect();
+        Ok(listed
+            .iter()
+            .filter(|name| !installed.contains(formula_token(name)))
+            .cloned()
+            .collect())
+    }
+
+    /// Installed kegs a Brewfile listing `listed` doesn't account for:
+    /// neither listed nor a runtime dependency, direct or not, of anything
+    /// that is. Pinned kegs, and what they depend on, are always kept.
+    pub async fn unlisted_kegs(&self, listed: &[String]) -> Result<Vec<String>, Error> {
+        let graph = self.installed_dependency_graph(true).await?;
+        let listed: HashSet<&str> = listed.iter().map(|name| formula_token(name)).collect();
+        let pinned: HashSet<String> = self.db.list_pinned()?.into_iter().collect();
+
+        let mut needed: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<&str> = graph
+            .keys()
+            .filter(|name| listed.contains(formula_token(name)) || pinned.contains(*name))
+            .map(String::as_str)
+            .collect();
+        while let Some(name) = stack.pop() {
+            if needed.insert(name) {
+                stack.extend(graph.get(name).into_iter().flatten().map(String::as_str));
+            }
+        }
+
+        Ok(graph
+            .keys()
+            .filter(|name| !needed.contains(name.as_str()))
+            .cloned()
+            .collect())
+    }
+
+    /// Runtime dependencies of every installed formula, and the formulas
+    /// each installed cask depends on, by name, as recorded when each keg
+    /// was installed.
+    ///
+    /// Kegs installed before dependencies were recorded fall back to their
+    /// current metadata, which is then recorded for next time. With
+    /// `strict`, a formula whose metadata can't be fetched fails the
+    /// lookup: deciding what to remove from a partial graph isn't safe.
+    /// Otherwise it is kept with no dependencies and a warning.
+    pub(super) async fn installed_dependency_graph(
+        &self,
+        strict: bool,
+    ) -> Result<BTreeMap<String, Vec<String>>, Error> {
+        let mut recorded = self.db.list_keg_dependencies()?;
+        let mut graph = BTreeMap::new();
+        for keg in self.db.list_installed()? {
+            if let Some(deps) = recorded.remove(&keg.name) {
+                graph.insert(keg.name, deps);
+                continue;
+            }
+            let fetched = match keg.name.strip_prefix("cask:") {
+                Some(token) => self
+                    .api_client
+                    .get_cask(token)
+                    .await
+                    .map(|cask| cask_formula_dependencies(&cask)),
+                None => self
+                    .api_client
+                    .get_formula(&keg.name)
+                    .await
+                    .map(|formula| formula.runtime_dependencies()),
+            };
+            let deps = match fetched {
+                Ok(deps) => {
+                    if let Err(e) = self.db.record_dependencies(&keg.name, &deps) {
+                        warn!(formula = %keg.name, error = %e, "failed to record dependencies");
+                    }
+                    deps
+                }
+                Err(e) if strict => return Err(e),
+                Err(e) => {
+                    warn!(formula = %keg.name, error = %e, "skipping dependents check");
+                    Vec::new()
+                }
+            };
+            graph.insert(keg.name, deps);
+        }
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::super::test_support::{formula_json, mock_api_client, test_installer};
+
+    #[tokio::test]
+    async fn deps_and_uses_walk_the_graph_in_both_directions() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        for (name, deps) in [
+            ("app", &["lib", "extra"][..]),
+            ("lib", &["base"][..]),
+            ("base", &[][..]),
+            ("extra", &[][..]),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(format!("/formula/{name}.json")))
+                .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(name, deps)))
+                .mount(&mock_server)
+                .await;
+        }
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        {
+            let tx = installer.db.transaction().unwrap();
+            for name in ["app", "lib", "base"] {
+                tx.record_install(name, "1.0.0", &format!("{name}key"))
+                    .unwrap();
+            }
+            tx.commit().unwrap();
+        }
+
+        assert_eq!(
+            installer.dependencies("app").await.unwrap(),
+            vec!["base", "extra", "lib"]
+        );
+
+        let tree = installer.dependency_tree("app").await.unwrap();
+        let children: Vec<(&str, bool)> = tree
+            .dependencies
+            .iter()
+            .map(|d| (d.name.as_str(), d.installed))
+            .collect();
+        assert_eq!(children, vec![("lib", true), ("extra", false)]);
+        assert_eq!(tree.dependencies[0].dependencies[0].name, "base");
+        assert!(tree.dependencies[0].dependencies[0].dependencies.is_empty());
+
+        assert_eq!(installer.uses("base", false).await.unwrap(), vec!["lib"]);
+        assert_eq!(
+            installer.uses("base", true).await.unwrap(),
+            vec!["app", "lib"]
+        );
+    }
+
+    #[tokio::test]
+    async fn autoremove_takes_only_dependencies_nothing_explicit_needs() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        for (name, deps) in [
+            ("app", &["lib"][..]),
+            ("lib", &["base"][..]),
+            ("base", &[][..]),
+            ("stray", &[][..]),
+            ("tool", &[][..]),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(format!("/formula/{name}.json")))
+                .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(name, deps)))
+                .mount(&mock_server)
+                .await;
+        }
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        for name in ["app", "lib", "base", "stray", "tool"] {
+            fs::create_dir_all(installer.keg_path(name, "1.0.0")).unwrap();
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install(name, "1.0.0", &format!("{name}key"))
+                .unwrap();
+            tx.commit().unwrap();
+        }
+        for name in ["lib", "base", "stray"] {
+            installer.db.mark_dependency(name).unwrap();
+        }
+
+        assert_eq!(installer.leaves().await.unwrap(), vec!["app", "tool"]);
+        assert_eq!(
+            installer.orphaned_dependencies().await.unwrap(),
+            vec!["stray"]
+        );
+
+        installer.uninstall("app").unwrap();
+        installer.db.pin("base", "1.0.0").unwrap();
+        assert_eq!(installer.autoremove().await.unwrap(), vec!["lib", "stray"]);
+
+        assert!(installer.is_installed("base"));
+        assert!(installer.is_installed("tool"));
+        assert!(!installer.is_installed("lib"));
+        assert!(!installer.keg_path("stray", "1.0.0").exists());
+    }
+
+    #[tokio::test]
+    async fn recorded_dependencies_are_used_without_fetching_metadata() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        // Only the keg installed before dependencies were recorded has
+        // metadata to fetch; after that it is recorded too.
+        Mock::given(method("GET"))
+            .and(path("/formula/legacy.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json("legacy", &["lib"])),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+        {
+            let tx = installer.db.transaction().unwrap();
+            for name in ["app", "lib", "base", "legacy"] {
+                tx.record_install(name, "1.0.0", &format!("{name}key"))
+                    .unwrap();
+            }
+            tx.record_dependencies("app", &["lib".to_string()]).unwrap();
+            tx.record_dependencies("lib", &["base".to_string()])
+                .unwrap();
+            tx.record_dependencies("base", &[]).unwrap();
+            tx.commit().unwrap();
+        }
+        installer.db.mark_dependency("base").unwrap();
+
+        assert_eq!(
+            installer.installed_dependents("lib").await.unwrap(),
+            vec!["app", "legacy"]
+        );
+        assert!(installer.orphaned_dependencies().await.unwrap().is_empty());
+        assert_eq!(
+            installer.db.list_keg_dependencies().unwrap()["legacy"],
+            vec!["lib"]
+        );
+    }
+
+    #[tokio::test]
+    async fn bundle_diff_keeps_listed_kegs_and_their_dependencies() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        for (name, deps) in [
+            ("app", &["lib"][..]),
+            ("lib", &[][..]),
+            ("stray", &[][..]),
+            ("held", &[][..]),
+            ("fontconfig", &[][..]),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(format!("/formula/{name}.json")))
+                .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(name, deps)))
+                .mount(&mock_server)
+                .await;
+        }
+
+        let installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+        {
+            let tx = installer.db.transaction().unwrap();
+            for name in ["app", "lib", "stray", "held", "fontconfig", "cask:old-app"] {
+                tx.record_install(name, "1.0.0", &format!("{name}key"))
+                    .unwrap();
+            }
+            tx.record_dependencies("cask:old-app", &["fontconfig".to_string()])
+                .unwrap();
+            tx.commit().unwrap();
+        }
+        installer.db.pin("held", "1.0.0").unwrap();
+
+        let listed = vec![
+            "example/tap/app".to_string(),
+            "jq".to_string(),
+            "cask:old-app".to_string(),
+            "cask:new-app".to_string(),
+        ];
+        assert_eq!(
+            installer.missing_kegs(&listed).unwrap(),
+            vec!["jq", "cask:new-app"]
+        );
+        assert_eq!(
+            installer.unlisted_kegs(&listed).await.unwrap(),
+            vec!["stray"]
+        );
+        assert_eq!(
+            installer.unlisted_kegs(&["lib".to_string()]).await.unwrap(),
+            vec!["app", "cask:old-app", "fontconfig", "stray"]
+        );
+    }
+}
@@ -1,12 +1,22 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
 use std::path::PathBuf;
+use std::time::SystemTime;
 
+use tracing::warn;
 use zb_core::{Error, formula_token};
 
+use crate::cellar::linkage::{BrokenLinkage, check_keg_linkage};
 use crate::storage::db::StoreRef;
 
 use super::Installer;
+use super::prune::STALE_BUILD_DIR_AGE;
 
+/// What `zb doctor` found.
+///
+/// The report holds the install lock until it is dropped or handed to
+/// [`Installer::repair`], so nothing it calls orphaned can be an install
+/// still in progress.
 #[derive(Debug, Default)]
 pub struct DiagnosticReport {
     pub orphaned_cellar_kegs: Vec<OrphanedKeg>,
@@ -15,6 +25,11 @@ pub struct DiagnosticReport {
     pub stale_store_refs: Vec<StaleStoreRef>,
     pub broken_symlinks: Vec<PathBuf>,
     pub stale_keg_file_records: usize,
+    pub stale_opt_links: Vec<StaleOptLink>,
+    pub broken_linkage: Vec<KegLinkage>,
+    /// Leftover work dirs under `prefix/tmp/build`.
+    pub stale_build_dirs: Vec<PathBuf>,
+    _lock: Option<File>,
 }
 
 #[derive(Debug)]
@@ -31,6 +46,24 @@ pub struct MissingKeg {
     pub expected_path: PathBuf,
 }
 
+/// An `opt/<name>` link that is missing or doesn't resolve to the active keg.
+#[derive(Debug)]
+pub struct StaleOptLink {
+    pub name: String,
+    pub version: String,
+    pub link_path: PathBuf,
+    /// Where the link currently points, `None` when it is missing.
+    pub current_target: Option<PathBuf>,
+}
+
+/// Binaries in an installed keg whose library references don't resolve.
+#[derive(Debug)]
+pub struct KegLinkage {
+    pub name: String,
+    pub version: String,
+    pub broken: Vec<BrokenLinkage>,
+}
+
 #[derive(Debug)]
 pub struct StaleStoreRef {
     pub store_key: String,
@@ -47,12 +80,28 @@ impl DiagnosticReport {
             && self.stale_store_refs.is_empty()
             && self.broken_symlinks.is_empty()
             && self.stale_keg_file_records == 0
+            && self.stale_opt_links.is_empty()
+            && self.broken_linkage.is_empty()
+            && self.stale_build_dirs.is_empty()
     }
 }
 
 impl Installer {
+    /// Diagnose the Cellar, store, database and prefix links.
+    ///
+    /// Takes the install lock, waiting for it or failing as
+    /// [`crate::InstallerBuilder::wait_for_lock`] says, and the report keeps
+    /// holding it.
     pub fn doctor(&mut self) -> Result<DiagnosticReport, Error> {
-        let mut report = DiagnosticReport::default();
+        // Before locking: holding the lock would make every build dir look
+        // like it belongs to a running install.
+        let stale_build_dirs = self.stale_build_dirs(STALE_BUILD_DIR_AGE)?;
+        let lock = self.install_lock()?;
+        let mut report = DiagnosticReport {
+            stale_build_dirs,
+            _lock: Some(lock),
+            ..DiagnosticReport::default()
+        };
 
         let installed = self.db.list_installed()?;
         let db_store_refs = self.db.list_store_refs()?;
@@ -114,7 +163,9 @@ impl Installer {
                 .unwrap_or(0);
             let on_disk = disk_store_set.contains(store_ref.store_key.as_str());
 
-            if store_ref.refcount != actual_count || !on_disk {
+            if store_ref.refcount != actual_count
+                || (!on_disk && is_store_backed(&store_ref.store_key))
+            {
                 report.stale_store_refs.push(StaleStoreRef {
                     store_key: store_ref.store_key.clone(),
                     refcount: store_ref.refcount,
@@ -155,16 +206,48 @@ impl Installer {
 
         report.stale_keg_file_records = self.db.count_stale_keg_file_records()?;
 
+        for keg in &installed {
+            let token = formula_token(&keg.name);
+            let keg_path = self.cellar.keg_path(token, &keg.version);
+            if !keg_path.exists() || self.linker.opt_link_is_current(&keg_path) {
+                continue;
+            }
+
+            let link_path = self.linker.opt_link_path(token);
+            report.stale_opt_links.push(StaleOptLink {
+                name: keg.name.clone(),
+                version: keg.version.clone(),
+                current_target: std::fs::read_link(&link_path).ok(),
+                link_path,
+            });
+        }
+
+        for keg in &installed {
+            let keg_path = self.cellar.keg_path(formula_token(&keg.name), &keg.version);
+            if !keg_path.exists() {
+                continue;
+            }
+            let broken = check_keg_linkage(&keg_path, &self.prefix);
+            if !broken.is_empty() {
+                report.broken_linkage.push(KegLinkage {
+                    name: keg.name.clone(),
+                    version: keg.version.clone(),
+                    broken,
+                });
+            }
+        }
+
         Ok(report)
     }
 
-    pub fn repair(&mut self, report: &DiagnosticReport) -> Result<RepairSummary, Error> {
+    /// Fix everything in `report`. The install lock the report holds is
+    /// released once the repair is done.
+    pub fn repair(&mut self, report: DiagnosticReport) -> Result<RepairSummary, Error> {
         let mut summary = RepairSummary::default();
 
-        for orphan in &report.orphaned_cellar_kegs {
-            self.linker.unlink_keg(&orphan.path).ok();
-            self.cellar.remove_keg(&orphan.name, &orphan.version)?;
-            summary.removed_orphaned_kegs += 1;
+        for orphan in newest_orphan_per_name(&report.orphaned_cellar_kegs) {
+            self.adopt_orphaned_keg(orphan)?;
+            summary.adopted_orphaned_kegs += 1;
         }
 
         for missing in &report.missing_cellar_kegs {
@@ -211,27 +294,217 @@ impl Installer {
             summary.pruned_keg_file_records = self.db.prune_stale_keg_file_records()?;
         }
 
+        for stale in &report.stale_opt_links {
+            let keg_path = self
+                .cellar
+                .keg_path(formula_token(&stale.name), &stale.version);
+            self.linker.link_opt(&keg_path)?;
+            summary.repointed_opt_links += 1;
+        }
+
+        for keg in &report.broken_linkage {
+            self.cellar
+                .relocate(formula_token(&keg.name), &keg.version)?;
+            summary.relocated_kegs += 1;
+        }
+
+        // No build can be running while the report holds the lock.
+        summary.removed_build_dirs = self.remove_build_dirs(&report.stale_build_dirs)?.removed;
+
         Ok(summary)
     }
+
+    /// Record a Cellar keg that has no database entry so it is tracked again.
+    /// The keg didn't come through the store, so it gets a synthetic key the
+    /// same way source builds do.
+    fn adopt_orphaned_keg(&mut self, orphan: &OrphanedKeg) -> Result<(), Error> {
+        let store_key = format!("adopted:{}:{}", orphan.name, orphan.version);
+        let tx = self.db.transaction()?;
+        tx.record_install(&orphan.name, &orphan.version, &store_key)?;
+        tx.commit()?;
+
+        if let Err(e) = self.linker.link_opt(&orphan.path) {
+            warn!(formula = %orphan.name, error = %e, "failed to create opt link for adopted keg");
+        }
+        Ok(())
+    }
+}
+
+/// Bottle installs are keyed by the blob's sha256 and must have a store
+/// entry; source builds and adopted kegs use `kind:name:version` keys that
+/// never do.
+fn is_store_backed(store_key: &str) -> bool {
+    !store_key.contains(':')
+}
+
+/// When a formula has several orphaned versions, only the most recently
+/// written one is adopted; the others stay on disk as older kegs.
+fn newest_orphan_per_name(orphans: &[OrphanedKeg]) -> Vec<&OrphanedKeg> {
+    let mut newest: BTreeMap<&str, (&OrphanedKeg, Option<SystemTime>)> = BTreeMap::new();
+    for orphan in orphans {
+        let modified = std::fs::metadata(&orphan.path)
+            .and_then(|m| m.modified())
+            .ok();
+        match newest.get(orphan.name.as_str()) {
+            Some((current, current_modified))
+                if (*current_modified, current.version.as_str())
+                    >= (modified, orphan.version.as_str()) => {}
+            _ => {
+                newest.insert(orphan.name.as_str(), (orphan, modified));
+            }
+        }
+    }
+    newest.into_values().map(|(orphan, _)| orphan).collect()
 }
 
 #[derive(Debug, Default)]
 pub struct RepairSummary {
-    pub removed_orphaned_kegs: usize,
+    pub adopted_orphaned_kegs: usize,
     pub removed_missing_records: usize,
     pub fixed_store_refs: usize,
     pub removed_orphaned_store_entries: usize,
     pub removed_broken_symlinks: usize,
     pub pruned_keg_file_records: usize,
+    pub repointed_opt_links: usize,
+    pub relocated_kegs: usize,
+    pub removed_build_dirs: usize,
 }
 
 impl RepairSummary {
     pub fn total_fixes(&self) -> usize {
-        self.removed_orphaned_kegs
+        self.adopted_orphaned_kegs
             + self.removed_missing_records
             + self.fixed_store_refs
             + self.removed_orphaned_store_entries
             + self.removed_broken_symlinks
             + self.pruned_keg_file_records
+            + self.repointed_opt_links
+            + self.relocated_kegs
+            + self.removed_build_dirs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use crate::network::api::ApiClient;
+
+    use super::super::test_support::test_installer;
+
+    #[test]
+    fn stale_opt_link_is_repointed_to_active_keg() {
+        let tmp = TempDir::new().unwrap();
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+
+        let old_keg = installer.keg_path("foo", "1.0.0");
+        let active_keg = installer.keg_path("foo", "2.0.0");
+        fs::create_dir_all(&active_keg).unwrap();
+
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("foo", "2.0.0", "fookey").unwrap();
+            tx.commit().unwrap();
+        }
+
+        let opt_link = installer.prefix.join("opt/foo");
+        std::os::unix::fs::symlink(&old_keg, &opt_link).unwrap();
+
+        let report = installer.doctor().unwrap();
+        assert_eq!(report.stale_opt_links.len(), 1);
+        assert_eq!(report.stale_opt_links[0].name, "foo");
+        assert_eq!(report.stale_opt_links[0].current_target, Some(old_keg));
+
+        let summary = installer.repair(report).unwrap();
+        assert_eq!(summary.repointed_opt_links, 1);
+        assert_eq!(fs::read_link(&opt_link).unwrap(), active_keg);
+        assert!(installer.doctor().unwrap().stale_opt_links.is_empty());
+    }
+
+    #[test]
+    fn db_and_cellar_mismatches_are_detected_and_reconciled() {
+        let tmp = TempDir::new().unwrap();
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+
+        // A db record whose keg directory is gone...
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("gone", "1.0.0", "gonekey").unwrap();
+            tx.commit().unwrap();
+        }
+        // ...and a keg directory with no db record.
+        let stray = installer.keg_path("stray", "2.1.0");
+        fs::create_dir_all(stray.join("bin")).unwrap();
+        fs::write(stray.join("bin/stray"), "#!/bin/sh\n").unwrap();
+
+        let report = installer.doctor().unwrap();
+        assert_eq!(report.missing_cellar_kegs.len(), 1);
+        assert_eq!(report.missing_cellar_kegs[0].name, "gone");
+        assert_eq!(report.orphaned_cellar_kegs.len(), 1);
+        assert_eq!(report.orphaned_cellar_kegs[0].name, "stray");
+        assert_eq!(report.orphaned_cellar_kegs[0].version, "2.1.0");
+
+        let summary = installer.repair(report).unwrap();
+        assert_eq!(summary.removed_missing_records, 1);
+        assert_eq!(summary.adopted_orphaned_kegs, 1);
+
+        assert!(installer.get_installed("gone").is_none());
+        let adopted = installer.get_installed("stray").unwrap();
+        assert_eq!(adopted.version, "2.1.0");
+        assert!(stray.join("bin/stray").exists());
+        assert_eq!(
+            fs::read_link(installer.prefix.join("opt/stray")).unwrap(),
+            stray
+        );
+
+        let after = installer.doctor().unwrap();
+        assert!(after.missing_cellar_kegs.is_empty());
+        assert!(after.orphaned_cellar_kegs.is_empty());
+        assert!(after.stale_store_refs.is_empty());
+        assert!(after.stale_opt_links.is_empty());
+    }
+
+    #[test]
+    fn missing_opt_link_is_recreated() {
+        let tmp = TempDir::new().unwrap();
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+
+        let keg = installer.keg_path("bar", "1.0.0");
+        fs::create_dir_all(&keg).unwrap();
+
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("bar", "1.0.0", "barkey").unwrap();
+            tx.commit().unwrap();
+        }
+
+        let report = installer.doctor().unwrap();
+        assert_eq!(report.stale_opt_links.len(), 1);
+        assert_eq!(report.stale_opt_links[0].current_target, None);
+
+        installer.repair(report).unwrap();
+        assert_eq!(
+            fs::read_link(installer.prefix.join("opt/bar")).unwrap(),
+            keg
+        );
+    }
+
+    #[test]
+    fn report_holds_the_install_lock_until_repaired() {
+        let tmp = TempDir::new().unwrap();
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+        installer.wait_for_lock = false;
+
+        let report = installer.doctor().unwrap();
+        assert!(installer.install_lock().is_err());
+        assert!(matches!(
+            installer.uninstall("foo"),
+            Err(zb_core::Error::ExecutionError { .. })
+        ));
+
+        installer.repair(report).unwrap();
+        assert!(installer.install_lock().is_ok());
     }
 }
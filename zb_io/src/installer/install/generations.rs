@@ -0,0 +1,266 @@
+use tracing::warn;
+
+use zb_core::{Error, formula_token};
+
+use crate::storage::db::{Generation, GenerationKeg};
+
+use super::Installer;
+
+/// What [`Installer::rollback_generation`] changed to reach a generation.
+#[derive(Debug, Clone, Default)]
+pub struct GenerationSwitch {
+    pub generation: i64,
+    /// Kegs made the installed version, whether new or a different version.
+    pub activated: Vec<GenerationKeg>,
+    /// Installed kegs the generation doesn't have at that version.
+    pub deactivated: Vec<GenerationKeg>,
+    /// Kegs kept at the same version but linked or unlinked.
+    pub relinked: Vec<GenerationKeg>,
+}
+
+impl Installer {
+    pub fn list_generations(&self) -> Result<Vec<Generation>, Error> {
+        self.db.list_generations()
+    }
+
+    pub fn generation_kegs(&self, id: i64) -> Result<Vec<GenerationKeg>, Error> {
+        self.db.generation_kegs(id)
+    }
+
+    /// Record the installed formulas and which of them are linked as a new
+    /// generation, unless they match the current one. Casks are left out:
+    /// their artifacts live outside the prefix and can't be swapped back.
+    ///
+    /// Runs after an operation has already changed the prefix, so a failure
+    /// is logged rather than failing that operation.
+    pub(crate) fn record_generation(&self, operation: &str) {
+        let result = self.generation_snapshot().and_then(|kegs| {
+            let current = self.db.list_generations()?.into_iter().find(|g| g.current);
+            if let Some(current) = current
+                && self.db.generation_kegs(current.id)? == kegs
+            {
+                return Ok(());
+            }
+            self.db.record_generation(operation, &kegs).map(|_| ())
+        });
+        if let Err(e) = result {
+            warn!(operation, error = %e, "failed to record generation");
+        }
+    }
+
+    /// The formulas installed right now, ordered by name like
+    /// [`Database::generation_kegs`](crate::storage::db::Database::generation_kegs).
+    fn generation_snapshot(&self) -> Result<Vec<GenerationKeg>, Error> {
+        Ok(self
+            .db
+            .list_installed()?
+            .into_iter()
+            .filter(|keg| !keg.name.starts_with("cask:"))
+            .map(|keg| {
+                let keg_path = self.cellar.keg_path(formula_token(&keg.name), &keg.version);
+                GenerationKeg {
+                    linked: self.linker.is_linked(&keg_path),
+                    name: keg.name,
+                    version: keg.version,
+                    store_key: keg.store_key,
+                }
+            })
+            .collect())
+    }
+
+    /// Swap the prefix back to generation `to`, or to the one before the
+    /// current generation.
+    ///
+    /// Kegs the generation doesn't have are deactivated but left in the
+    /// Cellar, like `upgrade --no-cleanup`, so rolling forward again is
+    /// cheap. A keg that has since been removed is materialized again from
+    /// its store entry; once `gc` has reclaimed that too, the rollback
+    /// fails before changing anything.
+    pub fn rollback_generation(&mut self, to: Option<i64>) -> Result<GenerationSwitch, Error> {
+        let _lock = self.install_lock()?;
+
+        let generations = self.db.list_generations()?;
+        let target =
+            match to {
+                Some(id) => generations.iter().find(|g| g.id == id).ok_or_else(|| {
+                    Error::ExecutionError {
+                        message: format!("no generation {id}; run `zb generations` to list them"),
+                    }
+                })?,
+                None => {
+                    let current = generations.iter().find(|g| g.current).map(|g| g.id);
+                    generations
+                        .iter()
+                        .rev()
+                        .find(|g| current.is_some_and(|current| g.id < current))
+                        .ok_or_else(|| Error::ExecutionError {
+                            message: "no earlier generation to roll back to".to_string(),
+                        })?
+                }
+            };
+
+        let wanted = self.db.generation_kegs(target.id)?;
+        let installed = self.generation_snapshot()?;
+        let installed_version = |name: &str| {
+            installed
+                .iter()
+                .find(|keg| keg.name == name)
+                .map(|keg| keg.version.as_str())
+        };
+
+        for keg in &wanted {
+            if installed_version(&keg.name) == Some(keg.version.as_str()) {
+                continue;
+            }
+            if !self.cellar.has_keg(formula_token(&keg.name), &keg.version)
+                && !self.has_populated_store_entry(&keg.store_key)
+            {
+                return Err(Error::ExecutionError {
+                    message: format!(
+                        "{} {} is no longer in the Cellar or the store; it may have been removed by `zb gc`",
+                        keg.name, keg.version
+                    ),
+                });
+            }
+        }
+
+        let mut switch = GenerationSwitch {
+            generation: target.id,
+            ..GenerationSwitch::default()
+        };
+
+        for keg in &installed {
+            let kept = wanted
+                .iter()
+                .any(|w| w.name == keg.name && w.version == keg.version);
+            if !kept {
+                self.deactivate_version(&keg.name, &keg.version)?;
+                switch.deactivated.push(keg.clone());
+            }
+        }
+
+        for keg in wanted {
+            let token = formula_token(&keg.name);
+            let keg_path = self.cellar.keg_path(token, &keg.version);
+            match installed.iter().find(|i| i.name == keg.name) {
+                Some(current) if current.version == keg.version => {
+                    if current.linked == keg.linked {
+                        continue;
+                    }
+                    if keg.linked {
+                        let linked_files = self
+                            .linker
+                            .link_keg_with(&keg_path, self.link_policy_for(&keg.name))?;
+                        self.record_linked_files(&keg.name, &keg.version, &linked_files);
+                    } else {
+                        self.linker.unlink_keg_files(&keg_path)?;
+                        let tx = self.db.transaction()?;
+                        tx.clear_keg_file_records(&keg.name)?;
+                        tx.commit()?;
+                    }
+                    switch.relinked.push(keg);
+                }
+                _ => {
+                    if !self.cellar.has_keg(token, &keg.version) {
+                        let entry = self.store.entry_path(&keg.store_key);
+                        self.cellar.materialize(token, &keg.version, &entry)?;
+                    }
+                    self.activate_version(&keg.name, &keg.version, &keg.store_key, keg.linked)?;
+                    switch.activated.push(keg);
+                }
+            }
+        }
+
+        self.db.set_current_generation(target.id)?;
+        Ok(switch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use crate::Installer;
+    use crate::network::api::ApiClient;
+
+    use super::super::test_support::test_installer;
+
+    fn write_keg(installer: &Installer, name: &str, version: &str) {
+        let keg = installer.keg_path(name, version);
+        fs::create_dir_all(keg.join("bin")).unwrap();
+        fs::write(keg.join("bin").join(name), version).unwrap();
+    }
+
+    #[test]
+    fn rollback_swaps_kegs_back_and_forward() {
+        let tmp = TempDir::new().unwrap();
+        let prefix = tmp.path().join("homebrew");
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+
+        write_keg(&installer, "foo", "1.0.0");
+        installer
+            .activate_version("foo", "1.0.0", "foo1", true)
+            .unwrap();
+        installer.record_generation("install");
+        // Nothing changed, so no new generation.
+        installer.record_generation("install");
+        let first = installer.list_generations().unwrap()[0].id;
+
+        installer.deactivate_version("foo", "1.0.0").unwrap();
+        write_keg(&installer, "foo", "2.0.0");
+        installer
+            .activate_version("foo", "2.0.0", "foo2", true)
+            .unwrap();
+        write_keg(&installer, "bar", "1.0.0");
+        installer
+            .activate_version("bar", "1.0.0", "bar1", false)
+            .unwrap();
+        installer.record_generation("upgrade");
+        assert_eq!(installer.list_generations().unwrap().len(), 2);
+        assert_eq!(fs::read(prefix.join("bin/foo")).unwrap(), b"2.0.0");
+
+        let switch = installer.rollback_generation(None).unwrap();
+        assert_eq!(switch.generation, first);
+        assert_eq!(switch.activated.len(), 1);
+        assert_eq!(switch.deactivated.len(), 2);
+        assert_eq!(installer.get_installed("foo").unwrap().version, "1.0.0");
+        assert!(!installer.is_installed("bar"));
+        assert_eq!(fs::read(prefix.join("bin/foo")).unwrap(), b"1.0.0");
+
+        let err = installer.rollback_generation(None).unwrap_err();
+        assert!(
+            err.to_string().contains("no earlier generation"),
+            "got: {err}"
+        );
+
+        let latest = installer.list_generations().unwrap()[1].id;
+        installer.rollback_generation(Some(latest)).unwrap();
+        assert_eq!(installer.get_installed("foo").unwrap().version, "2.0.0");
+        assert!(installer.is_installed("bar"));
+        assert!(!prefix.join("bin/bar").exists());
+        assert!(installer.rollback_generation(Some(latest + 1)).is_err());
+    }
+
+    #[test]
+    fn rollback_refuses_kegs_gone_from_cellar_and_store() {
+        let tmp = TempDir::new().unwrap();
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+
+        write_keg(&installer, "foo", "1.0.0");
+        installer
+            .activate_version("foo", "1.0.0", "foo1", true)
+            .unwrap();
+        installer.record_generation("install");
+        installer.uninstall("foo").unwrap();
+
+        let err = installer.rollback_generation(None).unwrap_err();
+        assert!(
+            err.to_string().contains("no longer in the Cellar"),
+            "got: {err}"
+        );
+        let generations = installer.list_generations().unwrap();
+        assert!(generations[1].current);
+    }
+}
@@ -0,0 +1,160 @@
+use zb_core::{BuildPlan, Error, Formula, InstallMethod, head_version};
+
+use super::{InstallPlan, Installer};
+use crate::build::source::resolve_head_commit;
+
+impl Installer {
+    /// Plan `names` as `--HEAD` builds of whatever commit their head branch
+    /// points at now. Dependencies are planned as usual.
+    pub async fn plan_head(
+        &self,
+        names: &[String],
+        build_from_source: bool,
+    ) -> Result<InstallPlan, Error> {
+        let mut plan = self.plan_with_options(names, build_from_source).await?;
+        for item in plan.items.iter_mut().filter(|item| item.requested) {
+            let commit = self.head_commit(&item.formula).await?;
+            let build_plan = BuildPlan::for_head(&item.formula, &self.prefix, &commit)
+                .ok_or_else(|| no_head(&item.formula))?;
+            item.method = InstallMethod::Source(Box::new(build_plan));
+        }
+        Ok(plan)
+    }
+
+    /// The version a `--HEAD` install of `formula` would get right now, for
+    /// telling whether a HEAD keg is behind its branch.
+    pub(super) async fn current_head_version(&self, formula: &Formula) -> Result<String, Error> {
+        Ok(head_version(&self.head_commit(formula).await?))
+    }
+
+    async fn head_commit(&self, formula: &Formula) -> Result<String, Error> {
+        let head = formula.head_url().ok_or_else(|| no_head(formula))?;
+        resolve_head_commit(&head.url, head.branch.as_deref()).await
+    }
+}
+
+fn no_head(formula: &Formula) -> Error {
+    Error::UnsupportedFormula {
+        name: formula.name.clone(),
+        reason: "no git HEAD repository to build from".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process::Command;
+
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::cellar::Cellar;
+    use crate::installer::install::test_support::*;
+    use crate::network::api::ApiClient;
+    use crate::storage::blob::BlobCache;
+    use crate::storage::db::Database;
+    use crate::storage::store::Store;
+    use crate::{Installer, Linker};
+
+    use super::*;
+
+    fn git(dir: &std::path::Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .current_dir(dir)
+            .args(["-c", "user.name=zb", "-c", "user.email=zb@example.com"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    #[tokio::test]
+    async fn head_plans_follow_the_branch_tip() {
+        if Command::new("git").arg("--version").output().is_err() {
+            eprintln!("Skipping HEAD plan test: git not found");
+            return;
+        }
+
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("widget.git");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "--quiet", "--initial-branch", "main"]);
+        git(
+            &repo,
+            &["commit", "--quiet", "--allow-empty", "-m", "first"],
+        );
+        let first = git(&repo, &["rev-parse", "HEAD"]);
+
+        let tag = get_test_bottle_tag();
+        Mock::given(method("GET"))
+            .and(path("/formula/widget.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!(
+                r#"{{
+                    "name": "widget",
+                    "versions": {{ "stable": "1.0.0" }},
+                    "dependencies": [],
+                    "urls": {{ "head": {{ "url": "{repo}", "branch": "main" }} }},
+                    "bottle": {{
+                        "stable": {{
+                            "files": {{
+                                "{tag}": {{
+                                    "url": "{uri}/bottles/widget-1.0.0.{tag}.bottle.tar.gz",
+                                    "sha256": "{sha}"
+                                }}
+                            }}
+                        }}
+                    }}
+                }}"#,
+                repo = repo.display(),
+                uri = mock_server.uri(),
+                sha = "a".repeat(64),
+            )))
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        fs::create_dir_all(root.join("db")).unwrap();
+        let mut installer = Installer::new(
+            ApiClient::with_base_url(format!("{}/formula", mock_server.uri())).unwrap(),
+            BlobCache::new(&root.join("cache")).unwrap(),
+            Store::new(&root).unwrap(),
+            Cellar::new(&root).unwrap(),
+            Linker::new(&prefix).unwrap(),
+            Database::open(&root.join("db/zb.sqlite3")).unwrap(),
+            prefix.clone(),
+            root.join("locks"),
+        );
+
+        let plan = installer
+            .plan_head(&["widget".to_string()], false)
+            .await
+            .unwrap();
+        let InstallMethod::Source(build_plan) = &plan.items[0].method else {
+            panic!("expected a HEAD source build");
+        };
+        assert_eq!(build_plan.head.as_ref().unwrap().commit, first);
+        assert_eq!(plan.items[0].version(), head_version(&first));
+
+        {
+            let tx = installer.db.transaction().unwrap();
+            let version = head_version(&first);
+            tx.record_install("widget", &version, &format!("source:widget:{version}"))
+                .unwrap();
+            tx.commit().unwrap();
+        }
+        assert!(installer.is_outdated("widget").await.unwrap().is_none());
+
+        git(
+            &repo,
+            &["commit", "--quiet", "--allow-empty", "-m", "second"],
+        );
+        let second = git(&repo, &["rev-parse", "HEAD"]);
+        let outdated = installer.is_outdated("widget").await.unwrap().unwrap();
+        assert!(outdated.is_source_build);
+        assert_eq!(outdated.current_version, head_version(&second));
+    }
+}
@@ -0,0 +1,462 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use zb_core::{Error, formula_token};
+
+use super::{Installer, try_acquire_install_lock};
+
+/// How far one keg's install got. Each step is recorded before it is taken,
+/// so undoing a failed or interrupted install only ever undoes too much of
+/// a step that never happened, which is harmless.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct InstallJournal {
+    name: String,
+    version: String,
+    keg: PathBuf,
+    /// The keg directory didn't exist before, so rolling back removes it.
+    creates_keg: bool,
+    records_install: bool,
+    links: bool,
+    /// The version an upgrade is replacing, deactivated before the new keg
+    /// is installed. Rolling back makes it the installed version again.
+    #[serde(default)]
+    replaces: Option<ReplacedKeg>,
+}
+
+/// What an upgrade needs to put back the version it replaces.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub(super) struct ReplacedKeg {
+    pub(super) version: String,
+    pub(super) store_key: String,
+    /// Whether its files were linked into the prefix.
+    pub(super) linked: bool,
+    /// Where a rebuild at the same version moved the keg aside.
+    #[serde(default)]
+    pub(super) backup: Option<PathBuf>,
+}
+
+/// An in-progress install's journal file. Dropping it without
+/// [`Self::commit`] leaves the file for the next install to roll back.
+pub(super) struct JournalEntry {
+    path: PathBuf,
+    journal: InstallJournal,
+}
+
+impl JournalEntry {
+    pub(super) fn recording_install(&mut self) -> Result<(), Error> {
+        self.journal.records_install = true;
+        self.save()
+    }
+
+    pub(super) fn linking(&mut self) -> Result<(), Error> {
+        self.journal.links = true;
+        self.save()
+    }
+
+    /// The install is complete and nothing needs undoing.
+    pub(super) fn commit(&self) -> Result<(), Error> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(Error::store("failed to remove install journal")(e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Write to a temporary file and rename it into place, so a crash never
+    /// leaves a half-written journal.
+    fn save(&self) -> Result<(), Error> {
+        let contents = serde_json::to_vec(&self.journal)
+            .map_err(Error::store("failed to serialize install journal"))?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, contents).map_err(Error::store("failed to write install journal"))?;
+        fs::rename(&tmp, &self.path).map_err(Error::store("failed to write install journal"))
+    }
+}
+
+impl Installer {
+    fn journal_dir(&self) -> PathBuf {
+        self.locks_dir.join("journal")
+    }
+
+    /// Start journaling an upgrade of `name` before `replaced` is
+    /// deactivated, so a crash at any point puts `replaced` back.
+    ///
+    /// Until the returned entry is committed, installs of `name` carry
+    /// `replaced` in their own journal and the replacement's journal is
+    /// not mistaken for one a crash left behind.
+    pub(super) fn begin_replace_journal(
+        &mut self,
+        name: &str,
+        replaced: ReplacedKeg,
+    ) -> Result<JournalEntry, Error> {
+        let keg = self.cellar.keg_path(formula_token(name), &replaced.version);
+        let entry = self.start_journal(InstallJournal {
+            name: name.to_string(),
+            version: replaced.version.clone(),
+            keg,
+            creates_keg: false,
+            records_install: false,
+            links: false,
+            replaces: Some(replaced.clone()),
+        })?;
+        self.replacing.insert(name.to_string(), replaced);
+        Ok(entry)
+    }
+
+    /// Note in the upgrade `entry` journals that the replaced keg is about to
+    /// be moved aside to `backup`.
+    pub(super) fn journal_replaced_backup(
+        &mut self,
+        entry: &mut JournalEntry,
+        backup: &Path,
+    ) -> Result<(), Error> {
+        let Some(replaced) = &mut entry.journal.replaces else {
+            return Ok(());
+        };
+        replaced.backup = Some(backup.to_path_buf());
+        self.replacing
+            .insert(entry.journal.name.clone(), replaced.clone());
+        entry.save()
+    }
+
+    /// The upgrade `entry` journals is over, one way or the other.
+    pub(super) fn finish_replace_journal(&mut self, entry: JournalEntry) -> Result<(), Error> {
+        self.replacing.remove(&entry.journal.name);
+        entry.commit()
+    }
+
+    /// Write `journal` to its file, starting the entry.
+    fn start_journal(&self, journal: InstallJournal) -> Result<JournalEntry, Error> {
+        let dir = self.journal_dir();
+        fs::create_dir_all(&dir).map_err(Error::store("failed to create journal directory"))?;
+
+        let entry = JournalEntry {
+            path: dir.join(format!("{}.json", journal.name.replace('/', "--"))),
+            journal,
+        };
+        entry.save()?;
+        Ok(entry)
+    }
+
+    /// Start journaling the install of `version` of `name` into `keg`.
+    pub(super) fn begin_install_journal(
+        &self,
+        name: &str,
+        version: &str,
+        keg: &Path,
+    ) -> Result<JournalEntry, Error> {
+        self.start_journal(InstallJournal {
+            name: name.to_string(),
+            version: version.to_string(),
+            keg: keg.to_path_buf(),
+            creates_keg: !keg.exists(),
+            records_install: false,
+            links: false,
+            replaces: self.replacing.get(name).cloned(),
+        })
+    }
+
+    /// Undo a failed install and hand back the error that failed it.
+    pub(super) fn abort_install(&mut self, entry: JournalEntry, error: Error) -> Error {
+        self.roll_back(&entry.journal);
+        if let Err(e) = entry.commit() {
+            warn!(formula = %entry.journal.name, error = %e, "failed to remove install journal");
+        }
+        error
+    }
+
+    /// Undo whatever an install journal says was started, in reverse order.
+    /// Each step is best effort, so one failure doesn't stop the others.
+    fn roll_back(&mut self, journal: &InstallJournal) {
+        let name = &journal.name;
+        debug!(formula = %name, version = %journal.version, "rolling back install");
+
+        if (journal.links || journal.records_install)
+            && journal.keg.exists()
+            && let Err(e) = self.linker.unlink_keg(&journal.keg)
+        {
+            warn!(formula = %name, error = %e, "failed to remove links while rolling back");
+        }
+
+        if journal.records_install {
+            let removed = self.db.transaction().and_then(|tx| {
+                tx.record_uninstall(name)?;
+                tx.commit()
+            });
+            if let Err(e) = removed {
+                warn!(formula = %name, error = %e, "failed to remove install record while rolling back");
+            }
+        }
+
+        if journal.creates_keg && journal.keg.exists() {
+            match fs::remove_dir_all(&journal.keg) {
+                Ok(()) => {
+                    if let Some(parent) = journal.keg.parent() {
+                        let _ = fs::remove_dir(parent);
+                    }
+                }
+                Err(e) => {
+                    warn!(formula = %name, error = %e, "failed to remove keg while rolling back")
+                }
+            }
+        }
+
+        if let Some(replaced) = &journal.replaces
+            && let Err(e) = self.restore_replaced_keg(name, replaced)
+        {
+            warn!(formula = %name, error = %e, "failed to restore previous version while rolling back");
+        }
+    }
+
+    /// Make `replaced` the installed version of `name` again, moving its
+    /// keg back from any backup, unless it already is or its keg is gone.
+    pub(super) fn restore_replaced_keg(
+        &mut self,
+        name: &str,
+        replaced: &ReplacedKeg,
+    ) -> Result<(), Error> {
+        let keg = self.cellar.keg_path(formula_token(name), &replaced.version);
+        if let Some(backup) = &replaced.backup
+            && backup.exists()
+        {
+            Self::restore_source_keg_from_backup(&keg, backup, name, &replaced.version)?;
+        }
+        let active = self.db.get_installed(name).is_some_and(|installed| {
+            installed.version == replaced.version && installed.store_key == replaced.store_key
+        }) && (!replaced.linked || self.linker.is_linked(&keg));
+        if active || !keg.exists() {
+            return Ok(());
+        }
+        self.activate_version(
+            name,
+            &replaced.version,
+            &replaced.store_key,
+            replaced.linked,
+        )
+    }
+
+    /// Roll back installs a crash interrupted, returning their names.
+    /// Callers must hold the install lock.
+    pub(super) fn roll_back_journals(&mut self) -> Result<Vec<String>, Error> {
+        let entries = match fs::read_dir(self.journal_dir()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::store("failed to read journal directory")(e)),
+        };
+
+        let mut rolled_back = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(Error::store("failed to read journal directory"))?
+                .path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                // A temporary file from a save that never completed.
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+
+            let journal = fs::read(&path)
+                .ok()
+                .and_then(|contents| serde_json::from_slice::<InstallJournal>(&contents).ok());
+            match journal {
+                // An upgrade of ours still in progress, not a crash.
+                Some(journal) if self.replacing.contains_key(&journal.name) => continue,
+                Some(journal) => {
+                    self.roll_back(&journal);
+                    rolled_back.push(journal.name);
+                }
+                None => warn!(path = %path.display(), "discarding unreadable install journal"),
+            }
+            fs::remove_file(&path).map_err(Error::store("failed to remove install journal"))?;
+        }
+
+        rolled_back.sort();
+        Ok(rolled_back)
+    }
+
+    /// Undo installs left half-done by a crash or kill, returning their
+    /// names. Does nothing while another zerobrew process is installing.
+    pub fn recover_interrupted_installs(&mut self) -> Result<Vec<String>, Error> {
+        let Some(_lock) = try_acquire_install_lock(&self.locks_dir)? else {
+            return Ok(Vec::new());
+        };
+        self.roll_back_journals()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::installer::install::test_support::*;
+    use crate::network::api::ApiClient;
+    use crate::network::cache::ApiCache;
+
+    use super::ReplacedKeg;
+
+    #[tokio::test]
+    async fn link_conflict_rolls_back_the_keg_and_record() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        let bottle = create_bottle_tarball("clashpkg");
+        let bottle_sha = sha256_hex(&bottle);
+        let tag = get_test_bottle_tag();
+        let formula_json = formula_json_with_bottle(
+            "clashpkg",
+            "1.0.0",
+            &[],
+            &format!("{}/bottles", mock_server.uri()),
+            &bottle_sha,
+        );
+        Mock::given(method("GET"))
+            .and(path("/formula/clashpkg.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(&formula_json))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/clashpkg-1.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle))
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        let mut installer = test_installer(
+            tmp.path(),
+            mock_api_client(&mock_server).with_cache(ApiCache::in_memory().unwrap()),
+        );
+
+        // Something zerobrew doesn't own is already where the link would go.
+        fs::create_dir_all(prefix.join("bin")).unwrap();
+        fs::write(prefix.join("bin/clashpkg"), "not ours").unwrap();
+
+        let err = installer
+            .install(&["clashpkg".to_string()], true)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, zb_core::Error::LinkConflict { .. }), "{err}");
+
+        assert!(installer.db.get_installed("clashpkg").is_none());
+        assert!(!root.join("cellar/clashpkg/1.0.0").exists());
+        assert!(!prefix.join("opt/clashpkg").exists());
+        assert_eq!(
+            fs::read_to_string(prefix.join("bin/clashpkg")).unwrap(),
+            "not ours"
+        );
+        assert!(installer.roll_back_journals().unwrap().is_empty());
+    }
+
+    #[test]
+    fn leftover_journal_is_rolled_back_on_recovery() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        let api_client = ApiClient::with_base_url("http://127.0.0.1:9/formula".to_string())
+            .unwrap()
+            .with_cache(ApiCache::in_memory().unwrap());
+        let mut installer = test_installer(tmp.path(), api_client);
+
+        // A crash mid-link: keg on disk, record written, one link made.
+        let keg = root.join("cellar/halfpkg/2.0.0");
+        let mut entry = installer
+            .begin_install_journal("halfpkg", "2.0.0", &keg)
+            .unwrap();
+        fs::create_dir_all(keg.join("bin")).unwrap();
+        fs::write(keg.join("bin/halfpkg"), "#!/bin/sh\n").unwrap();
+        entry.recording_install().unwrap();
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("halfpkg", "2.0.0", "key").unwrap();
+            tx.commit().unwrap();
+        }
+        entry.linking().unwrap();
+        fs::create_dir_all(prefix.join("bin")).unwrap();
+        std::os::unix::fs::symlink(keg.join("bin/halfpkg"), prefix.join("bin/halfpkg")).unwrap();
+        drop(entry);
+
+        assert_eq!(
+            installer.recover_interrupted_installs().unwrap(),
+            ["halfpkg"]
+        );
+        assert!(installer.db.get_installed("halfpkg").is_none());
+        assert!(!keg.exists());
+        assert!(fs::symlink_metadata(prefix.join("bin/halfpkg")).is_err());
+        assert!(installer.recover_interrupted_installs().unwrap().is_empty());
+    }
+
+    #[test]
+    fn journal_left_mid_upgrade_restores_the_replaced_version() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        let api_client = ApiClient::with_base_url("http://127.0.0.1:9/formula".to_string())
+            .unwrap()
+            .with_cache(ApiCache::in_memory().unwrap());
+        let mut installer = test_installer(tmp.path(), api_client);
+
+        let old_keg = root.join("cellar/midpkg/1.0.0");
+        fs::create_dir_all(old_keg.join("bin")).unwrap();
+        fs::write(old_keg.join("bin/midpkg"), "#!/bin/sh\n").unwrap();
+        installer
+            .activate_version("midpkg", "1.0.0", "oldkey", true)
+            .unwrap();
+
+        // A crash while upgrading to 2.0.0: the old version deactivated, the
+        // new keg extracted and recorded but not yet linked.
+        let replace = installer
+            .begin_replace_journal(
+                "midpkg",
+                ReplacedKeg {
+                    version: "1.0.0".to_string(),
+                    store_key: "oldkey".to_string(),
+                    linked: true,
+                    backup: None,
+                },
+            )
+            .unwrap();
+        installer.deactivate_version("midpkg", "1.0.0").unwrap();
+        let new_keg = root.join("cellar/midpkg/2.0.0");
+        let mut entry = installer
+            .begin_install_journal("midpkg", "2.0.0", &new_keg)
+            .unwrap();
+        fs::create_dir_all(new_keg.join("bin")).unwrap();
+        fs::write(new_keg.join("bin/midpkg"), "#!/bin/sh\n").unwrap();
+        entry.recording_install().unwrap();
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("midpkg", "2.0.0", "newkey").unwrap();
+            tx.commit().unwrap();
+        }
+        drop(entry);
+        drop(replace);
+        // The next run is a new process, with no upgrade in progress.
+        installer.replacing.clear();
+
+        assert_eq!(
+            installer.recover_interrupted_installs().unwrap(),
+            ["midpkg"]
+        );
+        let installed = installer.db.get_installed("midpkg").unwrap();
+        assert_eq!(installed.version, "1.0.0");
+        assert_eq!(installed.store_key, "oldkey");
+        assert!(!new_keg.exists());
+        assert!(
+            fs::read_link(prefix.join("bin/midpkg"))
+                .unwrap()
+                .starts_with(&old_keg)
+        );
+        assert!(
+            fs::read_link(prefix.join("opt/midpkg"))
+                .unwrap()
+                .ends_with("midpkg/1.0.0")
+        );
+    }
+}
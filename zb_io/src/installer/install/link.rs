@@ -0,0 +1,247 @@
+use std::path::{Path, PathBuf};
+
+use zb_core::{ConflictedLink, Error, formula_token};
+
+use crate::cellar::link::{LinkConflictPolicy, LinkedFile};
+
+use super::Installer;
+
+/// What to do about a formula whose links collide with files already in
+/// the prefix, as answered by a [`LinkConflictResolver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkConflictDecision {
+    /// Leave the existing files alone and link the rest.
+    Skip,
+    /// Replace the existing files this once.
+    Overwrite,
+    /// Replace them, now and whenever this formula is linked again.
+    AlwaysOverwrite,
+    /// Fail the install, as without a resolver.
+    Abort,
+}
+
+/// Asked about each install whose links collide with existing files, with
+/// the formula's name and every conflict.
+pub type LinkConflictResolver =
+    Box<dyn Fn(&str, &[ConflictedLink]) -> LinkConflictDecision + Send + Sync>;
+
+impl Installer {
+    /// Ask `resolver` what to do when an install's links collide with
+    /// existing files and no policy says otherwise.
+    pub fn set_link_conflict_resolver(&mut self, resolver: Option<LinkConflictResolver>) {
+        self.link_conflict_resolver = resolver;
+    }
+
+    /// How to handle conflicts when linking `name`: the policy set for this
+    /// run, else whatever was chosen for `name` before.
+    pub(super) fn link_policy_for(&self, name: &str) -> LinkConflictPolicy {
+        if self.link_conflict_policy != LinkConflictPolicy::Fail {
+            return self.link_conflict_policy;
+        }
+        self.db
+            .link_conflict_policy(name)
+            .and_then(|policy| LinkConflictPolicy::parse(&policy))
+            .unwrap_or_default()
+    }
+
+    /// Link a keg being installed, consulting the resolver about conflicts.
+    /// Skipping and always overwriting are remembered for later relinks.
+    pub(super) fn link_new_keg(
+        &self,
+        name: &str,
+        keg_path: &Path,
+    ) -> Result<Vec<LinkedFile>, Error> {
+        let policy = self.link_policy_for(name);
+        if policy != LinkConflictPolicy::Fail {
+            return self.linker.link_keg_with(keg_path, policy);
+        }
+        let Some(resolver) = &self.link_conflict_resolver else {
+            return self.linker.link_keg(keg_path);
+        };
+        let conflicts = self.linker.find_conflicts(keg_path);
+        if conflicts.is_empty() {
+            return self.linker.link_keg(keg_path);
+        }
+
+        let (policy, remember) = match resolver(name, &conflicts) {
+            LinkConflictDecision::Abort => return Err(Error::LinkConflict { conflicts }),
+            LinkConflictDecision::Skip => (LinkConflictPolicy::Skip, true),
+            LinkConflictDecision::Overwrite => (LinkConflictPolicy::Overwrite, false),
+            LinkConflictDecision::AlwaysOverwrite => (LinkConflictPolicy::Overwrite, true),
+        };
+        if remember {
+            self.db.record_link_conflict_policy(name, policy.as_str())?;
+        }
+        self.linker.link_keg_with(keg_path, policy)
+    }
+
+    /// Link an installed keg into the prefix. Keg-only formulas are refused
+    /// unless `force` is set; with `overwrite`, files and other kegs' links
+    /// in the way are replaced instead of failing the link.
+    pub async fn link(
+        &mut self,
+        name: &str,
+        force: bool,
+        overwrite: bool,
+    ) -> Result<Vec<LinkedFile>, Error> {
+        let _lock = self.install_lock()?;
+        let (version, keg_path) = self.linkable_keg(name, force).await?;
+        let policy = if overwrite {
+            LinkConflictPolicy::Overwrite
+        } else {
+            self.link_policy_for(name)
+        };
+        let linked = self.linker.link_keg_with(&keg_path, policy)?;
+        self.record_linked_files(name, &version, &linked);
+        Ok(linked)
+    }
+
+    /// The prefix paths `link(name, force, true)` would replace.
+    pub async fn link_conflicts(
+        &self,
+        name: &str,
+        force: bool,
+    ) -> Result<Vec<ConflictedLink>, Error> {
+        let (_, keg_path) = self.linkable_keg(name, force).await?;
+        Ok(self.linker.find_conflicts(&keg_path))
+    }
+
+    /// Remove `name`'s links from the prefix, keeping the keg and its `opt/`
+    /// link. Returns how many links were removed.
+    pub fn unlink(&mut self, name: &str) -> Result<usize, Error> {
+        let _lock = self.install_lock()?;
+        let installed = self.db.get_installed(name).ok_or(Error::NotInstalled {
+            name: name.to_string(),
+        })?;
+        let keg_path = self
+            .cellar
+            .keg_path(formula_token(name), &installed.version);
+        let unlinked = self.linker.unlink_keg_files(&keg_path)?;
+
+        let tx = self.db.transaction()?;
+        tx.clear_keg_file_records(name)?;
+        tx.commit()?;
+
+        Ok(unlinked.len())
+    }
+
+    async fn linkable_keg(&self, name: &str, force: bool) -> Result<(String, PathBuf), Error> {
+        let installed = self.db.get_installed(name).ok_or(Error::NotInstalled {
+            name: name.to_string(),
+        })?;
+
+        if !force && !name.starts_with("cask:") {
+            let formula = self.api_client.get_formula(name).await?;
+            if formula.is_keg_only() {
+                return Err(Error::ExecutionError {
+                    message: format!(
+                        "{name} is keg-only; run `zb link --force {name}` to link it anyway"
+                    ),
+                });
+            }
+        }
+
+        let keg_path = self
+            .cellar
+            .keg_path(formula_token(name), &installed.version);
+        Ok((installed.version, keg_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::network::api::ApiClient;
+    use crate::{LinkConflictDecision, LinkConflictPolicy};
+
+    use super::super::test_support::{mock_api_client, test_installer};
+
+    #[tokio::test]
+    async fn keg_only_formula_links_only_when_forced_and_unlinks_cleanly() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let prefix = tmp.path().join("homebrew");
+
+        Mock::given(method("GET"))
+            .and(path("/formula/libpq.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{
+                    "name": "libpq",
+                    "versions": { "stable": "16.0" },
+                    "dependencies": [],
+                    "keg_only": "it conflicts with PostgreSQL",
+                    "bottle": { "stable": { "files": {} } }
+                }"#,
+            ))
+            .mount(&mock_server)
+            .await;
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        let keg = installer.keg_path("libpq", "16.0");
+        fs::create_dir_all(keg.join("bin")).unwrap();
+        fs::write(keg.join("bin/psql"), b"#!/bin/sh\n").unwrap();
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("libpq", "16.0", "libpqkey").unwrap();
+            tx.commit().unwrap();
+        }
+
+        let err = installer.link("libpq", false, false).await.unwrap_err();
+        assert!(err.to_string().contains("keg-only"), "got: {err}");
+        assert!(!prefix.join("bin/psql").exists());
+
+        fs::write(prefix.join("bin/psql"), b"from elsewhere").unwrap();
+        let conflicts = installer.link_conflicts("libpq", true).await.unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, prefix.join("bin/psql"));
+        assert!(installer.link("libpq", true, false).await.is_err());
+
+        let linked = installer.link("libpq", true, true).await.unwrap();
+        assert_eq!(linked.len(), 1);
+        assert_eq!(fs::read(prefix.join("bin/psql")).unwrap(), b"#!/bin/sh\n");
+
+        assert_eq!(installer.unlink("libpq").unwrap(), 1);
+        assert!(!prefix.join("bin/psql").exists());
+        assert!(prefix.join("opt/libpq").exists());
+        assert!(installer.is_installed("libpq"));
+    }
+
+    #[test]
+    fn resolver_decision_to_skip_is_remembered_for_relinks() {
+        let tmp = TempDir::new().unwrap();
+        let prefix = tmp.path().join("homebrew");
+
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+
+        let keg = installer.keg_path("tool", "1.0");
+        fs::create_dir_all(keg.join("bin")).unwrap();
+        fs::create_dir_all(keg.join("share/man/man1")).unwrap();
+        fs::write(keg.join("bin/tool"), b"tool").unwrap();
+        fs::write(keg.join("share/man/man1/tool.1"), b"ours").unwrap();
+        fs::create_dir_all(prefix.join("share/man/man1")).unwrap();
+        fs::write(prefix.join("share/man/man1/tool.1"), b"theirs").unwrap();
+
+        installer.set_link_conflict_resolver(Some(Box::new(|name, conflicts| {
+            assert_eq!(name, "tool");
+            assert_eq!(conflicts.len(), 1);
+            LinkConflictDecision::Skip
+        })));
+        let linked = installer.link_new_keg("tool", &keg).unwrap();
+
+        assert_eq!(linked.len(), 1);
+        assert!(prefix.join("bin/tool").exists());
+        assert_eq!(
+            fs::read(prefix.join("share/man/man1/tool.1")).unwrap(),
+            b"theirs"
+        );
+        installer.set_link_conflict_resolver(None);
+        assert_eq!(installer.link_policy_for("tool"), LinkConflictPolicy::Skip);
+        assert_eq!(installer.link_policy_for("other"), LinkConflictPolicy::Fail);
+    }
+}
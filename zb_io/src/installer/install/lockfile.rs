@@ -0,0 +1,554 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use zb_core::{Error, InstallMethod, SelectedBottle};
+
+use super::{InstallPlan, Installer, PlannedInstall};
+
+/// A pinned install set: every formula in the closure with the version,
+/// direct dependencies and bottles it resolved to when the lockfile was
+/// written.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub formulas: Vec<LockedFormula>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedFormula {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Every published bottle keyed by platform tag, so a lockfile written
+    /// on one machine still pins the bottle another machine would pick.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bottles: BTreeMap<String, LockedBottle>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedBottle {
+    pub url: String,
+    pub sha256: String,
+}
+
+impl Lockfile {
+    /// Pin every formula in `plan`, sorted by name.
+    pub fn from_plan(plan: &InstallPlan) -> Self {
+        let mut formulas: Vec<LockedFormula> = plan
+            .items
+            .iter()
+            .map(|item| LockedFormula {
+                name: item.install_name.clone(),
+                version: item.formula.effective_version(),
+                dependencies: item.formula.runtime_dependencies(),
+                bottles: item
+                    .formula
+                    .bottle
+                    .stable
+                    .files
+                    .iter()
+                    .map(|(tag, file)| {
+                        (
+                            tag.clone(),
+                            LockedBottle {
+                                url: file.url.clone(),
+                                sha256: file.sha256.clone(),
+                            },
+                        )
+                    })
+                    .collect(),
+            })
+            .collect();
+        formulas.sort_by(|a, b| a.name.cmp(&b.name));
+        Self { formulas }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let mut contents = serde_json::to_string_pretty(self).map_err(|e| Error::FileError {
+            message: format!("failed to serialize lockfile: {e}"),
+        })?;
+        contents.push('\n');
+        std::fs::write(path, contents).map_err(|e| Error::FileError {
+            message: format!("failed to write lockfile {}: {e}", path.display()),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path).map_err(|e| Error::FileError {
+            message: format!("failed to read lockfile {}: {e}", path.display()),
+        })?;
+        serde_json::from_str(&contents).map_err(|e| Error::FileError {
+            message: format!("failed to parse lockfile {}: {e}", path.display()),
+        })
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.formulas.iter().map(|f| f.name.clone()).collect()
+    }
+}
+
+/// One way the current resolution differs from a lockfile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockfileChange {
+    Added {
+        name: String,
+        version: String,
+    },
+    Removed {
+        name: String,
+        version: String,
+    },
+    VersionChanged {
+        name: String,
+        locked: String,
+        resolved: String,
+    },
+    DependenciesChanged {
+        name: String,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    /// The bottle this host would install is not the one pinned for its
+    /// tag, or the lockfile pins no bottle for that tag at all.
+    BottleChanged {
+        name: String,
+        tag: String,
+        locked: Option<String>,
+        resolved: String,
+    },
+}
+
+impl fmt::Display for LockfileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockfileChange::Added { name, version } => write!(f, "+ {name} {version}"),
+            LockfileChange::Removed { name, version } => write!(f, "- {name} {version}"),
+            LockfileChange::VersionChanged {
+                name,
+                locked,
+                resolved,
+            } => write!(f, "~ {name} {locked} -> {resolved}"),
+            LockfileChange::DependenciesChanged {
+                name,
+                added,
+                removed,
+            } => {
+                write!(f, "~ {name} dependencies:")?;
+                for dep in added {
+                    write!(f, " +{dep}")?;
+                }
+                for dep in removed {
+                    write!(f, " -{dep}")?;
+                }
+                Ok(())
+            }
+            LockfileChange::BottleChanged {
+                name,
+                tag,
+                locked: Some(locked),
+                resolved,
+            } => write!(f, "~ {name} {tag} bottle {locked} -> {resolved}"),
+            LockfileChange::BottleChanged {
+                name,
+                tag,
+                locked: None,
+                resolved,
+            } => write!(f, "+ {name} {tag} bottle {resolved}"),
+        }
+    }
+}
+
+/// Compare a lockfile against a freshly resolved plan, ordered by name.
+pub fn diff_lockfile(lockfile: &Lockfile, plan: &InstallPlan) -> Vec<LockfileChange> {
+    let locked: BTreeMap<&str, &LockedFormula> = lockfile
+        .formulas
+        .iter()
+        .map(|f| (f.name.as_str(), f))
+        .collect();
+    let resolved: BTreeMap<&str, (String, BTreeSet<String>, Option<&SelectedBottle>)> = plan
+        .items
+        .iter()
+        .map(|item| {
+            let bottle = match &item.method {
+                InstallMethod::Bottle(bottle) => Some(bottle),
+                InstallMethod::Source(_) => None,
+            };
+            (
+                item.install_name.as_str(),
+                (
+                    item.formula.effective_version(),
+                    item.formula.runtime_dependencies().into_iter().collect(),
+                    bottle,
+                ),
+            )
+        })
+        .collect();
+
+    let mut changes = Vec::new();
+    let names: BTreeSet<&str> = locked.keys().chain(resolved.keys()).copied().collect();
+    for name in names {
+        match (locked.get(name), resolved.get(name)) {
+            (None, Some((version, _, _))) => changes.push(LockfileChange::Added {
+                name: name.to_string(),
+                version: version.clone(),
+            }),
+            (Some(entry), None) => changes.push(LockfileChange::Removed {
+                name: name.to_string(),
+                version: entry.version.clone(),
+            }),
+            (Some(entry), Some((version, deps, bottle))) => {
+                if &entry.version != version {
+                    changes.push(LockfileChange::VersionChanged {
+                        name: name.to_string(),
+                        locked: entry.version.clone(),
+                        resolved: version.clone(),
+                    });
+                }
+
+                let locked_deps: BTreeSet<String> = entry.dependencies.iter().cloned().collect();
+                let added: Vec<String> = deps.difference(&locked_deps).cloned().collect();
+                let removed: Vec<String> = locked_deps.difference(deps).cloned().collect();
+                if !added.is_empty() || !removed.is_empty() {
+                    changes.push(LockfileChange::DependenciesChanged {
+                        name: name.to_string(),
+                        added,
+                        removed,
+                    });
+                }
+
+                // Lockfiles written before bottles were recorded pin none.
+                if let Some(bottle) = bottle
+                    && !entry.bottles.is_empty()
+                {
+                    let locked = entry.bottles.get(&bottle.tag).map(|b| &b.sha256);
+                    if locked != Some(&bottle.sha256) {
+                        changes.push(LockfileChange::BottleChanged {
+                            name: name.to_string(),
+                            tag: bottle.tag.clone(),
+                            locked: locked.cloned(),
+                            resolved: bottle.sha256.clone(),
+                        });
+                    }
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    changes
+}
+
+impl Installer {
+    /// Plan the install set pinned in `lockfile`.
+    ///
+    /// The locked formulas are resolved against current metadata and then
+    /// pinned back to the lockfile: only locked names are installed, at their
+    /// locked versions and with their locked bottles. Returns the plan
+    /// alongside any drift between the lockfile and current metadata. With
+    /// `frozen` set, any drift is an error whose message lists the changes.
+    pub async fn plan_from_lockfile(
+        &self,
+        lockfile: &Lockfile,
+        build_from_source: bool,
+        frozen: bool,
+    ) -> Result<(InstallPlan, Vec<LockfileChange>), Error> {
+        let resolved = self
+            .plan_with_options(&lockfile.names(), build_from_source)
+            .await?;
+        let changes = diff_lockfile(lockfile, &resolved);
+
+        if frozen && !changes.is_empty() {
+            let diff: Vec<String> = changes.iter().map(|c| format!("  {c}")).collect();
+            return Err(Error::ExecutionError {
+                message: format!(
+                    "lockfile is out of date with current formula metadata (--frozen):\n{}",
+                    diff.join("\n")
+                ),
+            });
+        }
+
+        Ok((pin_to_lockfile(lockfile, resolved)?, changes))
+    }
+}
+
+/// Narrow a plan resolved from `lockfile`'s names back to the locked set.
+///
+/// Dependencies upstream has added since are dropped, and each locked
+/// formula installs its locked version and bottle. A version that moved on
+/// can only be installed from the bottle the lockfile recorded for this
+/// host's tag; without one there is nothing to install it from.
+fn pin_to_lockfile(lockfile: &Lockfile, plan: InstallPlan) -> Result<InstallPlan, Error> {
+    let locked: BTreeMap<&str, &LockedFormula> = lockfile
+        .formulas
+        .iter()
+        .map(|f| (f.name.as_str(), f))
+        .collect();
+
+    let mut pinned = Vec::with_capacity(plan.items.len());
+    for mut item in plan.items {
+        // `:build` dependencies of source builds are never locked.
+        if item.build_only {
+            pinned.push(item);
+            continue;
+        }
+        let Some(entry) = locked.get(item.install_name.as_str()) else {
+            continue;
+        };
+
+        let locked_bottle = match &item.method {
+            InstallMethod::Bottle(bottle) => {
+                entry.bottles.get(&bottle.tag).map(|locked| SelectedBottle {
+                    tag: bottle.tag.clone(),
+                    url: locked.url.clone(),
+                    sha256: locked.sha256.clone(),
+                })
+            }
+            InstallMethod::Source(_) => None,
+        };
+
+        if item.formula.effective_version() != entry.version {
+            let Some(bottle) = locked_bottle else {
+                return Err(Error::ExecutionError {
+                    message: format!(
+                        "lockfile pins {} {}, but current metadata has {} and the lockfile \
+                         records no bottle for this host to install {} from",
+                        entry.name,
+                        entry.version,
+                        item.formula.effective_version(),
+                        entry.version
+                    ),
+                });
+            };
+            let (stable, revision) = split_revision(&entry.version);
+            item.formula.versions.stable = stable;
+            item.formula.revision = revision;
+            item.method = InstallMethod::Bottle(bottle);
+        } else if let Some(bottle) = locked_bottle {
+            item.method = InstallMethod::Bottle(bottle);
+        }
+
+        item.formula.dependencies = entry.dependencies.clone();
+        pinned.push(item);
+    }
+
+    Ok(InstallPlan {
+        items: order_by_locked_dependencies(pinned),
+    })
+}
+
+/// Split an effective version such as `1.2.3_1` into its stable version and
+/// revision.
+fn split_revision(version: &str) -> (String, u32) {
+    match version.rsplit_once('_') {
+        Some((stable, revision)) => match revision.parse() {
+            Ok(revision) => (stable.to_string(), revision),
+            Err(_) => (version.to_string(), 0),
+        },
+        None => (version.to_string(), 0),
+    }
+}
+
+/// Reorder `items` so each comes after the locked dependencies it has in
+/// the set, keeping the resolved order otherwise.
+fn order_by_locked_dependencies(mut items: Vec<PlannedInstall>) -> Vec<PlannedInstall> {
+    let mut ordered: Vec<PlannedInstall> = Vec::with_capacity(items.len());
+    while !items.is_empty() {
+        let ready = items
+            .iter()
+            .position(|item| {
+                item.formula
+                    .dependencies
+                    .iter()
+                    .all(|dep| !items.iter().any(|other| &other.install_name == dep))
+            })
+            // A cycle can't be ordered; leave the rest as resolved.
+            .unwrap_or(0);
+        ordered.push(items.remove(ready));
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::super::test_support::{formula_json_with_bottle, mock_api_client, test_installer};
+    use super::*;
+
+    async fn mount_formula(mock_server: &MockServer, name: &str, version: &str, deps: &[&str]) {
+        Mock::given(method("GET"))
+            .and(path(format!("/formula/{name}.json")))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    name,
+                    version,
+                    deps,
+                    "https://example.com",
+                    &"ab".repeat(32),
+                )),
+            )
+            .mount(mock_server)
+            .await;
+    }
+
+    fn locked(name: &str, version: &str, deps: &[&str]) -> LockedFormula {
+        LockedFormula {
+            name: name.to_string(),
+            version: version.to_string(),
+            dependencies: deps.iter().map(|d| d.to_string()).collect(),
+            bottles: BTreeMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn frozen_errors_when_upstream_adds_a_dependency() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        mount_formula(&mock_server, "foo", "1.0.0", &["bar"]).await;
+        mount_formula(&mock_server, "bar", "2.0.0", &[]).await;
+        let installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        let lockfile = Lockfile {
+            formulas: vec![locked("foo", "1.0.0", &[])],
+        };
+
+        let err = installer
+            .plan_from_lockfile(&lockfile, false, true)
+            .await
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("--frozen"), "{message}");
+        assert!(message.contains("+ bar 2.0.0"), "{message}");
+        assert!(message.contains("~ foo dependencies: +bar"), "{message}");
+
+        let (plan, changes) = installer
+            .plan_from_lockfile(&lockfile, false, false)
+            .await
+            .unwrap();
+        assert_eq!(plan.install_names(), vec!["foo"]);
+        assert!(plan.items[0].formula.runtime_dependencies().is_empty());
+        assert_eq!(
+            changes,
+            vec![
+                LockfileChange::Added {
+                    name: "bar".to_string(),
+                    version: "2.0.0".to_string(),
+                },
+                LockfileChange::DependenciesChanged {
+                    name: "foo".to_string(),
+                    added: vec!["bar".to_string()],
+                    removed: Vec::new(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn installs_the_locked_version_and_bottle_when_upstream_moves_on() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        mount_formula(&mock_server, "foo", "1.1.0", &[]).await;
+        let installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+        let tag = get_test_bottle_tag();
+
+        let mut entry = locked("foo", "1.0.0_2", &[]);
+        entry.bottles.insert(
+            tag.to_string(),
+            LockedBottle {
+                url: "https://example.com/foo-1.0.0_2.bottle.tar.gz".to_string(),
+                sha256: "cd".repeat(32),
+            },
+        );
+        let lockfile = Lockfile {
+            formulas: vec![entry],
+        };
+
+        let (plan, changes) = installer
+            .plan_from_lockfile(&lockfile, false, false)
+            .await
+            .unwrap();
+        assert_eq!(changes.len(), 2, "{changes:?}");
+        assert_eq!(plan.items[0].version(), "1.0.0_2");
+        let InstallMethod::Bottle(bottle) = &plan.items[0].method else {
+            panic!("expected a bottle");
+        };
+        assert_eq!(bottle.url, "https://example.com/foo-1.0.0_2.bottle.tar.gz");
+        assert_eq!(bottle.sha256, "cd".repeat(32));
+
+        let unpinned = Lockfile {
+            formulas: vec![locked("foo", "1.0.0_2", &[])],
+        };
+        let err = installer
+            .plan_from_lockfile(&unpinned, false, false)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("lockfile pins foo 1.0.0_2"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn frozen_accepts_matching_lockfile() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        mount_formula(&mock_server, "foo", "1.0.0", &["bar"]).await;
+        mount_formula(&mock_server, "bar", "2.0.0", &[]).await;
+        let installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        let lockfile = Lockfile {
+            formulas: vec![
+                locked("foo", "1.0.0", &["bar"]),
+                locked("bar", "2.0.0", &[]),
+            ],
+        };
+
+        let (plan, changes) = installer
+            .plan_from_lockfile(&lockfile, false, true)
+            .await
+            .unwrap();
+        assert_eq!(plan.items.len(), 2);
+        assert!(changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn generated_lockfile_pins_bottles_and_rejects_a_rebuilt_one() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        mount_formula(&mock_server, "foo", "1.0.0", &["bar"]).await;
+        mount_formula(&mock_server, "bar", "2.0.0", &[]).await;
+        let installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        let plan = installer.plan(&["foo".to_string()]).await.unwrap();
+        let path = tmp.path().join("Brewfile.lock.json");
+        Lockfile::from_plan(&plan).save(&path).unwrap();
+
+        let lockfile = Lockfile::load(&path).unwrap();
+        assert_eq!(lockfile.names(), vec!["bar", "foo"]);
+        let tag = get_test_bottle_tag();
+        assert_eq!(lockfile.formulas[1].bottles[tag].sha256, "ab".repeat(32));
+        let (_, changes) = installer
+            .plan_from_lockfile(&lockfile, false, true)
+            .await
+            .unwrap();
+        assert!(changes.is_empty());
+
+        let mut rebuilt = lockfile.clone();
+        rebuilt.formulas[0].bottles.get_mut(tag).unwrap().sha256 = "cd".repeat(32);
+        rebuilt.formulas[1].bottles.clear();
+        let err = installer
+            .plan_from_lockfile(&rebuilt, false, true)
+            .await
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains(&format!("~ bar {tag} bottle {}", "cd".repeat(32))),
+            "{message}"
+        );
+        assert!(!message.contains("~ foo"), "{message}");
+    }
+}
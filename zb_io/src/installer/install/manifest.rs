@@ -0,0 +1,215 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use zb_core::{Error, formula_token};
+
+use super::Installer;
+
+/// A snapshot of everything installed, in a form meant to be written out
+/// and compared against the same snapshot from another machine.
+///
+/// Timestamps and store keys are left out on purpose: they differ between
+/// machines holding the same environment.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Sorted by name. Casks keep their `cask:` prefix.
+    pub formulas: Vec<ManifestFormula>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestFormula {
+    pub name: String,
+    pub version: String,
+    pub reason: InstallReason,
+    #[serde(default)]
+    pub pinned: bool,
+    /// Whether the keg's files are linked into the prefix.
+    #[serde(default)]
+    pub linked: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallReason {
+    /// Installed by name with `zb install`.
+    Installed,
+    /// Pulled in as a runtime dependency of something installed by name.
+    Dependency,
+    /// Installed by `zb install --build-deps-only`; `gc --aggressive` may
+    /// remove it.
+    BuildDependency,
+}
+
+impl Manifest {
+    pub fn names(&self) -> Vec<&str> {
+        self.formulas.iter().map(|f| f.name.as_str()).collect()
+    }
+
+    /// The taps the installed formulas and casks come from, sorted.
+    pub fn taps(&self) -> Vec<&str> {
+        self.formulas
+            .iter()
+            .filter_map(ManifestFormula::tap)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+impl ManifestFormula {
+    /// The tap this comes from, e.g. `hashicorp/tap` for
+    /// `hashicorp/tap/terraform`. `None` for homebrew/core and
+    /// homebrew/cask, which are implied.
+    pub fn tap(&self) -> Option<&str> {
+        let name = self.name.strip_prefix("cask:").unwrap_or(&self.name);
+        let (tap, _) = name.rsplit_once('/')?;
+        (tap.matches('/').count() == 1).then_some(tap)
+    }
+}
+
+impl Installer {
+    pub fn export_manifest(&self) -> Result<Manifest, Error> {
+        let pinned = self.db.list_pinned()?;
+        let build_dependencies = self.db.list_build_dependencies()?;
+        let dependencies = self.db.list_dependencies()?;
+
+        let mut formulas: Vec<ManifestFormula> = self
+            .db
+            .list_installed()?
+            .into_iter()
+            .map(|keg| {
+                let keg_path = self.cellar.keg_path(formula_token(&keg.name), &keg.version);
+                ManifestFormula {
+                    reason: if build_dependencies.contains(&keg.name) {
+                        InstallReason::BuildDependency
+                    } else if dependencies.contains(&keg.name) {
+                        InstallReason::Dependency
+                    } else {
+                        InstallReason::Installed
+                    },
+                    pinned: pinned.contains(&keg.name),
+                    linked: self.linker.is_linked(&keg_path),
+                    name: keg.name,
+                    version: keg.version,
+                }
+            })
+            .collect();
+        formulas.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Manifest { formulas })
+    }
+
+    /// One-line descriptions for installed `names`, as `zb bundle dump
+    /// --describe` writes them. Core formulas come from the formula index;
+    /// tap formulas and casks are looked up one by one. Anything that
+    /// can't be described is left out rather than failing the dump.
+    pub async fn descriptions(&self, names: &[&str]) -> HashMap<String, String> {
+        let index = match self.api_client.formula_descriptions().await {
+            Ok(index) => Some(index),
+            Err(e) => {
+                warn!(error = %e, "formula index unavailable for descriptions");
+                None
+            }
+        };
+
+        let mut descriptions = HashMap::new();
+        for name in names {
+            let desc = if let Some(token) = name.strip_prefix("cask:") {
+                self.api_client.get_cask(token).await.ok().and_then(|cask| {
+                    cask.get("desc")
+                        .and_then(|desc| desc.as_str())
+                        .map(str::to_string)
+                })
+            } else if let Some(desc) = index.as_ref().and_then(|index| index.get(*name)) {
+                Some(desc.clone())
+            } else {
+                self.api_client
+                    .get_formula(name)
+                    .await
+                    .ok()
+                    .and_then(|formula| formula.desc)
+            };
+            if let Some(desc) = desc.filter(|desc| !desc.trim().is_empty()) {
+                descriptions.insert(name.to_string(), desc.trim().to_string());
+            }
+        }
+        descriptions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use crate::network::api::ApiClient;
+
+    use super::super::test_support::test_installer;
+    use super::{InstallReason, Manifest};
+
+    #[test]
+    fn manifest_matches_installed_set_and_round_trips() {
+        let tmp = TempDir::new().unwrap();
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+
+        for (name, version) in [("wget", "1.24.5"), ("jq", "1.7.1"), ("cmake", "3.30.0")] {
+            let keg = installer.keg_path(name, version);
+            fs::create_dir_all(keg.join("bin")).unwrap();
+            fs::write(keg.join("bin").join(name), name).unwrap();
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install(name, version, &format!("{name}key"))
+                .unwrap();
+            tx.commit().unwrap();
+        }
+        installer
+            .linker
+            .link_keg(&installer.keg_path("jq", "1.7.1"))
+            .unwrap();
+        installer.db.pin("wget", "1.24.5").unwrap();
+        installer
+            .mark_build_dependencies(&["cmake".to_string()])
+            .unwrap();
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("hashicorp/tap/terraform", "1.9.0", "tfkey")
+                .unwrap();
+            tx.commit().unwrap();
+        }
+        installer
+            .db
+            .mark_dependency("hashicorp/tap/terraform")
+            .unwrap();
+
+        let manifest = installer.export_manifest().unwrap();
+
+        assert_eq!(
+            manifest.names(),
+            vec!["cmake", "hashicorp/tap/terraform", "jq", "wget"]
+        );
+        assert_eq!(manifest.taps(), vec!["hashicorp/tap"]);
+        let entry = |name: &str| {
+            manifest
+                .formulas
+                .iter()
+                .find(|f| f.name == name)
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(entry("cmake").reason, InstallReason::BuildDependency);
+        assert_eq!(entry("jq").reason, InstallReason::Installed);
+        assert_eq!(
+            entry("hashicorp/tap/terraform").reason,
+            InstallReason::Dependency
+        );
+        assert!(entry("jq").linked && !entry("wget").linked);
+        assert!(entry("wget").pinned && !entry("jq").pinned);
+        assert_eq!(entry("wget").version, "1.24.5");
+
+        let json = serde_json::to_string(&manifest).unwrap();
+        assert!(json.contains(r#""reason":"build_dependency""#));
+        let parsed: Manifest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, manifest);
+    }
+}
@@ -1,57 +1,178 @@
+mod attestation;
+pub(crate) mod audit;
 mod bottle;
-pub mod doctor;
+pub(crate) mod builder;
+pub(crate) mod cache;
+mod cask_artifacts;
+pub(crate) mod caveats;
+mod conflicts;
+mod dependents;
+pub(crate) mod deps;
+pub(crate) mod doctor;
+pub(crate) mod generations;
+mod head;
+mod journal;
+pub(crate) mod link;
+pub(crate) mod lockfile;
+pub(crate) mod manifest;
+pub(crate) mod offline;
 mod outdated;
+pub(crate) mod paths;
+mod pin;
 mod plan;
+mod post_install;
+pub(crate) mod prune;
+mod remote;
+pub(crate) mod request;
+pub(crate) mod sbom;
+pub(crate) mod search;
+pub(crate) mod services;
+pub(crate) mod size;
 mod source;
 mod uninstall;
 mod upgrade;
+pub(crate) mod verify;
+mod versions;
 
+use std::collections::HashMap;
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use tracing::warn;
 
-use crate::cellar::link::Linker;
+use crate::build::BuildOptions;
+use crate::cellar::link::{LinkConflictPolicy, Linker};
 use crate::cellar::materialize::Cellar;
 use crate::network::api::ApiClient;
-use crate::network::cache::ApiCache;
-use crate::network::download::{DownloadProgressCallback, DownloadRequest, ParallelDownloader};
+use crate::network::download::{
+    DownloadManager, DownloadProgressCallback, DownloadRequest, DownloadResult, RetryPolicy,
+};
 use crate::progress::{InstallProgress, ProgressCallback};
 use crate::storage::blob::BlobCache;
 use crate::storage::db::Database;
 use crate::storage::store::Store;
 
-use zb_core::{Error, Formula, InstallMethod};
+use zb_core::{
+    BottlePolicy, BottleTag, DependencyOptions, Error, Formula, InstallMethod, bottle_tags,
+};
 
 use bottle::dependency_cellar_path;
+use caveats::FormulaCaveats;
+use journal::ReplacedKeg;
+use link::LinkConflictResolver;
+use request::InstalledFormula;
 
 const MAX_CORRUPTION_RETRIES: usize = 3;
 
 /// Acquire the cross-process install lock. The returned `File` must be kept
 /// alive (e.g. `let _lock = ...`) for the duration the lock should be held —
 /// dropping it releases the flock. Re-acquiring in the same process while the
-/// guard is alive would deadlock, so multi-step flows (e.g. `upgrade`) take
-/// the lock once and call the no-lock `execute_inner` directly.
-pub(crate) fn acquire_install_lock(locks_dir: &Path) -> Result<File, Error> {
+/// guard is alive would deadlock, so multi-step flows (e.g. `upgrade`,
+/// `autoremove`) take the lock once and call the no-lock `execute_inner` or
+/// `uninstall_inner` directly.
+///
+/// There is one lock for the whole root, not one per formula. Every install
+/// writes the shared database, store refs and prefix links, and plans for
+/// unrelated formulas still share dependencies. So installs run one at a
+/// time rather than trying to prove two plans disjoint.
+///
+/// When another process holds the lock, this waits for it unless `wait` is
+/// false, in which case it fails naming that process.
+///
+/// `on_wait` hears about the wait, with the holder's pid when known.
+pub(crate) fn acquire_install_lock(
+    locks_dir: &Path,
+    wait: bool,
+    on_wait: impl FnOnce(Option<u32>),
+) -> Result<File, Error> {
     let lock_path = locks_dir.join("install.lock");
-    let lock_file =
-        File::create(&lock_path).map_err(Error::store("failed to create install lock"))?;
-    lock_file
-        .lock()
-        .map_err(Error::store("failed to acquire install lock"))?;
+    let lock_file = open_install_lock(&lock_path)?;
+    match lock_file.try_lock() {
+        Ok(()) => {}
+        Err(std::fs::TryLockError::WouldBlock) => {
+            let pid = lock_holder(&lock_path);
+            if !wait {
+                let holder = pid.map(|pid| format!(" (pid {pid})")).unwrap_or_default();
+                return Err(Error::ExecutionError {
+                    message: format!(
+                        "another zerobrew process{holder} is installing; \
+                         try again once it finishes"
+                    ),
+                });
+            }
+            on_wait(pid);
+            lock_file
+                .lock()
+                .map_err(Error::store("failed to acquire install lock"))?;
+        }
+        Err(std::fs::TryLockError::Error(e)) => {
+            return Err(Error::store("failed to acquire install lock")(e));
+        }
+    }
+
+    // Only read while the lock is contended, so failing to record the
+    // holder isn't worth failing the install over.
+    if let Err(e) = fs::write(&lock_path, std::process::id().to_string()) {
+        warn!(error = %e, "failed to record install lock holder");
+    }
     Ok(lock_file)
 }
 
+/// Non-blocking [`acquire_install_lock`]: `None` when another install holds
+/// the lock.
+pub(crate) fn try_acquire_install_lock(locks_dir: &Path) -> Result<Option<File>, Error> {
+    let lock_file = open_install_lock(&locks_dir.join("install.lock"))?;
+    match lock_file.try_lock() {
+        Ok(()) => Ok(Some(lock_file)),
+        Err(std::fs::TryLockError::WouldBlock) => Ok(None),
+        Err(std::fs::TryLockError::Error(e)) => {
+            Err(Error::store("failed to acquire install lock")(e))
+        }
+    }
+}
+
+/// Open without truncating, so the holder's pid survives other processes
+/// opening the file to wait on it.
+fn open_install_lock(lock_path: &Path) -> Result<File, Error> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path)
+        .map_err(Error::store("failed to create install lock"))
+}
+
+/// The pid of the process that last took the install lock.
+fn lock_holder(lock_path: &Path) -> Option<u32> {
+    fs::read_to_string(lock_path).ok()?.trim().parse().ok()
+}
+
 pub struct Installer {
     api_client: ApiClient,
-    downloader: ParallelDownloader,
+    downloader: DownloadManager,
     store: Store,
     cellar: Cellar,
     linker: Linker,
     pub(crate) db: Database,
     prefix: PathBuf,
     locks_dir: PathBuf,
+    build_options: BuildOptions,
+    verify_after_install: bool,
+    require_relocation: bool,
+    verify_signatures: bool,
+    ignored_conflicts: Vec<String>,
+    ignore_pins: bool,
+    appdir: PathBuf,
+    offline: bool,
+    wait_for_lock: bool,
+    bottle_policy: BottlePolicy,
+    dependency_options: DependencyOptions,
+    progress: Option<Arc<ProgressCallback>>,
+    link_conflict_policy: LinkConflictPolicy,
+    link_conflict_resolver: Option<LinkConflictResolver>,
+    /// Upgrades in progress, by formula, with the version each replaces.
+    replacing: HashMap<String, ReplacedKeg>,
 }
 
 #[derive(Debug)]
@@ -59,6 +180,22 @@ pub struct PlannedInstall {
     pub install_name: String,
     pub formula: Formula,
     pub method: InstallMethod,
+    /// Named when planning, as opposed to pulled in as a dependency.
+    pub requested: bool,
+    /// Only needed to build another item from source, so `gc --aggressive`
+    /// may reclaim it afterwards.
+    pub build_only: bool,
+}
+
+impl PlannedInstall {
+    /// The version this item installs: a `--HEAD` build's commit version,
+    /// otherwise the formula's.
+    pub fn version(&self) -> String {
+        match &self.method {
+            InstallMethod::Source(build_plan) => build_plan.version.clone(),
+            InstallMethod::Bottle(_) => self.formula.effective_version(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -66,14 +203,39 @@ pub struct InstallPlan {
     pub items: Vec<PlannedInstall>,
 }
 
+impl InstallPlan {
+    pub fn install_names(&self) -> Vec<String> {
+        self.items
+            .iter()
+            .map(|item| item.install_name.clone())
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 pub struct PlanFailure {
     pub name: String,
     pub error: Error,
 }
 
+#[derive(Debug, Default)]
 pub struct ExecuteResult {
     pub installed: usize,
+    /// The formulas installed, in install order.
+    pub formulas: Vec<InstalledFormula>,
+    /// Caveats of the formulas installed, in install order.
+    pub caveats: Vec<FormulaCaveats>,
+    /// Pinned formulas the plan would have moved to another version, left
+    /// out instead.
+    pub skipped_pinned: Vec<SkippedPin>,
+}
+
+/// A plan item left out because it would replace a pinned keg.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SkippedPin {
+    pub name: String,
+    pub pinned_version: String,
+    pub planned_version: String,
 }
 
 /// A package that has a newer version available upstream.
@@ -90,6 +252,24 @@ pub struct OutdatedPackage {
     pub is_source_build: bool,
 }
 
+/// A formula's resolved `.rb` file, as handed to source builds.
+#[derive(Debug, Clone)]
+pub struct FormulaFile {
+    pub name: String,
+    pub path: PathBuf,
+    /// `homebrew/core`, a tap's `owner/repo`, or the source URL.
+    pub origin: String,
+    pub url: String,
+}
+
+/// Outdated packages split by whether a bulk upgrade may replace them.
+#[derive(Debug, Default)]
+pub struct UpgradeSelection {
+    pub outdated: Vec<OutdatedPackage>,
+    pub pinned: Vec<OutdatedPackage>,
+    pub warnings: Vec<String>,
+}
+
 impl Installer {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -104,16 +284,52 @@ impl Installer {
     ) -> Self {
         Self {
             api_client,
-            downloader: ParallelDownloader::new(blob_cache),
+            downloader: DownloadManager::new(blob_cache),
             store,
             cellar,
             linker,
             db,
             prefix,
             locks_dir,
+            build_options: BuildOptions::default(),
+            verify_after_install: false,
+            require_relocation: false,
+            verify_signatures: false,
+            ignored_conflicts: Vec::new(),
+            ignore_pins: false,
+            appdir: PathBuf::from(cask_artifacts::DEFAULT_APPDIR),
+            offline: false,
+            wait_for_lock: true,
+            bottle_policy: BottlePolicy::default(),
+            dependency_options: DependencyOptions::default(),
+            progress: None,
+            link_conflict_policy: LinkConflictPolicy::default(),
+            link_conflict_resolver: None,
+            replacing: HashMap::new(),
         }
     }
 
+    /// Subscribe to progress from every stage, including resolution and
+    /// waiting on the install lock. Executes that aren't handed a callback
+    /// of their own report here too.
+    pub fn set_progress(&mut self, progress: Option<Arc<ProgressCallback>>) {
+        self.progress = progress;
+    }
+
+    pub(crate) fn report(&self, event: InstallProgress) {
+        if let Some(cb) = &self.progress {
+            cb(event);
+        }
+    }
+
+    /// Take the install lock, waiting or failing as
+    /// [`crate::InstallerBuilder::wait_for_lock`] says.
+    pub(crate) fn install_lock(&self) -> Result<File, Error> {
+        acquire_install_lock(&self.locks_dir, self.wait_for_lock, |holder| {
+            self.report(InstallProgress::WaitingForLock { holder })
+        })
+    }
+
     pub fn clear_api_cache(&self) -> Result<usize, Error> {
         self.api_client.clear_cache()
     }
@@ -128,8 +344,11 @@ impl Installer {
         link: bool,
         progress: Option<Arc<ProgressCallback>>,
     ) -> Result<ExecuteResult, Error> {
-        let _lock = acquire_install_lock(&self.locks_dir)?;
-        self.execute_inner(plan, link, progress).await
+        let _lock = self.install_lock()?;
+        let progress = progress.or_else(|| self.progress.clone());
+        let result = self.execute_inner(plan, link, progress).await;
+        self.record_generation("install");
+        result
     }
 
     /// No-lock variant of `execute_with_progress`. Callers MUST already hold
@@ -137,7 +356,7 @@ impl Installer {
     /// under a single lock acquisition.
     pub(crate) async fn execute_inner(
         &mut self,
-        plan: InstallPlan,
+        mut plan: InstallPlan,
         link: bool,
         progress: Option<Arc<ProgressCallback>>,
     ) -> Result<ExecuteResult, Error> {
@@ -147,33 +366,93 @@ impl Installer {
             }
         };
 
-        let (bottle_items, source_items): (Vec<_>, Vec<_>) = plan
-            .items
-            .into_iter()
-            .partition(|item| matches!(item.method, InstallMethod::Bottle(_)));
+        // Undo anything a crash left half-installed before adding to it.
+        for name in self.roll_back_journals()? {
+            warn!(formula = %name, "rolled back an interrupted install");
+        }
 
-        if bottle_items.is_empty() && source_items.is_empty() {
-            return Ok(ExecuteResult { installed: 0 });
+        let skipped_pinned = self.retain_unpinned(&mut plan)?;
+        self.check_conflicts(&plan)?;
+        if self.offline {
+            self.ensure_available_offline(&plan)?;
         }
 
-        let mut installed = 0usize;
-        let mut error: Option<Error> = None;
+        // Anything installed through a regular plan is wanted in its own
+        // right, even if it was first pulled in by `--build-deps-only`,
+        // except what a source build in the plan needs only to build.
+        // Dependencies are marked for `autoremove`, unless they were
+        // already installed for their own sake.
+        for item in &plan.items {
+            if item.build_only {
+                self.db.mark_build_dependency(&item.install_name)?;
+                continue;
+            }
+            self.db.clear_build_dependency(&item.install_name)?;
+            self.db.clear_ephemeral(&item.install_name)?;
+            if item.requested {
+                self.db.clear_dependency(&item.install_name)?;
+            } else if !self.is_installed(&item.install_name) {
+                self.db.mark_dependency(&item.install_name)?;
+            }
+        }
 
-        if !bottle_items.is_empty() {
-            let requests: Vec<DownloadRequest> = bottle_items
-                .iter()
-                .map(|item| {
-                    let InstallMethod::Bottle(ref bottle) = item.method else {
-                        unreachable!()
-                    };
-                    DownloadRequest {
+        // Route each item by its planned method. Bottles share one streaming
+        // download pass; source builds run serially afterwards. Offline, a
+        // bottle already unpacked in the store skips the download entirely,
+        // since its blob may have been pruned.
+        let mut stored_items = Vec::new();
+        let mut bottle_items = Vec::new();
+        let mut requests = Vec::new();
+        let mut source_items = Vec::new();
+        for item in plan.items {
+            match &item.method {
+                InstallMethod::Bottle(bottle)
+                    if self.offline && self.has_populated_store_entry(&bottle.sha256) =>
+                {
+                    stored_items.push((bottle.clone(), item));
+                }
+                InstallMethod::Bottle(bottle) => {
+                    requests.push(DownloadRequest {
                         url: bottle.url.clone(),
                         sha256: bottle.sha256.clone(),
                         name: item.formula.name.clone(),
-                    }
-                })
-                .collect();
+                    });
+                    bottle_items.push((bottle.clone(), item));
+                }
+                InstallMethod::Source(build_plan) => {
+                    source_items.push((build_plan.clone(), item));
+                }
+            }
+        }
+
+        let mut result = ExecuteResult {
+            skipped_pinned,
+            ..Default::default()
+        };
+        if stored_items.is_empty() && bottle_items.is_empty() && source_items.is_empty() {
+            return Ok(result);
+        }
+
+        let mut installed_items: Vec<&PlannedInstall> = Vec::new();
+        let mut error: Option<Error> = None;
+
+        for (bottle, item) in &stored_items {
+            let download = DownloadResult {
+                name: item.formula.name.clone(),
+                sha256: bottle.sha256.clone(),
+                blob_path: self.downloader.blob_cache().blob_path(&bottle.sha256),
+                index: 0,
+            };
+            match self
+                .process_bottle_item(item, bottle, &download, &None, link, &report)
+                .await
+            {
+                Ok(()) => installed_items.push(item),
+                Err(e) => error = Some(e),
+            }
+        }
 
+        if !bottle_items.is_empty() {
             let download_progress: Option<DownloadProgressCallback> = progress.clone().map(|cb| {
                 Arc::new(move |event: InstallProgress| {
                     cb(event);
@@ -187,9 +466,11 @@ impl Installer {
             while let Some(result) = rx.recv().await {
                 match result {
                     Ok(download) => {
+                        let (bottle, item) = &bottle_items[download.index];
                         match self
                             .process_bottle_item(
-                                &bottle_items[download.index],
+                                item,
+                                bottle,
                                 &download,
                                 &download_progress,
                                 link,
@@ -197,7 +478,7 @@ impl Installer {
                             )
                             .await
                         {
-                            Ok(()) => installed += 1,
+                            Ok(()) => installed_items.push(item),
                             Err(e) => error = Some(e),
                         }
                     }
@@ -208,11 +489,7 @@ impl Installer {
             }
         }
 
-        for item in &source_items {
-            let InstallMethod::Source(ref build_plan) = item.method else {
-                unreachable!()
-            };
-
+        for (build_plan, item) in &source_items {
             report(InstallProgress::UnpackStarted {
                 name: item.formula.name.clone(),
             });
@@ -221,7 +498,7 @@ impl Installer {
                 .install_from_source(item, build_plan, link, &report)
                 .await
             {
-                Ok(()) => installed += 1,
+                Ok(()) => installed_items.push(item),
                 Err(e) => {
                     error = Some(e);
                     continue;
@@ -229,11 +506,36 @@ impl Installer {
             }
         }
 
+        // Once every keg is in place, so a `post_install` block can use its
+        // dependencies.
+        for item in installed_items {
+            self.record_installed(&mut result, item);
+            if item.formula.post_install_defined {
+                self.post_install_after_install(&item.install_name, &item.formula)
+                    .await;
+            }
+        }
+
         if let Some(e) = error {
             return Err(e);
         }
 
-        Ok(ExecuteResult { installed })
+        Ok(result)
+    }
+
+    fn record_installed(&self, result: &mut ExecuteResult, item: &PlannedInstall) {
+        let formula = &item.formula;
+        result.installed += 1;
+        result.formulas.push(InstalledFormula {
+            name: item.install_name.clone(),
+            version: item.version(),
+        });
+        if let Some(text) = self.formula_caveats(formula) {
+            result.caveats.push(FormulaCaveats {
+                name: formula.name.clone(),
+                text,
+            });
+        }
     }
 
     pub async fn install(&mut self, names: &[String], link: bool) -> Result<ExecuteResult, Error> {
@@ -242,18 +544,58 @@ impl Installer {
             .cloned()
             .partition(|name| name.starts_with("cask:"));
 
-        let mut installed = 0usize;
+        let mut result = ExecuteResult::default();
 
         if !formulas.is_empty() {
             let plan = self.plan(&formulas).await?;
-            installed += self.execute(plan, link).await?.installed;
+            result = self.execute(plan, link).await?;
         }
 
         if !casks.is_empty() {
-            installed += self.install_casks(&casks, link).await?.installed;
+            result.installed += self.install_casks(&casks, link).await?.installed;
+        }
+
+        Ok(result)
+    }
+
+    /// Install just the `:build` dependencies of `names`, recording them as
+    /// such so `zb gc --aggressive` can reclaim them once they're unneeded.
+    pub async fn install_build_deps_only(
+        &mut self,
+        names: &[String],
+        link: bool,
+    ) -> Result<ExecuteResult, Error> {
+        let plan = self.plan_build_dependencies(names, false).await?;
+        let planned = plan.install_names();
+        let result = self.execute(plan, link).await?;
+        self.mark_build_dependencies(&planned)?;
+        Ok(result)
+    }
+
+    /// Install `names` unlinked for `zb run --ephemeral`, marking whatever
+    /// wasn't already installed so `zb gc --aggressive` can reclaim it. A
+    /// later regular install of any of them clears the mark.
+    pub async fn install_ephemeral(&mut self, names: &[String]) -> Result<ExecuteResult, Error> {
+        let plan = self.plan(names).await?;
+        let new: Vec<String> = plan
+            .install_names()
+            .into_iter()
+            .filter(|name| !self.is_installed(name))
+            .collect();
+        let result = self.execute(plan, false).await?;
+        for name in &new {
+            self.db.mark_ephemeral(name)?;
         }
+        Ok(result)
+    }
 
-        Ok(ExecuteResult { installed })
+    /// Record formulas from a [`Self::plan_build_dependencies`] plan once
+    /// it has been executed.
+    pub fn mark_build_dependencies(&self, names: &[String]) -> Result<(), Error> {
+        for name in names {
+            self.db.mark_build_dependency(name)?;
+        }
+        Ok(())
     }
 
     pub async fn install_casks(
@@ -269,7 +611,10 @@ impl Installer {
             self.install_single_cask(token, link).await?;
             installed += 1;
         }
-        Ok(ExecuteResult { installed })
+        Ok(ExecuteResult {
+            installed,
+            ..Default::default()
+        })
     }
 
     pub fn is_installed(&self, name: &str) -> bool {
@@ -284,95 +629,133 @@ impl Installer {
         self.db.list_installed()
     }
 
+    pub fn install_history(
+        &self,
+        name: &str,
+    ) -> Result<Vec<crate::storage::db::InstallHistoryEntry>, Error> {
+        self.db.install_history(name)
+    }
+
     pub fn keg_path(&self, name: &str, version: &str) -> PathBuf {
         self.cellar.keg_path(name, version)
     }
 
-    fn cleanup_materialized(cellar: &Cellar, name: &str, version: &str) {
-        if let Err(e) = cellar.remove_keg(name, version) {
-            warn!(
-                formula = %name,
-                version = %version,
-                error = %e,
-                "failed to remove keg after install error"
-            );
-        }
+    pub async fn bottle_tags(&self, name: &str) -> Result<Vec<BottleTag>, Error> {
+        let formula = self.api_client.get_formula(name).await?;
+        Ok(bottle_tags(&formula))
+    }
+
+    /// `name`'s formula as the API has it now, installed or not.
+    pub async fn get_formula(&self, name: &str) -> Result<Formula, Error> {
+        self.api_client.get_formula(name).await
     }
 }
 
+/// [`Installer::builder`] with every download setting given at once. Kept
+/// for existing callers; new code should use the builder.
+#[deprecated(note = "use `Installer::builder`")]
 pub fn create_installer(
     root: &Path,
     prefix: &Path,
     concurrency: usize,
+    max_download_rate: Option<u64>,
+    retry: RetryPolicy,
+    mirrors: Vec<String>,
 ) -> Result<Installer, Error> {
-    if !root.exists() {
-        fs::create_dir_all(root).map_err(|e| {
-            if e.kind() == std::io::ErrorKind::PermissionDenied {
-                Error::StoreCorruption {
-                    message: format!(
-                        "cannot create root directory '{}': permission denied.\n\n\
-                        Create it with:\n  sudo mkdir -p {} && sudo chown $USER {}",
-                        root.display(),
-                        root.display(),
-                        root.display()
-                    ),
-                }
-            } else {
-                Error::StoreCorruption {
-                    message: format!("failed to create root directory '{}': {e}", root.display()),
-                }
-            }
-        })?;
-    }
+    Installer::builder(root, prefix)
+        .concurrency(concurrency)
+        .max_download_rate(max_download_rate)
+        .retry_policy(retry)
+        .mirrors(mirrors)
+        .build()
+}
 
-    fs::create_dir_all(root.join("db")).map_err(Error::store("failed to create db directory"))?;
+#[cfg(test)]
+mod test_support {
+    use std::fs;
+    use std::path::Path;
 
-    fs::create_dir_all(root.join("cache"))
-        .map_err(Error::store("failed to create cache directory"))?;
+    use wiremock::MockServer;
 
-    let api_cache_path = root.join("cache/api-cache.sqlite");
-    let api_cache =
-        ApiCache::open(&api_cache_path).map_err(Error::store("failed to open API cache"))?;
+    use crate::cellar::Cellar;
+    use crate::network::api::ApiClient;
+    use crate::storage::blob::BlobCache;
+    use crate::storage::db::Database;
+    use crate::storage::store::Store;
+    use crate::{Installer, Linker};
+
+    /// An installer rooted at `<dir>/zerobrew` with its prefix at
+    /// `<dir>/homebrew`.
+    pub fn test_installer(dir: &Path, api_client: ApiClient) -> Installer {
+        let root = dir.join("zerobrew");
+        let prefix = dir.join("homebrew");
+        fs::create_dir_all(root.join("db")).unwrap();
 
-    let api_client = match std::env::var("ZEROBREW_API_URL") {
-        Ok(url) => ApiClient::with_base_url(url)?,
-        Err(_) => ApiClient::new(),
+        Installer::new(
+            api_client,
+            BlobCache::new(&root.join("cache")).unwrap(),
+            Store::new(&root).unwrap(),
+            Cellar::new(&root).unwrap(),
+            Linker::new(&prefix).unwrap(),
+            Database::open(&root.join("db/zb.sqlite3")).unwrap(),
+            prefix,
+            root.join("locks"),
+        )
+    }
+
+    /// An API client that fetches formula JSON from `mock_server`.
+    pub fn mock_api_client(mock_server: &MockServer) -> ApiClient {
+        ApiClient::with_base_url(format!("{}/formula", mock_server.uri())).unwrap()
+    }
+
+    /// Formula JSON at 1.0.0 whose bottle for this host is never downloaded.
+    pub fn formula_json(name: &str, deps: &[&str]) -> String {
+        formula_json_with_bottle(name, "1.0.0", deps, "https://example.com", &"ab".repeat(32))
+    }
+
+    pub fn formula_json_with_bottle(
+        name: &str,
+        version: &str,
+        deps: &[&str],
+        bottle_base_url: &str,
+        sha256: &str,
+    ) -> String {
+        let tag = get_test_bottle_tag();
+        let deps = serde_json::to_string(deps).unwrap();
+        format!(
+            r#"{{
+                "name": "{name}",
+                "versions": {{ "stable": "{version}" }},
+                "dependencies": {deps},
+                "license": "MIT",
+                "homepage": "https://example.com/{name}",
+                "bottle": {{
+                    "stable": {{
+                        "files": {{
+                            "{tag}": {{
+                                "url": "{bottle_base_url}/{name}-{version}.{tag}.bottle.tar.gz",
+                                "sha256": "{sha256}"
+                            }}
+                        }}
+                    }}
+                }}
+            }}"#
+        )
     }
-    .with_cache(api_cache);
-
-    let blob_cache =
-        BlobCache::new(&root.join("cache")).map_err(Error::store("failed to create blob cache"))?;
-    let store = Store::new(root).map_err(Error::store("failed to create store"))?;
-    // Use prefix/Cellar so bottles' hardcoded rpaths work
-    let cellar =
-        Cellar::new_at(prefix.join("Cellar")).map_err(Error::store("failed to create cellar"))?;
-    let linker = Linker::new(prefix).map_err(Error::store("failed to create linker"))?;
-    let db = Database::open(&root.join("db/zb.sqlite3"))?;
-
-    let locks_dir = root.join("locks");
-    fs::create_dir_all(&locks_dir).map_err(Error::store("failed to create locks directory"))?;
-
-    let parallel_downloader = ParallelDownloader::with_concurrency(blob_cache, concurrency);
-
-    Ok(Installer {
-        api_client,
-        downloader: parallel_downloader,
-        store,
-        cellar,
-        linker,
-        db,
-        prefix: prefix.to_path_buf(),
-        locks_dir,
-    })
-}
 
-#[cfg(test)]
-mod test_support {
     pub fn create_bottle_tarball(formula_name: &str) -> Vec<u8> {
         create_bottle_tarball_with_version(formula_name, "1.0.0")
     }
 
     pub fn create_bottle_tarball_with_version(formula_name: &str, version: &str) -> Vec<u8> {
+        create_bottle_tarball_with_binary(formula_name, version, formula_name)
+    }
+
+    pub fn create_bottle_tarball_with_binary(
+        formula_name: &str,
+        version: &str,
+        binary: &str,
+    ) -> Vec<u8> {
         use flate2::Compression;
         use flate2::write::GzEncoder;
         use std::io::Write;
@@ -384,7 +767,7 @@ mod test_support {
 
         let mut header = tar::Header::new_gnu();
         header
-            .set_path(format!("{}/{}/bin/{}", formula_name, version, formula_name))
+            .set_path(format!("{}/{}/bin/{}", formula_name, version, binary))
             .unwrap();
         header.set_size(content.len() as u64);
         header.set_mode(0o755);
@@ -399,6 +782,36 @@ mod test_support {
         encoder.finish().unwrap()
     }
 
+    pub fn create_bottle_tarball_with_file(
+        formula_name: &str,
+        version: &str,
+        file: &str,
+        contents: &[u8],
+    ) -> Vec<u8> {
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+        use tar::Builder;
+
+        let mut builder = Builder::new(Vec::new());
+
+        let mut header = tar::Header::new_gnu();
+        header
+            .set_path(format!("{}/{}/{}", formula_name, version, file))
+            .unwrap();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+
+        builder.append(&header, contents).unwrap();
+
+        let tar_data = builder.into_inner().unwrap();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar_data).unwrap();
+        encoder.finish().unwrap()
+    }
+
     pub fn sha256_hex(data: &[u8]) -> String {
         use sha2::{Digest, Sha256};
         let mut hasher = Sha256::new();
@@ -437,6 +850,30 @@ mod tests {
 
     use super::test_support::*;
 
+    #[test]
+    fn no_wait_lock_fails_naming_the_holder() {
+        let tmp = TempDir::new().unwrap();
+        let locks = tmp.path().join("locks");
+        fs::create_dir_all(&locks).unwrap();
+
+        let held = super::acquire_install_lock(&locks, false).unwrap();
+        assert_eq!(
+            super::lock_holder(&locks.join("install.lock")),
+            Some(std::process::id())
+        );
+
+        let err = super::acquire_install_lock(&locks, false).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&format!("(pid {})", std::process::id())),
+            "{err}"
+        );
+        assert!(super::try_acquire_install_lock(&locks).unwrap().is_none());
+
+        drop(held);
+        assert!(super::acquire_install_lock(&locks, false).is_ok());
+    }
+
     #[tokio::test]
     async fn install_completes_successfully() {
         let mock_server = MockServer::start().await;
@@ -519,6 +956,272 @@ mod tests {
         assert_eq!(installed.unwrap().version, "1.0.0");
     }
 
+    #[tokio::test]
+    async fn require_relocation_rejects_binary_with_unrelocatable_reference() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        // Not a loadable ELF, so the relocator skips it and the baked-in
+        // default-prefix path survives.
+        let mut binary = b"\x7fELF\x02\x01\x01\0".to_vec();
+        binary.extend_from_slice(b"/opt/homebrew/opt/relocpkg/libexec/helper\0");
+        let bottle = create_bottle_tarball_with_file("relocpkg", "1.0.0", "bin/relocpkg", &binary);
+        let bottle_sha = sha256_hex(&bottle);
+
+        let tag = get_test_bottle_tag();
+        let formula_json = format!(
+            r#"{{
+                "name": "relocpkg",
+                "versions": {{ "stable": "1.0.0" }},
+                "dependencies": [],
+                "bottle": {{
+                    "stable": {{
+                        "files": {{
+                            "{tag}": {{
+                                "url": "{uri}/bottles/relocpkg-1.0.0.{tag}.bottle.tar.gz",
+                                "sha256": "{bottle_sha}"
+                            }}
+                        }}
+                    }}
+                }}
+            }}"#,
+            uri = mock_server.uri(),
+        );
+
+        Mock::given(method("GET"))
+            .and(path("/formula/relocpkg.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(&formula_json))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/relocpkg-1.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle))
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        fs::create_dir_all(root.join("db")).unwrap();
+
+        let mut installer = Installer::new(
+            ApiClient::with_base_url(format!("{}/formula", mock_server.uri())).unwrap(),
+            BlobCache::new(&root.join("cache")).unwrap(),
+            Store::new(&root).unwrap(),
+            Cellar::new(&root).unwrap(),
+            Linker::new(&prefix).unwrap(),
+            Database::open(&root.join("db/zb.sqlite3")).unwrap(),
+            prefix.clone(),
+            root.join("locks"),
+        );
+        installer.require_relocation = true;
+
+        let err = installer
+            .install(&["relocpkg".to_string()], true)
+            .await
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("bin/relocpkg"), "{message}");
+        assert!(
+            message.contains("/opt/homebrew/opt/relocpkg/libexec/helper"),
+            "{message}"
+        );
+        assert!(!root.join("cellar/relocpkg/1.0.0").exists());
+        assert!(installer.db.get_installed("relocpkg").is_none());
+
+        installer.require_relocation = false;
+        installer
+            .install(&["relocpkg".to_string()], true)
+            .await
+            .unwrap();
+        assert!(root.join("cellar/relocpkg/1.0.0").exists());
+    }
+
+    #[tokio::test]
+    async fn build_deps_only_installs_build_deps_but_not_runtime_deps() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let tag = get_test_bottle_tag();
+
+        let formula_json = |name: &str, deps: &str, build_deps: &str, sha: &str| {
+            format!(
+                r#"{{
+                    "name": "{name}",
+                    "versions": {{ "stable": "1.0.0" }},
+                    "dependencies": [{deps}],
+                    "build_dependencies": [{build_deps}],
+                    "bottle": {{
+                        "stable": {{
+                            "files": {{
+                                "{tag}": {{
+                                    "url": "{uri}/bottles/{name}-1.0.0.{tag}.bottle.tar.gz",
+                                    "sha256": "{sha}"
+                                }}
+                            }}
+                        }}
+                    }}
+                }}"#,
+                uri = mock_server.uri(),
+            )
+        };
+
+        let builddep_bottle = create_bottle_tarball("builddep");
+        let builddep_sha = sha256_hex(&builddep_bottle);
+        Mock::given(method("GET"))
+            .and(path("/formula/app.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(
+                "app",
+                r#""runtimedep""#,
+                r#""builddep""#,
+                &"ab".repeat(32),
+            )))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/formula/builddep.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(
+                "builddep",
+                "",
+                "",
+                &builddep_sha,
+            )))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/formula/runtimedep.json"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/builddep-1.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(builddep_bottle))
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        fs::create_dir_all(root.join("db")).unwrap();
+
+        let mut installer = Installer::new(
+            ApiClient::with_base_url(format!("{}/formula", mock_server.uri())).unwrap(),
+            BlobCache::new(&root.join("cache")).unwrap(),
+            Store::new(&root).unwrap(),
+            Cellar::new(&root).unwrap(),
+            Linker::new(&prefix).unwrap(),
+            Database::open(&root.join("db/zb.sqlite3")).unwrap(),
+            prefix.clone(),
+            root.join("locks"),
+        );
+
+        let result = installer
+            .install_build_deps_only(&["app".to_string()], true)
+            .await
+            .unwrap();
+
+        assert_eq!(result.installed, 1);
+        assert!(installer.is_installed("builddep"));
+        assert!(!installer.is_installed("runtimedep"));
+        assert!(!installer.is_installed("app"));
+        assert_eq!(
+            installer.db.list_build_dependencies().unwrap(),
+            vec!["builddep"]
+        );
+
+        // A second run has nothing left to install.
+        let result = installer
+            .install_build_deps_only(&["app".to_string()], true)
+            .await
+            .unwrap();
+        assert_eq!(result.installed, 0);
+
+        let plan = installer.plan_aggressive_gc().await.unwrap();
+        assert_eq!(plan.build_dependencies.len(), 1);
+        assert_eq!(plan.build_dependencies[0].name, "builddep");
+        drop(plan);
+
+        // Installing it explicitly makes it no longer reclaimable.
+        installer
+            .install(&["builddep".to_string()], true)
+            .await
+            .unwrap();
+        assert!(installer.db.list_build_dependencies().unwrap().is_empty());
+        assert!(
+            installer
+                .plan_aggressive_gc()
+                .await
+                .unwrap()
+                .build_dependencies
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn ephemeral_install_is_reclaimed_until_installed_for_real() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        let bottle = create_bottle_tarball("runonce");
+        let bottle_sha = sha256_hex(&bottle);
+        let tag = get_test_bottle_tag();
+        let formula_json = format!(
+            r#"{{"name":"runonce","versions":{{"stable":"1.0.0"}},"dependencies":[],"bottle":{{"stable":{{"files":{{"{tag}":{{"url":"{}/bottles/runonce-1.0.0.{tag}.bottle.tar.gz","sha256":"{bottle_sha}"}}}}}}}}}}"#,
+            mock_server.uri(),
+        );
+
+        Mock::given(method("GET"))
+            .and(path("/formula/runonce.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(&formula_json))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/runonce-1.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle))
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        fs::create_dir_all(root.join("db")).unwrap();
+
+        let mut installer = Installer::new(
+            ApiClient::with_base_url(format!("{}/formula", mock_server.uri())).unwrap(),
+            BlobCache::new(&root.join("cache")).unwrap(),
+            Store::new(&root).unwrap(),
+            Cellar::new(&root).unwrap(),
+            Linker::new(&prefix).unwrap(),
+            Database::open(&root.join("db/zb.sqlite3")).unwrap(),
+            prefix.clone(),
+            root.join("locks"),
+        );
+
+        installer
+            .install_ephemeral(&["runonce".to_string()])
+            .await
+            .unwrap();
+        assert!(installer.is_installed("runonce"));
+        assert!(!prefix.join("bin/runonce").exists());
+        assert_eq!(installer.db.list_ephemeral().unwrap(), vec!["runonce"]);
+
+        let plan = installer.plan_aggressive_gc().await.unwrap();
+        assert_eq!(plan.ephemeral.len(), 1);
+        assert_eq!(plan.ephemeral[0].name, "runonce");
+        drop(plan);
+
+        installer
+            .install(&["runonce".to_string()], true)
+            .await
+            .unwrap();
+        assert!(installer.db.list_ephemeral().unwrap().is_empty());
+        assert!(
+            installer
+                .plan_aggressive_gc()
+                .await
+                .unwrap()
+                .ephemeral
+                .is_empty()
+        );
+    }
+
     #[tokio::test]
     async fn install_with_dependencies() {
         let mock_server = MockServer::start().await;
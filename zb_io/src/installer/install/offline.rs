@@ -0,0 +1,253 @@
+use std::fs;
+use std::sync::Arc;
+
+use zb_core::{Error, InstallMethod};
+
+use super::{InstallPlan, Installer};
+use crate::network::download::{DownloadProgressCallback, DownloadRequest};
+use crate::progress::{InstallProgress, ProgressCallback};
+
+/// What `fetch` left on disk for a later offline install.
+#[derive(Debug, Default)]
+pub struct FetchSummary {
+    /// Bottles downloaded into the blob cache by this fetch.
+    pub downloaded: Vec<String>,
+    /// Bottles unpacked into the store, ready to be materialized.
+    pub unpacked: Vec<String>,
+    /// Bottles that were already local and needed nothing.
+    pub already_local: Vec<String>,
+    /// Formulas without a bottle for this platform. They would be built
+    /// from source, which an offline install can't do.
+    pub source_only: Vec<String>,
+}
+
+impl Installer {
+    /// Cache everything an offline install of `names` needs: the metadata of
+    /// their whole closure and its bottles, unpacked into the store unless
+    /// `download_only` is set. Nothing is linked or recorded as installed.
+    pub async fn fetch(
+        &self,
+        names: &[String],
+        download_only: bool,
+        progress: Option<Arc<ProgressCallback>>,
+    ) -> Result<FetchSummary, Error> {
+        let plan = self.plan(names).await?;
+        let mut summary = FetchSummary::default();
+
+        let mut requests = Vec::new();
+        let mut pending = Vec::new();
+        for item in &plan.items {
+            let bottle = match &item.method {
+                InstallMethod::Bottle(bottle) => bottle,
+                InstallMethod::Source(_) => {
+                    summary.source_only.push(item.install_name.clone());
+                    continue;
+                }
+            };
+
+            let cached = self.downloader.blob_cache().has_blob(&bottle.sha256);
+            if self.has_populated_store_entry(&bottle.sha256) || (cached && download_only) {
+                summary.already_local.push(item.install_name.clone());
+                continue;
+            }
+
+            requests.push(DownloadRequest {
+                url: bottle.url.clone(),
+                sha256: bottle.sha256.clone(),
+                name: item.formula.name.clone(),
+            });
+            pending.push((item.install_name.clone(), bottle.sha256.clone(), cached));
+        }
+
+        let report = |event: InstallProgress| {
+            if let Some(cb) = &progress {
+                cb(event);
+            }
+        };
+        let download_progress: Option<DownloadProgressCallback> = progress.clone().map(|cb| {
+            Arc::new(move |event: InstallProgress| {
+                cb(event);
+            }) as DownloadProgressCallback
+        });
+
+        let mut rx = self
+            .downloader
+            .download_streaming(requests, download_progress);
+        while let Some(result) = rx.recv().await {
+            let download = result?;
+            let (name, sha256, cached) = &pending[download.index];
+            if !cached {
+                summary.downloaded.push(name.clone());
+            }
+            if !download_only {
+                report(InstallProgress::UnpackStarted {
+                    name: download.name.clone(),
+                });
+                self.ensure_store_entry(sha256, &download.blob_path)?;
+                report(InstallProgress::UnpackCompleted {
+                    name: download.name.clone(),
+                });
+                summary.unpacked.push(name.clone());
+            }
+            report(InstallProgress::InstallCompleted {
+                name: download.name.clone(),
+            });
+        }
+
+        summary.downloaded.sort();
+        summary.unpacked.sort();
+        Ok(summary)
+    }
+
+    /// Fail before any work starts if an offline install of `plan` would
+    /// need the network.
+    pub(super) fn ensure_available_offline(&self, plan: &InstallPlan) -> Result<(), Error> {
+        let mut missing = Vec::new();
+        for item in &plan.items {
+            match &item.method {
+                InstallMethod::Bottle(bottle) => {
+                    if !self.has_populated_store_entry(&bottle.sha256)
+                        && !self.downloader.blob_cache().has_blob(&bottle.sha256)
+                    {
+                        missing.push(item.install_name.as_str());
+                    }
+                }
+                InstallMethod::Source(_) => {
+                    return Err(Error::NetworkFailure {
+                        message: format!(
+                            "offline: {} has no bottle for this platform and would be built from source",
+                            item.install_name
+                        ),
+                    });
+                }
+            }
+        }
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let missing = missing.join(" ");
+        Err(Error::NetworkFailure {
+            message: format!(
+                "offline: no local bottle for {missing}; run `zb fetch {missing}` on a connected machine and copy the zerobrew root"
+            ),
+        })
+    }
+
+    /// Whether the store holds an unpacked bottle for `sha256`. An empty
+    /// directory is what an interrupted extraction leaves behind.
+    pub(super) fn has_populated_store_entry(&self, sha256: &str) -> bool {
+        fs::read_dir(self.store.entry_path(sha256))
+            .map(|mut entries| entries.next().is_some())
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::cellar::Cellar;
+    use crate::installer::install::test_support::*;
+    use crate::network::api::ApiClient;
+    use crate::network::cache::ApiCache;
+    use crate::storage::blob::BlobCache;
+    use crate::storage::db::Database;
+    use crate::storage::store::Store;
+    use crate::{Installer, Linker};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn fetched_formula_installs_offline_from_the_store() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        let bottle = create_bottle_tarball("offlinepkg");
+        let bottle_sha = sha256_hex(&bottle);
+        let tag = get_test_bottle_tag();
+        let formula_json = format!(
+            r#"{{
+                "name": "offlinepkg",
+                "versions": {{ "stable": "1.0.0" }},
+                "dependencies": [],
+                "bottle": {{
+                    "stable": {{
+                        "files": {{
+                            "{tag}": {{
+                                "url": "{}/bottles/offlinepkg-1.0.0.{tag}.bottle.tar.gz",
+                                "sha256": "{bottle_sha}"
+                            }}
+                        }}
+                    }}
+                }}
+            }}"#,
+            mock_server.uri(),
+        );
+
+        Mock::given(method("GET"))
+            .and(path("/formula/offlinepkg.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(&formula_json))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/bottles/offlinepkg-1.0.0.{tag}.bottle.tar.gz"
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        fs::create_dir_all(root.join("db")).unwrap();
+
+        let api_client = ApiClient::with_base_url(format!("{}/formula", mock_server.uri()))
+            .unwrap()
+            .with_cache(ApiCache::in_memory().unwrap());
+        let mut installer = Installer::new(
+            api_client,
+            BlobCache::new(&root.join("cache")).unwrap(),
+            Store::new(&root).unwrap(),
+            Cellar::new(&root).unwrap(),
+            Linker::new(&prefix).unwrap(),
+            Database::open(&root.join("db/zb.sqlite3")).unwrap(),
+            prefix.clone(),
+            root.join("locks"),
+        );
+
+        let summary = installer
+            .fetch(&["offlinepkg".to_string()], false, None)
+            .await
+            .unwrap();
+        assert_eq!(summary.downloaded, ["offlinepkg"]);
+        assert_eq!(summary.unpacked, ["offlinepkg"]);
+        assert!(installer.db.get_installed("offlinepkg").is_none());
+
+        // The store entry alone is enough, even once the blob is pruned.
+        installer.downloader.remove_blob(&bottle_sha);
+        mock_server.verify().await;
+        mock_server.reset().await;
+
+        installer.offline = true;
+        installer.api_client.set_offline(true);
+        installer
+            .install(&["offlinepkg".to_string()], true)
+            .await
+            .unwrap();
+        assert!(prefix.join("bin/offlinepkg").exists());
+        assert!(mock_server.received_requests().await.unwrap().is_empty());
+
+        let err = installer
+            .install(&["otherpkg".to_string()], true)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("offline:"), "{err}");
+    }
+}
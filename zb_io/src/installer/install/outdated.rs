@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 
-use zb_core::{Error, select_bottle};
+use zb_core::{Error, Formula, is_head_version, select_bottle};
 
 use super::{Installer, OutdatedPackage};
+use crate::storage::db::InstalledKeg;
 
 impl Installer {
     pub async fn is_outdated(&self, name: &str) -> Result<Option<OutdatedPackage>, Error> {
@@ -11,37 +12,25 @@ impl Installer {
         })?;
 
         let formula = self.api_client.get_formula(name).await?;
-        let is_source = installed.store_key.starts_with("source:");
-
-        if is_source {
-            let current_version = formula.effective_version();
-            if installed.version == current_version {
-                Ok(None)
-            } else {
-                Ok(Some(OutdatedPackage {
-                    name: name.to_string(),
-                    installed_version: installed.version,
-                    installed_sha256: installed.store_key,
-                    current_version,
-                    current_sha256: String::new(),
-                    is_source_build: true,
-                }))
-            }
+        // A HEAD keg is outdated once its branch has new commits.
+        let head_version = if is_head_build(&installed) {
+            Some(self.current_head_version(&formula).await?)
         } else {
-            let bottle = select_bottle(&formula)?;
-            if installed.store_key == bottle.sha256 {
-                Ok(None)
-            } else {
-                Ok(Some(OutdatedPackage {
-                    name: name.to_string(),
-                    installed_version: installed.version,
-                    installed_sha256: installed.store_key,
-                    current_version: formula.effective_version(),
-                    current_sha256: bottle.sha256,
-                    is_source_build: false,
-                }))
-            }
+            None
+        };
+        outdated_against(installed, &formula, head_version)
+    }
+
+    /// Like [`Self::is_outdated`], but only from metadata already cached, so
+    /// it never waits on the network. `None` when nothing is cached for
+    /// `name`, and for HEAD kegs, whose new commits only the repository knows.
+    pub fn cached_outdated(&self, name: &str) -> Option<OutdatedPackage> {
+        let installed = self.db.get_installed(name)?;
+        if is_head_build(&installed) {
+            return None;
         }
+        let formula = self.api_client.cached_formula(name)?;
+        outdated_against(installed, &formula, None).ok().flatten()
     }
 
     pub async fn check_outdated(&self) -> Result<(Vec<OutdatedPackage>, Vec<String>), Error> {
@@ -89,7 +78,17 @@ impl Installer {
             let is_source = keg.store_key.starts_with("source:");
 
             if is_source {
-                let current_version = formula.effective_version();
+                let current_version = if is_head_version(&keg.version) {
+                    match self.current_head_version(&formula).await {
+                        Ok(version) => version,
+                        Err(e) => {
+                            warnings.push(format!("{}: {}", keg.name, e));
+                            continue;
+                        }
+                    }
+                } else {
+                    formula.effective_version()
+                };
                 if keg.version != current_version {
                     outdated.push(OutdatedPackage {
                         name: keg.name.clone(),
@@ -128,70 +127,60 @@ impl Installer {
     }
 }
 
+fn is_head_build(installed: &InstalledKeg) -> bool {
+    installed.store_key.starts_with("source:") && is_head_version(&installed.version)
+}
+
+/// Compare `installed` with `formula` as the API has it now. `head_version`
+/// is the version a HEAD keg would be rebuilt at.
+fn outdated_against(
+    installed: InstalledKeg,
+    formula: &Formula,
+    head_version: Option<String>,
+) -> Result<Option<OutdatedPackage>, Error> {
+    if installed.store_key.starts_with("source:") {
+        let current_version = head_version.unwrap_or_else(|| formula.effective_version());
+        if installed.version == current_version {
+            return Ok(None);
+        }
+        return Ok(Some(OutdatedPackage {
+            name: installed.name,
+            installed_version: installed.version,
+            installed_sha256: installed.store_key,
+            current_version,
+            current_sha256: String::new(),
+            is_source_build: true,
+        }));
+    }
+
+    let bottle = select_bottle(formula)?;
+    if installed.store_key == bottle.sha256 {
+        return Ok(None);
+    }
+    Ok(Some(OutdatedPackage {
+        name: installed.name,
+        installed_version: installed.version,
+        installed_sha256: installed.store_key,
+        current_version: formula.effective_version(),
+        current_sha256: bottle.sha256,
+        is_source_build: false,
+    }))
+}
+
 #[cfg(test)]
 mod tests {
-    use std::fs;
-
     use tempfile::TempDir;
     use wiremock::matchers::{method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
-    use crate::cellar::Cellar;
-    use crate::network::api::ApiClient;
-    use crate::storage::blob::BlobCache;
-    use crate::storage::db::Database;
-    use crate::storage::store::Store;
-    use crate::{Installer, Linker};
-
-    use super::super::test_support::get_test_bottle_tag;
-
-    fn formula_json(name: &str, version: &str, sha256: &str) -> String {
-        let tag = get_test_bottle_tag();
-        format!(
-            r#"{{
-                "name": "{}",
-                "versions": {{ "stable": "{}" }},
-                "dependencies": [],
-                "bottle": {{
-                    "stable": {{
-                        "files": {{
-                            "{}": {{
-                                "url": "https://example.com/{}-{}.{}.bottle.tar.gz",
-                                "sha256": "{}"
-                            }}
-                        }}
-                    }}
-                }}
-            }}"#,
-            name, version, tag, name, version, tag, sha256
-        )
-    }
+    use crate::Installer;
 
-    async fn test_installer() -> (Installer, MockServer, TempDir) {
+    use super::super::test_support::{formula_json_with_bottle, mock_api_client, test_installer};
+
+    async fn mock_installer() -> (Installer, MockServer, TempDir) {
         let mock_server = MockServer::start().await;
         let tmp = TempDir::new().unwrap();
-        let root = tmp.path().join("zerobrew");
-        let prefix = tmp.path().join("homebrew");
-        fs::create_dir_all(root.join("db")).unwrap();
-
-        let api_client =
-            ApiClient::with_base_url(format!("{}/formula", mock_server.uri())).unwrap();
-        let blob_cache = BlobCache::new(&root.join("cache")).unwrap();
-        let store = Store::new(&root).unwrap();
-        let cellar = Cellar::new(&root).unwrap();
-        let linker = Linker::new(&prefix).unwrap();
-        let db = Database::open(&root.join("db/zb.sqlite3")).unwrap();
-
-        let installer = Installer::new(
-            api_client,
-            blob_cache,
-            store,
-            cellar,
-            linker,
-            db,
-            prefix,
-            root.join("locks"),
-        );
+        let installer = test_installer(tmp.path(), mock_api_client(&mock_server));
         (installer, mock_server, tmp)
     }
 
@@ -212,28 +201,7 @@ mod tests {
             .mount(&mock_server)
             .await;
 
-        let root = tmp.path().join("zerobrew");
-        let prefix = tmp.path().join("homebrew");
-        fs::create_dir_all(root.join("db")).unwrap();
-
-        let api_client =
-            ApiClient::with_base_url(format!("{}/formula", mock_server.uri())).unwrap();
-        let blob_cache = BlobCache::new(&root.join("cache")).unwrap();
-        let store = Store::new(&root).unwrap();
-        let cellar = Cellar::new(&root).unwrap();
-        let linker = Linker::new(&prefix).unwrap();
-        let db = Database::open(&root.join("db/zb.sqlite3")).unwrap();
-
-        let installer = Installer::new(
-            api_client,
-            blob_cache,
-            store,
-            cellar,
-            linker,
-            db,
-            prefix,
-            root.join("locks"),
-        );
+        let installer = test_installer(tmp.path(), mock_api_client(&mock_server));
 
         let suggestions = installer.suggest_formulas("pythn", 3).await.unwrap();
         assert_eq!(suggestions.first().map(String::as_str), Some("python"));
@@ -241,7 +209,7 @@ mod tests {
 
     #[tokio::test]
     async fn is_outdated_returns_none_when_sha256_matches() {
-        let (mut installer, mock_server, _tmp) = test_installer().await;
+        let (mut installer, mock_server, _tmp) = mock_installer().await;
         let sha = "abc123def456";
 
         {
@@ -253,7 +221,13 @@ mod tests {
         Mock::given(method("GET"))
             .and(path("/formula/jq.json"))
             .respond_with(
-                ResponseTemplate::new(200).set_body_string(formula_json("jq", "1.7.1", sha)),
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "jq",
+                    "1.7.1",
+                    &[],
+                    "https://example.com",
+                    sha,
+                )),
             )
             .mount(&mock_server)
             .await;
@@ -264,7 +238,7 @@ mod tests {
 
     #[tokio::test]
     async fn is_outdated_returns_some_when_sha256_differs() {
-        let (mut installer, mock_server, _tmp) = test_installer().await;
+        let (mut installer, mock_server, _tmp) = mock_installer().await;
 
         {
             let tx = installer.db.transaction().unwrap();
@@ -274,11 +248,15 @@ mod tests {
 
         Mock::given(method("GET"))
             .and(path("/formula/jq.json"))
-            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(
-                "jq",
-                "1.7.1",
-                "new_sha256",
-            )))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "jq",
+                    "1.7.1",
+                    &[],
+                    "https://example.com",
+                    "new_sha256",
+                )),
+            )
             .mount(&mock_server)
             .await;
 
@@ -289,9 +267,50 @@ mod tests {
         assert!(!result.is_source_build);
     }
 
+    #[tokio::test]
+    async fn cached_outdated_never_sends_a_request() {
+        let (mut installer, mock_server, _tmp) = mock_installer().await;
+
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("jq", "1.7.0", "old_sha256").unwrap();
+            tx.commit().unwrap();
+        }
+
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "jq",
+                    "1.7.1",
+                    &[],
+                    "https://example.com",
+                    "new_sha256",
+                )),
+            )
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        assert!(installer.cached_outdated("jq").is_none());
+
+        installer.api_client.register_formula(
+            serde_json::from_str(&formula_json_with_bottle(
+                "jq",
+                "1.7.1",
+                &[],
+                "https://example.com",
+                "new_sha256",
+            ))
+            .unwrap(),
+        );
+        let result = installer.cached_outdated("jq").unwrap();
+        assert_eq!(result.installed_version, "1.7.0");
+        assert_eq!(result.current_version, "1.7.1");
+    }
+
     #[tokio::test]
     async fn is_outdated_errors_for_not_installed() {
-        let (installer, _mock_server, _tmp) = test_installer().await;
+        let (installer, _mock_server, _tmp) = mock_installer().await;
 
         let err = installer.is_outdated("jq").await.unwrap_err();
         assert!(matches!(err, zb_core::Error::NotInstalled { .. }));
@@ -299,7 +318,7 @@ mod tests {
 
     #[tokio::test]
     async fn is_outdated_source_build_compares_version_only() {
-        let (mut installer, mock_server, _tmp) = test_installer().await;
+        let (mut installer, mock_server, _tmp) = mock_installer().await;
 
         {
             let tx = installer.db.transaction().unwrap();
@@ -309,11 +328,15 @@ mod tests {
 
         Mock::given(method("GET"))
             .and(path("/formula/jq.json"))
-            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(
-                "jq",
-                "1.7.1",
-                "irrelevant",
-            )))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "jq",
+                    "1.7.1",
+                    &[],
+                    "https://example.com",
+                    "irrelevant",
+                )),
+            )
             .mount(&mock_server)
             .await;
 
@@ -323,7 +346,7 @@ mod tests {
 
     #[tokio::test]
     async fn is_outdated_source_build_detects_new_version() {
-        let (mut installer, mock_server, _tmp) = test_installer().await;
+        let (mut installer, mock_server, _tmp) = mock_installer().await;
 
         {
             let tx = installer.db.transaction().unwrap();
@@ -333,11 +356,15 @@ mod tests {
 
         Mock::given(method("GET"))
             .and(path("/formula/jq.json"))
-            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(
-                "jq",
-                "1.7.1",
-                "irrelevant",
-            )))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "jq",
+                    "1.7.1",
+                    &[],
+                    "https://example.com",
+                    "irrelevant",
+                )),
+            )
             .mount(&mock_server)
             .await;
 
@@ -349,7 +376,7 @@ mod tests {
 
     #[tokio::test]
     async fn check_outdated_empty_when_nothing_installed() {
-        let (installer, _mock_server, _tmp) = test_installer().await;
+        let (installer, _mock_server, _tmp) = mock_installer().await;
 
         let (outdated, warnings) = installer.check_outdated().await.unwrap();
         assert!(outdated.is_empty());
@@ -358,7 +385,7 @@ mod tests {
 
     #[tokio::test]
     async fn check_outdated_continues_on_network_failure() {
-        let (mut installer, mock_server, _tmp) = test_installer().await;
+        let (mut installer, mock_server, _tmp) = mock_installer().await;
 
         {
             let tx = installer.db.transaction().unwrap();
@@ -367,7 +394,10 @@ mod tests {
             tx.commit().unwrap();
         }
 
-        let bulk = format!("[{}]", formula_json("good", "2.0.0", "new_sha"));
+        let bulk = format!(
+            "[{}]",
+            formula_json_with_bottle("good", "2.0.0", &[], "https://example.com", "new_sha")
+        );
         Mock::given(method("GET"))
             .and(path("/formula.json"))
             .respond_with(ResponseTemplate::new(200).set_body_string(bulk))
@@ -389,7 +419,7 @@ mod tests {
 
     #[tokio::test]
     async fn check_outdated_warns_on_missing_bottle() {
-        let (mut installer, mock_server, _tmp) = test_installer().await;
+        let (mut installer, mock_server, _tmp) = mock_installer().await;
 
         {
             let tx = installer.db.transaction().unwrap();
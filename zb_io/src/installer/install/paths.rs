@@ -0,0 +1,232 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use zb_core::{Error, formula_token};
+
+use super::Installer;
+
+/// Where an installed formula lives on disk and what it put on PATH.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KegPaths {
+    pub keg: PathBuf,
+    pub opt: PathBuf,
+    /// Entries in `prefix/bin` that are symlinks into `keg`, sorted.
+    pub linked_binaries: Vec<PathBuf>,
+}
+
+/// The installed formula a file in the prefix belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileOwner {
+    pub name: String,
+    pub version: String,
+    /// The file as asked about, e.g. a link in `prefix/bin`.
+    pub path: PathBuf,
+    /// Where it lives inside the keg.
+    pub target: PathBuf,
+}
+
+impl Installer {
+    /// The formula that put `command` on PATH by linking it into
+    /// `prefix/bin` or `prefix/sbin`.
+    pub fn which(&self, command: &str) -> Result<Option<FileOwner>, Error> {
+        for dir in ["bin", "sbin"] {
+            let path = self.prefix.join(dir).join(command);
+            if let Some(owner) = self.provides(&path)?.into_iter().next() {
+                return Ok(Some(owner));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The formulas owning `path`: the kegs recorded as linking it, or
+    /// failing that the keg it lies in, directly or through a linked
+    /// directory or `opt` link.
+    pub fn provides(&self, path: &Path) -> Result<Vec<FileOwner>, Error> {
+        let path = normalize(path);
+        let owners: Vec<FileOwner> = self
+            .db
+            .keg_file_owners(&path.to_string_lossy())?
+            .into_iter()
+            .map(|record| FileOwner {
+                name: record.name,
+                version: record.version,
+                path: path.clone(),
+                target: PathBuf::from(record.target_path),
+            })
+            .collect();
+        if !owners.is_empty() {
+            return Ok(owners);
+        }
+
+        let resolved = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        Ok(self.keg_owner(&path, &resolved)?.into_iter().collect())
+    }
+
+    /// The installed keg `resolved` lies in, going by its
+    /// `Cellar/<name>/<version>` components.
+    fn keg_owner(&self, path: &Path, resolved: &Path) -> Result<Option<FileOwner>, Error> {
+        let cellar = self.cellar.path();
+        let canonical_cellar = fs::canonicalize(cellar).unwrap_or_else(|_| cellar.to_path_buf());
+        let Ok(rest) = resolved
+            .strip_prefix(&canonical_cellar)
+            .or_else(|_| resolved.strip_prefix(cellar))
+        else {
+            return Ok(None);
+        };
+        let mut parts = rest.components().map(|c| c.as_os_str().to_string_lossy());
+        let (Some(token), Some(version)) = (parts.next(), parts.next()) else {
+            return Ok(None);
+        };
+
+        // Only an installed keg counts, not a stray directory in the Cellar.
+        let owner = self
+            .db
+            .list_installed()?
+            .into_iter()
+            .find(|keg| formula_token(&keg.name) == token && keg.version == version)
+            .map(|keg| FileOwner {
+                name: keg.name,
+                version: keg.version,
+                path: path.to_path_buf(),
+                target: resolved.to_path_buf(),
+            });
+        Ok(owner)
+    }
+
+    pub fn keg_paths(&self, name: &str) -> Result<KegPaths, Error> {
+        let installed = self.db.get_installed(name).ok_or(Error::NotInstalled {
+            name: name.to_string(),
+        })?;
+        let token = formula_token(&installed.name);
+        let keg = self.cellar.keg_path(token, &installed.version);
+
+        Ok(KegPaths {
+            opt: self.linker.opt_link_path(token),
+            linked_binaries: links_into(&self.prefix.join("bin"), &keg),
+            keg,
+        })
+    }
+}
+
+/// Symlinks directly in `dir` whose target lies inside `keg`. Relative
+/// targets are resolved against `dir`; the target doesn't have to exist.
+fn links_into(dir: &Path, keg: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut links: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            fs::read_link(path).is_ok_and(|target| {
+                let target = dir.join(target);
+                normalize(&target).starts_with(keg)
+            })
+        })
+        .collect();
+    links.sort();
+    links
+}
+
+/// Collapse `.` and `..` lexically, so `bin/../Cellar/foo` compares equal
+/// to `Cellar/foo` without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use crate::network::api::ApiClient;
+
+    use super::super::test_support::test_installer;
+
+    #[test]
+    fn keg_paths_lists_only_binaries_linked_from_this_keg() {
+        let tmp = TempDir::new().unwrap();
+        let prefix = tmp.path().join("homebrew");
+
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+
+        let keg = installer.keg_path("jq", "1.7.1");
+        fs::create_dir_all(keg.join("bin")).unwrap();
+        fs::write(keg.join("bin/jq"), "jq").unwrap();
+        fs::write(keg.join("bin/jq-helper"), "helper").unwrap();
+        let other = installer.keg_path("wget", "1.24.5");
+        fs::create_dir_all(other.join("bin")).unwrap();
+        fs::write(other.join("bin/wget"), "wget").unwrap();
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("jq", "1.7.1", "jqkey").unwrap();
+            tx.commit().unwrap();
+        }
+        installer.linker.link_keg(&keg).unwrap();
+        installer.linker.link_keg(&other).unwrap();
+        installer.linker.link_opt(&keg).unwrap();
+
+        let paths = installer.keg_paths("jq").unwrap();
+
+        assert_eq!(paths.keg, keg);
+        assert_eq!(paths.opt, prefix.join("opt/jq"));
+        assert_eq!(fs::read_link(&paths.opt).unwrap(), keg);
+        assert_eq!(
+            paths.linked_binaries,
+            vec![prefix.join("bin/jq"), prefix.join("bin/jq-helper")]
+        );
+        assert!(installer.keg_paths("wget").is_err());
+    }
+
+    #[test]
+    fn which_and_provides_find_the_owning_keg() {
+        let tmp = TempDir::new().unwrap();
+        let prefix = tmp.path().join("homebrew");
+
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+
+        let keg = installer.keg_path("jq", "1.7.1");
+        fs::create_dir_all(keg.join("bin")).unwrap();
+        fs::create_dir_all(keg.join("share/doc")).unwrap();
+        fs::write(keg.join("bin/jq"), "jq").unwrap();
+        fs::write(keg.join("share/doc/README"), "readme").unwrap();
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("jq", "1.7.1", "jqkey").unwrap();
+            tx.commit().unwrap();
+        }
+        let linked = installer.linker.link_keg(&keg).unwrap();
+        installer.record_linked_files("jq", "1.7.1", &linked);
+        installer.linker.link_opt(&keg).unwrap();
+
+        let owner = installer.which("jq").unwrap().unwrap();
+        assert_eq!(owner.name, "jq");
+        assert_eq!(owner.version, "1.7.1");
+        assert_eq!(owner.path, prefix.join("bin/jq"));
+        assert!(installer.which("wget").unwrap().is_none());
+
+        let owners = installer
+            .provides(&prefix.join("opt/jq/share/doc/README"))
+            .unwrap();
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners[0].name, "jq");
+        assert!(
+            installer
+                .provides(&tmp.path().join("elsewhere"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use zb_core::Error;
+
+use super::{InstallPlan, Installer, SkippedPin};
+
+impl Installer {
+    /// Pin `name` at its installed version so bulk upgrades skip it.
+    pub fn pin(&mut self, name: &str) -> Result<(), Error> {
+        let _lock = self.install_lock()?;
+        let installed = self.db.get_installed(name).ok_or(Error::NotInstalled {
+            name: name.to_string(),
+        })?;
+        self.db.pin(name, &installed.version)
+    }
+
+    /// Returns whether `name` was pinned.
+    pub fn unpin(&mut self, name: &str) -> Result<bool, Error> {
+        let _lock = self.install_lock()?;
+        self.db.unpin(name)
+    }
+
+    pub fn is_pinned(&self, name: &str) -> Result<bool, Error> {
+        self.db.is_pinned(name)
+    }
+
+    /// Drop plan items that would replace a pinned keg with another
+    /// version, whatever planned them: a dependency of a new install, a
+    /// migration or a bundle. Reinstalling the pinned version is left in.
+    /// Returns what was dropped, so callers can say so.
+    pub(super) fn retain_unpinned(&self, plan: &mut InstallPlan) -> Result<Vec<SkippedPin>, Error> {
+        if self.ignore_pins {
+            return Ok(Vec::new());
+        }
+        let pinned = self.db.list_pinned()?;
+        if pinned.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut skipped = Vec::new();
+        plan.items.retain(|item| {
+            if !pinned.contains(&item.install_name) {
+                return true;
+            }
+            let Some(installed) = self.db.get_installed(&item.install_name) else {
+                return true;
+            };
+            let planned = item.version();
+            if installed.version == planned {
+                return true;
+            }
+            skipped.push(SkippedPin {
+                name: item.install_name.clone(),
+                pinned_version: installed.version,
+                planned_version: planned,
+            });
+            false
+        });
+        Ok(skipped)
+    }
+}
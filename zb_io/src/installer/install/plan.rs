@@ -1,9 +1,10 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
 
 use tracing::warn;
-use zb_core::{BuildPlan, Error, Formula, InstallMethod, select_bottle};
+use zb_core::{DependencyOptions, Error, Formula, InstallMethod, select_bottle_with_policy};
 
 use super::{InstallPlan, Installer, PlanFailure, PlannedInstall};
+use crate::progress::InstallProgress;
 
 impl Installer {
     pub async fn plan(&self, names: &[String]) -> Result<InstallPlan, Error> {
@@ -15,18 +16,120 @@ impl Installer {
         names: &[String],
         build_from_source: bool,
     ) -> Result<InstallPlan, Error> {
+        self.plan_closure(names, |_| build_from_source).await
+    }
+
+    /// Plan `names` so that every dependency is built from source while the
+    /// requested formulas themselves still use a bottle when one exists.
+    pub async fn plan_dependencies_from_source(
+        &self,
+        names: &[String],
+    ) -> Result<InstallPlan, Error> {
+        self.plan_closure(names, |install_name| {
+            !names.iter().any(|n| n == install_name)
+        })
+        .await
+    }
+
+    async fn plan_closure(
+        &self,
+        names: &[String],
+        from_source: impl Fn(&str) -> bool,
+    ) -> Result<InstallPlan, Error> {
+        self.report(InstallProgress::ResolveStarted {
+            names: names.to_vec(),
+        });
         let formulas = self.fetch_all_formulas(names).await?;
-        let ordered = zb_core::resolve_closure(names, &formulas)?;
+        let ordered = zb_core::resolve_closure_with(names, &formulas, &self.dependency_options)?;
 
         let mut items = Vec::with_capacity(ordered.len());
         for install_name in ordered {
             let formula = formulas.get(&install_name).cloned().unwrap();
-            items.push(self.plan_item(install_name, formula, build_from_source)?);
+            let build_from_source = from_source(&install_name);
+            // Optional dependencies asked for by name are wanted for their
+            // own sake, so `autoremove` leaves them alone.
+            let requested = names.contains(&install_name)
+                || self.dependency_options.with.contains(&install_name);
+            items.push(self.plan_item(install_name, formula, build_from_source, requested)?);
         }
 
+        let build_items = self.plan_source_build_dependencies(&items).await?;
+        items.splice(0..0, build_items);
+
+        self.report(InstallProgress::ResolveCompleted { count: items.len() });
         Ok(InstallPlan { items })
     }
 
+    /// The `:build` dependencies of the source builds in `items`, with their
+    /// runtime closure, that neither `items` nor the Cellar already has.
+    /// Bottles never need them. They come first in the plan so they're in
+    /// place before the builds that use them.
+    async fn plan_source_build_dependencies(
+        &self,
+        items: &[PlannedInstall],
+    ) -> Result<Vec<PlannedInstall>, Error> {
+        let planned = |name: &str| items.iter().any(|item| item.install_name == name);
+        let mut build_deps: Vec<String> = Vec::new();
+        for item in items {
+            if !matches!(item.method, InstallMethod::Source(_)) {
+                continue;
+            }
+            for dep in item.formula.all_build_dependencies() {
+                if !planned(&dep) && !self.is_installed(&dep) && !build_deps.contains(&dep) {
+                    build_deps.push(dep);
+                }
+            }
+        }
+        if build_deps.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let formulas = self.fetch_all_formulas(&build_deps).await?;
+        let ordered =
+            zb_core::resolve_closure_with(&build_deps, &formulas, &self.dependency_options)?;
+        let mut build_items = Vec::new();
+        for install_name in ordered {
+            if planned(&install_name) || self.is_installed(&install_name) {
+                continue;
+            }
+            let formula = formulas.get(&install_name).cloned().unwrap();
+            let mut item = self.plan_item(install_name, formula, false, false)?;
+            item.build_only = true;
+            build_items.push(item);
+        }
+        Ok(build_items)
+    }
+
+    /// Plan only the `:build` dependencies of `names` (and their runtime
+    /// closure), leaving out the formulas themselves, their runtime-only
+    /// dependencies and anything already installed.
+    pub async fn plan_build_dependencies(
+        &self,
+        names: &[String],
+        build_from_source: bool,
+    ) -> Result<InstallPlan, Error> {
+        let mut build_deps: Vec<String> = Vec::new();
+        for name in names {
+            let formula = self.api_client.get_formula(name).await?;
+            for dep in formula.all_build_dependencies() {
+                if !names.contains(&dep) && !build_deps.contains(&dep) {
+                    build_deps.push(dep);
+                }
+            }
+        }
+
+        if build_deps.is_empty() {
+            return Ok(InstallPlan { items: Vec::new() });
+        }
+
+        let mut plan = self
+            .plan_with_options(&build_deps, build_from_source)
+            .await?;
+        plan.items
+            .retain(|item| !self.is_installed(&item.install_name));
+        Ok(plan)
+    }
+
     pub async fn plan_best_effort(
         &self,
         names: &[String],
@@ -59,7 +162,9 @@ impl Installer {
                 continue;
             }
 
-            if let Some(failure) = root_dependency_failure(name, &formulas, &fetch_failures) {
+            if let Some(failure) =
+                root_dependency_failure(name, &formulas, &fetch_failures, &self.dependency_options)
+            {
                 failures.push(failure);
                 continue;
             }
@@ -68,11 +173,17 @@ impl Installer {
         }
 
         if !valid_roots.is_empty() {
-            match zb_core::resolve_closure(&valid_roots, &formulas) {
+            match zb_core::resolve_closure_with(&valid_roots, &formulas, &self.dependency_options) {
                 Ok(ordered) => {
                     for install_name in ordered {
                         let formula = formulas.get(&install_name).cloned().unwrap();
-                        match self.plan_item(install_name.clone(), formula, build_from_source) {
+                        let requested = valid_roots.contains(&install_name);
+                        match self.plan_item(
+                            install_name.clone(),
+                            formula,
+                            build_from_source,
+                            requested,
+                        ) {
                             Ok(item) => items.push(item),
                             Err(error) => failures.push(PlanFailure {
                                 name: install_name,
@@ -98,37 +209,21 @@ impl Installer {
         install_name: String,
         formula: Formula,
         build_from_source: bool,
+        requested: bool,
     ) -> Result<PlannedInstall, Error> {
-        let method = if build_from_source {
-            match BuildPlan::from_formula(&formula, &self.prefix) {
-                Some(plan) => InstallMethod::Source(plan),
-                None => match select_bottle(&formula) {
-                    Ok(bottle) => InstallMethod::Bottle(bottle),
-                    Err(_) => {
-                        return Err(Error::UnsupportedBottle {
-                            name: formula.name.clone(),
-                        });
-                    }
-                },
-            }
-        } else {
-            match select_bottle(&formula) {
-                Ok(bottle) => InstallMethod::Bottle(bottle),
-                Err(_) => match BuildPlan::from_formula(&formula, &self.prefix) {
-                    Some(plan) => InstallMethod::Source(plan),
-                    None => {
-                        return Err(Error::UnsupportedBottle {
-                            name: formula.name.clone(),
-                        });
-                    }
-                },
-            }
-        };
+        let method = InstallMethod::select_with_policy(
+            &formula,
+            &self.prefix,
+            build_from_source,
+            &self.bottle_policy,
+        )?;
 
         Ok(PlannedInstall {
             install_name,
             formula,
             method,
+            requested,
+            build_only: false,
         })
     }
 
@@ -142,19 +237,17 @@ impl Installer {
         let mut to_fetch: Vec<String> = names.to_vec();
 
         while !to_fetch.is_empty() {
+            // A dependency shared by several formulas in the previous batch
+            // is queued once per dependent; fetch it once.
             let batch: Vec<String> = to_fetch
                 .drain(..)
-                .filter(|n| !fetched.contains(n))
+                .filter(|n| fetched.insert(n.clone()))
                 .collect();
 
             if batch.is_empty() {
                 break;
             }
 
-            for n in &batch {
-                fetched.insert(n.clone());
-            }
-
             let futures: Vec<_> = batch
                 .iter()
                 .map(|n| self.api_client.get_formula(n))
@@ -172,7 +265,9 @@ impl Installer {
                     }
                 };
 
-                if select_bottle(&formula).is_err() && !formula.has_source_url() {
+                if select_bottle_with_policy(&formula, &self.bottle_policy).is_err()
+                    && !formula.has_source_url()
+                {
                     warn!(
                         formula = %formula.name,
                         "skipping formula with no bottle or source available for this platform"
@@ -186,7 +281,7 @@ impl Installer {
                     continue;
                 }
 
-                for dep in formula.runtime_dependencies() {
+                for dep in formula.dependencies_with(&self.dependency_options) {
                     if !fetched.contains(&dep)
                         && !to_fetch.contains(&dep)
                         && !failures.contains_key(&dep)
@@ -202,7 +297,7 @@ impl Installer {
         (formulas, failures)
     }
 
-    async fn fetch_all_formulas(
+    pub(super) async fn fetch_all_formulas(
         &self,
         names: &[String],
     ) -> Result<BTreeMap<String, Formula>, Error> {
@@ -239,7 +334,9 @@ impl Installer {
                     Err(e) => return Err(e),
                 };
 
-                if select_bottle(&formula).is_err() && !formula.has_source_url() {
+                if select_bottle_with_policy(&formula, &self.bottle_policy).is_err()
+                    && !formula.has_source_url()
+                {
                     warn!(
                         formula = %formula.name,
                         "skipping formula with no bottle or source available for this platform"
@@ -247,7 +344,7 @@ impl Installer {
                     continue;
                 }
 
-                for dep in formula.runtime_dependencies() {
+                for dep in formula.dependencies_with(&self.dependency_options) {
                     if !fetched.contains(&dep) && !to_fetch.contains(&dep) {
                         to_fetch.push(dep);
                     }
@@ -265,6 +362,7 @@ fn root_dependency_failure(
     root: &str,
     formulas: &BTreeMap<String, Formula>,
     fetch_failures: &HashMap<String, Error>,
+    options: &DependencyOptions,
 ) -> Option<PlanFailure> {
     let mut seen = HashSet::new();
     let mut stack = vec![root.to_string()];
@@ -278,7 +376,7 @@ fn root_dependency_failure(
             continue;
         };
 
-        for dep in formula.runtime_dependencies() {
+        for dep in formula.dependencies_with(options) {
             if let Some(error) = fetch_failures.get(&dep) {
                 return Some(PlanFailure {
                     name: root.to_string(),
@@ -432,6 +530,29 @@ end
             .respond_with(ResponseTemplate::new(200).set_body_string(formula_json))
             .mount(&mock_server)
             .await;
+        let pkgconf_json = format!(
+            r#"{{
+                "name": "pkgconf",
+                "versions": {{ "stable": "2.3.0" }},
+                "dependencies": [],
+                "bottle": {{
+                    "stable": {{
+                        "files": {{
+                            "{tag}": {{
+                                "url": "https://example.com/pkgconf.bottle.tar.gz",
+                                "sha256": "aabbccdd"
+                            }}
+                        }}
+                    }}
+                }}
+            }}"#,
+            tag = get_test_bottle_tag()
+        );
+        Mock::given(method("GET"))
+            .and(path("/formula/pkgconf.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(pkgconf_json))
+            .mount(&mock_server)
+            .await;
 
         let root = tmp.path().join("zerobrew");
         let prefix = tmp.path().join("homebrew");
@@ -458,20 +579,97 @@ end
 
         let plan = installer.plan(&["nobottle".to_string()]).await.unwrap();
 
-        assert_eq!(plan.items.len(), 1);
-        assert_eq!(plan.items[0].formula.name, "nobottle");
+        // The source build pulls in its build dependency ahead of itself,
+        // marked so `autoremove` can drop it afterwards.
+        assert_eq!(plan.items.len(), 2);
+        assert_eq!(plan.items[0].formula.name, "pkgconf");
+        assert!(plan.items[0].build_only);
+        assert!(!plan.items[0].requested);
+        assert_eq!(plan.items[1].formula.name, "nobottle");
+        assert!(!plan.items[1].build_only);
         assert!(matches!(
-            plan.items[0].method,
+            plan.items[1].method,
             zb_core::InstallMethod::Source(_)
         ));
 
-        if let zb_core::InstallMethod::Source(ref bp) = plan.items[0].method {
+        if let zb_core::InstallMethod::Source(ref bp) = plan.items[1].method {
             assert_eq!(bp.source_url, "https://example.com/nobottle-1.0.0.tar.gz");
             assert_eq!(bp.formula_name, "nobottle");
             assert_eq!(bp.build_dependencies, vec!["pkgconf"]);
         }
     }
 
+    #[tokio::test]
+    async fn dependencies_from_source_keeps_the_target_bottle() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let tag = get_test_bottle_tag();
+
+        let formula = |name: &str, deps: &str| {
+            format!(
+                r#"{{
+                    "name": "{name}",
+                    "versions": {{ "stable": "1.0.0" }},
+                    "dependencies": [{deps}],
+                    "urls": {{
+                        "stable": {{
+                            "url": "https://example.com/{name}-1.0.0.tar.gz",
+                            "checksum": "abc123"
+                        }}
+                    }},
+                    "ruby_source_path": "Formula/{name}.rb",
+                    "bottle": {{
+                        "stable": {{
+                            "files": {{
+                                "{tag}": {{
+                                    "url": "https://example.com/{name}.bottle.tar.gz",
+                                    "sha256": "aabbccdd"
+                                }}
+                            }}
+                        }}
+                    }}
+                }}"#
+            )
+        };
+        for (name, deps) in [("app", r#""libdep""#), ("libdep", "")] {
+            Mock::given(method("GET"))
+                .and(path(format!("/formula/{name}.json")))
+                .respond_with(ResponseTemplate::new(200).set_body_string(formula(name, deps)))
+                .mount(&mock_server)
+                .await;
+        }
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        fs::create_dir_all(root.join("db")).unwrap();
+
+        let installer = Installer::new(
+            ApiClient::with_base_url(format!("{}/formula", mock_server.uri())).unwrap(),
+            BlobCache::new(&root.join("cache")).unwrap(),
+            Store::new(&root).unwrap(),
+            Cellar::new(&root).unwrap(),
+            Linker::new(&prefix).unwrap(),
+            Database::open(&root.join("db/zb.sqlite3")).unwrap(),
+            prefix.clone(),
+            root.join("locks"),
+        );
+
+        let plan = installer
+            .plan_dependencies_from_source(&["app".to_string()])
+            .await
+            .unwrap();
+
+        assert_eq!(plan.install_names(), vec!["libdep", "app"]);
+        assert!(matches!(
+            plan.items[0].method,
+            zb_core::InstallMethod::Source(_)
+        ));
+        assert!(matches!(
+            plan.items[1].method,
+            zb_core::InstallMethod::Bottle(_)
+        ));
+    }
+
     #[tokio::test]
     async fn prefers_bottle_over_source() {
         let mock_server = MockServer::start().await;
@@ -542,6 +740,146 @@ end
         ));
     }
 
+    #[tokio::test]
+    async fn replanning_the_same_closure_reuses_parsed_formulas() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let tag = get_test_bottle_tag();
+
+        let formula = |name: &str, deps: &str| {
+            format!(
+                r#"{{
+                    "name": "{name}",
+                    "versions": {{ "stable": "1.0.0" }},
+                    "dependencies": [{deps}],
+                    "bottle": {{
+                        "stable": {{
+                            "files": {{
+                                "{tag}": {{
+                                    "url": "https://example.com/{name}.bottle.tar.gz",
+                                    "sha256": "aabbccdd"
+                                }}
+                            }}
+                        }}
+                    }}
+                }}"#
+            )
+        };
+        for (name, deps) in [
+            ("app", r#""liba", "libb""#),
+            ("liba", r#""libb""#),
+            ("libb", ""),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(format!("/formula/{name}.json")))
+                .respond_with(ResponseTemplate::new(200).set_body_string(formula(name, deps)))
+                .mount(&mock_server)
+                .await;
+        }
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        fs::create_dir_all(root.join("db")).unwrap();
+
+        let installer = Installer::new(
+            ApiClient::with_base_url(format!("{}/formula", mock_server.uri())).unwrap(),
+            BlobCache::new(&root.join("cache")).unwrap(),
+            Store::new(&root).unwrap(),
+            Cellar::new(&root).unwrap(),
+            Linker::new(&prefix).unwrap(),
+            Database::open(&root.join("db/zb.sqlite3")).unwrap(),
+            prefix.clone(),
+            root.join("locks"),
+        );
+
+        let first = installer.plan(&["app".to_string()]).await.unwrap();
+        assert_eq!(first.items.len(), 3);
+        assert_eq!(installer.api_client.formula_parses(), 3);
+
+        let second = installer.plan(&["app".to_string()]).await.unwrap();
+        assert_eq!(second.install_names(), first.install_names());
+        assert_eq!(
+            installer.api_client.formula_parses(),
+            3,
+            "unchanged bodies must not be parsed again"
+        );
+    }
+
+    #[tokio::test]
+    async fn planning_several_roots_resolves_shared_dependencies_once() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let tag = get_test_bottle_tag();
+
+        let formula = |name: &str, deps: &str| {
+            format!(
+                r#"{{
+                    "name": "{name}",
+                    "versions": {{ "stable": "1.0.0" }},
+                    "dependencies": [{deps}],
+                    "bottle": {{
+                        "stable": {{
+                            "files": {{
+                                "{tag}": {{
+                                    "url": "https://example.com/{name}.bottle.tar.gz",
+                                    "sha256": "aabbccdd"
+                                }}
+                            }}
+                        }}
+                    }}
+                }}"#
+            )
+        };
+        for (name, deps) in [
+            ("app1", r#""libshared""#),
+            ("app2", r#""libshared", "libbase""#),
+            ("libshared", r#""libbase""#),
+            ("libbase", ""),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(format!("/formula/{name}.json")))
+                .respond_with(ResponseTemplate::new(200).set_body_string(formula(name, deps)))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        fs::create_dir_all(root.join("db")).unwrap();
+
+        let installer = Installer::new(
+            ApiClient::with_base_url(format!("{}/formula", mock_server.uri())).unwrap(),
+            BlobCache::new(&root.join("cache")).unwrap(),
+            Store::new(&root).unwrap(),
+            Cellar::new(&root).unwrap(),
+            Linker::new(&prefix).unwrap(),
+            Database::open(&root.join("db/zb.sqlite3")).unwrap(),
+            prefix.clone(),
+            root.join("locks"),
+        );
+
+        let plan = installer
+            .plan(&["app1".to_string(), "app2".to_string()])
+            .await
+            .unwrap();
+        let names = plan.install_names();
+        assert_eq!(names.len(), 4, "each formula is planned once: {names:?}");
+        let position = |name: &str| names.iter().position(|n| n == name).unwrap();
+        assert!(position("libbase") < position("libshared"));
+        assert!(position("libshared") < position("app1"));
+        assert!(position("libshared") < position("app2"));
+
+        let requested: Vec<&str> = plan
+            .items
+            .iter()
+            .filter(|item| item.requested)
+            .map(|item| item.install_name.as_str())
+            .collect();
+        assert_eq!(requested.len(), 2);
+        assert!(requested.contains(&"app1") && requested.contains(&"app2"));
+    }
+
     #[tokio::test]
     async fn errors_when_no_bottle_and_no_source() {
         let mock_server = MockServer::start().await;
@@ -0,0 +1,49 @@
+use tracing::warn;
+use zb_core::{Error, Formula};
+
+use crate::build::BuildExecutor;
+
+use super::Installer;
+
+impl Installer {
+    /// Rerun the `post_install` block of the installed formula `name`.
+    /// Returns `false` when the formula doesn't define one.
+    pub async fn post_install(&self, name: &str) -> Result<bool, Error> {
+        let keg = self
+            .db
+            .get_installed(name)
+            .ok_or_else(|| Error::NotInstalled {
+                name: name.to_string(),
+            })?;
+        let formula = self.get_formula(name).await?;
+        if !formula.post_install_defined {
+            return Ok(false);
+        }
+        self.run_post_install(&formula, &keg.version).await?;
+        Ok(true)
+    }
+
+    /// Run `post_install` for a keg that was just installed. Like Homebrew,
+    /// a failure is only warned about: the keg is in place and linked, and
+    /// `zb postinstall` can retry the step.
+    pub(super) async fn post_install_after_install(&self, install_name: &str, formula: &Formula) {
+        let Some(keg) = self.db.get_installed(install_name) else {
+            return;
+        };
+        if let Err(e) = self.run_post_install(formula, &keg.version).await {
+            warn!(
+                formula = %formula.name,
+                error = %e,
+                "post_install did not complete; rerun it with `zb postinstall {install_name}`"
+            );
+        }
+    }
+
+    async fn run_post_install(&self, formula: &Formula, version: &str) -> Result<(), Error> {
+        let formula_rb = self.fetch_formula_file(formula).await?;
+        BuildExecutor::new(self.prefix.clone())
+            .with_options(self.build_options.clone())
+            .run_post_install(&formula.name, version, self.cellar.path(), &formula_rb)
+            .await
+    }
+}
@@ -0,0 +1,737 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use zb_core::{Error, formula_token};
+
+use crate::build::executor::build_work_root;
+use crate::build::log::superseded_build_logs;
+
+use super::{Installer, try_acquire_install_lock};
+
+/// Source build work dirs untouched for this long are treated as abandoned
+/// even while another install is running.
+pub const STALE_BUILD_DIR_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A keg that is neither the active version of an installed formula nor
+/// held by a pin: superseded versions and kegs with no install record.
+#[derive(Debug, Clone)]
+pub struct ReclaimableKeg {
+    pub name: String,
+    pub version: String,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+}
+
+/// A store entry or cached download, keyed by its store key or sha256.
+#[derive(Debug, Clone)]
+pub struct ReclaimableEntry {
+    pub key: String,
+    pub size_bytes: u64,
+}
+
+/// A source build log older than the formula's latest one.
+#[derive(Debug, Clone)]
+pub struct ReclaimableLog {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+}
+
+/// Everything `zb gc --aggressive` would remove. Computed without touching
+/// anything so it can be shown before a single confirmation.
+///
+/// The plan holds the install lock until it is dropped or handed to
+/// [`Installer::gc_aggressive`], so no install can start using a keg or
+/// store entry between planning and removal.
+#[derive(Debug, Default)]
+pub struct GcPlan {
+    pub kegs: Vec<ReclaimableKeg>,
+    /// Active kegs installed only by `zb install --build-deps-only`.
+    pub build_dependencies: Vec<ReclaimableKeg>,
+    /// Active kegs installed only by `zb run --ephemeral`.
+    pub ephemeral: Vec<ReclaimableKeg>,
+    /// Active kegs installed as dependencies that nothing installed for its
+    /// own sake needs anymore: what `zb autoremove` removes.
+    pub orphaned_dependencies: Vec<ReclaimableKeg>,
+    pub broken_links: Vec<PathBuf>,
+    pub stale_keg_file_records: usize,
+    pub store_entries: Vec<ReclaimableEntry>,
+    pub cached_downloads: Vec<ReclaimableEntry>,
+    pub build_logs: Vec<ReclaimableLog>,
+    _lock: Option<File>,
+}
+
+impl GcPlan {
+    pub fn is_empty(&self) -> bool {
+        self.kegs.is_empty()
+            && self.build_dependencies.is_empty()
+            && self.ephemeral.is_empty()
+            && self.orphaned_dependencies.is_empty()
+            && self.broken_links.is_empty()
+            && self.stale_keg_file_records == 0
+            && self.store_entries.is_empty()
+            && self.cached_downloads.is_empty()
+            && self.build_logs.is_empty()
+    }
+
+    pub fn reclaimable_bytes(&self) -> u64 {
+        self.kegs
+            .iter()
+            .chain(&self.build_dependencies)
+            .chain(&self.ephemeral)
+            .chain(&self.orphaned_dependencies)
+            .map(|keg| keg.size_bytes)
+            .sum::<u64>()
+            + self
+                .store_entries
+                .iter()
+                .chain(&self.cached_downloads)
+                .map(|entry| entry.size_bytes)
+                .sum::<u64>()
+            + self
+                .build_logs
+                .iter()
+                .map(|log| log.size_bytes)
+                .sum::<u64>()
+    }
+}
+
+/// Work dirs removed from `prefix/tmp/build`.
+#[derive(Debug, Default)]
+pub struct BuildTmpPrune {
+    pub removed: usize,
+    pub freed_bytes: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct GcSummary {
+    pub removed_kegs: usize,
+    pub removed_build_dependencies: usize,
+    pub removed_ephemeral: usize,
+    pub removed_orphaned_dependencies: usize,
+    pub removed_links: usize,
+    pub pruned_keg_file_records: usize,
+    pub removed_store_entries: usize,
+    pub removed_cached_downloads: usize,
+    pub removed_build_logs: usize,
+    pub freed_bytes: u64,
+}
+
+impl Installer {
+    /// Collect every kind of reclaimable data. Active kegs, and every keg
+    /// of a pinned formula, are never included.
+    ///
+    /// Takes the install lock, waiting for it or failing as
+    /// [`crate::InstallerBuilder::wait_for_lock`] says, and the plan keeps
+    /// holding it.
+    pub async fn plan_aggressive_gc(&self) -> Result<GcPlan, Error> {
+        let lock = self.install_lock()?;
+        let installed = self.db.list_installed()?;
+        let pinned: HashSet<String> = self.db.list_pinned()?.into_iter().collect();
+
+        let mut plan = GcPlan {
+            kegs: self.superseded_kegs()?,
+            _lock: Some(lock),
+            ..GcPlan::default()
+        };
+
+        let marked_kegs = |names: Vec<String>| -> Result<Vec<ReclaimableKeg>, Error> {
+            let mut kegs = Vec::new();
+            for name in names {
+                let Some(keg) = installed.iter().find(|keg| keg.name == name) else {
+                    continue;
+                };
+                if pinned.contains(&keg.name) {
+                    continue;
+                }
+                let token = formula_token(&keg.name);
+                kegs.push(ReclaimableKeg {
+                    size_bytes: self.cellar.keg_size(token, &keg.version)?,
+                    name: keg.name.clone(),
+                    version: keg.version.clone(),
+                    path: self.cellar.keg_path(token, &keg.version),
+                });
+            }
+            Ok(kegs)
+        };
+        plan.build_dependencies = marked_kegs(self.db.list_build_dependencies()?)?;
+        plan.ephemeral = marked_kegs(self.db.list_ephemeral()?)?;
+        let orphans: Vec<String> = self
+            .orphaned_dependencies()
+            .await?
+            .into_iter()
+            .filter(|name| {
+                !plan
+                    .build_dependencies
+                    .iter()
+                    .chain(&plan.ephemeral)
+                    .any(|keg| &keg.name == name)
+            })
+            .collect();
+        plan.orphaned_dependencies = marked_kegs(orphans)?;
+
+        for record in self.db.list_keg_files()? {
+            let link = PathBuf::from(&record.linked_path);
+            if link.is_symlink() && !link.exists() && !plan.broken_links.contains(&link) {
+                plan.broken_links.push(link);
+            }
+        }
+        plan.stale_keg_file_records = self.db.count_stale_keg_file_records()?;
+
+        // Store entries of marked kegs being reclaimed go with them.
+        let referenced: HashSet<&str> = installed
+            .iter()
+            .filter(|k| {
+                !plan
+                    .build_dependencies
+                    .iter()
+                    .chain(&plan.ephemeral)
+                    .chain(&plan.orphaned_dependencies)
+                    .any(|b| b.name == k.name)
+            })
+            .map(|k| k.store_key.as_str())
+            .collect();
+        for key in self.store.list_entries()? {
+            if referenced.contains(key.as_str()) {
+                continue;
+            }
+            plan.store_entries.push(ReclaimableEntry {
+                size_bytes: tree_size(&self.store.entry_path(&key)),
+                key,
+            });
+        }
+
+        plan.cached_downloads = self.cached_downloads(Duration::ZERO)?;
+
+        if let Some(logs_dir) = &self.build_options.log_dir {
+            plan.build_logs = superseded_build_logs(logs_dir)
+                .into_iter()
+                .map(|path| ReclaimableLog {
+                    size_bytes: tree_size(&path),
+                    path,
+                })
+                .collect();
+        }
+
+        Ok(plan)
+    }
+
+    /// What `zb cleanup` removes: superseded kegs and downloads cached for
+    /// at least `max_download_age`. Run it with [`Self::gc_aggressive`].
+    /// Like [`Self::plan_aggressive_gc`], the plan holds the install lock.
+    pub fn plan_cleanup(&self, max_download_age: Duration) -> Result<GcPlan, Error> {
+        let lock = self.install_lock()?;
+        Ok(GcPlan {
+            kegs: self.superseded_kegs()?,
+            cached_downloads: self.cached_downloads(max_download_age)?,
+            _lock: Some(lock),
+            ..GcPlan::default()
+        })
+    }
+
+    /// Kegs that are neither an installed formula's active version nor
+    /// any version of a pinned formula.
+    fn superseded_kegs(&self) -> Result<Vec<ReclaimableKeg>, Error> {
+        let installed = self.db.list_installed()?;
+        let pinned = self.db.list_pinned()?;
+        let active: HashSet<(&str, &str)> = installed
+            .iter()
+            .map(|keg| (formula_token(&keg.name), keg.version.as_str()))
+            .collect();
+        let pinned_tokens: HashSet<&str> = pinned.iter().map(|name| formula_token(name)).collect();
+
+        let mut kegs = Vec::new();
+        for keg in self.cellar.list_kegs()? {
+            if active.contains(&(keg.name.as_str(), keg.version.as_str()))
+                || pinned_tokens.contains(keg.name.as_str())
+            {
+                continue;
+            }
+            kegs.push(ReclaimableKeg {
+                size_bytes: self.cellar.keg_size(&keg.name, &keg.version)?,
+                name: keg.name,
+                version: keg.version,
+                path: keg.path,
+            });
+        }
+        Ok(kegs)
+    }
+
+    /// Cached bottle downloads last written at least `min_age` ago.
+    fn cached_downloads(&self, min_age: Duration) -> Result<Vec<ReclaimableEntry>, Error> {
+        let blob_cache = self.downloader.blob_cache();
+        let now = SystemTime::now();
+        let mut entries = Vec::new();
+        for sha256 in blob_cache
+            .list_blobs()
+            .map_err(Error::store("failed to list cached downloads"))?
+        {
+            let path = blob_cache.blob_path(&sha256);
+            let age = fs::metadata(&path)
+                .and_then(|meta| meta.modified())
+                .map(|modified| now.duration_since(modified).unwrap_or(Duration::ZERO));
+            if matches!(age, Ok(age) if age < min_age) {
+                continue;
+            }
+            entries.push(ReclaimableEntry {
+                size_bytes: tree_size(&path),
+                key: sha256,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Remove everything in `plan`, unlinking kegs before deleting them so
+    /// no links are left dangling into the Cellar. The install lock the plan
+    /// holds is released once everything is removed.
+    pub fn gc_aggressive(&mut self, plan: GcPlan) -> Result<GcSummary, Error> {
+        let mut summary = GcSummary::default();
+
+        for keg in &plan.kegs {
+            self.linker.unlink_keg(&keg.path)?;
+            self.cellar.remove_keg(&keg.name, &keg.version)?;
+            if let Some(active) = self
+                .db
+                .list_installed()?
+                .into_iter()
+                .find(|installed| formula_token(&installed.name) == keg.name)
+            {
+                // Unlinking a superseded keg drops `opt/<name>` if it still
+                // pointed there; point it back at the active keg.
+                let active_path = self.cellar.keg_path(&keg.name, &active.version);
+                if active_path.exists() {
+                    self.linker.link_opt(&active_path)?;
+                }
+            }
+            summary.removed_kegs += 1;
+            summary.freed_bytes += keg.size_bytes;
+        }
+
+        for keg in &plan.build_dependencies {
+            self.uninstall_inner(&keg.name)?;
+            summary.removed_build_dependencies += 1;
+            summary.freed_bytes += keg.size_bytes;
+        }
+
+        for keg in &plan.ephemeral {
+            self.uninstall_inner(&keg.name)?;
+            summary.removed_ephemeral += 1;
+            summary.freed_bytes += keg.size_bytes;
+        }
+
+        for keg in &plan.orphaned_dependencies {
+            self.uninstall_inner(&keg.name)?;
+            summary.removed_orphaned_dependencies += 1;
+            summary.freed_bytes += keg.size_bytes;
+        }
+
+        for link in &plan.broken_links {
+            if link.is_symlink() && !link.exists() && fs::remove_file(link).is_ok() {
+                summary.removed_links += 1;
+            }
+        }
+        if plan.stale_keg_file_records > 0 {
+            summary.pruned_keg_file_records = self.db.prune_stale_keg_file_records()?;
+        }
+
+        for entry in &plan.store_entries {
+            self.store.remove_entry(&entry.key)?;
+            self.db.delete_store_ref(&entry.key)?;
+            summary.removed_store_entries += 1;
+            summary.freed_bytes += entry.size_bytes;
+        }
+
+        for entry in &plan.cached_downloads {
+            if self.downloader.remove_blob(&entry.key) {
+                summary.removed_cached_downloads += 1;
+                summary.freed_bytes += entry.size_bytes;
+            }
+        }
+
+        for log in &plan.build_logs {
+            match fs::remove_file(&log.path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(Error::store("failed to remove build log")(e)),
+            }
+            summary.removed_build_logs += 1;
+            summary.freed_bytes += log.size_bytes;
+        }
+
+        Ok(summary)
+    }
+}
+
+impl Installer {
+    /// Work dirs under `prefix/tmp/build` that [`Self::prune_build_tmp`]
+    /// would remove right now.
+    pub fn stale_build_dirs(&self, older_than: Duration) -> Result<Vec<PathBuf>, Error> {
+        if !build_work_root(&self.prefix).exists() {
+            return Ok(Vec::new());
+        }
+        let idle = try_acquire_install_lock(&self.locks_dir)?;
+        self.build_dirs_to_prune(older_than, idle.is_some())
+    }
+
+    /// Remove leftover source build work dirs. Builds only run under the
+    /// install lock, so when it is free every work dir is abandoned; while
+    /// another install holds it, only dirs with nothing modified in
+    /// `older_than` are removed so an in-progress build keeps its own.
+    pub fn prune_build_tmp(&self, older_than: Duration) -> Result<BuildTmpPrune, Error> {
+        if !build_work_root(&self.prefix).exists() {
+            return Ok(BuildTmpPrune::default());
+        }
+        // Held until we're done so no build can start in a dir being removed.
+        let idle = try_acquire_install_lock(&self.locks_dir)?;
+        let dirs = self.build_dirs_to_prune(older_than, idle.is_some())?;
+        self.remove_build_dirs(&dirs)
+    }
+
+    /// Remove build work dirs already known to be abandoned. Ones that are
+    /// gone by now are skipped.
+    pub(crate) fn remove_build_dirs(&self, dirs: &[PathBuf]) -> Result<BuildTmpPrune, Error> {
+        let mut pruned = BuildTmpPrune::default();
+        for dir in dirs {
+            let size = tree_size(dir);
+            match fs::remove_dir_all(dir) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(Error::store("failed to remove build directory")(e)),
+            }
+            pruned.removed += 1;
+            pruned.freed_bytes += size;
+        }
+        Ok(pruned)
+    }
+
+    fn build_dirs_to_prune(&self, older_than: Duration, idle: bool) -> Result<Vec<PathBuf>, Error> {
+        let work_root = build_work_root(&self.prefix);
+        let entries = match fs::read_dir(&work_root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::store("failed to read build directory")(e)),
+        };
+
+        let cutoff = SystemTime::now()
+            .checked_sub(older_than)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let mut dirs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+            .map(|entry| entry.path())
+            .filter(|dir| idle || last_modified(dir) < cutoff)
+            .collect();
+        dirs.sort();
+        Ok(dirs)
+    }
+}
+
+/// Newest modification time anywhere in the tree, so a build that is still
+/// writing deep inside its work dir doesn't look idle.
+fn last_modified(path: &Path) -> SystemTime {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+        .max()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Apparent size of a file or directory tree, without following symlinks.
+pub fn tree_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::Installer;
+    use crate::build::build_logs_dir;
+    use crate::network::api::ApiClient;
+
+    use super::super::test_support::{formula_json, mock_api_client, test_installer};
+    use super::super::{acquire_install_lock, try_acquire_install_lock};
+    use super::STALE_BUILD_DIR_AGE;
+
+    fn write_keg(installer: &Installer, name: &str, version: &str) -> std::path::PathBuf {
+        let keg = installer.keg_path(name, version);
+        fs::create_dir_all(keg.join("bin")).unwrap();
+        fs::write(keg.join("bin").join(name), format!("{name} {version}")).unwrap();
+        keg
+    }
+
+    fn set_mtime(dir: &std::path::Path, age: Duration) {
+        let when = SystemTime::now() - age;
+        for entry in walkdir::WalkDir::new(dir) {
+            let entry = entry.unwrap();
+            fs::File::open(entry.path())
+                .unwrap()
+                .set_modified(when)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn prune_build_tmp_keeps_recent_dirs_while_an_install_holds_the_lock() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+
+        let installer = test_installer(tmp.path(), ApiClient::new());
+
+        let work_root = prefix.join("tmp/build");
+        for name in ["crashed", "running"] {
+            fs::create_dir_all(work_root.join(name).join("src")).unwrap();
+            fs::write(work_root.join(name).join("src/config.log"), "log").unwrap();
+        }
+        set_mtime(
+            &work_root.join("crashed"),
+            Duration::from_secs(3 * 24 * 60 * 60),
+        );
+
+        let lock = acquire_install_lock(&root.join("locks"), true, |_| {}).unwrap();
+        assert_eq!(
+            installer.stale_build_dirs(STALE_BUILD_DIR_AGE).unwrap(),
+            vec![work_root.join("crashed")]
+        );
+        let pruned = installer.prune_build_tmp(STALE_BUILD_DIR_AGE).unwrap();
+        assert_eq!(pruned.removed, 1);
+        assert_eq!(pruned.freed_bytes, 3);
+        assert!(!work_root.join("crashed").exists());
+        assert!(
+            work_root.join("running").exists(),
+            "an in-progress build's work dir must be kept"
+        );
+        drop(lock);
+
+        let pruned = installer.prune_build_tmp(STALE_BUILD_DIR_AGE).unwrap();
+        assert_eq!(pruned.removed, 1);
+        assert!(!work_root.join("running").exists());
+        assert_eq!(
+            installer
+                .prune_build_tmp(STALE_BUILD_DIR_AGE)
+                .unwrap()
+                .removed,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn aggressive_gc_removes_every_category_but_keeps_active_and_pinned_kegs() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+        let blob_cache = installer.downloader.blob_cache();
+        fs::write(blob_cache.blob_path(&"cd".repeat(32)), b"bottle").unwrap();
+
+        let old_foo = write_keg(&installer, "foo", "1.0.0");
+        let foo = write_keg(&installer, "foo", "2.0.0");
+        let old_bar = write_keg(&installer, "bar", "0.9.0");
+        let bar = write_keg(&installer, "bar", "1.0.0");
+        let stray = write_keg(&installer, "stray", "3.0.0");
+
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("foo", "2.0.0", "fookey").unwrap();
+            tx.record_install("bar", "1.0.0", "barkey").unwrap();
+            tx.record_linked_file(
+                "foo",
+                "2.0.0",
+                &prefix.join("bin/foo-gone").to_string_lossy(),
+                &foo.join("bin/foo-gone").to_string_lossy(),
+            )
+            .unwrap();
+            tx.record_linked_file("gone", "1.0.0", "/nonexistent/link", "/nonexistent/target")
+                .unwrap();
+            tx.commit().unwrap();
+        }
+        installer.pin("bar").unwrap();
+
+        // foo 1.0.0 was the linked version before an upgrade left it behind.
+        installer.linker.link_keg(&old_foo).unwrap();
+        std::os::unix::fs::symlink(foo.join("bin/foo-gone"), prefix.join("bin/foo-gone")).unwrap();
+
+        for key in ["fookey", "barkey", "deadbeef"] {
+            let entry = installer.store.entry_path(key);
+            fs::create_dir_all(&entry).unwrap();
+            fs::write(entry.join("payload"), key).unwrap();
+        }
+
+        let logs_dir = build_logs_dir(&root);
+        installer.build_options.log_dir = Some(logs_dir.clone());
+        fs::create_dir_all(logs_dir.join("foo")).unwrap();
+        let old_log = logs_dir.join("foo/100.log");
+        let latest_log = logs_dir.join("foo/200.log");
+        fs::write(&old_log, "old build").unwrap();
+        fs::write(&latest_log, "latest build").unwrap();
+
+        let plan = installer.plan_aggressive_gc().await.unwrap();
+        let mut kegs: Vec<(String, String)> = plan
+            .kegs
+            .iter()
+            .map(|keg| (keg.name.clone(), keg.version.clone()))
+            .collect();
+        kegs.sort();
+        assert_eq!(
+            kegs,
+            vec![
+                ("foo".to_string(), "1.0.0".to_string()),
+                ("stray".to_string(), "3.0.0".to_string()),
+            ]
+        );
+        assert_eq!(plan.broken_links, vec![prefix.join("bin/foo-gone")]);
+        assert_eq!(plan.stale_keg_file_records, 1);
+        assert_eq!(plan.store_entries.len(), 1);
+        assert_eq!(plan.store_entries[0].key, "deadbeef");
+        assert_eq!(plan.cached_downloads.len(), 1);
+        assert_eq!(plan.build_logs.len(), 1);
+        assert_eq!(plan.build_logs[0].path, old_log);
+        let reclaimable = plan.reclaimable_bytes();
+        assert!(reclaimable > 0);
+
+        let summary = installer.gc_aggressive(plan).unwrap();
+        assert_eq!(summary.removed_kegs, 2);
+        assert_eq!(summary.removed_links, 1);
+        assert_eq!(summary.pruned_keg_file_records, 1);
+        assert_eq!(summary.removed_store_entries, 1);
+        assert_eq!(summary.removed_cached_downloads, 1);
+        assert_eq!(summary.removed_build_logs, 1);
+        assert_eq!(summary.freed_bytes, reclaimable);
+
+        assert!(!old_foo.exists());
+        assert!(!stray.exists());
+        assert!(foo.exists(), "active keg must be kept");
+        assert!(bar.exists(), "pinned keg must be kept");
+        assert!(
+            old_bar.exists(),
+            "every version of a pinned formula is kept"
+        );
+        assert!(
+            prefix.join("bin/foo").symlink_metadata().is_err(),
+            "links into removed kegs must not be left dangling"
+        );
+        assert_eq!(fs::read_link(prefix.join("opt/foo")).unwrap(), foo);
+        assert!(installer.store.has_entry("fookey"));
+        assert!(!installer.store.has_entry("deadbeef"));
+        assert!(!old_log.exists());
+        assert!(
+            latest_log.exists(),
+            "the latest build log is kept for `zb log`"
+        );
+
+        assert!(installer.plan_aggressive_gc().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn cleanup_keeps_active_kegs_and_recent_downloads() {
+        let tmp = TempDir::new().unwrap();
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+        let blob_cache = installer.downloader.blob_cache();
+        let (old_blob, new_blob) = ("ab".repeat(32), "cd".repeat(32));
+        fs::write(blob_cache.blob_path(&old_blob), b"old bottle").unwrap();
+        fs::write(blob_cache.blob_path(&new_blob), b"new bottle").unwrap();
+        set_mtime(
+            &blob_cache.blob_path(&old_blob),
+            Duration::from_secs(200 * 24 * 60 * 60),
+        );
+
+        let old_foo = write_keg(&installer, "foo", "1.0.0");
+        let foo = write_keg(&installer, "foo", "2.0.0");
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("foo", "2.0.0", "fookey").unwrap();
+            tx.commit().unwrap();
+        }
+
+        let plan = installer
+            .plan_cleanup(Duration::from_secs(120 * 24 * 60 * 60))
+            .unwrap();
+        assert_eq!(plan.kegs.len(), 1);
+        assert_eq!(plan.kegs[0].path, old_foo);
+        let downloads: Vec<&str> = plan
+            .cached_downloads
+            .iter()
+            .map(|e| e.key.as_str())
+            .collect();
+        assert_eq!(downloads, vec![old_blob.as_str()]);
+        assert!(plan.store_entries.is_empty() && plan.broken_links.is_empty());
+
+        let summary = installer.gc_aggressive(plan).unwrap();
+        assert_eq!(summary.removed_kegs, 1);
+        assert_eq!(summary.removed_cached_downloads, 1);
+        assert!(!old_foo.exists());
+        assert!(foo.exists());
+
+        let everything = installer.plan_cleanup(Duration::ZERO).unwrap();
+        assert!(everything.kegs.is_empty());
+        assert_eq!(everything.cached_downloads.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn aggressive_gc_reclaims_orphaned_dependencies_while_holding_the_lock() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("zerobrew");
+
+        for (name, deps) in [("app", &["lib"][..]), ("lib", &[]), ("old", &[])] {
+            Mock::given(method("GET"))
+                .and(path(format!("/formula/{name}.json")))
+                .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(name, deps)))
+                .mount(&mock_server)
+                .await;
+        }
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+        installer.wait_for_lock = false;
+
+        for name in ["app", "lib", "old"] {
+            write_keg(&installer, name, "1.0.0");
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install(name, "1.0.0", &format!("{name}key"))
+                .unwrap();
+            tx.commit().unwrap();
+        }
+        for name in ["lib", "old"] {
+            installer.db.mark_dependency(name).unwrap();
+        }
+
+        let plan = installer.plan_aggressive_gc().await.unwrap();
+        let orphans: Vec<&str> = plan
+            .orphaned_dependencies
+            .iter()
+            .map(|keg| keg.name.as_str())
+            .collect();
+        assert_eq!(orphans, vec!["old"]);
+        assert!(
+            try_acquire_install_lock(&root.join("locks"))
+                .unwrap()
+                .is_none()
+        );
+        assert!(installer.plan_cleanup(Duration::ZERO).is_err());
+
+        let summary = installer.gc_aggressive(plan).unwrap();
+        assert_eq!(summary.removed_orphaned_dependencies, 1);
+        assert!(!installer.is_installed("old"));
+        assert!(installer.is_installed("lib"));
+        assert!(
+            try_acquire_install_lock(&root.join("locks"))
+                .unwrap()
+                .is_some()
+        );
+    }
+}
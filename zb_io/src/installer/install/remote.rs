@@ -0,0 +1,301 @@
+use std::fs;
+use std::path::Path;
+
+use zb_core::Error;
+use zb_core::formula::RubySourceChecksum;
+
+use crate::network::tap_formula::{TapFormulaRef, parse_tap_formula_ruby};
+
+use super::Installer;
+
+impl Installer {
+    /// Fetch a standalone formula `.rb` from `url`, parse it and make it
+    /// resolvable by name for the rest of this run, without a tap.
+    ///
+    /// The file is cached with the other formula sources and, when
+    /// `expected_sha256` is given, must match it. Returns the formula name.
+    pub async fn add_formula_from_url(
+        &self,
+        url: &str,
+        expected_sha256: Option<&str>,
+    ) -> Result<String, Error> {
+        let spec = remote_formula_ref(url)?;
+        let cache_dir = self.prefix.join("tmp").join("rb_cache");
+        let path = self
+            .api_client
+            .fetch_formula_rb(url, &cache_dir, expected_sha256)
+            .await?;
+        let source = fs::read_to_string(&path).map_err(Error::file("failed to read rb file"))?;
+
+        self.register_ruby_formula(&spec, &source, url, expected_sha256)
+    }
+
+    /// Parse a formula `.rb` on this machine and make it resolvable by name
+    /// for the rest of this run, like [`Self::add_formula_from_url`]. Its
+    /// dependencies still resolve against the API. Returns the formula name.
+    pub fn add_formula_from_file(&self, path: &Path) -> Result<String, Error> {
+        let path = fs::canonicalize(path).map_err(|e| Error::FileError {
+            message: format!("failed to read formula file '{}': {e}", path.display()),
+        })?;
+        let spec = local_formula_ref(&path)?;
+        let source = fs::read_to_string(&path).map_err(Error::file("failed to read rb file"))?;
+        let ruby_source_path = path.to_str().ok_or_else(|| Error::InvalidArgument {
+            message: format!("formula file path is not valid UTF-8: {}", path.display()),
+        })?;
+
+        self.register_ruby_formula(&spec, &source, ruby_source_path, None)
+    }
+
+    fn register_ruby_formula(
+        &self,
+        spec: &TapFormulaRef,
+        source: &str,
+        ruby_source_path: &str,
+        expected_sha256: Option<&str>,
+    ) -> Result<String, Error> {
+        let mut formula = parse_tap_formula_ruby(spec, source)?;
+        formula.ruby_source_path = Some(ruby_source_path.to_string());
+        formula.ruby_source_checksum = expected_sha256.map(|sha256| RubySourceChecksum {
+            sha256: sha256.to_string(),
+        });
+
+        let name = formula.name.clone();
+        self.api_client.register_formula(formula);
+        Ok(name)
+    }
+}
+
+/// Name the formula after the `.rb` file. For raw GitHub URLs the owner and
+/// repo are kept so bottles without a `root_url` resolve the way tap bottles
+/// do.
+fn remote_formula_ref(url: &str) -> Result<TapFormulaRef, Error> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| Error::InvalidArgument {
+        message: format!("invalid formula URL '{url}': {e}"),
+    })?;
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        return Err(Error::InvalidArgument {
+            message: format!("formula URL must use http or https: {url}"),
+        });
+    }
+
+    let segments: Vec<&str> = parsed
+        .path_segments()
+        .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    let formula = segments
+        .last()
+        .and_then(|file| file.strip_suffix(".rb"))
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| Error::InvalidArgument {
+            message: format!("formula URL must point at a .rb file: {url}"),
+        })?;
+
+    let (owner, repo) = match (parsed.host_str(), segments.as_slice()) {
+        (Some("raw.githubusercontent.com"), [owner, repo, ..]) => (
+            owner.to_string(),
+            repo.trim_start_matches("homebrew-").to_string(),
+        ),
+        _ => (String::new(), String::new()),
+    };
+
+    Ok(TapFormulaRef {
+        owner,
+        repo,
+        formula: formula.to_string(),
+    })
+}
+
+/// Name a local formula after its `.rb` file; it belongs to no tap.
+fn local_formula_ref(path: &Path) -> Result<TapFormulaRef, Error> {
+    let formula = path
+        .file_name()
+        .and_then(|file| file.to_str())
+        .and_then(|file| file.strip_suffix(".rb"))
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| Error::InvalidArgument {
+            message: format!("formula file must be a .rb file: {}", path.display()),
+        })?;
+
+    Ok(TapFormulaRef {
+        owner: String::new(),
+        repo: String::new(),
+        formula: formula.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::super::test_support::{
+        create_bottle_tarball_with_version, formula_json_with_bottle, get_test_bottle_tag,
+        mock_api_client, sha256_hex, test_installer,
+    };
+    use super::*;
+
+    #[test]
+    fn formula_is_named_after_the_rb_file() {
+        let spec = remote_formula_ref(
+            "https://raw.githubusercontent.com/acme/homebrew-tools/main/Formula/widget.rb",
+        )
+        .unwrap();
+        assert_eq!(spec.owner, "acme");
+        assert_eq!(spec.repo, "tools");
+        assert_eq!(spec.formula, "widget");
+
+        assert!(remote_formula_ref("https://example.com/widget.tar.gz").is_err());
+        assert!(remote_formula_ref("file:///tmp/widget.rb").is_err());
+    }
+
+    #[tokio::test]
+    async fn served_formula_is_fetched_and_installed() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+
+        let tag = get_test_bottle_tag();
+        let widget_bottle = create_bottle_tarball_with_version("widget", "2.1.0");
+        let helper_bottle = create_bottle_tarball_with_version("helper", "1.0.0");
+        let widget_rb = format!(
+            r#"class Widget < Formula
+  desc "One-off tool"
+  url "https://example.com/widget-2.1.0.tar.gz"
+  version "2.1.0"
+
+  bottle do
+    root_url "{uri}/bottles"
+    sha256 cellar: :any_skip_relocation, {tag}: "{sha}"
+  end
+
+  depends_on "helper"
+end
+"#,
+            uri = mock_server.uri(),
+            sha = sha256_hex(&widget_bottle)
+        );
+        let helper_json = formula_json_with_bottle(
+            "helper",
+            "1.0.0",
+            &[],
+            &format!("{}/bottles", mock_server.uri()),
+            &sha256_hex(&helper_bottle),
+        );
+
+        Mock::given(method("GET"))
+            .and(path("/remote/widget.rb"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(widget_rb.clone()))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/formula/helper.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(helper_json))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/widget-2.1.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(widget_bottle))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/helper-1.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(helper_bottle))
+            .mount(&mock_server)
+            .await;
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        let url = format!("{}/remote/widget.rb", mock_server.uri());
+        let wrong = "00".repeat(32);
+        assert!(matches!(
+            installer.add_formula_from_url(&url, Some(&wrong)).await,
+            Err(Error::ChecksumMismatch { .. })
+        ));
+
+        let rb_sha = sha256_hex(widget_rb.as_bytes());
+        let name = installer
+            .add_formula_from_url(&url, Some(&rb_sha))
+            .await
+            .unwrap();
+        assert_eq!(name, "widget");
+
+        installer.install(&[name], true).await.unwrap();
+
+        assert!(installer.is_installed("widget"));
+        assert!(installer.is_installed("helper"));
+        assert!(root.join("cellar/widget/2.1.0/bin/widget").exists());
+        assert!(prefix.join("bin/widget").exists());
+    }
+
+    #[tokio::test]
+    async fn local_formula_file_plans_a_source_build() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        Mock::given(method("GET"))
+            .and(path("/formula/helper.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "helper",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &"b".repeat(64),
+                )),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let rb = tmp.path().join("my-tool.rb");
+        fs::write(
+            &rb,
+            r#"class MyTool < Formula
+  desc "In-house tool"
+  url "https://example.com/my-tool-0.3.0.tar.gz"
+  sha256 "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+  version "0.3.0"
+
+  depends_on "helper" => :build
+end
+"#,
+        )
+        .unwrap();
+
+        let installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        assert!(
+            installer
+                .add_formula_from_file(&tmp.path().join("widget.tar.gz"))
+                .is_err()
+        );
+        let name = installer.add_formula_from_file(&rb).unwrap();
+        assert_eq!(name, "my-tool");
+
+        let plan = installer.plan(&[name.clone()]).await.unwrap();
+        let item = plan
+            .items
+            .iter()
+            .find(|item| item.install_name == name)
+            .unwrap();
+        match &item.method {
+            zb_core::InstallMethod::Source(build) => {
+                assert_eq!(build.build_dependencies, vec!["helper"]);
+            }
+            other => panic!("expected a source build, got {other:?}"),
+        }
+
+        // The build gets a snapshot, not the file being edited.
+        let file = installer.formula_file(&name).await.unwrap();
+        let canonical = fs::canonicalize(&rb).unwrap();
+        assert_eq!(file.origin, canonical.display().to_string());
+        assert_ne!(file.path, canonical);
+        assert_eq!(
+            fs::read_to_string(&file.path).unwrap(),
+            fs::read_to_string(&rb).unwrap()
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use zb_core::Error;
+
+use super::Installer;
+use super::caveats::FormulaCaveats;
+use crate::progress::ProgressCallback;
+
+/// What to install, and how. Handed to [`Installer::install_request`], this
+/// is the one call a program embedding zerobrew needs: planning and
+/// executing happen together, and the outcome comes back as an
+/// [`InstallReport`].
+#[non_exhaustive]
+pub struct InstallRequest {
+    pub formulas: Vec<String>,
+    pub link: bool,
+    pub build_from_source: bool,
+    pub progress: Option<Arc<ProgressCallback>>,
+}
+
+impl InstallRequest {
+    /// Install `formulas` from bottles and link them.
+    pub fn new<I, S>(formulas: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            formulas: formulas.into_iter().map(Into::into).collect(),
+            link: true,
+            build_from_source: false,
+            progress: None,
+        }
+    }
+
+    pub fn link(mut self, link: bool) -> Self {
+        self.link = link;
+        self
+    }
+
+    pub fn build_from_source(mut self, build_from_source: bool) -> Self {
+        self.build_from_source = build_from_source;
+        self
+    }
+
+    /// Hear about this install's progress; without one, events go to the
+    /// installer's own subscriber, if any.
+    pub fn progress(mut self, progress: Arc<ProgressCallback>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+}
+
+/// A formula an install put in place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct InstalledFormula {
+    pub name: String,
+    pub version: String,
+}
+
+/// What an [`InstallRequest`] did.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct InstallReport {
+    /// Every formula installed, dependencies included, in install order.
+    /// Anything already installed is left out.
+    pub installed: Vec<InstalledFormula>,
+    /// Caveats of the formulas installed, in install order.
+    pub caveats: Vec<FormulaCaveats>,
+}
+
+impl Installer {
+    /// Resolve, download and install `request.formulas` with their
+    /// dependencies under the install lock.
+    pub async fn install_request(
+        &mut self,
+        request: InstallRequest,
+    ) -> Result<InstallReport, Error> {
+        let plan = self
+            .plan_with_options(&request.formulas, request.build_from_source)
+            .await?;
+        let result = self
+            .execute_with_progress(plan, request.link, request.progress)
+            .await?;
+        Ok(InstallReport {
+            installed: result.formulas,
+            caveats: result.caveats,
+        })
+    }
+}
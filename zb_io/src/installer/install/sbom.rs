@@ -0,0 +1,380 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use serde_json::{Value, json};
+use tracing::warn;
+use zb_core::{Error, Formula, formula_token};
+
+use super::Installer;
+
+/// One installed keg as it is described in a software bill of materials.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SbomPackage {
+    pub name: String,
+    pub version: String,
+    /// SPDX expression from the formula, when it declares one.
+    pub license: Option<String>,
+    pub homepage: Option<String>,
+    /// The bottle or source archive the keg was installed from.
+    pub download_url: Option<String>,
+    pub sha256: Option<String>,
+    /// Installed runtime dependencies, by name.
+    pub dependencies: Vec<String>,
+}
+
+/// Installed kegs plus the ones the document is about, ready to be
+/// rendered as SPDX or CycloneDX JSON.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sbom {
+    /// Sorted by name.
+    pub packages: Vec<SbomPackage>,
+    /// The names asked for; every package when the whole prefix was.
+    pub roots: Vec<String>,
+}
+
+impl Installer {
+    /// A bill of materials for `names` and everything installed they depend
+    /// on at runtime, or for every installed keg when `names` is empty.
+    /// Licenses and URLs come from the formula metadata; a formula that
+    /// can't be fetched is still listed, just without them.
+    pub async fn sbom(&self, names: &[String]) -> Result<Sbom, Error> {
+        let installed: BTreeMap<String, (String, String)> = self
+            .db
+            .list_installed()?
+            .into_iter()
+            .map(|keg| (keg.name, (keg.version, keg.store_key)))
+            .collect();
+        let by_token: HashMap<&str, &str> = installed
+            .keys()
+            .map(|name| (formula_token(name), name.as_str()))
+            .collect();
+
+        let roots: Vec<String> = if names.is_empty() {
+            installed.keys().cloned().collect()
+        } else {
+            names
+                .iter()
+                .map(|name| {
+                    by_token
+                        .get(formula_token(name))
+                        .map(|name| name.to_string())
+                        .ok_or_else(|| Error::NotInstalled { name: name.clone() })
+                })
+                .collect::<Result<_, _>>()?
+        };
+
+        let mut packages = BTreeMap::new();
+        let mut queue: VecDeque<String> = roots.iter().cloned().collect();
+        while let Some(name) = queue.pop_front() {
+            if packages.contains_key(&name) {
+                continue;
+            }
+            let (version, store_key) = installed[&name].clone();
+
+            let formula = if name.starts_with("cask:") {
+                None
+            } else {
+                match self.api_client.get_formula(&name).await {
+                    Ok(formula) => Some(formula),
+                    Err(e) => {
+                        warn!(formula = %name, error = %e, "no formula metadata for SBOM entry");
+                        None
+                    }
+                }
+            };
+
+            let mut package = SbomPackage {
+                name: name.clone(),
+                version,
+                license: None,
+                homepage: None,
+                download_url: None,
+                sha256: None,
+                dependencies: Vec::new(),
+            };
+            if let Some(formula) = &formula {
+                package.license = formula.license.clone();
+                package.homepage = formula.homepage.clone();
+                (package.download_url, package.sha256) = artifact(formula, &store_key);
+                for dep in formula.runtime_dependencies() {
+                    if let Some(dep) = by_token.get(formula_token(&dep)) {
+                        package.dependencies.push(dep.to_string());
+                        queue.push_back(dep.to_string());
+                    }
+                }
+                package.dependencies.sort();
+                package.dependencies.dedup();
+            }
+            packages.insert(name, package);
+        }
+
+        Ok(Sbom {
+            packages: packages.into_values().collect(),
+            roots,
+        })
+    }
+}
+
+/// Where a keg installed under `store_key` was downloaded from. Bottle
+/// installs are keyed by the bottle's sha256; source builds record the
+/// archive's checksum in the formula instead.
+fn artifact(formula: &Formula, store_key: &str) -> (Option<String>, Option<String>) {
+    if let Some(file) = formula
+        .bottle
+        .stable
+        .files
+        .values()
+        .find(|file| file.sha256 == store_key)
+    {
+        return (Some(file.url.clone()), Some(file.sha256.clone()));
+    }
+    if store_key.starts_with("source:")
+        && let Some(source) = formula.source_url()
+    {
+        return (Some(source.url.clone()), source.checksum.clone());
+    }
+    (None, None)
+}
+
+impl Sbom {
+    /// SPDX 2.3 JSON. `created` is an RFC 3339 timestamp and `tool` names
+    /// what generated the document, e.g. `zerobrew-0.3.1`.
+    pub fn to_spdx(&self, name: &str, created: &str, tool: &str) -> Value {
+        let spdx_id = |name: &str| {
+            let id: String = name
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '.' {
+                        c
+                    } else {
+                        '-'
+                    }
+                })
+                .collect();
+            format!("SPDXRef-Package-{id}")
+        };
+
+        let packages: Vec<Value> = self
+            .packages
+            .iter()
+            .map(|package| {
+                let mut entry = json!({
+                    "SPDXID": spdx_id(&package.name),
+                    "name": package.name,
+                    "versionInfo": package.version,
+                    "downloadLocation": package.download_url.as_deref().unwrap_or("NOASSERTION"),
+                    "filesAnalyzed": false,
+                    "licenseConcluded": "NOASSERTION",
+                    "licenseDeclared": package.license.as_deref().unwrap_or("NOASSERTION"),
+                    "copyrightText": "NOASSERTION",
+                    "externalRefs": [{
+                        "referenceCategory": "PACKAGE-MANAGER",
+                        "referenceType": "purl",
+                        "referenceLocator": purl(package),
+                    }],
+                });
+                if let Some(sha256) = &package.sha256 {
+                    entry["checksums"] =
+                        json!([{ "algorithm": "SHA256", "checksumValue": sha256 }]);
+                }
+                if let Some(homepage) = &package.homepage {
+                    entry["homepage"] = json!(homepage);
+                }
+                entry
+            })
+            .collect();
+
+        let mut relationships: Vec<Value> = self
+            .roots
+            .iter()
+            .map(|root| {
+                json!({
+                    "spdxElementId": "SPDXRef-DOCUMENT",
+                    "relationshipType": "DESCRIBES",
+                    "relatedSpdxElement": spdx_id(root),
+                })
+            })
+            .collect();
+        for package in &self.packages {
+            for dep in &package.dependencies {
+                relationships.push(json!({
+                    "spdxElementId": spdx_id(&package.name),
+                    "relationshipType": "DEPENDS_ON",
+                    "relatedSpdxElement": spdx_id(dep),
+                }));
+            }
+        }
+
+        json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": name,
+            "documentNamespace": format!("https://spdx.org/spdxdocs/{name}-{created}"),
+            "creationInfo": {
+                "created": created,
+                "creators": [format!("Tool: {tool}")],
+            },
+            "packages": packages,
+            "relationships": relationships,
+        })
+    }
+
+    /// CycloneDX 1.5 JSON, with the same `created` and `tool` as
+    /// [`Self::to_spdx`].
+    pub fn to_cyclonedx(&self, created: &str, tool: &str) -> Value {
+        let components: Vec<Value> = self
+            .packages
+            .iter()
+            .map(|package| {
+                let mut component = json!({
+                    "type": "application",
+                    "bom-ref": purl(package),
+                    "name": package.name,
+                    "version": package.version,
+                    "purl": purl(package),
+                });
+                if let Some(license) = &package.license {
+                    component["licenses"] = json!([{ "expression": license }]);
+                }
+                if let Some(sha256) = &package.sha256 {
+                    component["hashes"] = json!([{ "alg": "SHA-256", "content": sha256 }]);
+                }
+                let mut references = Vec::new();
+                if let Some(url) = &package.download_url {
+                    references.push(json!({ "type": "distribution", "url": url }));
+                }
+                if let Some(homepage) = &package.homepage {
+                    references.push(json!({ "type": "website", "url": homepage }));
+                }
+                if !references.is_empty() {
+                    component["externalReferences"] = json!(references);
+                }
+                component
+            })
+            .collect();
+
+        let refs: HashMap<&str, String> = self
+            .packages
+            .iter()
+            .map(|package| (package.name.as_str(), purl(package)))
+            .collect();
+        let dependencies: Vec<Value> = self
+            .packages
+            .iter()
+            .map(|package| {
+                json!({
+                    "ref": refs[package.name.as_str()],
+                    "dependsOn": package
+                        .dependencies
+                        .iter()
+                        .filter_map(|dep| refs.get(dep.as_str()))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "timestamp": created,
+                "tools": [{ "name": tool }],
+            },
+            "components": components,
+            "dependencies": dependencies,
+        })
+    }
+}
+
+/// Package URL for an installed keg. Casks keep their own purl type.
+fn purl(package: &SbomPackage) -> String {
+    match package.name.strip_prefix("cask:") {
+        Some(token) => format!("pkg:brew/cask/{token}@{}", package.version),
+        None => format!("pkg:brew/{}@{}", package.name, package.version),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::super::test_support::{formula_json_with_bottle, mock_api_client, test_installer};
+
+    #[tokio::test]
+    async fn sbom_covers_the_installed_closure_with_licenses_and_hashes() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        let shas: Vec<(&str, String)> = vec![
+            ("app", "aa".repeat(32)),
+            ("lib", "bb".repeat(32)),
+            ("other", "cc".repeat(32)),
+        ];
+        for (name, deps) in [
+            ("app", &["lib", "missing"][..]),
+            ("lib", &[][..]),
+            ("other", &[][..]),
+        ] {
+            let sha = &shas.iter().find(|(n, _)| *n == name).unwrap().1;
+            Mock::given(method("GET"))
+                .and(path(format!("/formula/{name}.json")))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                        name,
+                        "1.0.0",
+                        deps,
+                        "https://example.com",
+                        sha,
+                    )),
+                )
+                .mount(&mock_server)
+                .await;
+        }
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+        {
+            let tx = installer.db.transaction().unwrap();
+            for (name, sha) in &shas {
+                tx.record_install(name, "1.0.0", sha).unwrap();
+            }
+            tx.commit().unwrap();
+        }
+
+        let sbom = installer.sbom(&["app".to_string()]).await.unwrap();
+        let names: Vec<&str> = sbom.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["app", "lib"]);
+        assert_eq!(sbom.roots, vec!["app"]);
+        let app = &sbom.packages[0];
+        assert_eq!(app.license.as_deref(), Some("MIT"));
+        assert_eq!(app.sha256.as_deref(), Some("aa".repeat(32).as_str()));
+        assert!(
+            app.download_url
+                .as_deref()
+                .unwrap()
+                .ends_with(".bottle.tar.gz")
+        );
+        assert_eq!(app.dependencies, vec!["lib"]);
+
+        let spdx = sbom.to_spdx("app", "2026-01-01T00:00:00Z", "zerobrew-test");
+        assert_eq!(spdx["spdxVersion"], "SPDX-2.3");
+        assert_eq!(spdx["packages"][0]["licenseDeclared"], "MIT");
+        assert_eq!(
+            spdx["packages"][0]["checksums"][0]["checksumValue"],
+            "aa".repeat(32)
+        );
+        assert_eq!(spdx["relationships"][1]["relationshipType"], "DEPENDS_ON");
+
+        let cdx = sbom.to_cyclonedx("2026-01-01T00:00:00Z", "zerobrew-test");
+        assert_eq!(cdx["components"][1]["purl"], "pkg:brew/lib@1.0.0");
+        assert_eq!(cdx["dependencies"][0]["dependsOn"][0], "pkg:brew/lib@1.0.0");
+
+        assert_eq!(installer.sbom(&[]).await.unwrap().packages.len(), 3);
+        assert!(matches!(
+            installer.sbom(&["nope".to_string()]).await,
+            Err(zb_core::Error::NotInstalled { .. })
+        ));
+    }
+}
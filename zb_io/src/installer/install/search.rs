@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+
+use zb_core::Error;
+
+use crate::network::suggest::{MatchKind, search_formula_names};
+
+use super::Installer;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub name: String,
+    pub kind: MatchKind,
+    pub installed: bool,
+    /// From the formula index; `None` for installed-only searches.
+    pub desc: Option<String>,
+}
+
+impl Installer {
+    /// Search formula names for `query`, ranking exact, substring and fuzzy
+    /// matches in that order.
+    ///
+    /// With `installed_only` the candidates come from the local database, so
+    /// no index is fetched and the search works offline. Otherwise the
+    /// cached formula index is used, and with `descriptions` formulas whose
+    /// description contains `query` follow the name matches.
+    pub async fn search(
+        &self,
+        query: &str,
+        installed_only: bool,
+        descriptions: bool,
+    ) -> Result<Vec<SearchResult>, Error> {
+        let installed: HashSet<String> = self
+            .db
+            .list_installed()?
+            .into_iter()
+            .map(|keg| keg.name)
+            .collect();
+
+        if installed_only {
+            let candidates: Vec<String> = installed.iter().cloned().collect();
+            return Ok(search_formula_names(query, &candidates)
+                .into_iter()
+                .map(|m| SearchResult {
+                    installed: true,
+                    name: m.name,
+                    kind: m.kind,
+                    desc: None,
+                })
+                .collect());
+        }
+
+        let candidates = self.api_client.formula_candidates().await?;
+        let descs = self.api_client.formula_descriptions().await?;
+
+        let mut results: Vec<SearchResult> = search_formula_names(query, &candidates)
+            .into_iter()
+            .map(|m| SearchResult {
+                installed: installed.contains(&m.name),
+                desc: descs.get(&m.name).cloned(),
+                name: m.name,
+                kind: m.kind,
+            })
+            .collect();
+
+        if descriptions {
+            let needle = query.trim().to_lowercase();
+            let matched: HashSet<String> = results.iter().map(|r| r.name.clone()).collect();
+            let mut by_desc: Vec<SearchResult> = descs
+                .iter()
+                .filter(|(name, desc)| {
+                    !needle.is_empty()
+                        && !matched.contains(*name)
+                        && desc.to_lowercase().contains(&needle)
+                })
+                .map(|(name, desc)| SearchResult {
+                    name: name.clone(),
+                    kind: MatchKind::Description,
+                    installed: installed.contains(name),
+                    desc: Some(desc.clone()),
+                })
+                .collect();
+            by_desc.sort_by(|a, b| a.name.cmp(&b.name));
+            results.extend(by_desc);
+        }
+
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::Installer;
+    use crate::network::suggest::MatchKind;
+
+    use super::super::test_support::{self, mock_api_client};
+
+    async fn test_installer() -> (Installer, MockServer, TempDir) {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let mut installer = test_support::test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("jq", "1.7.1", "jqkey").unwrap();
+            tx.record_install("ripgrep", "14.1.0", "rgkey").unwrap();
+            tx.commit().unwrap();
+        }
+
+        (installer, mock_server, tmp)
+    }
+
+    #[tokio::test]
+    async fn installed_search_returns_only_installed_names_without_index() {
+        let (installer, mock_server, _tmp) = test_installer().await;
+
+        Mock::given(method("GET"))
+            .and(path("/formula.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let results = installer.search("jqq", true, false).await.unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "jq");
+        assert!(results[0].installed);
+
+        let results = installer.search("grep", true, false).await.unwrap();
+        let names: Vec<_> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["ripgrep"]);
+    }
+
+    #[tokio::test]
+    async fn index_search_marks_installed_results() {
+        let (installer, mock_server, _tmp) = test_installer().await;
+
+        Mock::given(method("GET"))
+            .and(path("/formula.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"[{"name":"jq"},{"name":"jqp"},{"name":"gojq"},{"name":"wget"}]"#,
+            ))
+            .mount(&mock_server)
+            .await;
+
+        let results = installer.search("jq", false, false).await.unwrap();
+        let names: Vec<_> = results.iter().map(|r| r.name.as_str()).collect();
+
+        assert_eq!(names, vec!["jq", "jqp", "gojq"]);
+        assert!(results[0].installed);
+        assert!(!results[1].installed);
+    }
+
+    #[tokio::test]
+    async fn description_search_appends_formulas_whose_description_matches() {
+        let (installer, mock_server, _tmp) = test_installer().await;
+
+        Mock::given(method("GET"))
+            .and(path("/formula.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"[
+                    {"name":"jq","desc":"Lightweight and flexible command-line JSON processor"},
+                    {"name":"fx","desc":"Terminal JSON viewer"},
+                    {"name":"jsonnet","desc":"Data templating language"},
+                    {"name":"wget","desc":"Internet file retriever"}
+                ]"#,
+            ))
+            .mount(&mock_server)
+            .await;
+
+        let results = installer.search("json", false, true).await.unwrap();
+        let names: Vec<_> = results.iter().map(|r| r.name.as_str()).collect();
+
+        assert_eq!(names, vec!["jsonnet", "fx", "jq"]);
+        assert_eq!(results[0].kind, MatchKind::Substring);
+        assert_eq!(results[1].kind, MatchKind::Description);
+        assert_eq!(results[1].desc.as_deref(), Some("Terminal JSON viewer"));
+        assert!(results[2].installed);
+
+        let without = installer.search("json", false, false).await.unwrap();
+        let names: Vec<_> = without.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["jsonnet"]);
+    }
+}
@@ -0,0 +1,205 @@
+use std::path::PathBuf;
+
+use tracing::warn;
+use zb_core::{Error, Formula, ServiceSpec, service_label};
+
+use super::Installer;
+use crate::services::ServiceManager;
+
+/// Whether a formula's service is running, as `zb services list` shows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceState {
+    Started,
+    Stopped,
+    /// Started through zerobrew, but the service manager no longer has it.
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub label: String,
+    pub state: ServiceState,
+    /// The definition zerobrew wrote, while the service is started.
+    pub file: Option<PathBuf>,
+}
+
+impl Installer {
+    /// Every installed formula that defines a service, with its state.
+    pub async fn services(&self, manager: &ServiceManager) -> Result<Vec<ServiceStatus>, Error> {
+        let mut services = Vec::new();
+        for keg in self.db.list_installed()? {
+            if keg.name.starts_with("cask:") {
+                continue;
+            }
+            let Ok(formula) = self.api_client.get_formula(&keg.name).await else {
+                continue;
+            };
+            if formula.service.is_none() {
+                continue;
+            }
+            services.push(self.service_status(&keg.name, manager));
+        }
+        Ok(services)
+    }
+
+    fn service_status(&self, name: &str, manager: &ServiceManager) -> ServiceStatus {
+        match self.db.get_service(name) {
+            Some(record) => ServiceStatus {
+                name: name.to_string(),
+                state: if manager.is_active(&record.label) {
+                    ServiceState::Started
+                } else {
+                    ServiceState::Error
+                },
+                label: record.label,
+                file: Some(PathBuf::from(record.file)),
+            },
+            None => ServiceStatus {
+                name: name.to_string(),
+                label: service_label(name),
+                state: ServiceState::Stopped,
+                file: None,
+            },
+        }
+    }
+
+    /// Install `name`'s service for the current user and start it, now and
+    /// at every login. Starting a started service reloads its definition.
+    pub async fn start_service(
+        &self,
+        name: &str,
+        manager: &ServiceManager,
+    ) -> Result<ServiceStatus, Error> {
+        let spec = self.service_spec(name).await?;
+        let path = manager.start(&spec)?;
+        self.db
+            .record_service(name, &spec.label, &path.display().to_string())?;
+        Ok(self.service_status(name, manager))
+    }
+
+    /// Stop `name`'s service and remove its definition. Returns whether it
+    /// had been started.
+    pub fn stop_service(&self, name: &str, manager: &ServiceManager) -> Result<bool, Error> {
+        let Some(record) = self.db.get_service(name) else {
+            return Ok(false);
+        };
+        manager.stop(&record.label)?;
+        self.db.remove_service(name)?;
+        Ok(true)
+    }
+
+    pub async fn restart_service(
+        &self,
+        name: &str,
+        manager: &ServiceManager,
+    ) -> Result<ServiceStatus, Error> {
+        // Resolve first, so a formula without a service isn't stopped.
+        self.service_spec(name).await?;
+        self.stop_service(name, manager)?;
+        self.start_service(name, manager).await
+    }
+
+    async fn service_spec(&self, name: &str) -> Result<ServiceSpec, Error> {
+        if !self.is_installed(name) {
+            return Err(Error::NotInstalled {
+                name: name.to_string(),
+            });
+        }
+        let formula: Formula = self.api_client.get_formula(name).await?;
+        let service = formula
+            .service
+            .as_ref()
+            .ok_or_else(|| Error::InvalidArgument {
+                message: format!("{name} does not define a service"),
+            })?;
+        service.resolve(name, &self.prefix, self.cellar.path())
+    }
+
+    /// Stop a started service before its formula is uninstalled, so the
+    /// service manager isn't left restarting a deleted binary.
+    pub(super) fn stop_service_for_uninstall(&self, name: &str) {
+        if self.db.get_service(name).is_none() {
+            return;
+        }
+        let stopped =
+            ServiceManager::for_user().and_then(|manager| self.stop_service(name, &manager));
+        if let Err(e) = stopped {
+            warn!(formula = %name, error = %e, "failed to stop service before uninstall");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::network::cache::ApiCache;
+
+    use super::super::test_support::{mock_api_client, test_installer};
+    use super::*;
+
+    #[tokio::test]
+    async fn lists_installed_formulas_that_define_services() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        for (name, service) in [
+            (
+                "redis",
+                r#", "service": { "run": ["$HOMEBREW_PREFIX/opt/redis/bin/redis-server"], "keep_alive": true }"#,
+            ),
+            ("jq", ""),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(format!("/formula/{name}.json")))
+                .respond_with(ResponseTemplate::new(200).set_body_string(format!(
+                    r#"{{
+                        "name": "{name}",
+                        "versions": {{ "stable": "1.0.0" }},
+                        "bottle": {{ "stable": {{ "files": {{}} }} }}
+                        {service}
+                    }}"#
+                )))
+                .mount(&mock_server)
+                .await;
+        }
+
+        let api_client = mock_api_client(&mock_server).with_cache(ApiCache::in_memory().unwrap());
+        let mut installer = test_installer(tmp.path(), api_client);
+        for name in ["redis", "jq"] {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install(name, "1.0.0", "key").unwrap();
+            tx.commit().unwrap();
+        }
+        let manager = ServiceManager::new(tmp.path().join("agents"));
+
+        let services = installer.services(&manager).await.unwrap();
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].name, "redis");
+        assert_eq!(services[0].label, "zerobrew.redis");
+        assert_eq!(services[0].state, ServiceState::Stopped);
+
+        let err = installer.start_service("jq", &manager).await.unwrap_err();
+        assert!(
+            err.to_string().contains("does not define a service"),
+            "{err}"
+        );
+        let err = installer
+            .start_service("postgresql", &manager)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::NotInstalled { .. }));
+
+        // A record the service manager doesn't know about is an error.
+        installer
+            .db
+            .record_service("redis", "zerobrew.redis-missing", "/nowhere")
+            .unwrap();
+        let services = installer.services(&manager).await.unwrap();
+        assert_eq!(services[0].state, ServiceState::Error);
+    }
+}
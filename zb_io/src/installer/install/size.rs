@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+
+use zb_core::{Error, formula_token};
+
+use crate::storage::db::InstalledKeg;
+
+use super::Installer;
+
+/// Disk usage of one formula, summed over every version in the Cellar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormulaDiskUsage {
+    pub name: String,
+    pub versions: usize,
+    pub size_bytes: u64,
+}
+
+impl Installer {
+    /// On-disk size of an installed keg in bytes.
+    ///
+    /// Served from the size cache in the database when present; otherwise
+    /// the keg is walked once and the result cached until it is reinstalled.
+    pub fn keg_size(&self, keg: &InstalledKeg) -> Result<u64, Error> {
+        if let Some(size) = self.db.cached_keg_size(&keg.name, &keg.version) {
+            return Ok(size);
+        }
+
+        let size = self
+            .cellar
+            .keg_size(formula_token(&keg.name), &keg.version)?;
+        self.db.record_keg_size(&keg.name, &keg.version, size)?;
+        Ok(size)
+    }
+
+    /// Per-formula disk usage across all versions present in the Cellar,
+    /// largest first. An empty `names` reports every formula in the Cellar.
+    pub fn disk_usage(&self, names: &[String]) -> Result<Vec<FormulaDiskUsage>, Error> {
+        let mut usage: BTreeMap<String, FormulaDiskUsage> = BTreeMap::new();
+        for name in names {
+            let token = formula_token(name).to_string();
+            usage.insert(
+                token.clone(),
+                FormulaDiskUsage {
+                    name: token,
+                    versions: 0,
+                    size_bytes: 0,
+                },
+            );
+        }
+
+        for keg in self.cellar.list_kegs()? {
+            if !names.is_empty() && !usage.contains_key(&keg.name) {
+                continue;
+            }
+
+            let size = match self.db.cached_keg_size(&keg.name, &keg.version) {
+                Some(size) => size,
+                None => {
+                    let size = self.cellar.keg_size(&keg.name, &keg.version)?;
+                    self.db.record_keg_size(&keg.name, &keg.version, size)?;
+                    size
+                }
+            };
+
+            let entry = usage
+                .entry(keg.name.clone())
+                .or_insert_with(|| FormulaDiskUsage {
+                    name: keg.name.clone(),
+                    versions: 0,
+                    size_bytes: 0,
+                });
+            entry.versions += 1;
+            entry.size_bytes += size;
+        }
+
+        if let Some(missing) = usage.values().find(|u| u.versions == 0) {
+            return Err(Error::NotInstalled {
+                name: missing.name.clone(),
+            });
+        }
+
+        let mut usage: Vec<FormulaDiskUsage> = usage.into_values().collect();
+        usage.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes).then(a.name.cmp(&b.name)));
+        Ok(usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use crate::network::api::ApiClient;
+
+    use super::super::test_support::test_installer;
+    use super::FormulaDiskUsage;
+
+    #[test]
+    fn keg_size_walks_once_then_uses_cache() {
+        let tmp = TempDir::new().unwrap();
+        let mut installer = test_installer(tmp.path(), ApiClient::new());
+
+        let keg_path = installer.keg_path("foo", "1.0.0");
+        fs::create_dir_all(&keg_path).unwrap();
+        fs::write(keg_path.join("data"), vec![0u8; 512]).unwrap();
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("foo", "1.0.0", "fookey").unwrap();
+            tx.commit().unwrap();
+        }
+
+        let keg = installer.get_installed("foo").unwrap();
+        assert_eq!(installer.keg_size(&keg).unwrap(), 512);
+
+        // Growing the keg behind the cache's back is not observed.
+        fs::write(keg_path.join("more"), vec![0u8; 512]).unwrap();
+        assert_eq!(installer.keg_size(&keg).unwrap(), 512);
+    }
+
+    #[test]
+    fn disk_usage_sums_versions_and_sorts_by_size() {
+        let tmp = TempDir::new().unwrap();
+        let installer = test_installer(tmp.path(), ApiClient::new());
+
+        for (name, version, bytes) in [
+            ("foo", "1.0.0", 100),
+            ("foo", "1.1.0", 300),
+            ("bar", "2.0.0", 1000),
+            ("baz", "0.1.0", 50),
+        ] {
+            let keg_path = installer.keg_path(name, version);
+            fs::create_dir_all(keg_path.join("bin")).unwrap();
+            fs::write(keg_path.join("bin").join(name), vec![0u8; bytes]).unwrap();
+        }
+
+        let usage = installer.disk_usage(&[]).unwrap();
+        assert_eq!(
+            usage,
+            vec![
+                FormulaDiskUsage {
+                    name: "bar".to_string(),
+                    versions: 1,
+                    size_bytes: 1000,
+                },
+                FormulaDiskUsage {
+                    name: "foo".to_string(),
+                    versions: 2,
+                    size_bytes: 400,
+                },
+                FormulaDiskUsage {
+                    name: "baz".to_string(),
+                    versions: 1,
+                    size_bytes: 50,
+                },
+            ]
+        );
+        assert_eq!(usage.iter().map(|u| u.size_bytes).sum::<u64>(), 1450);
+
+        let usage = installer
+            .disk_usage(&["foo".to_string(), "baz".to_string()])
+            .unwrap();
+        let names: Vec<&str> = usage.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, vec!["foo", "baz"]);
+
+        assert!(matches!(
+            installer.disk_usage(&["missing".to_string()]),
+            Err(zb_core::Error::NotInstalled { .. })
+        ));
+    }
+}
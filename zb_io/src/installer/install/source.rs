@@ -2,43 +2,60 @@ use std::fs;
 use std::path::{Path, PathBuf};
 
 use tracing::warn;
-use zb_core::{BuildPlan, Error};
+use zb_core::{BuildPlan, Error, Formula};
 
 use crate::progress::InstallProgress;
 
-use super::{Installer, PlannedInstall, dependency_cellar_path};
+use super::{FormulaFile, Installer, PlannedInstall, dependency_cellar_path};
 
 impl Installer {
-    pub(super) async fn install_from_source(
-        &mut self,
-        item: &PlannedInstall,
-        build_plan: &BuildPlan,
-        link: bool,
-        report: &impl Fn(InstallProgress),
-    ) -> Result<(), Error> {
-        let install_name = &item.install_name;
-        let formula_name = &item.formula.name;
-        let version = item.formula.effective_version();
+    /// Resolve the `.rb` file a source build of `name` would be handed as
+    /// `ZEROBREW_FORMULA_FILE`, fetching it into the rb cache if needed.
+    pub async fn formula_file(&self, name: &str) -> Result<FormulaFile, Error> {
+        let formula = self.api_client.get_formula(name).await?;
+        let path = self.fetch_formula_file(&formula).await?;
+        let ruby_source_path = formula.ruby_source_path.as_deref().unwrap_or_default();
+
+        Ok(FormulaFile {
+            name: formula.name.clone(),
+            path,
+            origin: self.api_client.formula_rb_origin(ruby_source_path),
+            url: self.api_client.formula_rb_url(ruby_source_path),
+        })
+    }
 
+    pub(super) async fn fetch_formula_file(&self, formula: &Formula) -> Result<PathBuf, Error> {
         let ruby_source_path =
-            item.formula
+            formula
                 .ruby_source_path
                 .as_deref()
                 .ok_or_else(|| Error::ExecutionError {
-                    message: format!("no ruby_source_path for formula '{formula_name}'"),
+                    message: format!("no ruby_source_path for formula '{}'", formula.name),
                 })?;
 
         let cache_dir = self.prefix.join("tmp").join("rb_cache");
-        let formula_rb_checksum = item
-            .formula
+        let formula_rb_checksum = formula
             .ruby_source_checksum
             .as_ref()
             .map(|checksum| checksum.sha256.as_str());
 
-        let formula_rb = self
-            .api_client
+        self.api_client
             .fetch_formula_rb(ruby_source_path, &cache_dir, formula_rb_checksum)
-            .await?;
+            .await
+    }
+
+    pub(super) async fn install_from_source(
+        &mut self,
+        item: &PlannedInstall,
+        build_plan: &BuildPlan,
+        link: bool,
+        report: &impl Fn(InstallProgress),
+    ) -> Result<(), Error> {
+        let install_name = &item.install_name;
+        let formula_name = &item.formula.name;
+        let version = build_plan.version.clone();
+
+        let formula_rb = self.fetch_formula_file(&item.formula).await?;
 
         let mut installed_deps = std::collections::HashMap::new();
         for dep_name in &build_plan.runtime_dependencies {
@@ -53,10 +70,15 @@ impl Installer {
         }
 
         let keg_path = self.cellar.keg_path(formula_name, &version);
+        let mut journal = self.begin_install_journal(install_name, &version, &keg_path)?;
         let previous_keg_backup =
-            Self::backup_existing_source_keg(&keg_path, formula_name, &version)?;
+            match Self::backup_existing_source_keg(&keg_path, formula_name, &version) {
+                Ok(backup) => backup,
+                Err(e) => return Err(self.abort_install(journal, e)),
+            };
 
-        let executor = crate::build::BuildExecutor::new(self.prefix.clone());
+        let executor = crate::build::BuildExecutor::new(self.prefix.clone())
+            .with_options(self.build_options.clone());
         if let Err(build_err) = executor
             .execute(build_plan, &formula_rb, &installed_deps)
             .await
@@ -69,7 +91,7 @@ impl Installer {
                     &version,
                 )?;
             }
-            return Err(build_err);
+            return Err(self.abort_install(journal, build_err));
         }
 
         if let Some(backup_path) = previous_keg_backup.as_ref() {
@@ -82,19 +104,13 @@ impl Installer {
 
         let store_key = format!("source:{formula_name}:{version}");
 
-        let tx = self.db.transaction().inspect_err(|_| {
-            Self::cleanup_materialized(&self.cellar, formula_name, &version);
-        })?;
-
-        if let Err(e) = tx.record_install(install_name, &version, &store_key) {
-            drop(tx);
-            Self::cleanup_materialized(&self.cellar, formula_name, &version);
-            return Err(e);
-        }
-
-        if let Err(e) = tx.commit() {
-            Self::cleanup_materialized(&self.cellar, formula_name, &version);
-            return Err(e);
+        if let Err(e) = journal.recording_install().and_then(|()| {
+            let tx = self.db.transaction()?;
+            tx.record_install(install_name, &version, &store_key)?;
+            tx.record_dependencies(install_name, &item.formula.runtime_dependencies())?;
+            tx.commit()
+        }) {
+            return Err(self.abort_install(journal, e));
         }
 
         if let Err(e) = self.linker.link_opt(&keg_path) {
@@ -107,7 +123,10 @@ impl Installer {
             report(InstallProgress::LinkStarted {
                 name: formula_name.clone(),
             });
-            match self.linker.link_keg(&keg_path) {
+            match journal
+                .linking()
+                .and_then(|()| self.link_new_keg(install_name, &keg_path))
+            {
                 Ok(files) => {
                     report(InstallProgress::LinkCompleted {
                         name: formula_name.clone(),
@@ -136,32 +155,38 @@ impl Installer {
                     }
                 }
                 Err(e) => {
-                    let _ = self.linker.unlink_keg(&keg_path);
                     report(InstallProgress::InstallCompleted {
                         name: formula_name.clone(),
                     });
-                    return Err(e);
+                    return Err(self.abort_install(journal, e));
                 }
             }
         } else if link && item.formula.is_keg_only() {
-            let reason = match &item.formula.keg_only {
-                zb_core::KegOnly::Reason(s) => s.clone(),
-                _ if item.formula.name.contains('@') => "versioned formula".to_string(),
-                _ => "keg-only formula".to_string(),
-            };
             report(InstallProgress::LinkSkipped {
                 name: formula_name.clone(),
-                reason,
+                reason: item.formula.keg_only_reason_text().unwrap_or_default(),
             });
         }
 
+        if let Err(e) = journal.commit() {
+            return Err(self.abort_install(journal, e));
+        }
+
+        let verified = if self.verify_after_install {
+            self.verify_installed_keg(install_name, &version, &keg_path)
+                .map(|_| ())
+        } else {
+            Ok(())
+        };
+
         report(InstallProgress::InstallCompleted {
             name: formula_name.clone(),
         });
-        Ok(())
+
+        verified
     }
 
-    fn backup_existing_source_keg(
+    pub(super) fn backup_existing_source_keg(
         keg_path: &Path,
         formula_name: &str,
         version: &str,
@@ -171,8 +196,21 @@ impl Installer {
         }
 
         let backup_path = Self::source_keg_backup_path(keg_path);
+        Self::backup_source_keg_to(keg_path, &backup_path, formula_name, version)?;
+
+        Ok(Some(backup_path))
+    }
+
+    /// Move `keg_path` to `backup_path`, for callers that need to record
+    /// where the keg went before moving it.
+    pub(super) fn backup_source_keg_to(
+        keg_path: &Path,
+        backup_path: &Path,
+        formula_name: &str,
+        version: &str,
+    ) -> Result<(), Error> {
         if backup_path.exists() {
-            fs::remove_dir_all(&backup_path).map_err(|e| Error::StoreCorruption {
+            fs::remove_dir_all(backup_path).map_err(|e| Error::StoreCorruption {
                 message: format!(
                     "failed to remove stale source-build backup for '{}@{}': {}",
                     formula_name, version, e
@@ -180,17 +218,15 @@ impl Installer {
             })?;
         }
 
-        fs::rename(keg_path, &backup_path).map_err(|e| Error::StoreCorruption {
+        fs::rename(keg_path, backup_path).map_err(|e| Error::StoreCorruption {
             message: format!(
                 "failed to backup existing keg for '{}@{}': {}",
                 formula_name, version, e
             ),
-        })?;
-
-        Ok(Some(backup_path))
+        })
     }
 
-    fn restore_source_keg_from_backup(
+    pub(super) fn restore_source_keg_from_backup(
         keg_path: &Path,
         backup_path: &Path,
         formula_name: &str,
@@ -213,7 +249,7 @@ impl Installer {
         })
     }
 
-    fn remove_source_keg_backup(
+    pub(super) fn remove_source_keg_backup(
         backup_path: &Path,
         formula_name: &str,
         version: &str,
@@ -230,7 +266,7 @@ impl Installer {
         })
     }
 
-    fn source_keg_backup_path(keg_path: &Path) -> PathBuf {
+    pub(super) fn source_keg_backup_path(keg_path: &Path) -> PathBuf {
         let backup_suffix = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .map(|d| d.as_nanos())
@@ -249,8 +285,77 @@ mod tests {
     use std::fs;
 
     use tempfile::TempDir;
-
-    use crate::Installer;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::cellar::Cellar;
+    use crate::network::api::ApiClient;
+    use crate::storage::blob::BlobCache;
+    use crate::storage::db::Database;
+    use crate::storage::store::Store;
+    use crate::{Installer, Linker};
+
+    #[tokio::test]
+    async fn formula_file_matches_path_fed_to_build() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let rb_url = format!("{}/rb/example.rb", mock_server.uri());
+        let rb_body = "class Example < Formula\nend\n";
+
+        Mock::given(method("GET"))
+            .and(path("/formula/example.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!(
+                r#"{{
+                    "name": "example",
+                    "versions": {{ "stable": "1.0.0" }},
+                    "dependencies": [],
+                    "urls": {{
+                        "stable": {{
+                            "url": "https://example.com/example-1.0.0.tar.gz",
+                            "checksum": "abc123"
+                        }}
+                    }},
+                    "ruby_source_path": "{rb_url}"
+                }}"#
+            )))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rb/example.rb"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(rb_body))
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        fs::create_dir_all(root.join("db")).unwrap();
+        let installer = Installer::new(
+            ApiClient::with_base_url(format!("{}/formula", mock_server.uri())).unwrap(),
+            BlobCache::new(&root.join("cache")).unwrap(),
+            Store::new(&root).unwrap(),
+            Cellar::new(&root).unwrap(),
+            Linker::new(&prefix).unwrap(),
+            Database::open(&root.join("db/zb.sqlite3")).unwrap(),
+            prefix.clone(),
+            root.join("locks"),
+        );
+
+        let file = installer.formula_file("example").await.unwrap();
+        assert_eq!(file.origin, rb_url);
+        assert_eq!(file.url, rb_url);
+        assert!(file.path.starts_with(prefix.join("tmp/rb_cache")));
+        assert_eq!(fs::read_to_string(&file.path).unwrap(), rb_body);
+
+        let plan = installer
+            .plan_with_options(&["example".to_string()], true)
+            .await
+            .unwrap();
+        let build_input = installer
+            .fetch_formula_file(&plan.items[0].formula)
+            .await
+            .unwrap();
+        assert_eq!(build_input, file.path);
+    }
 
     #[test]
     fn source_keg_backup_can_restore_previous_installation() {
@@ -4,30 +4,83 @@ use super::Installer;
 
 impl Installer {
     pub fn uninstall(&mut self, name: &str) -> Result<(), Error> {
+        let _lock = self.install_lock()?;
+        self.uninstall_inner(name)
+    }
+
+    /// No-lock variant of `uninstall`. Callers MUST already hold the install
+    /// lock — used by `autoremove` and `gc --aggressive`, which decide what
+    /// to remove under the same lock acquisition.
+    pub(crate) fn uninstall_inner(&mut self, name: &str) -> Result<(), Error> {
         let installed = self.db.get_installed(name).ok_or(Error::NotInstalled {
             name: name.to_string(),
         })?;
-        self.uninstall_by_version(name, &installed.version)
+        self.stop_service_for_uninstall(name);
+        self.uninstall_by_version(name, &installed.version)?;
+
+        // Upgrades go through `uninstall_by_version` and keep the history
+        // and pin; a full uninstall forgets both.
+        let tx = self.db.transaction()?;
+        tx.clear_install_history(name)?;
+        tx.commit()?;
+        self.db.unpin(name)?;
+        self.record_generation("uninstall");
+        Ok(())
     }
 
     pub fn uninstall_by_version(&mut self, name: &str, version: &str) -> Result<(), Error> {
-        let keg_name = formula_token(name);
+        if name.starts_with("cask:") {
+            self.remove_cask_artifacts(name)?;
+        }
+        self.deactivate_version(name, version)?;
+        self.cellar.remove_keg(formula_token(name), version)?;
+
+        Ok(())
+    }
 
-        let keg_path = self.cellar.keg_path(keg_name, version);
+    /// Unlink `version` of `name` and drop its install record, leaving the
+    /// keg itself in the Cellar. `gc --aggressive` reclaims it later.
+    pub(crate) fn deactivate_version(&mut self, name: &str, version: &str) -> Result<(), Error> {
+        let keg_path = self.cellar.keg_path(formula_token(name), version);
         self.linker.unlink_keg(&keg_path)?;
 
+        let tx = self.db.transaction()?;
+        tx.record_uninstall(name)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Make an on-disk keg the installed version of `name` again: record it
+    /// under `store_key`, point `opt/` at it and, with `link`, link it into
+    /// the prefix.
+    pub(crate) fn activate_version(
+        &mut self,
+        name: &str,
+        version: &str,
+        store_key: &str,
+        link: bool,
+    ) -> Result<(), Error> {
         {
             let tx = self.db.transaction()?;
-            tx.record_uninstall(name)?;
+            tx.record_install(name, version, store_key)?;
             tx.commit()?;
         }
 
-        self.cellar.remove_keg(keg_name, version)?;
+        let keg_path = self.cellar.keg_path(formula_token(name), version);
+        self.linker.link_opt(&keg_path)?;
+        if link {
+            let linked_files = self
+                .linker
+                .link_keg_with(&keg_path, self.link_policy_for(name))?;
+            self.record_linked_files(name, version, &linked_files);
+        }
 
         Ok(())
     }
 
     pub fn gc(&mut self) -> Result<Vec<String>, Error> {
+        let _lock = self.install_lock()?;
         let unreferenced = self.db.get_unreferenced_store_keys()?;
         let mut removed = Vec::new();
 
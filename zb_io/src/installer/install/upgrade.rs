@@ -1,10 +1,17 @@
 use std::sync::Arc;
 
-use zb_core::{Error, InstallMethod};
+use std::cmp::Ordering;
 
-use super::{InstallPlan, Installer, acquire_install_lock};
-use crate::network::download::{DownloadProgressCallback, DownloadRequest};
+use tracing::warn;
+use zb_core::{
+    Error, InstallMethod, SelectedBottle, compare_versions, formula_token, is_head_version,
+};
+
+use super::journal::ReplacedKeg;
+use super::{InstallPlan, Installer, PlannedInstall, UpgradeSelection};
+use crate::network::download::{DownloadProgressCallback, DownloadRequest, DownloadResult};
 use crate::progress::{InstallProgress, ProgressCallback};
+use crate::storage::db::InstalledKeg;
 
 impl Installer {
     /// Upgrade an installed package to its latest version.
@@ -14,23 +21,31 @@ impl Installer {
     /// existing installation intact. Source-built upgrades have no
     /// equivalent pre-build stage and still go through uninstall-first.
     ///
-    /// Uninstall-first on the link step is required either way: a fresh
-    /// install would hit `LinkConflict` on the old version's symlinks and
-    /// leave the old cellar directory behind on disk (the leak this method
-    /// exists to fix).
+    /// The old keg is unlinked before the new one is installed, since a fresh
+    /// install would otherwise hit `LinkConflict` on its symlinks, but it
+    /// stays on disk with `opt/` pointing at it until the new keg replaces
+    /// the link, so dependents keep resolving throughout. If installing the
+    /// new version fails, the old one is recorded and relinked as before.
+    ///
+    /// With `cleanup` off the old keg's Cellar directory is kept after a
+    /// successful upgrade for a quick switch back; otherwise it is removed.
+    /// A rebuild at the same version has to reuse the keg's directory, so
+    /// the old keg is moved aside instead and put back if the install fails.
     ///
     /// Returns `Ok(())` when the package is already on its latest version,
-    /// `Error::NotInstalled` when there is no existing installation.
+    /// `Error::NotInstalled` when there is no existing installation and
+    /// `Error::ExecutionError` when it is pinned.
     pub async fn upgrade(
         &mut self,
         name: &str,
         build_from_source: bool,
         link: bool,
+        cleanup: bool,
         progress: Option<Arc<ProgressCallback>>,
     ) -> Result<(), Error> {
         // One lock for the entire flow — uninstall + install must not race
         // with other zb processes touching the same package.
-        let _lock = acquire_install_lock(&self.locks_dir)?;
+        let _lock = self.install_lock()?;
 
         let old = self.db.get_installed(name).ok_or(Error::NotInstalled {
             name: name.to_string(),
@@ -38,26 +53,364 @@ impl Installer {
 
         // `plan_with_options` doesn't consult the installed DB, so an
         // empty-plan check wouldn't fire on already-current packages.
-        if self.is_outdated(name).await?.is_none() {
+        let Some(outdated) = self.is_outdated(name).await? else {
             return Ok(());
+        };
+        if self.db.is_pinned(name)? {
+            return Err(Error::ExecutionError {
+                message: format!(
+                    "{name} is pinned at {}; run `zb unpin {name}` to upgrade it",
+                    old.version
+                ),
+            });
         }
 
-        let plan = self
-            .plan_with_options(&[name.to_string()], build_from_source)
-            .await?;
+        // A HEAD keg follows its branch rather than stable releases.
+        let plan = if is_head_version(&old.version) {
+            self.plan_head(&[name.to_string()], build_from_source)
+                .await?
+        } else {
+            self.plan_with_options(&[name.to_string()], build_from_source)
+                .await?
+        };
 
         // Fetch new bottles before touching the old install — a download
         // failure here leaves the existing keg intact.
         self.prefetch_plan_bottles(&plan, progress.clone()).await?;
 
-        self.uninstall_by_version(name, &old.version)?;
+        // Replacing the keg drops its dependency mark; an upgrade shouldn't
+        // turn a dependency into something `autoremove` won't touch.
+        let dependency_only = self.db.is_dependency(name)?;
+        let result = self
+            .replace_keg(
+                &old,
+                &outdated.current_version,
+                plan,
+                link,
+                cleanup,
+                progress,
+            )
+            .await;
+        if dependency_only && self.is_installed(name) {
+            self.db.mark_dependency(name)?;
+        }
+        self.record_generation("upgrade");
+        result
+    }
+
+    async fn replace_keg(
+        &mut self,
+        old: &InstalledKeg,
+        new_version: &str,
+        plan: InstallPlan,
+        link: bool,
+        cleanup: bool,
+        progress: Option<Arc<ProgressCallback>>,
+    ) -> Result<(), Error> {
+        let name = old.name.as_str();
+        let old_keg = self.cellar.keg_path(formula_token(name), &old.version);
+        let mut replaced = ReplacedKeg {
+            version: old.version.clone(),
+            store_key: old.store_key.clone(),
+            linked: self.linker.is_linked(&old_keg),
+            backup: None,
+        };
+
+        // Journaled before anything changes, so a crash at any point from
+        // here puts the old version back on the next run.
+        let mut journal = self.begin_replace_journal(name, replaced.clone())?;
+
+        let mut result = if new_version == old.version {
+            // A rebuild at the same version reuses the keg directory, so the
+            // old keg is moved aside, as `reinstall` does, until the new one
+            // is in place.
+            let backup = Self::source_keg_backup_path(&old_keg);
+            replaced.backup = Some(backup.clone());
+            self.journal_replaced_backup(&mut journal, &backup)
+                .and_then(|()| {
+                    if name.starts_with("cask:") {
+                        self.remove_cask_artifacts(name)?;
+                    }
+                    self.deactivate_version(name, &old.version)
+                })
+                .and_then(|()| Self::backup_source_keg_to(&old_keg, &backup, name, &old.version))
+        } else {
+            self.deactivate_version(name, &old.version)
+                .and_then(|()| self.linker.link_opt(&old_keg))
+        };
+        if result.is_ok() {
+            // We already hold the lock, so call the no-lock variant.
+            result = self.execute_inner(plan, link, progress).await.map(|_| ());
+        }
+
+        if result.is_err()
+            && let Err(restore) = self.restore_replaced_keg(name, &replaced)
+        {
+            warn!(formula = %name, error = %restore, "failed to restore previous version after upgrade error");
+        }
+        let finished = self.finish_replace_journal(journal);
+        result?;
+        finished?;
+
+        if let Some(backup) = &replaced.backup {
+            Self::remove_source_keg_backup(backup, name, &old.version)?;
+        } else if cleanup {
+            self.cellar.remove_keg(formula_token(name), &old.version)?;
+        }
+
+        Ok(())
+    }
+
+    /// Make sure at least version `floor` of `name` is installed: a no-op
+    /// when the installed version is already `floor` or newer, otherwise a
+    /// fresh install or an upgrade.
+    ///
+    /// Fails without touching anything when even the latest available
+    /// version is older than `floor`. Returns the version that was
+    /// installed, or `None` when nothing needed doing.
+    pub async fn install_at_least(
+        &mut self,
+        name: &str,
+        floor: &str,
+        build_from_source: bool,
+        link: bool,
+        progress: Option<Arc<ProgressCallback>>,
+    ) -> Result<Option<String>, Error> {
+        let installed = self.db.get_installed(name);
+        if let Some(keg) = &installed
+            && compare_versions(&keg.version, floor) != Ordering::Less
+        {
+            return Ok(None);
+        }
+
+        let latest = self.api_client.get_formula(name).await?.effective_version();
+        if compare_versions(&latest, floor) == Ordering::Less {
+            return Err(Error::UnsupportedFormula {
+                name: name.to_string(),
+                reason: format!("latest available version {latest} is older than {floor}"),
+            });
+        }
+
+        if installed.is_some() {
+            self.upgrade(name, build_from_source, link, true, progress)
+                .await?;
+        } else {
+            let plan = self
+                .plan_with_options(&[name.to_string()], build_from_source)
+                .await?;
+            self.execute_with_progress(plan, link, progress).await?;
+        }
+
+        Ok(Some(latest))
+    }
+
+    /// Plan a reinstall of `name` at its installed version.
+    ///
+    /// Dependencies that are already installed are left alone. With
+    /// `from_source` the target must resolve to a source build even when a
+    /// bottle is available; there is no bottle fallback.
+    pub async fn plan_reinstall(
+        &self,
+        name: &str,
+        from_source: bool,
+    ) -> Result<InstallPlan, Error> {
+        let old = self.db.get_installed(name).ok_or(Error::NotInstalled {
+            name: name.to_string(),
+        })?;
+
+        let mut plan = self
+            .plan_with_options(&[name.to_string()], from_source)
+            .await?;
+        plan.items
+            .retain(|item| item.install_name == name || !self.is_installed(&item.install_name));
+
+        let target = plan
+            .items
+            .iter()
+            .find(|item| item.install_name == name)
+            .ok_or(Error::MissingFormula {
+                name: name.to_string(),
+            })?;
+
+        let version = target.formula.effective_version();
+        if version != old.version {
+            return Err(Error::ExecutionError {
+                message: format!(
+                    "cannot reinstall {name} {}: the formula now provides {version} (use `zb upgrade {name}`)",
+                    old.version
+                ),
+            });
+        }
+
+        if from_source && !matches!(target.method, InstallMethod::Source(_)) {
+            return Err(Error::UnsupportedFormula {
+                name: name.to_string(),
+                reason: "no source URL to build from".to_string(),
+            });
+        }
+
+        Ok(plan)
+    }
+
+    /// Remove and reinstall `name` at the same version, optionally forcing a
+    /// source build of a bottle-installed keg.
+    ///
+    /// A bottle keg whose store entry survived is rebuilt from the store
+    /// without touching the network; a missing or unusable entry is dropped
+    /// and the bottle fetched again. Like `upgrade`, bottles are fetched
+    /// before the old keg is touched, and the old keg is set aside rather
+    /// than removed until the new one is in place: a failed download or
+    /// build restores it, links included. Dependents are left alone, and
+    /// install history, pins and dependency marks are kept.
+    pub async fn reinstall(
+        &mut self,
+        name: &str,
+        from_source: bool,
+        link: bool,
+        progress: Option<Arc<ProgressCallback>>,
+    ) -> Result<(), Error> {
+        let _lock = self.install_lock()?;
+
+        let mut plan = self.plan_reinstall(name, from_source).await?;
+        let old = self.db.get_installed(name).ok_or(Error::NotInstalled {
+            name: name.to_string(),
+        })?;
+        let dependency_only = self.db.is_dependency(name)?;
+
+        let from_store = self.take_stored_bottle(&mut plan, name);
+
+        self.prefetch_plan_bottles(&plan, progress.clone()).await?;
+
+        if name.starts_with("cask:") {
+            self.remove_cask_artifacts(name)?;
+        }
+        let old_keg = self.cellar.keg_path(formula_token(name), &old.version);
+        let was_linked = self.linker.is_linked(&old_keg);
+        self.deactivate_version(name, &old.version)?;
+        let backup = Self::backup_existing_source_keg(&old_keg, name, &old.version)?;
+
+        let mut result = self
+            .execute_inner(plan, link, progress.clone())
+            .await
+            .map(|_| ());
+        if result.is_ok()
+            && let Some((item, bottle)) = from_store
+        {
+            result = self
+                .reinstall_from_store(item, bottle, link, progress)
+                .await;
+        }
+
+        if let Err(e) = result {
+            if let Some(backup) = &backup {
+                let restored =
+                    Self::restore_source_keg_from_backup(&old_keg, backup, name, &old.version)
+                        .and_then(|()| {
+                            self.activate_version(name, &old.version, &old.store_key, was_linked)
+                        });
+                if let Err(restore) = restored {
+                    warn!(formula = %name, error = %restore, "failed to restore previous keg after reinstall error");
+                }
+            }
+            return Err(e);
+        }
+        if let Some(backup) = &backup {
+            Self::remove_source_keg_backup(backup, name, &old.version)?;
+        }
+
+        if dependency_only {
+            self.db.mark_dependency(name)?;
+        }
+        self.record_generation("reinstall");
+
+        Ok(())
+    }
+
+    /// Pull `name`'s bottle out of `plan` when its store entry can be reused.
+    /// An entry that exists but holds nothing is removed so the bottle is
+    /// downloaded and extracted afresh.
+    fn take_stored_bottle(
+        &self,
+        plan: &mut InstallPlan,
+        name: &str,
+    ) -> Option<(PlannedInstall, SelectedBottle)> {
+        let index = plan
+            .items
+            .iter()
+            .position(|item| item.install_name == name)?;
+        let InstallMethod::Bottle(bottle) = &plan.items[index].method else {
+            return None;
+        };
+        let bottle = bottle.clone();
+
+        if !self.has_populated_store_entry(&bottle.sha256) {
+            if self.store.entry_path(&bottle.sha256).exists()
+                && let Err(e) = self.store.remove_entry(&bottle.sha256)
+            {
+                warn!(formula = %name, error = %e, "failed to remove unusable store entry");
+            }
+            return None;
+        }
+
+        Some((plan.items.remove(index), bottle))
+    }
+
+    /// Rebuild a keg from its existing store entry. If that fails, the entry
+    /// is treated as corrupt: it is removed along with any partial keg and
+    /// the bottle goes through a regular download and install instead.
+    async fn reinstall_from_store(
+        &mut self,
+        item: PlannedInstall,
+        bottle: SelectedBottle,
+        link: bool,
+        progress: Option<Arc<ProgressCallback>>,
+    ) -> Result<(), Error> {
+        let download = DownloadResult {
+            name: item.formula.name.clone(),
+            sha256: bottle.sha256.clone(),
+            blob_path: self.downloader.blob_cache().blob_path(&bottle.sha256),
+            index: 0,
+        };
+        let report = |event: InstallProgress| {
+            if let Some(cb) = &progress {
+                cb(event);
+            }
+        };
 
-        // We already hold the lock, so call the no-lock variant.
-        self.execute_inner(plan, link, progress).await?;
+        let Err(e) = self
+            .process_bottle_item(&item, &bottle, &download, &None, link, &report)
+            .await
+        else {
+            return Ok(());
+        };
 
+        warn!(formula = %item.install_name, error = %e, "store entry unusable, downloading bottle again");
+        let version = item.formula.effective_version();
+        self.cellar.remove_keg(&item.formula.name, &version)?;
+        self.store.remove_entry(&bottle.sha256)?;
+        self.execute_inner(InstallPlan { items: vec![item] }, link, progress)
+            .await?;
         Ok(())
     }
 
+    /// Collect every outdated package for `upgrade --all`, setting aside
+    /// pinned ones so callers can report them instead of upgrading.
+    pub async fn select_upgrades(&self) -> Result<UpgradeSelection, Error> {
+        let (outdated, warnings) = self.check_outdated().await?;
+        let pinned_names: std::collections::HashSet<String> =
+            self.db.list_pinned()?.into_iter().collect();
+
+        let (pinned, outdated): (Vec<_>, Vec<_>) = outdated
+            .into_iter()
+            .partition(|pkg| pinned_names.contains(&pkg.name));
+
+        Ok(UpgradeSelection {
+            outdated,
+            pinned,
+            warnings,
+        })
+    }
+
     /// Pre-download bottle artifacts in `plan` into the blob cache. No-op
     /// for source-only plans.
     async fn prefetch_plan_bottles(
@@ -106,56 +459,34 @@ mod tests {
     use wiremock::matchers::{method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
-    use crate::cellar::Cellar;
+    use crate::SkippedPin;
     use crate::installer::install::test_support::*;
-    use crate::network::api::ApiClient;
-    use crate::storage::blob::BlobCache;
-    use crate::storage::db::Database;
-    use crate::storage::store::Store;
-    use crate::{Installer, Linker};
-
-    fn formula_json(mock_uri: &str, name: &str, version: &str, tag: &str, sha: &str) -> String {
-        format!(
-            r#"{{
-                "name": "{name}",
-                "versions": {{ "stable": "{version}" }},
-                "dependencies": [],
-                "bottle": {{
-                    "stable": {{
-                        "files": {{
-                            "{tag}": {{
-                                "url": "{mock_uri}/bottles/{name}-{version}.{tag}.bottle.tar.gz",
-                                "sha256": "{sha}"
-                            }}
-                        }}
-                    }}
-                }}
-            }}"#
-        )
-    }
 
-    fn make_installer(
-        root: &std::path::Path,
-        prefix: &std::path::Path,
-        mock_uri: &str,
-    ) -> Installer {
-        fs::create_dir_all(root.join("db")).unwrap();
-        let api_client = ApiClient::with_base_url(format!("{mock_uri}/formula")).unwrap();
-        let blob_cache = BlobCache::new(&root.join("cache")).unwrap();
-        let store = Store::new(root).unwrap();
-        let cellar = Cellar::new(root).unwrap();
-        let linker = Linker::new(prefix).unwrap();
-        let db = Database::open(&root.join("db/zb.sqlite3")).unwrap();
-        Installer::new(
-            api_client,
-            blob_cache,
-            store,
-            cellar,
-            linker,
-            db,
-            prefix.to_path_buf(),
-            root.join("locks"),
-        )
+    /// Serve `name` at `version`, formula and bottle, with no request limit.
+    async fn mount_version(mock_server: &MockServer, name: &str, version: &str) {
+        let tag = get_test_bottle_tag();
+        let bottle = create_bottle_tarball_with_version(name, version);
+        let sha = sha256_hex(&bottle);
+        Mock::given(method("GET"))
+            .and(path(format!("/formula/{name}.json")))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    name,
+                    version,
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha,
+                )),
+            )
+            .mount(mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/bottles/{name}-{version}.{tag}.bottle.tar.gz"
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle))
+            .mount(mock_server)
+            .await;
     }
 
     #[tokio::test]
@@ -171,13 +502,15 @@ mod tests {
 
         Mock::given(method("GET"))
             .and(path("/formula/testpkg.json"))
-            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(
-                &mock_server.uri(),
-                "testpkg",
-                "1.0.0",
-                tag,
-                &sha_v1,
-            )))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "testpkg",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha_v1,
+                )),
+            )
             .up_to_n_times(1)
             .mount(&mock_server)
             .await;
@@ -189,13 +522,15 @@ mod tests {
             .await;
         Mock::given(method("GET"))
             .and(path("/formula/testpkg.json"))
-            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(
-                &mock_server.uri(),
-                "testpkg",
-                "2.0.0",
-                tag,
-                &sha_v2,
-            )))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "testpkg",
+                    "2.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha_v2,
+                )),
+            )
             .mount(&mock_server)
             .await;
         Mock::given(method("GET"))
@@ -206,7 +541,7 @@ mod tests {
 
         let root = tmp.path().join("zerobrew");
         let prefix = tmp.path().join("homebrew");
-        let mut installer = make_installer(&root, &prefix, &mock_server.uri());
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
 
         installer
             .install(&["testpkg".to_string()], true)
@@ -216,7 +551,7 @@ mod tests {
         assert!(prefix.join("bin/testpkg").exists());
 
         installer
-            .upgrade("testpkg", false, true, None)
+            .upgrade("testpkg", false, true, true, None)
             .await
             .unwrap();
 
@@ -243,6 +578,225 @@ mod tests {
         assert_eq!(installed.version, "2.0.0");
     }
 
+    #[tokio::test]
+    async fn upgrade_without_cleanup_keeps_old_keg_and_activates_new_one() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let tag = get_test_bottle_tag();
+
+        let bottle_v1 = create_bottle_tarball_with_version("keeppkg", "1.0.0");
+        let sha_v1 = sha256_hex(&bottle_v1);
+        let bottle_v2 = create_bottle_tarball_with_version("keeppkg", "2.0.0");
+        let sha_v2 = sha256_hex(&bottle_v2);
+
+        Mock::given(method("GET"))
+            .and(path("/formula/keeppkg.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "keeppkg",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha_v1,
+                )),
+            )
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/keeppkg-1.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle_v1))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/formula/keeppkg.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "keeppkg",
+                    "2.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha_v2,
+                )),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/keeppkg-2.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle_v2))
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        installer
+            .install(&["keeppkg".to_string()], true)
+            .await
+            .unwrap();
+        installer
+            .upgrade("keeppkg", false, true, false, None)
+            .await
+            .unwrap();
+
+        assert!(root.join("cellar/keeppkg/1.0.0/bin/keeppkg").exists());
+        assert!(root.join("cellar/keeppkg/2.0.0/bin/keeppkg").exists());
+
+        let target = fs::read_link(prefix.join("bin/keeppkg")).unwrap();
+        assert!(
+            target.starts_with(root.join("cellar/keeppkg/2.0.0")),
+            "symlink must point at 2.0.0, got {}",
+            target.display()
+        );
+        assert_eq!(installer.get_installed("keeppkg").unwrap().version, "2.0.0");
+        let versions: Vec<String> = installer
+            .install_history("keeppkg")
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.version)
+            .collect();
+        assert!(versions.contains(&"1.0.0".to_string()));
+    }
+
+    #[tokio::test]
+    async fn rollback_after_upgrade_restores_previous_version() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let tag = get_test_bottle_tag();
+
+        let bottle_v1 = create_bottle_tarball_with_version("backpkg", "1.0.0");
+        let sha_v1 = sha256_hex(&bottle_v1);
+        let bottle_v2 = create_bottle_tarball_with_version("backpkg", "2.0.0");
+        let sha_v2 = sha256_hex(&bottle_v2);
+
+        Mock::given(method("GET"))
+            .and(path("/formula/backpkg.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "backpkg",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha_v1,
+                )),
+            )
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/backpkg-1.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle_v1))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/formula/backpkg.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "backpkg",
+                    "2.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha_v2,
+                )),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/backpkg-2.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle_v2))
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        installer
+            .install(&["backpkg".to_string()], true)
+            .await
+            .unwrap();
+        let err = installer.rollback("backpkg").unwrap_err();
+        assert!(err.to_string().contains("--no-cleanup"), "{err}");
+
+        installer
+            .upgrade("backpkg", false, true, false, None)
+            .await
+            .unwrap();
+        assert_eq!(installer.rollback("backpkg").unwrap(), "1.0.0");
+
+        assert_eq!(installer.get_installed("backpkg").unwrap().version, "1.0.0");
+        let v1 = root.join("cellar/backpkg/1.0.0");
+        assert!(
+            fs::read_link(prefix.join("bin/backpkg"))
+                .unwrap()
+                .starts_with(&v1)
+        );
+        assert_eq!(fs::read_link(prefix.join("opt/backpkg")).unwrap(), v1);
+        assert!(root.join("cellar/backpkg/2.0.0").exists());
+
+        // Rolling back again returns to the version we just left.
+        assert_eq!(installer.rollback("backpkg").unwrap(), "2.0.0");
+    }
+
+    #[tokio::test]
+    async fn install_at_least_installs_upgrades_or_does_nothing() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        let root = tmp.path().join("zerobrew");
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        // Fresh install when nothing is there yet.
+        mount_version(&mock_server, "floorpkg", "1.0.0").await;
+        let installed = installer
+            .install_at_least("floorpkg", "1.0", false, true, None)
+            .await
+            .unwrap();
+        assert_eq!(installed.as_deref(), Some("1.0.0"));
+        assert!(root.join("cellar/floorpkg/1.0.0").exists());
+
+        // Equal or newer than the floor is a no-op.
+        mock_server.reset().await;
+        mount_version(&mock_server, "floorpkg", "2.0.0").await;
+        for floor in ["1.0.0", "0.9"] {
+            let result = installer
+                .install_at_least("floorpkg", floor, false, true, None)
+                .await
+                .unwrap();
+            assert_eq!(result, None);
+        }
+        assert_eq!(
+            installer.get_installed("floorpkg").unwrap().version,
+            "1.0.0"
+        );
+
+        // Older than the floor upgrades.
+        let upgraded = installer
+            .install_at_least("floorpkg", "1.5", false, true, None)
+            .await
+            .unwrap();
+        assert_eq!(upgraded.as_deref(), Some("2.0.0"));
+        assert_eq!(
+            installer.get_installed("floorpkg").unwrap().version,
+            "2.0.0"
+        );
+        assert!(!root.join("cellar/floorpkg/1.0.0").exists());
+
+        // A floor nothing can satisfy fails and leaves the install alone.
+        let err = installer
+            .install_at_least("floorpkg", "3.0", false, true, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, zb_core::Error::UnsupportedFormula { .. }));
+        assert_eq!(
+            installer.get_installed("floorpkg").unwrap().version,
+            "2.0.0"
+        );
+    }
+
     #[tokio::test]
     async fn upgrade_with_no_link_does_not_create_symlinks() {
         let mock_server = MockServer::start().await;
@@ -256,13 +810,15 @@ mod tests {
 
         Mock::given(method("GET"))
             .and(path("/formula/nolinkpkg.json"))
-            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(
-                &mock_server.uri(),
-                "nolinkpkg",
-                "1.0.0",
-                tag,
-                &sha_v1,
-            )))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "nolinkpkg",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha_v1,
+                )),
+            )
             .up_to_n_times(1)
             .mount(&mock_server)
             .await;
@@ -276,13 +832,15 @@ mod tests {
             .await;
         Mock::given(method("GET"))
             .and(path("/formula/nolinkpkg.json"))
-            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(
-                &mock_server.uri(),
-                "nolinkpkg",
-                "2.0.0",
-                tag,
-                &sha_v2,
-            )))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "nolinkpkg",
+                    "2.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha_v2,
+                )),
+            )
             .mount(&mock_server)
             .await;
         Mock::given(method("GET"))
@@ -295,7 +853,7 @@ mod tests {
 
         let root = tmp.path().join("zerobrew");
         let prefix = tmp.path().join("homebrew");
-        let mut installer = make_installer(&root, &prefix, &mock_server.uri());
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
 
         installer
             .install(&["nolinkpkg".to_string()], true)
@@ -304,7 +862,7 @@ mod tests {
         assert!(prefix.join("bin/nolinkpkg").exists());
 
         installer
-            .upgrade("nolinkpkg", false, false, None)
+            .upgrade("nolinkpkg", false, false, true, None)
             .await
             .unwrap();
 
@@ -327,13 +885,15 @@ mod tests {
 
         Mock::given(method("GET"))
             .and(path("/formula/steadypkg.json"))
-            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(
-                &mock_server.uri(),
-                "steadypkg",
-                "1.0.0",
-                tag,
-                &sha,
-            )))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "steadypkg",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha,
+                )),
+            )
             .mount(&mock_server)
             .await;
         Mock::given(method("GET"))
@@ -347,7 +907,7 @@ mod tests {
 
         let root = tmp.path().join("zerobrew");
         let prefix = tmp.path().join("homebrew");
-        let mut installer = make_installer(&root, &prefix, &mock_server.uri());
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
 
         installer
             .install(&["steadypkg".to_string()], true)
@@ -355,7 +915,7 @@ mod tests {
             .unwrap();
 
         installer
-            .upgrade("steadypkg", false, true, None)
+            .upgrade("steadypkg", false, true, true, None)
             .await
             .unwrap();
 
@@ -371,12 +931,10 @@ mod tests {
     async fn upgrade_errors_when_not_installed() {
         let mock_server = MockServer::start().await;
         let tmp = TempDir::new().unwrap();
-        let root = tmp.path().join("zerobrew");
-        let prefix = tmp.path().join("homebrew");
-        let mut installer = make_installer(&root, &prefix, &mock_server.uri());
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
 
         let err = installer
-            .upgrade("nonexistent", false, true, None)
+            .upgrade("nonexistent", false, true, true, None)
             .await
             .unwrap_err();
         assert!(matches!(err, zb_core::Error::NotInstalled { .. }));
@@ -393,13 +951,15 @@ mod tests {
 
         Mock::given(method("GET"))
             .and(path("/formula/flakypkg.json"))
-            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(
-                &mock_server.uri(),
-                "flakypkg",
-                "1.0.0",
-                tag,
-                &sha,
-            )))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "flakypkg",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha,
+                )),
+            )
             .up_to_n_times(1)
             .mount(&mock_server)
             .await;
@@ -414,13 +974,15 @@ mod tests {
         // returns 500 — exercise the pre-fetch failure path.
         Mock::given(method("GET"))
             .and(path("/formula/flakypkg.json"))
-            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json(
-                &mock_server.uri(),
-                "flakypkg",
-                "2.0.0",
-                tag,
-                "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
-            )))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "flakypkg",
+                    "2.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+                )),
+            )
             .mount(&mock_server)
             .await;
         Mock::given(method("GET"))
@@ -431,7 +993,7 @@ mod tests {
 
         let root = tmp.path().join("zerobrew");
         let prefix = tmp.path().join("homebrew");
-        let mut installer = make_installer(&root, &prefix, &mock_server.uri());
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
 
         installer
             .install(&["flakypkg".to_string()], true)
@@ -441,7 +1003,7 @@ mod tests {
         let bin_link = prefix.join("bin/flakypkg");
         assert!(bin_link.exists());
 
-        let result = installer.upgrade("flakypkg", false, true, None).await;
+        let result = installer.upgrade("flakypkg", false, true, true, None).await;
         assert!(result.is_err(), "upgrade should fail when bottle 500s");
 
         assert!(
@@ -458,4 +1020,567 @@ mod tests {
         let installed = installer.get_installed("flakypkg").unwrap();
         assert_eq!(installed.version, "1.0.0");
     }
+
+    #[tokio::test]
+    async fn upgrade_restores_old_version_when_new_keg_fails_to_install() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let tag = get_test_bottle_tag();
+
+        let bottle = create_bottle_tarball_with_version("brokenpkg", "1.0.0");
+        let sha = sha256_hex(&bottle);
+        Mock::given(method("GET"))
+            .and(path("/formula/brokenpkg.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "brokenpkg",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha,
+                )),
+            )
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/bottles/brokenpkg-1.0.0.{tag}.bottle.tar.gz"
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle))
+            .mount(&mock_server)
+            .await;
+
+        // The 2.0.0 bottle downloads and matches its checksum, so prefetch
+        // succeeds, but it isn't a tarball: the failure comes after the old
+        // keg has been unlinked.
+        let broken = b"not a tarball".to_vec();
+        let broken_sha = sha256_hex(&broken);
+        Mock::given(method("GET"))
+            .and(path("/formula/brokenpkg.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "brokenpkg",
+                    "2.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &broken_sha,
+                )),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/bottles/brokenpkg-2.0.0.{tag}.bottle.tar.gz"
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(broken))
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        installer
+            .install(&["brokenpkg".to_string()], true)
+            .await
+            .unwrap();
+        let old_key = installer.get_installed("brokenpkg").unwrap().store_key;
+
+        let result = installer
+            .upgrade("brokenpkg", false, true, true, None)
+            .await;
+        assert!(result.is_err(), "upgrade should fail on a corrupt bottle");
+
+        let installed = installer.get_installed("brokenpkg").unwrap();
+        assert_eq!(installed.version, "1.0.0");
+        assert_eq!(installed.store_key, old_key);
+        assert!(root.join("cellar/brokenpkg/1.0.0").exists());
+        assert!(!root.join("cellar/brokenpkg/2.0.0").exists());
+        let bin_link = prefix.join("bin/brokenpkg");
+        assert!(
+            fs::read_link(&bin_link)
+                .unwrap()
+                .to_string_lossy()
+                .contains("1.0.0")
+        );
+        assert!(
+            fs::read_link(prefix.join("opt/brokenpkg"))
+                .unwrap()
+                .ends_with("brokenpkg/1.0.0")
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_rebuild_at_the_same_version_puts_the_old_keg_back() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let tag = get_test_bottle_tag();
+
+        let bottle = create_bottle_tarball_with_version("rebuildpkg", "1.0.0");
+        let sha = sha256_hex(&bottle);
+        Mock::given(method("GET"))
+            .and(path("/formula/rebuildpkg.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "rebuildpkg",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha,
+                )),
+            )
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/bottles/rebuildpkg-1.0.0.{tag}.bottle.tar.gz"
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+
+        // A rebuilt bottle at the same version that downloads fine but
+        // isn't a tarball.
+        let broken = b"not a tarball".to_vec();
+        let broken_sha = sha256_hex(&broken);
+        Mock::given(method("GET"))
+            .and(path("/formula/rebuildpkg.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "rebuildpkg",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &broken_sha,
+                )),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/bottles/rebuildpkg-1.0.0.{tag}.bottle.tar.gz"
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(broken))
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        installer
+            .install(&["rebuildpkg".to_string()], true)
+            .await
+            .unwrap();
+        let old_key = installer.get_installed("rebuildpkg").unwrap().store_key;
+
+        let result = installer
+            .upgrade("rebuildpkg", false, true, true, None)
+            .await;
+        assert!(result.is_err(), "upgrade should fail on a corrupt bottle");
+
+        let installed = installer.get_installed("rebuildpkg").unwrap();
+        assert_eq!(installed.version, "1.0.0");
+        assert_eq!(installed.store_key, old_key);
+        let keg = root.join("cellar/rebuildpkg/1.0.0");
+        assert!(keg.join("bin/rebuildpkg").exists());
+        assert_eq!(
+            fs::read_dir(root.join("cellar/rebuildpkg"))
+                .unwrap()
+                .count(),
+            1,
+            "no backup of the old keg is left behind"
+        );
+        assert!(
+            fs::read_link(prefix.join("bin/rebuildpkg"))
+                .unwrap()
+                .starts_with(&keg)
+        );
+        assert!(
+            fs::read_link(prefix.join("opt/rebuildpkg"))
+                .unwrap()
+                .ends_with("rebuildpkg/1.0.0")
+        );
+    }
+
+    #[tokio::test]
+    async fn pinned_keg_is_not_replaced_by_upgrade_or_install() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("zerobrew");
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        mount_version(&mock_server, "held", "1.0.0").await;
+        installer
+            .install(&["held".to_string()], true)
+            .await
+            .unwrap();
+        installer.pin("held").unwrap();
+
+        mock_server.reset().await;
+        mount_version(&mock_server, "held", "2.0.0").await;
+
+        let err = installer
+            .upgrade("held", false, true, true, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("zb unpin held"));
+
+        let result = installer
+            .install(&["held".to_string()], true)
+            .await
+            .unwrap();
+        assert_eq!(
+            result.skipped_pinned,
+            vec![SkippedPin {
+                name: "held".to_string(),
+                pinned_version: "1.0.0".to_string(),
+                planned_version: "2.0.0".to_string(),
+            }]
+        );
+        assert_eq!(installer.get_installed("held").unwrap().version, "1.0.0");
+        assert!(!root.join("cellar/held/2.0.0").exists());
+
+        installer.unpin("held").unwrap();
+        installer
+            .upgrade("held", false, true, true, None)
+            .await
+            .unwrap();
+        assert_eq!(installer.get_installed("held").unwrap().version, "2.0.0");
+    }
+
+    #[tokio::test]
+    async fn ignore_pins_lets_an_install_replace_a_pinned_keg() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        mount_version(&mock_server, "held", "1.0.0").await;
+        installer
+            .install(&["held".to_string()], false)
+            .await
+            .unwrap();
+        installer.pin("held").unwrap();
+
+        mock_server.reset().await;
+        mount_version(&mock_server, "held", "2.0.0").await;
+
+        installer.ignore_pins = true;
+        let result = installer
+            .install(&["held".to_string()], false)
+            .await
+            .unwrap();
+        assert!(result.skipped_pinned.is_empty());
+        assert_eq!(installer.get_installed("held").unwrap().version, "2.0.0");
+    }
+
+    #[tokio::test]
+    async fn select_upgrades_separates_pinned_and_up_to_date() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let uri = mock_server.uri();
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("stale", "1.0.0", "stale_old").unwrap();
+            tx.record_install("held", "1.0.0", "held_old").unwrap();
+            tx.record_install("fresh", "1.0.0", "fresh_sha").unwrap();
+            tx.commit().unwrap();
+        }
+        installer.pin("held").unwrap();
+
+        let bulk = format!(
+            "[{},{},{}]",
+            formula_json_with_bottle(
+                "stale",
+                "2.0.0",
+                &[],
+                &format!("{uri}/bottles"),
+                "stale_new"
+            ),
+            formula_json_with_bottle("held", "2.0.0", &[], &format!("{uri}/bottles"), "held_new"),
+            formula_json_with_bottle(
+                "fresh",
+                "1.0.0",
+                &[],
+                &format!("{uri}/bottles"),
+                "fresh_sha"
+            ),
+        );
+        Mock::given(method("GET"))
+            .and(path("/formula.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(bulk))
+            .mount(&mock_server)
+            .await;
+
+        let selection = installer.select_upgrades().await.unwrap();
+
+        let outdated: Vec<_> = selection.outdated.iter().map(|p| p.name.as_str()).collect();
+        let pinned: Vec<_> = selection.pinned.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(outdated, vec!["stale"]);
+        assert_eq!(pinned, vec!["held"]);
+        assert!(selection.warnings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reinstall_from_source_routes_bottle_keg_through_build() {
+        // The build runs the formula through the Ruby shim.
+        if std::process::Command::new("ruby")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            return;
+        }
+
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let tag = get_test_bottle_tag();
+
+        let bottle = create_bottle_tarball("testpkg");
+        let sha = sha256_hex(&bottle);
+        let source = create_bottle_tarball("testpkg-src");
+        let mut formula: serde_json::Value = serde_json::from_str(&formula_json_with_bottle(
+            "testpkg",
+            "1.0.0",
+            &[],
+            &format!("{}/bottles", mock_server.uri()),
+            &sha,
+        ))
+        .unwrap();
+        formula["urls"] = serde_json::json!({
+            "stable": {
+                "url": format!("{}/src/testpkg-1.0.0.tar.gz", mock_server.uri()),
+                "checksum": sha256_hex(&source),
+            }
+        });
+        formula["ruby_source_path"] =
+            serde_json::json!(format!("{}/rb/testpkg.rb", mock_server.uri()));
+
+        Mock::given(method("GET"))
+            .and(path("/formula/testpkg.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(formula.to_string()))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/testpkg-1.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/src/testpkg-1.0.0.tar.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(source))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rb/testpkg.rb"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "class Testpkg < Formula\n  def install\n    (prefix/\"built-from-source\").write \"yes\"\n  end\nend\n",
+            ))
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        installer
+            .install(&["testpkg".to_string()], true)
+            .await
+            .unwrap();
+        assert_eq!(installer.get_installed("testpkg").unwrap().store_key, sha);
+
+        let plan = installer.plan_reinstall("testpkg", false).await.unwrap();
+        assert!(matches!(
+            plan.items[0].method,
+            zb_core::InstallMethod::Bottle(_)
+        ));
+
+        installer
+            .reinstall("testpkg", true, true, None)
+            .await
+            .unwrap();
+
+        let keg = root.join("cellar/testpkg/1.0.0");
+        assert_eq!(
+            fs::read_to_string(keg.join("built-from-source")).unwrap(),
+            "yes"
+        );
+        assert!(!keg.join("bin/testpkg").exists());
+        assert_eq!(
+            installer.get_installed("testpkg").unwrap().store_key,
+            "source:testpkg:1.0.0"
+        );
+        // Nothing of the bottle keg is left beside the rebuilt one.
+        assert_eq!(
+            fs::read_dir(root.join("cellar/testpkg")).unwrap().count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn reinstall_restores_the_old_keg_when_the_build_fails() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let tag = get_test_bottle_tag();
+
+        let bottle = create_bottle_tarball("testpkg");
+        let sha = sha256_hex(&bottle);
+        let mut formula: serde_json::Value = serde_json::from_str(&formula_json_with_bottle(
+            "testpkg",
+            "1.0.0",
+            &[],
+            &format!("{}/bottles", mock_server.uri()),
+            &sha,
+        ))
+        .unwrap();
+        formula["urls"] = serde_json::json!({
+            "stable": {
+                "url": format!("{}/src/testpkg-1.0.0.tar.gz", mock_server.uri()),
+                "checksum": "ab".repeat(32),
+            }
+        });
+
+        Mock::given(method("GET"))
+            .and(path("/formula/testpkg.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(formula.to_string()))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/testpkg-1.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/src/testpkg-1.0.0.tar.gz"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+        installer
+            .install(&["testpkg".to_string()], true)
+            .await
+            .unwrap();
+
+        assert!(
+            installer
+                .reinstall("testpkg", true, true, None)
+                .await
+                .is_err()
+        );
+
+        let keg = root.join("cellar/testpkg/1.0.0");
+        assert!(keg.join("bin/testpkg").exists());
+        assert_eq!(installer.get_installed("testpkg").unwrap().store_key, sha);
+        assert!(prefix.join("bin/testpkg").exists());
+        assert_eq!(
+            fs::read_dir(root.join("cellar/testpkg")).unwrap().count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn reinstall_from_source_requires_a_source_url() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+
+        Mock::given(method("GET"))
+            .and(path("/formula/testpkg.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "testpkg",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &"ab".repeat(32),
+                )),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("testpkg", "1.0.0", &"ab".repeat(32))
+                .unwrap();
+            tx.commit().unwrap();
+        }
+
+        let err = installer.plan_reinstall("testpkg", true).await.unwrap_err();
+        assert!(matches!(err, zb_core::Error::UnsupportedFormula { .. }));
+    }
+
+    #[tokio::test]
+    async fn reinstall_rebuilds_from_store_and_refetches_an_empty_entry() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let tag = get_test_bottle_tag();
+
+        let bottle = create_bottle_tarball("testpkg");
+        let sha = sha256_hex(&bottle);
+        Mock::given(method("GET"))
+            .and(path("/formula/testpkg.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "testpkg",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &sha,
+                )),
+            )
+            .mount(&mock_server)
+            .await;
+        // One download for the install and one for the emptied entry; the
+        // first reinstall must come straight from the store.
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/testpkg-1.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        installer
+            .install(&["testpkg".to_string()], true)
+            .await
+            .unwrap();
+        installer.db.mark_dependency("testpkg").unwrap();
+
+        let keg_binary = installer.keg_path("testpkg", "1.0.0").join("bin/testpkg");
+        fs::remove_file(&keg_binary).unwrap();
+        fs::remove_dir_all(root.join("cache")).unwrap();
+        fs::create_dir_all(root.join("cache")).unwrap();
+
+        installer
+            .reinstall("testpkg", false, true, None)
+            .await
+            .unwrap();
+        assert!(keg_binary.exists());
+        assert!(prefix.join("bin/testpkg").exists());
+        assert!(installer.db.is_dependency("testpkg").unwrap());
+
+        let entry = root.join("store").join(&sha);
+        fs::remove_dir_all(&entry).unwrap();
+        fs::create_dir_all(&entry).unwrap();
+
+        installer
+            .reinstall("testpkg", false, true, None)
+            .await
+            .unwrap();
+        assert!(keg_binary.exists());
+        assert!(entry.join("testpkg/1.0.0/bin/testpkg").exists());
+        assert_eq!(installer.get_installed("testpkg").unwrap().store_key, sha);
+    }
 }
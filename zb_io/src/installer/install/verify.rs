@@ -0,0 +1,432 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use zb_core::{Error, formula_token};
+
+use crate::cellar::manifest::{ManifestEntry, scan_keg};
+use crate::network::download::DownloadRequest;
+
+use super::Installer;
+
+/// How an installed keg's files compare to the manifest recorded for it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct KegVerification {
+    pub name: String,
+    pub version: String,
+    /// False when no manifest was recorded (installed without
+    /// `--verify-after-install`), in which case nothing could be compared.
+    pub manifest: bool,
+    pub modified: Vec<String>,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+}
+
+impl KegVerification {
+    pub fn is_ok(&self) -> bool {
+        self.modified.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// How a store entry's files compare to the checksums recorded when it was
+/// extracted from its verified bottle.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct StoreVerification {
+    pub store_key: String,
+    /// Installed formulas whose keg came from this entry.
+    pub formulas: Vec<String>,
+    /// False when the entry predates recorded store manifests.
+    pub manifest: bool,
+    /// The entry was recorded but its directory is gone.
+    pub entry_missing: bool,
+    pub modified: Vec<String>,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+}
+
+impl StoreVerification {
+    pub fn is_ok(&self) -> bool {
+        !self.entry_missing
+            && self.modified.is_empty()
+            && self.missing.is_empty()
+            && self.extra.is_empty()
+    }
+}
+
+/// Compare a recorded manifest against a fresh scan, returning the paths
+/// that were modified, missing and extra, each sorted.
+pub fn diff_manifest(
+    recorded: &[ManifestEntry],
+    current: &[ManifestEntry],
+) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let recorded: BTreeMap<&str, &str> = recorded
+        .iter()
+        .map(|e| (e.path.as_str(), e.sha256.as_str()))
+        .collect();
+    let current: BTreeMap<&str, &str> = current
+        .iter()
+        .map(|e| (e.path.as_str(), e.sha256.as_str()))
+        .collect();
+
+    let mut modified = Vec::new();
+    let mut missing = Vec::new();
+    for (path, sha256) in &recorded {
+        match current.get(path) {
+            Some(actual) if actual != sha256 => modified.push(path.to_string()),
+            Some(_) => {}
+            None => missing.push(path.to_string()),
+        }
+    }
+    let extra = current
+        .keys()
+        .filter(|path| !recorded.contains_key(*path))
+        .map(|path| path.to_string())
+        .collect();
+
+    (modified, missing, extra)
+}
+
+impl Installer {
+    /// Check an installed keg against its recorded manifest. Read-only.
+    pub fn verify_keg(&self, name: &str) -> Result<KegVerification, Error> {
+        let keg = self.db.get_installed(name).ok_or(Error::NotInstalled {
+            name: name.to_string(),
+        })?;
+        let recorded = self.db.keg_manifest(&keg.name, &keg.version)?;
+
+        let mut verification = KegVerification {
+            name: keg.name.clone(),
+            version: keg.version.clone(),
+            manifest: !recorded.is_empty(),
+            modified: Vec::new(),
+            missing: Vec::new(),
+            extra: Vec::new(),
+        };
+        if recorded.is_empty() {
+            return Ok(verification);
+        }
+
+        let keg_path = self.cellar.keg_path(formula_token(&keg.name), &keg.version);
+        let current = if keg_path.exists() {
+            scan_keg(&keg_path)?.0
+        } else {
+            Vec::new()
+        };
+
+        let (modified, missing, extra) = diff_manifest(&recorded, &current);
+        verification.modified = modified;
+        verification.missing = missing;
+        verification.extra = extra;
+        Ok(verification)
+    }
+
+    /// `verify_keg` for every installed formula, ordered by name.
+    pub fn verify_all(&self) -> Result<Vec<KegVerification>, Error> {
+        self.db
+            .list_installed()?
+            .iter()
+            .map(|keg| self.verify_keg(&keg.name))
+            .collect()
+    }
+
+    /// Re-hash every store entry, and every entry an installed keg was
+    /// recorded from, against the checksums taken at extraction. Read-only.
+    pub fn verify_store(&self) -> Result<Vec<StoreVerification>, Error> {
+        let installed = self.db.list_installed()?;
+        let mut keys: BTreeSet<String> = self.store.list_entries()?.into_iter().collect();
+        keys.extend(installed.iter().map(|keg| keg.store_key.clone()));
+
+        let mut results = Vec::new();
+        for store_key in keys {
+            let recorded = self.db.store_manifest(&store_key)?;
+            let entry_path = self.store.entry_path(&store_key);
+            // Source builds and non-archive casks never had an entry.
+            if recorded.is_empty() && !entry_path.exists() {
+                continue;
+            }
+
+            let mut verification = StoreVerification {
+                formulas: installed
+                    .iter()
+                    .filter(|keg| keg.store_key == store_key)
+                    .map(|keg| keg.name.clone())
+                    .collect(),
+                store_key,
+                manifest: !recorded.is_empty(),
+                entry_missing: !entry_path.exists(),
+                modified: Vec::new(),
+                missing: Vec::new(),
+                extra: Vec::new(),
+            };
+            if verification.manifest && !verification.entry_missing {
+                let (current, _) = scan_keg(&entry_path)?;
+                let (modified, missing, extra) = diff_manifest(&recorded, &current);
+                verification.modified = modified;
+                verification.missing = missing;
+                verification.extra = extra;
+            }
+            results.push(verification);
+        }
+        Ok(results)
+    }
+
+    /// Replace a damaged store entry by re-downloading the bottle it was
+    /// extracted from. Kegs already materialized from it keep their files
+    /// until they are reinstalled.
+    pub async fn repair_store_entry(&self, verification: &StoreVerification) -> Result<(), Error> {
+        let store_key = &verification.store_key;
+        let mut bottle = None;
+        for name in &verification.formulas {
+            let formula = self.api_client.get_formula(name).await?;
+            if let Some(file) = formula
+                .bottle
+                .stable
+                .files
+                .into_values()
+                .find(|file| &file.sha256 == store_key)
+            {
+                bottle = Some((name.clone(), file));
+                break;
+            }
+        }
+        let (name, file) = bottle.ok_or_else(|| Error::StoreCorruption {
+            message: format!(
+                "store entry {} is damaged and no installed formula's bottle matches it; \
+                 reinstall the formulas that use it",
+                &store_key[..store_key.len().min(12)]
+            ),
+        })?;
+
+        // A blob that no longer matches its name can't be re-extracted.
+        let blob_cache = self.downloader.blob_cache();
+        if blob_cache.has_blob(store_key) && !blob_cache.rehash_blob(store_key).unwrap_or(false) {
+            self.downloader.remove_blob(store_key);
+        }
+        let blob_path = self
+            .downloader
+            .download_single(
+                DownloadRequest {
+                    url: file.url,
+                    sha256: file.sha256,
+                    name,
+                },
+                None,
+            )
+            .await?;
+
+        self.store.remove_entry(store_key)?;
+        self.ensure_store_entry(store_key, &blob_path)?;
+        Ok(())
+    }
+
+    /// Extract `blob_path` into the store unless it is already there,
+    /// recording the new entry's checksums for `verify_store`.
+    pub(super) fn ensure_store_entry(
+        &self,
+        store_key: &str,
+        blob_path: &Path,
+    ) -> Result<PathBuf, Error> {
+        let fresh = !self.store.has_entry(store_key);
+        let entry = self.store.ensure_entry(store_key, blob_path)?;
+        if fresh {
+            let (entries, _) = scan_keg(&entry)?;
+            self.db.record_store_manifest(store_key, &entries)?;
+        }
+        Ok(entry)
+    }
+
+    /// Record a checksum manifest for a freshly installed keg and confirm
+    /// every symlink inside it, and every prefix link pointing into it,
+    /// resolves.
+    ///
+    /// The manifest is recorded even when broken links are found so the
+    /// keg's contents stay auditable; the broken links are then reported as
+    /// an error naming each one.
+    pub(super) fn verify_installed_keg(
+        &self,
+        name: &str,
+        version: &str,
+        keg_path: &Path,
+    ) -> Result<usize, Error> {
+        let (entries, mut broken) = scan_keg(keg_path)?;
+
+        for record in self.db.list_keg_files()? {
+            if record.name != name || record.version != version {
+                continue;
+            }
+            let link = PathBuf::from(&record.linked_path);
+            if fs::metadata(&link).is_err() {
+                broken.push(link);
+            }
+        }
+
+        self.db.record_keg_manifest(name, version, &entries)?;
+
+        if !broken.is_empty() {
+            let paths: Vec<String> = broken
+                .iter()
+                .map(|path| format!("  {}", path.display()))
+                .collect();
+            return Err(Error::StoreCorruption {
+                message: format!(
+                    "post-install verification of {name} {version} found broken symlinks:\n{}",
+                    paths.join("\n")
+                ),
+            });
+        }
+
+        Ok(entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+    use wiremock::MockServer;
+
+    use super::super::test_support::{mock_api_client, test_installer};
+
+    #[tokio::test]
+    async fn verification_records_manifest_and_detects_broken_symlink() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        let keg_path = installer.keg_path("foo", "1.0.0");
+        fs::create_dir_all(keg_path.join("bin")).unwrap();
+        fs::create_dir_all(keg_path.join("lib")).unwrap();
+        fs::write(keg_path.join("bin/foo"), b"#!/bin/sh\n").unwrap();
+        std::os::unix::fs::symlink("libfoo.1.dylib", keg_path.join("lib/libfoo.dylib")).unwrap();
+
+        let err = installer
+            .verify_installed_keg("foo", "1.0.0", &keg_path)
+            .unwrap_err();
+        assert!(err.to_string().contains("lib/libfoo.dylib"), "{err}");
+
+        let manifest = installer.db.keg_manifest("foo", "1.0.0").unwrap();
+        assert_eq!(manifest.len(), 1);
+        assert_eq!(manifest[0].path, "bin/foo");
+        assert_eq!(manifest[0].sha256.len(), 64);
+
+        fs::write(keg_path.join("lib/libfoo.1.dylib"), b"lib").unwrap();
+        assert_eq!(
+            installer
+                .verify_installed_keg("foo", "1.0.0", &keg_path)
+                .unwrap(),
+            2
+        );
+        assert_eq!(installer.db.keg_manifest("foo", "1.0.0").unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn verify_keg_flags_modified_missing_and_extra_files() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        let keg_path = installer.keg_path("foo", "1.0.0");
+        fs::create_dir_all(keg_path.join("bin")).unwrap();
+        fs::write(keg_path.join("bin/foo"), b"foo").unwrap();
+        fs::write(keg_path.join("bin/bar"), b"bar").unwrap();
+        fs::write(keg_path.join("README"), b"docs").unwrap();
+        {
+            let tx = installer.db.transaction().unwrap();
+            tx.record_install("foo", "1.0.0", "fookey").unwrap();
+            tx.commit().unwrap();
+        }
+
+        let unverified = installer.verify_keg("foo").unwrap();
+        assert!(!unverified.manifest);
+        assert!(unverified.is_ok());
+
+        installer
+            .verify_installed_keg("foo", "1.0.0", &keg_path)
+            .unwrap();
+        assert!(installer.verify_keg("foo").unwrap().is_ok());
+
+        fs::write(keg_path.join("bin/foo"), b"tampered").unwrap();
+        fs::remove_file(keg_path.join("README")).unwrap();
+        fs::write(keg_path.join("bin/dropper"), b"new").unwrap();
+
+        let report = installer.verify_keg("foo").unwrap();
+        assert!(report.manifest);
+        assert!(!report.is_ok());
+        assert_eq!(report.modified, vec!["bin/foo"]);
+        assert_eq!(report.missing, vec!["README"]);
+        assert_eq!(report.extra, vec!["bin/dropper"]);
+
+        assert_eq!(installer.verify_all().unwrap(), vec![report]);
+    }
+
+    #[tokio::test]
+    async fn verify_store_finds_and_repairs_a_modified_entry() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        use crate::installer::install::test_support::*;
+
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let bottle = create_bottle_tarball("storepkg");
+        let bottle_sha = sha256_hex(&bottle);
+        let tag = get_test_bottle_tag();
+
+        Mock::given(method("GET"))
+            .and(path("/formula/storepkg.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(formula_json_with_bottle(
+                    "storepkg",
+                    "1.0.0",
+                    &[],
+                    &format!("{}/bottles", mock_server.uri()),
+                    &bottle_sha,
+                )),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/bottles/storepkg-1.0.0.{tag}.bottle.tar.gz")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle))
+            .mount(&mock_server)
+            .await;
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+        installer
+            .install(&["storepkg".to_string()], true)
+            .await
+            .unwrap();
+
+        let results = installer.verify_store().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].manifest && results[0].is_ok());
+        assert_eq!(results[0].formulas, vec!["storepkg".to_string()]);
+
+        let entry = installer.store.entry_path(&bottle_sha);
+        let file = walkdir::WalkDir::new(&entry)
+            .into_iter()
+            .filter_map(Result::ok)
+            .find(|e| e.file_type().is_file())
+            .unwrap()
+            .into_path();
+        fs::remove_file(&file).unwrap();
+        fs::write(&file, b"tampered").unwrap();
+
+        let damaged = installer.verify_store().unwrap().remove(0);
+        assert!(!damaged.is_ok());
+        assert_eq!(
+            damaged.modified,
+            vec![
+                file.strip_prefix(&entry)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            ]
+        );
+
+        installer.repair_store_entry(&damaged).await.unwrap();
+        assert!(installer.verify_store().unwrap()[0].is_ok());
+        assert_ne!(fs::read(&file).unwrap(), b"tampered");
+    }
+}
@@ -0,0 +1,221 @@
+use zb_core::{Error, formula_token};
+
+use crate::storage::db::InstalledKeg;
+
+use super::Installer;
+
+impl Installer {
+    /// Installed kegs for `tool`: the unversioned formula plus any
+    /// `tool@<version>` formulas, ordered by name.
+    pub fn installed_versions_of(&self, tool: &str) -> Result<Vec<InstalledKeg>, Error> {
+        let versioned = format!("{tool}@");
+        Ok(self
+            .db
+            .list_installed()?
+            .into_iter()
+            .filter(|keg| keg.name == tool || keg.name.starts_with(&versioned))
+            .collect())
+    }
+
+    /// Make `version` of `tool` the one the generic commands resolve to.
+    ///
+    /// `version` picks the `tool@version` formula when one is installed, and
+    /// otherwise any installed keg of `tool` whose version is `version` or
+    /// starts with `version.`. Every other keg of `tool` is unlinked (keeping
+    /// its `opt/` link so dependents still resolve) and the chosen keg is
+    /// linked even if it is keg-only, which versioned formulas always are.
+    ///
+    /// Returns the name of the formula that is now active.
+    pub fn use_version(&mut self, tool: &str, version: &str) -> Result<String, Error> {
+        let _lock = self.install_lock()?;
+
+        let candidates = self.installed_versions_of(tool)?;
+        let versioned = format!("{tool}@{version}");
+        let prefix = format!("{version}.");
+        let chosen = candidates
+            .iter()
+            .find(|keg| keg.name == versioned)
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .find(|keg| keg.version == version || keg.version.starts_with(&prefix))
+            })
+            .cloned()
+            .ok_or(Error::NotInstalled { name: versioned })?;
+
+        for keg in &candidates {
+            if keg.name == chosen.name {
+                continue;
+            }
+            let keg_path = self.cellar.keg_path(formula_token(&keg.name), &keg.version);
+            if !self.linker.is_linked(&keg_path) {
+                continue;
+            }
+            self.linker.unlink_keg(&keg_path)?;
+            self.linker.link_opt(&keg_path)?;
+            let tx = self.db.transaction()?;
+            tx.clear_keg_file_records(&keg.name)?;
+            tx.commit()?;
+        }
+
+        let keg_path = self
+            .cellar
+            .keg_path(formula_token(&chosen.name), &chosen.version);
+        let linked_files = self
+            .linker
+            .link_keg_with(&keg_path, self.link_policy_for(&chosen.name))?;
+        self.record_linked_files(&chosen.name, &chosen.version, &linked_files);
+
+        Ok(chosen.name)
+    }
+
+    /// Reactivate the most recently installed earlier version of `name`
+    /// whose keg is still in the Cellar, as kept by `upgrade --no-cleanup`.
+    ///
+    /// The version being replaced is deactivated but stays on disk, so a
+    /// second rollback undoes the first. The restored keg is linked only if
+    /// the replaced one was. Returns the version that is now active.
+    pub fn rollback(&mut self, name: &str) -> Result<String, Error> {
+        let _lock = self.install_lock()?;
+
+        let current = self.db.get_installed(name).ok_or(Error::NotInstalled {
+            name: name.to_string(),
+        })?;
+        let token = formula_token(name);
+
+        let previous = self
+            .db
+            .install_history(name)?
+            .into_iter()
+            .rev()
+            .find(|entry| {
+                entry.version != current.version
+                    && self.cellar.keg_path(token, &entry.version).exists()
+            })
+            .ok_or_else(|| Error::ExecutionError {
+                message: format!(
+                    "no earlier version of {name} is kept in the Cellar (use `zb upgrade --no-cleanup {name}` to keep one next time)"
+                ),
+            })?;
+
+        let current_path = self.cellar.keg_path(token, &current.version);
+        let was_linked = self.linker.is_linked(&current_path);
+        self.deactivate_version(name, &current.version)?;
+
+        // The old keg's store reference was dropped when it was superseded,
+        // so it comes back the way doctor adopts untracked kegs.
+        let store_key = format!("adopted:{name}:{}", previous.version);
+        self.activate_version(name, &previous.version, &store_key, was_linked)?;
+        self.record_generation("rollback");
+
+        Ok(previous.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::super::test_support::{
+        create_bottle_tarball_with_binary, formula_json_with_bottle, get_test_bottle_tag,
+        mock_api_client, sha256_hex, test_installer,
+    };
+
+    async fn mount_node(mock_server: &MockServer, name: &str, version: &str) {
+        let tag = get_test_bottle_tag();
+        let bottle = create_bottle_tarball_with_binary(name, version, "node");
+        let formula_json = formula_json_with_bottle(
+            name,
+            version,
+            &[],
+            &format!("{}/bottles", mock_server.uri()),
+            &sha256_hex(&bottle),
+        );
+
+        Mock::given(method("GET"))
+            .and(path(format!("/formula/{name}.json")))
+            .respond_with(ResponseTemplate::new(200).set_body_string(formula_json))
+            .mount(mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/bottles/{name}-{version}.{tag}.bottle.tar.gz"
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(bottle))
+            .mount(mock_server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn versioned_installs_coexist_and_use_switches_generic_command() {
+        let mock_server = MockServer::start().await;
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("zerobrew");
+        let prefix = tmp.path().join("homebrew");
+
+        mount_node(&mock_server, "node@18", "18.20.4").await;
+        mount_node(&mock_server, "node@20", "20.17.0").await;
+
+        let mut installer = test_installer(tmp.path(), mock_api_client(&mock_server));
+
+        installer
+            .install(&["node@18".to_string(), "node@20".to_string()], true)
+            .await
+            .unwrap();
+
+        let installed: Vec<String> = installer
+            .installed_versions_of("node")
+            .unwrap()
+            .into_iter()
+            .map(|keg| keg.name)
+            .collect();
+        assert_eq!(installed, vec!["node@18", "node@20"]);
+        assert!(root.join("cellar/node@18/18.20.4/bin/node").exists());
+        assert!(root.join("cellar/node@20/20.17.0/bin/node").exists());
+        let node = prefix.join("bin/node");
+        assert!(
+            node.symlink_metadata().is_err(),
+            "versioned formulas are keg-only"
+        );
+
+        assert_eq!(installer.use_version("node", "18").unwrap(), "node@18");
+        assert!(
+            fs::read_link(&node)
+                .unwrap()
+                .to_string_lossy()
+                .contains("18.20.4")
+        );
+
+        assert_eq!(installer.use_version("node", "20").unwrap(), "node@20");
+        assert!(
+            fs::read_link(&node)
+                .unwrap()
+                .to_string_lossy()
+                .contains("20.17.0")
+        );
+        assert!(
+            prefix.join("opt/node@18").exists(),
+            "inactive version keeps its opt link"
+        );
+        assert!(
+            installer
+                .db
+                .list_keg_files()
+                .unwrap()
+                .iter()
+                .all(|record| record.name == "node@20")
+        );
+
+        assert!(installer.use_version("node", "16").is_err());
+
+        installer.wait_for_lock = false;
+        let lock = installer.install_lock().unwrap();
+        assert!(installer.use_version("node", "18").is_err());
+        drop(lock);
+        assert_eq!(installer.use_version("node", "18").unwrap(), "node@18");
+    }
+}
@@ -4,9 +4,37 @@ pub mod install;
 
 pub use homebrew::{
     HomebrewMigrationPackages, HomebrewPackage, categorize_packages, get_homebrew_packages,
-    parse_casks_from_plain_text, parse_formulas_from_json,
+    get_homebrew_packages_only, parse_casks_from_plain_text, parse_formulas_from_json,
+    select_with_dependencies,
 };
-pub use install::doctor::{DiagnosticReport, RepairSummary};
+pub use install::audit::{AuditReport, KegAudit};
+pub use install::builder::{DEFAULT_CONCURRENCY, InstallerBuilder};
+pub use install::cache::{CacheClean, CachedDownload};
+pub use install::caveats::FormulaCaveats;
+#[allow(deprecated)]
+pub use install::create_installer;
+pub use install::deps::DependencyTree;
+pub use install::doctor::{
+    DiagnosticReport, KegLinkage, MissingKeg, OrphanedKeg, RepairSummary, StaleOptLink,
+    StaleStoreRef,
+};
+pub use install::generations::GenerationSwitch;
+pub use install::link::{LinkConflictDecision, LinkConflictResolver};
+pub use install::lockfile::{LockedBottle, LockedFormula, Lockfile, LockfileChange};
+pub use install::manifest::{InstallReason, Manifest, ManifestFormula};
+pub use install::offline::FetchSummary;
+pub use install::paths::{FileOwner, KegPaths};
+pub use install::prune::{
+    BuildTmpPrune, GcPlan, GcSummary, ReclaimableEntry, ReclaimableKeg, ReclaimableLog,
+    STALE_BUILD_DIR_AGE, tree_size,
+};
+pub use install::request::{InstallReport, InstallRequest, InstalledFormula};
+pub use install::sbom::{Sbom, SbomPackage};
+pub use install::search::SearchResult;
+pub use install::services::{ServiceState, ServiceStatus};
+pub use install::size::FormulaDiskUsage;
+pub use install::verify::{KegVerification, StoreVerification};
 pub use install::{
-    ExecuteResult, InstallPlan, Installer, OutdatedPackage, PlanFailure, create_installer,
+    ExecuteResult, FormulaFile, InstallPlan, Installer, OutdatedPackage, PlanFailure, SkippedPin,
+    UpgradeSelection,
 };
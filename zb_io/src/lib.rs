@@ -1,3 +1,12 @@
+//! zerobrew's installer as a library.
+//!
+//! The stable surface for embedding is small: open an [`Installer`] with
+//! [`Installer::builder`], install with [`InstallRequest`] and
+//! [`Installer::install_request`], read the [`InstallReport`], and branch on
+//! failures by [`ErrorKind`]. Progress arrives as [`InstallProgress`] events,
+//! through a callback or [`progress_channel`]. The other modules are public
+//! for the `zb` CLI and may change between releases.
+
 pub mod build;
 pub mod cellar;
 pub(crate) mod checksum;
@@ -6,21 +15,41 @@ pub mod installer;
 pub mod network;
 pub mod path;
 pub mod progress;
+pub mod services;
 pub mod ssl;
 pub mod storage;
 
-pub use build::{BuildExecutor, DepInfo};
-pub use cellar::{Cellar, LinkedFile, Linker, MaterializedKeg};
+pub use build::{BuildExecutor, BuildOptions, DepInfo, build_logs_dir, latest_build_log};
+pub use cellar::{
+    BrokenLinkage, Cellar, LinkConflictPolicy, LinkageProblem, LinkedFile, Linker, MaterializedKeg,
+};
 pub use extraction::extract_tarball;
+#[allow(deprecated)]
+pub use installer::create_installer;
 pub use installer::{
-    DiagnosticReport, ExecuteResult, HomebrewMigrationPackages, HomebrewPackage, InstallPlan,
-    Installer, OutdatedPackage, PlanFailure, RepairSummary, create_installer,
-    get_homebrew_packages,
+    AuditReport, BuildTmpPrune, CacheClean, CachedDownload, DEFAULT_CONCURRENCY, DependencyTree,
+    DiagnosticReport, ExecuteResult, FetchSummary, FileOwner, FormulaCaveats, FormulaDiskUsage,
+    FormulaFile, GcPlan, GcSummary, GenerationSwitch, HomebrewMigrationPackages, HomebrewPackage,
+    InstallPlan, InstallReason, InstallReport, InstallRequest, InstalledFormula, Installer,
+    InstallerBuilder, KegAudit, KegPaths, KegVerification, LinkConflictDecision,
+    LinkConflictResolver, LockedBottle, LockedFormula, Lockfile, LockfileChange, Manifest,
+    ManifestFormula, OutdatedPackage, PlanFailure, RepairSummary, STALE_BUILD_DIR_AGE, Sbom,
+    SbomPackage, SearchResult, ServiceState, ServiceStatus, SkippedPin, StoreVerification,
+    UpgradeSelection, get_homebrew_packages, get_homebrew_packages_only, tree_size,
 };
+#[allow(deprecated)]
+pub use network::ParallelDownloader;
 pub use network::{
-    ApiCache, ApiClient, DownloadProgressCallback, DownloadRequest, Downloader, ParallelDownloader,
+    ApiCache, ApiClient, DownloadJob, DownloadManager, DownloadProgressCallback, DownloadRequest,
+    Downloader, OsvClient, RegistryAuth, RegistryCredentials, RetryPolicy, RetryableError,
+    Severity, Vulnerability, configure_tls, set_proxy,
 };
 pub use path::validate_privileged_path;
-pub use progress::{InstallProgress, ProgressCallback};
+pub use progress::{InstallProgress, ProgressCallback, progress_channel};
+pub use services::ServiceManager;
 pub use ssl::{find_ca_bundle_from_prefix, find_ca_dir};
-pub use storage::{BlobCache, Database, InstalledKeg, KegFileRecord, Store, StoreRef};
+pub use storage::{
+    BlobCache, Database, Generation, GenerationKeg, InstallHistoryEntry, InstalledKeg,
+    KegFileRecord, Store, StoreRef,
+};
+pub use zb_core::{Error, ErrorKind};
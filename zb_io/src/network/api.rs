@@ -1,11 +1,18 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
-use crate::checksum::verify_sha256_bytes;
+use sha2::{Digest, Sha256};
+
+use crate::checksum::{sha256_hex, verify_sha256_bytes};
 use crate::network::cache::{ApiCache, CacheEntry};
+use crate::network::download::{RetryPolicy, RetryableError};
 use crate::network::suggest::rank_formula_suggestions;
 use crate::network::tap_formula::{parse_tap_formula_ref, parse_tap_formula_ruby};
+use crate::network::tls::describe_request_error;
 use futures_util::stream::{self, StreamExt};
+use tracing::{debug, warn};
 use zb_core::{Error, Formula};
 
 const HOMEBREW_CORE_RAW_BASE: &str =
@@ -16,6 +23,8 @@ enum RubySourceLocator<'a> {
     CoreRelativePath(&'a str),
     AbsoluteUrl(&'a str),
     TapEncodedUrl(&'a str),
+    /// An absolute path to a `.rb` file on this machine.
+    LocalPath(&'a str),
 }
 
 impl<'a> RubySourceLocator<'a> {
@@ -30,6 +39,10 @@ impl<'a> RubySourceLocator<'a> {
             return Self::AbsoluteUrl(input);
         }
 
+        if input.starts_with('/') {
+            return Self::LocalPath(input);
+        }
+
         Self::CoreRelativePath(input)
     }
 
@@ -38,6 +51,7 @@ impl<'a> RubySourceLocator<'a> {
             Self::CoreRelativePath(_) => original,
             Self::AbsoluteUrl(url) => url,
             Self::TapEncodedUrl(url) => url,
+            Self::LocalPath(path) => path,
         }
     }
 
@@ -45,6 +59,7 @@ impl<'a> RubySourceLocator<'a> {
         match self {
             Self::CoreRelativePath(path) => format!("{HOMEBREW_CORE_RAW_BASE}/{path}"),
             Self::AbsoluteUrl(url) | Self::TapEncodedUrl(url) => url.to_string(),
+            Self::LocalPath(path) => format!("file://{path}"),
         }
     }
 
@@ -58,6 +73,14 @@ enum CachedGetResult {
     Fresh(reqwest::Response),
 }
 
+fn offline_miss(url: &str) -> Error {
+    Error::NetworkFailure {
+        message: format!(
+            "offline: {url} is not cached; run `zb fetch` on a connected machine and copy the zerobrew root"
+        ),
+    }
+}
+
 #[derive(Debug, serde::Deserialize)]
 struct FormulaSuggestionEntry {
     #[serde(default)]
@@ -66,6 +89,8 @@ struct FormulaSuggestionEntry {
     aliases: Vec<String>,
     #[serde(default)]
     oldnames: Vec<String>,
+    #[serde(default)]
+    desc: Option<String>,
 }
 
 #[derive(Debug)]
@@ -77,10 +102,23 @@ pub struct ApiClient {
     cache: Option<ApiCache>,
     formula_candidates: RwLock<Option<Arc<[String]>>>,
     alias_map: RwLock<Option<Arc<HashMap<String, String>>>>,
+    descriptions: RwLock<Option<Arc<HashMap<String, String>>>>,
+    /// Formula JSON by name, split out of a fresh cached bulk index.
+    formula_index: RwLock<Option<Arc<HashMap<String, String>>>>,
+    /// How long a cached bulk index is used without revalidating it.
+    index_ttl: Duration,
+    retry: RetryPolicy,
+    /// Serve only what the cache holds and never touch the network.
+    offline: bool,
+    registered_formulas: RwLock<HashMap<String, Formula>>,
+    /// Parsed formulas keyed by the sha256 of their JSON body.
+    parsed_formulas: RwLock<HashMap<String, Formula>>,
+    formula_parses: AtomicUsize,
 }
 
 impl ApiClient {
     const DEFAULT_BASE_URL: &'static str = "https://formulae.brew.sh/api/formula";
+    const DEFAULT_INDEX_TTL: Duration = Duration::from_secs(60 * 60);
 
     pub fn new() -> Self {
         Self::build_client(Self::DEFAULT_BASE_URL.to_string())
@@ -109,7 +147,7 @@ impl ApiClient {
     }
 
     fn build_client(base_url: String) -> Self {
-        let client = reqwest::Client::builder()
+        let client = crate::network::proxy::with_proxy(reqwest::Client::builder())
             .user_agent("zerobrew/0.1")
             .pool_max_idle_per_host(20)
             .use_preconfigured_tls((*crate::network::tls::shared_tls_config()).clone())
@@ -124,6 +162,14 @@ impl ApiClient {
             cache: None,
             formula_candidates: RwLock::new(None),
             alias_map: RwLock::new(None),
+            descriptions: RwLock::new(None),
+            formula_index: RwLock::new(None),
+            index_ttl: Self::DEFAULT_INDEX_TTL,
+            retry: RetryPolicy::default(),
+            offline: false,
+            registered_formulas: RwLock::new(HashMap::new()),
+            parsed_formulas: RwLock::new(HashMap::new()),
+            formula_parses: AtomicUsize::new(0),
         }
     }
 
@@ -144,6 +190,23 @@ impl ApiClient {
         self
     }
 
+    pub fn with_index_ttl(mut self, ttl: Duration) -> Self {
+        self.index_ttl = ttl;
+        self
+    }
+
+    /// How transient failures on API requests are retried.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Answer every request from the on-disk cache, however old, and fail
+    /// with a `NetworkFailure` for anything it doesn't hold.
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+
     /// Clear all cached API responses. Returns the number removed.
     pub fn clear_cache(&self) -> Result<usize, Error> {
         match &self.cache {
@@ -161,6 +224,9 @@ impl ApiClient {
         expected_sha256: Option<&str>,
     ) -> Result<std::path::PathBuf, Error> {
         let locator = RubySourceLocator::parse(ruby_source_path);
+        if let RubySourceLocator::LocalPath(path) = locator {
+            return Self::copy_local_formula_rb(path, cache_dir, expected_sha256);
+        }
         let source_id = locator.source_id(ruby_source_path);
         let url = locator.to_url();
 
@@ -168,6 +234,46 @@ impl ApiClient {
             .await
     }
 
+    /// The URL a formula's `.rb` file is fetched from.
+    pub fn formula_rb_url(&self, ruby_source_path: &str) -> String {
+        RubySourceLocator::parse(ruby_source_path).to_url()
+    }
+
+    /// Human-readable origin of a formula's `.rb` file: `homebrew/core`,
+    /// the `owner/repo` of a tap, or the URL itself.
+    pub fn formula_rb_origin(&self, ruby_source_path: &str) -> String {
+        match RubySourceLocator::parse(ruby_source_path) {
+            RubySourceLocator::CoreRelativePath(_) => "homebrew/core".to_string(),
+            RubySourceLocator::TapEncodedUrl(url) => url
+                .strip_prefix(self.tap_raw_base_url.trim_end_matches('/'))
+                .map(|rest| rest.trim_start_matches('/').splitn(3, '/'))
+                .and_then(|mut parts| Some(format!("{}/{}", parts.next()?, parts.next()?)))
+                .unwrap_or_else(|| url.to_string()),
+            RubySourceLocator::AbsoluteUrl(url) | RubySourceLocator::LocalPath(url) => {
+                url.to_string()
+            }
+        }
+    }
+
+    /// Snapshot a local `.rb` into the rb cache, so edits made to it while
+    /// a build runs don't change what the build sees.
+    fn copy_local_formula_rb(
+        path: &str,
+        cache_dir: &std::path::Path,
+        expected_sha256: Option<&str>,
+    ) -> Result<std::path::PathBuf, Error> {
+        let body = std::fs::read(path).map_err(|e| Error::FileError {
+            message: format!("failed to read formula file '{path}': {e}"),
+        })?;
+        verify_sha256_bytes(&body, expected_sha256)
+            .map_err(|e| Self::map_formula_rb_checksum_error(e, path, "local file"))?;
+
+        let dest = cache_dir.join(path.replace('/', "_"));
+        std::fs::create_dir_all(cache_dir).map_err(Error::file("failed to create rb cache dir"))?;
+        std::fs::write(&dest, &body).map_err(Error::file("failed to write rb file"))?;
+        Ok(dest)
+    }
+
     async fn fetch_formula_rb_from_url(
         &self,
         ruby_source_path: &str,
@@ -187,16 +293,18 @@ impl ApiClient {
                 .map_err(Error::file("failed to write cached rb file"))?;
             return Ok(dest);
         }
+        if self.offline {
+            return Err(offline_miss(url));
+        }
 
         let response = self
-            .client
-            .get(url)
-            .send()
+            .send_with_retry(self.client.get(url))
             .await
             .map_err(Error::network("failed to fetch formula rb"))?;
 
         if !response.status().is_success() {
-            return Err(Error::NetworkFailure {
+            return Err(Error::HttpStatus {
+                status: response.status().as_u16(),
                 message: format!("formula rb fetch returned HTTP {}", response.status()),
             });
         }
@@ -239,6 +347,12 @@ impl ApiClient {
 
     async fn cached_get(&self, url: &str) -> Result<CachedGetResult, Error> {
         let cached_entry = self.cache.as_ref().and_then(|c| c.get(url));
+        if self.offline {
+            return match cached_entry {
+                Some(entry) => Ok(CachedGetResult::Cached(entry.body)),
+                None => Err(offline_miss(url)),
+            };
+        }
 
         let mut request = self.client.get(url);
 
@@ -251,9 +365,14 @@ impl ApiClient {
             }
         }
 
-        let response = request.send().await.map_err(|e| Error::NetworkFailure {
-            message: e.to_string(),
-        })?;
+        debug!(url, cached = cached_entry.is_some(), "GET");
+        let response = self
+            .send_with_retry(request)
+            .await
+            .map_err(|e| Error::NetworkFailure {
+                message: describe_request_error(&e),
+            })?;
+        debug!(url, status = %response.status(), "response");
 
         if response.status() == reqwest::StatusCode::NOT_MODIFIED
             && let Some(entry) = cached_entry
@@ -264,6 +383,32 @@ impl ApiClient {
         Ok(CachedGetResult::Fresh(response))
     }
 
+    /// Send `request`, retrying dropped connections and retryable statuses
+    /// per the retry policy. The last response is returned as-is once retries
+    /// run out, so callers still see its status.
+    async fn send_with_retry(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let mut attempt = 0;
+        loop {
+            let Some(this_try) = request.try_clone() else {
+                return request.send().await;
+            };
+            let retries_left = attempt < self.retry.max_retries;
+
+            match this_try.send().await {
+                Ok(response) if retries_left && response.status().is_retryable() => {}
+                Err(e) if retries_left && e.is_retryable() => {}
+                result => return result,
+            }
+
+            debug!(attempt = attempt + 1, "retrying request");
+            tokio::time::sleep(self.retry.delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+
     fn store_response_in_cache(
         &self,
         url: &str,
@@ -281,14 +426,61 @@ impl ApiClient {
         }
     }
 
+    /// Serve `formula` for its name from `get_formula` instead of the API,
+    /// for formulas that don't come from any registered source.
+    pub fn register_formula(&self, formula: Formula) {
+        if let Ok(mut registered) = self.registered_formulas.write() {
+            registered.insert(formula.name.clone(), formula);
+        }
+    }
+
+    /// `name`'s formula from what this client already has, without sending
+    /// a request: a registered formula, the bulk index, or the formula's own
+    /// cached JSON however old it is. Tap formulas are never found here.
+    pub fn cached_formula(&self, name: &str) -> Option<Formula> {
+        if let Some(formula) = self
+            .registered_formulas
+            .read()
+            .ok()
+            .and_then(|registered| registered.get(name).cloned())
+        {
+            return Some(formula);
+        }
+
+        if let Some(formula) = self
+            .indexed_formula_json(name)
+            .and_then(|body| self.parse_formula(&body).ok())
+        {
+            return Some(formula);
+        }
+
+        let url = format!("{}/{}.json", self.base_url, name);
+        let entry = self.cache.as_ref()?.get(&url)?;
+        self.parse_formula(&entry.body).ok()
+    }
+
     pub async fn get_formula(&self, name: &str) -> Result<Formula, Error> {
+        if let Some(formula) = self
+            .registered_formulas
+            .read()
+            .ok()
+            .and_then(|registered| registered.get(name).cloned())
+        {
+            return Ok(formula);
+        }
+
         if let Some(spec) = parse_tap_formula_ref(name) {
             return self.get_tap_formula(&spec).await;
         }
 
-        let parse_body = |body: String| {
-            serde_json::from_str(&body).map_err(Error::network("failed to parse formula JSON"))
-        };
+        if let Some(formula) = self
+            .indexed_formula_json(name)
+            .and_then(|body| self.parse_formula(&body).ok())
+        {
+            return Ok(formula);
+        }
+
+        let parse_body = |body: String| self.parse_formula(&body);
 
         match self.fetch_formula_json(name).await {
             Ok(body) => parse_body(body),
@@ -309,6 +501,42 @@ impl ApiClient {
         }
     }
 
+    /// Parse a formula body, reusing an earlier parse of identical content.
+    ///
+    /// Closures are resolved several times per run (`is_outdated`, then the
+    /// plan, then dependents), and each pass fetches every member again;
+    /// the ETag cache makes those fetches cheap but the bodies would still
+    /// be parsed every time. Keying by content hash means an updated body is
+    /// simply a miss, so there is nothing to invalidate.
+    fn parse_formula(&self, body: &str) -> Result<Formula, Error> {
+        let mut hasher = Sha256::new();
+        hasher.update(body.as_bytes());
+        let key = sha256_hex(hasher);
+
+        if let Some(formula) = self
+            .parsed_formulas
+            .read()
+            .ok()
+            .and_then(|parsed| parsed.get(&key).cloned())
+        {
+            return Ok(formula);
+        }
+
+        let formula: Formula =
+            serde_json::from_str(body).map_err(Error::network("failed to parse formula JSON"))?;
+        self.formula_parses.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut parsed) = self.parsed_formulas.write() {
+            parsed.insert(key, formula.clone());
+        }
+        Ok(formula)
+    }
+
+    /// How many formula bodies this client has actually parsed.
+    #[cfg(test)]
+    pub(crate) fn formula_parses(&self) -> usize {
+        self.formula_parses.load(Ordering::Relaxed)
+    }
+
     async fn fetch_formula_json(&self, name: &str) -> Result<String, Error> {
         let url = format!("{}/{}.json", self.base_url, name);
 
@@ -321,7 +549,8 @@ impl ApiClient {
                     });
                 }
                 if !response.status().is_success() {
-                    return Err(Error::NetworkFailure {
+                    return Err(Error::HttpStatus {
+                        status: response.status().as_u16(),
                         message: format!("HTTP {}", response.status()),
                     });
                 }
@@ -348,14 +577,46 @@ impl ApiClient {
         }
     }
 
+    /// The bulk formula index, as the JSON array the API serves.
+    ///
+    /// A cached copy younger than the index TTL is used without asking the
+    /// server. Past that it is revalidated, and if the server can't be
+    /// reached an expired copy is still better than no index at all.
     pub async fn get_all_formulas_raw(&self) -> Result<String, Error> {
         let url = format!("{}.json", self.base_url);
 
-        match self.cached_get(&url).await? {
-            CachedGetResult::Cached(body) => Ok(body),
+        if let Some(entry) = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.get_fresh(&url, self.index_ttl))
+        {
+            return Ok(entry.body);
+        }
+
+        match self.fetch_all_formulas_raw(&url).await {
+            Ok(body) => Ok(body),
+            Err(e) => match self.cache.as_ref().and_then(|cache| cache.get(&url)) {
+                Some(entry) => {
+                    warn!(error = %e, "formula index unreachable, using cached copy");
+                    Ok(entry.body)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    async fn fetch_all_formulas_raw(&self, url: &str) -> Result<String, Error> {
+        match self.cached_get(url).await? {
+            CachedGetResult::Cached(body) => {
+                if let Some(cache) = &self.cache {
+                    let _ = cache.touch(url);
+                }
+                Ok(body)
+            }
             CachedGetResult::Fresh(response) => {
                 if !response.status().is_success() {
-                    return Err(Error::NetworkFailure {
+                    return Err(Error::HttpStatus {
+                        status: response.status().as_u16(),
                         message: format!("bulk formula fetch returned HTTP {}", response.status()),
                     });
                 }
@@ -376,7 +637,7 @@ impl ApiClient {
                     .await
                     .map_err(Error::network("failed to read bulk formula response body"))?;
 
-                self.store_response_in_cache(&url, etag, last_modified, &body);
+                self.store_response_in_cache(url, etag, last_modified, &body);
                 Ok(body)
             }
         }
@@ -395,7 +656,7 @@ impl ApiClient {
         Ok(rank_formula_suggestions(query, &candidates, limit))
     }
 
-    async fn formula_candidates(&self) -> Result<Arc<[String]>, Error> {
+    pub(crate) async fn formula_candidates(&self) -> Result<Arc<[String]>, Error> {
         if let Some(candidates) = self.formula_candidates.read().ok().and_then(|c| c.clone()) {
             return Ok(candidates);
         }
@@ -450,6 +711,58 @@ impl ApiClient {
         }
     }
 
+    /// The bulk index's entry for `name`, if a fresh copy of the index is
+    /// already cached. This never touches the network: it's only worth
+    /// skipping the per-formula fetch when the index costs nothing.
+    fn indexed_formula_json(&self, name: &str) -> Option<String> {
+        if let Some(index) = self.formula_index.read().ok().and_then(|i| i.clone()) {
+            return index.get(name).cloned();
+        }
+
+        let url = format!("{}.json", self.base_url);
+        let cache = self.cache.as_ref()?;
+        let entry = if self.offline {
+            cache.get(&url)?
+        } else {
+            cache.get_fresh(&url, self.index_ttl)?
+        };
+        let entries: Vec<serde_json::Value> = serde_json::from_str(&entry.body).ok()?;
+        let index: Arc<HashMap<String, String>> = Arc::new(
+            entries
+                .into_iter()
+                .filter_map(|entry| {
+                    let name = entry.get("name")?.as_str()?.to_string();
+                    Some((name, entry.to_string()))
+                })
+                .collect(),
+        );
+        if let Ok(mut cached) = self.formula_index.write() {
+            *cached = Some(Arc::clone(&index));
+        }
+        index.get(name).cloned()
+    }
+
+    /// One-line descriptions by formula name, from the bulk index.
+    pub(crate) async fn formula_descriptions(&self) -> Result<Arc<HashMap<String, String>>, Error> {
+        if let Some(map) = self.descriptions.read().ok().and_then(|m| m.clone()) {
+            return Ok(map);
+        }
+
+        let raw = self.get_all_formulas_raw().await?;
+        let entries: Vec<FormulaSuggestionEntry> = serde_json::from_str(&raw)
+            .map_err(Error::network("failed to parse bulk formula JSON"))?;
+        let map: Arc<HashMap<String, String>> = Arc::new(
+            entries
+                .into_iter()
+                .filter_map(|entry| Some((entry.name?, entry.desc?)))
+                .collect(),
+        );
+        if let Ok(mut cached) = self.descriptions.write() {
+            *cached = Some(Arc::clone(&map));
+        }
+        Ok(map)
+    }
+
     async fn get_alias_map(&self) -> Result<Arc<HashMap<String, String>>, Error> {
         if let Some(map) = self.alias_map.read().ok().and_then(|m| m.clone()) {
             return Ok(map);
@@ -485,37 +798,60 @@ impl ApiClient {
 
     pub async fn get_cask(&self, token: &str) -> Result<serde_json::Value, Error> {
         let url = format!("{}/{}.json", self.cask_base_url, token);
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| Error::NetworkFailure {
-                message: e.to_string(),
-            })?;
 
-        if response.status() == reqwest::StatusCode::NOT_FOUND {
-            return Err(Error::MissingFormula {
-                name: format!("cask:{token}"),
-            });
-        }
+        let body = match self.cached_get(&url).await? {
+            CachedGetResult::Cached(body) => body,
+            CachedGetResult::Fresh(response) => {
+                if response.status() == reqwest::StatusCode::NOT_FOUND {
+                    return Err(Error::MissingFormula {
+                        name: format!("cask:{token}"),
+                    });
+                }
 
-        if !response.status().is_success() {
-            return Err(Error::NetworkFailure {
-                message: format!("HTTP {}", response.status()),
-            });
-        }
+                if !response.status().is_success() {
+                    return Err(Error::HttpStatus {
+                        status: response.status().as_u16(),
+                        message: format!("HTTP {}", response.status()),
+                    });
+                }
 
-        response
-            .json::<serde_json::Value>()
-            .await
-            .map_err(Error::network("failed to parse cask JSON"))
+                let etag = response
+                    .headers()
+                    .get("etag")
+                    .and_then(|v| v.to_str().ok())
+                    .map(|s| s.to_string());
+                let last_modified = response
+                    .headers()
+                    .get("last-modified")
+                    .and_then(|v| v.to_str().ok())
+                    .map(|s| s.to_string());
+
+                let body = response
+                    .text()
+                    .await
+                    .map_err(Error::network("failed to read response body"))?;
+
+                self.store_response_in_cache(&url, etag, last_modified, &body);
+                body
+            }
+        };
+
+        serde_json::from_str(&body).map_err(Error::network("failed to parse cask JSON"))
     }
 
     async fn get_tap_formula(
         &self,
         spec: &crate::network::tap_formula::TapFormulaRef,
     ) -> Result<Formula, Error> {
+        if self.offline {
+            return Err(Error::NetworkFailure {
+                message: format!(
+                    "offline: tap formula {}/{}/{} can't be resolved without the network",
+                    spec.owner, spec.repo, spec.formula
+                ),
+            });
+        }
+
         let candidate_repos = if spec.repo.starts_with("homebrew-") {
             vec![
                 spec.repo.clone(),
@@ -577,7 +913,7 @@ impl ApiClient {
                         }
                         Err(e) => {
                             last_network_error = Some(Error::NetworkFailure {
-                                message: e.to_string(),
+                                message: describe_request_error(&e),
                             });
                         }
                     }
@@ -672,6 +1008,10 @@ mod tests {
             RubySourceLocator::parse(&encoded),
             RubySourceLocator::TapEncodedUrl("https://example.com/tap/foo.rb")
         );
+        assert_eq!(
+            RubySourceLocator::parse("/home/me/formulas/foo.rb"),
+            RubySourceLocator::LocalPath("/home/me/formulas/foo.rb")
+        );
     }
 
     #[test]
@@ -691,6 +1031,10 @@ mod tests {
             .to_url(),
             "https://raw.githubusercontent.com/org/tap/main/foo.rb"
         );
+        assert_eq!(
+            RubySourceLocator::LocalPath("/home/me/foo.rb").to_url(),
+            "file:///home/me/foo.rb"
+        );
     }
 
     #[tokio::test]
@@ -1109,7 +1453,7 @@ end
             .await
             .unwrap_err();
 
-        assert!(matches!(err, Error::NetworkFailure { .. }));
+        assert!(matches!(err, Error::HttpStatus { status: 500, .. }));
     }
 
     #[tokio::test]
@@ -1221,6 +1565,71 @@ end
         assert_eq!(cask["version"], "3.5.0");
     }
 
+    #[tokio::test]
+    async fn transient_api_errors_are_retried() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/iterm2.json"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/iterm2.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"token":"iterm2"}"#))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = ApiClient::with_base_url(mock_server.uri())
+            .unwrap()
+            .with_cask_base_url(mock_server.uri())
+            .with_retry_policy(RetryPolicy {
+                base_delay: Duration::from_millis(1),
+                ..RetryPolicy::default()
+            });
+        let cask = client.get_cask("iterm2").await.unwrap();
+        assert_eq!(cask["token"], "iterm2");
+    }
+
+    #[tokio::test]
+    async fn offline_serves_cached_metadata_without_requests() {
+        let mock_server = MockServer::start().await;
+        let fixture = include_str!("../../../zb_core/fixtures/formula_foo.json");
+
+        Mock::given(method("GET"))
+            .and(path("/foo.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixture))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/iterm2.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"token":"iterm2"}"#))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mut client = ApiClient::with_base_url(mock_server.uri())
+            .unwrap()
+            .with_cask_base_url(mock_server.uri())
+            .with_cache(ApiCache::in_memory().unwrap());
+        client.get_formula("foo").await.unwrap();
+        client.get_cask("iterm2").await.unwrap();
+
+        client.set_offline(true);
+        assert_eq!(client.get_formula("foo").await.unwrap().name, "foo");
+        assert_eq!(client.get_cask("iterm2").await.unwrap()["token"], "iterm2");
+
+        let err = client.get_formula("bar").await.unwrap_err();
+        assert!(
+            matches!(&err, Error::NetworkFailure { message } if message.starts_with("offline:")),
+            "{err}"
+        );
+    }
+
     #[tokio::test]
     async fn get_all_formulas_raw_returns_bulk_json() {
         let mock_server = MockServer::start().await;
@@ -1242,6 +1651,63 @@ end
         assert_eq!(formulas[0].versions.stable, "1.2.3");
     }
 
+    #[tokio::test]
+    async fn fresh_cached_index_serves_formulas_without_refetching() {
+        let mock_server = MockServer::start().await;
+        let fixture = include_str!("../../../zb_core/fixtures/formula_foo.json");
+
+        Mock::given(method("GET"))
+            .and(path("/formula.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!("[{fixture}]")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/formula/foo.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixture))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let client = ApiClient::with_base_url(format!("{}/formula", mock_server.uri()))
+            .unwrap()
+            .with_cache(ApiCache::in_memory().unwrap());
+        client.get_all_formulas_raw().await.unwrap();
+
+        // Within the TTL neither the index nor the formula is fetched again.
+        client.get_all_formulas_raw().await.unwrap();
+        let formula = client.get_formula("foo").await.unwrap();
+        assert_eq!(formula.versions.stable, "1.2.3");
+    }
+
+    #[tokio::test]
+    async fn expired_index_is_used_when_the_server_is_unreachable() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/formula.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"[{"name":"jq"}]"#))
+            .mount(&mock_server)
+            .await;
+
+        let client = ApiClient::with_base_url(format!("{}/formula", mock_server.uri()))
+            .unwrap()
+            .with_cache(ApiCache::in_memory().unwrap())
+            .with_index_ttl(Duration::ZERO)
+            .with_retry_policy(RetryPolicy::none());
+        client.get_all_formulas_raw().await.unwrap();
+
+        mock_server.reset().await;
+        Mock::given(method("GET"))
+            .and(path("/formula.json"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let raw = client.get_all_formulas_raw().await.unwrap();
+        assert_eq!(raw, r#"[{"name":"jq"}]"#);
+    }
+
     #[test]
     fn formula_suggestion_entry_defaults_optional_lists() {
         let entry: FormulaSuggestionEntry = serde_json::from_str(r#"{"name":"python"}"#).unwrap();
@@ -1394,4 +1860,21 @@ end
             Error::MissingFormula { name } if name == "nonexistent"
         ));
     }
+
+    #[test]
+    fn formula_rb_origin_names_core_tap_and_url_sources() {
+        let client = ApiClient::new().with_tap_raw_base_url("https://raw.example.com".into());
+
+        assert_eq!(client.formula_rb_origin("Formula/j/jq.rb"), "homebrew/core");
+        assert_eq!(
+            client.formula_rb_origin(
+                "tap-rb-url:https://raw.example.com/hashicorp/homebrew-tap/main/Formula/terraform.rb"
+            ),
+            "hashicorp/homebrew-tap"
+        );
+        assert_eq!(
+            client.formula_rb_origin("https://example.com/foo.rb"),
+            "https://example.com/foo.rb"
+        );
+    }
 }
@@ -1,5 +1,6 @@
 use rusqlite::{Connection, params};
 use std::path::Path;
+use std::time::Duration;
 
 pub struct ApiCache {
     conn: Connection,
@@ -100,6 +101,35 @@ impl ApiCache {
             .ok()
     }
 
+    /// Like `get`, but only if the entry was stored or revalidated less than
+    /// `max_age` ago.
+    pub fn get_fresh(&self, url: &str, max_age: Duration) -> Option<CacheEntry> {
+        let oldest = now_secs() - max_age.as_secs() as i64;
+        self.conn
+            .query_row(
+                "SELECT etag, last_modified, body FROM api_cache
+                 WHERE url = ?1 AND cached_at > ?2",
+                params![url, oldest],
+                |row| {
+                    Ok(CacheEntry {
+                        etag: row.get(0)?,
+                        last_modified: row.get(1)?,
+                        body: row.get(2)?,
+                    })
+                },
+            )
+            .ok()
+    }
+
+    /// Mark an entry as just revalidated, e.g. after a 304.
+    pub fn touch(&self, url: &str) -> Result<(), rusqlite::Error> {
+        self.conn.execute(
+            "UPDATE api_cache SET cached_at = ?2 WHERE url = ?1",
+            params![url, now_secs()],
+        )?;
+        Ok(())
+    }
+
     /// Clear all cached entries. Returns the number of entries removed.
     pub fn clear(&self) -> Result<usize, rusqlite::Error> {
         let removed = self.conn.execute("DELETE FROM api_cache", [])?;
@@ -107,20 +137,22 @@ impl ApiCache {
     }
 
     pub fn put(&self, url: &str, entry: &CacheEntry) -> Result<(), rusqlite::Error> {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map(|d| d.as_secs() as i64)
-            .unwrap_or(0);
-
         self.conn.execute(
             "INSERT OR REPLACE INTO api_cache (url, etag, last_modified, body, cached_at)
              VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![url, entry.etag, entry.last_modified, entry.body, now],
+            params![url, entry.etag, entry.last_modified, entry.body, now_secs()],
         )?;
         Ok(())
     }
 }
 
+fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -165,6 +197,30 @@ mod tests {
         assert!(cache.get("https://example.com/b.json").is_none());
     }
 
+    #[test]
+    fn get_fresh_ignores_entries_older_than_max_age() {
+        let cache = ApiCache::in_memory().unwrap();
+        let entry = CacheEntry {
+            etag: None,
+            last_modified: None,
+            body: "[]".to_string(),
+        };
+        cache
+            .put("https://example.com/formula.json", &entry)
+            .unwrap();
+        cache
+            .conn
+            .execute("UPDATE api_cache SET cached_at = cached_at - 7200", [])
+            .unwrap();
+
+        let url = "https://example.com/formula.json";
+        assert!(cache.get_fresh(url, Duration::from_secs(3600)).is_none());
+        assert!(cache.get_fresh(url, Duration::from_secs(86400)).is_some());
+
+        cache.touch(url).unwrap();
+        assert!(cache.get_fresh(url, Duration::from_secs(3600)).is_some());
+    }
+
     #[test]
     fn clear_on_empty_cache_returns_zero() {
         let cache = ApiCache::in_memory().unwrap();
@@ -9,7 +9,15 @@ use tokio::sync::RwLock;
 
 use zb_core::Error;
 
-use super::MAX_CHUNK_RETRIES;
+use super::credentials::{RegistryAuth, RegistryCredentials, host_for_url};
+use super::retry::{RetryPolicy, RetryableError};
+use crate::network::tls::describe_request_error;
+
+/// How long a bearer token is reused when the token endpoint doesn't say.
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(240);
+
+/// How long to keep sending Basic credentials once a registry asked for them.
+const BASIC_AUTH_LIFETIME: Duration = Duration::from_secs(60 * 60);
 
 pub(crate) fn bearer_header(token: &str) -> Result<HeaderValue, Error> {
     HeaderValue::from_str(&format!("Bearer {token}")).map_err(|_| Error::NetworkFailure {
@@ -19,45 +27,109 @@ pub(crate) fn bearer_header(token: &str) -> Result<HeaderValue, Error> {
 
 #[derive(Deserialize)]
 struct TokenResponse {
-    token: String,
+    token: Option<String>,
+    access_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+#[derive(Clone)]
+pub(crate) enum CachedAuth {
+    Bearer(String),
+    Basic,
 }
 
 pub(crate) struct CachedToken {
-    pub(crate) token: String,
+    pub(crate) auth: CachedAuth,
     pub(crate) expires_at: Instant,
 }
 
-pub(crate) type TokenCache = Arc<RwLock<HashMap<String, CachedToken>>>;
+/// Registry credentials plus the tokens earned with them, keyed by registry
+/// host and repository so one registry's token is never sent to another.
+#[derive(Clone, Default)]
+pub(crate) struct TokenCache {
+    tokens: Arc<RwLock<HashMap<String, CachedToken>>>,
+    credentials: Arc<RegistryCredentials>,
+}
+
+impl TokenCache {
+    pub(crate) fn new(credentials: RegistryCredentials) -> Self {
+        Self {
+            tokens: Arc::default(),
+            credentials: Arc::new(credentials),
+        }
+    }
+
+    fn credentials_for(&self, url: &str) -> Option<&RegistryAuth> {
+        self.credentials.for_host(&host_for_url(url)?)
+    }
+}
+
+fn token_key(url: &str) -> Option<String> {
+    let host = host_for_url(url)?;
+    Some(match extract_scope_for_url(url) {
+        Some(scope) => format!("{host} {scope}"),
+        None => host,
+    })
+}
 
 pub(crate) async fn fetch_download_response_internal(
     client: &reqwest::Client,
     token_cache: &TokenCache,
     url: &str,
+    retry: &RetryPolicy,
 ) -> Result<reqwest::Response, Error> {
-    let cached_token = get_cached_token_for_url_internal(token_cache, url).await;
+    let mut last_error = None;
 
-    let mut request = client.get(url);
-    if let Some(token) = &cached_token {
-        request = request.header(AUTHORIZATION, bearer_header(token)?);
-    }
+    for attempt in 0..=retry.max_retries {
+        let request = authorize(token_cache, url, client.get(url)).await?;
 
-    let response = request.send().await.map_err(|e| Error::NetworkFailure {
-        message: e.to_string(),
-    })?;
+        match request.send().await {
+            Ok(response) => {
+                let response = if response.status() == StatusCode::UNAUTHORIZED {
+                    handle_auth_challenge_internal(client, token_cache, url, None, response).await?
+                } else {
+                    response
+                };
 
-    let response = if response.status() == StatusCode::UNAUTHORIZED {
-        handle_auth_challenge_internal(client, token_cache, url, response).await?
-    } else {
-        response
-    };
+                let status = response.status();
+                if !status.is_success() {
+                    let err = Error::HttpStatus {
+                        status: status.as_u16(),
+                        message: format!("HTTP {status}"),
+                    };
 
-    if !response.status().is_success() {
-        return Err(Error::NetworkFailure {
-            message: format!("HTTP {}", response.status()),
-        });
+                    if status.is_retryable() && attempt < retry.max_retries {
+                        last_error = Some(err);
+                        tokio::time::sleep(retry.delay(attempt)).await;
+                        continue;
+                    }
+                    return Err(err);
+                }
+
+                return Ok(response);
+            }
+            Err(e) => {
+                let retryable = e.is_retryable();
+                let err = Error::NetworkFailure {
+                    message: describe_request_error(&e),
+                };
+
+                if !retryable {
+                    return Err(err);
+                }
+                last_error = Some(err);
+
+                if attempt < retry.max_retries {
+                    tokio::time::sleep(retry.delay(attempt)).await;
+                    continue;
+                }
+            }
+        }
     }
 
-    Ok(response)
+    Err(last_error.unwrap_or_else(|| Error::NetworkFailure {
+        message: "download request failed after retries".into(),
+    }))
 }
 
 pub(crate) async fn fetch_range_response_internal(
@@ -65,36 +137,32 @@ pub(crate) async fn fetch_range_response_internal(
     token_cache: &TokenCache,
     url: &str,
     range: &str,
+    retry: &RetryPolicy,
 ) -> Result<reqwest::Response, Error> {
     let mut last_error = None;
 
-    for attempt in 0..=MAX_CHUNK_RETRIES {
-        let cached_token = get_cached_token_for_url_internal(token_cache, url).await;
-
-        let mut request = client.get(url).header("Range", range);
-        if let Some(token) = &cached_token {
-            request = request.header(AUTHORIZATION, bearer_header(token)?);
-        }
+    for attempt in 0..=retry.max_retries {
+        let request = authorize(token_cache, url, client.get(url).header("Range", range)).await?;
 
         match request.send().await {
             Ok(response) => {
                 let response = if response.status() == StatusCode::UNAUTHORIZED {
-                    match handle_auth_challenge_internal(client, token_cache, url, response).await {
-                        Ok(resp) => resp,
-                        Err(e) => return Err(e),
-                    }
+                    handle_auth_challenge_internal(client, token_cache, url, Some(range), response)
+                        .await?
                 } else {
                     response
                 };
 
-                if !response.status().is_success() {
-                    let err = Error::NetworkFailure {
-                        message: format!("HTTP {}", response.status()),
+                let status = response.status();
+                if !status.is_success() {
+                    let err = Error::HttpStatus {
+                        status: status.as_u16(),
+                        message: format!("HTTP {status}"),
                     };
 
-                    if response.status().is_server_error() && attempt < MAX_CHUNK_RETRIES {
+                    if status.is_retryable() && attempt < retry.max_retries {
                         last_error = Some(err);
-                        tokio::time::sleep(Duration::from_millis(100 * (1 << attempt))).await;
+                        tokio::time::sleep(retry.delay(attempt)).await;
                         continue;
                     }
                     return Err(err);
@@ -103,12 +171,18 @@ pub(crate) async fn fetch_range_response_internal(
                 return Ok(response);
             }
             Err(e) => {
-                last_error = Some(Error::NetworkFailure {
-                    message: e.to_string(),
-                });
+                let retryable = e.is_retryable();
+                let err = Error::NetworkFailure {
+                    message: describe_request_error(&e),
+                };
 
-                if attempt < MAX_CHUNK_RETRIES {
-                    tokio::time::sleep(Duration::from_millis(100 * (1 << attempt))).await;
+                if !retryable {
+                    return Err(err);
+                }
+                last_error = Some(err);
+
+                if attempt < retry.max_retries {
+                    tokio::time::sleep(retry.delay(attempt)).await;
                     continue;
                 }
             }
@@ -120,24 +194,47 @@ pub(crate) async fn fetch_range_response_internal(
     }))
 }
 
-pub(crate) async fn get_cached_token_for_url_internal(
+/// Add whatever `url` is known to need: a configured static token, or the
+/// bearer token or Basic credentials an earlier challenge asked for.
+pub(crate) async fn authorize(
     token_cache: &TokenCache,
     url: &str,
-) -> Option<String> {
-    let scope = extract_scope_for_url(url)?;
-    let cache = token_cache.read().await;
-    let now = Instant::now();
-
-    cache
-        .get(&scope)
-        .filter(|cached| cached.expires_at > now)
-        .map(|cached| cached.token.clone())
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::RequestBuilder, Error> {
+    let credentials = token_cache.credentials_for(url);
+    if let Some(RegistryAuth::Token(token)) = credentials {
+        return Ok(request.header(AUTHORIZATION, bearer_header(token)?));
+    }
+
+    let Some(key) = token_key(url) else {
+        return Ok(request);
+    };
+    let cached = token_cache
+        .tokens
+        .read()
+        .await
+        .get(&key)
+        .filter(|cached| cached.expires_at > Instant::now())
+        .map(|cached| cached.auth.clone());
+
+    Ok(match (cached, credentials) {
+        (Some(CachedAuth::Bearer(token)), _) => {
+            request.header(AUTHORIZATION, bearer_header(&token)?)
+        }
+        (Some(CachedAuth::Basic), Some(RegistryAuth::Basic { username, password })) => {
+            request.basic_auth(username, Some(password))
+        }
+        _ => request,
+    })
 }
 
+/// Answer a 401 for `url` and retry it once with the new authorization,
+/// re-sending `range` if the original request had one.
 pub(crate) async fn handle_auth_challenge_internal(
     client: &reqwest::Client,
     token_cache: &TokenCache,
     url: &str,
+    range: Option<&str>,
     response: reqwest::Response,
 ) -> Result<reqwest::Response, Error> {
     let www_auth_header = response.headers().get(WWW_AUTHENTICATE);
@@ -155,15 +252,18 @@ pub(crate) async fn handle_auth_challenge_internal(
         }
     };
 
-    let token = fetch_bearer_token_internal(client, token_cache, www_auth).await?;
+    refresh_authorization(client, token_cache, url, www_auth).await?;
 
-    let response = client
-        .get(url)
-        .header(AUTHORIZATION, bearer_header(&token)?)
+    let mut request = client.get(url);
+    if let Some(range) = range {
+        request = request.header("Range", range);
+    }
+    let response = authorize(token_cache, url, request)
+        .await?
         .send()
         .await
         .map_err(|e| Error::NetworkFailure {
-            message: e.to_string(),
+            message: describe_request_error(&e),
         })?;
 
     if response.status() == StatusCode::UNAUTHORIZED {
@@ -175,34 +275,87 @@ pub(crate) async fn handle_auth_challenge_internal(
     Ok(response)
 }
 
-pub(crate) async fn fetch_bearer_token_internal(
+/// Replace whatever authorization is cached for `url` with one answering
+/// `www_authenticate`. The cached one is what the server just rejected, so
+/// an expired or revoked token is never offered twice.
+pub(crate) async fn refresh_authorization(
     client: &reqwest::Client,
     token_cache: &TokenCache,
+    url: &str,
     www_authenticate: &str,
-) -> Result<String, Error> {
-    let (realm, service, scope) = parse_www_authenticate(www_authenticate)?;
-
-    {
-        let cache = token_cache.read().await;
-        if let Some(cached) = cache.get(&scope)
-            && cached.expires_at > Instant::now()
-        {
-            return Ok(cached.token.clone());
+) -> Result<(), Error> {
+    let key = token_key(url).ok_or_else(|| Error::NetworkFailure {
+        message: format!("cannot authenticate against {url}"),
+    })?;
+    token_cache.tokens.write().await.remove(&key);
+
+    let credentials = token_cache.credentials_for(url);
+    let (scheme, params) = www_authenticate
+        .split_once(' ')
+        .unwrap_or((www_authenticate, ""));
+
+    let (auth, lifetime) = if scheme.eq_ignore_ascii_case("bearer") {
+        fetch_bearer_token_internal(client, params, url, credentials).await?
+    } else if scheme.eq_ignore_ascii_case("basic") {
+        if !matches!(credentials, Some(RegistryAuth::Basic { .. })) {
+            return Err(Error::NetworkFailure {
+                message: format!(
+                    "{} requires a username and password; add them to the registry credentials",
+                    host_for_url(url).unwrap_or_default()
+                ),
+            });
         }
+        (CachedAuth::Basic, BASIC_AUTH_LIFETIME)
+    } else {
+        return Err(Error::NetworkFailure {
+            message: "unsupported auth scheme".to_string(),
+        });
+    };
+
+    token_cache.tokens.write().await.insert(
+        key,
+        CachedToken {
+            auth,
+            expires_at: Instant::now() + lifetime,
+        },
+    );
+    Ok(())
+}
+
+/// Exchange a `Bearer` challenge for a token, presenting Basic credentials
+/// to the token endpoint when the registry has them configured.
+async fn fetch_bearer_token_internal(
+    client: &reqwest::Client,
+    challenge: &str,
+    url: &str,
+    credentials: Option<&RegistryAuth>,
+) -> Result<(CachedAuth, Duration), Error> {
+    let (realm, service, scope) = parse_bearer_challenge(challenge)?;
+    let scope = scope.or_else(|| extract_scope_for_url(url));
+
+    let mut params = Vec::new();
+    if let Some(service) = &service {
+        params.push(("service", service.as_str()));
+    }
+    if let Some(scope) = &scope {
+        params.push(("scope", scope.as_str()));
     }
+    let token_url = reqwest::Url::parse_with_params(&realm, &params)
+        .map_err(Error::network("failed to construct token URL"))?;
 
-    let token_url =
-        reqwest::Url::parse_with_params(&realm, &[("service", &service), ("scope", &scope)])
-            .map_err(Error::network("failed to construct token URL"))?;
+    let mut request = client.get(token_url);
+    if let Some(RegistryAuth::Basic { username, password }) = credentials {
+        request = request.basic_auth(username, Some(password));
+    }
 
-    let response = client
-        .get(token_url)
+    let response = request
         .send()
         .await
         .map_err(Error::network("token request failed"))?;
 
     if !response.status().is_success() {
-        return Err(Error::NetworkFailure {
+        return Err(Error::HttpStatus {
+            status: response.status().as_u16(),
             message: format!("token request returned HTTP {}", response.status()),
         });
     }
@@ -211,47 +364,49 @@ pub(crate) async fn fetch_bearer_token_internal(
         .json()
         .await
         .map_err(Error::network("failed to parse token response"))?;
+    let token = token_response
+        .token
+        .or(token_response.access_token)
+        .ok_or_else(|| Error::NetworkFailure {
+            message: "token response did not contain a token".to_string(),
+        })?;
 
-    {
-        let mut cache = token_cache.write().await;
-        cache.insert(
-            scope,
-            CachedToken {
-                token: token_response.token.clone(),
-                expires_at: Instant::now() + Duration::from_secs(240),
-            },
-        );
-    }
+    // Renew a little early so a token doesn't lapse mid-request.
+    let lifetime = token_response
+        .expires_in
+        .map(|secs| Duration::from_secs(secs * 9 / 10))
+        .unwrap_or(DEFAULT_TOKEN_LIFETIME);
 
-    Ok(token_response.token)
+    Ok((CachedAuth::Bearer(token), lifetime))
 }
 
+/// The pull scope for an OCI blob or manifest URL on any registry, e.g.
+/// `repository:homebrew/core/jq:pull`. Mirrors served under a path prefix
+/// work too, since only what follows `/v2/` is read.
 pub(crate) fn extract_scope_for_url(url: &str) -> Option<String> {
-    let marker = "ghcr.io/v2/";
-    let start = url.find(marker)? + marker.len();
-    let remainder = &url[start..];
-    let mut parts = remainder.split('/');
-    let owner = parts.next()?;
-    let repo = parts.next()?;
-    let formula = parts.next()?;
-    if owner.is_empty() || repo.is_empty() || formula.is_empty() {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    let path = parsed.path();
+    let path = &path[path.find("/v2/")? + "/v2/".len()..];
+
+    let segments: Vec<&str> = path.split('/').collect();
+    let end = segments
+        .iter()
+        .position(|segment| *segment == "blobs" || *segment == "manifests")?;
+    let repository = &segments[..end];
+    if repository.is_empty() || repository.iter().any(|segment| segment.is_empty()) {
         return None;
     }
-    Some(format!("repository:{owner}/{repo}/{formula}:pull"))
+    Some(format!("repository:{}:pull", repository.join("/")))
 }
 
-fn parse_www_authenticate(header: &str) -> Result<(String, String, String), Error> {
-    let header = header
-        .strip_prefix("Bearer ")
-        .ok_or_else(|| Error::NetworkFailure {
-            message: "unsupported auth scheme".to_string(),
-        })?;
-
+/// Split the parameters of a `Bearer` challenge into realm, service and
+/// scope. Only the realm is required; registries may leave the others out.
+fn parse_bearer_challenge(params: &str) -> Result<(String, Option<String>, Option<String>), Error> {
     let mut realm = None;
     let mut service = None;
     let mut scope = None;
 
-    for part in header.split(',') {
+    for part in params.split(',') {
         let part = part.trim();
         if let Some((key, value)) = part.split_once('=') {
             let value = value.trim_matches('"');
@@ -267,12 +422,6 @@ fn parse_www_authenticate(header: &str) -> Result<(String, String, String), Erro
     let realm = realm.ok_or_else(|| Error::NetworkFailure {
         message: "missing realm in WWW-Authenticate".to_string(),
     })?;
-    let service = service.ok_or_else(|| Error::NetworkFailure {
-        message: "missing service in WWW-Authenticate".to_string(),
-    })?;
-    let scope = scope.ok_or_else(|| Error::NetworkFailure {
-        message: "missing scope in WWW-Authenticate".to_string(),
-    })?;
 
     Ok((realm, service, scope))
 }
@@ -295,4 +444,144 @@ mod tests {
                 .unwrap();
         assert_eq!(scope, "repository:hashicorp/tap/terraform:pull");
     }
+
+    #[test]
+    fn extract_scope_for_url_supports_other_registries() {
+        let scope = extract_scope_for_url(
+            "https://registry.corp.example:5000/mirror/v2/team/bottles/jq/blobs/sha256:abc",
+        )
+        .unwrap();
+        assert_eq!(scope, "repository:team/bottles/jq:pull");
+        assert!(extract_scope_for_url("https://example.com/bottles/jq.tar.gz").is_none());
+    }
+
+    mod registry {
+        use tempfile::TempDir;
+        use wiremock::matchers::{basic_auth, bearer_token, method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        use super::*;
+        use crate::network::download::Downloader;
+        use crate::storage::blob::BlobCache;
+
+        const CONTENT: &[u8] = b"hello world";
+        const CONTENT_SHA256: &str =
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+        const BLOB_PATH: &str = "/v2/team/bottles/pkg/blobs/sha256:abc";
+
+        fn basic_credentials(server: &MockServer) -> RegistryCredentials {
+            let mut credentials = RegistryCredentials::default();
+            credentials.insert(
+                &server.address().to_string(),
+                RegistryAuth::Basic {
+                    username: "ci".to_string(),
+                    password: "s3cret".to_string(),
+                },
+            );
+            credentials
+        }
+
+        async fn mount_challenge(server: &MockServer, challenge: String) {
+            Mock::given(path(BLOB_PATH))
+                .respond_with(
+                    ResponseTemplate::new(401).insert_header("WWW-Authenticate", challenge),
+                )
+                .with_priority(10)
+                .mount(server)
+                .await;
+        }
+
+        #[tokio::test]
+        async fn bearer_token_is_fetched_with_configured_credentials() {
+            let server = MockServer::start().await;
+            mount_challenge(
+                &server,
+                format!(
+                    r#"Bearer realm="{}/token",service="registry""#,
+                    server.uri()
+                ),
+            )
+            .await;
+            Mock::given(method("GET"))
+                .and(path("/token"))
+                .and(basic_auth("ci", "s3cret"))
+                .and(query_param("scope", "repository:team/bottles/pkg:pull"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_string(r#"{"access_token": "private-token", "expires_in": 300}"#),
+                )
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path(BLOB_PATH))
+                .and(bearer_token("private-token"))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(CONTENT))
+                .mount(&server)
+                .await;
+
+            let tmp = TempDir::new().unwrap();
+            let downloader = Downloader::new(BlobCache::new(tmp.path()).unwrap())
+                .with_registry_credentials(basic_credentials(&server));
+
+            let url = format!("{}{BLOB_PATH}", server.uri());
+            let blob = downloader.download(&url, CONTENT_SHA256).await.unwrap();
+            assert_eq!(std::fs::read(blob).unwrap(), CONTENT);
+        }
+
+        #[tokio::test]
+        async fn basic_challenge_is_answered_only_with_credentials() {
+            let server = MockServer::start().await;
+            mount_challenge(&server, r#"Basic realm="registry""#.to_string()).await;
+            Mock::given(method("GET"))
+                .and(path(BLOB_PATH))
+                .and(basic_auth("ci", "s3cret"))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(CONTENT))
+                .mount(&server)
+                .await;
+            let url = format!("{}{BLOB_PATH}", server.uri());
+
+            let tmp = TempDir::new().unwrap();
+            let anonymous = Downloader::new(BlobCache::new(tmp.path()).unwrap());
+            let err = anonymous.download(&url, CONTENT_SHA256).await.unwrap_err();
+            assert!(err.to_string().contains("requires a username"), "{err}");
+
+            let downloader = Downloader::new(BlobCache::new(tmp.path()).unwrap())
+                .with_registry_credentials(basic_credentials(&server));
+            let blob = downloader.download(&url, CONTENT_SHA256).await.unwrap();
+            assert_eq!(std::fs::read(blob).unwrap(), CONTENT);
+        }
+
+        #[tokio::test]
+        async fn refresh_replaces_a_rejected_token() {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/token"))
+                .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"token": "fresh"}"#))
+                .mount(&server)
+                .await;
+
+            let url = format!("{}{BLOB_PATH}", server.uri());
+            let token_cache = TokenCache::default();
+            token_cache.tokens.write().await.insert(
+                token_key(&url).unwrap(),
+                CachedToken {
+                    auth: CachedAuth::Bearer("stale".to_string()),
+                    expires_at: Instant::now() + Duration::from_secs(60),
+                },
+            );
+
+            let client = reqwest::Client::new();
+            let challenge = format!(r#"Bearer realm="{}/token""#, server.uri());
+            refresh_authorization(&client, &token_cache, &url, &challenge)
+                .await
+                .unwrap();
+
+            let request = authorize(&token_cache, &url, client.get(&url))
+                .await
+                .unwrap()
+                .build()
+                .unwrap();
+            assert_eq!(request.headers()[AUTHORIZATION], "Bearer fresh");
+        }
+    }
 }
@@ -3,23 +3,25 @@ use std::io::Write;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Duration;
 
+use crate::network::tls::describe_request_error;
 use crate::progress::InstallProgress;
 use crate::storage::blob::BlobCache;
 use futures_util::StreamExt;
 use reqwest::StatusCode;
-use reqwest::header::{ACCEPT_RANGES, AUTHORIZATION, CONTENT_RANGE};
+use reqwest::header::{ACCEPT_RANGES, CONTENT_RANGE};
 use sha2::{Digest, Sha256};
 use tokio::sync::{Mutex, Semaphore, mpsc};
 use zb_core::Error;
 
 use super::auth::{
-    TokenCache, bearer_header, fetch_bearer_token_internal, fetch_download_response_internal,
-    fetch_range_response_internal, get_cached_token_for_url_internal,
+    TokenCache, authorize, fetch_download_response_internal, fetch_range_response_internal,
+    refresh_authorization,
 };
-use super::single::download_response_internal;
-use super::{DownloadProgressCallback, MAX_CHUNK_RETRIES, MAX_CONCURRENT_CHUNKS};
+use super::rate_limit::RateLimiter;
+use super::retry::{RetryPolicy, RetryableError};
+use super::single::{ResumeSource, download_response_internal};
+use super::{DownloadProgressCallback, MAX_CONCURRENT_CHUNKS};
 
 const MIN_CHUNK_SIZE: u64 = 5 * 1024 * 1024;
 const MAX_CHUNK_SIZE: u64 = 20 * 1024 * 1024;
@@ -32,6 +34,8 @@ struct ChunkDownloadContext<'a> {
     name: Option<String>,
     file_size: u64,
     total_downloaded: Arc<AtomicU64>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    retry: RetryPolicy,
 }
 
 pub(crate) struct ChunkedDownloadContext<'a> {
@@ -44,6 +48,8 @@ pub(crate) struct ChunkedDownloadContext<'a> {
     pub(crate) progress: Option<DownloadProgressCallback>,
     pub(crate) file_size: u64,
     pub(crate) global_semaphore: &'a Arc<Semaphore>,
+    pub(crate) rate_limiter: Option<&'a Arc<RateLimiter>>,
+    pub(crate) retry: RetryPolicy,
 }
 
 struct ChunkRange {
@@ -92,16 +98,15 @@ async fn download_chunk(
 
     let mut last_error = None;
 
-    for attempt in 0..=MAX_CHUNK_RETRIES {
-        let cached_token = get_cached_token_for_url_internal(ctx.token_cache, ctx.url).await;
-
-        let mut request = ctx
-            .client
-            .get(ctx.url)
-            .header("Range", range_header.clone());
-        if let Some(token) = &cached_token {
-            request = request.header(AUTHORIZATION, bearer_header(token)?);
-        }
+    for attempt in 0..=ctx.retry.max_retries {
+        let request = authorize(
+            ctx.token_cache,
+            ctx.url,
+            ctx.client
+                .get(ctx.url)
+                .header("Range", range_header.clone()),
+        )
+        .await?;
 
         match request.send().await {
             Ok(response) => {
@@ -119,8 +124,10 @@ async fn download_chunk(
                         }
                     };
 
-                    match fetch_bearer_token_internal(ctx.client, ctx.token_cache, www_auth).await {
-                        Ok(_new_token) => {
+                    match refresh_authorization(ctx.client, ctx.token_cache, ctx.url, www_auth)
+                        .await
+                    {
+                        Ok(()) => {
                             last_error = Some(Error::NetworkFailure {
                                 message: "token expired, retrying with new token".to_string(),
                             });
@@ -150,14 +157,16 @@ async fn download_chunk(
                     }
                 }
 
-                if !response.status().is_success() {
-                    let err = Error::NetworkFailure {
-                        message: format!("chunk download returned HTTP {}", response.status()),
+                let status = response.status();
+                if !status.is_success() {
+                    let err = Error::HttpStatus {
+                        status: status.as_u16(),
+                        message: format!("chunk download returned HTTP {status}"),
                     };
 
-                    if response.status().is_server_error() && attempt < MAX_CHUNK_RETRIES {
+                    if status.is_retryable() && attempt < ctx.retry.max_retries {
                         last_error = Some(err);
-                        tokio::time::sleep(Duration::from_millis(100 * (1 << attempt))).await;
+                        tokio::time::sleep(ctx.retry.delay(attempt)).await;
                         continue;
                     }
                     return Err(err);
@@ -169,6 +178,10 @@ async fn download_chunk(
                 while let Some(item) = stream.next().await {
                     let bytes = item.map_err(Error::network("failed to read chunk bytes"))?;
 
+                    if let Some(limiter) = &ctx.rate_limiter {
+                        limiter.acquire(bytes.len() as u64).await;
+                    }
+
                     chunk_data.extend_from_slice(&bytes);
 
                     if let (Some(cb), Some(n)) = (&ctx.progress, &ctx.name) {
@@ -196,10 +209,18 @@ async fn download_chunk(
                 return Ok(chunk_data);
             }
             Err(e) => {
-                last_error = Some(Error::network("chunk download failed")(e));
+                let retryable = e.is_retryable();
+                let err = Error::NetworkFailure {
+                    message: format!("chunk download failed: {}", describe_request_error(&e)),
+                };
+
+                if !retryable {
+                    return Err(err);
+                }
+                last_error = Some(err);
 
-                if attempt < MAX_CHUNK_RETRIES {
-                    tokio::time::sleep(Duration::from_millis(100 * (1 << attempt))).await;
+                if attempt < ctx.retry.max_retries {
+                    tokio::time::sleep(ctx.retry.delay(attempt)).await;
                     continue;
                 }
             }
@@ -216,13 +237,21 @@ pub(crate) async fn download_with_chunks(
 ) -> Result<PathBuf, Error> {
     if !validate_range_support(ctx).await? {
         let response =
-            fetch_download_response_internal(ctx.client, ctx.token_cache, ctx.url).await?;
+            fetch_download_response_internal(ctx.client, ctx.token_cache, ctx.url, &ctx.retry)
+                .await?;
         return download_response_internal(
             ctx.blob_cache,
             response,
             ctx.expected_sha256,
             ctx.name.clone(),
             ctx.progress.clone(),
+            ctx.rate_limiter.map(|limiter| limiter.as_ref()),
+            Some(ResumeSource {
+                client: ctx.client,
+                token_cache: ctx.token_cache,
+                url: ctx.url,
+                retry: &ctx.retry,
+            }),
         )
         .await;
     }
@@ -262,6 +291,8 @@ pub(crate) async fn download_with_chunks(
         let chunk_tx = chunk_tx.clone();
         let file_size = ctx.file_size;
         let writer = writer.clone();
+        let rate_limiter = ctx.rate_limiter.cloned();
+        let retry = ctx.retry;
 
         let handle = tokio::spawn(async move {
             let _permit = global_semaphore
@@ -277,6 +308,8 @@ pub(crate) async fn download_with_chunks(
                 name: name.clone(),
                 file_size,
                 total_downloaded: total_downloaded.clone(),
+                rate_limiter,
+                retry,
             };
 
             let chunk_data = download_chunk(&chunk_ctx, &chunk).await?;
@@ -397,12 +430,18 @@ pub(crate) async fn download_with_chunks(
         });
     }
 
-    writer.commit()
+    writer.commit_verified()
 }
 
 async fn validate_range_support(ctx: &ChunkedDownloadContext<'_>) -> Result<bool, Error> {
-    let response =
-        fetch_range_response_internal(ctx.client, ctx.token_cache, ctx.url, "bytes=0-0").await?;
+    let response = fetch_range_response_internal(
+        ctx.client,
+        ctx.token_cache,
+        ctx.url,
+        "bytes=0-0",
+        &ctx.retry,
+    )
+    .await?;
 
     if response.status() != StatusCode::PARTIAL_CONTENT {
         return Ok(false);
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+use zb_core::Error;
+
+/// How to authenticate against one registry.
+#[derive(Clone, PartialEq, Eq)]
+pub enum RegistryAuth {
+    /// Sent to the registry's token endpoint in exchange for a bearer token,
+    /// or straight to registries that challenge with `Basic`.
+    Basic { username: String, password: String },
+    /// A bearer token sent with every request as-is.
+    Token(String),
+}
+
+impl fmt::Debug for RegistryAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &"<redacted>")
+                .finish(),
+            Self::Token(_) => f.debug_tuple("Token").field(&"<redacted>").finish(),
+        }
+    }
+}
+
+/// Credentials for private OCI registries, keyed by host (with its port,
+/// if any). Registries without an entry are accessed anonymously, the way
+/// ghcr.io serves Homebrew's public bottles.
+#[derive(Debug, Clone, Default)]
+pub struct RegistryCredentials {
+    hosts: HashMap<String, RegistryAuth>,
+}
+
+#[derive(Deserialize)]
+struct CredentialsFile {
+    #[serde(default)]
+    registries: HashMap<String, CredentialsEntry>,
+}
+
+#[derive(Deserialize)]
+struct CredentialsEntry {
+    username: Option<String>,
+    password: Option<String>,
+    token: Option<String>,
+}
+
+impl RegistryCredentials {
+    pub fn insert(&mut self, host: &str, auth: RegistryAuth) {
+        self.hosts.insert(normalize_host(host), auth);
+    }
+
+    pub fn for_host(&self, host: &str) -> Option<&RegistryAuth> {
+        self.hosts.get(host)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+
+    /// Read a JSON file of the form
+    /// `{"registries": {"host": {"username": .., "password": ..} | {"token": ..}}}`.
+    /// A missing file holds no credentials.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(Error::FileError {
+                    message: format!(
+                        "failed to read registry credentials '{}': {e}",
+                        path.display()
+                    ),
+                });
+            }
+        };
+
+        let file: CredentialsFile =
+            serde_json::from_str(&contents).map_err(|e| Error::InvalidArgument {
+                message: format!("invalid registry credentials '{}': {e}", path.display()),
+            })?;
+
+        let mut credentials = Self::default();
+        for (host, entry) in file.registries {
+            let auth = match entry {
+                CredentialsEntry {
+                    token: Some(token), ..
+                } => RegistryAuth::Token(token),
+                CredentialsEntry {
+                    username: Some(username),
+                    password: Some(password),
+                    ..
+                } => RegistryAuth::Basic { username, password },
+                _ => {
+                    return Err(Error::InvalidArgument {
+                        message: format!(
+                            "registry credentials for '{host}' need a token or a username and password"
+                        ),
+                    });
+                }
+            };
+            credentials.insert(&host, auth);
+        }
+        Ok(credentials)
+    }
+
+    /// Add credentials for `ZEROBREW_REGISTRY_HOST` from
+    /// `ZEROBREW_REGISTRY_TOKEN`, or `ZEROBREW_REGISTRY_USERNAME` and
+    /// `ZEROBREW_REGISTRY_PASSWORD`, replacing any from a file.
+    pub fn apply_env(&mut self) {
+        self.apply_vars(|name| std::env::var(name).ok().filter(|v| !v.is_empty()));
+    }
+
+    fn apply_vars(&mut self, var: impl Fn(&str) -> Option<String>) {
+        let Some(host) = var("ZEROBREW_REGISTRY_HOST") else {
+            return;
+        };
+
+        let auth = match (
+            var("ZEROBREW_REGISTRY_TOKEN"),
+            var("ZEROBREW_REGISTRY_USERNAME"),
+            var("ZEROBREW_REGISTRY_PASSWORD"),
+        ) {
+            (Some(token), _, _) => RegistryAuth::Token(token),
+            (None, Some(username), Some(password)) => RegistryAuth::Basic { username, password },
+            _ => return,
+        };
+        self.insert(&host, auth);
+    }
+}
+
+/// `https://registry.example:5000/v2/` and `registry.example:5000` both
+/// name the host `registry.example:5000`.
+fn normalize_host(host: &str) -> String {
+    let host = host.split_once("://").map(|(_, rest)| rest).unwrap_or(host);
+    host.split('/').next().unwrap_or(host).to_ascii_lowercase()
+}
+
+/// The credentials key for `url`: its host, plus the port if one is given.
+pub(crate) fn host_for_url(url: &str) -> Option<String> {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    let host = parsed.host_str()?.to_ascii_lowercase();
+    Some(match parsed.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn loads_basic_and_token_entries_by_host() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("registry-auth.json");
+        std::fs::write(
+            &path,
+            r#"{"registries": {
+                "https://Registry.corp.example:5000/v2/": {"username": "ci", "password": "s3cret"},
+                "bottles.example": {"token": "abc"}
+            }}"#,
+        )
+        .unwrap();
+
+        let credentials = RegistryCredentials::load(&path).unwrap();
+        assert_eq!(
+            credentials.for_host("registry.corp.example:5000"),
+            Some(&RegistryAuth::Basic {
+                username: "ci".to_string(),
+                password: "s3cret".to_string(),
+            })
+        );
+        assert_eq!(
+            credentials.for_host("bottles.example"),
+            Some(&RegistryAuth::Token("abc".to_string()))
+        );
+        assert!(credentials.for_host("ghcr.io").is_none());
+    }
+
+    #[test]
+    fn missing_file_has_no_credentials_but_incomplete_entries_are_rejected() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("registry-auth.json");
+        assert!(RegistryCredentials::load(&path).unwrap().is_empty());
+
+        std::fs::write(
+            &path,
+            r#"{"registries": {"r.example": {"username": "ci"}}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            RegistryCredentials::load(&path),
+            Err(Error::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn env_credentials_need_a_host() {
+        let vars = |set: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                set.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        let mut credentials = RegistryCredentials::default();
+        credentials.apply_vars(vars(&[("ZEROBREW_REGISTRY_TOKEN", "abc")]));
+        assert!(credentials.is_empty());
+
+        credentials.apply_vars(vars(&[
+            ("ZEROBREW_REGISTRY_HOST", "registry.corp.example"),
+            ("ZEROBREW_REGISTRY_USERNAME", "ci"),
+            ("ZEROBREW_REGISTRY_PASSWORD", "s3cret"),
+        ]));
+        assert!(matches!(
+            credentials.for_host("registry.corp.example"),
+            Some(RegistryAuth::Basic { username, .. }) if username == "ci"
+        ));
+    }
+
+    #[test]
+    fn debug_output_redacts_secrets() {
+        let basic = RegistryAuth::Basic {
+            username: "ci".to_string(),
+            password: "s3cret".to_string(),
+        };
+        assert!(!format!("{basic:?}").contains("s3cret"));
+        assert!(!format!("{:?}", RegistryAuth::Token("abc".to_string())).contains("abc"));
+    }
+
+    #[test]
+    fn url_hosts_keep_their_port() {
+        assert_eq!(
+            host_for_url("https://ghcr.io/v2/homebrew/core/jq/blobs/sha256:a").as_deref(),
+            Some("ghcr.io")
+        );
+        assert_eq!(
+            host_for_url("http://127.0.0.1:8080/v2/x/blobs/sha256:a").as_deref(),
+            Some("127.0.0.1:8080")
+        );
+    }
+}
@@ -0,0 +1,651 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use futures_util::stream::FuturesUnordered;
+use tokio::sync::{Mutex, Semaphore, mpsc};
+use tokio::task::JoinHandle;
+
+use crate::storage::blob::BlobCache;
+use zb_core::Error;
+
+use super::credentials::RegistryCredentials;
+use super::retry::RetryPolicy;
+use super::single::Downloader;
+use super::{DownloadProgressCallback, DownloadResult, GLOBAL_DOWNLOAD_CONCURRENCY};
+
+pub struct DownloadRequest {
+    pub url: String,
+    pub sha256: String,
+    pub name: String,
+}
+
+type InflightMap = HashMap<String, Arc<tokio::sync::broadcast::Sender<Result<PathBuf, String>>>>;
+
+/// Coordinates every bottle and cask download for an installer.
+///
+/// The manager owns the shared HTTP client and rate limiter (through its
+/// `Downloader`), the concurrency semaphore, and the in-flight map that
+/// collapses duplicate requests for the same blob. Jobs are submitted and
+/// awaited; each resolves to a checksum-verified path in the blob cache.
+/// Transient failures are handled below this layer: chunk requests retry,
+/// and a single-connection body that drops part-way resumes with a range
+/// request instead of starting over. Its bytes are kept in the cache as a
+/// `.part` file, so a later run can resume it too.
+///
+/// A concurrency limit of 1 makes the manager strictly serial: downloads run
+/// one at a time in request order, each over a single connection, so their
+/// progress events never interleave.
+///
+/// Single, batch and streaming downloads all run as submitted jobs, so they
+/// share the same retry, dedup and progress handling.
+#[derive(Clone)]
+pub struct DownloadManager {
+    downloader: Arc<Downloader>,
+    semaphore: Arc<Semaphore>,
+    inflight: Arc<Mutex<InflightMap>>,
+    serial: bool,
+}
+
+impl DownloadManager {
+    pub fn new(blob_cache: BlobCache) -> Self {
+        let semaphore = Arc::new(Semaphore::new(GLOBAL_DOWNLOAD_CONCURRENCY));
+        Self {
+            downloader: Arc::new(Downloader::with_semaphore(
+                blob_cache,
+                Some(semaphore.clone()),
+            )),
+            semaphore,
+            inflight: Arc::new(Mutex::new(HashMap::new())),
+            serial: false,
+        }
+    }
+
+    pub fn with_concurrency(blob_cache: BlobCache, concurrency: usize) -> Self {
+        Self::with_limits(
+            blob_cache,
+            concurrency,
+            None,
+            RetryPolicy::default(),
+            Vec::new(),
+            RegistryCredentials::default(),
+        )
+    }
+
+    /// Like `with_concurrency`, additionally capping aggregate download
+    /// throughput across all concurrent requests when `max_download_rate`
+    /// (bytes per second) is set, retrying transient failures per `retry`,
+    /// falling back to `mirrors` in order when ghcr.io fails, and
+    /// authenticating to private registries with `credentials`.
+    pub fn with_limits(
+        blob_cache: BlobCache,
+        concurrency: usize,
+        max_download_rate: Option<u64>,
+        retry: RetryPolicy,
+        mirrors: Vec<String>,
+        credentials: RegistryCredentials,
+    ) -> Self {
+        let serial = concurrency == 1;
+        let semaphore = Arc::new(Semaphore::new(concurrency));
+        let mut downloader = Downloader::with_semaphore(blob_cache, Some(semaphore.clone()))
+            .with_retry_policy(retry)
+            .with_mirrors(mirrors)
+            .with_registry_credentials(credentials);
+        if let Some(rate) = max_download_rate {
+            downloader = downloader.with_max_download_rate(rate);
+        }
+        if serial {
+            downloader = downloader.with_serial_transfers();
+        }
+
+        Self {
+            downloader: Arc::new(downloader),
+            semaphore,
+            inflight: Arc::new(Mutex::new(HashMap::new())),
+            serial,
+        }
+    }
+
+    pub fn blob_cache(&self) -> &BlobCache {
+        &self.downloader.blob_cache
+    }
+
+    pub fn remove_blob(&self, sha256: &str) -> bool {
+        self.downloader.remove_blob(sha256)
+    }
+
+    /// Queue a download and return a handle that resolves once the blob is
+    /// in the cache. The job starts immediately, subject to the concurrency
+    /// limit, whether or not the handle is awaited.
+    pub fn submit(
+        &self,
+        request: DownloadRequest,
+        progress: Option<DownloadProgressCallback>,
+    ) -> DownloadJob {
+        let downloader = self.downloader.clone();
+        let semaphore = self.semaphore.clone();
+        let inflight = self.inflight.clone();
+        let name = request.name.clone();
+
+        let handle = tokio::spawn(async move {
+            Self::download_with_dedup(downloader, semaphore, inflight, request, progress).await
+        });
+
+        DownloadJob { name, handle }
+    }
+
+    pub async fn download_single(
+        &self,
+        request: DownloadRequest,
+        progress: Option<DownloadProgressCallback>,
+    ) -> Result<PathBuf, Error> {
+        self.submit(request, progress).wait().await
+    }
+
+    pub async fn download_all(
+        &self,
+        requests: Vec<DownloadRequest>,
+    ) -> Result<Vec<PathBuf>, Error> {
+        self.download_all_with_progress(requests, None).await
+    }
+
+    pub async fn download_all_with_progress(
+        &self,
+        requests: Vec<DownloadRequest>,
+        progress: Option<DownloadProgressCallback>,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let mut paths = vec![None; requests.len()];
+        let mut rx = self.download_streaming(requests, progress);
+        while let Some(result) = rx.recv().await {
+            let result = result?;
+            paths[result.index] = Some(result.blob_path);
+        }
+
+        Ok(paths.into_iter().flatten().collect())
+    }
+
+    /// Download `requests`, sending each result as it finishes. In serial
+    /// mode a job is only submitted once the one before it is done.
+    pub fn download_streaming(
+        &self,
+        requests: Vec<DownloadRequest>,
+        progress: Option<DownloadProgressCallback>,
+    ) -> mpsc::Receiver<Result<DownloadResult, Error>> {
+        let (tx, rx) = mpsc::channel(requests.len().max(1));
+        let manager = self.clone();
+
+        tokio::spawn(async move {
+            if manager.serial {
+                for (index, req) in requests.into_iter().enumerate() {
+                    let sha256 = req.sha256.clone();
+                    let job = manager.submit(req, progress.clone());
+                    if tx.send(job.finish(index, sha256).await).await.is_err() {
+                        break;
+                    }
+                }
+                return;
+            }
+
+            let mut pending: FuturesUnordered<_> = requests
+                .into_iter()
+                .enumerate()
+                .map(|(index, req)| {
+                    let sha256 = req.sha256.clone();
+                    manager.submit(req, progress.clone()).finish(index, sha256)
+                })
+                .collect();
+            while let Some(result) = pending.next().await {
+                if tx.send(result).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        rx
+    }
+
+    async fn download_with_dedup(
+        downloader: Arc<Downloader>,
+        semaphore: Arc<Semaphore>,
+        inflight: Arc<Mutex<InflightMap>>,
+        req: DownloadRequest,
+        progress: Option<DownloadProgressCallback>,
+    ) -> Result<PathBuf, Error> {
+        let mut receiver = {
+            let mut map = inflight.lock().await;
+
+            if let Some(sender) = map.get(&req.sha256) {
+                Some(sender.subscribe())
+            } else {
+                let (tx, _) = tokio::sync::broadcast::channel(1);
+                map.insert(req.sha256.clone(), Arc::new(tx));
+                None
+            }
+        };
+
+        if let Some(ref mut rx) = receiver {
+            let result = rx
+                .recv()
+                .await
+                .map_err(Error::network("broadcast recv error"))?;
+
+            return result.map_err(|msg| Error::NetworkFailure { message: msg });
+        }
+
+        let _permit = semaphore
+            .acquire()
+            .await
+            .map_err(Error::network("semaphore error"))?;
+
+        let result = downloader
+            .download_with_progress(&req.url, &req.sha256, Some(req.name), progress)
+            .await;
+
+        {
+            let mut map = inflight.lock().await;
+            if let Some(sender) = map.remove(&req.sha256) {
+                let broadcast_result = match &result {
+                    Ok(path) => Ok(path.clone()),
+                    Err(e) => Err(e.to_string()),
+                };
+                let _ = sender.send(broadcast_result);
+            }
+        }
+
+        result
+    }
+}
+
+/// The name `DownloadManager` had before it took over single downloads.
+#[deprecated(note = "renamed to `DownloadManager`")]
+pub type ParallelDownloader = DownloadManager;
+
+/// A download submitted to a `DownloadManager`.
+pub struct DownloadJob {
+    name: String,
+    handle: JoinHandle<Result<PathBuf, Error>>,
+}
+
+impl DownloadJob {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Wait for the job and return the verified blob path.
+    pub async fn wait(self) -> Result<PathBuf, Error> {
+        self.handle
+            .await
+            .map_err(Error::network("task join error"))?
+    }
+
+    /// Wait for the job as item `index` of a batch.
+    async fn finish(self, index: usize, sha256: String) -> Result<DownloadResult, Error> {
+        let name = self.name.clone();
+        self.wait().await.map(|blob_path| DownloadResult {
+            name,
+            sha256,
+            blob_path,
+            index,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use sha2::{Digest, Sha256};
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::progress::InstallProgress;
+    use crate::storage::blob::BlobCache;
+
+    use super::super::GLOBAL_DOWNLOAD_CONCURRENCY;
+    use super::*;
+
+    #[tokio::test]
+    async fn peak_concurrent_downloads_within_limit() {
+        let mock_server = MockServer::start().await;
+        let concurrent_count = Arc::new(AtomicUsize::new(0));
+        let max_concurrent = Arc::new(AtomicUsize::new(0));
+
+        let content = b"test content";
+        let count_clone = concurrent_count.clone();
+        let max_clone = max_concurrent.clone();
+
+        Mock::given(method("GET"))
+            .respond_with(move |_: &wiremock::Request| {
+                let current = count_clone.fetch_add(1, Ordering::SeqCst) + 1;
+                max_clone.fetch_max(current, Ordering::SeqCst);
+
+                std::thread::sleep(Duration::from_millis(50));
+
+                count_clone.fetch_sub(1, Ordering::SeqCst);
+                ResponseTemplate::new(200).set_body_bytes(content.to_vec())
+            })
+            .mount(&mock_server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let blob_cache = BlobCache::new(tmp.path()).unwrap();
+        let downloader = DownloadManager::new(blob_cache);
+
+        let requests: Vec<_> = (0..5)
+            .map(|i| {
+                let sha256 = format!("{:064x}", i);
+                DownloadRequest {
+                    url: format!("{}/file{i}.tar.gz", mock_server.uri()),
+                    sha256,
+                    name: format!("pkg{i}"),
+                }
+            })
+            .collect();
+
+        let _ = downloader.download_all(requests).await;
+
+        let peak = max_concurrent.load(Ordering::SeqCst);
+        assert!(
+            peak <= GLOBAL_DOWNLOAD_CONCURRENCY,
+            "peak concurrent downloads was {peak}, expected <= {GLOBAL_DOWNLOAD_CONCURRENCY}"
+        );
+    }
+
+    #[tokio::test]
+    async fn concurrency_one_downloads_serially_in_request_order() {
+        let mock_server = MockServer::start().await;
+        let concurrent_count = Arc::new(AtomicUsize::new(0));
+        let max_concurrent = Arc::new(AtomicUsize::new(0));
+
+        let mut requests = Vec::new();
+        for i in 0..4 {
+            let content = format!("package {i}").into_bytes();
+            let count_clone = concurrent_count.clone();
+            let max_clone = max_concurrent.clone();
+            let body = content.clone();
+            Mock::given(method("GET"))
+                .and(path(format!("/pkg{i}.tar.gz")))
+                .respond_with(move |_: &wiremock::Request| {
+                    let current = count_clone.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_clone.fetch_max(current, Ordering::SeqCst);
+                    // Later requests answer faster, so any overlap would
+                    // reorder completions.
+                    std::thread::sleep(Duration::from_millis(80 - i * 20));
+                    count_clone.fetch_sub(1, Ordering::SeqCst);
+                    ResponseTemplate::new(200).set_body_bytes(body.clone())
+                })
+                .mount(&mock_server)
+                .await;
+
+            requests.push(DownloadRequest {
+                url: format!("{}/pkg{i}.tar.gz", mock_server.uri()),
+                sha256: sha256_of(&content),
+                name: format!("pkg{i}"),
+            });
+        }
+
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let events_clone = events.clone();
+        let progress: DownloadProgressCallback =
+            Arc::new(move |event: InstallProgress| match event {
+                InstallProgress::DownloadStarted { name, .. } => {
+                    events_clone.lock().unwrap().push(format!("start {name}"));
+                }
+                InstallProgress::DownloadCompleted { name, .. } => {
+                    events_clone.lock().unwrap().push(format!("done {name}"));
+                }
+                _ => {}
+            });
+
+        let tmp = TempDir::new().unwrap();
+        let manager = DownloadManager::with_concurrency(BlobCache::new(tmp.path()).unwrap(), 1);
+        let mut rx = manager.download_streaming(requests, Some(progress));
+
+        let mut order = Vec::new();
+        while let Some(result) = rx.recv().await {
+            order.push(result.unwrap().index);
+        }
+
+        assert_eq!(order, vec![0, 1, 2, 3]);
+        assert_eq!(max_concurrent.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            *events.lock().unwrap(),
+            (0..4)
+                .flat_map(|i| [format!("start pkg{i}"), format!("done pkg{i}")])
+                .collect::<Vec<_>>()
+        );
+    }
+
+    fn sha256_of(content: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(content);
+        crate::checksum::sha256_hex(hasher)
+    }
+
+    /// Minimal HTTP/1.1 server that supports `Range` requests. The first GET
+    /// of `truncate_path` advertises the full length but closes the
+    /// connection halfway through the body. Returns the base URL and a log of
+    /// every `Range` header received.
+    fn spawn_range_server(
+        files: HashMap<&'static str, Vec<u8>>,
+        truncate_path: &'static str,
+    ) -> (String, Arc<std::sync::Mutex<Vec<String>>>) {
+        use std::io::{BufRead, BufReader, Write};
+        use std::sync::atomic::AtomicBool;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let files = Arc::new(files);
+        let truncated = Arc::new(AtomicBool::new(false));
+        let ranges = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let ranges_log = ranges.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let files = files.clone();
+                let truncated = truncated.clone();
+                let ranges = ranges.clone();
+
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request_line = String::new();
+                    if reader.read_line(&mut request_line).is_err() {
+                        return;
+                    }
+                    let mut parts = request_line.split_whitespace();
+                    let method = parts.next().unwrap_or("").to_string();
+                    let path = parts.next().unwrap_or("").to_string();
+
+                    let mut range = None;
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                            break;
+                        }
+                        if let Some((key, value)) = line.split_once(':')
+                            && key.eq_ignore_ascii_case("range")
+                        {
+                            range = Some(value.trim().to_string());
+                        }
+                    }
+
+                    let Some(body) = files.get(path.as_str()) else {
+                        let _ = stream.write_all(
+                            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        );
+                        return;
+                    };
+                    let len = body.len();
+
+                    if method == "HEAD" {
+                        let _ = write!(
+                            stream,
+                            "HTTP/1.1 200 OK\r\nContent-Length: {len}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n\r\n"
+                        );
+                        return;
+                    }
+
+                    if let Some(range) = range {
+                        ranges.lock().unwrap().push(format!("{path} {range}"));
+                        let start: usize = range
+                            .trim_start_matches("bytes=")
+                            .trim_end_matches('-')
+                            .parse()
+                            .unwrap();
+                        let rest = &body[start..];
+                        let _ = write!(
+                            stream,
+                            "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {start}-{}/{len}\r\nConnection: close\r\n\r\n",
+                            rest.len(),
+                            len - 1
+                        );
+                        let _ = stream.write_all(rest);
+                        return;
+                    }
+
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Length: {len}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n\r\n"
+                    );
+                    if path == truncate_path && !truncated.swap(true, Ordering::SeqCst) {
+                        let _ = stream.write_all(&body[..len / 2]);
+                        let _ = stream.flush();
+                        let _ = stream.shutdown(std::net::Shutdown::Both);
+                        return;
+                    }
+                    let _ = stream.write_all(body);
+                });
+            }
+        });
+
+        (base_url, ranges_log)
+    }
+
+    #[tokio::test]
+    async fn concurrent_jobs_fail_resume_and_report_progress_independently() {
+        let ok_body = vec![b'a'; 64 * 1024];
+        let resume_body: Vec<u8> = (0..128 * 1024).map(|i| (i % 251) as u8).collect();
+        let ok_sha = sha256_of(&ok_body);
+        let resume_sha = sha256_of(&resume_body);
+
+        let mut files = HashMap::new();
+        files.insert("/ok.tar.gz", ok_body.clone());
+        files.insert("/resume.tar.gz", resume_body.clone());
+        let (base_url, ranges) = spawn_range_server(files, "/resume.tar.gz");
+
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let events_clone = events.clone();
+        let progress: DownloadProgressCallback = Arc::new(move |event| {
+            events_clone.lock().unwrap().push(event);
+        });
+
+        let tmp = TempDir::new().unwrap();
+        let blob_cache = BlobCache::new(tmp.path()).unwrap();
+        let manager = DownloadManager::with_concurrency(blob_cache, 4);
+
+        let ok_job = manager.submit(
+            DownloadRequest {
+                url: format!("{base_url}/ok.tar.gz"),
+                sha256: ok_sha.clone(),
+                name: "ok".to_string(),
+            },
+            Some(progress.clone()),
+        );
+        let resume_job = manager.submit(
+            DownloadRequest {
+                url: format!("{base_url}/resume.tar.gz"),
+                sha256: resume_sha.clone(),
+                name: "resume".to_string(),
+            },
+            Some(progress.clone()),
+        );
+        let missing_job = manager.submit(
+            DownloadRequest {
+                url: format!("{base_url}/missing.tar.gz"),
+                sha256: format!("{:064x}", 7),
+                name: "missing".to_string(),
+            },
+            Some(progress.clone()),
+        );
+        assert_eq!(missing_job.name(), "missing");
+
+        let ok_path = ok_job.wait().await.unwrap();
+        let resume_path = resume_job.wait().await.unwrap();
+        let missing = missing_job.wait().await;
+
+        assert_eq!(std::fs::read(ok_path).unwrap(), ok_body);
+        assert_eq!(std::fs::read(resume_path).unwrap(), resume_body);
+        assert!(missing.is_err());
+
+        let ranges = ranges.lock().unwrap();
+        assert!(
+            ranges
+                .iter()
+                .any(|range| range.starts_with("/resume.tar.gz bytes=")),
+            "expected a resume range request, got {ranges:?}"
+        );
+
+        let events = events.lock().unwrap();
+        for name in ["ok", "resume"] {
+            assert!(events.iter().any(|event| matches!(
+                event,
+                InstallProgress::DownloadStarted { name: n, .. } if n == name
+            )));
+            assert!(events.iter().any(|event| matches!(
+                event,
+                InstallProgress::DownloadCompleted { name: n, .. } if n == name
+            )));
+        }
+        assert!(!events.iter().any(|event| matches!(
+            event,
+            InstallProgress::DownloadCompleted { name: n, .. } if n == "missing"
+        )));
+    }
+
+    #[tokio::test]
+    async fn same_blob_requested_multiple_times_fetches_once() {
+        let mock_server = MockServer::start().await;
+        let content = b"deduplicated content";
+
+        let actual_sha256 = {
+            let mut hasher = Sha256::new();
+            hasher.update(content);
+            crate::checksum::sha256_hex(hasher)
+        };
+
+        Mock::given(method("GET"))
+            .and(path("/dedup.tar.gz"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(content.to_vec())
+                    .set_delay(Duration::from_millis(100)),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let blob_cache = BlobCache::new(tmp.path()).unwrap();
+        let downloader = DownloadManager::new(blob_cache);
+
+        let requests: Vec<_> = (0..5)
+            .map(|i| DownloadRequest {
+                url: format!("{}/dedup.tar.gz", mock_server.uri()),
+                sha256: actual_sha256.clone(),
+                name: format!("dedup{i}"),
+            })
+            .collect();
+
+        let results = downloader.download_all(requests).await.unwrap();
+
+        assert_eq!(results.len(), 5);
+        for path in &results {
+            assert!(path.exists());
+        }
+    }
+}
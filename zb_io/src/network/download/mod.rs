@@ -1,6 +1,9 @@
 mod auth;
 mod chunked;
-mod parallel;
+mod credentials;
+mod manager;
+mod rate_limit;
+mod retry;
 mod single;
 
 use std::path::PathBuf;
@@ -27,8 +30,9 @@ const GLOBAL_DOWNLOAD_CONCURRENCY: usize = 20;
 /// With 20 global concurrency, we can have 3-4 large files downloading concurrently.
 const MAX_CONCURRENT_CHUNKS: usize = 6;
 
-/// Maximum retry attempts for failed chunk downloads
-const MAX_CHUNK_RETRIES: u32 = 3;
+/// Maximum times a single-connection download resumes after its body stream
+/// drops part-way through
+const MAX_RESUME_ATTEMPTS: u32 = 3;
 
 #[derive(Debug, Clone)]
 pub struct DownloadResult {
@@ -38,5 +42,10 @@ pub struct DownloadResult {
     pub index: usize,
 }
 
-pub use parallel::{DownloadRequest, ParallelDownloader};
+pub use credentials::{RegistryAuth, RegistryCredentials};
+#[allow(deprecated)]
+pub use manager::ParallelDownloader;
+pub use manager::{DownloadJob, DownloadManager, DownloadRequest};
+pub use rate_limit::RateLimiter;
+pub use retry::{RetryPolicy, RetryableError};
 pub use single::Downloader;
@@ -0,0 +1,93 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+/// Token-bucket limiter shared by every download running through a
+/// `Downloader`, so the cap applies to aggregate throughput rather than
+/// per connection.
+///
+/// The bucket starts empty and holds at most one second worth of tokens,
+/// which keeps bursts after an idle period bounded by the configured rate.
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            bucket: Mutex::new(Bucket {
+                tokens: 0.0,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    /// Wait until `bytes` worth of tokens are available and consume them.
+    pub async fn acquire(&self, bytes: u64) {
+        let rate = self.bytes_per_sec as f64;
+        let mut remaining = bytes as f64;
+
+        while remaining > 0.0 {
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+                bucket.last_refill = now;
+
+                let take = remaining.min(bucket.tokens);
+                bucket.tokens -= take;
+                remaining -= take;
+
+                if remaining <= 0.0 {
+                    return;
+                }
+
+                // Sleep for at most one bucket's worth so concurrent callers
+                // interleave instead of one large read starving the others.
+                Duration::from_secs_f64(remaining.min(rate) / rate)
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn acquire_paces_to_configured_rate() {
+        let limiter = RateLimiter::new(10_000);
+        let start = Instant::now();
+
+        for _ in 0..5 {
+            limiter.acquire(1_000).await;
+        }
+
+        // 5_000 bytes at 10_000 B/s from an empty bucket takes ~500ms.
+        assert!(start.elapsed() >= Duration::from_millis(450));
+    }
+
+    #[tokio::test]
+    async fn zero_byte_acquire_returns_immediately() {
+        let limiter = RateLimiter::new(1);
+        let start = Instant::now();
+
+        limiter.acquire(0).await;
+
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,290 @@
+use std::io;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use zb_core::Error;
+
+/// Classifies failures as transient (worth another attempt) or terminal.
+///
+/// The download retry loops consult this instead of deciding per call site,
+/// so the policy lives in one place: timeouts, dropped connections and 5xx
+/// responses are retried, while checksum mismatches, 4xx responses and
+/// permission errors fail immediately.
+pub trait RetryableError {
+    fn is_retryable(&self) -> bool;
+}
+
+impl RetryableError for StatusCode {
+    fn is_retryable(&self) -> bool {
+        self.is_server_error()
+            || *self == StatusCode::REQUEST_TIMEOUT
+            || *self == StatusCode::TOO_MANY_REQUESTS
+    }
+}
+
+impl RetryableError for reqwest::Error {
+    fn is_retryable(&self) -> bool {
+        if let Some(status) = self.status() {
+            return status.is_retryable();
+        }
+
+        self.is_timeout() || self.is_connect() || self.is_request() || self.is_body()
+    }
+}
+
+impl RetryableError for io::Error {
+    fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind(),
+            io::ErrorKind::TimedOut
+                | io::ErrorKind::Interrupted
+                | io::ErrorKind::WouldBlock
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::UnexpectedEof
+        )
+    }
+}
+
+impl RetryableError for Error {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::HttpStatus { status, .. } => {
+                StatusCode::from_u16(*status).is_ok_and(|status| status.is_retryable())
+            }
+            Error::NetworkFailure { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// How many times a transient failure is retried and how long to wait
+/// between attempts.
+///
+/// Delays double from `base_delay` up to `max_delay`. With `jitter` set, each
+/// delay keeps half of its backoff and randomises the rest, so downloads that
+/// failed together do not all retry in the same instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that gives up after the first failure.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before retry number `attempt + 1`.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt).unwrap_or(u32::MAX);
+        let backoff = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if !self.jitter {
+            return backoff;
+        }
+
+        let half = backoff / 2;
+        half + half.mul_f64(random_fraction())
+    }
+}
+
+/// A value in `[0, 1)` from the process-seeded std hasher, which is enough
+/// to spread retries without pulling in an RNG.
+fn random_fraction() -> f64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    let bits = RandomState::new().build_hasher().finish() >> 11;
+    bits as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn server_errors_and_throttling_are_retryable() {
+        assert!(StatusCode::INTERNAL_SERVER_ERROR.is_retryable());
+        assert!(StatusCode::BAD_GATEWAY.is_retryable());
+        assert!(StatusCode::SERVICE_UNAVAILABLE.is_retryable());
+        assert!(StatusCode::GATEWAY_TIMEOUT.is_retryable());
+        assert!(StatusCode::REQUEST_TIMEOUT.is_retryable());
+        assert!(StatusCode::TOO_MANY_REQUESTS.is_retryable());
+    }
+
+    #[test]
+    fn client_errors_are_terminal() {
+        assert!(!StatusCode::BAD_REQUEST.is_retryable());
+        assert!(!StatusCode::UNAUTHORIZED.is_retryable());
+        assert!(!StatusCode::FORBIDDEN.is_retryable());
+        assert!(!StatusCode::NOT_FOUND.is_retryable());
+        assert!(!StatusCode::GONE.is_retryable());
+    }
+
+    #[test]
+    fn io_error_kinds_are_classified() {
+        for kind in [
+            io::ErrorKind::TimedOut,
+            io::ErrorKind::Interrupted,
+            io::ErrorKind::ConnectionReset,
+            io::ErrorKind::ConnectionAborted,
+            io::ErrorKind::UnexpectedEof,
+        ] {
+            assert!(io::Error::from(kind).is_retryable(), "{kind:?}");
+        }
+
+        for kind in [
+            io::ErrorKind::PermissionDenied,
+            io::ErrorKind::NotFound,
+            io::ErrorKind::AlreadyExists,
+            io::ErrorKind::InvalidData,
+        ] {
+            assert!(!io::Error::from(kind).is_retryable(), "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn transport_and_server_failures_are_retryable_core_errors() {
+        assert!(
+            Error::NetworkFailure {
+                message: "connection reset".to_string(),
+            }
+            .is_retryable()
+        );
+        assert!(
+            Error::HttpStatus {
+                status: 503,
+                message: "HTTP 503 Service Unavailable".to_string(),
+            }
+            .is_retryable()
+        );
+
+        let terminal = [
+            Error::HttpStatus {
+                status: 404,
+                message: "HTTP 404 Not Found".to_string(),
+            },
+            Error::HttpStatus {
+                status: 403,
+                message: "HTTP 403 Forbidden".to_string(),
+            },
+            Error::ChecksumMismatch {
+                expected: "aaa".to_string(),
+                actual: "bbb".to_string(),
+            },
+            Error::MissingFormula {
+                name: "foo".to_string(),
+            },
+            Error::FileError {
+                message: "permission denied".to_string(),
+            },
+            Error::StoreCorruption {
+                message: "bad blob".to_string(),
+            },
+            Error::UnsupportedBottle {
+                name: "foo".to_string(),
+            },
+            Error::InvalidArgument {
+                message: "bad".to_string(),
+            },
+        ];
+        for err in terminal {
+            assert!(!err.is_retryable(), "{err}");
+        }
+    }
+
+    #[tokio::test]
+    async fn reqwest_status_errors_follow_status_classification() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/missing"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flaky"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&mock_server)
+            .await;
+
+        let client = reqwest::Client::new();
+        let status_error = |route: &'static str| {
+            let client = client.clone();
+            let url = format!("{}/{route}", mock_server.uri());
+            async move {
+                client
+                    .get(url)
+                    .send()
+                    .await
+                    .unwrap()
+                    .error_for_status()
+                    .unwrap_err()
+            }
+        };
+
+        assert!(!status_error("missing").await.is_retryable());
+        assert!(status_error("flaky").await.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn reqwest_connect_errors_are_retryable() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let err = reqwest::Client::new()
+            .get(format!("http://{addr}/"))
+            .send()
+            .await
+            .unwrap_err();
+
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn retry_delay_backs_off_exponentially_up_to_the_cap() {
+        let policy = RetryPolicy {
+            jitter: false,
+            max_delay: Duration::from_millis(500),
+            ..RetryPolicy::default()
+        };
+
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(1), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(500));
+        assert_eq!(policy.delay(40), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn jittered_delay_stays_within_half_and_full_backoff() {
+        let policy = RetryPolicy::default();
+
+        for attempt in 0..6 {
+            let backoff = Duration::from_millis(100 * (1 << attempt));
+            for _ in 0..20 {
+                let delay = policy.delay(attempt);
+                assert!(delay >= backoff / 2, "{delay:?} < half of {backoff:?}");
+                assert!(delay <= backoff, "{delay:?} > {backoff:?}");
+            }
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use std::collections::HashMap;
 use std::io::Write;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -7,45 +6,63 @@ use std::time::Duration;
 
 use futures_util::StreamExt;
 use futures_util::future::select_all;
-use reqwest::header::{AUTHORIZATION, CONTENT_LENGTH};
+use reqwest::StatusCode;
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE};
 use sha2::{Digest, Sha256};
-use tokio::sync::{Notify, RwLock, Semaphore};
-use tracing::warn;
+use tokio::sync::{Notify, Semaphore};
+use tracing::{debug, warn};
 
+use crate::network::proxy::with_proxy;
 use crate::network::tls::shared_tls_config;
 use crate::progress::InstallProgress;
 use crate::storage::blob::BlobCache;
 use zb_core::Error;
 
 use super::auth::{
-    TokenCache, bearer_header, fetch_download_response_internal, get_cached_token_for_url_internal,
+    TokenCache, authorize, fetch_download_response_internal, fetch_range_response_internal,
 };
 use super::chunked::{ChunkedDownloadContext, download_with_chunks, server_supports_ranges};
+use super::credentials::RegistryCredentials;
+use super::rate_limit::RateLimiter;
+use super::retry::RetryPolicy;
 use super::{
     CHUNKED_DOWNLOAD_THRESHOLD, DownloadProgressCallback, GLOBAL_DOWNLOAD_CONCURRENCY,
-    RACING_CONNECTIONS, RACING_STAGGER_MS,
+    MAX_RESUME_ATTEMPTS, RACING_CONNECTIONS, RACING_STAGGER_MS,
 };
 
-fn get_alternate_urls(primary_url: &str) -> Vec<String> {
-    let mut alternates = Vec::new();
-
-    if let Ok(mirrors) = std::env::var("HOMEBREW_BOTTLE_MIRRORS") {
-        for mirror in mirrors.split(',') {
-            let mirror = mirror.trim();
-            if !mirror.is_empty()
-                && let Some(alt) = transform_url_to_mirror(primary_url, mirror)
-            {
-                alternates.push(alt);
-            }
+/// Mirror URLs to try, in order, after `primary_url`: the configured mirrors
+/// first, then any listed in `HOMEBREW_BOTTLE_MIRRORS`.
+fn get_alternate_urls(primary_url: &str, configured: &[String]) -> Vec<String> {
+    let from_env = std::env::var("HOMEBREW_BOTTLE_MIRRORS").unwrap_or_default();
+    let mirrors = configured
+        .iter()
+        .map(String::as_str)
+        .chain(from_env.split(','));
+
+    let mut alternates: Vec<String> = Vec::new();
+    for mirror in mirrors {
+        let mirror = mirror.trim();
+        if !mirror.is_empty()
+            && let Some(alt) = transform_url_to_mirror(primary_url, mirror)
+            && alt != primary_url
+            && !alternates.contains(&alt)
+        {
+            alternates.push(alt);
         }
     }
 
     alternates
 }
 
-fn transform_url_to_mirror(url: &str, mirror_domain: &str) -> Option<String> {
-    if url.contains("ghcr.io") {
-        Some(url.replace("ghcr.io", mirror_domain))
+/// Point a ghcr.io URL at `mirror`, which is either a bare domain that
+/// replaces `ghcr.io` or a base URL such as `https://mirror.example/ghcr`
+/// that replaces the scheme and host.
+fn transform_url_to_mirror(url: &str, mirror: &str) -> Option<String> {
+    if mirror.contains("://") {
+        let path = url.strip_prefix("https://ghcr.io")?;
+        Some(format!("{}{path}", mirror.trim_end_matches('/')))
+    } else if url.contains("ghcr.io") {
+        Some(url.replace("ghcr.io", mirror))
     } else {
         None
     }
@@ -56,6 +73,10 @@ pub struct Downloader {
     pub(crate) blob_cache: BlobCache,
     pub(crate) token_cache: TokenCache,
     pub(crate) global_semaphore: Option<Arc<Semaphore>>,
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+    retry: RetryPolicy,
+    mirrors: Vec<String>,
+    serial: bool,
     tls_config: Arc<rustls::ClientConfig>,
 }
 
@@ -67,7 +88,7 @@ impl Downloader {
     pub fn with_semaphore(blob_cache: BlobCache, semaphore: Option<Arc<Semaphore>>) -> Self {
         let tls_config = shared_tls_config();
 
-        let client = reqwest::Client::builder()
+        let client = with_proxy(reqwest::Client::builder())
             .user_agent("zerobrew/0.1")
             .use_preconfigured_tls((*tls_config).clone())
             .pool_max_idle_per_host(10)
@@ -84,14 +105,52 @@ impl Downloader {
         Self {
             client,
             blob_cache,
-            token_cache: Arc::new(RwLock::new(HashMap::new())),
+            token_cache: TokenCache::default(),
             global_semaphore: semaphore,
+            rate_limiter: None,
+            retry: RetryPolicy::default(),
+            mirrors: Vec::new(),
+            serial: false,
             tls_config,
         }
     }
 
+    /// Cap aggregate body throughput for every download made through this
+    /// downloader, including concurrent and chunked ones.
+    pub fn with_max_download_rate(mut self, bytes_per_sec: u64) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(bytes_per_sec)));
+        self
+    }
+
+    /// How transient failures on bottle requests are retried.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Mirrors tried in order when ghcr.io fails, ahead of any listed in
+    /// `HOMEBREW_BOTTLE_MIRRORS`.
+    pub fn with_mirrors(mut self, mirrors: Vec<String>) -> Self {
+        self.mirrors = mirrors;
+        self
+    }
+
+    /// Authenticate against private registries with `credentials`; hosts
+    /// without an entry are still accessed anonymously.
+    pub fn with_registry_credentials(mut self, credentials: RegistryCredentials) -> Self {
+        self.token_cache = TokenCache::new(credentials);
+        self
+    }
+
+    /// Use one connection per download: no chunked ranges and no racing.
+    /// Mirrors are still tried, one after another, if the primary fails.
+    pub fn with_serial_transfers(mut self) -> Self {
+        self.serial = true;
+        self
+    }
+
     fn create_isolated_client(&self) -> reqwest::Client {
-        reqwest::Client::builder()
+        with_proxy(reqwest::Client::builder())
             .user_agent("zerobrew/0.1")
             .use_preconfigured_tls((*self.tls_config).clone())
             .pool_max_idle_per_host(0)
@@ -123,22 +182,53 @@ impl Downloader {
         progress: Option<DownloadProgressCallback>,
     ) -> Result<PathBuf, Error> {
         if self.blob_cache.has_blob(expected_sha256) {
-            if let (Some(cb), Some(n)) = (&progress, &name) {
-                cb(InstallProgress::DownloadCompleted {
-                    name: n.clone(),
-                    total_bytes: 0,
-                });
+            if self.cached_blob_is_intact(expected_sha256).await {
+                debug!(url, sha256 = %expected_sha256, "using cached download");
+                if let (Some(cb), Some(n)) = (&progress, &name) {
+                    cb(InstallProgress::DownloadCompleted {
+                        name: n.clone(),
+                        total_bytes: 0,
+                    });
+                }
+                return Ok(self.blob_cache.blob_path(expected_sha256));
             }
-            return Ok(self.blob_cache.blob_path(expected_sha256));
+
+            // The fresh download is verified against the same checksum, so a
+            // second mismatch surfaces as `ChecksumMismatch` below.
+            warn!(
+                sha256 = %expected_sha256,
+                "cached blob no longer matches its checksum; re-downloading"
+            );
+            self.blob_cache.remove_blob(expected_sha256).ok();
         }
 
-        let alternates = get_alternate_urls(url);
+        if let Some(result) = self
+            .resume_partial(url, expected_sha256, name.clone(), progress.clone())
+            .await
+        {
+            match result {
+                Err(Error::ChecksumMismatch { .. }) => warn!(
+                    sha256 = %expected_sha256,
+                    "resumed download did not match its checksum; starting over"
+                ),
+                result => return result,
+            }
+        }
+
+        let alternates = get_alternate_urls(url, &self.mirrors);
+        debug!(url, ?alternates, sha256 = %expected_sha256, "downloading");
+
+        if self.serial {
+            return self
+                .download_sequentially(url, &alternates, expected_sha256, name, progress)
+                .await;
+        }
 
         self.download_with_racing(url, &alternates, expected_sha256, name, progress)
             .await
     }
 
-    async fn download_with_racing(
+    async fn download_sequentially(
         &self,
         primary_url: &str,
         alternate_urls: &[String],
@@ -146,14 +236,117 @@ impl Downloader {
         name: Option<String>,
         progress: Option<DownloadProgressCallback>,
     ) -> Result<PathBuf, Error> {
-        let (use_chunked, file_size) = {
-            let cached_token =
-                get_cached_token_for_url_internal(&self.token_cache, primary_url).await;
+        let mut last_error = None;
 
-            let mut request = self.client.head(primary_url);
-            if let Some(token) = &cached_token {
-                request = request.header(AUTHORIZATION, bearer_header(token)?);
+        for url in std::iter::once(primary_url).chain(alternate_urls.iter().map(String::as_str)) {
+            let response = match fetch_download_response_internal(
+                &self.client,
+                &self.token_cache,
+                url,
+                &self.retry,
+            )
+            .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+
+            match download_response_internal(
+                &self.blob_cache,
+                response,
+                expected_sha256,
+                name.clone(),
+                progress.clone(),
+                self.rate_limiter.as_deref(),
+                Some(ResumeSource {
+                    client: &self.client,
+                    token_cache: &self.token_cache,
+                    url,
+                    retry: &self.retry,
+                }),
+            )
+            .await
+            {
+                Ok(path) => return Ok(path),
+                Err(e) => last_error = Some(e),
             }
+        }
+
+        Err(last_error.unwrap_or_else(|| Error::NetworkFailure {
+            message: "all download attempts failed".to_string(),
+        }))
+    }
+
+    /// Continue a download an earlier run left in the partial file, if the
+    /// server honours a range request for the rest. `None` means there was
+    /// nothing to resume and the caller should download from scratch.
+    async fn resume_partial(
+        &self,
+        url: &str,
+        expected_sha256: &str,
+        name: Option<String>,
+        progress: Option<DownloadProgressCallback>,
+    ) -> Option<Result<PathBuf, Error>> {
+        let offset = self.blob_cache.partial_len(expected_sha256);
+        if offset == 0 {
+            return None;
+        }
+
+        let source = ResumeSource {
+            client: &self.client,
+            token_cache: &self.token_cache,
+            url,
+            retry: &self.retry,
+        };
+        let response = source.resume_from(offset).await?;
+        warn!(
+            sha256 = %expected_sha256,
+            offset,
+            "resuming interrupted download with a range request"
+        );
+
+        Some(
+            download_response_internal(
+                &self.blob_cache,
+                response,
+                expected_sha256,
+                name,
+                progress,
+                self.rate_limiter.as_deref(),
+                Some(source),
+            )
+            .await,
+        )
+    }
+
+    async fn cached_blob_is_intact(&self, sha256: &str) -> bool {
+        let blob_cache = self.blob_cache.clone();
+        let sha256 = sha256.to_string();
+        tokio::task::spawn_blocking(move || blob_cache.blob_matches(&sha256))
+            .await
+            .ok()
+            .and_then(Result::ok)
+            .unwrap_or(false)
+    }
+
+    async fn download_with_racing(
+        &self,
+        primary_url: &str,
+        alternate_urls: &[String],
+        expected_sha256: &str,
+        name: Option<String>,
+        progress: Option<DownloadProgressCallback>,
+    ) -> Result<PathBuf, Error> {
+        let (use_chunked, file_size) = {
+            let request = authorize(
+                &self.token_cache,
+                primary_url,
+                self.client.head(primary_url),
+            )
+            .await?;
 
             match request.send().await {
                 Ok(response) if response.status().is_success() => {
@@ -200,6 +393,8 @@ impl Downloader {
                     progress: progress.clone(),
                     file_size: size,
                     global_semaphore: &semaphore,
+                    rate_limiter: self.rate_limiter.as_ref(),
+                    retry: self.retry,
                 };
 
                 match download_with_chunks(&ctx).await {
@@ -244,6 +439,8 @@ impl Downloader {
             let done = done.clone();
             let done_notify = done_notify.clone();
             let body_download_gate = body_download_gate.clone();
+            let rate_limiter = self.rate_limiter.clone();
+            let retry = self.retry;
 
             let delay = Duration::from_millis(idx as u64 * RACING_STAGGER_MS);
 
@@ -269,9 +466,13 @@ impl Downloader {
                     return Ok(blob_cache.blob_path(&expected_sha256));
                 }
 
-                let response =
-                    fetch_download_response_internal(&downloader_client, &token_cache, &url)
-                        .await?;
+                let response = fetch_download_response_internal(
+                    &downloader_client,
+                    &token_cache,
+                    &url,
+                    &retry,
+                )
+                .await?;
 
                 let _permit = tokio::select! {
                     permit = body_download_gate.acquire_owned() => permit.map_err(|_| Error::NetworkFailure {
@@ -309,6 +510,13 @@ impl Downloader {
                     &expected_sha256,
                     name,
                     progress,
+                    rate_limiter.as_deref(),
+                    Some(ResumeSource {
+                        client: &downloader_client,
+                        token_cache: &token_cache,
+                        url: &url,
+                        retry: &retry,
+                    }),
                 )
                 .await;
 
@@ -348,18 +556,64 @@ impl Downloader {
     }
 }
 
+/// Where to re-request the rest of a body from if the connection drops
+/// part-way through a single-connection download.
+pub(crate) struct ResumeSource<'a> {
+    pub(crate) client: &'a reqwest::Client,
+    pub(crate) token_cache: &'a TokenCache,
+    pub(crate) url: &'a str,
+    pub(crate) retry: &'a RetryPolicy,
+}
+
+impl ResumeSource<'_> {
+    /// Request everything from byte `offset` onward. Only a `206` whose
+    /// `Content-Range` starts at `offset` can be appended to what we already
+    /// have; anything else means the server can't resume this body.
+    async fn resume_from(&self, offset: u64) -> Option<reqwest::Response> {
+        let response = fetch_range_response_internal(
+            self.client,
+            self.token_cache,
+            self.url,
+            &format!("bytes={offset}-"),
+            self.retry,
+        )
+        .await
+        .ok()?;
+
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return None;
+        }
+
+        let content_range = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+        content_range
+            .starts_with(&format!("bytes {offset}-"))
+            .then_some(response)
+    }
+}
+
 pub(crate) async fn download_response_internal(
     blob_cache: &BlobCache,
     response: reqwest::Response,
     expected_sha256: &str,
     name: Option<String>,
     progress: Option<DownloadProgressCallback>,
+    rate_limiter: Option<&RateLimiter>,
+    resume: Option<ResumeSource<'_>>,
 ) -> Result<PathBuf, Error> {
+    // A `206` continues the partial file an interrupted attempt left behind;
+    // anything else starts it over.
+    let offset = if response.status() == StatusCode::PARTIAL_CONTENT {
+        blob_cache.partial_len(expected_sha256)
+    } else {
+        0
+    };
+
     let total_bytes = response
         .headers()
         .get(CONTENT_LENGTH)
         .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.parse::<u64>().ok());
+        .and_then(|s| s.parse::<u64>().ok())
+        .map(|len| len + offset);
 
     if let (Some(cb), Some(n)) = (&progress, &name) {
         cb(InstallProgress::DownloadStarted {
@@ -368,16 +622,50 @@ pub(crate) async fn download_response_internal(
         });
     }
 
+    let mut hasher = Sha256::new();
+    if offset > 0 {
+        let mut partial = std::fs::File::open(blob_cache.partial_path(expected_sha256))
+            .map_err(Error::network("failed to read partial download"))?;
+        std::io::copy(&mut partial, &mut hasher)
+            .map_err(Error::network("failed to read partial download"))?;
+    }
+
     let mut writer = blob_cache
-        .start_write(expected_sha256)
+        .start_partial_write(expected_sha256, offset > 0)
         .map_err(Error::network("failed to create blob writer"))?;
 
-    let mut hasher = Sha256::new();
     let mut stream = response.bytes_stream();
-    let mut downloaded: u64 = 0;
+    let mut downloaded: u64 = offset;
+    let mut resumes = 0;
 
     while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(Error::network("failed to read chunk"))?;
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                let resumed = match &resume {
+                    Some(source) if resumes < MAX_RESUME_ATTEMPTS && downloaded > 0 => {
+                        source.resume_from(downloaded).await
+                    }
+                    _ => None,
+                };
+                let Some(resumed) = resumed else {
+                    return Err(Error::network("failed to read chunk")(err));
+                };
+
+                resumes += 1;
+                warn!(
+                    error = %err,
+                    offset = downloaded,
+                    "download interrupted; resuming with a range request"
+                );
+                stream = resumed.bytes_stream();
+                continue;
+            }
+        };
+
+        if let Some(limiter) = rate_limiter {
+            limiter.acquire(chunk.len() as u64).await;
+        }
 
         downloaded += chunk.len() as u64;
         hasher.update(&chunk);
@@ -397,6 +685,7 @@ pub(crate) async fn download_response_internal(
     let actual_hash = crate::checksum::sha256_hex(hasher);
 
     if actual_hash != expected_sha256 {
+        writer.discard();
         return Err(Error::ChecksumMismatch {
             expected: expected_sha256.to_string(),
             actual: actual_hash,
@@ -414,7 +703,7 @@ pub(crate) async fn download_response_internal(
         });
     }
 
-    writer.commit()
+    writer.commit_verified()
 }
 
 #[cfg(test)]
@@ -511,4 +800,235 @@ mod tests {
 
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn max_download_rate_caps_throughput() {
+        use sha2::{Digest, Sha256};
+        use std::time::Instant;
+
+        let mock_server = MockServer::start().await;
+        let content = vec![0x5a_u8; 128 * 1024];
+        let sha256 = {
+            let mut hasher = Sha256::new();
+            hasher.update(&content);
+            crate::checksum::sha256_hex(hasher)
+        };
+
+        Mock::given(method("GET"))
+            .and(path("/limited.tar.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(content.clone()))
+            .mount(&mock_server)
+            .await;
+
+        let rate: u64 = 64 * 1024;
+        let tmp = TempDir::new().unwrap();
+        let blob_cache = BlobCache::new(tmp.path()).unwrap();
+        let downloader = Downloader::new(blob_cache).with_max_download_rate(rate);
+
+        let url = format!("{}/limited.tar.gz", mock_server.uri());
+        let start = Instant::now();
+        let result = downloader.download(&url, &sha256).await;
+        let elapsed = start.elapsed().as_secs_f64();
+
+        assert!(result.is_ok());
+        let throughput = content.len() as f64 / elapsed;
+        assert!(
+            throughput <= rate as f64 * 1.1,
+            "throughput {throughput:.0} B/s exceeded cap of {rate} B/s"
+        );
+    }
+
+    #[tokio::test]
+    async fn tampered_cached_blob_is_refetched() {
+        let mock_server = MockServer::start().await;
+        let content = b"hello world";
+        let sha256 = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+        Mock::given(method("GET"))
+            .and(path("/test.tar.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(content.to_vec()))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let blob_cache = BlobCache::new(tmp.path()).unwrap();
+
+        let mut writer = blob_cache.start_write(sha256).unwrap();
+        writer.write_all(b"republished upstream").unwrap();
+        writer.commit().unwrap();
+
+        let downloader = Downloader::new(blob_cache);
+        let url = format!("{}/test.tar.gz", mock_server.uri());
+        let path = downloader.download(&url, sha256).await.unwrap();
+
+        assert_eq!(std::fs::read(path).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn refetch_that_still_mismatches_fails_with_checksum_error() {
+        let mock_server = MockServer::start().await;
+        let sha256 = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+        Mock::given(method("GET"))
+            .and(path("/test.tar.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"still wrong".to_vec()))
+            .mount(&mock_server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let blob_cache = BlobCache::new(tmp.path()).unwrap();
+
+        let mut writer = blob_cache.start_write(sha256).unwrap();
+        writer.write_all(b"stale cache").unwrap();
+        writer.commit().unwrap();
+
+        let downloader = Downloader::new(blob_cache);
+        let url = format!("{}/test.tar.gz", mock_server.uri());
+        let err = downloader.download(&url, sha256).await.unwrap_err();
+
+        assert!(matches!(err, Error::ChecksumMismatch { .. }));
+    }
+
+    #[tokio::test]
+    async fn partial_download_from_an_earlier_run_is_resumed() {
+        use wiremock::matchers::header;
+
+        let mock_server = MockServer::start().await;
+        let sha256 = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+        Mock::given(method("GET"))
+            .and(path("/test.tar.gz"))
+            .and(header("range", "bytes=5-"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .insert_header("content-range", "bytes 5-10/11")
+                    .set_body_bytes(b" world".to_vec()),
+            )
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/test.tar.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"hello world".to_vec()))
+            .expect(1..)
+            .mount(&mock_server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let blob_cache = BlobCache::new(tmp.path()).unwrap();
+        {
+            let mut writer = blob_cache.start_partial_write(sha256, false).unwrap();
+            writer.write_all(b"hello").unwrap();
+        }
+
+        let downloader = Downloader::new(blob_cache.clone());
+        let url = format!("{}/test.tar.gz", mock_server.uri());
+        let path = downloader.download(&url, sha256).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello world");
+        assert!(!blob_cache.partial_path(sha256).exists());
+
+        // A partial file that doesn't belong to this blob fails the checksum
+        // once resumed and is replaced by a full download.
+        blob_cache.remove_blob(sha256).unwrap();
+        {
+            let mut writer = blob_cache.start_partial_write(sha256, false).unwrap();
+            writer.write_all(b"HELLO").unwrap();
+        }
+        let path = downloader.download(&url, sha256).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello world");
+        assert!(!blob_cache.partial_path(sha256).exists());
+    }
+
+    #[tokio::test]
+    async fn transient_server_errors_are_retried_before_giving_up() {
+        let mock_server = MockServer::start().await;
+        let sha256 = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+        Mock::given(method("GET"))
+            .and(path("/flaky.tar.gz"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flaky.tar.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"hello world".to_vec()))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let downloader = Downloader::new(BlobCache::new(tmp.path()).unwrap())
+            .with_serial_transfers()
+            .with_retry_policy(RetryPolicy {
+                base_delay: Duration::from_millis(1),
+                ..RetryPolicy::default()
+            });
+
+        let url = format!("{}/flaky.tar.gz", mock_server.uri());
+        let blob = downloader.download(&url, sha256).await.unwrap();
+        assert_eq!(std::fs::read(blob).unwrap(), b"hello world");
+    }
+
+    #[tokio::test]
+    async fn without_retries_a_transient_error_fails_the_download() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/flaky.tar.gz"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let downloader = Downloader::new(BlobCache::new(tmp.path()).unwrap())
+            .with_serial_transfers()
+            .with_retry_policy(RetryPolicy::none());
+
+        let url = format!("{}/flaky.tar.gz", mock_server.uri());
+        let err = downloader.download(&url, "deadbeef").await.unwrap_err();
+        assert!(matches!(err, Error::HttpStatus { status: 503, .. }));
+    }
+
+    #[test]
+    fn mirrors_accept_domains_or_base_urls() {
+        let url = "https://ghcr.io/v2/homebrew/core/jq/blobs/sha256:abc";
+
+        assert_eq!(
+            transform_url_to_mirror(url, "mirror.example").as_deref(),
+            Some("https://mirror.example/v2/homebrew/core/jq/blobs/sha256:abc")
+        );
+        assert_eq!(
+            transform_url_to_mirror(url, "https://cache.example/ghcr/").as_deref(),
+            Some("https://cache.example/ghcr/v2/homebrew/core/jq/blobs/sha256:abc")
+        );
+        assert_eq!(
+            transform_url_to_mirror("https://example.com/jq.tar.gz", "mirror.example"),
+            None
+        );
+    }
+
+    #[test]
+    fn configured_mirrors_keep_their_order_without_duplicates() {
+        let url = "https://ghcr.io/v2/homebrew/core/jq/blobs/sha256:abc";
+        let mirrors = vec![
+            "https://b.example".to_string(),
+            "a.example".to_string(),
+            "https://b.example/".to_string(),
+            "ghcr.io".to_string(),
+        ];
+
+        let alternates = get_alternate_urls(url, &mirrors);
+        assert_eq!(
+            &alternates[..2],
+            [
+                "https://b.example/v2/homebrew/core/jq/blobs/sha256:abc",
+                "https://a.example/v2/homebrew/core/jq/blobs/sha256:abc",
+            ]
+        );
+        assert!(!alternates.iter().any(|alt| alt == url));
+    }
 }
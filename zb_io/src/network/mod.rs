@@ -1,12 +1,20 @@
 pub mod api;
 pub mod cache;
 pub mod download;
+pub mod osv;
+pub(crate) mod proxy;
 pub mod suggest;
 pub mod tap_formula;
 pub(crate) mod tls;
 
 pub use api::ApiClient;
 pub use cache::{ApiCache, CacheEntry};
+#[allow(deprecated)]
+pub use download::ParallelDownloader;
 pub use download::{
-    DownloadProgressCallback, DownloadRequest, DownloadResult, Downloader, ParallelDownloader,
+    DownloadJob, DownloadManager, DownloadProgressCallback, DownloadRequest, DownloadResult,
+    Downloader, RegistryAuth, RegistryCredentials, RetryPolicy, RetryableError,
 };
+pub use osv::{OsvClient, OsvQuery, Severity, Vulnerability};
+pub use proxy::set_proxy;
+pub use tls::configure_tls;
@@ -0,0 +1,421 @@
+use std::fmt;
+
+use serde::Deserialize;
+use serde_json::json;
+use zb_core::Error;
+
+use crate::network::tls::describe_request_error;
+
+/// OSV.dev accepts at most this many queries per batch.
+const MAX_BATCH: usize = 1000;
+
+/// A release of an upstream project to look up, by its git repository,
+/// since Homebrew formulas have no ecosystem of their own in OSV.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OsvQuery {
+    pub repo: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Unknown => "unknown",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+
+    fn from_label(label: &str) -> Self {
+        match label.to_ascii_uppercase().as_str() {
+            "CRITICAL" => Severity::Critical,
+            "HIGH" => Severity::High,
+            "MODERATE" | "MEDIUM" => Severity::Medium,
+            "LOW" => Severity::Low,
+            _ => Severity::Unknown,
+        }
+    }
+
+    fn from_score(score: f64) -> Self {
+        match score {
+            s if s >= 9.0 => Severity::Critical,
+            s if s >= 7.0 => Severity::High,
+            s if s >= 4.0 => Severity::Medium,
+            s if s > 0.0 => Severity::Low,
+            _ => Severity::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vulnerability {
+    pub id: String,
+    pub summary: Option<String>,
+    /// Other identifiers for the same issue, usually the CVE.
+    pub aliases: Vec<String>,
+    pub severity: Severity,
+    /// CVSS v3 base score, when the advisory carries a vector.
+    pub score: Option<f64>,
+    /// Versions or commits the advisory lists as fixed.
+    pub fixed: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResponse {
+    #[serde(default)]
+    results: Vec<BatchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResult {
+    #[serde(default)]
+    vulns: Vec<VulnRef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VulnRef {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct VulnRecord {
+    id: String,
+    #[serde(default)]
+    summary: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    severity: Vec<SeverityRecord>,
+    #[serde(default)]
+    database_specific: Option<serde_json::Value>,
+    #[serde(default)]
+    affected: Vec<AffectedRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SeverityRecord {
+    #[serde(rename = "type")]
+    kind: String,
+    score: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AffectedRecord {
+    #[serde(default)]
+    ranges: Vec<RangeRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RangeRecord {
+    #[serde(default)]
+    events: Vec<serde_json::Value>,
+}
+
+/// Client for the OSV.dev vulnerability database.
+#[derive(Debug, Clone)]
+pub struct OsvClient {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl Default for OsvClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OsvClient {
+    const DEFAULT_BASE_URL: &'static str = "https://api.osv.dev/v1";
+
+    /// Talks to `ZEROBREW_OSV_URL` if set, else OSV.dev.
+    pub fn new() -> Self {
+        let base_url = std::env::var("ZEROBREW_OSV_URL")
+            .unwrap_or_else(|_| Self::DEFAULT_BASE_URL.to_string());
+        Self::with_base_url(base_url)
+    }
+
+    pub fn with_base_url(base_url: String) -> Self {
+        let client = crate::network::proxy::with_proxy(reqwest::Client::builder())
+            .user_agent("zerobrew/0.1")
+            .timeout(std::time::Duration::from_secs(60))
+            .use_preconfigured_tls((*crate::network::tls::shared_tls_config()).clone())
+            .build()
+            .expect("failed to build HTTP client");
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+        }
+    }
+
+    /// The advisory ids affecting each query, in the same order.
+    pub async fn query_batch(&self, queries: &[OsvQuery]) -> Result<Vec<Vec<String>>, Error> {
+        let mut ids = Vec::with_capacity(queries.len());
+        for chunk in queries.chunks(MAX_BATCH) {
+            let body = json!({
+                "queries": chunk
+                    .iter()
+                    .map(|query| json!({
+                        "package": { "name": query.repo, "ecosystem": "GIT" },
+                        "version": query.version,
+                    }))
+                    .collect::<Vec<_>>(),
+            });
+            let response: BatchResponse = self
+                .send(
+                    self.client
+                        .post(format!("{}/querybatch", self.base_url))
+                        .json(&body),
+                )
+                .await?;
+            if response.results.len() != chunk.len() {
+                return Err(Error::NetworkFailure {
+                    message: format!(
+                        "OSV returned {} results for {} queries",
+                        response.results.len(),
+                        chunk.len()
+                    ),
+                });
+            }
+            ids.extend(
+                response
+                    .results
+                    .into_iter()
+                    .map(|result| result.vulns.into_iter().map(|v| v.id).collect()),
+            );
+        }
+        Ok(ids)
+    }
+
+    /// The full advisory behind an id from [`Self::query_batch`].
+    pub async fn vulnerability(&self, id: &str) -> Result<Vulnerability, Error> {
+        let record: VulnRecord = self
+            .send(self.client.get(format!("{}/vulns/{id}", self.base_url)))
+            .await?;
+        Ok(record.into())
+    }
+
+    async fn send<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T, Error> {
+        let response = request.send().await.map_err(|e| Error::NetworkFailure {
+            message: format!("OSV request failed: {}", describe_request_error(&e)),
+        })?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::HttpStatus {
+                status: status.as_u16(),
+                message: format!("OSV returned HTTP {status}"),
+            });
+        }
+        response.json().await.map_err(|e| Error::NetworkFailure {
+            message: format!("failed to parse OSV response: {e}"),
+        })
+    }
+}
+
+impl From<VulnRecord> for Vulnerability {
+    fn from(record: VulnRecord) -> Self {
+        let score = record
+            .severity
+            .iter()
+            .filter(|s| s.kind.starts_with("CVSS_V3"))
+            .find_map(|s| cvss3_base_score(&s.score));
+        let labelled = record
+            .database_specific
+            .as_ref()
+            .and_then(|specific| specific.get("severity"))
+            .and_then(|severity| severity.as_str())
+            .map(Severity::from_label)
+            .unwrap_or(Severity::Unknown);
+        let severity = match score {
+            Some(score) => labelled.max(Severity::from_score(score)),
+            None => labelled,
+        };
+
+        let mut fixed: Vec<String> = record
+            .affected
+            .iter()
+            .flat_map(|affected| &affected.ranges)
+            .flat_map(|range| &range.events)
+            .filter_map(|event| event.get("fixed").and_then(|f| f.as_str()))
+            .map(str::to_string)
+            .collect();
+        fixed.sort();
+        fixed.dedup();
+
+        Self {
+            id: record.id,
+            summary: record.summary,
+            aliases: record.aliases,
+            severity,
+            score,
+            fixed,
+        }
+    }
+}
+
+/// The base score of a CVSS v3.0/v3.1 vector such as
+/// `CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H`.
+fn cvss3_base_score(vector: &str) -> Option<f64> {
+    let mut metrics = std::collections::HashMap::new();
+    for part in vector.strip_prefix("CVSS:3.")?.split('/').skip(1) {
+        let (key, value) = part.split_once(':')?;
+        metrics.insert(key, value);
+    }
+    let changed = match *metrics.get("S")? {
+        "U" => false,
+        "C" => true,
+        _ => return None,
+    };
+    let av = match *metrics.get("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        "P" => 0.2,
+        _ => return None,
+    };
+    let ac = match *metrics.get("AC")? {
+        "L" => 0.77,
+        "H" => 0.44,
+        _ => return None,
+    };
+    let pr = match (*metrics.get("PR")?, changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        ("H", false) => 0.27,
+        ("H", true) => 0.5,
+        _ => return None,
+    };
+    let ui = match *metrics.get("UI")? {
+        "N" => 0.85,
+        "R" => 0.62,
+        _ => return None,
+    };
+    let cia = |key: &str| match *metrics.get(key)? {
+        "H" => Some(0.56),
+        "L" => Some(0.22),
+        "N" => Some(0.0),
+        _ => None,
+    };
+    let iss = 1.0 - (1.0 - cia("C")?) * (1.0 - cia("I")?) * (1.0 - cia("A")?);
+    let impact = if changed {
+        7.52 * (iss - 0.029) - 3.25 * (iss - 0.02f64).powi(15)
+    } else {
+        6.42 * iss
+    };
+    if impact <= 0.0 {
+        return Some(0.0);
+    }
+    let exploitability = 8.22 * av * ac * pr * ui;
+    let base = if changed {
+        (1.08 * (impact + exploitability)).min(10.0)
+    } else {
+        (impact + exploitability).min(10.0)
+    };
+    Some(round_up(base))
+}
+
+/// CVSS v3.1's "round up to one decimal", which avoids floating point
+/// artefacts like 4.000000001 becoming 4.1.
+fn round_up(value: f64) -> f64 {
+    let scaled = (value * 100_000.0).round() as i64;
+    if scaled % 10_000 == 0 {
+        scaled as f64 / 100_000.0
+    } else {
+        ((scaled / 10_000) + 1) as f64 / 10.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn scores_cvss3_vectors() {
+        assert_eq!(
+            cvss3_base_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"),
+            Some(9.8)
+        );
+        assert_eq!(
+            cvss3_base_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:C/C:H/I:H/A:H"),
+            Some(10.0)
+        );
+        assert_eq!(
+            cvss3_base_score("CVSS:3.0/AV:L/AC:L/PR:L/UI:N/S:U/C:H/I:N/A:N"),
+            Some(5.5)
+        );
+        assert_eq!(
+            cvss3_base_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:N"),
+            Some(0.0)
+        );
+        assert_eq!(cvss3_base_score("AV:N/AC:L/Au:N/C:P/I:P/A:P"), None);
+    }
+
+    #[tokio::test]
+    async fn batches_queries_and_reads_advisories() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/querybatch"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{"results": [{"vulns": [{"id": "OSV-1", "modified": "2026-01-01T00:00:00Z"}]}, {}]}"#,
+            ))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/vulns/OSV-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{
+                    "id": "OSV-1",
+                    "summary": "heap overflow",
+                    "aliases": ["CVE-2026-0001"],
+                    "severity": [{"type": "CVSS_V3", "score": "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"}],
+                    "affected": [{"ranges": [{"type": "GIT", "events": [{"introduced": "0"}, {"fixed": "abc123"}]}]}]
+                }"#,
+            ))
+            .mount(&mock_server)
+            .await;
+
+        let client = OsvClient::with_base_url(mock_server.uri());
+        let ids = client
+            .query_batch(&[
+                OsvQuery {
+                    repo: "https://github.com/example/lib".to_string(),
+                    version: "1.0.0".to_string(),
+                },
+                OsvQuery {
+                    repo: "https://github.com/example/safe".to_string(),
+                    version: "2.0.0".to_string(),
+                },
+            ])
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![vec!["OSV-1".to_string()], Vec::new()]);
+
+        let vuln = client.vulnerability("OSV-1").await.unwrap();
+        assert_eq!(vuln.severity, Severity::Critical);
+        assert_eq!(vuln.score, Some(9.8));
+        assert_eq!(vuln.aliases, vec!["CVE-2026-0001"]);
+        assert_eq!(vuln.fixed, vec!["abc123"]);
+    }
+}
@@ -0,0 +1,27 @@
+use std::sync::OnceLock;
+
+use zb_core::Error;
+
+static PROXY: OnceLock<reqwest::Proxy> = OnceLock::new();
+
+/// Send every request this process makes through the proxy at `url`, in
+/// place of whatever `HTTPS_PROXY` or `ALL_PROXY` name. Hosts listed in
+/// `NO_PROXY` are still reached directly. Only the first call takes effect,
+/// since clients built before a later call couldn't honor it.
+pub fn set_proxy(url: &str) -> Result<(), Error> {
+    let proxy = reqwest::Proxy::all(url)
+        .map_err(|e| Error::InvalidArgument {
+            message: format!("invalid proxy '{url}': {e}"),
+        })?
+        .no_proxy(reqwest::NoProxy::from_env());
+    let _ = PROXY.set(proxy);
+    Ok(())
+}
+
+/// Apply the proxy set by `set_proxy`, if any, to a client being built.
+pub(crate) fn with_proxy(builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+    match PROXY.get() {
+        Some(proxy) => builder.proxy(proxy.clone()),
+        None => builder,
+    }
+}
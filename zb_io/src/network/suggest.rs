@@ -74,6 +74,106 @@ where
     scored.into_iter().take(limit).map(|s| s.name).collect()
 }
 
+/// Stricter than `MIN_SIMILARITY_SCORE`: search returns every match rather
+/// than a handful of suggestions, so loose fuzzy hits would flood the output.
+const MIN_SEARCH_SIMILARITY: f64 = 0.6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchKind {
+    Exact,
+    Substring,
+    Fuzzy,
+    /// The name didn't match but the formula's description contains the query.
+    Description,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchMatch {
+    pub name: String,
+    pub kind: MatchKind,
+    score: f64,
+}
+
+/// Match `query` against `candidates`, returning exact, substring and fuzzy
+/// hits ranked in that order. Fuzzy hits are either close by edit distance
+/// or contain the query as a subsequence (`rgrp` → `ripgrep`).
+pub fn search_formula_names(query: &str, candidates: &[String]) -> Vec<SearchMatch> {
+    let query = query.trim().to_ascii_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut matches: Vec<SearchMatch> = candidates
+        .iter()
+        .filter_map(|candidate| {
+            let normalized = candidate.trim().to_ascii_lowercase();
+            if normalized.is_empty() {
+                return None;
+            }
+
+            let (kind, score) = if normalized == query {
+                (MatchKind::Exact, 1.0)
+            } else if let Some(pos) = normalized.find(&query) {
+                // Prefer prefix hits and tighter matches.
+                let coverage = query.len() as f64 / normalized.len() as f64;
+                let prefix_bonus = if pos == 0 { 1.0 } else { 0.0 };
+                (MatchKind::Substring, prefix_bonus + coverage)
+            } else {
+                (MatchKind::Fuzzy, fuzzy_score(&query, &normalized)?)
+            };
+
+            Some(SearchMatch {
+                name: candidate.clone(),
+                kind,
+                score,
+            })
+        })
+        .collect();
+
+    matches.sort_by(|a, b| {
+        a.kind
+            .cmp(&b.kind)
+            .then_with(|| {
+                b.score
+                    .partial_cmp(&a.score)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    matches
+}
+
+fn fuzzy_score(query: &str, candidate: &str) -> Option<f64> {
+    let mut best: Option<f64> = None;
+
+    if is_plausible_candidate(query, candidate) {
+        let distance = damerau_levenshtein(query, candidate);
+        let max_len = query.len().max(candidate.len());
+        let similarity = 1.0 - (distance as f64 / max_len as f64);
+        if similarity >= MIN_SEARCH_SIMILARITY {
+            best = Some(similarity);
+        }
+    }
+
+    // Short queries are a subsequence of far too many names to be useful.
+    if query.len() >= 3
+        && candidate.len() <= query.len() * 2
+        && query.chars().next() == candidate.chars().next()
+        && is_subsequence(query, candidate)
+    {
+        let coverage = query.len() as f64 / candidate.len() as f64;
+        best = Some(best.map_or(coverage, |b| b.max(coverage)));
+    }
+
+    best
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
 fn is_plausible_candidate(query: &str, candidate: &str) -> bool {
     query.len().abs_diff(candidate.len()) <= max_len_delta(query.len())
 }
@@ -88,7 +188,14 @@ mod tests {
 
     use strsim::damerau_levenshtein;
 
-    use super::{max_len_delta, rank_formula_suggestions, rank_formula_suggestions_with};
+    use super::{
+        MatchKind, max_len_delta, rank_formula_suggestions, rank_formula_suggestions_with,
+        search_formula_names,
+    };
+
+    fn names(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
 
     #[test]
     fn ranks_common_typo_as_top_match() {
@@ -166,4 +273,47 @@ mod tests {
 
         assert!(!suggestions.iter().any(|candidate| candidate == "git"));
     }
+
+    #[test]
+    fn search_ranks_exact_match_first() {
+        let candidates = names(&["jqp", "jq", "gojq"]);
+
+        let matches = search_formula_names("jq", &candidates);
+
+        assert_eq!(matches[0].name, "jq");
+        assert_eq!(matches[0].kind, MatchKind::Exact);
+    }
+
+    #[test]
+    fn search_finds_substring_matches() {
+        let candidates = names(&["ripgrep", "grep", "wget"]);
+
+        let matches = search_formula_names("grep", &candidates);
+        let found: Vec<_> = matches.iter().map(|m| m.name.as_str()).collect();
+
+        assert_eq!(found, vec!["grep", "ripgrep"]);
+        assert_eq!(matches[1].kind, MatchKind::Substring);
+    }
+
+    #[test]
+    fn search_finds_fuzzy_matches() {
+        let candidates = names(&["jq", "wget", "ripgrep"]);
+
+        let matches = search_formula_names("jqq", &candidates);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].name, "jq");
+        assert_eq!(matches[0].kind, MatchKind::Fuzzy);
+
+        let matches = search_formula_names("rgrp", &candidates);
+        assert_eq!(matches.first().map(|m| m.name.as_str()), Some("ripgrep"));
+    }
+
+    #[test]
+    fn search_fuzzy_threshold_skips_unrelated_names() {
+        let candidates = names(&["git", "go", "gnupg", "jq", "zstd"]);
+
+        let matches = search_formula_names("gx", &candidates);
+
+        assert!(matches.is_empty());
+    }
 }
@@ -2,7 +2,7 @@ use regex::Regex;
 use std::collections::BTreeMap;
 use std::sync::LazyLock;
 use zb_core::formula::{
-    Bottle, BottleFile, BottleStable, FormulaUrls, KegOnly, SourceUrl, Versions,
+    Bottle, BottleFile, BottleStable, FormulaUrls, KegOnly, SourceUrl, UsesFromMacos, Versions,
 };
 use zb_core::{Error, Formula};
 
@@ -28,9 +28,31 @@ static REVISION_RE: LazyLock<Regex> = LazyLock::new(|| {
 static DEPENDS_ON_RE: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r#"(?m)^\s*depends_on\s+["']([^"']+)["'](.*)$"#).expect("DEPENDS_ON_RE must compile")
 });
+static CAVEATS_DEF_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^def\s+caveats\s*$").expect("CAVEATS_DEF_RE must compile"));
+static HEREDOC_START_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^<<([~-]?)(["']?)([A-Z_]+)["']?$"#).expect("HEREDOC_START_RE must compile")
+});
+static POST_INSTALL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*def\s+post_install\b").expect("POST_INSTALL_RE must compile")
+});
+static USES_FROM_MACOS_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?m)^\s*uses_from_macos\s+["']([^"']+)["'](.*)$"#)
+        .expect("USES_FROM_MACOS_RE must compile")
+});
+static CONFLICTS_WITH_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^conflicts_with\s+(.*)$"#).expect("CONFLICTS_WITH_RE must compile")
+});
+static QUOTED_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"["']([^"']+)["']"#).expect("QUOTED_RE must compile"));
+static BECAUSE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"because:\s*"([^"]*)""#).expect("BECAUSE_RE must compile"));
 static SOURCE_URL_RE: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r#"(?m)^\s*url\s+["']([^"']+)["']"#).expect("SOURCE_URL_RE must compile")
 });
+static SOURCE_MIRROR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?m)^\s*mirror\s+["']([^"']+)["']"#).expect("SOURCE_MIRROR_RE must compile")
+});
 static SOURCE_SHA_RE: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r#"(?m)^\s*sha256\s+["']([0-9a-f]{64})["']\s*$"#)
         .expect("SOURCE_SHA_RE must compile")
@@ -343,6 +365,8 @@ pub fn parse_tap_formula_ruby(spec: &TapFormulaRef, source: &str) -> Result<Form
     let revision = parse_revision(&source).unwrap_or(0);
     let dependencies = parse_runtime_dependencies(&source);
     let build_dependencies = parse_build_dependencies(&source);
+    let uses_from_macos = parse_uses_from_macos(&source);
+    let (conflicts_with, conflicts_with_reasons) = parse_conflicts_with(&source);
     let parsed_source_url = parse_source_url(&source);
     let bottle = parse_bottle(spec, &source, &stable, revision);
 
@@ -376,15 +400,26 @@ pub fn parse_tap_formula_ruby(spec: &TapFormulaRef, source: &str) -> Result<Form
         keg_only: KegOnly::default(),
         keg_only_reason: None,
         build_dependencies,
+        test_dependencies: Vec::new(),
+        recommended_dependencies: Vec::new(),
+        optional_dependencies: Vec::new(),
         urls: source_url.map(|stable| FormulaUrls {
             stable: Some(stable),
             head: None,
         }),
         ruby_source_path: None,
         ruby_source_checksum: None,
-        uses_from_macos: Vec::new(),
+        uses_from_macos,
         requirements: Vec::new(),
         variations: None,
+        caveats: parse_caveats(&source, &spec.formula),
+        conflicts_with,
+        conflicts_with_reasons,
+        service: None,
+        desc: None,
+        homepage: None,
+        license: None,
+        post_install_defined: POST_INSTALL_RE.is_match(&source),
     })
 }
 
@@ -472,6 +507,128 @@ fn parse_build_dependencies(source: &str) -> Vec<String> {
     deps
 }
 
+/// Top-level `uses_from_macos` lines, which are real dependencies on Linux.
+/// `=> :build` and `=> :test` keep their context; `since:` bounds only
+/// matter on older macOS and are ignored.
+fn parse_uses_from_macos(source: &str) -> Vec<UsesFromMacos> {
+    let mut deps = Vec::new();
+    let body = extract_formula_class_body(source).unwrap_or(source);
+    let mut depth = 0usize;
+
+    for line in body.lines() {
+        let trimmed = line.trim();
+        if depth == 0
+            && let Some(cap) = USES_FROM_MACOS_RE.captures(trimmed)
+            && let Some(dep) = cap.get(1)
+        {
+            let options = cap.get(2).map(|m| m.as_str()).unwrap_or("");
+            let name = dep.as_str().to_string();
+            let dep = if options.contains(":build") {
+                UsesFromMacos::WithContext {
+                    name,
+                    context: "build".to_string(),
+                }
+            } else if options.contains(":test") {
+                UsesFromMacos::WithContext {
+                    name,
+                    context: "test".to_string(),
+                }
+            } else {
+                UsesFromMacos::Plain(name)
+            };
+            if !deps.contains(&dep) {
+                deps.push(dep);
+            }
+        }
+        update_depth(&mut depth, trimmed);
+    }
+
+    deps
+}
+
+/// Top-level `conflicts_with "a", "b", because: "..."` lines, as the
+/// conflicting names and a reason for each in the same order.
+fn parse_conflicts_with(source: &str) -> (Vec<String>, Vec<String>) {
+    let mut names = Vec::new();
+    let mut reasons = Vec::new();
+    let body = extract_formula_class_body(source).unwrap_or(source);
+    let mut depth = 0usize;
+
+    for line in body.lines() {
+        let trimmed = line.trim();
+        if depth == 0
+            && let Some(cap) = CONFLICTS_WITH_RE.captures(trimmed)
+            && let Some(args) = cap.get(1)
+        {
+            let args = args.as_str();
+            let (listed, reason) = match BECAUSE_RE.captures(args) {
+                Some(because) => (
+                    &args[..because.get(0).map_or(args.len(), |m| m.start())],
+                    because.get(1).map_or("", |m| m.as_str()),
+                ),
+                None => (args, ""),
+            };
+            for name in QUOTED_RE.captures_iter(listed).filter_map(|c| c.get(1)) {
+                let name = name.as_str().to_string();
+                if !names.contains(&name) {
+                    names.push(name);
+                    reasons.push(reason.to_string());
+                }
+            }
+        }
+        update_depth(&mut depth, trimmed);
+    }
+
+    (names, reasons)
+}
+
+/// The heredoc a top-level `def caveats` returns, the common shape in taps.
+/// The prefix and `opt_prefix` interpolations are turned into the
+/// `$HOMEBREW_PREFIX` form the API uses, so they render the same way.
+/// Caveats computed any other way are left out.
+fn parse_caveats(source: &str, name: &str) -> Option<String> {
+    let body = extract_formula_class_body(source).unwrap_or(source);
+    let mut lines = body.lines();
+    let mut depth = 0usize;
+    loop {
+        let trimmed = lines.next()?.trim();
+        if depth == 0 && CAVEATS_DEF_RE.is_match(trimmed) {
+            break;
+        }
+        update_depth(&mut depth, trimmed);
+    }
+
+    let start = lines.by_ref().map(str::trim).find(|l| !l.is_empty())?;
+    let cap = HEREDOC_START_RE.captures(start)?;
+    let squiggly = &cap[1] == "~";
+    let terminator = cap.get(3)?.as_str();
+
+    let heredoc: Vec<&str> = lines
+        .by_ref()
+        .take_while(|line| line.trim() != terminator)
+        .collect();
+    let indent = if squiggly {
+        heredoc
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0)
+    } else {
+        0
+    };
+
+    let text = heredoc
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or("").trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .replace("#{HOMEBREW_PREFIX}", "$HOMEBREW_PREFIX")
+        .replace("#{opt_prefix}", &format!("$HOMEBREW_PREFIX/opt/{name}"));
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
 enum ParsedSourceUrl {
     NotPresent,
     PresentMissingChecksum,
@@ -482,6 +639,7 @@ fn parse_source_url(source: &str) -> ParsedSourceUrl {
     let body = extract_formula_class_body(source).unwrap_or(source);
     let mut depth = 0usize;
     let mut url: Option<String> = None;
+    let mut mirrors: Vec<String> = Vec::new();
     let mut checksum: Option<String> = None;
 
     for line in body.lines() {
@@ -494,12 +652,17 @@ fn parse_source_url(source: &str) -> ParsedSourceUrl {
                 url = cap.get(1).map(|m| m.as_str().to_string());
             }
 
+            if let Some(cap) = SOURCE_MIRROR_RE.captures(trimmed) {
+                mirrors.extend(cap.get(1).map(|m| m.as_str().to_string()));
+            }
+
             if checksum.is_none()
                 && let Some(cap) = SOURCE_SHA_RE.captures(trimmed)
             {
                 checksum = cap.get(1).map(|m| m.as_str().to_string());
             }
 
+            // `mirror` lines sit between `url` and `sha256` in the stable block.
             if url.is_some() && checksum.is_some() {
                 break;
             }
@@ -514,6 +677,7 @@ fn parse_source_url(source: &str) -> ParsedSourceUrl {
             checksum: Some(checksum),
             tag: None,
             revision: None,
+            mirrors,
         }),
         (Some(_), None) => ParsedSourceUrl::PresentMissingChecksum,
         _ => ParsedSourceUrl::NotPresent,
@@ -1032,6 +1196,116 @@ end
         );
     }
 
+    #[test]
+    fn parses_heredoc_caveats() {
+        let source = r#"
+class Example < Formula
+  url "https://example.com/example-1.0.0.tar.gz"
+  sha256 "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+
+  def install
+    bin.install "example"
+  end
+
+  def caveats
+    <<~EOS
+      Add the completions to your shell:
+        source #{opt_prefix}/etc/example.sh
+
+      Config lives in #{HOMEBREW_PREFIX}/etc/example.
+    EOS
+  end
+end
+"#;
+
+        let spec = TapFormulaRef {
+            owner: "someone".to_string(),
+            repo: "tap".to_string(),
+            formula: "example".to_string(),
+        };
+
+        let formula = parse_tap_formula_ruby(&spec, source).unwrap();
+        assert_eq!(
+            formula.caveats.as_deref(),
+            Some(
+                "Add the completions to your shell:\n  source $HOMEBREW_PREFIX/opt/example/etc/example.sh\n\nConfig lives in $HOMEBREW_PREFIX/etc/example."
+            )
+        );
+    }
+
+    #[test]
+    fn parses_conflicts_with_and_reasons() {
+        let source = r#"
+class Example < Formula
+  url "https://example.com/example-1.0.0.tar.gz"
+  sha256 "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+
+  conflicts_with "mariadb", "percona-server", because: "both install the same binaries"
+  conflicts_with "other-example"
+
+  def install
+    bin.install "example"
+  end
+end
+"#;
+
+        let (names, reasons) = parse_conflicts_with(source);
+        assert_eq!(names, vec!["mariadb", "percona-server", "other-example"]);
+        assert_eq!(
+            reasons,
+            vec![
+                "both install the same binaries",
+                "both install the same binaries",
+                ""
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_uses_from_macos_with_context() {
+        let source = r#"
+class Example < Formula
+  url "https://example.com/example-1.0.0.tar.gz"
+  sha256 "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+  depends_on "openssl@3"
+  uses_from_macos "zlib"
+  uses_from_macos "curl", since: :monterey
+  uses_from_macos "bison" => :build
+
+  on_linux do
+    uses_from_macos "ignored"
+  end
+end
+"#;
+
+        let spec = TapFormulaRef {
+            owner: "someone".to_string(),
+            repo: "tap".to_string(),
+            formula: "example".to_string(),
+        };
+
+        let formula = parse_tap_formula_ruby(&spec, source).unwrap();
+        assert_eq!(
+            formula.uses_from_macos,
+            vec![
+                UsesFromMacos::Plain("zlib".to_string()),
+                UsesFromMacos::Plain("curl".to_string()),
+                UsesFromMacos::WithContext {
+                    name: "bison".to_string(),
+                    context: "build".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            formula.runtime_dependencies_for(zb_core::TargetOs::Linux),
+            vec!["openssl@3", "zlib", "curl"]
+        );
+        assert_eq!(
+            formula.runtime_dependencies_for(zb_core::TargetOs::MacOs),
+            vec!["openssl@3"]
+        );
+    }
+
     #[test]
     fn returns_unsupported_formula_when_neither_bottle_nor_source_is_available() {
         let source = r#"
@@ -1,7 +1,13 @@
+use std::path::Path;
 use std::sync::{Arc, OnceLock};
 
-use rustls::pki_types::CertificateDer;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
 use tracing::warn;
+use zb_core::Error;
 
 static SHARED_TLS_CONFIG: OnceLock<Arc<rustls::ClientConfig>> = OnceLock::new();
 
@@ -14,11 +20,44 @@ static SHARED_TLS_CONFIG: OnceLock<Arc<rustls::ClientConfig>> = OnceLock::new();
 /// none exist).
 pub(crate) fn shared_tls_config() -> Arc<rustls::ClientConfig> {
     SHARED_TLS_CONFIG
-        .get_or_init(|| Arc::new(build_rustls_config()))
+        .get_or_init(|| Arc::new(build_rustls_config(Vec::new())))
         .clone()
 }
 
-fn build_rustls_config() -> rustls::ClientConfig {
+/// Also trust the PEM certificates in `ca_bundle`, such as the root of a
+/// TLS-intercepting corporate proxy, or with `insecure` skip certificate
+/// verification entirely. Must be called before any client is built; only
+/// the first call takes effect.
+pub fn configure_tls(ca_bundle: Option<&Path>, insecure: bool) -> Result<(), Error> {
+    let config = if insecure {
+        warn!("TLS certificate verification is disabled");
+        build_insecure_config()
+    } else {
+        let extra_roots = match ca_bundle {
+            Some(path) => load_ca_bundle(path)?,
+            None => Vec::new(),
+        };
+        build_rustls_config(extra_roots)
+    };
+    let _ = SHARED_TLS_CONFIG.set(Arc::new(config));
+    Ok(())
+}
+
+fn load_ca_bundle(path: &Path) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let invalid = |e: &dyn std::fmt::Display| Error::InvalidArgument {
+        message: format!("invalid CA bundle '{}': {e}", path.display()),
+    };
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(|e| invalid(&e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| invalid(&e))?;
+    if certs.is_empty() {
+        return Err(invalid(&"no PEM certificates found"));
+    }
+    Ok(certs)
+}
+
+fn build_rustls_config(extra_roots: Vec<CertificateDer<'static>>) -> rustls::ClientConfig {
     let provider = rustls::crypto::aws_lc_rs::default_provider();
 
     let cert_result = rustls_native_certs::load_native_certs();
@@ -37,7 +76,7 @@ fn build_rustls_config() -> rustls::ClientConfig {
         );
     }
 
-    let root_store = assemble_root_store(cert_result.certs);
+    let root_store = assemble_root_store(cert_result.certs, extra_roots);
 
     rustls::ClientConfig::builder_with_provider(provider.into())
         .with_safe_default_protocol_versions()
@@ -49,10 +88,96 @@ fn build_rustls_config() -> rustls::ClientConfig {
         .with_no_client_auth()
 }
 
+fn build_insecure_config() -> rustls::ClientConfig {
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+
+    rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .expect("aws-lc-rs provider supports TLS 1.2 and 1.3")
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(NoCertificateVerification(provider)))
+        .with_no_client_auth()
+}
+
+/// Accepts any server certificate. Handshake signatures are still checked,
+/// so this only gives up on knowing who the server is.
+#[derive(Debug)]
+struct NoCertificateVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoCertificateVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Render a request error with its whole cause chain; reqwest's own message
+/// stops at "error sending request", hiding e.g. an untrusted certificate.
+pub(crate) fn describe_request_error(err: &reqwest::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        let cause_message = cause.to_string();
+        if !message.contains(&cause_message) {
+            message.push_str(": ");
+            message.push_str(&cause_message);
+        }
+        source = cause.source();
+    }
+
+    if message.contains("certificate") || message.contains("UnknownIssuer") {
+        message.push_str(
+            " (if a proxy intercepts TLS on this network, pass its CA certificate with --ca-bundle)",
+        );
+    }
+    message
+}
+
 /// Assemble a trust store from the system's native certs, falling back to the
 /// bundled Mozilla roots (`webpki-roots`) when no native roots are available
 /// (e.g. inside a packaging/build sandbox). The returned store is never empty.
-fn assemble_root_store(native_certs: Vec<CertificateDer<'static>>) -> rustls::RootCertStore {
+fn assemble_root_store(
+    native_certs: Vec<CertificateDer<'static>>,
+    extra_roots: Vec<CertificateDer<'static>>,
+) -> rustls::RootCertStore {
     let mut root_store = rustls::RootCertStore::empty();
 
     for cert in native_certs {
@@ -64,6 +189,12 @@ fn assemble_root_store(native_certs: Vec<CertificateDer<'static>>) -> rustls::Ro
         root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
     }
 
+    for cert in extra_roots {
+        if let Err(e) = root_store.add(cert) {
+            warn!(error = %e, "skipping unusable certificate in CA bundle");
+        }
+    }
+
     root_store
 }
 
@@ -73,7 +204,8 @@ mod tests {
 
     #[test]
     fn build_rustls_config_does_not_panic() {
-        let _ = build_rustls_config();
+        let _ = build_rustls_config(Vec::new());
+        let _ = build_insecure_config();
     }
 
     #[test]
@@ -81,7 +213,19 @@ mod tests {
         // Simulates a sandbox with no system trust store: the webpki-roots
         // fallback must still produce a non-empty trust store. This is the
         // regression guard for the "No CA certificates were loaded" panic.
-        let store = assemble_root_store(Vec::new());
+        let store = assemble_root_store(Vec::new(), Vec::new());
         assert!(!store.roots.is_empty());
     }
+
+    #[test]
+    fn ca_bundle_without_certificates_is_rejected() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("ca.pem");
+        std::fs::write(&path, "not a certificate\n").unwrap();
+        assert!(matches!(
+            load_ca_bundle(&path),
+            Err(Error::InvalidArgument { .. })
+        ));
+        assert!(load_ca_bundle(&tmp.path().join("missing.pem")).is_err());
+    }
 }
@@ -1,6 +1,18 @@
-/// Progress events during installation
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+
+/// Progress events during installation. New events may be added, so
+/// matches outside this crate need a wildcard arm.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum InstallProgress {
+    /// Resolving the dependency closure of the named formulas
+    ResolveStarted { names: Vec<String> },
+    /// Resolution finished with `count` formulas planned
+    ResolveCompleted { count: usize },
+    /// Another zerobrew process holds the install lock; waiting for it
+    WaitingForLock { holder: Option<u32> },
     /// Starting to download a package (with total size if known)
     DownloadStarted {
         name: String,
@@ -30,3 +42,43 @@ pub enum InstallProgress {
 
 /// Callback type for progress reporting
 pub type ProgressCallback = Box<dyn Fn(InstallProgress) + Send + Sync>;
+
+/// A progress callback that forwards every event into a channel, for
+/// consumers that would rather poll or `await` events than handle them
+/// inline on the installer's task.
+pub fn progress_channel() -> (
+    Arc<ProgressCallback>,
+    mpsc::UnboundedReceiver<InstallProgress>,
+) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let callback: ProgressCallback = Box::new(move |event| {
+        // A dropped receiver just means nobody is listening any more.
+        let _ = tx.send(event);
+    });
+    (Arc::new(callback), rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_channel_forwards_events_in_order() {
+        let (callback, mut rx) = progress_channel();
+        callback(InstallProgress::ResolveStarted {
+            names: vec!["jq".to_string()],
+        });
+        callback(InstallProgress::ResolveCompleted { count: 2 });
+        drop(callback);
+
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(InstallProgress::ResolveStarted { names }) if names == ["jq"]
+        ));
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(InstallProgress::ResolveCompleted { count: 2 })
+        ));
+        assert!(rx.try_recv().is_err());
+    }
+}
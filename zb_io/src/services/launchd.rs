@@ -0,0 +1,119 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use zb_core::{RestartPolicy, ServiceSpec};
+
+/// A per-user launch agent for `spec`, loaded at login.
+pub fn render_plist(spec: &ServiceSpec) -> String {
+    let mut plist = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n<dict>\n",
+    );
+
+    push_string(&mut plist, "Label", &spec.label);
+    plist.push_str("\t<key>ProgramArguments</key>\n\t<array>\n");
+    for arg in &spec.program_args {
+        let _ = writeln!(plist, "\t\t<string>{}</string>", escape(arg));
+    }
+    plist.push_str("\t</array>\n");
+    plist.push_str("\t<key>RunAtLoad</key>\n\t<true/>\n");
+
+    match spec.restart {
+        RestartPolicy::Never => {}
+        RestartPolicy::Always => plist.push_str("\t<key>KeepAlive</key>\n\t<true/>\n"),
+        RestartPolicy::OnFailure => plist.push_str(
+            "\t<key>KeepAlive</key>\n\t<dict>\n\t\t<key>SuccessfulExit</key>\n\t\t<false/>\n\t</dict>\n",
+        ),
+        RestartPolicy::OnSuccess => plist.push_str(
+            "\t<key>KeepAlive</key>\n\t<dict>\n\t\t<key>SuccessfulExit</key>\n\t\t<true/>\n\t</dict>\n",
+        ),
+    }
+
+    if let Some(interval) = spec.interval {
+        let _ = writeln!(
+            plist,
+            "\t<key>StartInterval</key>\n\t<integer>{interval}</integer>"
+        );
+    }
+    if let Some(dir) = &spec.working_dir {
+        push_path(&mut plist, "WorkingDirectory", dir);
+    }
+    if let Some(path) = &spec.log_path {
+        push_path(&mut plist, "StandardOutPath", path);
+    }
+    if let Some(path) = &spec.error_log_path {
+        push_path(&mut plist, "StandardErrorPath", path);
+    }
+    if !spec.environment.is_empty() {
+        plist.push_str("\t<key>EnvironmentVariables</key>\n\t<dict>\n");
+        for (key, value) in &spec.environment {
+            let _ = writeln!(
+                plist,
+                "\t\t<key>{}</key>\n\t\t<string>{}</string>",
+                escape(key),
+                escape(value)
+            );
+        }
+        plist.push_str("\t</dict>\n");
+    }
+
+    plist.push_str("</dict>\n</plist>\n");
+    plist
+}
+
+fn push_string(plist: &mut String, key: &str, value: &str) {
+    let _ = writeln!(
+        plist,
+        "\t<key>{key}</key>\n\t<string>{}</string>",
+        escape(value)
+    );
+}
+
+fn push_path(plist: &mut String, key: &str, path: &Path) {
+    push_string(plist, key, &path.display().to_string());
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn plist_carries_the_whole_spec() {
+        let spec = ServiceSpec {
+            label: "zerobrew.redis".to_string(),
+            program_args: vec![
+                "/opt/zerobrew/opt/redis/bin/redis-server".to_string(),
+                "/opt/zerobrew/etc/redis.conf".to_string(),
+            ],
+            interval: None,
+            restart: RestartPolicy::OnFailure,
+            working_dir: Some(PathBuf::from("/opt/zerobrew/var")),
+            log_path: Some(PathBuf::from("/opt/zerobrew/var/log/redis.log")),
+            error_log_path: None,
+            environment: BTreeMap::from([("GREETING".to_string(), "a<b & c".to_string())]),
+        };
+
+        let plist = render_plist(&spec);
+        assert!(plist.contains("\t<key>Label</key>\n\t<string>zerobrew.redis</string>\n"));
+        assert!(plist.contains(
+            "\t<array>\n\t\t<string>/opt/zerobrew/opt/redis/bin/redis-server</string>\n\
+             \t\t<string>/opt/zerobrew/etc/redis.conf</string>\n\t</array>\n"
+        ));
+        assert!(plist.contains("<key>SuccessfulExit</key>\n\t\t<false/>"));
+        assert!(plist.contains("<string>/opt/zerobrew/var/log/redis.log</string>"));
+        assert!(!plist.contains("StandardErrorPath"));
+        assert!(plist.contains("<string>a&lt;b &amp; c</string>"));
+        assert!(plist.ends_with("</dict>\n</plist>\n"));
+    }
+}
@@ -0,0 +1,238 @@
+pub mod launchd;
+pub mod systemd;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use zb_core::{Error, ServiceSpec};
+
+/// Installs per-user service definitions and drives them through launchd on
+/// macOS and `systemctl --user` elsewhere.
+pub struct ServiceManager {
+    dir: PathBuf,
+}
+
+impl ServiceManager {
+    /// Manage services in the directory the host's service manager loads
+    /// the user's definitions from: `~/Library/LaunchAgents` or
+    /// `$XDG_CONFIG_HOME/systemd/user`.
+    pub fn for_user() -> Result<Self, Error> {
+        let home = std::env::var_os("HOME")
+            .filter(|home| !home.is_empty())
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::InvalidArgument {
+                message: "HOME is not set, so there is nowhere to put service definitions"
+                    .to_string(),
+            })?;
+
+        #[cfg(target_os = "macos")]
+        let dir = home.join("Library/LaunchAgents");
+        #[cfg(not(target_os = "macos"))]
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".config"))
+            .join("systemd/user");
+
+        Ok(Self::new(dir))
+    }
+
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The file that defines the service labelled `label`.
+    pub fn definition_path(&self, label: &str) -> PathBuf {
+        if cfg!(target_os = "macos") {
+            self.dir.join(format!("{label}.plist"))
+        } else {
+            self.dir.join(format!("{label}.service"))
+        }
+    }
+
+    fn timer_path(&self, label: &str) -> PathBuf {
+        self.dir.join(format!("{label}.timer"))
+    }
+
+    /// Write `spec`'s definition (plus a timer for interval services under
+    /// systemd) and create its log directories, returning the definition's
+    /// path.
+    pub fn write(&self, spec: &ServiceSpec) -> Result<PathBuf, Error> {
+        fs::create_dir_all(&self.dir).map_err(Error::file("failed to create service directory"))?;
+
+        let path = self.definition_path(&spec.label);
+        if cfg!(target_os = "macos") {
+            fs::write(&path, launchd::render_plist(spec))
+        } else {
+            fs::write(&path, systemd::render_unit(spec))
+        }
+        .map_err(Error::file("failed to write service definition"))?;
+
+        let timer = self.timer_path(&spec.label);
+        match systemd::render_timer(spec).filter(|_| !cfg!(target_os = "macos")) {
+            Some(contents) => {
+                fs::write(&timer, contents).map_err(Error::file("failed to write service timer"))?
+            }
+            None => remove_if_present(&timer)?,
+        }
+
+        for log in [&spec.log_path, &spec.error_log_path].into_iter().flatten() {
+            if let Some(parent) = log.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(Error::file("failed to create service log directory"))?;
+            }
+        }
+
+        Ok(path)
+    }
+
+    /// Write `spec`'s definition and have the service manager start it, now
+    /// and at every login.
+    pub fn start(&self, spec: &ServiceSpec) -> Result<PathBuf, Error> {
+        let path = self.write(spec)?;
+
+        if cfg!(target_os = "macos") {
+            let domain = launchd_domain();
+            if self.is_active(&spec.label) {
+                run(
+                    "launchctl",
+                    &["bootout", &format!("{domain}/{}", spec.label)],
+                )?;
+            }
+            run(
+                "launchctl",
+                &["bootstrap", &domain, &path.display().to_string()],
+            )?;
+        } else {
+            run("systemctl", &["--user", "daemon-reload"])?;
+            run(
+                "systemctl",
+                &["--user", "enable", "--now", &self.systemd_unit(&spec.label)],
+            )?;
+        }
+
+        Ok(path)
+    }
+
+    /// Stop the service labelled `label` and remove its definition so it
+    /// doesn't come back at the next login.
+    pub fn stop(&self, label: &str) -> Result<(), Error> {
+        if cfg!(target_os = "macos") {
+            if self.is_active(label) {
+                run(
+                    "launchctl",
+                    &["bootout", &format!("{}/{label}", launchd_domain())],
+                )?;
+            }
+        } else if self.definition_path(label).exists() {
+            run(
+                "systemctl",
+                &["--user", "disable", "--now", &self.systemd_unit(label)],
+            )?;
+        }
+
+        remove_if_present(&self.definition_path(label))?;
+        remove_if_present(&self.timer_path(label))?;
+
+        if !cfg!(target_os = "macos") {
+            run("systemctl", &["--user", "daemon-reload"])?;
+        }
+        Ok(())
+    }
+
+    /// Whether the service manager has the service loaded: running, or
+    /// waiting for its next interval.
+    pub fn is_active(&self, label: &str) -> bool {
+        let status = if cfg!(target_os = "macos") {
+            Command::new("launchctl")
+                .args(["print", &format!("{}/{label}", launchd_domain())])
+                .output()
+        } else {
+            Command::new("systemctl")
+                .args(["--user", "is-active", "--quiet", &self.systemd_unit(label)])
+                .output()
+        };
+        status.is_ok_and(|output| output.status.success())
+    }
+
+    /// Interval services are driven by their timer, so that's the unit to
+    /// enable, stop and query.
+    fn systemd_unit(&self, label: &str) -> String {
+        if self.timer_path(label).exists() {
+            format!("{label}.timer")
+        } else {
+            format!("{label}.service")
+        }
+    }
+}
+
+fn launchd_domain() -> String {
+    // SAFETY: getuid has no preconditions and cannot fail.
+    format!("gui/{}", unsafe { libc::getuid() })
+}
+
+fn run(program: &str, args: &[&str]) -> Result<(), Error> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| Error::ExecutionError {
+            message: format!("failed to run {program}: {e}"),
+        })?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    Err(Error::ExecutionError {
+        message: format!(
+            "`{program} {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    })
+}
+
+fn remove_if_present(path: &std::path::Path) -> Result<(), Error> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(Error::FileError {
+            message: format!("failed to remove '{}': {e}", path.display()),
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use tempfile::TempDir;
+    use zb_core::RestartPolicy;
+
+    use super::*;
+
+    #[test]
+    fn write_creates_the_definition_and_log_directory() {
+        let tmp = TempDir::new().unwrap();
+        let manager = ServiceManager::new(tmp.path().join("agents"));
+        let spec = ServiceSpec {
+            label: "zerobrew.redis".to_string(),
+            program_args: vec!["/p/opt/redis/bin/redis-server".to_string()],
+            interval: None,
+            restart: RestartPolicy::Always,
+            working_dir: None,
+            log_path: Some(tmp.path().join("var/log/redis.log")),
+            error_log_path: None,
+            environment: BTreeMap::new(),
+        };
+
+        let path = manager.write(&spec).unwrap();
+        assert_eq!(path, manager.definition_path("zerobrew.redis"));
+        assert!(
+            fs::read_to_string(&path)
+                .unwrap()
+                .contains("/p/opt/redis/bin/redis-server")
+        );
+        assert!(tmp.path().join("var/log").is_dir());
+        assert!(!manager.timer_path("zerobrew.redis").exists());
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt::Write;
+
+use zb_core::{RestartPolicy, ServiceSpec};
+
+/// A user unit for `spec`, started with the user's session.
+pub fn render_unit(spec: &ServiceSpec) -> String {
+    let mut unit = String::new();
+    let _ = writeln!(unit, "[Unit]\nDescription=zerobrew: {}\n", spec.label);
+
+    unit.push_str("[Service]\n");
+    // Interval services run to completion each time their timer fires.
+    let service_type = if spec.interval.is_some() {
+        "oneshot"
+    } else {
+        "simple"
+    };
+    let _ = writeln!(unit, "Type={service_type}");
+    let exec: Vec<String> = spec.program_args.iter().map(|arg| quote(arg)).collect();
+    let _ = writeln!(unit, "ExecStart={}", exec.join(" "));
+
+    if spec.interval.is_none() {
+        let restart = match spec.restart {
+            RestartPolicy::Never => "no",
+            RestartPolicy::Always => "always",
+            RestartPolicy::OnFailure => "on-failure",
+            RestartPolicy::OnSuccess => "on-success",
+        };
+        let _ = writeln!(unit, "Restart={restart}");
+    }
+    if let Some(dir) = &spec.working_dir {
+        let _ = writeln!(unit, "WorkingDirectory={}", dir.display());
+    }
+    if let Some(path) = &spec.log_path {
+        let _ = writeln!(unit, "StandardOutput=append:{}", path.display());
+    }
+    if let Some(path) = &spec.error_log_path {
+        let _ = writeln!(unit, "StandardError=append:{}", path.display());
+    }
+    for (key, value) in &spec.environment {
+        let _ = writeln!(unit, "Environment={}", quote(&format!("{key}={value}")));
+    }
+
+    if spec.interval.is_none() {
+        unit.push_str("\n[Install]\nWantedBy=default.target\n");
+    }
+    unit
+}
+
+/// The timer that runs an interval service, or `None` for one that runs
+/// continuously.
+pub fn render_timer(spec: &ServiceSpec) -> Option<String> {
+    let interval = spec.interval?;
+    Some(format!(
+        "[Unit]\nDescription=zerobrew: {label} every {interval}s\n\n\
+         [Timer]\nOnActiveSec=0\nOnUnitActiveSec={interval}\nUnit={label}.service\n\n\
+         [Install]\nWantedBy=timers.target\n",
+        label = spec.label
+    ))
+}
+
+/// Quote an `ExecStart=`/`Environment=` word so systemd keeps it whole and
+/// doesn't expand `%` specifiers or `$` variables in it.
+fn quote(word: &str) -> String {
+    let escaped = word
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn spec(interval: Option<u64>) -> ServiceSpec {
+        ServiceSpec {
+            label: "zerobrew.postgresql-17".to_string(),
+            program_args: vec![
+                "/opt/zerobrew/opt/postgresql@17/bin/postgres".to_string(),
+                "-D".to_string(),
+                "/opt/zerobrew/var/postgresql@17".to_string(),
+            ],
+            interval,
+            restart: RestartPolicy::Always,
+            working_dir: Some(PathBuf::from("/opt/zerobrew")),
+            log_path: Some(PathBuf::from("/opt/zerobrew/var/log/postgresql@17.log")),
+            error_log_path: None,
+            environment: BTreeMap::from([("PGTZ".to_string(), "50% $off".to_string())]),
+        }
+    }
+
+    #[test]
+    fn unit_quotes_arguments_and_escapes_specifiers() {
+        let unit = render_unit(&spec(None));
+        assert!(unit.contains(
+            "ExecStart=\"/opt/zerobrew/opt/postgresql@17/bin/postgres\" \"-D\" \"/opt/zerobrew/var/postgresql@17\"\n"
+        ));
+        assert!(unit.contains("Type=simple\n"));
+        assert!(unit.contains("Restart=always\n"));
+        assert!(unit.contains("StandardOutput=append:/opt/zerobrew/var/log/postgresql@17.log\n"));
+        assert!(unit.contains("Environment=\"PGTZ=50%% $$off\"\n"));
+        assert!(unit.ends_with("[Install]\nWantedBy=default.target\n"));
+        assert_eq!(render_timer(&spec(None)), None);
+    }
+
+    #[test]
+    fn interval_services_run_from_a_timer() {
+        let unit = render_unit(&spec(Some(300)));
+        assert!(unit.contains("Type=oneshot\n"));
+        assert!(!unit.contains("Restart="));
+        assert!(!unit.contains("[Install]"));
+
+        let timer = render_timer(&spec(Some(300))).unwrap();
+        assert!(timer.contains("OnUnitActiveSec=300\n"));
+        assert!(timer.contains("Unit=zerobrew.postgresql-17.service\n"));
+        assert!(timer.ends_with("WantedBy=timers.target\n"));
+    }
+}
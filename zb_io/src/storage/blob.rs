@@ -1,12 +1,15 @@
 use std::fs;
 use std::io::{self, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
+use sha2::{Digest, Sha256};
 use tempfile::NamedTempFile;
 use zb_core::Error;
 
 #[derive(Clone)]
 pub struct BlobCache {
+    root: PathBuf,
     blobs_dir: PathBuf,
     tmp_dir: PathBuf,
 }
@@ -19,7 +22,16 @@ impl BlobCache {
         fs::create_dir_all(&blobs_dir)?;
         fs::create_dir_all(&tmp_dir)?;
 
-        Ok(Self { blobs_dir, tmp_dir })
+        Ok(Self {
+            root: cache_root.to_path_buf(),
+            blobs_dir,
+            tmp_dir,
+        })
+    }
+
+    /// The directory the cache lives in.
+    pub fn root(&self) -> &Path {
+        &self.root
     }
 
     pub fn blob_path(&self, sha256: &str) -> PathBuf {
@@ -30,9 +42,50 @@ impl BlobCache {
         self.blob_path(sha256).exists()
     }
 
+    /// Check a cached blob still matches the sha256 it is stored under.
+    /// A blob whose size and mtime are unchanged since it was last hashed is
+    /// trusted as is; anything else is hashed again with [`Self::rehash_blob`].
+    pub fn blob_matches(&self, sha256: &str) -> io::Result<bool> {
+        let path = self.blob_path(sha256);
+        let stamp = file_stamp(&fs::metadata(&path)?);
+        if stamp.is_some() && fs::read_to_string(stamp_path(&path)).ok() == stamp {
+            return Ok(true);
+        }
+        self.rehash_blob(sha256)
+    }
+
+    /// Hash a cached blob in full and check it matches the sha256 it is
+    /// stored under. Catches blobs that were tampered with or truncated on
+    /// disk after the original download was verified.
+    pub fn rehash_blob(&self, sha256: &str) -> io::Result<bool> {
+        let path = self.blob_path(sha256);
+        let mut file = fs::File::open(&path)?;
+        let mut hasher = Sha256::new();
+        io::copy(&mut file, &mut hasher)?;
+        let matches = crate::checksum::sha256_hex(hasher) == sha256.to_ascii_lowercase();
+        if matches {
+            record_verified(&path);
+        }
+        Ok(matches)
+    }
+
+    /// The sha256 of every blob in the cache.
+    pub fn list_blobs(&self) -> io::Result<Vec<String>> {
+        let mut blobs = Vec::new();
+        for entry in fs::read_dir(&self.blobs_dir)? {
+            let name = entry?.file_name();
+            if let Some(sha256) = name.to_str().and_then(|n| n.strip_suffix(".tar.gz")) {
+                blobs.push(sha256.to_string());
+            }
+        }
+        blobs.sort();
+        Ok(blobs)
+    }
+
     /// Remove a blob from the cache (used when extraction fails due to corruption)
     pub fn remove_blob(&self, sha256: &str) -> io::Result<bool> {
         let path = self.blob_path(sha256);
+        let _ = fs::remove_file(stamp_path(&path));
         if path.exists() {
             fs::remove_file(&path)?;
             Ok(true)
@@ -45,40 +98,146 @@ impl BlobCache {
         let final_path = self.blob_path(sha256);
         let temp_file = NamedTempFile::new_in(&self.tmp_dir)?;
         Ok(BlobWriter {
-            temp_file,
+            file: WriterFile::Temp(temp_file),
             final_path,
         })
     }
+
+    /// Where an interrupted download of `sha256` is kept so a later attempt
+    /// can pick up from where it stopped.
+    pub fn partial_path(&self, sha256: &str) -> PathBuf {
+        self.tmp_dir.join(format!("{sha256}.tar.gz.part"))
+    }
+
+    /// Every partial download left behind by an interrupted attempt.
+    pub fn list_partials(&self) -> io::Result<Vec<PathBuf>> {
+        let mut partials = Vec::new();
+        for entry in fs::read_dir(&self.tmp_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "part") {
+                partials.push(path);
+            }
+        }
+        partials.sort();
+        Ok(partials)
+    }
+
+    /// How many bytes of `sha256` an earlier attempt already downloaded.
+    pub fn partial_len(&self, sha256: &str) -> u64 {
+        fs::metadata(self.partial_path(sha256))
+            .map(|meta| meta.len())
+            .unwrap_or(0)
+    }
+
+    /// Like `start_write`, but into the partial file, which survives the
+    /// writer being dropped. With `append`, bytes already there are kept and
+    /// new ones go after them; otherwise the file starts out empty.
+    pub fn start_partial_write(&self, sha256: &str, append: bool) -> io::Result<BlobWriter> {
+        let path = self.partial_path(sha256);
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(&path)?;
+        Ok(BlobWriter {
+            file: WriterFile::Partial { file, path },
+            final_path: self.blob_path(sha256),
+        })
+    }
+}
+
+/// Sidecar holding the size and mtime a blob had when its content last
+/// hashed to its name.
+fn stamp_path(blob_path: &Path) -> PathBuf {
+    let mut path = blob_path.as_os_str().to_owned();
+    path.push(".verified");
+    PathBuf::from(path)
+}
+
+fn file_stamp(meta: &fs::Metadata) -> Option<String> {
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("{} {}", meta.len(), mtime.as_nanos()))
+}
+
+/// Note that `blob_path` was just found to match its name. Best effort: a
+/// missing stamp only means the next cache hit hashes the blob again.
+fn record_verified(blob_path: &Path) {
+    if let Some(stamp) = fs::metadata(blob_path).ok().as_ref().and_then(file_stamp) {
+        let _ = fs::write(stamp_path(blob_path), stamp);
+    }
+}
+
+enum WriterFile {
+    Temp(NamedTempFile),
+    Partial { file: fs::File, path: PathBuf },
 }
 
 pub struct BlobWriter {
-    temp_file: NamedTempFile,
+    file: WriterFile,
     final_path: PathBuf,
 }
 
 impl BlobWriter {
     pub fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        self.temp_file.seek(pos)
+        match &mut self.file {
+            WriterFile::Temp(temp_file) => temp_file.seek(pos),
+            WriterFile::Partial { file, .. } => file.seek(pos),
+        }
     }
 
     pub fn commit(self) -> Result<PathBuf, Error> {
         // Content-addressed: same sha256 = identical content, so overwrite is safe.
         // NamedTempFile::persist does an atomic rename(2) on Unix.
         // On drop (e.g. if persist is never called), the temp file is auto-deleted.
-        self.temp_file
-            .persist(&self.final_path)
-            .map_err(Error::store("failed to persist blob"))?;
+        let _ = fs::remove_file(stamp_path(&self.final_path));
+        match self.file {
+            WriterFile::Temp(temp_file) => {
+                temp_file
+                    .persist(&self.final_path)
+                    .map_err(Error::store("failed to persist blob"))?;
+            }
+            WriterFile::Partial { file, path } => {
+                drop(file);
+                fs::rename(&path, &self.final_path)
+                    .map_err(Error::store("failed to persist blob"))?;
+            }
+        }
         Ok(self.final_path)
     }
+
+    /// Like `commit`, for content already hashed against the sha256 it is
+    /// stored under, so later cache hits can trust it without hashing it
+    /// again.
+    pub fn commit_verified(self) -> Result<PathBuf, Error> {
+        let path = self.commit()?;
+        record_verified(&path);
+        Ok(path)
+    }
+
+    /// Throw away what was written, including a partial file that would
+    /// otherwise be kept for resuming.
+    pub fn discard(self) {
+        if let WriterFile::Partial { file, path } = self.file {
+            drop(file);
+            let _ = fs::remove_file(path);
+        }
+    }
 }
 
 impl Write for BlobWriter {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.temp_file.write(buf)
+        match &mut self.file {
+            WriterFile::Temp(temp_file) => temp_file.write(buf),
+            WriterFile::Partial { file, .. } => file.write(buf),
+        }
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        self.temp_file.flush()
+        match &mut self.file {
+            WriterFile::Temp(temp_file) => temp_file.flush(),
+            WriterFile::Partial { file, .. } => file.flush(),
+        }
     }
 }
 
@@ -127,6 +286,71 @@ mod tests {
         assert!(!has_temp_files, "temp files for {sha} should be cleaned up");
     }
 
+    #[test]
+    fn partial_write_survives_drop_and_appends_on_resume() {
+        let tmp = TempDir::new().unwrap();
+        let cache = BlobCache::new(tmp.path()).unwrap();
+        let sha = "abc123";
+
+        {
+            let mut writer = cache.start_partial_write(sha, false).unwrap();
+            writer.write_all(b"hello ").unwrap();
+        }
+        assert_eq!(cache.partial_len(sha), 6);
+        assert!(!cache.has_blob(sha));
+        assert_eq!(
+            cache.list_partials().unwrap(),
+            vec![cache.partial_path(sha)]
+        );
+
+        let mut writer = cache.start_partial_write(sha, true).unwrap();
+        writer.write_all(b"world").unwrap();
+        let final_path = writer.commit().unwrap();
+
+        assert_eq!(fs::read_to_string(final_path).unwrap(), "hello world");
+        assert!(!cache.partial_path(sha).exists());
+
+        let mut writer = cache.start_partial_write(sha, false).unwrap();
+        writer.write_all(b"garbage").unwrap();
+        writer.discard();
+        assert_eq!(cache.partial_len(sha), 0);
+        assert!(!cache.partial_path(sha).exists());
+    }
+
+    #[test]
+    fn blob_matches_detects_tampered_content() {
+        let tmp = TempDir::new().unwrap();
+        let cache = BlobCache::new(tmp.path()).unwrap();
+        let sha = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+        let mut writer = cache.start_write(sha).unwrap();
+        writer.write_all(b"hello world").unwrap();
+        writer.commit().unwrap();
+        assert!(cache.blob_matches(sha).unwrap());
+
+        fs::write(cache.blob_path(sha), b"tampered").unwrap();
+        assert!(!cache.blob_matches(sha).unwrap());
+    }
+
+    #[test]
+    fn verified_blob_is_trusted_until_it_changes() {
+        let tmp = TempDir::new().unwrap();
+        let cache = BlobCache::new(tmp.path()).unwrap();
+        let sha = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+        let mut writer = cache.start_write(sha).unwrap();
+        writer.write_all(b"hello world").unwrap();
+        writer.commit_verified().unwrap();
+        assert!(stamp_path(&cache.blob_path(sha)).is_file());
+        assert!(cache.blob_matches(sha).unwrap());
+
+        fs::write(cache.blob_path(sha), b"hello there, world").unwrap();
+        assert!(!cache.blob_matches(sha).unwrap());
+
+        cache.remove_blob(sha).unwrap();
+        assert!(!stamp_path(&cache.blob_path(sha)).exists());
+    }
+
     #[test]
     fn blob_path_uses_sha256() {
         let tmp = TempDir::new().unwrap();
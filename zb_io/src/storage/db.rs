@@ -1,9 +1,12 @@
+use std::collections::HashMap;
 use std::path::Path;
 
 use rusqlite::{Connection, OptionalExtension, Transaction, params};
 
 use zb_core::Error;
 
+use crate::cellar::manifest::ManifestEntry;
+
 pub struct Database {
     conn: Connection,
 }
@@ -16,12 +19,75 @@ pub struct InstalledKeg {
     pub installed_at: i64,
 }
 
+/// A snapshot of the prefix recorded after an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generation {
+    pub id: i64,
+    pub created_at: i64,
+    pub operation: String,
+    /// The generation the prefix is in now.
+    pub current: bool,
+    pub kegs: usize,
+}
+
+/// One installed keg in a generation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerationKeg {
+    pub name: String,
+    pub version: String,
+    pub store_key: String,
+    /// Whether the keg was linked into the prefix, not just `opt/`.
+    pub linked: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallHistoryEntry {
+    pub name: String,
+    pub version: String,
+    pub installed_at: i64,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StoreRef {
     pub store_key: String,
     pub refcount: i64,
 }
 
+/// Something a cask installed outside its keg, which uninstall must remove.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaskArtifact {
+    /// An app bundle copied into the appdir.
+    App(String),
+    /// A receipt left by an installer package.
+    PkgReceipt(String),
+}
+
+impl CaskArtifact {
+    fn kind(&self) -> &'static str {
+        match self {
+            CaskArtifact::App(_) => "app",
+            CaskArtifact::PkgReceipt(_) => "pkg",
+        }
+    }
+
+    fn value(&self) -> &str {
+        match self {
+            CaskArtifact::App(path) => path,
+            CaskArtifact::PkgReceipt(id) => id,
+        }
+    }
+}
+
+/// A service started through `zb services start`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceRecord {
+    pub name: String,
+    pub label: String,
+    /// The launchd plist or systemd unit written for it.
+    pub file: String,
+    pub started_at: i64,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct KegFileRecord {
     pub name: String,
@@ -31,7 +97,7 @@ pub struct KegFileRecord {
 }
 
 impl Database {
-    const SCHEMA_VERSION: u32 = 1;
+    const SCHEMA_VERSION: u32 = 15;
 
     pub fn open(path: &Path) -> Result<Self, Error> {
         let conn = Connection::open(path).map_err(Error::store("failed to open database"))?;
@@ -89,6 +155,20 @@ impl Database {
     fn migrate_to_version(conn: &Connection, version: u32) -> Result<(), Error> {
         match version {
             1 => Self::migrate_to_v1(conn),
+            2 => Self::migrate_to_v2(conn),
+            3 => Self::migrate_to_v3(conn),
+            4 => Self::migrate_to_v4(conn),
+            5 => Self::migrate_to_v5(conn),
+            6 => Self::migrate_to_v6(conn),
+            7 => Self::migrate_to_v7(conn),
+            8 => Self::migrate_to_v8(conn),
+            9 => Self::migrate_to_v9(conn),
+            10 => Self::migrate_to_v10(conn),
+            11 => Self::migrate_to_v11(conn),
+            12 => Self::migrate_to_v12(conn),
+            13 => Self::migrate_to_v13(conn),
+            14 => Self::migrate_to_v14(conn),
+            15 => Self::migrate_to_v15(conn),
             _ => Err(Error::StoreCorruption {
                 message: format!("unknown migration version {}", version),
             }),
@@ -124,6 +204,250 @@ impl Database {
         Ok(())
     }
 
+    fn migrate_to_v2(conn: &Connection) -> Result<(), Error> {
+        // Seed history from the active kegs so existing installs keep their
+        // original timestamp instead of starting with an empty history.
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS install_history (
+                name TEXT NOT NULL,
+                version TEXT NOT NULL,
+                installed_at INTEGER NOT NULL,
+                PRIMARY KEY (name, version)
+            );
+
+            INSERT OR IGNORE INTO install_history (name, version, installed_at)
+            SELECT name, version, installed_at FROM installed_kegs;
+            ",
+        )
+        .map_err(Error::store("failed to create install history table"))?;
+
+        Ok(())
+    }
+
+    fn migrate_to_v3(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS keg_sizes (
+                name TEXT NOT NULL,
+                version TEXT NOT NULL,
+                size_bytes INTEGER NOT NULL,
+                PRIMARY KEY (name, version)
+            );
+            ",
+        )
+        .map_err(Error::store("failed to create keg sizes table"))?;
+
+        Ok(())
+    }
+
+    fn migrate_to_v4(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS keg_manifests (
+                name TEXT NOT NULL,
+                version TEXT NOT NULL,
+                path TEXT NOT NULL,
+                sha256 TEXT NOT NULL,
+                PRIMARY KEY (name, version, path)
+            );
+            ",
+        )
+        .map_err(Error::store("failed to create keg manifests table"))?;
+
+        Ok(())
+    }
+
+    /// Formulas installed only to satisfy a source build's `:build`
+    /// dependencies, which `zb gc --aggressive` may reclaim.
+    fn migrate_to_v5(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS build_dependency_kegs (
+                name TEXT PRIMARY KEY,
+                installed_at INTEGER NOT NULL
+            );
+            ",
+        )
+        .map_err(Error::store("failed to create build dependency kegs table"))?;
+
+        Ok(())
+    }
+
+    fn migrate_to_v6(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS pinned_kegs (
+                name TEXT PRIMARY KEY,
+                version TEXT NOT NULL,
+                pinned_at INTEGER NOT NULL
+            );
+            ",
+        )
+        .map_err(Error::store("failed to create pinned kegs table"))?;
+
+        Ok(())
+    }
+
+    /// Formulas pulled in only as runtime dependencies of something else,
+    /// which `zb autoremove` removes once nothing needs them. Kegs installed
+    /// before this table existed have no mark and count as explicit.
+    fn migrate_to_v7(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS dependency_kegs (
+                name TEXT PRIMARY KEY,
+                installed_at INTEGER NOT NULL
+            );
+            ",
+        )
+        .map_err(Error::store("failed to create dependency kegs table"))?;
+
+        Ok(())
+    }
+
+    /// Apps and pkg receipts a cask put outside the Cellar, kept until the
+    /// cask is uninstalled so they can be removed with it.
+    fn migrate_to_v8(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS cask_artifacts (
+                name TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (name, kind, value)
+            );
+            ",
+        )
+        .map_err(Error::store("failed to create cask artifacts table"))?;
+
+        Ok(())
+    }
+
+    /// Kegs installed only so `zb run --ephemeral` could execute them, which
+    /// `zb gc --aggressive` removes.
+    fn migrate_to_v9(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS ephemeral_kegs (
+                name TEXT PRIMARY KEY,
+                installed_at INTEGER NOT NULL
+            );
+            ",
+        )
+        .map_err(Error::store("failed to create ephemeral kegs table"))?;
+
+        Ok(())
+    }
+
+    /// Services `zb services start` handed to launchd or systemd. Upgrades
+    /// keep them, since definitions run the formula through its `opt/` link.
+    fn migrate_to_v10(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS services (
+                name TEXT PRIMARY KEY,
+                label TEXT NOT NULL,
+                file TEXT NOT NULL,
+                started_at INTEGER NOT NULL
+            );
+            ",
+        )
+        .map_err(Error::store("failed to create services table"))?;
+
+        Ok(())
+    }
+
+    /// Per-file checksums of each store entry, taken when it was extracted
+    /// from a verified bottle, for `zb verify --store`.
+    fn migrate_to_v11(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS store_manifests (
+                store_key TEXT NOT NULL,
+                path TEXT NOT NULL,
+                sha256 TEXT NOT NULL,
+                PRIMARY KEY (store_key, path)
+            );
+            ",
+        )
+        .map_err(Error::store("failed to create store manifests table"))?;
+
+        Ok(())
+    }
+
+    /// Snapshots of which kegs were installed and linked after each
+    /// operation, for `zb rollback --to`.
+    fn migrate_to_v12(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS generations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at INTEGER NOT NULL,
+                operation TEXT NOT NULL,
+                current INTEGER NOT NULL DEFAULT 0
+            );
+
+            CREATE TABLE IF NOT EXISTS generation_kegs (
+                generation INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                version TEXT NOT NULL,
+                store_key TEXT NOT NULL,
+                linked INTEGER NOT NULL,
+                PRIMARY KEY (generation, name)
+            );
+            ",
+        )
+        .map_err(Error::store("failed to create generations tables"))?;
+
+        Ok(())
+    }
+
+    /// Look up which keg linked a path, for `zb which` and `zb provides`.
+    fn migrate_to_v13(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS keg_files_linked_path ON keg_files (linked_path);",
+        )
+        .map_err(Error::store("failed to index keg files"))?;
+
+        Ok(())
+    }
+
+    /// How to handle link conflicts for a formula, as chosen when they
+    /// first came up, so later relinks do the same.
+    fn migrate_to_v14(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS link_conflict_policies (
+                name TEXT PRIMARY KEY,
+                policy TEXT NOT NULL
+            );
+            ",
+        )
+        .map_err(Error::store(
+            "failed to create link conflict policies table",
+        ))?;
+
+        Ok(())
+    }
+
+    /// The runtime dependencies each keg was installed with, one per line,
+    /// so dependency queries don't refetch every formula. Kegs installed
+    /// before this table existed have no row.
+    fn migrate_to_v15(conn: &Connection) -> Result<(), Error> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS keg_dependencies (
+                name TEXT PRIMARY KEY,
+                dependencies TEXT NOT NULL
+            );
+            ",
+        )
+        .map_err(Error::store("failed to create keg dependencies table"))?;
+
+        Ok(())
+    }
+
     pub fn transaction(&mut self) -> Result<InstallTransaction<'_>, Error> {
         let tx = self
             .conn
@@ -133,41 +457,618 @@ impl Database {
         Ok(InstallTransaction { tx })
     }
 
-    pub fn get_installed(&self, name: &str) -> Option<InstalledKeg> {
-        self.conn
-            .query_row(
-                "SELECT name, version, store_key, installed_at FROM installed_kegs WHERE name = ?1",
-                params![name],
-                |row| {
-                    Ok(InstalledKeg {
-                        name: row.get(0)?,
-                        version: row.get(1)?,
-                        store_key: row.get(2)?,
-                        installed_at: row.get(3)?,
-                    })
-                },
+    pub fn get_installed(&self, name: &str) -> Option<InstalledKeg> {
+        self.conn
+            .query_row(
+                "SELECT name, version, store_key, installed_at FROM installed_kegs WHERE name = ?1",
+                params![name],
+                |row| {
+                    Ok(InstalledKeg {
+                        name: row.get(0)?,
+                        version: row.get(1)?,
+                        store_key: row.get(2)?,
+                        installed_at: row.get(3)?,
+                    })
+                },
+            )
+            .ok()
+    }
+
+    pub fn list_installed(&self) -> Result<Vec<InstalledKeg>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT name, version, store_key, installed_at FROM installed_kegs ORDER BY name",
+            )
+            .map_err(Error::store("failed to prepare statement"))?;
+
+        let kegs = stmt
+            .query_map([], |row| {
+                Ok(InstalledKeg {
+                    name: row.get(0)?,
+                    version: row.get(1)?,
+                    store_key: row.get(2)?,
+                    installed_at: row.get(3)?,
+                })
+            })
+            .map_err(Error::store("failed to query installed kegs"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::store("failed to collect results"))?;
+
+        Ok(kegs)
+    }
+
+    /// Every recorded version of `name`, oldest first.
+    pub fn install_history(&self, name: &str) -> Result<Vec<InstallHistoryEntry>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT name, version, installed_at FROM install_history
+                 WHERE name = ?1
+                 ORDER BY installed_at, rowid",
+            )
+            .map_err(Error::store("failed to prepare statement"))?;
+
+        let entries = stmt
+            .query_map(params![name], |row| {
+                Ok(InstallHistoryEntry {
+                    name: row.get(0)?,
+                    version: row.get(1)?,
+                    installed_at: row.get(2)?,
+                })
+            })
+            .map_err(Error::store("failed to query install history"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::store("failed to collect results"))?;
+
+        Ok(entries)
+    }
+
+    pub fn pin(&self, name: &str, version: &str) -> Result<(), Error> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        self.conn
+            .execute(
+                "INSERT INTO pinned_kegs (name, version, pinned_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(name) DO UPDATE SET
+                     version = excluded.version,
+                     pinned_at = excluded.pinned_at",
+                params![name, version, now],
+            )
+            .map_err(Error::store("failed to pin keg"))?;
+        Ok(())
+    }
+
+    /// Returns whether a pin was removed.
+    pub fn unpin(&self, name: &str) -> Result<bool, Error> {
+        let removed = self
+            .conn
+            .execute("DELETE FROM pinned_kegs WHERE name = ?1", params![name])
+            .map_err(Error::store("failed to unpin keg"))?;
+        Ok(removed > 0)
+    }
+
+    pub fn is_pinned(&self, name: &str) -> Result<bool, Error> {
+        self.conn
+            .query_row(
+                "SELECT 1 FROM pinned_kegs WHERE name = ?1",
+                params![name],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(Error::store("failed to query pinned kegs"))
+    }
+
+    pub fn list_pinned(&self) -> Result<Vec<String>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM pinned_kegs ORDER BY name")
+            .map_err(Error::store("failed to prepare statement"))?;
+
+        let names = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(Error::store("failed to query pinned kegs"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::store("failed to collect results"))?;
+
+        Ok(names)
+    }
+
+    pub fn mark_build_dependency(&self, name: &str) -> Result<(), Error> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO build_dependency_kegs (name, installed_at) VALUES (?1, ?2)",
+                params![name, now],
+            )
+            .map_err(Error::store("failed to mark build dependency"))?;
+        Ok(())
+    }
+
+    /// Returns whether a mark was removed.
+    pub fn clear_build_dependency(&self, name: &str) -> Result<bool, Error> {
+        let removed = self
+            .conn
+            .execute(
+                "DELETE FROM build_dependency_kegs WHERE name = ?1",
+                params![name],
+            )
+            .map_err(Error::store("failed to clear build dependency"))?;
+        Ok(removed > 0)
+    }
+
+    pub fn list_build_dependencies(&self) -> Result<Vec<String>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM build_dependency_kegs ORDER BY name")
+            .map_err(Error::store("failed to prepare statement"))?;
+
+        let names = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(Error::store("failed to query build dependencies"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::store("failed to collect results"))?;
+
+        Ok(names)
+    }
+
+    pub fn mark_ephemeral(&self, name: &str) -> Result<(), Error> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO ephemeral_kegs (name, installed_at) VALUES (?1, ?2)",
+                params![name, now],
+            )
+            .map_err(Error::store("failed to mark ephemeral keg"))?;
+        Ok(())
+    }
+
+    /// Returns whether a mark was removed.
+    pub fn clear_ephemeral(&self, name: &str) -> Result<bool, Error> {
+        let removed = self
+            .conn
+            .execute("DELETE FROM ephemeral_kegs WHERE name = ?1", params![name])
+            .map_err(Error::store("failed to clear ephemeral keg"))?;
+        Ok(removed > 0)
+    }
+
+    pub fn list_ephemeral(&self) -> Result<Vec<String>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM ephemeral_kegs ORDER BY name")
+            .map_err(Error::store("failed to prepare statement"))?;
+
+        let names = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(Error::store("failed to query ephemeral kegs"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::store("failed to collect results"))?;
+
+        Ok(names)
+    }
+
+    pub fn record_service(&self, name: &str, label: &str, file: &str) -> Result<(), Error> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO services (name, label, file, started_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![name, label, file, now],
+            )
+            .map_err(Error::store("failed to record service"))?;
+        Ok(())
+    }
+
+    /// Returns whether a record was removed.
+    pub fn remove_service(&self, name: &str) -> Result<bool, Error> {
+        let removed = self
+            .conn
+            .execute("DELETE FROM services WHERE name = ?1", params![name])
+            .map_err(Error::store("failed to remove service record"))?;
+        Ok(removed > 0)
+    }
+
+    pub fn get_service(&self, name: &str) -> Option<ServiceRecord> {
+        self.conn
+            .query_row(
+                "SELECT name, label, file, started_at FROM services WHERE name = ?1",
+                params![name],
+                |row| {
+                    Ok(ServiceRecord {
+                        name: row.get(0)?,
+                        label: row.get(1)?,
+                        file: row.get(2)?,
+                        started_at: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    pub fn record_link_conflict_policy(&self, name: &str, policy: &str) -> Result<(), Error> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO link_conflict_policies (name, policy) VALUES (?1, ?2)",
+                params![name, policy],
+            )
+            .map_err(Error::store("failed to record link conflict policy"))?;
+        Ok(())
+    }
+
+    pub fn link_conflict_policy(&self, name: &str) -> Option<String> {
+        self.conn
+            .query_row(
+                "SELECT policy FROM link_conflict_policies WHERE name = ?1",
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    pub fn mark_dependency(&self, name: &str) -> Result<(), Error> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO dependency_kegs (name, installed_at) VALUES (?1, ?2)",
+                params![name, now],
+            )
+            .map_err(Error::store("failed to mark dependency"))?;
+        Ok(())
+    }
+
+    /// Returns whether a mark was removed.
+    pub fn clear_dependency(&self, name: &str) -> Result<bool, Error> {
+        let removed = self
+            .conn
+            .execute("DELETE FROM dependency_kegs WHERE name = ?1", params![name])
+            .map_err(Error::store("failed to clear dependency"))?;
+        Ok(removed > 0)
+    }
+
+    pub fn is_dependency(&self, name: &str) -> Result<bool, Error> {
+        self.conn
+            .query_row(
+                "SELECT 1 FROM dependency_kegs WHERE name = ?1",
+                params![name],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(Error::store("failed to query dependency kegs"))
+    }
+
+    pub fn list_dependencies(&self) -> Result<Vec<String>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM dependency_kegs ORDER BY name")
+            .map_err(Error::store("failed to prepare statement"))?;
+
+        let names = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(Error::store("failed to query dependency kegs"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::store("failed to collect results"))?;
+
+        Ok(names)
+    }
+
+    /// Record dependencies for a keg installed before they were recorded
+    /// at install time.
+    pub fn record_dependencies(&self, name: &str, dependencies: &[String]) -> Result<(), Error> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO keg_dependencies (name, dependencies) VALUES (?1, ?2)",
+                params![name, dependencies.join("\n")],
+            )
+            .map_err(Error::store("failed to record dependencies"))?;
+        Ok(())
+    }
+
+    /// Recorded runtime dependencies, by keg. Kegs without a record are
+    /// left out.
+    pub fn list_keg_dependencies(&self) -> Result<HashMap<String, Vec<String>>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name, dependencies FROM keg_dependencies")
+            .map_err(Error::store("failed to prepare statement"))?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(Error::store("failed to query keg dependencies"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::store("failed to collect results"))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(name, dependencies)| {
+                let dependencies = dependencies.lines().map(str::to_string).collect();
+                (name, dependencies)
+            })
+            .collect())
+    }
+
+    pub fn list_cask_artifacts(&self, name: &str) -> Result<Vec<CaskArtifact>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT kind, value FROM cask_artifacts WHERE name = ?1 ORDER BY kind, value")
+            .map_err(Error::store("failed to prepare statement"))?;
+
+        let rows = stmt
+            .query_map(params![name], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(Error::store("failed to query cask artifacts"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::store("failed to collect results"))?;
+
+        rows.into_iter()
+            .map(|(kind, value)| match kind.as_str() {
+                "app" => Ok(CaskArtifact::App(value)),
+                "pkg" => Ok(CaskArtifact::PkgReceipt(value)),
+                other => Err(Error::StoreCorruption {
+                    message: format!("unknown cask artifact kind '{other}' for {name}"),
+                }),
+            })
+            .collect()
+    }
+
+    pub fn clear_cask_artifacts(&self, name: &str) -> Result<(), Error> {
+        self.conn
+            .execute("DELETE FROM cask_artifacts WHERE name = ?1", params![name])
+            .map_err(Error::store("failed to clear cask artifacts"))?;
+        Ok(())
+    }
+
+    pub fn cached_keg_size(&self, name: &str, version: &str) -> Option<u64> {
+        self.conn
+            .query_row(
+                "SELECT size_bytes FROM keg_sizes WHERE name = ?1 AND version = ?2",
+                params![name, version],
+                |row| row.get::<_, i64>(0),
+            )
+            .ok()
+            .map(|size| size as u64)
+    }
+
+    pub fn record_keg_size(&self, name: &str, version: &str, size_bytes: u64) -> Result<(), Error> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO keg_sizes (name, version, size_bytes)
+                 VALUES (?1, ?2, ?3)",
+                params![name, version, size_bytes as i64],
+            )
+            .map_err(Error::store("failed to record keg size"))?;
+        Ok(())
+    }
+
+    /// Replace the recorded file manifest for a keg.
+    pub fn record_keg_manifest(
+        &self,
+        name: &str,
+        version: &str,
+        entries: &[ManifestEntry],
+    ) -> Result<(), Error> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(Error::store("failed to start transaction"))?;
+
+        tx.execute(
+            "DELETE FROM keg_manifests WHERE name = ?1 AND version = ?2",
+            params![name, version],
+        )
+        .map_err(Error::store("failed to clear keg manifest"))?;
+
+        for entry in entries {
+            tx.execute(
+                "INSERT INTO keg_manifests (name, version, path, sha256)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![name, version, entry.path, entry.sha256],
+            )
+            .map_err(Error::store("failed to record keg manifest"))?;
+        }
+
+        tx.commit()
+            .map_err(Error::store("failed to commit keg manifest"))
+    }
+
+    pub fn keg_manifest(&self, name: &str, version: &str) -> Result<Vec<ManifestEntry>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT path, sha256 FROM keg_manifests
+                 WHERE name = ?1 AND version = ?2
+                 ORDER BY path",
+            )
+            .map_err(Error::store("failed to prepare statement"))?;
+
+        let entries = stmt
+            .query_map(params![name, version], |row| {
+                Ok(ManifestEntry {
+                    path: row.get(0)?,
+                    sha256: row.get(1)?,
+                })
+            })
+            .map_err(Error::store("failed to query keg manifest"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::store("failed to collect results"))?;
+
+        Ok(entries)
+    }
+
+    pub fn record_store_manifest(
+        &self,
+        store_key: &str,
+        entries: &[ManifestEntry],
+    ) -> Result<(), Error> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(Error::store("failed to start transaction"))?;
+
+        tx.execute(
+            "DELETE FROM store_manifests WHERE store_key = ?1",
+            params![store_key],
+        )
+        .map_err(Error::store("failed to clear store manifest"))?;
+
+        for entry in entries {
+            tx.execute(
+                "INSERT INTO store_manifests (store_key, path, sha256)
+                 VALUES (?1, ?2, ?3)",
+                params![store_key, entry.path, entry.sha256],
+            )
+            .map_err(Error::store("failed to record store manifest"))?;
+        }
+
+        tx.commit()
+            .map_err(Error::store("failed to commit store manifest"))
+    }
+
+    /// The checksums recorded for `store_key`'s files, empty when the entry
+    /// was extracted before manifests were recorded.
+    pub fn store_manifest(&self, store_key: &str) -> Result<Vec<ManifestEntry>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT path, sha256 FROM store_manifests
+                 WHERE store_key = ?1
+                 ORDER BY path",
+            )
+            .map_err(Error::store("failed to prepare statement"))?;
+
+        let entries = stmt
+            .query_map(params![store_key], |row| {
+                Ok(ManifestEntry {
+                    path: row.get(0)?,
+                    sha256: row.get(1)?,
+                })
+            })
+            .map_err(Error::store("failed to query store manifest"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::store("failed to collect results"))?;
+
+        Ok(entries)
+    }
+
+    /// Record a new generation holding `kegs` and make it current.
+    pub fn record_generation(&self, operation: &str, kegs: &[GenerationKeg]) -> Result<i64, Error> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(Error::store("failed to start transaction"))?;
+
+        tx.execute("UPDATE generations SET current = 0", [])
+            .map_err(Error::store("failed to update generations"))?;
+        tx.execute(
+            "INSERT INTO generations (created_at, operation, current) VALUES (?1, ?2, 1)",
+            params![now, operation],
+        )
+        .map_err(Error::store("failed to record generation"))?;
+        let id = tx.last_insert_rowid();
+
+        for keg in kegs {
+            tx.execute(
+                "INSERT INTO generation_kegs (generation, name, version, store_key, linked)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![id, keg.name, keg.version, keg.store_key, keg.linked],
+            )
+            .map_err(Error::store("failed to record generation keg"))?;
+        }
+
+        tx.commit()
+            .map_err(Error::store("failed to commit generation"))?;
+        Ok(id)
+    }
+
+    pub fn set_current_generation(&self, id: i64) -> Result<(), Error> {
+        self.conn
+            .execute("UPDATE generations SET current = (id = ?1)", params![id])
+            .map_err(Error::store("failed to update generations"))?;
+        Ok(())
+    }
+
+    /// Every generation, oldest first.
+    pub fn list_generations(&self) -> Result<Vec<Generation>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT g.id, g.created_at, g.operation, g.current,
+                        (SELECT COUNT(*) FROM generation_kegs k WHERE k.generation = g.id)
+                 FROM generations g
+                 ORDER BY g.id",
             )
-            .ok()
+            .map_err(Error::store("failed to prepare statement"))?;
+
+        let generations = stmt
+            .query_map([], |row| {
+                Ok(Generation {
+                    id: row.get(0)?,
+                    created_at: row.get(1)?,
+                    operation: row.get(2)?,
+                    current: row.get(3)?,
+                    kegs: row.get::<_, i64>(4)? as usize,
+                })
+            })
+            .map_err(Error::store("failed to query generations"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::store("failed to collect results"))?;
+
+        Ok(generations)
     }
 
-    pub fn list_installed(&self) -> Result<Vec<InstalledKeg>, Error> {
+    /// The kegs of generation `id`, ordered by name.
+    pub fn generation_kegs(&self, id: i64) -> Result<Vec<GenerationKeg>, Error> {
         let mut stmt = self
             .conn
             .prepare(
-                "SELECT name, version, store_key, installed_at FROM installed_kegs ORDER BY name",
+                "SELECT name, version, store_key, linked FROM generation_kegs
+                 WHERE generation = ?1
+                 ORDER BY name",
             )
             .map_err(Error::store("failed to prepare statement"))?;
 
         let kegs = stmt
-            .query_map([], |row| {
-                Ok(InstalledKeg {
+            .query_map(params![id], |row| {
+                Ok(GenerationKeg {
                     name: row.get(0)?,
                     version: row.get(1)?,
                     store_key: row.get(2)?,
-                    installed_at: row.get(3)?,
+                    linked: row.get(3)?,
                 })
             })
-            .map_err(Error::store("failed to query installed kegs"))?
+            .map_err(Error::store("failed to query generation kegs"))?
             .collect::<Result<Vec<_>, _>>()
             .map_err(Error::store("failed to collect results"))?;
 
@@ -206,6 +1107,12 @@ impl Database {
                 params![store_key],
             )
             .map_err(Error::store("failed to delete store ref"))?;
+        self.conn
+            .execute(
+                "DELETE FROM store_manifests WHERE store_key = ?1",
+                params![store_key],
+            )
+            .map_err(Error::store("failed to delete store manifest"))?;
         Ok(())
     }
 
@@ -229,6 +1136,35 @@ impl Database {
         Ok(refs)
     }
 
+    /// The kegs that linked `linked_path`. Normally at most one; more only
+    /// if a conflict was overridden.
+    pub fn keg_file_owners(&self, linked_path: &str) -> Result<Vec<KegFileRecord>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT name, version, linked_path, target_path
+                 FROM keg_files
+                 WHERE linked_path = ?1
+                 ORDER BY name",
+            )
+            .map_err(Error::store("failed to prepare statement"))?;
+
+        let records = stmt
+            .query_map(params![linked_path], |row| {
+                Ok(KegFileRecord {
+                    name: row.get(0)?,
+                    version: row.get(1)?,
+                    linked_path: row.get(2)?,
+                    target_path: row.get(3)?,
+                })
+            })
+            .map_err(Error::store("failed to query keg files"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::store("failed to collect results"))?;
+
+        Ok(records)
+    }
+
     pub fn list_keg_files(&self) -> Result<Vec<KegFileRecord>, Error> {
         let mut stmt = self
             .conn
@@ -346,6 +1282,34 @@ impl<'a> InstallTransaction<'a> {
             )
             .map_err(Error::store("failed to record install"))?;
 
+        self.tx
+            .execute(
+                "INSERT INTO install_history (name, version, installed_at)
+                 VALUES (?1, ?2, ?3)
+                 ON CONFLICT(name, version) DO UPDATE SET
+                     installed_at = excluded.installed_at",
+                params![name, version, now],
+            )
+            .map_err(Error::store("failed to record install history"))?;
+
+        // A reinstall may change the keg contents; recompute lazily.
+        self.tx
+            .execute("DELETE FROM keg_sizes WHERE name = ?1", params![name])
+            .map_err(Error::store("failed to invalidate keg size"))?;
+
+        self.tx
+            .execute("DELETE FROM keg_manifests WHERE name = ?1", params![name])
+            .map_err(Error::store("failed to invalidate keg manifest"))?;
+
+        // Dependencies belong to the version installed; callers that know
+        // them record them again.
+        self.tx
+            .execute(
+                "DELETE FROM keg_dependencies WHERE name = ?1",
+                params![name],
+            )
+            .map_err(Error::store("failed to invalidate keg dependencies"))?;
+
         match previous_store_key.as_deref() {
             Some(previous) if previous == store_key => {}
             other => {
@@ -371,6 +1335,17 @@ impl<'a> InstallTransaction<'a> {
         Ok(())
     }
 
+    pub fn record_dependencies(&self, name: &str, dependencies: &[String]) -> Result<(), Error> {
+        self.tx
+            .execute(
+                "INSERT OR REPLACE INTO keg_dependencies (name, dependencies) VALUES (?1, ?2)",
+                params![name, dependencies.join("\n")],
+            )
+            .map_err(Error::store("failed to record dependencies"))?;
+
+        Ok(())
+    }
+
     pub fn record_linked_file(
         &self,
         name: &str,
@@ -389,6 +1364,17 @@ impl<'a> InstallTransaction<'a> {
         Ok(())
     }
 
+    pub fn record_cask_artifact(&self, name: &str, artifact: &CaskArtifact) -> Result<(), Error> {
+        self.tx
+            .execute(
+                "INSERT OR IGNORE INTO cask_artifacts (name, kind, value) VALUES (?1, ?2, ?3)",
+                params![name, artifact.kind(), artifact.value()],
+            )
+            .map_err(Error::store("failed to record cask artifact"))?;
+
+        Ok(())
+    }
+
     pub fn record_uninstall(&self, name: &str) -> Result<Option<String>, Error> {
         // Get the store_key before removing
         let store_key: Option<String> = self
@@ -409,6 +1395,36 @@ impl<'a> InstallTransaction<'a> {
             .execute("DELETE FROM keg_files WHERE name = ?1", params![name])
             .map_err(Error::store("failed to remove keg files records"))?;
 
+        self.tx
+            .execute("DELETE FROM keg_sizes WHERE name = ?1", params![name])
+            .map_err(Error::store("failed to remove keg size records"))?;
+
+        self.tx
+            .execute("DELETE FROM keg_manifests WHERE name = ?1", params![name])
+            .map_err(Error::store("failed to remove keg manifest records"))?;
+
+        self.tx
+            .execute(
+                "DELETE FROM build_dependency_kegs WHERE name = ?1",
+                params![name],
+            )
+            .map_err(Error::store("failed to remove build dependency mark"))?;
+
+        self.tx
+            .execute("DELETE FROM ephemeral_kegs WHERE name = ?1", params![name])
+            .map_err(Error::store("failed to remove ephemeral mark"))?;
+
+        self.tx
+            .execute("DELETE FROM dependency_kegs WHERE name = ?1", params![name])
+            .map_err(Error::store("failed to remove dependency mark"))?;
+
+        self.tx
+            .execute(
+                "DELETE FROM keg_dependencies WHERE name = ?1",
+                params![name],
+            )
+            .map_err(Error::store("failed to remove keg dependencies"))?;
+
         // Decrement store ref if we had one
         if let Some(ref key) = store_key {
             self.tx
@@ -438,6 +1454,14 @@ impl<'a> InstallTransaction<'a> {
         Ok(())
     }
 
+    pub fn clear_install_history(&self, name: &str) -> Result<(), Error> {
+        self.tx
+            .execute("DELETE FROM install_history WHERE name = ?1", params![name])
+            .map_err(Error::store("failed to clear install history"))?;
+
+        Ok(())
+    }
+
     pub fn commit(self) -> Result<(), Error> {
         self.tx
             .commit()
@@ -468,6 +1492,52 @@ mod tests {
         assert_eq!(installed[0].store_key, "abc123");
     }
 
+    #[test]
+    fn store_manifest_goes_with_its_store_ref() {
+        let db = Database::in_memory().unwrap();
+        let entries = vec![ManifestEntry {
+            path: "bin/foo".to_string(),
+            sha256: "ab".repeat(32),
+        }];
+
+        db.record_store_manifest("abc123", &entries).unwrap();
+        assert_eq!(db.store_manifest("abc123").unwrap(), entries);
+        assert!(db.store_manifest("other").unwrap().is_empty());
+
+        db.delete_store_ref("abc123").unwrap();
+        assert!(db.store_manifest("abc123").unwrap().is_empty());
+    }
+
+    #[test]
+    fn generations_track_the_current_one() {
+        let db = Database::in_memory().unwrap();
+        let keg = GenerationKeg {
+            name: "foo".to_string(),
+            version: "1.0.0".to_string(),
+            store_key: "abc123".to_string(),
+            linked: true,
+        };
+
+        let first = db.record_generation("install", &[keg.clone()]).unwrap();
+        let second = db.record_generation("uninstall", &[]).unwrap();
+        let generations = db.list_generations().unwrap();
+        assert_eq!(generations.len(), 2);
+        assert_eq!((generations[0].kegs, generations[0].current), (1, false));
+        assert_eq!((generations[1].kegs, generations[1].current), (0, true));
+        assert_eq!(db.generation_kegs(first).unwrap(), vec![keg]);
+        assert!(db.generation_kegs(second).unwrap().is_empty());
+
+        db.set_current_generation(first).unwrap();
+        let current: Vec<i64> = db
+            .list_generations()
+            .unwrap()
+            .into_iter()
+            .filter(|g| g.current)
+            .map(|g| g.id)
+            .collect();
+        assert_eq!(current, vec![first]);
+    }
+
     #[test]
     fn rollback_leaves_no_partial_state() {
         let mut db = Database::in_memory().unwrap();
@@ -653,10 +1723,226 @@ mod tests {
     }
 
     #[test]
-    fn new_database_starts_at_version_1() {
+    fn installs_of_different_versions_are_kept_in_history() {
+        let mut db = Database::in_memory().unwrap();
+
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_install("foo", "1.0.0", "oldkey").unwrap();
+            tx.commit().unwrap();
+        }
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_install("foo", "1.1.0", "newkey").unwrap();
+            tx.commit().unwrap();
+        }
+
+        let history = db.install_history("foo").unwrap();
+        let versions: Vec<_> = history.iter().map(|e| e.version.as_str()).collect();
+        assert_eq!(versions, vec!["1.0.0", "1.1.0"]);
+        assert!(history.iter().all(|e| e.installed_at > 0));
+    }
+
+    #[test]
+    fn reinstall_of_same_version_does_not_duplicate_history() {
+        let mut db = Database::in_memory().unwrap();
+
+        for _ in 0..2 {
+            let tx = db.transaction().unwrap();
+            tx.record_install("foo", "1.0.0", "samekey").unwrap();
+            tx.commit().unwrap();
+        }
+
+        assert_eq!(db.install_history("foo").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn pin_and_unpin_round_trip() {
+        let db = Database::in_memory().unwrap();
+
+        db.pin("foo", "1.0.0").unwrap();
+        assert!(db.is_pinned("foo").unwrap());
+        assert!(!db.is_pinned("bar").unwrap());
+        assert_eq!(db.list_pinned().unwrap(), vec!["foo"]);
+
+        assert!(db.unpin("foo").unwrap());
+        assert!(!db.unpin("foo").unwrap());
+        assert!(!db.is_pinned("foo").unwrap());
+    }
+
+    #[test]
+    fn build_dependency_marks_are_dropped_on_uninstall() {
+        let mut db = Database::in_memory().unwrap();
+
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_install("pkgconf", "2.3.0", "key").unwrap();
+            tx.commit().unwrap();
+        }
+        db.mark_build_dependency("pkgconf").unwrap();
+        db.mark_build_dependency("pkgconf").unwrap();
+        assert_eq!(db.list_build_dependencies().unwrap(), vec!["pkgconf"]);
+
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_uninstall("pkgconf").unwrap();
+            tx.commit().unwrap();
+        }
+        assert!(db.list_build_dependencies().unwrap().is_empty());
+        assert!(!db.clear_build_dependency("pkgconf").unwrap());
+    }
+
+    #[test]
+    fn service_records_survive_reinstall_until_removed() {
+        let mut db = Database::in_memory().unwrap();
+        db.record_service(
+            "redis",
+            "zerobrew.redis",
+            "/home/u/.config/systemd/user/zerobrew.redis.service",
+        )
+        .unwrap();
+
+        // Upgrades drop and re-record the install; the service stays.
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_install("redis", "7.4.0", "key").unwrap();
+            tx.record_uninstall("redis").unwrap();
+            tx.commit().unwrap();
+        }
+        let record = db.get_service("redis").unwrap();
+        assert_eq!(record.label, "zerobrew.redis");
+
+        assert!(db.remove_service("redis").unwrap());
+        assert!(db.get_service("redis").is_none());
+        assert!(!db.remove_service("redis").unwrap());
+    }
+
+    #[test]
+    fn keg_dependencies_follow_the_installed_version() {
+        let mut db = Database::in_memory().unwrap();
+
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_install("wget", "1.24.5", "key").unwrap();
+            tx.record_dependencies("wget", &["openssl@3".to_string(), "libidn2".to_string()])
+                .unwrap();
+            tx.record_install("tree", "2.2.1", "tree-key").unwrap();
+            tx.record_dependencies("tree", &[]).unwrap();
+            tx.commit().unwrap();
+        }
+        let recorded = db.list_keg_dependencies().unwrap();
+        assert_eq!(recorded["wget"], vec!["openssl@3", "libidn2"]);
+        assert!(recorded["tree"].is_empty());
+
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_install("wget", "1.25.0", "new-key").unwrap();
+            tx.record_uninstall("tree").unwrap();
+            tx.commit().unwrap();
+        }
+        assert!(db.list_keg_dependencies().unwrap().is_empty());
+    }
+
+    #[test]
+    fn ephemeral_marks_are_dropped_on_uninstall() {
+        let mut db = Database::in_memory().unwrap();
+
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_install("ripgrep", "14.1.0", "key").unwrap();
+            tx.commit().unwrap();
+        }
+        db.mark_ephemeral("ripgrep").unwrap();
+        assert_eq!(db.list_ephemeral().unwrap(), vec!["ripgrep"]);
+
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_uninstall("ripgrep").unwrap();
+            tx.commit().unwrap();
+        }
+        assert!(db.list_ephemeral().unwrap().is_empty());
+        assert!(!db.clear_ephemeral("ripgrep").unwrap());
+    }
+
+    #[test]
+    fn dependency_marks_are_dropped_on_uninstall() {
+        let mut db = Database::in_memory().unwrap();
+
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_install("openssl@3", "3.3.0", "key").unwrap();
+            tx.commit().unwrap();
+        }
+        db.mark_dependency("openssl@3").unwrap();
+        assert!(db.is_dependency("openssl@3").unwrap());
+        assert_eq!(db.list_dependencies().unwrap(), vec!["openssl@3"]);
+
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_uninstall("openssl@3").unwrap();
+            tx.commit().unwrap();
+        }
+        assert!(!db.is_dependency("openssl@3").unwrap());
+        assert!(!db.clear_dependency("openssl@3").unwrap());
+    }
+
+    #[test]
+    fn cask_artifacts_round_trip_until_cleared() {
+        let mut db = Database::in_memory().unwrap();
+
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_install("cask:docker", "4.30.0", "key").unwrap();
+            tx.record_cask_artifact(
+                "cask:docker",
+                &CaskArtifact::App("/Applications/Docker.app".to_string()),
+            )
+            .unwrap();
+            tx.record_cask_artifact(
+                "cask:docker",
+                &CaskArtifact::PkgReceipt("com.docker.helper".to_string()),
+            )
+            .unwrap();
+            tx.commit().unwrap();
+        }
+
+        assert_eq!(
+            db.list_cask_artifacts("cask:docker").unwrap(),
+            vec![
+                CaskArtifact::App("/Applications/Docker.app".to_string()),
+                CaskArtifact::PkgReceipt("com.docker.helper".to_string()),
+            ]
+        );
+
+        db.clear_cask_artifacts("cask:docker").unwrap();
+        assert!(db.list_cask_artifacts("cask:docker").unwrap().is_empty());
+    }
+
+    #[test]
+    fn keg_size_cache_is_invalidated_by_reinstall() {
+        let mut db = Database::in_memory().unwrap();
+
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_install("foo", "1.0.0", "key").unwrap();
+            tx.commit().unwrap();
+        }
+        db.record_keg_size("foo", "1.0.0", 4096).unwrap();
+        assert_eq!(db.cached_keg_size("foo", "1.0.0"), Some(4096));
+
+        {
+            let tx = db.transaction().unwrap();
+            tx.record_install("foo", "1.0.0", "key").unwrap();
+            tx.commit().unwrap();
+        }
+        assert_eq!(db.cached_keg_size("foo", "1.0.0"), None);
+    }
+
+    #[test]
+    fn new_database_starts_at_latest_version() {
         let db = Database::in_memory().expect("failed to create database");
         let version = Database::get_schema_version(&db.conn).expect("failed to get version");
-        assert_eq!(version, 1);
+        assert_eq!(version, Database::SCHEMA_VERSION);
     }
 
     #[test]
@@ -665,7 +1951,7 @@ mod tests {
         Database::migrate(&db.conn).expect("first migration failed");
         Database::migrate(&db.conn).expect("second migration failed");
         let version = Database::get_schema_version(&db.conn).expect("failed to get version");
-        assert_eq!(version, 1);
+        assert_eq!(version, Database::SCHEMA_VERSION);
     }
 
     #[test]
@@ -704,4 +1990,27 @@ mod tests {
             .expect("failed to query data");
         assert_eq!(name, "test");
     }
+
+    #[test]
+    fn v2_migration_seeds_history_from_installed_kegs() {
+        let conn = Connection::open_in_memory().expect("failed to open connection");
+        Database::migrate_to_v1(&conn).expect("v1 migration failed");
+        Database::set_schema_version(&conn, 1).expect("failed to set version");
+        conn.execute(
+            "INSERT INTO installed_kegs VALUES ('test', '1.0.0', 'key123', 1234567890)",
+            [],
+        )
+        .expect("failed to insert keg");
+
+        Database::migrate(&conn).expect("migration failed");
+
+        let installed_at: i64 = conn
+            .query_row(
+                "SELECT installed_at FROM install_history WHERE name = 'test' AND version = '1.0.0'",
+                [],
+                |row| row.get(0),
+            )
+            .expect("failed to query history");
+        assert_eq!(installed_at, 1234567890);
+    }
 }
@@ -3,5 +3,8 @@ pub mod db;
 pub mod store;
 
 pub use blob::{BlobCache, BlobWriter};
-pub use db::{Database, InstallTransaction, InstalledKeg, KegFileRecord, StoreRef};
+pub use db::{
+    CaskArtifact, Database, Generation, GenerationKeg, InstallHistoryEntry, InstallTransaction,
+    InstalledKeg, KegFileRecord, ServiceRecord, StoreRef,
+};
 pub use store::Store;